@@ -0,0 +1,53 @@
+//! Flat A* against the path graph over a cave a few chunks across. Run
+//! with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use mc::entity::{Footprint, Movement};
+use mc::game_map::{GameMap, TileKind};
+use mc::path_graph::PathGraph;
+use mc::pathfinding;
+
+/// Digs out a walled in cave at (x, y, z) with a pillar every fourth tile
+/// each way, so searches have something to go around
+fn pillared_cave(map: &mut GameMap, (x, y, z): (u32, u32, u32), width: u32, height: u32) {
+    for ty in y..y + height {
+        for tx in x..x + width {
+            let mut tile = map.get_tile(tx, ty, z);
+            let border = tx == x || ty == y || tx == x + width - 1 || ty == y + height - 1;
+            let pillar = (tx - x) % 4 == 2 && (ty - y) % 4 == 2;
+            if border || pillar {
+                tile.kind = TileKind::Wall;
+            } else {
+                tile.dig();
+            }
+            map.set_tile(tx, ty, z, tile);
+        }
+    }
+}
+
+fn bench_paths(c: &mut Criterion) {
+    let mut map = GameMap::with_seed(10);
+    let (x, y, z) = (1024, 1024, 40);
+    pillared_cave(&mut map, (x, y, z), 192, 128);
+    map.take_reshaped();
+    let mut graph = PathGraph::new();
+
+    for (name, goal) in [("one chunk", (x + 60, y + 40)), ("three chunks", (x + 189, y + 125))] {
+        let start = (x + 1, y + 1);
+        let mut group = c.benchmark_group(name);
+        group.bench_function("flat A*", |b| b.iter(|| {
+            pathfinding::find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false)
+        }));
+        group.bench_function("path graph", |b| b.iter(|| {
+            graph.find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false)
+        }));
+        group.bench_function("path graph, nothing cached", |b| b.iter(|| {
+            PathGraph::new().find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false)
+        }));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_paths);
+criterion_main!(benches);
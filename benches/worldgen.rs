@@ -0,0 +1,71 @@
+//! Generating a chunk of fresh rock near the surface and deep under the
+//! water table, on one thread and on every core. The rows of a chunk are
+//! sampled side by side, so the second goes as many times faster as there
+//! are cores to spare. Then the rock's noise for a chunk on its own,
+//! noise::Billow tile by tile as generation used to sample it against
+//! RowBillow a row at a time. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use noise::{Billow, MultiFractal, NoiseFn, ScalePoint, Seedable};
+
+use mc::billow::RowBillow;
+use mc::game_map::GameMap;
+use mc::worldgen::WorldGenConfig;
+
+fn bench_chunks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate a chunk");
+    group.sample_size(10);
+    let mut thread_counts = vec![1, rayon::current_num_threads()];
+    thread_counts.dedup();
+    for threads in thread_counts {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let on = if threads == 1 { String::from("one thread") } else { format!("{} threads", threads) };
+        for (name, z) in [("near the surface", 10), ("flooded", 100)] {
+            group.bench_function(format!("{} on {}", name, on), |b| b.iter_batched(
+                || GameMap::with_seed(10),
+                |mut map| pool.install(|| map.get_tile(1024, 1024, z)),
+                BatchSize::LargeInput));
+        }
+    }
+    group.finish();
+}
+
+fn bench_noise(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sample a chunk's noise");
+    group.sample_size(10);
+    let worldgen = black_box(WorldGenConfig::default());
+    let (seed, size) = (10, 64);
+    let (x_min, y_min, z_min) = (1024, 1024, 0);
+
+    let billow = ScalePoint::new(Billow::new()
+        .set_seed(seed)
+        .set_frequency(worldgen.frequency)
+        .set_persistence(worldgen.persistence)
+        ).set_scale(worldgen.scale);
+    group.bench_function("Billow tile by tile", |b| b.iter(|| {
+        let mut samples = Vec::with_capacity((size * size * size) as usize);
+        for z in z_min..z_min + size {
+            for y in y_min..y_min + size {
+                for x in x_min..x_min + size {
+                    samples.push(billow.get([x as f64, y as f64, z as f64]));
+                }
+            }
+        }
+        samples
+    }));
+
+    let rows = RowBillow::new(seed, worldgen.frequency, worldgen.persistence, worldgen.scale);
+    group.bench_function("RowBillow a row at a time", |b| b.iter(|| {
+        let columns = rows.columns((x_min..x_min + size).map(|x| x as f64));
+        let mut samples = vec![0.0; (size * size * size) as usize];
+        for (row, out) in samples.chunks_mut(size as usize).enumerate() {
+            let (z, y) = (z_min + row as u32 / size, y_min + row as u32 % size);
+            rows.row(&columns, y as f64, z as f64, out);
+        }
+        samples
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_chunks, bench_noise);
+criterion_main!(benches);
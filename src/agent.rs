@@ -0,0 +1,311 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::EntityId;
+use crate::events::GameEvent;
+use crate::net::{Order, HOST_PEER};
+use crate::simulation::Simulation;
+
+/// How many tiles around its colonist an agent sees by default
+pub const SIGHT_RADIUS: u32 = 8;
+
+/// What an agent is shown at the start of a tick
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub tick: u64,
+    pub colonist: EntityId,
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub hp: i32,
+    pub max_hp: i32,
+    /// The glyphs of the tiles around the colonist, a row for each from
+    /// north to south with the colonist in the middle one. Past the north
+    /// and south edges of the world is a space.
+    pub surroundings: Vec<String>,
+    /// Everything else on the level within sight
+    pub nearby: Vec<Seen>,
+    /// What happened last tick, anywhere in the world
+    pub events: Vec<String>,
+    /// Why the orders given last tick failed, if any did
+    pub errors: Vec<String>,
+    pub lost: bool,
+}
+
+/// Something an agent's colonist can see
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Seen {
+    pub id: EntityId,
+    pub glyph: char,
+    pub x: u32,
+    pub y: u32,
+    pub hp: i32,
+    pub hostile: bool,
+}
+
+/// A program steering a colonist instead of a player, for stress tests
+/// and AI experiments against the simulation. It's shown what its colonist
+/// sees each tick and gives orders back, the same orders a player's input
+/// turns into.
+pub trait Agent {
+
+    /// Takes in what the colonist sees
+    fn observe(&mut self, observation: &Observation);
+
+    /// The orders to carry out this tick
+    fn act(&mut self) -> Vec<Order>;
+
+    /// Whether the agent is done, a run ends once it is
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// Runs a simulation with an agent steering one of its colonists
+pub struct Harness {
+    pub colonist: EntityId,
+    pub radius: u32,
+    /// What happened last tick, for the next observation
+    events: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl Harness {
+
+    pub fn new(colonist: EntityId) -> Harness {
+        Harness { colonist, radius: SIGHT_RADIUS, events: Vec::new(), errors: Vec::new() }
+    }
+
+    pub fn with_radius(mut self, radius: u32) -> Harness {
+        self.radius = radius;
+        self
+    }
+
+    /// What the colonist sees, None once they're gone
+    pub fn observe(&self, sim: &mut Simulation) -> Option<Observation> {
+        let colonist = sim.entities.get(self.colonist)?.clone();
+        let (x, y, z) = (colonist.pos.x as u32, colonist.pos.y as u32, colonist.depth);
+        let (_, max_y, _) = sim.map.size();
+        let radius = self.radius as i64;
+        let surroundings = (-radius..=radius).map(|dy| {
+            (-radius..=radius).map(|dx| {
+                let ty = y as i64 + dy;
+                if ty < 0 || ty >= max_y as i64 {
+                    return ' ';
+                }
+                let tx = sim.map.wrap_x(x as i64 + dx);
+                sim.map.get_tile(tx, ty as u32, z).glyph
+            }).collect()
+        }).collect();
+        let nearby = sim.entities.iter()
+            .filter(|&(id, entity)| id != self.colonist && entity.depth == z && entity.is_alive())
+            .filter(|(_, entity)| {
+                let dx = sim.map.column_from(entity.pos.x as u32, x).min(sim.map.column_from(x, entity.pos.x as u32));
+                let dy = (entity.pos.y as i64 - y as i64).unsigned_abs();
+                dx <= self.radius && dy <= self.radius as u64
+            })
+            .map(|(id, entity)| Seen {
+                id,
+                glyph: entity.glyph,
+                x: entity.pos.x as u32,
+                y: entity.pos.y as u32,
+                hp: entity.hp,
+                hostile: entity.hostile,
+            })
+            .collect();
+        Some(Observation {
+            tick: sim.tick,
+            colonist: self.colonist,
+            x,
+            y,
+            z,
+            hp: colonist.hp,
+            max_hp: colonist.max_hp,
+            surroundings,
+            nearby,
+            events: self.events.clone(),
+            errors: self.errors.clone(),
+            lost: sim.lost.is_some(),
+        })
+    }
+
+    /// Shows the agent what its colonist sees, carries out its orders and
+    /// runs a tick, returning the tick's events. Nothing is asked of the
+    /// agent once the colonist is gone, the tick still runs.
+    pub fn tick(&mut self, sim: &mut Simulation, agent: &mut dyn Agent) -> Vec<GameEvent> {
+        self.errors.clear();
+        if let Some(observation) = self.observe(sim) {
+            agent.observe(&observation);
+            for order in agent.act() {
+                if let Err(err) = sim.apply_order(HOST_PEER, order) {
+                    self.errors.push(err);
+                }
+            }
+        }
+        sim.step();
+        let events = sim.events.drain();
+        self.events = events.iter().map(|event| format!("{:?}", event)).collect();
+        events
+    }
+}
+
+/// Ticks a Tunneler goes without getting anywhere before it turns, digging
+/// a tile and stepping into it takes two
+const STUCK_TICKS: u32 = 4;
+
+/// Steps the colonist the same way every tick, digging through rock in the
+/// way and turning a quarter clockwise when something else blocks it, for
+/// stress testing digging and the fluids it lets loose
+pub struct Tunneler {
+    pub dx: i32,
+    pub dy: i32,
+    colonist: Option<EntityId>,
+    /// Where the colonist was last seen and how many ticks they've been there
+    at: (u32, u32),
+    stuck: u32,
+}
+
+impl Tunneler {
+
+    pub fn new(dx: i32, dy: i32) -> Tunneler {
+        Tunneler { dx, dy, colonist: None, at: (0, 0), stuck: 0 }
+    }
+}
+
+impl Agent for Tunneler {
+
+    fn observe(&mut self, observation: &Observation) {
+        self.colonist = Some(observation.colonist);
+        if (observation.x, observation.y) == self.at {
+            self.stuck += 1;
+        } else {
+            self.at = (observation.x, observation.y);
+            self.stuck = 0;
+        }
+        if self.stuck >= STUCK_TICKS {
+            (self.dx, self.dy) = (-self.dy, self.dx);
+            self.stuck = 0;
+        }
+    }
+
+    fn act(&mut self) -> Vec<Order> {
+        self.colonist.iter()
+            .map(|&colonist| Order::Step { colonist, dx: self.dx, dy: self.dy })
+            .collect()
+    }
+}
+
+/// An agent run as another program, talking in lines of json over its
+/// standard input and output: it's sent an Observation each tick and
+/// answers with an array of orders, like `[]` or
+/// `[{"Step":{"colonist":{"index":3,"generation":0},"dx":1,"dy":0}}]`.
+/// It's finished once it exits or stops answering with orders.
+pub struct ProcessAgent {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    finished: bool,
+}
+
+impl ProcessAgent {
+
+    /// Starts the program with the arguments
+    pub fn spawn(program: &str, args: &[String]) -> io::Result<ProcessAgent> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().ok_or_else(|| io::Error::other("no input to the agent"))?;
+        let output = child.stdout.take().ok_or_else(|| io::Error::other("no output from the agent"))?;
+        Ok(ProcessAgent { child, input, output: BufReader::new(output), finished: false })
+    }
+
+    fn send(&mut self, observation: &Observation) -> io::Result<()> {
+        let line = serde_json::to_string(observation)?;
+        writeln!(self.input, "{}", line)?;
+        self.input.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<Order>> {
+        let mut line = String::new();
+        if self.output.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the agent exited"));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Agent for ProcessAgent {
+
+    fn observe(&mut self, observation: &Observation) {
+        if self.finished {
+            return;
+        }
+        if let Err(err) = self.send(observation) {
+            log::warn!("could not send the agent what it sees: {}", err);
+            self.finished = true;
+        }
+    }
+
+    fn act(&mut self) -> Vec<Order> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.receive().unwrap_or_else(|err| {
+            log::warn!("agent stopped: {}", err);
+            self.finished = true;
+            Vec::new()
+        })
+    }
+
+    fn finished(&self) -> bool {
+        self.finished
+    }
+}
+
+impl Drop for ProcessAgent {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::GameMap;
+    use crate::simulation::start_position;
+
+    #[test]
+    fn test_agent_steers_its_colonist() {
+        let mut sim = Simulation::new(GameMap::with_seed(10));
+        let (x, y, z) = start_position(&sim.map);
+        let player = sim.populate(x, y, z);
+        // the start is under water, the player's moved up out of it
+        sim.entities.get_mut(player).unwrap().depth = 10;
+        let mut harness = Harness::new(player).with_radius(2);
+        let observation = harness.observe(&mut sim).unwrap();
+        println!("{:#?}", observation);
+        let player_at = sim.entities.get(player).map(|player| (player.pos.x as u32, player.pos.y as u32));
+        assert_eq!(Some((observation.x, observation.y)), player_at);
+        assert_eq!(observation.z, 10);
+        assert_eq!(observation.surroundings.len(), 5);
+        assert!(observation.surroundings.iter().all(|row| row.chars().count() == 5));
+
+        // stepping east a tick at a time digs through whatever's there
+        let mut tunneler = Tunneler::new(1, 0);
+        for _ in 0..200 {
+            harness.tick(&mut sim, &mut tunneler);
+        }
+        let moved = harness.observe(&mut sim).unwrap();
+        assert!(moved.x > observation.x, "stayed at {}", moved.x);
+        assert!(!tunneler.finished());
+
+        // the observation goes over the wire and back the same
+        let line = serde_json::to_string(&moved).unwrap();
+        assert_eq!(serde_json::from_str::<Observation>(&line).unwrap(), moved);
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color_scheme::ColorName;
+
+/// A glyph and color, shown for duration_ms before the next frame
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub glyph: char,
+    pub color: ColorName,
+    pub duration_ms: u32,
+}
+
+impl Frame {
+
+    pub fn new(glyph: char, color: ColorName, duration_ms: u32) -> Frame {
+        Frame { glyph, color, duration_ms }
+    }
+}
+
+/// Frames something cycles through while it's drawn, looping forever.
+/// Only changes how it looks, never what it is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Renderable {
+    pub frames: Vec<Frame>,
+}
+
+impl Renderable {
+
+    pub fn new(frames: Vec<Frame>) -> Renderable {
+        Renderable { frames }
+    }
+
+    /// A single frame that never changes, a look picked for something
+    /// rather than the one it's made with
+    pub fn still(glyph: char, color: ColorName) -> Renderable {
+        Renderable::new(vec![Frame::new(glyph, color, 0)])
+    }
+
+    /// A spinning drill head
+    pub fn drill() -> Renderable {
+        Renderable::new(vec![
+            Frame::new('8', ColorName::LightYellow, 150),
+            Frame::new('0', ColorName::Yellow, 150),
+        ])
+    }
+
+    /// Water shimmering between its color and a lighter one
+    pub fn water(color: ColorName) -> Renderable {
+        let highlight = match color {
+            ColorName::Blue => ColorName::LightBlue,
+            _ => ColorName::LightAqua,
+        };
+        Renderable::new(vec![
+            Frame::new('≈', color, 900),
+            Frame::new('≈', highlight, 300),
+        ])
+    }
+
+    /// How long one loop through every frame takes
+    pub fn duration_ms(&self) -> u64 {
+        self.frames.iter().map(|frame| frame.duration_ms as u64).sum()
+    }
+
+    /// The frame showing elapsed_ms into the animation
+    pub fn frame(&self, elapsed_ms: u64) -> Option<&Frame> {
+        let duration_ms = self.duration_ms();
+        if duration_ms == 0 {
+            return self.frames.first();
+        }
+        let mut time_ms = elapsed_ms % duration_ms;
+        for frame in self.frames.iter() {
+            if time_ms < frame.duration_ms as u64 {
+                return Some(frame);
+            }
+            time_ms -= frame.duration_ms as u64;
+        }
+        None
+    }
+}
+
+/// The time every animation plays from, so things animated alike stay in
+/// step. Only drawing reads it, the simulation runs off ticks.
+pub struct AnimationClock {
+    start: Instant,
+}
+
+impl AnimationClock {
+
+    pub fn new() -> AnimationClock {
+        AnimationClock {
+            start: Instant::now(),
+        }
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+}
+
+/// Offset into an animation for a tile, so neighboring tiles animated alike
+/// don't all change together
+pub fn phase_ms(x: u32, y: u32) -> u64 {
+    (x as u64 * 7 + y as u64 * 13) % 16 * 75
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_loop() {
+        let water = Renderable::water(ColorName::Blue);
+        let colors: Vec<_> = [0, 899, 900, 1199, 1200, 2150].iter()
+            .map(|ms| water.frame(*ms).unwrap().color)
+            .collect();
+        println!("{:?}", colors);
+        assert_eq!(colors, vec![
+            ColorName::Blue, ColorName::Blue, ColorName::LightBlue,
+            ColorName::LightBlue, ColorName::Blue, ColorName::LightBlue,
+        ]);
+        assert!(Renderable::new(Vec::new()).frame(10).is_none());
+    }
+}
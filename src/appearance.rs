@@ -0,0 +1,175 @@
+use quicksilver::prelude::*;
+
+use crate::assets::Glyphs;
+use crate::color_scheme::{ColorName, ColorScheme};
+use crate::layer::RenderLayer;
+use crate::locale::Locale;
+
+/// Colors the player can be drawn in, bright enough to stand out on rock
+pub const COLORS: [ColorName; 14] = [
+    ColorName::LightPurple, ColorName::Purple,
+    ColorName::LightRed, ColorName::Red,
+    ColorName::LightOrange, ColorName::Orange,
+    ColorName::LightYellow, ColorName::Yellow,
+    ColorName::LightGreen, ColorName::Green,
+    ColorName::LightAqua, ColorName::Aqua,
+    ColorName::LightBlue, ColorName::Fg,
+];
+/// How many glyphs are shown either side of the one picked
+const GLYPHS_AROUND: usize = 6;
+/// Size of a glyph in the row to pick from, before the UI scale
+const CELL_PX: f32 = 32.0;
+
+/// What the player did on the appearance picker
+#[derive(Debug, PartialEq)]
+pub enum AppearanceChoice {
+    Pick(char, ColorName),
+    Cancel,
+}
+
+/// Picks the glyph and color the player is drawn with, out of the glyphs
+/// the tileset has
+pub struct AppearancePicker {
+    glyphs: Vec<char>,
+    glyph: usize,
+    color: usize,
+}
+
+impl AppearancePicker {
+
+    /// Starts on the current look, or the first glyph and color for
+    /// whatever there's none of to pick
+    pub fn new(glyphs: Vec<char>, (glyph, color): (char, ColorName)) -> AppearancePicker {
+        let glyphs: Vec<char> = glyphs.into_iter().filter(|glyph| !glyph.is_whitespace()).collect();
+        AppearancePicker {
+            glyph: glyphs.iter().position(|&listed| listed == glyph).unwrap_or(0),
+            color: COLORS.iter().position(|&listed| listed == color).unwrap_or(0),
+            glyphs,
+        }
+    }
+
+    pub fn glyph(&self) -> char {
+        self.glyphs.get(self.glyph).copied().unwrap_or('@')
+    }
+
+    pub fn color(&self) -> ColorName {
+        COLORS[self.color]
+    }
+
+    /// Moves along the glyphs and colors, wrapping around at the ends
+    fn step(&mut self, glyphs: isize, colors: isize) {
+        let wrap = |index: usize, step: isize, len: usize| {
+            (index as isize + step).rem_euclid(len.max(1) as isize) as usize
+        };
+        self.glyph = wrap(self.glyph, glyphs, self.glyphs.len());
+        self.color = wrap(self.color, colors, COLORS.len());
+    }
+
+    /// Left and Right change the glyph, Up and Down the color, Return
+    /// picks them
+    pub fn handle_input(&mut self, window: &Window, escape: bool) -> Option<AppearanceChoice> {
+        let pressed = |key| window.keyboard()[key] == ButtonState::Pressed;
+        if escape {
+            return Some(AppearanceChoice::Cancel);
+        }
+        let glyphs = pressed(Key::Right) as isize - pressed(Key::Left) as isize;
+        let colors = pressed(Key::Down) as isize - pressed(Key::Up) as isize;
+        self.step(glyphs, colors);
+        if pressed(Key::Return) {
+            return Some(AppearanceChoice::Pick(self.glyph(), self.color()));
+        }
+        None
+    }
+
+    /// Draws the glyphs around the picked one in its color, and the colors
+    /// under them, centered on the screen
+    pub fn draw(&self, window: &mut Window, font: &mut Font, glyphs: &Glyphs,
+                color_scheme: &ColorScheme, locale: &Locale, scale: f32) -> Result<()> {
+        let cell = CELL_PX * scale;
+        let line_height = 28.0 * scale;
+        let padding = 20.0 * scale;
+
+        let title = font.render(locale.get("appearance.title"), &FontStyle::new(
+            24.0 * scale, Color::from_hex(&color_scheme.light_yellow)))?;
+        let hint = font.render(locale.get("appearance.hint"), &FontStyle::new(
+            18.0 * scale, Color::from_hex(&color_scheme.fg3)))?;
+
+        let columns = GLYPHS_AROUND * 2 + 1;
+        let size = Vector::new(
+            (columns as f32 * cell).max(hint.area().width()),
+            line_height * 2.0 + cell * 2.0) + Vector::new(padding, padding) * 2.0;
+        let area = Rectangle::new_sized(size).with_center(window.screen_size() / 2.0);
+        window.draw_ex(&area, Col(Color::from_hex(&color_scheme.bg)), Transform::IDENTITY, RenderLayer::UiTop.z());
+        window.draw_ex(
+            &title.area().translate(area.top_left() + Vector::new(padding, padding)),
+            Img(&title),
+            Transform::IDENTITY,
+            RenderLayer::UiTop.over(),
+        );
+
+        // the picked glyph in the middle of the row, in the picked color,
+        // the ones around it dimmed
+        let row = area.top_left() + Vector::new((size.x - columns as f32 * cell) / 2.0, padding + line_height);
+        let picked = color_scheme.color(&self.color());
+        let dimmed = Color::from_hex(&color_scheme.gray);
+        let count = self.glyphs.len() as isize;
+        for column in 0..columns {
+            let offset = column as isize - GLYPHS_AROUND as isize;
+            if count == 0 || offset.unsigned_abs() >= self.glyphs.len() {
+                continue;
+            }
+            let glyph = self.glyphs[(self.glyph as isize + offset).rem_euclid(count) as usize];
+            if let Some(image) = glyphs.get(glyph) {
+                let center = row + Vector::new((column as f32 + 0.5) * cell, cell / 2.0);
+                let zoom = cell / image.area().height().max(1.0);
+                window.draw_ex(
+                    &Rectangle::new_sized(image.area().size()).with_center(center),
+                    Blended(image, if offset == 0 { picked } else { dimmed }),
+                    Transform::scale((zoom, zoom)),
+                    RenderLayer::UiTop.over(),
+                );
+            }
+        }
+
+        // a swatch for each color, the picked one twice as tall
+        let swatch = (size.x - padding * 2.0) / COLORS.len() as f32;
+        let swatches = Vector::new(area.x() + padding, row.y + cell * 1.25);
+        for (index, color) in COLORS.iter().enumerate() {
+            let height = if index == self.color { cell * 0.75 } else { cell * 0.375 };
+            window.draw_ex(
+                &Rectangle::new(swatches + Vector::new(index as f32 * swatch, 0.0), (swatch - 2.0 * scale, height)),
+                Col(color_scheme.color(color)),
+                Transform::IDENTITY,
+                RenderLayer::UiTop.over(),
+            );
+        }
+
+        window.draw_ex(
+            &hint.area().translate(Vector::new(area.x() + padding, area.y() + size.y - padding - line_height)),
+            Img(&hint),
+            Transform::IDENTITY,
+            RenderLayer::UiTop.over(),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picker_starts_on_the_current_look_and_wraps() {
+        let mut picker = AppearancePicker::new("#@ g".chars().collect(), ('@', ColorName::LightPurple));
+        assert_eq!((picker.glyph(), picker.color()), ('@', ColorName::LightPurple));
+        picker.step(1, -1);
+        println!("{} {:?}", picker.glyph(), picker.color());
+        // the space isn't a glyph to pick
+        assert_eq!((picker.glyph(), picker.color()), ('g', ColorName::Fg));
+        picker.step(1, 1);
+        assert_eq!((picker.glyph(), picker.color()), ('#', ColorName::LightPurple));
+
+        let unknown = AppearancePicker::new(vec!['#'], ('x', ColorName::Stone3));
+        assert_eq!((unknown.glyph(), unknown.color()), ('#', COLORS[0]));
+    }
+}
@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use quicksilver::prelude::*;
+
+use crate::error::{GameError, GameResult};
+use crate::logging;
+
+/// Sizes the glyphs are rendered at, as multiples of the tile size, so
+/// zoomed in views can scale a larger render down instead of blowing up
+/// a small one
+const RENDER_SCALES: [f32; 3] = [1.0, 2.0, 3.0];
+
+/// Drawn in place of any glyph that hasn't been rendered
+pub const FALLBACK_GLYPH: char = '?';
+
+/// The glyph images tiles and entities are drawn with. Glyphs that are
+/// asked for but were never rendered show up as FALLBACK_GLYPH until the
+/// next update renders them.
+pub struct Assets {
+    /// Fonts and the glyphs rendered from each, glyphs added at runtime
+    /// go on the first font
+    glyph_map: Vec<(String, String)>,
+    tile_size_px: Vector,
+    /// Glyph images at each of RENDER_SCALES, smallest first
+    renders: Vec<(f32, HashMap<char, Image>)>,
+    /// Glyphs drawn since the last update that had no image
+    missing: RefCell<BTreeSet<char>>,
+    /// Glyphs already reported missing, so each is only logged once
+    logged: HashSet<char>,
+}
+
+impl Assets {
+
+    /// Nothing is rendered until render is called
+    pub fn new(glyph_map: &[(&str, &str)], tile_size_px: Vector) -> Assets {
+        let mut assets = Assets {
+            glyph_map: glyph_map.iter()
+                .map(|(font, glyphs)| (String::from(*font), String::from(*glyphs)))
+                .collect(),
+            tile_size_px,
+            renders: Vec::new(),
+            missing: RefCell::new(BTreeSet::new()),
+            logged: HashSet::new(),
+        };
+        assets.require(&FALLBACK_GLYPH.to_string());
+        assets
+    }
+
+    /// Every glyph the tileset has, in the order the fonts list them
+    pub fn glyphs(&self) -> Vec<char> {
+        self.glyph_map.iter().flat_map(|(_, glyphs)| glyphs.chars()).collect()
+    }
+
+    /// Adds the glyphs that aren't in any font's list yet to the first
+    /// font's, returning the ones that were added. They are drawn as
+    /// FALLBACK_GLYPH until rendered.
+    pub fn require(&mut self, glyphs: &str) -> String {
+        let mut added = String::new();
+        for glyph in glyphs.chars() {
+            if !added.contains(glyph)
+               && !self.glyph_map.iter().any(|(_, listed)| listed.contains(glyph)) {
+                added.push(glyph);
+            }
+        }
+        if let Some((_, listed)) = self.glyph_map.first_mut() {
+            listed.push_str(&added);
+        }
+        added
+    }
+
+    /// Renders every required glyph at every scale. If a font can't be
+    /// loaded nothing is rendered and the map is drawn as plain tiles.
+    pub fn render(&mut self) -> GameResult<()> {
+        self.renders.clear();
+        let mut renders = Vec::new();
+        for scale in RENDER_SCALES.iter() {
+            renders.push((*scale, render_glyphs(&self.glyph_map, self.tile_size_px * *scale)?));
+        }
+        self.renders = renders;
+        Ok(())
+    }
+
+    /// Renders everything again for a new tile size
+    pub fn resize(&mut self, tile_size_px: Vector) -> GameResult<()> {
+        self.tile_size_px = tile_size_px;
+        self.render()
+    }
+
+    /// The smallest render at least as large as the zoom, or the largest
+    /// one when zoomed in past all of them
+    pub fn for_zoom(&self, zoom: f32) -> Glyphs<'_> {
+        let render = self.renders.iter()
+            .find(|(scale, _)| *scale >= zoom)
+            .or_else(|| self.renders.last());
+        Glyphs {
+            scale: render.map_or(1.0, |(scale, _)| *scale),
+            images: render.map(|(_, images)| images),
+            missing: &self.missing,
+        }
+    }
+
+    /// Logs the glyphs found missing while drawing and renders just those
+    /// characters from the first font, at every scale
+    pub fn update(&mut self) -> GameResult<()> {
+        let missing = std::mem::take(&mut *self.missing.borrow_mut());
+        let logged = &mut self.logged;
+        let missing: String = missing.into_iter()
+            .filter(|glyph| logged.insert(*glyph))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        log::warn!(target: logging::RENDER, "missing glyphs {:?}, drawing them as {:?} while they render",
+                   missing, FALLBACK_GLYPH);
+        let added = self.require(&missing);
+        if added.is_empty() || self.renders.is_empty() {
+            return Ok(());
+        }
+        let font = self.glyph_map[0].0.clone();
+        for (scale, images) in self.renders.iter_mut() {
+            let tile_size_px = self.tile_size_px * *scale;
+            images.extend(render_glyphs(&[(font.clone(), added.clone())], tile_size_px)?);
+        }
+        Ok(())
+    }
+}
+
+/// The glyph images at one render scale
+pub struct Glyphs<'a> {
+    /// How many times the tile size the images were rendered at
+    pub scale: f32,
+    images: Option<&'a HashMap<char, Image>>,
+    missing: &'a RefCell<BTreeSet<char>>,
+}
+
+impl<'a> Glyphs<'a> {
+
+    /// The glyph's image, or FALLBACK_GLYPH's if it hasn't been rendered.
+    /// None when nothing could be rendered at all.
+    pub fn get(&self, glyph: char) -> Option<&'a Image> {
+        let images = self.images?;
+        images.get(&glyph).or_else(|| {
+            self.missing.borrow_mut().insert(glyph);
+            images.get(&FALLBACK_GLYPH)
+        })
+    }
+}
+
+/// Renders each glyph on its own image. Glyphs don't all advance by
+/// exactly a tile, so slicing one rendered string into tiles would cut
+/// wide ones like ™ apart and shift everything after them.
+fn render_glyphs(glyph_map: &[(String, String)], tile_size_px: Vector)
+    -> GameResult<HashMap<char, Image>> {
+
+    let mut tile_map = HashMap::new();
+    for (font_name, glyphs) in glyph_map.iter().cloned() {
+        let path = font_name.clone();
+        let rendered = Font::load(font_name).and_then(move |font: Font| {
+            let style = FontStyle::new(tile_size_px.y, Color::WHITE);
+            let mut _tile_map = HashMap::new();
+            for glyph in glyphs.chars() {
+                _tile_map.insert(glyph, font.render(&glyph.to_string(), &style)?);
+            }
+            Ok(_tile_map)
+        }).wait();
+        match rendered {
+            Ok(tiles) => tile_map.extend(tiles),
+            Err(err) => return Err(GameError::MissingAsset { path, reason: err.to_string() }),
+        }
+    }
+
+    Ok(tile_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_adds_new_glyphs_to_the_first_font() {
+        let mut assets = Assets::new(&[("a.ttf", "#@"), ("b.ttf", "≈")],
+                                     Vector::new(16, 16));
+        let added = assets.require("@gg≈k");
+        println!("{:?}", assets.glyph_map);
+        assert_eq!(added, "gk");
+        assert_eq!(assets.glyph_map[0].1, "#@?gk");
+        assert_eq!(assets.glyph_map[1].1, "≈");
+        assert!(assets.require("?g").is_empty());
+
+        // nothing rendered, so nothing is drawn or reported missing
+        assert!(assets.for_zoom(2.0).get('x').is_none());
+        assert!(assets.missing.borrow().is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+// Bits for the orthogonal neighbors a wall connects to
+pub const NORTH: u8 = 1;
+pub const EAST: u8 = 2;
+pub const SOUTH: u8 = 4;
+pub const WEST: u8 = 8;
+
+/// Number of tiles of context needed on every side of a region to decide
+/// the glyphs of the walls inside it
+pub const PADDING: u32 = 2;
+
+/// Picks the box-drawing glyph that joins a wall to the neighboring walls
+/// in mask, a combination of NORTH, EAST, SOUTH and WEST
+pub fn connected_wall_glyph(mask: u8) -> char {
+    match mask {
+        m if m == NORTH || m == SOUTH || m == NORTH | SOUTH => '║',
+        m if m == EAST || m == WEST || m == EAST | WEST => '═',
+        m if m == EAST | SOUTH => '╔',
+        m if m == SOUTH | WEST => '╗',
+        m if m == NORTH | EAST => '╚',
+        m if m == NORTH | WEST => '╝',
+        m if m == NORTH | EAST | SOUTH => '╠',
+        m if m == NORTH | SOUTH | WEST => '╣',
+        m if m == EAST | SOUTH | WEST => '╦',
+        m if m == NORTH | EAST | WEST => '╩',
+        m if m == NORTH | EAST | SOUTH | WEST => '╬',
+        _ => '#',
+    }
+}
+
+/// Given wall flags for a width x height region padded by PADDING tiles on
+/// every side (row-major), returns the glyph for every tile of the inner
+/// region, or None where the tile is not a wall.
+///
+/// Walls buried in solid rock keep the plain '#' glyph, walls that touch
+/// open ground are joined to their exposed orthogonal neighbors.
+pub fn wall_glyphs(walls: &[bool], width: u32, height: u32) -> Vec<Option<char>> {
+    let padded_width = width + PADDING * 2;
+    let is_wall = |x: u32, y: u32| walls[(x + y * padded_width) as usize];
+    let is_exposed = |x: u32, y: u32| {
+        for ny in y - 1..=y + 1 {
+            for nx in x - 1..=x + 1 {
+                if !is_wall(nx, ny) {
+                    return true;
+                }
+            }
+        }
+        false
+    };
+    let connects = |x: u32, y: u32| is_wall(x, y) && is_exposed(x, y);
+
+    let mut glyphs = Vec::with_capacity((width * height) as usize);
+    for y in PADDING..height + PADDING {
+        for x in PADDING..width + PADDING {
+            if !is_wall(x, y) {
+                glyphs.push(None);
+            }
+            else if !is_exposed(x, y) {
+                glyphs.push(Some('#'));
+            }
+            else {
+                let mut mask = 0;
+                if connects(x, y - 1) { mask |= NORTH; }
+                if connects(x + 1, y) { mask |= EAST; }
+                if connects(x, y + 1) { mask |= SOUTH; }
+                if connects(x - 1, y) { mask |= WEST; }
+                glyphs.push(Some(connected_wall_glyph(mask)));
+            }
+        }
+    }
+    glyphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connected_wall_glyph_corners() {
+        assert_eq!(connected_wall_glyph(EAST | SOUTH), '╔');
+        assert_eq!(connected_wall_glyph(SOUTH | WEST), '╗');
+        assert_eq!(connected_wall_glyph(NORTH | EAST), '╚');
+        assert_eq!(connected_wall_glyph(NORTH | WEST), '╝');
+        assert_eq!(connected_wall_glyph(0), '#');
+    }
+
+    #[test]
+    fn test_wall_glyphs_room() {
+        let rows = [
+            "#########",
+            "#########",
+            "#########",
+            "###...###",
+            "###...###",
+            "#########",
+            "#########",
+            "#########",
+        ];
+        let walls: Vec<bool> = rows.iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect();
+        let glyphs: String = wall_glyphs(&walls, 5, 4)
+            .iter()
+            .map(|glyph| glyph.unwrap_or('.'))
+            .collect();
+        println!("{}", glyphs);
+        assert_eq!(glyphs, concat!(
+            "╔═══╗",
+            "║...║",
+            "║...║",
+            "╚═══╝",
+        ));
+    }
+}
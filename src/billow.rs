@@ -0,0 +1,220 @@
+//! The rock's Billow noise a row of tiles at a time. Along a row only x
+//! changes, so each octave works out its lattice cell in y and z, which
+//! corners are too far away to count and their gradients once for the
+//! row, or once per cell it crosses, rather than for every tile. What
+//! comes out is bit for bit what noise::Billow over noise::ScalePoint
+//! gives, so seeds make the same worlds they always have.
+
+use noise::Billow;
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+/// What noise::Perlin scales its sum of surflets by to get into -1..1
+const SCALE_FACTOR: f64 = 3.889_855_325_553_107_4;
+
+const DIAG: f64 = std::f64::consts::FRAC_1_SQRT_2;
+const DIAG2: f64 = 0.577_350_269_189_625_8;
+
+/// noise::Perlin's gradients, the 12 edges of a cube twice then its 8
+/// corners
+const GRADIENTS: [[f64; 3]; 32] = [
+    [DIAG, DIAG, 0.0], [-DIAG, DIAG, 0.0], [DIAG, -DIAG, 0.0], [-DIAG, -DIAG, 0.0],
+    [DIAG, 0.0, DIAG], [-DIAG, 0.0, DIAG], [DIAG, 0.0, -DIAG], [-DIAG, 0.0, -DIAG],
+    [0.0, DIAG, DIAG], [0.0, -DIAG, DIAG], [0.0, DIAG, -DIAG], [0.0, -DIAG, -DIAG],
+    [DIAG, DIAG, 0.0], [-DIAG, DIAG, 0.0], [DIAG, -DIAG, 0.0], [-DIAG, -DIAG, 0.0],
+    [DIAG, 0.0, DIAG], [-DIAG, 0.0, DIAG], [DIAG, 0.0, -DIAG], [-DIAG, 0.0, -DIAG],
+    [0.0, DIAG, DIAG], [0.0, -DIAG, DIAG], [0.0, DIAG, -DIAG], [0.0, -DIAG, -DIAG],
+    [DIAG2, DIAG2, DIAG2], [-DIAG2, DIAG2, DIAG2], [DIAG2, -DIAG2, DIAG2], [-DIAG2, -DIAG2, DIAG2],
+    [DIAG2, DIAG2, -DIAG2], [-DIAG2, DIAG2, -DIAG2], [DIAG2, -DIAG2, -DIAG2], [-DIAG2, -DIAG2, -DIAG2],
+];
+
+/// Where a coordinate falls in an octave's lattice: the corner below it,
+/// how far it is from that corner and the one above, and those squared
+#[derive(Clone, Copy, Debug)]
+struct Axis {
+    near: isize,
+    distance: [f64; 2],
+    squared: [f64; 2],
+}
+
+impl Axis {
+    fn new(c: f64) -> Axis {
+        let floored = c.floor();
+        let near_distance = c - floored;
+        let far_distance = near_distance - 1.0;
+        Axis {
+            near: floored as isize,
+            distance: [near_distance, far_distance],
+            squared: [near_distance * near_distance, far_distance * far_distance],
+        }
+    }
+}
+
+/// The lattice cells a run of x coordinates fall in, octave by octave,
+/// worked out once and used for every row
+pub struct Columns {
+    len: usize,
+    /// The first octave's cells for every column, then the second's...
+    axes: Vec<Axis>,
+}
+
+impl Columns {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// An octave's source: noise::Perlin's permutation table for its seed,
+/// and how much it adds to the whole
+struct Octave {
+    permutation: [u8; 256],
+    amplitude: f64,
+}
+
+impl Octave {
+    /// The gradient at a corner of the lattice
+    fn gradient(&self, x: isize, y: isize, z: isize) -> [f64; 3] {
+        let p = &self.permutation;
+        let hash = p[p[p[(x & 0xff) as usize] as usize ^ (y & 0xff) as usize] as usize
+                     ^ (z & 0xff) as usize];
+        GRADIENTS[hash as usize % 32]
+    }
+}
+
+/// Billow noise with Billow's default octaves and lacunarity, over a
+/// ScalePoint, sampled a row at a time
+pub struct RowBillow {
+    octaves: Vec<Octave>,
+    frequency: f64,
+    lacunarity: f64,
+    scale: f64,
+}
+
+impl RowBillow {
+    pub fn new(seed: u32, frequency: f64, persistence: f64, scale: f64) -> Self {
+        let octaves = (0..Billow::DEFAULT_OCTAVE_COUNT).map(|octave| Octave {
+            permutation: permutation(seed.wrapping_add(octave as u32)),
+            amplitude: persistence.powi(octave as i32),
+        }).collect();
+        RowBillow { octaves, frequency, lacunarity: Billow::DEFAULT_LACUNARITY, scale }
+    }
+
+    /// The cells of each octave a coordinate falls in, scaled the way
+    /// ScalePoint and Billow scale it
+    fn axes(&self, c: f64) -> impl Iterator<Item = Axis> {
+        let (lacunarity, mut c) = (self.lacunarity, (c * self.scale) * self.frequency);
+        (0..self.octaves.len()).map(move |octave| {
+            if octave > 0 {
+                c *= lacunarity;
+            }
+            Axis::new(c)
+        })
+    }
+
+    pub fn columns(&self, xs: impl Iterator<Item = f64>) -> Columns {
+        let by_column: Vec<Vec<Axis>> = xs.map(|x| self.axes(x).collect()).collect();
+        let axes = (0..self.octaves.len())
+            .flat_map(|octave| by_column.iter().map(move |column| column[octave]))
+            .collect();
+        Columns { len: by_column.len(), axes }
+    }
+
+    /// Writes the noise at (x, y, z) into out for the x of every column
+    pub fn row(&self, columns: &Columns, y: f64, z: f64, out: &mut [f64]) {
+        let out = &mut out[..columns.len];
+        out.iter_mut().for_each(|val| *val = 0.0);
+        for ((octave, ay), (az, xs)) in self.octaves.iter().zip(self.axes(y))
+            .zip(self.axes(z).zip(columns.axes.chunks(columns.len.max(1)))) {
+            // corner c is (c & 1, (c >> 1) & 1, c >> 2) of the cell. Those too
+            // far in y and z alone are too far for any tile of the row.
+            let mut live = [false; 8];
+            for (c, live) in live.iter_mut().enumerate() {
+                *live = ay.squared[(c >> 1) & 1] + az.squared[c >> 2] < 1.0;
+            }
+            let mut cell = None;
+            // the x part of each corner's gradient, and the y and z parts
+            // of its dot product, the same across the cell
+            let mut corners = [(0.0, 0.0, 0.0); 8];
+            for (val, ax) in out.iter_mut().zip(xs) {
+                if cell != Some(ax.near) {
+                    cell = Some(ax.near);
+                    for (c, corner) in corners.iter_mut().enumerate() {
+                        let (iy, iz) = ((c >> 1) & 1, c >> 2);
+                        let gradient = octave.gradient(ax.near + (c & 1) as isize,
+                                                       ay.near + iy as isize, az.near + iz as isize);
+                        *corner = (gradient[0], ay.distance[iy] * gradient[1],
+                                   az.distance[iz] * gradient[2]);
+                    }
+                }
+                // summed in noise::Perlin's order, leaving out what it
+                // would add as 0
+                let mut sum = 0.0;
+                for (c, &(gx, dgy, dgz)) in corners.iter().enumerate() {
+                    if !live[c] {
+                        continue;
+                    }
+                    let (ix, iy, iz) = (c & 1, (c >> 1) & 1, c >> 2);
+                    let attenuation = 1.0 - (ax.squared[ix] + ay.squared[iy] + az.squared[iz]);
+                    if attenuation > 0.0 {
+                        sum += attenuation.powi(4) * (ax.distance[ix] * gx + dgy + dgz);
+                    }
+                }
+                let signal = (sum * SCALE_FACTOR).clamp(-1.0, 1.0);
+                *val += (signal.abs() * 2.0 - 1.0) * octave.amplitude;
+            }
+        }
+        out.iter_mut().for_each(|val| *val *= 0.5);
+    }
+}
+
+/// noise::Perlin's permutation table for a seed, shuffled by the same
+/// generator seeded the same way
+fn permutation(seed: u32) -> [u8; 256] {
+    let mut rng_seed = [0; 16];
+    rng_seed[0] = 1;
+    for i in 1..4 {
+        rng_seed[i * 4..i * 4 + 4].copy_from_slice(&seed.to_le_bytes());
+    }
+    let mut rng: XorShiftRng = SeedableRng::from_seed(rng_seed);
+    let mut values: Vec<u8> = (0..=255).collect();
+    rng.shuffle(&mut values);
+    let mut permutation = [0; 256];
+    permutation.copy_from_slice(&values);
+    permutation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noise::{MultiFractal, NoiseFn, ScalePoint, Seedable};
+
+    #[test]
+    fn test_same_noise_as_billow() {
+        // read at run time, as the game reads them from its config. Folded
+        // at compile time, noise::Billow's powi can round differently.
+        let (frequency, persistence, scale) = std::hint::black_box((0.0125, 0.035, 0.1));
+        for &seed in &[0, 10, 123_456_789] {
+            let billow = ScalePoint::new(Billow::new()
+                .set_seed(seed)
+                .set_frequency(frequency)
+                .set_persistence(persistence)
+                ).set_scale(scale);
+            let rows = RowBillow::new(seed, frequency, persistence, scale);
+            // west of 0 too, where the seam samples
+            let xs: Vec<f64> = (-300..300).map(|x| (x * 37) as f64).collect();
+            let columns = rows.columns(xs.iter().copied());
+            let mut out = vec![0.0; xs.len()];
+            for &(y, z) in &[(0.0, 0.0), (1000.0, 20.0), (123_456.0, 700.0)] {
+                rows.row(&columns, y, z, &mut out);
+                let different = xs.iter().zip(&out)
+                    .filter(|&(&x, val)| billow.get([x, y, z]).to_bits() != val.to_bits())
+                    .count();
+                println!("seed {} y {} z {}: {} different", seed, y, z, different);
+                assert_eq!(different, 0);
+            }
+        }
+    }
+}
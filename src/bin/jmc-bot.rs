@@ -0,0 +1,104 @@
+//! Runs a colony with nobody at the screen and a program steering its
+//! player, as fast as it'll go. `jmc-bot -- <program> [args]` runs the
+//! program as the agent, without one the player tunnels east.
+
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+use mc::agent::{Agent, Harness, ProcessAgent, Tunneler};
+use mc::difficulty::Difficulty;
+use mc::embark::Region;
+use mc::events::GameEvent;
+use mc::game_map::GameMap;
+use mc::logging;
+use mc::mods::{self, Content};
+use mc::scripting::{self, Scripts};
+use mc::simulation::{self, Simulation};
+use mc::spawner::Spawner;
+use mc::worldgen::WorldGenConfig;
+
+const USAGE: &str = "usage: jmc-bot [--ticks <n>] [--seed <n>] [--difficulty <name>] [-- <program> [args]]";
+/// Ticks run without --ticks, ten minutes of play at the usual rate
+const DEFAULT_TICKS: u64 = 6000;
+
+fn main() {
+    logging::init();
+    let args: Vec<String> = std::env::args().collect();
+    // everything after -- is the agent's command line
+    let split = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+    let (options, command) = (&args[..split], args.get(split + 1..).unwrap_or_default());
+    let ticks = option(options, "--ticks").unwrap_or(DEFAULT_TICKS);
+    let seed = option(options, "--seed").unwrap_or(10);
+    let difficulty = match options.iter().position(|arg| arg == "--difficulty") {
+        Some(index) => options.get(index + 1).and_then(|name| Difficulty::from_name(name))
+            .unwrap_or_else(|| exit_with_usage("--difficulty needs Peaceful, Standard or Brutal")),
+        None => Difficulty::default(),
+    };
+    let mut agent: Box<dyn Agent> = match command.split_first() {
+        Some((program, args)) => match ProcessAgent::spawn(program, args) {
+            Ok(agent) => Box::new(agent),
+            Err(err) => {
+                eprintln!("could not start {}: {}", program, err);
+                process::exit(1);
+            },
+        },
+        None => Box::new(Tunneler::new(1, 0)),
+    };
+
+    let (content, log) = Content::load(Path::new(mods::MODS_DIR));
+    print_lines(log);
+    let mut scripts = Scripts::new();
+    print_lines(scripts.load_dir(Path::new(scripting::SCRIPTS_DIR)));
+    let map = GameMap::with_seed(seed).with_difficulty(difficulty)
+        .with_worldgen(WorldGenConfig::load_or_default());
+    let mut sim = Simulation::new(map);
+    print_lines(sim.run_worldgen(&scripts));
+    // lands where the new colony screen starts out on
+    let region = Region::survey(&sim.map);
+    let (column, row) = region.default_cell();
+    let (x, y, z) = simulation::landing_position(&sim.map, region.center(column, row));
+    let player_id = sim.populate(x, y, z);
+    sim.spawner = Spawner::new(&content);
+
+    let mut harness = Harness::new(player_id);
+    let started = Instant::now();
+    let mut ran = 0;
+    while ran < ticks && !agent.finished() {
+        for event in harness.tick(&mut sim, agent.as_mut()) {
+            if let GameEvent::ColonyLost { loss, .. } = event {
+                println!("{} is lost: {}", sim.name, loss.describe());
+            }
+            print_lines(sim.script_event(&scripts, &event, Some(player_id)));
+        }
+        ran += 1;
+    }
+
+    let elapsed = started.elapsed();
+    println!("ran {} ticks in {:.2?}, {:.0} ticks a second", ran, elapsed, ran as f64 / elapsed.as_secs_f64());
+    match harness.observe(&mut sim) {
+        Some(seen) => println!("the player is at ({}, {}, {}) with {} of {} hit points",
+                               seen.x, seen.y, seen.z, seen.hp, seen.max_hp),
+        None => println!("the player is gone"),
+    }
+}
+
+/// Reads "<flag> <number>" from the options
+fn option<T: std::str::FromStr>(options: &[String], flag: &str) -> Option<T> {
+    let index = options.iter().position(|arg| arg == flag)?;
+    match options.get(index + 1).map(|value| value.parse()) {
+        Some(Ok(value)) => Some(value),
+        _ => exit_with_usage(&format!("{} needs a number", flag)),
+    }
+}
+
+fn exit_with_usage(err: &str) -> ! {
+    eprintln!("{}\n{}", err, USAGE);
+    process::exit(2);
+}
+
+fn print_lines(lines: Vec<String>) {
+    for line in lines {
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,41 @@
+//! Compares two snapshots written with `--desync-dumps`, showing which
+//! systems of the simulation went different between the games or runs
+//! that wrote them: `jmc-desync desync/peer0-tick1200.txt
+//! desync/peer1-tick1200.txt`
+
+use std::fs;
+use std::process;
+
+use mc::desync::Snapshot;
+
+const USAGE: &str = "usage: jmc-desync <snapshot> <snapshot>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (first, second) = match &args[1..] {
+        [first, second] => (read(first), read(second)),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        },
+    };
+    let report = first.diff(&second);
+    if report.is_empty() {
+        println!("the snapshots match");
+        return;
+    }
+    for line in report {
+        println!("{}", line);
+    }
+    process::exit(1);
+}
+
+fn read(path: &str) -> Snapshot {
+    let parsed = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| Snapshot::parse(&text));
+    parsed.unwrap_or_else(|err| {
+        eprintln!("could not read {}: {}", path, err);
+        process::exit(1);
+    })
+}
@@ -0,0 +1,143 @@
+//! Runs a colony with nobody at the screen, players join it with
+//! `mc --join <address>`
+
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use mc::desync::{self, Checksum, Snapshot};
+use mc::difficulty::Difficulty;
+use mc::entity::EntityId;
+use mc::events::GameEvent;
+use mc::game_map::GameMap;
+use mc::logging;
+use mc::mods::{self, Content};
+use mc::net::{self, Host};
+use mc::save;
+use mc::scripting::{self, Scripts};
+use mc::simulation::{self, Simulation};
+use mc::spawner::Spawner;
+use mc::timestep::{self, FixedTimestep};
+use mc::worldgen::WorldGenConfig;
+
+const USAGE: &str = "usage: jmc-server [--port <port>] [--difficulty <name>] [--desync-dumps]";
+/// The save profile the server keeps its colony in, apart from the game's
+const SERVER_PROFILE: &str = "server";
+/// Ticks between saves, a minute at the usual rate
+const SAVE_INTERVAL: u64 = 600;
+/// How long the loop sleeps between looking for ticks and players
+const IDLE: Duration = Duration::from_millis(5);
+
+fn main() {
+    logging::init();
+    let args: Vec<String> = std::env::args().collect();
+    let port = net::parse_port(&args).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+    let difficulty = parse_difficulty(&args).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+    let desync_dumps = args.iter().any(|arg| arg == desync::DUMP_FLAG);
+    let mut host = Host::listen(port).unwrap_or_else(|err| {
+        eprintln!("could not listen on port {}: {}", port, err);
+        process::exit(1);
+    });
+
+    let (content, log) = Content::load(Path::new(mods::MODS_DIR));
+    print_lines(log);
+    let mut scripts = Scripts::new();
+    print_lines(scripts.load_dir(Path::new(scripting::SCRIPTS_DIR)));
+    let (mut sim, player_id) = load_colony(&scripts, difficulty);
+    sim.spawner = Spawner::new(&content);
+    println!("serving {} on port {}, tick {}", sim.name, port, sim.tick);
+
+    let mut timestep = FixedTimestep::new(timestep::TICKS_PER_SECOND);
+    loop {
+        for peer in host.accept(|| sim.save_data(player_id)) {
+            println!("player {} joined", peer);
+        }
+        for peer in host.receive(&sim.squad) {
+            println!("player {} left", peer);
+        }
+        for desync in host.take_desyncs() {
+            println!("{}", desync.describe());
+        }
+        for _ in 0..timestep.advance() {
+            for (peer, order) in host.tick(sim.tick + 1) {
+                if let Err(err) = sim.apply_order(peer, order) {
+                    log::debug!("player {}'s order failed: {}", peer, err);
+                }
+            }
+            sim.step();
+            // the same point in the tick the players' games run scripts
+            for event in sim.events.drain() {
+                if let GameEvent::ColonyLost { loss, .. } = event {
+                    println!("{} is lost: {}", sim.name, loss.describe());
+                }
+                print_lines(sim.script_event(&scripts, &event, None));
+            }
+            if sim.tick.is_multiple_of(desync::CHECK_INTERVAL) {
+                if desync_dumps {
+                    let snapshot = Snapshot::of(&sim.save_data(player_id));
+                    if let Err(err) = desync::write_dump(&snapshot, net::HOST_PEER) {
+                        log::warn!("could not write the snapshot: {}", err);
+                    }
+                }
+                host.record(Checksum::of(&sim));
+            }
+            if sim.unsaved_changes && sim.tick.is_multiple_of(SAVE_INTERVAL) {
+                save(&mut sim, player_id);
+            }
+        }
+        thread::sleep(IDLE);
+    }
+}
+
+/// Reads "--difficulty <name>" from the arguments, for a new colony
+fn parse_difficulty(args: &[String]) -> Result<Difficulty, String> {
+    match args.iter().position(|arg| arg == "--difficulty") {
+        Some(index) => {
+            let name = args.get(index + 1).ok_or("--difficulty needs Peaceful, Standard or Brutal")?;
+            Difficulty::from_name(name).ok_or_else(|| format!("{:?} is not a difficulty", name))
+        },
+        None => Ok(Difficulty::default()),
+    }
+}
+
+/// The colony the server saved last, or a new one at the difficulty
+fn load_colony(scripts: &Scripts, difficulty: Difficulty) -> (Simulation, Option<EntityId>) {
+    let saved = save::load_game(SERVER_PROFILE).ok();
+    let map = match &saved {
+        Some(data) => GameMap::with_seed(data.random_seed).with_difficulty(data.difficulty),
+        None => GameMap::new().with_difficulty(difficulty),
+    }.with_worldgen(WorldGenConfig::load_or_default());
+    let mut sim = Simulation::new(map);
+    print_lines(sim.run_worldgen(scripts));
+    let player_id = match saved {
+        Some(data) => {
+            sim.restore(&data);
+            data.player_id
+        },
+        None => {
+            let (x, y, z) = simulation::start_position(&sim.map);
+            Some(sim.populate(x, y, z))
+        },
+    };
+    (sim, player_id)
+}
+
+fn save(sim: &mut Simulation, player_id: Option<EntityId>) {
+    match save::save_game(SERVER_PROFILE, &sim.save_data(player_id)) {
+        Ok(()) => sim.unsaved_changes = false,
+        Err(err) => log::warn!("could not save to {:?}: {:?}", SERVER_PROFILE, err),
+    }
+}
+
+fn print_lines(lines: Vec<String>) {
+    for line in lines {
+        println!("{}", line);
+    }
+}
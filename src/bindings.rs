@@ -0,0 +1,579 @@
+use enum_map::{enum_map, Enum, EnumMap};
+use quicksilver::input::Keyboard;
+use quicksilver::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Something the player can do from the map with a key, outside of menus
+#[derive(Clone, Copy, Debug, PartialEq, Enum)]
+pub enum Action {
+    ScrollLeft,
+    ScrollRight,
+    ScrollUp,
+    ScrollDown,
+    FastScrollLeft,
+    FastScrollRight,
+    FastScrollUp,
+    FastScrollDown,
+    JumpWest,
+    JumpEast,
+    JumpNorth,
+    JumpSouth,
+    LevelDown,
+    LevelUp,
+    JumpDeepest,
+    JumpSurface,
+    ZoomIn,
+    ZoomOut,
+    /// Zooms the camera to fit the area being selected
+    FitSelection,
+    /// Takes the camera to the latest entry in the chronicle
+    JumpToEvent,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    Pause,
+    CommandMenu,
+    Console,
+    Screenshot,
+    ToggleFollow,
+    /// Watches the selected colonist in a picture in the corner
+    TogglePip,
+    /// Splits the map into two views side by side, or joins them again
+    ToggleSplit,
+    /// Hands the keyboard to the other view of a split map
+    SwitchView,
+    CycleOverlay,
+    CycleChunkGrid,
+    CancelMove,
+    NextColonist,
+    ToggleOverseer,
+    BuildPillar,
+    Undo,
+    /// Writes a note on the tile under the mouse
+    PlaceNote,
+    ToggleMap,
+    ToggleTitle,
+    ToggleCredits,
+    ToggleDebug,
+    Help,
+    HelpNextPage,
+    HelpPreviousPage,
+}
+
+impl Action {
+
+    /// What the action does, for the help screen
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::ScrollLeft => "scroll camera west",
+            Action::ScrollRight => "scroll camera east",
+            Action::ScrollUp => "scroll camera north",
+            Action::ScrollDown => "scroll camera south",
+            Action::FastScrollLeft => "scroll camera west fast",
+            Action::FastScrollRight => "scroll camera east fast",
+            Action::FastScrollUp => "scroll camera north fast",
+            Action::FastScrollDown => "scroll camera south fast",
+            Action::JumpWest => "jump camera to the west edge",
+            Action::JumpEast => "jump camera to the east edge",
+            Action::JumpNorth => "jump camera to the north edge",
+            Action::JumpSouth => "jump camera to the south edge",
+            Action::LevelDown => "camera down a level",
+            Action::LevelUp => "camera up a level",
+            Action::JumpDeepest => "camera to the deepest level",
+            Action::JumpSurface => "camera to the surface",
+            Action::ZoomIn => "zoom in",
+            Action::ZoomOut => "zoom out",
+            Action::FitSelection => "zoom to fit the area being selected",
+            Action::JumpToEvent => "camera to the latest event in the chronicle",
+            Action::MoveLeft => "move player west, digging",
+            Action::MoveRight => "move player east, digging",
+            Action::MoveUp => "move player north, digging",
+            Action::MoveDown => "move player south, digging",
+            Action::MoveUpLeft => "move player northwest, digging",
+            Action::MoveUpRight => "move player northeast, digging",
+            Action::MoveDownLeft => "move player southwest, digging",
+            Action::MoveDownRight => "move player southeast, digging",
+            Action::Pause => "close menu or pause",
+            Action::CommandMenu => "command menu",
+            Action::Console => "debug console",
+            Action::Screenshot => "save the map view to a png",
+            Action::ToggleFollow => "follow player",
+            Action::TogglePip => "watch the selected colonist in a corner, or stop",
+            Action::ToggleSplit => "split the map in two views, or join them again",
+            Action::SwitchView => "switch to the other view of a split map",
+            Action::CycleOverlay => "cycle map overlays",
+            Action::CycleChunkGrid => "show chunk borders, then a tile grid as well",
+            Action::CancelMove => "cancel move orders, right click a tile to order one",
+            Action::NextColonist => "select the next colonist, or left click one",
+            Action::ToggleOverseer => "switch between steering a colonist and the overseer's free camera",
+            Action::BuildPillar => "build support pillar",
+            Action::Undo => "undo the last designation or track laid",
+            Action::PlaceNote => "leave a note on the tile under the mouse",
+            Action::ToggleMap => "show map",
+            Action::ToggleTitle => "show title",
+            Action::ToggleCredits => "show credits",
+            Action::ToggleDebug => "show debug info",
+            Action::Help => "this help",
+            Action::HelpNextPage => "next help page",
+            Action::HelpPreviousPage => "previous help page",
+        }
+    }
+
+    /// Which way a scroll action pans the camera and whether it's fast,
+    /// None for everything else
+    pub fn pan(&self) -> Option<(Vector, bool)> {
+        match self {
+            Action::ScrollLeft => Some((Vector::new(-1, 0), false)),
+            Action::ScrollRight => Some((Vector::new(1, 0), false)),
+            Action::ScrollUp => Some((Vector::new(0, -1), false)),
+            Action::ScrollDown => Some((Vector::new(0, 1), false)),
+            Action::FastScrollLeft => Some((Vector::new(-1, 0), true)),
+            Action::FastScrollRight => Some((Vector::new(1, 0), true)),
+            Action::FastScrollUp => Some((Vector::new(0, -1), true)),
+            Action::FastScrollDown => Some((Vector::new(0, 1), true)),
+            _ => None,
+        }
+    }
+
+    /// Which way a move action steps, None for everything else
+    pub fn step(&self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveLeft => Some((-1, 0)),
+            Action::MoveRight => Some((1, 0)),
+            Action::MoveUp => Some((0, -1)),
+            Action::MoveDown => Some((0, 1)),
+            Action::MoveUpLeft => Some((-1, -1)),
+            Action::MoveUpRight => Some((1, -1)),
+            Action::MoveDownLeft => Some((-1, 1)),
+            Action::MoveDownRight => Some((1, 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Which keys move the player, picked in settings
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MovementKeys {
+    /// W, A, S, D, without diagonals
+    Wasd,
+    /// The number pad, 8 directions
+    Numpad,
+    /// h, j, k, l and y, u, b, n for the diagonals, Ctrl+B shows the
+    /// debug info instead of B and Ctrl+N leaves a note instead of N
+    Vi,
+}
+
+impl MovementKeys {
+
+    pub fn name(&self) -> &str {
+        match self {
+            MovementKeys::Wasd => "W, A, S, D",
+            MovementKeys::Numpad => "numpad",
+            MovementKeys::Vi => "vi keys",
+        }
+    }
+
+    /// The layout after this one, for cycling through them in settings
+    pub fn next(&self) -> MovementKeys {
+        match self {
+            MovementKeys::Wasd => MovementKeys::Numpad,
+            MovementKeys::Numpad => MovementKeys::Vi,
+            MovementKeys::Vi => MovementKeys::Wasd,
+        }
+    }
+
+    /// The key for each move action the layout has
+    fn keys(&self) -> Vec<(Action, Key)> {
+        match self {
+            MovementKeys::Wasd => vec![
+                (Action::MoveLeft, Key::A),
+                (Action::MoveRight, Key::D),
+                (Action::MoveUp, Key::W),
+                (Action::MoveDown, Key::S),
+            ],
+            MovementKeys::Numpad => vec![
+                (Action::MoveLeft, Key::Numpad4),
+                (Action::MoveRight, Key::Numpad6),
+                (Action::MoveUp, Key::Numpad8),
+                (Action::MoveDown, Key::Numpad2),
+                (Action::MoveUpLeft, Key::Numpad7),
+                (Action::MoveUpRight, Key::Numpad9),
+                (Action::MoveDownLeft, Key::Numpad1),
+                (Action::MoveDownRight, Key::Numpad3),
+            ],
+            MovementKeys::Vi => vec![
+                (Action::MoveLeft, Key::H),
+                (Action::MoveRight, Key::L),
+                (Action::MoveUp, Key::K),
+                (Action::MoveDown, Key::J),
+                (Action::MoveUpLeft, Key::Y),
+                (Action::MoveUpRight, Key::U),
+                (Action::MoveDownLeft, Key::B),
+                (Action::MoveDownRight, Key::N),
+            ],
+        }
+    }
+}
+
+/// The keyboard the game is played on. Keys are bound by where they are
+/// on a US QWERTY keyboard, the way scancodes are, and the layout says
+/// which key the keyboard reports from each of those places, so W, A, S, D
+/// stay a cluster and the help names the keys printed on them.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum KeyboardLayout {
+    Qwerty,
+    /// German and central European, Y and Z swapped
+    Qwertz,
+    /// French
+    Azerty,
+    Dvorak,
+}
+
+impl KeyboardLayout {
+
+    pub fn name(&self) -> &str {
+        match self {
+            KeyboardLayout::Qwerty => "QWERTY",
+            KeyboardLayout::Qwertz => "QWERTZ",
+            KeyboardLayout::Azerty => "AZERTY",
+            KeyboardLayout::Dvorak => "Dvorak",
+        }
+    }
+
+    /// The layout after this one, for cycling through them in settings
+    pub fn next(&self) -> KeyboardLayout {
+        match self {
+            KeyboardLayout::Qwerty => KeyboardLayout::Qwertz,
+            KeyboardLayout::Qwertz => KeyboardLayout::Azerty,
+            KeyboardLayout::Azerty => KeyboardLayout::Dvorak,
+            KeyboardLayout::Dvorak => KeyboardLayout::Qwerty,
+        }
+    }
+
+    /// The key the keyboard reports from where position is on a QWERTY
+    /// one, keys the layout doesn't move report as themselves
+    pub fn key_at(&self, position: Key) -> Key {
+        let moved: &[(Key, Key)] = match self {
+            KeyboardLayout::Qwerty => &[],
+            KeyboardLayout::Qwertz => &[(Key::Y, Key::Z), (Key::Z, Key::Y)],
+            KeyboardLayout::Azerty => &[
+                (Key::Q, Key::A), (Key::A, Key::Q), (Key::W, Key::Z), (Key::Z, Key::W),
+                (Key::Semicolon, Key::M), (Key::M, Key::Comma), (Key::Comma, Key::Semicolon),
+                (Key::Period, Key::Colon),
+            ],
+            KeyboardLayout::Dvorak => &[
+                (Key::Q, Key::Apostrophe), (Key::W, Key::Comma), (Key::E, Key::Period),
+                (Key::R, Key::P), (Key::T, Key::Y), (Key::Y, Key::F), (Key::U, Key::G),
+                (Key::I, Key::C), (Key::O, Key::R), (Key::P, Key::L),
+                (Key::LBracket, Key::Slash), (Key::RBracket, Key::Equals),
+                (Key::S, Key::O), (Key::D, Key::E), (Key::F, Key::U), (Key::G, Key::I),
+                (Key::H, Key::D), (Key::J, Key::H), (Key::K, Key::T), (Key::L, Key::N),
+                (Key::Semicolon, Key::S), (Key::Apostrophe, Key::Minus),
+                (Key::Z, Key::Semicolon), (Key::X, Key::Q), (Key::C, Key::J), (Key::V, Key::K),
+                (Key::B, Key::X), (Key::N, Key::B), (Key::Comma, Key::W), (Key::Period, Key::V),
+                (Key::Slash, Key::Z),
+            ],
+        };
+        moved.iter().find(|(from, _)| *from == position).map_or(position, |(_, to)| *to)
+    }
+}
+
+/// Modifier keys held down, the left and right ones count the same
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+
+    pub const NONE: Modifiers = Modifiers { ctrl: false, shift: false, alt: false };
+    pub const CTRL: Modifiers = Modifiers { ctrl: true, shift: false, alt: false };
+    pub const SHIFT: Modifiers = Modifiers { ctrl: false, shift: true, alt: false };
+
+    /// The modifiers held on the keyboard now
+    pub fn held(keyboard: &Keyboard) -> Modifiers {
+        let down = |left, right| keyboard[left].is_down() || keyboard[right].is_down();
+        Modifiers {
+            ctrl: down(Key::LControl, Key::RControl),
+            shift: down(Key::LShift, Key::RShift),
+            alt: down(Key::LAlt, Key::RAlt),
+        }
+    }
+
+    /// Whether every modifier in other is held in these
+    pub fn contains(self, other: Modifiers) -> bool {
+        (self.ctrl || !other.ctrl) && (self.shift || !other.shift) && (self.alt || !other.alt)
+    }
+
+    fn count(self) -> usize {
+        [self.ctrl, self.shift, self.alt].iter().filter(|held| **held).count()
+    }
+}
+
+/// A key pressed with some modifiers held, or none. When chords on the
+/// same key could both fire, the one with the most modifiers held wins, so
+/// Ctrl+Left never also scrolls Left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Chord {
+
+    pub fn key(key: Key) -> Chord {
+        Chord { modifiers: Modifiers::NONE, key }
+    }
+
+    pub fn ctrl(key: Key) -> Chord {
+        Chord { modifiers: Modifiers::CTRL, key }
+    }
+
+    pub fn shift(key: Key) -> Chord {
+        Chord { modifiers: Modifiers::SHIFT, key }
+    }
+
+    /// How the chord is written on the help screen
+    pub fn name(&self) -> String {
+        let key = match self.key {
+            Key::Left => String::from("Left"),
+            Key::Right => String::from("Right"),
+            Key::Up => String::from("Up"),
+            Key::Down => String::from("Down"),
+            Key::Comma => String::from(","),
+            Key::Period => String::from("."),
+            Key::Semicolon => String::from(";"),
+            Key::Colon => String::from(":"),
+            Key::Apostrophe => String::from("'"),
+            Key::Equals => String::from("="),
+            Key::Minus => String::from("-"),
+            Key::LBracket => String::from("["),
+            Key::RBracket => String::from("]"),
+            Key::Grave => String::from("`"),
+            Key::Back => String::from("Backspace"),
+            Key::Slash => String::from("?"),
+            Key::Escape => String::from("Esc"),
+            key => format!("{:?}", key),
+        };
+        let modifiers = [
+            (self.modifiers.ctrl, "Ctrl+"),
+            (self.modifiers.shift, "Shift+"),
+            (self.modifiers.alt, "Alt+"),
+        ];
+        let mut name: String = modifiers.iter()
+            .filter(|(held, _)| *held)
+            .map(|(_, prefix)| *prefix)
+            .collect();
+        name.push_str(&key);
+        name
+    }
+}
+
+/// Which keys trigger each action, the help screen is generated from these
+pub struct Bindings {
+    keys: EnumMap<Action, Vec<Chord>>,
+}
+
+impl Bindings {
+
+    /// The default keys, with the movement keys picked in settings, where
+    /// they are on the keyboard layout
+    pub fn new(movement: MovementKeys, layout: KeyboardLayout) -> Bindings {
+        use Chord as B;
+        let mut bindings = Bindings {
+            keys: enum_map! {
+                Action::ScrollLeft => vec![B::key(Key::Left)],
+                Action::ScrollRight => vec![B::key(Key::Right)],
+                Action::ScrollUp => vec![B::key(Key::Up)],
+                Action::ScrollDown => vec![B::key(Key::Down)],
+                Action::FastScrollLeft => vec![B::shift(Key::Left)],
+                Action::FastScrollRight => vec![B::shift(Key::Right)],
+                Action::FastScrollUp => vec![B::shift(Key::Up)],
+                Action::FastScrollDown => vec![B::shift(Key::Down)],
+                Action::JumpWest => vec![B::ctrl(Key::Left)],
+                Action::JumpEast => vec![B::ctrl(Key::Right)],
+                Action::JumpNorth => vec![B::ctrl(Key::Up)],
+                Action::JumpSouth => vec![B::ctrl(Key::Down)],
+                Action::LevelDown => vec![B::key(Key::Period)],
+                Action::LevelUp => vec![B::key(Key::Comma)],
+                Action::JumpDeepest => vec![B::ctrl(Key::RBracket)],
+                Action::JumpSurface => vec![B::ctrl(Key::LBracket)],
+                Action::ZoomIn => vec![B::key(Key::RBracket)],
+                Action::ZoomOut => vec![B::key(Key::LBracket)],
+                Action::FitSelection => vec![B::key(Key::Z)],
+                Action::JumpToEvent => vec![B::key(Key::Space)],
+                Action::MoveLeft | Action::MoveRight | Action::MoveUp | Action::MoveDown
+                | Action::MoveUpLeft | Action::MoveUpRight | Action::MoveDownLeft
+                | Action::MoveDownRight => Vec::new(),
+                Action::Pause => vec![B::key(Key::Escape)],
+                Action::CommandMenu => vec![B::key(Key::Tab)],
+                Action::Console => vec![B::key(Key::Grave)],
+                Action::Screenshot => vec![B::key(Key::F12)],
+                Action::ToggleFollow => vec![B::key(Key::F)],
+                Action::TogglePip => vec![B::ctrl(Key::F)],
+                Action::ToggleSplit => vec![B::key(Key::F2)],
+                Action::SwitchView => vec![B::shift(Key::Tab)],
+                Action::CycleOverlay => vec![B::key(Key::O)],
+                Action::CycleChunkGrid => vec![B::key(Key::G)],
+                Action::CancelMove => vec![B::key(Key::Back)],
+                Action::NextColonist => vec![B::ctrl(Key::Tab)],
+                Action::ToggleOverseer => vec![B::key(Key::V)],
+                Action::BuildPillar => vec![B::key(Key::P)],
+                Action::Undo => vec![B::ctrl(Key::Z)],
+                Action::PlaceNote => vec![B::key(Key::N)],
+                Action::ToggleMap => vec![B::key(Key::M)],
+                Action::ToggleTitle => vec![B::key(Key::T)],
+                Action::ToggleCredits => vec![B::key(Key::C)],
+                Action::ToggleDebug => vec![B::key(Key::B)],
+                Action::Help => vec![B::key(Key::F1), B::key(Key::Slash)],
+                Action::HelpNextPage => vec![B::key(Key::PageDown)],
+                Action::HelpPreviousPage => vec![B::key(Key::PageUp)],
+            },
+        };
+        for (action, key) in movement.keys() {
+            bindings.keys[action] = vec![B::key(key)];
+        }
+        if movement == MovementKeys::Vi {
+            bindings.keys[Action::ToggleDebug] = vec![B::ctrl(Key::B)];
+            bindings.keys[Action::PlaceNote] = vec![B::ctrl(Key::N)];
+        }
+        for (_, chords) in bindings.keys.iter_mut() {
+            for chord in chords {
+                chord.key = layout.key_at(chord.key);
+            }
+        }
+        bindings
+    }
+
+    /// Which actions have their chord held down
+    pub fn held_actions(&self, window: &Window) -> EnumMap<Action, bool> {
+        let keyboard = window.keyboard();
+        self.resolve(Modifiers::held(keyboard), |key| keyboard[key].is_down())
+    }
+
+    /// Which actions had their chord's key go down this frame
+    pub fn pressed_actions(&self, window: &Window) -> EnumMap<Action, bool> {
+        let keyboard = window.keyboard();
+        self.resolve(Modifiers::held(keyboard), |key| keyboard[key] == ButtonState::Pressed)
+    }
+
+    /// The actions whose chords fire with the modifiers held and the keys
+    /// active, only the chords with the most modifiers on each key
+    fn resolve(&self, held: Modifiers, active: impl Fn(Key) -> bool) -> EnumMap<Action, bool> {
+        let firing: Vec<(Action, Chord)> = self.keys.iter()
+            .flat_map(|(action, chords)| chords.iter().map(move |chord| (action, *chord)))
+            .filter(|(_, chord)| held.contains(chord.modifiers) && active(chord.key))
+            .collect();
+        let mut actions = EnumMap::default();
+        for (action, chord) in &firing {
+            let outranked = firing.iter().any(|(_, other)| {
+                other.key == chord.key && other.modifiers.count() > chord.modifiers.count()
+            });
+            if !outranked {
+                actions[*action] = true;
+            }
+        }
+        actions
+    }
+
+    /// A "keys: description" line for every action, in the order of Action
+    pub fn help_lines(&self) -> Vec<String> {
+        self.keys.iter().map(|(action, bindings)| {
+            let keys: Vec<String> = bindings.iter().map(Chord::name).collect();
+            let keys = if keys.is_empty() { String::from("unbound") } else { keys.join(" or ") };
+            format!("{}: {}", keys, action.description())
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_follows_bindings() {
+        let mut bindings = Bindings::new(MovementKeys::Wasd, KeyboardLayout::Qwerty);
+        let lines = bindings.help_lines();
+        println!("{:#?}", lines);
+        assert!(lines.contains(&String::from("F1 or ?: this help")));
+        assert!(lines.contains(&String::from("Ctrl+Left: jump camera to the west edge")));
+        assert!(lines.contains(&String::from("unbound: move player northwest, digging")));
+
+        bindings.keys[Action::BuildPillar] = vec![Chord::key(Key::K)];
+        assert!(bindings.help_lines().contains(&String::from("K: build support pillar")));
+        bindings.keys[Action::BuildPillar] = Vec::new();
+        assert!(bindings.help_lines().contains(&String::from("unbound: build support pillar")));
+    }
+
+    #[test]
+    fn test_chords_outrank_plain_keys() {
+        let bindings = Bindings::new(MovementKeys::Wasd, KeyboardLayout::Qwerty);
+        let ctrl_left = bindings.resolve(Modifiers::CTRL, |key| key == Key::Left);
+        println!("{:?}", ctrl_left);
+        assert!(ctrl_left[Action::JumpWest]);
+        assert!(!ctrl_left[Action::ScrollLeft]);
+
+        let left = bindings.resolve(Modifiers::NONE, |key| key == Key::Left);
+        assert!(left[Action::ScrollLeft] && !left[Action::JumpWest]);
+
+        let shift_left = bindings.resolve(Modifiers::SHIFT, |key| key == Key::Left);
+        assert!(shift_left[Action::FastScrollLeft] && !shift_left[Action::ScrollLeft]);
+
+        // a modifier nothing is bound with doesn't get in the way
+        let alt = Modifiers { alt: true, ..Modifiers::NONE };
+        let alt_left = bindings.resolve(alt, |key| key == Key::Left);
+        assert!(alt_left[Action::ScrollLeft] && !alt_left[Action::JumpWest]);
+
+        let both = bindings.resolve(Modifiers::CTRL, |key| key == Key::Tab || key == Key::W);
+        assert!(both[Action::NextColonist] && both[Action::MoveUp]);
+        assert!(!both[Action::CommandMenu]);
+        assert_eq!(Chord { modifiers: Modifiers { alt: true, ..Modifiers::CTRL }, key: Key::Up }.name(),
+                   "Ctrl+Alt+Up");
+    }
+
+    #[test]
+    fn test_movement_layouts() {
+        let vi = Bindings::new(MovementKeys::Vi, KeyboardLayout::Qwerty);
+        let b = vi.resolve(Modifiers::NONE, |key| key == Key::B);
+        println!("{:?}", vi.help_lines());
+        assert!(b[Action::MoveDownLeft] && !b[Action::ToggleDebug]);
+        assert!(vi.resolve(Modifiers::CTRL, |key| key == Key::B)[Action::ToggleDebug]);
+        assert!(!vi.resolve(Modifiers::NONE, |key| key == Key::W)[Action::MoveUp]);
+
+        let numpad = Bindings::new(MovementKeys::Numpad, KeyboardLayout::Qwerty);
+        let moves: Vec<_> = numpad.resolve(Modifiers::NONE, |key| key == Key::Numpad9).iter()
+            .filter(|(_, active)| **active)
+            .map(|(action, _)| action.step())
+            .collect();
+        assert_eq!(moves, vec![Some((1, -1))]);
+    }
+
+    #[test]
+    fn test_keys_bound_where_they_are_on_the_layout() {
+        let azerty = Bindings::new(MovementKeys::Wasd, KeyboardLayout::Azerty);
+        let lines = azerty.help_lines();
+        println!("{:#?}", lines);
+        assert!(lines.contains(&String::from("Z: move player north, digging")));
+        assert!(lines.contains(&String::from("Q: move player west, digging")));
+        assert!(lines.contains(&String::from(";: camera up a level")));
+        assert!(azerty.resolve(Modifiers::CTRL, |key| key == Key::W)[Action::Undo]);
+
+        // every place on the keyboard reports a key of its own, so moving
+        // the bindings around never puts two on the same key
+        let distinct = |bindings: &Bindings| {
+            let chords: Vec<Chord> = bindings.keys.values().flatten().copied().collect();
+            chords.iter().enumerate().filter(|(i, chord)| !chords[..*i].contains(chord)).count()
+        };
+        let qwerty = distinct(&Bindings::new(MovementKeys::Vi, KeyboardLayout::Qwerty));
+        for layout in [KeyboardLayout::Qwertz, KeyboardLayout::Azerty, KeyboardLayout::Dvorak].iter() {
+            assert_eq!(distinct(&Bindings::new(MovementKeys::Vi, *layout)), qwerty, "{:?}", layout);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rail::Track;
+
+/// Something a blueprint has built or dug on one of its tiles
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Plan {
+    /// Rock dug out, or designated to be
+    Dig,
+    Pillar,
+    Pump,
+    Track(Track),
+}
+
+impl Plan {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Plan::Dig => "dig",
+            Plan::Pillar => "pillar",
+            Plan::Pump => "pump",
+            Plan::Track(_) => "track",
+        }
+    }
+
+    /// The same plan a quarter turn clockwise, only switches point anywhere
+    fn turned(&self) -> Plan {
+        match self {
+            Plan::Track(Track::Switch(direction)) => Plan::Track(Track::Switch(direction.turn_right())),
+            plan => *plan,
+        }
+    }
+}
+
+/// The constructions and digs of an area, copied to be stamped down
+/// elsewhere
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub width: u32,
+    pub height: u32,
+    /// Each plan by its offset from the top left corner
+    pub plans: Vec<((u32, u32), Plan)>,
+}
+
+impl Blueprint {
+
+    pub fn new(width: u32, height: u32) -> Blueprint {
+        Blueprint { width, height, plans: Vec::new() }
+    }
+
+    pub fn add(&mut self, dx: u32, dy: u32, plan: Plan) {
+        self.plans.push(((dx, dy), plan));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plans.is_empty()
+    }
+
+    /// The blueprint turned a quarter clockwise, the top left corner stays
+    /// its top left corner
+    pub fn rotated(&self) -> Blueprint {
+        let plans = self.plans.iter()
+            .map(|&((dx, dy), plan)| ((self.height - 1 - dy, dx), plan.turned()))
+            .collect();
+        Blueprint { width: self.height, height: self.width, plans }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+
+    /// Reads a blueprint written by to_json, making sure its plans lie
+    /// inside it
+    pub fn from_json(text: &str) -> Result<Blueprint, String> {
+        let blueprint: Blueprint = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        if blueprint.plans.iter().any(|&((dx, dy), _)| dx >= blueprint.width || dy >= blueprint.height) {
+            return Err(String::from("it has plans outside its own area"));
+        }
+        Ok(blueprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rail::Direction;
+
+    #[test]
+    fn test_blueprints_rotate_and_round_trip() {
+        let mut blueprint = Blueprint::new(3, 2);
+        blueprint.add(0, 0, Plan::Dig);
+        blueprint.add(2, 0, Plan::Pillar);
+        blueprint.add(1, 1, Plan::Track(Track::Switch(Direction::North)));
+
+        let turned = blueprint.rotated();
+        println!("{:?}", turned);
+        assert_eq!((turned.width, turned.height), (2, 3));
+        assert_eq!(turned.plans, vec![
+            ((1, 0), Plan::Dig),
+            ((1, 2), Plan::Pillar),
+            ((0, 1), Plan::Track(Track::Switch(Direction::East))),
+        ]);
+        let around = turned.rotated().rotated().rotated();
+        assert_eq!(around, blueprint);
+
+        let json = blueprint.to_json().unwrap();
+        assert_eq!(Blueprint::from_json(&json).unwrap(), blueprint);
+        let mut outside = blueprint.clone();
+        outside.add(3, 0, Plan::Dig);
+        assert!(Blueprint::from_json(&outside.to_json().unwrap()).is_err());
+    }
+}
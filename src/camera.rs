@@ -1,5 +1,22 @@
 use quicksilver::prelude::*;
 
+use crate::entity::EntityId;
+
+/// Furthest the camera zooms out, the map is drawn in blocks well before
+/// this
+pub const MIN_ZOOM: f32 = 0.1;
+/// Furthest the camera zooms in, a bit past the largest tileset render
+pub const MAX_ZOOM: f32 = 4.0;
+/// Tiles a second the view pans at 1x zoom, more when zoomed out
+pub const PAN_SPEED: f32 = 10.0;
+/// Times faster the view pans fast
+pub const FAST_PAN: f32 = 10.0;
+/// Share of its speed a gliding view keeps after a second
+const GLIDE_KEPT: f32 = 0.05;
+/// Slowest a gliding view goes before it stops, in tiles a second
+const GLIDE_STOP: f32 = 0.5;
+
+#[derive(Clone)]
 pub struct Camera {
     //position: Position<u32>,
     //viewport_size: Vector,
@@ -9,6 +26,12 @@ pub struct Camera {
     pub max_x: u32,
     pub max_y: u32,
     pub max_z: u32,
+    /// Entity the camera keeps centered on, if any
+    pub follow: Option<EntityId>,
+    /// Tiles a second the view is panning at, at 1x zoom
+    pub velocity: Vector,
+    /// Whether it was panned since the last update
+    panning: bool,
     zoom_interval: f32,
     ref_camera: Rectangle,
 }
@@ -28,25 +51,28 @@ impl Camera {
             max_z: max_z,
             zoom_factor: 1.0,
             zoom_interval: 0.1,
+            follow: None,
+            velocity: Vector::ZERO,
+            panning: false,
         }
     }
 
+    /// Scrolls west, past the west edge of the world the view continues
+    /// from the east edge
     pub fn move_left(&mut self) {
         let delta = -1.0 / self.zoom_factor;
-        if self.viewport.x() + delta >= 0.0 {
-            self.ref_camera = self.ref_camera
-                .translate((delta , 0));
-            self.rescale(); 
-        }
+        self.ref_camera = self.ref_camera
+            .translate((delta , 0));
+        self.rescale(); 
     }
 
+    /// Scrolls east, past the east edge of the world the view continues
+    /// from the west edge
     pub fn move_right(&mut self) {
         let delta = 1.0 / self.zoom_factor;
-        if self.viewport.x() + delta < self.max_x as f32 {
-            self.ref_camera = self.ref_camera
-                .translate((delta, 0));
-            self.rescale(); 
-        }
+        self.ref_camera = self.ref_camera
+            .translate((delta, 0));
+        self.rescale(); 
     }
 
     pub fn move_up(&mut self) {
@@ -67,6 +93,29 @@ impl Camera {
         }
     }
 
+    /// Keeps the view panning the direction's way until the next update,
+    /// FAST_PAN times faster if fast
+    pub fn pan(&mut self, direction: Vector, fast: bool) {
+        self.velocity = direction * PAN_SPEED * if fast { FAST_PAN } else { 1.0 };
+        self.panning = true;
+    }
+
+    /// Moves the view as far as it's panned in seconds. A view that wasn't
+    /// panned since stops, or slows to a stop if it glides.
+    pub fn update(&mut self, seconds: f32, glide: bool) {
+        if !self.panning {
+            self.velocity = if glide { self.velocity * GLIDE_KEPT.powf(seconds) } else { Vector::ZERO };
+            if self.velocity.len() < GLIDE_STOP {
+                self.velocity = Vector::ZERO;
+            }
+        }
+        self.panning = false;
+        if self.velocity != Vector::ZERO {
+            self.ref_camera = self.ref_camera.translate(self.velocity * (seconds / self.zoom_factor));
+            self.rescale();
+        }
+    }
+
     pub fn elevate(&mut self) {
         if self.z_position > 0 {
             self.z_position -= 1;
@@ -80,35 +129,241 @@ impl Camera {
     }
 
     pub fn go_to(&mut self, x: f32, y: f32, z: u32) {
-        if x <= self.max_x as f32 && 
-           y <= self.max_y as f32 && 
+        if y <= self.max_y as f32 && 
            z <= self.max_z {
            self.ref_camera = Rectangle::new(
                (x, y), self.ref_camera.size()
            );    
            self.z_position = z;
+           self.rescale();
         }
     }
     
+    /// Moves the camera so (x, y) on level z is in the middle of the view
+    pub fn center_on(&mut self, x: f32, y: f32, z: u32) {
+        let size = self.ref_camera.size();
+        self.go_to(
+            x - size.x / 2.0, 
+            (y - size.y / 2.0).max(0.0), 
+            z);
+    }
+
+    /// Zooms so the width by height tiles from (x, y) on level z just fit
+    /// in the view, as near as the zoom goes, and centers on them
+    pub fn fit(&mut self, x: u32, y: u32, width: u32, height: u32, z: u32) {
+        let size = self.ref_camera.size();
+        let zoom = (size.x / width.max(1) as f32).min(size.y / height.max(1) as f32);
+        self.zoom_factor = zoom.max(self.min_zoom()).min(MAX_ZOOM);
+        self.center_on(x as f32 + width as f32 / 2.0, y as f32 + height as f32 / 2.0, z);
+    }
+
+    /// Scrolls just far enough that (x, y) is margin tiles or more inside
+    /// the edges of the view, round the seam if that's nearer
+    pub fn keep_in_view(&mut self, x: u32, y: u32, margin: f32) {
+        let world = self.world_size();
+        let size = self.viewport.size();
+        let margin_x = margin.min((size.x - 1.0) / 2.0).max(0.0);
+        let margin_y = margin.min((size.y - 1.0) / 2.0).max(0.0);
+        // columns far enough east of the view are nearer west of it
+        let mut column = (x as f32 - self.viewport.x()).rem_euclid(world.x);
+        if column > (world.x + size.x) / 2.0 {
+            column -= world.x;
+        }
+        let row = y as f32 - self.viewport.y();
+        let shift = |offset: f32, span: f32, margin: f32| if offset < margin {
+            offset - margin
+        } else if offset + 1.0 > span - margin {
+            offset + 1.0 - span + margin
+        } else {
+            0.0
+        };
+        self.ref_camera = self.ref_camera
+            .translate((shift(column, size.x, margin_x), shift(row, size.y, margin_y)));
+        self.rescale();
+    }
+
+    /// Changes how many tiles the view spans, keeping its center in place
+    pub fn resize(&mut self, viewport_size: impl Into<Vector>) {
+        let size = viewport_size.into();
+        let old_size = self.ref_camera.size();
+        self.max_x = (self.max_x as f32 + old_size.x - size.x) as u32;
+        self.max_y = (self.max_y as f32 + old_size.y - size.y) as u32;
+        let top_left = self.ref_camera.center() - size / 2.0;
+        self.ref_camera = Rectangle::new(
+            (top_left.x.max(0.0), top_left.y.max(0.0)), size);
+        self.rescale();
+    }
+
     pub fn zoom_in(&mut self) {
-        self.zoom_factor += self.zoom_interval;
+        self.zoom_factor = self.step_zoom(1.0).min(MAX_ZOOM);
         self.rescale();
     }
 
     pub fn zoom_out(&mut self) {
-        if self.zoom_factor > 0.2 {
-            self.zoom_factor -= self.zoom_interval;
-            self.rescale(); 
-        }
+        self.zoom_factor = self.step_zoom(-1.0).max(self.min_zoom());
+        self.rescale(); 
+    }
+
+    /// The zoom one interval in the direction, snapped to the interval so
+    /// repeated steps don't drift
+    fn step_zoom(&self, direction: f32) -> f32 {
+        ((self.zoom_factor / self.zoom_interval).round() + direction) * self.zoom_interval
+    }
+
+    /// Size of the world in tiles
+    fn world_size(&self) -> Vector {
+        Vector::new(
+            self.max_x as f32 + self.ref_camera.width(),
+            self.max_y as f32 + self.ref_camera.height())
+    }
+
+    /// Smallest zoom at which the whole view still fits in the world
+    pub fn min_zoom(&self) -> f32 {
+        let world = self.world_size();
+        MIN_ZOOM
+            .max(self.ref_camera.width() / world.x)
+            .max(self.ref_camera.height() / world.y)
     }
 
     fn rescale(&mut self) {
         let scaled_width = self.ref_camera.width() / self.zoom_factor;
         let scaled_height = self.ref_camera.height() / self.zoom_factor;
         let center = self.ref_camera.center();
-        self.viewport = Rectangle::new_sized((scaled_width, scaled_height))
+        let viewport = Rectangle::new_sized((scaled_width, scaled_height))
             .with_center((center.x, center.y));
+
+        // the world wraps around east to west, but the view has to stay
+        // between its north and south edges
+        let world = self.world_size();
+        let x = viewport.x().rem_euclid(world.x);
+        let y = viewport.y().min(world.y - scaled_height).max(0.0);
+        self.viewport = Rectangle::new((x, y), viewport.size());
+        self.ref_camera = self.ref_camera.with_center(self.viewport.center());
     }
 }
 
  
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_at(x: u32, y: u32) -> Camera {
+        // a 200 x 100 world viewed 60 x 30 tiles at a time
+        Camera::new(x, y, 0, 140, 70, 64, (60, 30))
+    }
+
+    #[test]
+    fn test_zoom_limits() {
+        let mut camera = camera_at(70, 35);
+        for _ in 0..100 {
+            camera.zoom_in();
+        }
+        println!("zoomed in: {}", camera.zoom_factor);
+        assert_eq!(camera.zoom_factor, MAX_ZOOM);
+
+        for _ in 0..100 {
+            camera.zoom_out();
+        }
+        println!("zoomed out: {} viewport: {:?}", camera.zoom_factor, camera.viewport);
+        // the 30 tall view fits the 100 tall world 0.3 times
+        assert_eq!(camera.zoom_factor, camera.min_zoom());
+        assert!(camera.viewport.height() <= 100.0);
+    }
+
+    #[test]
+    fn test_viewport_clamped_at_edges() {
+        let mut camera = camera_at(0, 0);
+        camera.zoom_out();
+        camera.zoom_out();
+        println!("top left: {:?}", camera.viewport);
+        assert_eq!(camera.viewport.y(), 0.0);
+
+        let mut camera = camera_at(140, 70);
+        camera.zoom_out();
+        camera.zoom_out();
+        let bottom = camera.viewport.y() + camera.viewport.height();
+        println!("bottom: {:?}", bottom);
+        assert!(bottom <= 100.0);
+        assert!(camera.viewport.y() >= 0.0);
+    }
+
+    #[test]
+    fn test_view_wraps_at_the_seam() {
+        let mut camera = camera_at(0, 35);
+        camera.move_left();
+        println!("past the west edge: {:?}", camera.viewport);
+        assert_eq!(camera.viewport.x(), 199.0);
+
+        camera.move_right();
+        camera.move_right();
+        println!("back east: {:?}", camera.viewport);
+        assert_eq!(camera.viewport.x(), 1.0);
+
+        // zooming out at the west edge wraps the view instead of pushing it east
+        let mut camera = camera_at(0, 35);
+        camera.zoom_out();
+        println!("zoomed out: {:?}", camera.viewport);
+        assert!(camera.viewport.x() > 190.0);
+    }
+
+    #[test]
+    fn test_panning_and_gliding() {
+        let mut camera = camera_at(70, 35);
+        camera.pan(Vector::new(1, 0), false);
+        camera.update(0.5, false);
+        println!("panned: {:?}", camera.viewport);
+        assert_eq!(camera.viewport.x(), 75.0);
+        // let go without gliding it stops at once
+        camera.update(0.5, false);
+        assert_eq!(camera.viewport.x(), 75.0);
+
+        camera.pan(Vector::new(0, -1), true);
+        camera.update(0.1, true);
+        assert_eq!(camera.viewport.y(), 25.0);
+        let mut last = camera.viewport.y();
+        for _ in 0..20 {
+            camera.update(0.1, true);
+            println!("gliding: {:?} {:?}", camera.velocity, camera.viewport);
+            assert!(camera.viewport.y() <= last);
+            last = camera.viewport.y();
+        }
+        assert!(last < 25.0);
+        // it's slowed to a stop by now
+        assert_eq!(camera.velocity, Vector::ZERO);
+    }
+
+    #[test]
+    fn test_fit_area_in_view() {
+        let mut camera = camera_at(70, 35);
+        camera.fit(10, 10, 120, 15, 2);
+        println!("wide: {} {:?}", camera.zoom_factor, camera.viewport);
+        assert_eq!(camera.zoom_factor, 0.5);
+        assert_eq!(camera.viewport.x(), 10.0);
+        assert_eq!(camera.viewport.width(), 120.0);
+        assert_eq!(camera.z_position, 2);
+
+        // a single tile only zooms in as far as the camera goes
+        camera.fit(100, 50, 1, 1, 0);
+        println!("tile: {} {:?}", camera.zoom_factor, camera.viewport);
+        assert_eq!(camera.zoom_factor, MAX_ZOOM);
+        assert_eq!(camera.viewport.center(), Vector::new(100.5, 50.5));
+    }
+
+    #[test]
+    fn test_view_scrolls_to_keep_a_tile_in_sight() {
+        let mut camera = camera_at(0, 35);
+        camera.keep_in_view(58, 40, 3.0);
+        println!("east: {:?}", camera.viewport);
+        assert_eq!(camera.viewport.x(), 2.0);
+
+        // west across the seam rather than all the way east
+        camera.keep_in_view(199, 40, 3.0);
+        println!("past the seam: {:?}", camera.viewport);
+        assert_eq!(camera.viewport.x(), 196.0);
+
+        camera.keep_in_view(10, 36, 3.0);
+        assert_eq!(camera.viewport.x(), 196.0);
+        assert_eq!(camera.viewport.y(), 33.0);
+    }
+}
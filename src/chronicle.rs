@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use crate::events::GameEvent;
+use crate::skills::MAX_LEVEL;
+
+/// Ticks water has to stand still, or the rock hold, before the next flood
+/// or cave-in is one of its own
+const QUIET_TICKS: u64 = 600;
+/// Levels between the depths the chronicle notes the colony reaching
+const DEPTH_MARK: u32 = 10;
+
+/// Something the colony will remember, when and where it happened
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub tick: u64,
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub text: String,
+}
+
+/// The colony's history, written off the event bus and kept in the save
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chronicle {
+    entries: Vec<Entry>,
+    /// Ticks water last flowed and the ceiling last came down, so a flood
+    /// or cave-in still going on isn't written down again
+    last_flood: Option<u64>,
+    last_cave_in: Option<u64>,
+    /// Deepest level anything was dug out on
+    deepest: Option<u32>,
+}
+
+impl Chronicle {
+
+    pub fn new() -> Chronicle {
+        Chronicle::default()
+    }
+
+    /// Oldest first
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Writes the event down if it's one worth remembering, who is the
+    /// name of the colonist it happened to if it happened to one. Returns
+    /// the entry written.
+    pub fn on_event(&mut self, event: &GameEvent, tick: u64, who: Option<&str>) -> Option<&Entry> {
+        let text = match *event {
+            GameEvent::Dug { z, .. } => {
+                let text = match self.deepest {
+                    None => format!("Broke ground on level {}", z),
+                    Some(deepest) if z / DEPTH_MARK > deepest / DEPTH_MARK => format!("Dug down to level {}", z),
+                    Some(_) => String::new(),
+                };
+                self.deepest = Some(self.deepest.map_or(z, |deepest| deepest.max(z)));
+                text
+            },
+            GameEvent::WaterFlowed { .. } => {
+                let quiet = self.last_flood.is_none_or(|last| tick.saturating_sub(last) >= QUIET_TICKS);
+                self.last_flood = Some(tick);
+                if quiet { String::from("Water broke loose") } else { String::new() }
+            },
+            GameEvent::CaveIn { .. } => {
+                let quiet = self.last_cave_in.is_none_or(|last| tick.saturating_sub(last) >= QUIET_TICKS);
+                self.last_cave_in = Some(tick);
+                if quiet { String::from("The ceiling came down") } else { String::new() }
+            },
+            GameEvent::Died { cause, .. } => match who {
+                Some(who) => format!("{} died of {}", who, cause.describe()),
+                None => String::new(),
+            },
+            GameEvent::SkillUp { skill, level, .. } if level >= MAX_LEVEL => match who {
+                Some(who) => format!("{} mastered {}", who, skill.name()),
+                None => String::new(),
+            },
+            GameEvent::ColonyLost { loss, .. } => loss.describe(),
+            GameEvent::Incident { incident, .. } => String::from(incident.describe()),
+            GameEvent::CaravanArrived { .. } => String::from("A caravan came down the drill to trade"),
+            _ => String::new(),
+        };
+        if text.is_empty() {
+            return None;
+        }
+        let (x, y, z) = event.position();
+        self.entries.push(Entry { tick, x, y, z, text });
+        self.entries.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::DeathCause;
+
+    #[test]
+    fn test_chronicle_keeps_what_matters() {
+        let mut chronicle = Chronicle::new();
+        for (tick, z) in [(1, 32), (2, 33), (3, 40), (4, 41)].iter() {
+            chronicle.on_event(&GameEvent::Dug { x: 5, y: 5, z: *z }, *tick, None);
+        }
+        for tick in 10..20 {
+            chronicle.on_event(&GameEvent::WaterFlowed { x: 1, y: 2, z: 40 }, tick, None);
+        }
+        chronicle.on_event(&GameEvent::WaterFlowed { x: 1, y: 2, z: 40 }, 20 + QUIET_TICKS, None);
+        chronicle.on_event(&GameEvent::Died { x: 3, y: 3, z: 40, cause: DeathCause::Gas }, 900, None);
+        let entry = chronicle.on_event(&GameEvent::Died { x: 3, y: 3, z: 40, cause: DeathCause::Gas }, 900, Some("Ann"));
+        assert_eq!(entry.map(|entry| entry.text.as_str()), Some("Ann died of toxic gas"));
+
+        let texts: Vec<&str> = chronicle.entries().iter().map(|entry| entry.text.as_str()).collect();
+        println!("{:?}", texts);
+        assert_eq!(texts, vec![
+            "Broke ground on level 32", "Dug down to level 40", "Water broke loose", "Water broke loose", "Ann died of toxic gas",
+        ]);
+        assert_eq!(chronicle.entries()[1].tick, 3);
+    }
+}
@@ -0,0 +1,223 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::logging;
+
+/// Where generated chunks are kept between games, a directory for each
+/// world
+pub const CACHE_DIR: &str = "cache/chunks";
+/// Bumped whenever generation changes, so chunks cached by older builds
+/// are generated again rather than loaded
+const CACHE_VERSION: u32 = 1;
+/// Bytes each tile takes up in a cached chunk, its noise value then
+/// whether it's a gas pocket
+const SAMPLE_BYTES: usize = 9;
+/// Bytes the cache may take up with every world in it together, about
+/// four hundred chunks. The worlds played longest ago are deleted to keep
+/// under it, and the world being played stops caching once it's full.
+const CACHE_LIMIT: u64 = 1 << 30;
+/// Holds when the world in its directory was last played, the worlds whose
+/// stamp is oldest are deleted first
+const STAMP: &str = "last_used";
+
+/// What generating a tile takes from the noise, enough to generate it
+/// again without sampling the noise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub val: f64,
+    pub gas_pocket: bool,
+}
+
+/// Chunks generated for a world, kept on disk so revisiting them or
+/// starting the same world again reads them back instead of sampling the
+/// noise. Only the generated rock is kept, what's changed since is saved
+/// with the game.
+pub struct ChunkCache {
+    dir: PathBuf,
+    /// Bytes the files in dir take up
+    size: AtomicU64,
+    limit: u64,
+    /// Whether a chunk was left out for the cache being full, it's only
+    /// logged the first time
+    full: AtomicBool,
+}
+
+impl ChunkCache {
+
+    /// The cache under root for worlds generated the same way, told apart
+    /// by the key. Opening it marks the world as played now and deletes
+    /// the worlds played longest ago while the cache is over CACHE_LIMIT.
+    pub fn new(root: impl AsRef<Path>, key: &str) -> ChunkCache {
+        ChunkCache::with_limit(root, key, CACHE_LIMIT)
+    }
+
+    fn with_limit(root: impl AsRef<Path>, key: &str, limit: u64) -> ChunkCache {
+        let root = root.as_ref();
+        let dir = root.join(format!("v{}-{}", CACHE_VERSION, key));
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos());
+        if let Err(err) = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(STAMP), now.to_string())) {
+            log::warn!(target: logging::WORLDGEN, "could not mark {} as played: {}", dir.display(), err);
+        }
+        let size = prune(root, &dir, limit);
+        ChunkCache { dir, size: AtomicU64::new(size), limit, full: AtomicBool::new(false) }
+    }
+
+    fn path(&self, (x, y, z): (u32, u32, u32)) -> PathBuf {
+        self.dir.join(format!("{}_{}_{}.chunk", x, y, z))
+    }
+
+    /// The samples of the chunk with its lowest corner at origin, None if
+    /// it isn't cached or the file doesn't hold count of them
+    pub fn load(&self, origin: (u32, u32, u32), count: usize) -> Option<Vec<Sample>> {
+        let bytes = fs::read(self.path(origin)).ok()?;
+        if bytes.len() != count * SAMPLE_BYTES {
+            return None;
+        }
+        Some(bytes.chunks_exact(SAMPLE_BYTES).map(|sample| {
+            let mut val = [0; 8];
+            val.copy_from_slice(&sample[..8]);
+            Sample { val: f64::from_le_bytes(val), gas_pocket: sample[8] != 0 }
+        }).collect())
+    }
+
+    /// Keeps the samples of the chunk with its lowest corner at origin,
+    /// unless the cache is full. They're written to a file of their own
+    /// first, so a game stopped partway through leaves no half a chunk.
+    pub fn store(&self, origin: (u32, u32, u32), samples: &[Sample]) -> io::Result<()> {
+        let len = (samples.len() * SAMPLE_BYTES) as u64;
+        if self.size.load(Ordering::Relaxed) + len > self.limit {
+            if !self.full.swap(true, Ordering::Relaxed) {
+                log::warn!(target: logging::WORLDGEN, "the chunk cache is full, chunks are generated again from here on");
+            }
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let mut bytes = Vec::with_capacity(samples.len() * SAMPLE_BYTES);
+        for sample in samples {
+            bytes.extend_from_slice(&sample.val.to_le_bytes());
+            bytes.push(sample.gas_pocket as u8);
+        }
+        let path = self.path(origin);
+        // another game playing the same world may be writing it too
+        let temp = path.with_extension(format!("{}.tmp", process::id()));
+        fs::write(&temp, bytes)?;
+        fs::rename(&temp, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })?;
+        self.size.fetch_add(len, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Deletes the directories under root of the worlds played longest ago,
+/// never keep, until what's left fits in limit. Returns the bytes keep
+/// takes up.
+fn prune(root: &Path, keep: &Path, limit: u64) -> u64 {
+    let mut kept = 0;
+    let mut others = Vec::new();
+    for entry in fs::read_dir(root).into_iter().flatten().flatten() {
+        let dir = entry.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let size = dir_size(&dir);
+        if dir == keep {
+            kept = size;
+        } else {
+            others.push((last_played(&dir), size, dir));
+        }
+    }
+    let mut total = kept + others.iter().map(|(_, size, _)| size).sum::<u64>();
+    others.sort_by_key(|(played, _, _)| *played);
+    for (_, size, dir) in others {
+        if total <= limit {
+            break;
+        }
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => total -= size,
+            Err(err) => log::warn!(target: logging::WORLDGEN, "could not delete the cached chunks in {}: {}", dir.display(), err),
+        }
+    }
+    kept
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// When the world cached in dir was last played, from its stamp. Worlds
+/// cached before there were stamps count as played longest ago.
+fn last_played(dir: &Path) -> u128 {
+    fs::read_to_string(dir.join(STAMP)).ok()
+        .and_then(|stamp| stamp.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_read_back_as_stored() {
+        let root = std::env::temp_dir().join("jmc-chunk-cache-test");
+        let _ = fs::remove_dir_all(&root);
+        let cache = ChunkCache::new(&root, "10-standard");
+        let samples = vec![
+            Sample { val: 0.25, gas_pocket: false },
+            Sample { val: -0.7, gas_pocket: true },
+            Sample { val: f64::MIN_POSITIVE, gas_pocket: false },
+        ];
+        assert_eq!(cache.load((0, 64, 128), samples.len()), None);
+        cache.store((0, 64, 128), &samples).unwrap();
+        let loaded = cache.load((0, 64, 128), samples.len());
+        println!("{:?}", loaded);
+        assert_eq!(loaded, Some(samples));
+
+        // a chunk of another size isn't taken for this one
+        assert_eq!(cache.load((0, 64, 128), 4), None);
+        assert_eq!(cache.load((64, 64, 128), 3), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_worlds_played_longest_ago_pruned() {
+        let root = std::env::temp_dir().join("jmc-chunk-cache-prune-test");
+        let _ = fs::remove_dir_all(&root);
+        let samples = vec![Sample { val: 0.5, gas_pocket: false }; 100];
+        for key in &["10-standard", "11-standard", "12-standard"] {
+            ChunkCache::new(&root, key).store((0, 0, 0), &samples).unwrap();
+        }
+        // played again, so the one played longest ago is now 11
+        ChunkCache::new(&root, "10-standard");
+        let world = dir_size(&root.join("v1-10-standard"));
+
+        let cache = ChunkCache::with_limit(&root, "13-standard", world * 3);
+        let mut left: Vec<String> = fs::read_dir(&root).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        println!("{:?}", left);
+        assert_eq!(left, vec!["v1-10-standard", "v1-12-standard", "v1-13-standard"]);
+
+        // the world being played stops caching at the limit, and no
+        // chunk is left half written
+        for x in 0..5 {
+            cache.store((x * 64, 0, 0), &samples).unwrap();
+        }
+        let stored = (0..5).filter(|&x| cache.load((x * 64, 0, 0), samples.len()).is_some()).count();
+        let files: Vec<_> = fs::read_dir(root.join("v1-13-standard")).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        println!("{} stored: {:?}", stored, files);
+        assert!(stored > 0 && stored < 5);
+        assert!(files.iter().all(|file| !file.ends_with(".tmp")));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,49 @@
+/// What the chunk grid debug view draws over the map, its key cycles
+/// through these in order
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChunkGrid {
+    Off,
+    /// Chunk borders, each chunk labeled with its coordinates
+    Chunks,
+    /// Chunk borders and a line between every tile
+    Tiles,
+}
+
+impl ChunkGrid {
+
+    pub fn next(self) -> ChunkGrid {
+        match self {
+            ChunkGrid::Off => ChunkGrid::Chunks,
+            ChunkGrid::Chunks => ChunkGrid::Tiles,
+            ChunkGrid::Tiles => ChunkGrid::Off,
+        }
+    }
+}
+
+/// The chunks a run of len columns (or rows) starting at first crosses,
+/// as the offset into the run where each one shows up and the chunk's
+/// coordinate. Coordinates wrap around at world_size.
+pub fn chunks_in_view(first: u32, len: u32, chunk_size: u32, world_size: u32)
+    -> Vec<(u32, u32)> {
+    let mut chunks = Vec::new();
+    for offset in 0..len {
+        let coord = (first + offset) % world_size;
+        if offset == 0 || coord.is_multiple_of(chunk_size) {
+            chunks.push((offset, coord / chunk_size));
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_wrap_around_the_world() {
+        let chunks = chunks_in_view(120, 80, 64, 192);
+        println!("{:?}", chunks);
+        assert_eq!(chunks, vec![(0, 1), (8, 2), (72, 0)]);
+        assert_eq!(chunks_in_view(64, 10, 64, 192), vec![(0, 1)]);
+    }
+}
@@ -1,6 +1,9 @@
 
+use quicksilver::graphics::Color;
+use serde::{Deserialize, Serialize};
+
 #[allow(dead_code)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ColorName {
     Bg,
     Fg,
@@ -35,64 +38,232 @@ pub enum ColorName {
     Stone6,
 }
 
-pub fn get_stone_color(val: &f64, min: &f64, max: &f64) -> ColorName {
-    let min = *min;
-    let max = *max;
+/// How a ramp colors the values between its stops
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Blend {
+    /// Each color covers an equal band of the range
+    Banded,
+    /// Colors fade into the next across the range
+    Smooth,
+}
+
+/// Colors spread evenly over a range of values, first to last from min
+/// to max
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorRamp {
+    pub colors: &'static [ColorName],
+    pub min: f64,
+    pub max: f64,
+    pub blend: Blend,
+    /// The color of values outside the range, None to give them the
+    /// color at the end they're past
+    pub outside: Option<ColorName>,
+}
+
+/// Rock by the noise it was generated from. Rock in the lowest band is
+/// hollowed out, and the noise outside the range is the densest rock.
+pub const STONE_RAMP: ColorRamp = ColorRamp::new(&[
+    ColorName::Void, ColorName::Stone1, ColorName::Stone2, ColorName::Stone3,
+    ColorName::Stone4, ColorName::Stone5, ColorName::Stone6,
+], 0.0, 0.5).with_outside(ColorName::Stone6);
+
+/// Cave floors by the noise they were generated from, darkest at the
+/// cave walls
+pub const FLOOR_RAMP: ColorRamp = ColorRamp::new(&[
+    ColorName::Stone6, ColorName::Stone6, ColorName::Stone5, ColorName::Stone4,
+    ColorName::Stone3, ColorName::Stone2, ColorName::Void, ColorName::Void,
+], 0.4, 1.0);
 
-    let step = (max - min) / 7.0;
-    if (min..(min+step)).contains(&val) {
-        ColorName::Void
+impl ColorRamp {
+
+    pub const fn new(colors: &'static [ColorName], min: f64, max: f64) -> ColorRamp {
+        ColorRamp { colors, min, max, blend: Blend::Banded, outside: None }
     }
-    else if (min+step..min+(2.0*step)).contains(&val) {
-        ColorName::Stone1
+
+    pub const fn with_blend(mut self, blend: Blend) -> ColorRamp {
+        self.blend = blend;
+        self
     }
-    else if (min+(2.0*step)..min+(3.0*step)).contains(&val) {
-        ColorName::Stone2
+
+    pub const fn with_outside(mut self, outside: ColorName) -> ColorRamp {
+        self.outside = Some(outside);
+        self
     }
-    else if (min+(3.0*step)..min+(4.0*step)).contains(&val) {
-        ColorName::Stone3
+
+    /// How far through the range the value is, from 0.0 at min to 1.0 at
+    /// max. None outside it.
+    fn position(&self, val: f64) -> Option<f64> {
+        let position = (val - self.min) / (self.max - self.min);
+        if (0.0..1.0).contains(&position) { Some(position) } else { None }
     }
-    else if (min+(4.0*step)..min+(5.0*step)).contains(&val) {
-        ColorName::Stone4
+
+    /// The color of the band the value falls in
+    pub fn band(&self, val: f64) -> ColorName {
+        let last = self.colors.len() - 1;
+        match (self.position(val), self.outside) {
+            (Some(position), _) => self.colors[((position * self.colors.len() as f64) as usize).min(last)],
+            (None, Some(outside)) => outside,
+            (None, None) if val < self.min => self.colors[0],
+            (None, None) => self.colors[last],
+        }
     }
-    //else if (min+(5.0*step)..min+(6.0*step)).contains(&val) {
-    //    ColorName::Stone4
-    //}
-    else if (min+(5.0*step)..min+(6.0*step)).contains(&val) {
-        ColorName::Stone5
+
+    /// The two colors the value lies between and how far it is from the
+    /// first to the second, stops spread evenly from min to max
+    pub fn between(&self, val: f64) -> (ColorName, ColorName, f32) {
+        let last = self.colors.len() - 1;
+        let position = match (self.position(val), self.outside) {
+            (Some(position), _) => position,
+            (None, Some(outside)) => return (outside, outside, 0.0),
+            (None, None) if val < self.min => 0.0,
+            (None, None) => 1.0,
+        };
+        let stop = position * last as f64;
+        let index = (stop as usize).min(last);
+        (self.colors[index], self.colors[(index + 1).min(last)], (stop - index as f64) as f32)
     }
-    else {
-        ColorName::Stone6
+
+    /// The color of the value in the scheme, banded or blended between
+    /// stops as the ramp says
+    pub fn color(&self, scheme: &ColorScheme, val: f64) -> Color {
+        self.shaded(scheme, val, 1.0)
+    }
+
+    /// The color of the value darkened by shade, from 1.0 for not at all
+    /// to 0.0 for black, with the scheme's brightness and contrast applied
+    pub fn shaded(&self, scheme: &ColorScheme, val: f64, shade: f32) -> Color {
+        let color = match self.blend {
+            Blend::Banded => scheme.parsed(&self.band(val)),
+            Blend::Smooth => {
+                let (from, to, along) = self.between(val);
+                lerp(scheme.parsed(&from), scheme.parsed(&to), along)
+            },
+        };
+        scheme.adjust.apply(color, shade)
     }
-    
 }
 
-pub fn get_floor_color(val: &f64, min: &f64, max: &f64) -> ColorName {
-    let min = *min;
-    let max = *max;
+/// The color along the way from one color to another, from 0.0 at the
+/// first to 1.0 at the second
+pub fn lerp(from: Color, to: Color, along: f32) -> Color {
+    Color {
+        r: from.r + (to.r - from.r) * along,
+        g: from.g + (to.g - from.g) * along,
+        b: from.b + (to.b - from.b) * along,
+        a: from.a + (to.a - from.a) * along,
+    }
+}
 
-    let step = (max - min) / 8.0;
-    if (min..(min+step)).contains(&val) {
-        ColorName::Stone6
+/// The mean of the colors, for drawing many tiles as one
+pub fn average_color(colors: &[Color]) -> Color {
+    if colors.is_empty() {
+        return Color::BLACK;
     }
-    else if (min+(2.0*step)..min+(3.0*step)).contains(&val) {
-        ColorName::Stone5
+    let count = colors.len() as f32;
+    let sum = colors.iter().fold((0.0, 0.0, 0.0), |sum, color| {
+        (sum.0 + color.r, sum.1 + color.g, sum.2 + color.b)
+    });
+    Color { r: sum.0 / count, g: sum.1 / count, b: sum.2 / count, a: 1.0 }
+}
+
+/// The color scheme presets players can pick from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Palette {
+    Gruvbox,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Palette {
+
+    pub fn name(&self) -> &str {
+        match self {
+            Palette::Gruvbox => "Gruvbox",
+            Palette::Deuteranopia => "Red-green safe",
+            Palette::Tritanopia => "Blue-yellow safe",
+        }
+    }
+
+    /// The preset after this one, for cycling through them in settings
+    pub fn next(&self) -> Palette {
+        match self {
+            Palette::Gruvbox => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::Gruvbox,
+        }
+    }
+}
+
+/// How much darker the map is drawn the deeper the level shown
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DepthShading {
+    Off,
+    /// Darkens evenly all the way down
+    Gentle,
+    /// Darkens quickly below the surface, then levels off
+    Steep,
+}
+
+impl DepthShading {
+
+    pub fn name(&self) -> &str {
+        match self {
+            DepthShading::Off => "Off",
+            DepthShading::Gentle => "Gentle",
+            DepthShading::Steep => "Steep",
+        }
     }
-    else if (min+(3.0*step)..min+(4.0*step)).contains(&val) {
-        ColorName::Stone4
+
+    /// The curve after this one, for cycling through them in settings
+    pub fn next(&self) -> DepthShading {
+        match self {
+            DepthShading::Off => DepthShading::Gentle,
+            DepthShading::Gentle => DepthShading::Steep,
+            DepthShading::Steep => DepthShading::Off,
+        }
     }
-    else if (min+(4.0*step)..min+(5.0*step)).contains(&val) {
-        ColorName::Stone3
+
+    /// How much of its color the level at z keeps out of a map depth
+    /// levels deep, 1.0 at the surface
+    pub fn shade(&self, z: u32, depth: u32) -> f32 {
+        let deep = (z as f32 / depth.max(1) as f32).min(1.0);
+        match self {
+            DepthShading::Off => 1.0,
+            DepthShading::Gentle => 1.0 - 0.4 * deep,
+            DepthShading::Steep => 1.0 - 0.6 * deep.sqrt(),
+        }
     }
-    else if (min+(5.0*step)..min+(6.0*step)).contains(&val) {
-        ColorName::Stone2
+}
+
+/// Brightness and contrast applied to colors as they're looked up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorAdjust {
+    /// Added to each channel, 0.0 leaves colors as they are
+    pub brightness: f32,
+    /// How far channels are pushed from the middle gray, 1.0 leaves colors
+    /// as they are
+    pub contrast: f32,
+}
+
+impl Default for ColorAdjust {
+    fn default() -> ColorAdjust {
+        ColorAdjust { brightness: 0.0, contrast: 1.0 }
     }
-    else {
-        ColorName::Void
+}
+
+impl ColorAdjust {
+
+    /// The color darkened by shade, from 1.0 for not at all to 0.0 for
+    /// black, then adjusted. Alpha is left alone.
+    pub fn apply(&self, color: Color, shade: f32) -> Color {
+        let channel = |value: f32| ((value * shade - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0);
+        Color { r: channel(color.r), g: channel(color.g), b: channel(color.b), a: color.a }
     }
-    
 }
 
+/// Hex codes for every color name. Serializes with the field names so
+/// content packs can replace single colors.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ColorScheme {
     pub bg: String,
     pub fg: String,
@@ -125,10 +296,30 @@ pub struct ColorScheme {
     pub stone4: String,
     pub stone5: String,
     pub stone6: String,
-   
+    /// Set from the player's settings rather than the scheme
+    #[serde(skip)]
+    pub adjust: ColorAdjust,
 }
 
 impl ColorScheme {
+
+    /// The named color with the brightness and contrast applied
+    pub fn color(&self, color_name: &ColorName) -> Color {
+        self.shaded(color_name, 1.0)
+    }
+
+    /// The named color darkened by shade, from 1.0 for not at all to 0.0
+    /// for black, with the brightness and contrast applied
+    pub fn shaded(&self, color_name: &ColorName, shade: f32) -> Color {
+        self.adjust.apply(self.parsed(color_name), shade)
+    }
+
+    /// The named color as the scheme has it, without the brightness and
+    /// contrast
+    pub fn parsed(&self, color_name: &ColorName) -> Color {
+        Color::from_hex(self.get_color_code(color_name))
+    }
+
     pub fn get_color_code(&self, color_name: &ColorName) -> &String {
        match color_name {
            ColorName::Bg => &self.bg,
@@ -164,4 +355,197 @@ impl ColorScheme {
            ColorName::Stone6 => &self.stone6,
        }
     }
+
+    pub fn from_palette(palette: Palette) -> ColorScheme {
+        match palette {
+            Palette::Gruvbox => ColorScheme::gruvbox(),
+            Palette::Deuteranopia => ColorScheme::deuteranopia(),
+            Palette::Tritanopia => ColorScheme::tritanopia(),
+        }
+    }
+
+    pub fn gruvbox() -> ColorScheme {
+        ColorScheme {
+            bg:           String::from("#282828"),
+            fg:           String::from("#ebdbb2"),
+            fg0:          String::from("#fbf1c7"),
+            fg1:          String::from("#ebdbb2"),
+            fg2:          String::from("#d5c4a1"),
+            fg3:          String::from("#bdae93"),
+            fg4:          String::from("#a89984"),
+            gray:         String::from("#a89984"),
+            light_gray:   String::from("#928374"),
+            red:          String::from("#cc241d"),
+            light_red:    String::from("#fb4934"),
+            green:        String::from("#98971a"),
+            light_green:  String::from("#b8bb26"),
+            yellow:       String::from("#d79921"),
+            light_yellow: String::from("#fabd2f"),
+            blue:         String::from("#458588"),
+            light_blue:   String::from("#83a598"),
+            purple:       String::from("#b16286"),
+            light_purple: String::from("#d3869b"),
+            aqua:         String::from("#689d6a"),
+            light_aqua:   String::from("#8ec07c"),
+            orange:       String::from("#d65d0e"),
+            light_orange: String::from("#fe8019"),
+            void:         String::from("#1d2021"),
+            stone0:       String::from("#282828"),
+            stone1:       String::from("#32302f"),
+            stone2:       String::from("#3c3836"),
+            stone3:       String::from("#504945"),
+            stone4:       String::from("#665c54"),
+            stone5:       String::from("#7c6f64"),
+            stone6:       String::from("#928374"),
+            adjust:       ColorAdjust::default(),
+        }
+    }
+
+    /// Gruvbox with its hues swapped for the Okabe-Ito palette, which stays
+    /// distinguishable with red-green color blindness (deuteranopia and
+    /// protanopia). Orange takes the palette's vermillion, red a darker
+    /// shade of it.
+    pub fn deuteranopia() -> ColorScheme {
+        ColorScheme {
+            red:          String::from("#993600"),
+            light_red:    String::from("#f0803c"),
+            green:        String::from("#009e73"),
+            light_green:  String::from("#7fdcc0"),
+            yellow:       String::from("#e69f00"),
+            light_yellow: String::from("#f0e442"),
+            blue:         String::from("#0072b2"),
+            light_blue:   String::from("#56b4e9"),
+            purple:       String::from("#cc79a7"),
+            light_purple: String::from("#e3b0cd"),
+            aqua:         String::from("#88ccee"),
+            light_aqua:   String::from("#b8e2f5"),
+            orange:       String::from("#d55e00"),
+            light_orange: String::from("#ffc14d"),
+            ..ColorScheme::gruvbox()
+        }
+    }
+
+    /// Gruvbox with hues told apart along the red-cyan axis, for blue-yellow
+    /// color blindness (tritanopia)
+    pub fn tritanopia() -> ColorScheme {
+        ColorScheme {
+            red:          String::from("#d81b60"),
+            light_red:    String::from("#f06292"),
+            green:        String::from("#00897b"),
+            light_green:  String::from("#80cbc4"),
+            yellow:       String::from("#ff7043"),
+            light_yellow: String::from("#ffab91"),
+            blue:         String::from("#006064"),
+            light_blue:   String::from("#26c6da"),
+            purple:       String::from("#8e24aa"),
+            light_purple: String::from("#ce93d8"),
+            aqua:         String::from("#4dd0e1"),
+            light_aqua:   String::from("#b2ebf2"),
+            orange:       String::from("#e64a19"),
+            light_orange: String::from("#ff8a65"),
+            ..ColorScheme::gruvbox()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_color() {
+        let average = average_color(&[Color::BLACK, Color::WHITE, Color::RED, Color::RED]);
+        println!("{:?}", average);
+        assert_eq!(average, Color { r: 0.75, g: 0.25, b: 0.25, a: 1.0 });
+        assert_eq!(average_color(&[]), Color::BLACK);
+    }
+
+    #[test]
+    fn test_hues_told_apart() {
+        let hues = [
+            ColorName::Red, ColorName::LightRed, ColorName::Green, ColorName::LightGreen,
+            ColorName::Yellow, ColorName::LightYellow, ColorName::Blue, ColorName::LightBlue,
+            ColorName::Purple, ColorName::LightPurple, ColorName::Aqua, ColorName::LightAqua,
+            ColorName::Orange, ColorName::LightOrange,
+        ];
+        for scheme in [ColorScheme::gruvbox(), ColorScheme::deuteranopia(), ColorScheme::tritanopia()] {
+            let mut codes: Vec<&String> = hues.iter().map(|hue| scheme.get_color_code(hue)).collect();
+            println!("{:?}", codes);
+            codes.sort();
+            codes.dedup();
+            assert_eq!(codes.len(), hues.len());
+        }
+    }
+
+    #[test]
+    fn test_color_ramps() {
+        println!("{:?}", (0..10).map(|val| STONE_RAMP.band(val as f64 / 20.0)).collect::<Vec<_>>());
+        assert_eq!(STONE_RAMP.band(0.0), ColorName::Void);
+        assert_eq!(STONE_RAMP.band(0.1), ColorName::Stone1);
+        assert_eq!(STONE_RAMP.band(0.49), ColorName::Stone6);
+        assert_eq!(STONE_RAMP.band(-0.2), ColorName::Stone6);
+        assert_eq!(STONE_RAMP.band(0.9), ColorName::Stone6);
+
+        // every value in range has a band, there's no gap after the first
+        assert_eq!(FLOOR_RAMP.band(0.5), ColorName::Stone6);
+        assert_eq!(FLOOR_RAMP.band(0.6), ColorName::Stone5);
+        assert_eq!(FLOOR_RAMP.band(0.8), ColorName::Stone2);
+        assert_eq!(FLOOR_RAMP.band(1.2), ColorName::Void);
+
+        let ramp = ColorRamp::new(&[ColorName::Bg, ColorName::Fg], 0.0, 10.0).with_blend(Blend::Smooth);
+        assert_eq!(ramp.between(2.5), (ColorName::Bg, ColorName::Fg, 0.25));
+        assert_eq!(ramp.between(-1.0), (ColorName::Bg, ColorName::Fg, 0.0));
+        assert_eq!(ramp.between(12.0), (ColorName::Fg, ColorName::Fg, 0.0));
+        let mut scheme = ColorScheme::gruvbox();
+        scheme.bg = String::from("000000");
+        scheme.fg = String::from("ffffff");
+        let gray = ramp.color(&scheme, 5.0);
+        assert!((gray.r - 0.5).abs() < 0.01 && gray.r == gray.g && gray.g == gray.b);
+        assert_eq!(ramp.with_blend(Blend::Banded).color(&scheme, 4.0), Color::from_hex("000000"));
+    }
+
+    #[test]
+    fn test_smooth_ramps_blend_between_stops() {
+        assert_eq!(lerp(Color::BLACK, Color::WHITE, 0.0), Color::BLACK);
+        assert_eq!(lerp(Color::BLACK, Color::WHITE, 1.0), Color::WHITE);
+        assert_eq!(lerp(Color::RED, Color::BLUE, 0.5), Color { r: 0.5, g: 0.0, b: 0.5, a: 1.0 });
+
+        let mut scheme = ColorScheme::gruvbox();
+        scheme.void = String::from("000000");
+        scheme.stone1 = String::from("404040");
+        let smooth = STONE_RAMP.with_blend(Blend::Smooth);
+        // a twelfth of the way up is halfway from the first stop to the second
+        let between = smooth.color(&scheme, 0.5 / 12.0);
+        println!("{:?}", between);
+        assert!((between.r - 0.125).abs() < 0.01);
+        // banded, the whole first band is the first color
+        assert_eq!(STONE_RAMP.color(&scheme, 0.5 / 12.0), Color::from_hex("000000"));
+        // darkened and adjusted like any other color
+        scheme.adjust.brightness = 0.5;
+        assert!((smooth.shaded(&scheme, 0.5 / 12.0, 0.0).r - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_colors_adjusted_as_looked_up() {
+        let gray = Color { r: 0.25, g: 0.5, b: 0.75, a: 0.5 };
+        assert_eq!(ColorAdjust::default().apply(gray, 1.0), gray);
+        let brighter = ColorAdjust { brightness: 0.25, contrast: 1.0 }.apply(gray, 1.0);
+        println!("{:?}", brighter);
+        assert_eq!(brighter, Color { r: 0.5, g: 0.75, b: 1.0, a: 0.5 });
+        let flat = ColorAdjust { brightness: 0.0, contrast: 0.0 }.apply(gray, 1.0);
+        assert_eq!((flat.r, flat.g, flat.b), (0.5, 0.5, 0.5));
+        assert_eq!(ColorAdjust::default().apply(gray, 0.5).g, 0.25);
+
+        // deeper levels keep less of their color, except with shading off
+        let depth = 300;
+        assert_eq!(DepthShading::Gentle.shade(0, depth), 1.0);
+        assert!(DepthShading::Gentle.shade(150, depth) > DepthShading::Gentle.shade(300, depth));
+        assert!(DepthShading::Steep.shade(30, depth) < DepthShading::Gentle.shade(30, depth));
+        assert_eq!(DepthShading::Off.shade(300, depth), 1.0);
+
+        let mut scheme = ColorScheme::gruvbox();
+        scheme.fg = String::from("808080");
+        scheme.adjust.brightness = -1.0;
+        assert_eq!(scheme.color(&ColorName::Fg), Color::BLACK);
+    }
 }
@@ -0,0 +1,298 @@
+use quicksilver::prelude::*;
+
+use crate::economy::Deal;
+use crate::locale::Locale;
+use crate::net::Named;
+use crate::rail::{Direction, Track};
+use crate::selection::{AreaCommand, Selection};
+use crate::text_input::{Filter, TextInput};
+
+/// Longest name a colonist or stockpile can be given
+pub const MAX_NAME_LEN: usize = 24;
+/// Longest note that can be left on a tile
+pub const MAX_NOTE_LEN: usize = 48;
+
+/// A page of hotkeys in the command bar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Menu {
+    Main,
+    Dig,
+    Build,
+    Stockpile,
+    Burrow,
+    Livestock,
+    Farm,
+    Blueprint,
+    View,
+}
+
+/// What pressing the key of a menu entry does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Open(Menu),
+    Area(AreaCommand),
+    BuildPillar,
+    /// Puts up or takes down a pump in front of the player
+    BuildPump,
+    /// Lays a stop or switch in front of the player
+    BuildTrack(Track),
+    PlaceMinecart,
+    CycleOverlay,
+    ToggleFollow,
+    /// Shows or hides the UI component by its name
+    Toggle(&'static str),
+    /// Sends the player off to explore on their own
+    Explore,
+    NameColonist,
+    /// Names the stockpile the player stands in
+    NameStockpile,
+    /// Keeps the player's colonist to the burrow or lets them go
+    KeepToBurrow,
+    /// Puts the colony on alert or stands it down
+    ToggleAlert,
+    /// Picks up the tool or armor next to the player
+    PickUp,
+    /// Opens the trade screen while a caravan is trading
+    Trade,
+    /// Opens the colony's history
+    Chronicle,
+    /// Opens the list of notes left on the map
+    Notes,
+    /// Turns the blueprint a quarter clockwise
+    RotateBlueprint,
+    /// Opens the list of the colony's rooms
+    Rooms,
+}
+
+pub struct MenuEntry {
+    pub key: Key,
+    /// The key of what it does in the string bundles
+    pub hint: &'static str,
+    pub command: Command,
+}
+
+impl MenuEntry {
+
+    fn new(key: Key, hint: &'static str, command: Command) -> MenuEntry {
+        MenuEntry { key, hint, command }
+    }
+}
+
+impl Menu {
+
+    /// The key of its title in the string bundles
+    pub fn title(&self) -> &'static str {
+        match self {
+            Menu::Main => "menu.main",
+            Menu::Dig => "menu.dig",
+            Menu::Build => "menu.build",
+            Menu::Stockpile => "menu.stockpile",
+            Menu::Burrow => "menu.burrow",
+            Menu::Livestock => "menu.livestock",
+            Menu::Farm => "menu.farm",
+            Menu::Blueprint => "menu.blueprint",
+            Menu::View => "menu.view",
+        }
+    }
+
+    pub fn entries(&self) -> Vec<MenuEntry> {
+        use Command::*;
+        match self {
+            Menu::Main => vec![
+                MenuEntry::new(Key::D, "hint.main.dig", Open(Menu::Dig)),
+                MenuEntry::new(Key::B, "hint.main.build", Open(Menu::Build)),
+                MenuEntry::new(Key::P, "hint.main.stockpile", Open(Menu::Stockpile)),
+                MenuEntry::new(Key::U, "hint.main.burrow", Open(Menu::Burrow)),
+                MenuEntry::new(Key::L, "hint.main.livestock", Open(Menu::Livestock)),
+                MenuEntry::new(Key::F, "hint.main.farm", Open(Menu::Farm)),
+                MenuEntry::new(Key::M, "hint.main.blueprint", Open(Menu::Blueprint)),
+                MenuEntry::new(Key::V, "hint.main.view", Open(Menu::View)),
+                MenuEntry::new(Key::T, "hint.main.travel", Area(AreaCommand::Travel)),
+                MenuEntry::new(Key::E, "hint.main.explore", Explore),
+                MenuEntry::new(Key::N, "hint.main.name", NameColonist),
+                MenuEntry::new(Key::A, "hint.main.alert", ToggleAlert),
+                MenuEntry::new(Key::G, "hint.main.pick_up", PickUp),
+                MenuEntry::new(Key::R, "hint.main.rally", Area(AreaCommand::Rally)),
+                MenuEntry::new(Key::C, "hint.main.trade", Trade),
+            ],
+            Menu::Dig => vec![
+                MenuEntry::new(Key::D, "hint.dig.designate", Area(AreaCommand::Dig)),
+                MenuEntry::new(Key::X, "hint.dig.cancel", Area(AreaCommand::Cancel)),
+            ],
+            Menu::Build => vec![
+                MenuEntry::new(Key::P, "hint.build.pillar", BuildPillar),
+                MenuEntry::new(Key::U, "hint.build.pump", BuildPump),
+                MenuEntry::new(Key::T, "hint.build.track", Area(AreaCommand::Track)),
+                MenuEntry::new(Key::S, "hint.build.stop", BuildTrack(Track::Stop)),
+                MenuEntry::new(Key::J, "hint.build.switch", BuildTrack(Track::Switch(Direction::North))),
+                MenuEntry::new(Key::C, "hint.build.minecart", PlaceMinecart),
+            ],
+            Menu::Stockpile => vec![
+                MenuEntry::new(Key::P, "hint.stockpile.place", Area(AreaCommand::Stockpile)),
+                MenuEntry::new(Key::R, "hint.stockpile.refuse", Area(AreaCommand::Refuse)),
+                MenuEntry::new(Key::X, "hint.stockpile.remove", Area(AreaCommand::Cancel)),
+                MenuEntry::new(Key::N, "hint.stockpile.name", NameStockpile),
+            ],
+            Menu::Burrow => vec![
+                MenuEntry::new(Key::A, "hint.burrow.add", Area(AreaCommand::Burrow)),
+                MenuEntry::new(Key::X, "hint.burrow.remove", Area(AreaCommand::Unburrow)),
+                MenuEntry::new(Key::K, "hint.burrow.keep", KeepToBurrow),
+            ],
+            Menu::Livestock => vec![
+                MenuEntry::new(Key::P, "hint.livestock.pen", Area(AreaCommand::Pen)),
+                MenuEntry::new(Key::X, "hint.livestock.remove", Area(AreaCommand::Unpen)),
+                MenuEntry::new(Key::C, "hint.livestock.capture", Area(AreaCommand::Capture)),
+            ],
+            Menu::Farm => vec![
+                MenuEntry::new(Key::P, "hint.farm.plot", Area(AreaCommand::Farm)),
+                MenuEntry::new(Key::X, "hint.farm.remove", Area(AreaCommand::Unfarm)),
+            ],
+            Menu::Blueprint => vec![
+                MenuEntry::new(Key::C, "hint.blueprint.copy", Area(AreaCommand::Copy)),
+                MenuEntry::new(Key::S, "hint.blueprint.stamp", Area(AreaCommand::Stamp)),
+                MenuEntry::new(Key::R, "hint.blueprint.rotate", RotateBlueprint),
+            ],
+            Menu::View => vec![
+                MenuEntry::new(Key::I, "hint.view.inspect", Area(AreaCommand::Inspect)),
+                MenuEntry::new(Key::O, "hint.view.overlay", CycleOverlay),
+                MenuEntry::new(Key::F, "hint.view.follow", ToggleFollow),
+                MenuEntry::new(Key::M, "hint.view.map", Toggle("map")),
+                MenuEntry::new(Key::T, "hint.view.title", Toggle("title")),
+                MenuEntry::new(Key::C, "hint.view.credits", Toggle("credits")),
+                MenuEntry::new(Key::B, "hint.view.debug", Toggle("debug")),
+                MenuEntry::new(Key::D, "hint.view.details", Toggle("details")),
+                MenuEntry::new(Key::K, "hint.view.clock", Toggle("clock")),
+                MenuEntry::new(Key::S, "hint.view.stockpiled", Toggle("resources")),
+                MenuEntry::new(Key::H, "hint.view.history", Chronicle),
+                MenuEntry::new(Key::N, "hint.view.notes", Notes),
+                MenuEntry::new(Key::R, "hint.view.rooms", Rooms),
+            ],
+        }
+    }
+
+    /// The command bound to the first key pressed this frame, if any
+    pub fn pressed(&self, window: &Window) -> Option<Command> {
+        self.entries().into_iter()
+            .find(|entry| window.keyboard()[entry.key] == ButtonState::Pressed)
+            .map(|entry| entry.command)
+    }
+}
+
+/// One layer of the mode stack, the topmost mode gets the input and
+/// Escape goes back to the one below it
+pub enum Mode {
+    Menu(Menu),
+    Select(Selection),
+    Console,
+    Name(Naming),
+    Trade(Trading),
+    Chronicle(Reading),
+    Notes(Reading),
+    Rooms(Reading),
+}
+
+/// A name being typed for a colonist or stockpile, or a note for a tile
+pub struct Naming {
+    pub target: Named,
+    pub input: TextInput,
+}
+
+impl Naming {
+
+    /// Starts off with the name it has now. Notes can say anything
+    /// printable, at more length.
+    pub fn new(target: Named, current: &str) -> Naming {
+        let input = match target {
+            Named::Note { .. } => TextInput::new(Filter::Any, MAX_NOTE_LEN),
+            Named::Colonist(_) | Named::Stockpile { .. } => TextInput::new(Filter::Name, MAX_NAME_LEN),
+        };
+        Naming { target, input: input.with_text(current) }
+    }
+}
+
+/// Goods being picked out to trade with the caravan
+pub struct Trading {
+    pub deal: Deal,
+    /// Row the cursor is on, the caravan's goods first then the colony's
+    pub cursor: usize,
+}
+
+impl Trading {
+
+    pub fn new() -> Trading {
+        Trading { deal: Deal::default(), cursor: 0 }
+    }
+
+    /// Moves the cursor dy rows, staying on the rows there are
+    pub fn move_cursor(&mut self, dy: i32, rows: usize) {
+        let last = rows.saturating_sub(1) as i64;
+        self.cursor = (self.cursor as i64 + dy as i64).clamp(0, last) as usize;
+    }
+}
+
+/// The colony's history being read, newest entry at the bottom
+pub struct Reading {
+    /// Entry the cursor is on
+    pub cursor: usize,
+}
+
+impl Reading {
+
+    /// Starts on the newest of the entries
+    pub fn new(entries: usize) -> Reading {
+        Reading { cursor: entries.saturating_sub(1) }
+    }
+
+    /// Moves the cursor dy entries, staying on the entries there are
+    pub fn move_cursor(&mut self, dy: i32, entries: usize) {
+        let last = entries.saturating_sub(1) as i64;
+        self.cursor = (self.cursor as i64 + dy as i64).clamp(0, last) as usize;
+    }
+}
+
+impl Mode {
+
+    /// Keys shown in the command bar while this mode is active
+    pub fn hints(&self, locale: &Locale) -> String {
+        match self {
+            Mode::Menu(menu) => {
+                let hints: Vec<String> = menu.entries().iter()
+                    .map(|entry| format!("{}: {}", format!("{:?}", entry.key).to_lowercase(), locale.get(entry.hint)))
+                    .collect();
+                locale.format("mode.menu", &[("title", &locale.get(menu.title())), ("hints", &hints.join("  "))])
+            },
+            Mode::Select(selection) => locale.format("mode.select", &[("command", &selection.command.name())]),
+            Mode::Console => String::from(locale.get("mode.console")),
+            Mode::Name(naming) => {
+                let what = match naming.target {
+                    Named::Colonist(_) => "mode.name_colonist",
+                    Named::Stockpile { .. } => "mode.name_stockpile",
+                    Named::Note { .. } => "mode.note",
+                };
+                locale.format("mode.name", &[("what", &locale.get(what)), ("text", &naming.input.display())])
+            },
+            Mode::Trade(_) => String::from(locale.get("mode.trade")),
+            Mode::Chronicle(_) => String::from(locale.get("mode.history")),
+            Mode::Notes(_) => String::from(locale.get("mode.notes")),
+            Mode::Rooms(_) => String::from(locale.get("mode.rooms")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menu_keys_are_unique() {
+        let english = Locale::english();
+        for menu in &[Menu::Main, Menu::Dig, Menu::Build, Menu::Stockpile, Menu::Livestock, Menu::Farm, Menu::Blueprint, Menu::View] {
+            let entries = menu.entries();
+            for (index, entry) in entries.iter().enumerate() {
+                println!("{:?} {}", menu, english.get(entry.hint));
+                assert_ne!(english.get(entry.hint), entry.hint);
+                assert!(entries[index + 1..].iter().all(|other| other.key != entry.key));
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use quicksilver::saving::{self, SaveError};
+use serde::{Deserialize, Serialize};
+
+use crate::bindings::{KeyboardLayout, MovementKeys};
+use crate::color_scheme::{ColorAdjust, DepthShading, Palette};
+use crate::locale;
+use crate::save::APP_NAME;
+
+const CONFIG_PROFILE: &str = "config";
+
+/// Player settings, kept in their own file so they survive starting a new
+/// colony. Fields missing from an older file get their default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// From 0.0 (off) to 1.0
+    pub music_volume: f32,
+    /// From 0.0 (off) to 1.0
+    pub effects_volume: f32,
+    pub palette: Palette,
+    /// Marks things told apart by color with a distinct glyph as well
+    pub shape_cues: bool,
+    /// Multiplies the size of tiles, text and panels, from 1.0 to 3.0
+    pub ui_scale: f32,
+    /// Manage the colony with a free camera instead of steering a
+    /// colonist, the game starts in whichever was used last
+    pub overseer: bool,
+    pub movement_keys: MovementKeys,
+    /// Where the keys are, the bindings follow them around
+    pub keyboard_layout: KeyboardLayout,
+    /// The code of the language the UI is in, like en or fr
+    pub language: String,
+    /// The camera glides to a stop after the arrows are let go of rather
+    /// than stopping at once
+    pub camera_glide: bool,
+    /// Generated chunks are kept on disk and read back rather than
+    /// generated again, from the next world that's loaded
+    pub cache_chunks: bool,
+    /// Added to every color of the map, from -0.3 to 0.3
+    pub brightness: f32,
+    /// From 0.5 to 1.5, 1.0 leaves colors as they are
+    pub contrast: f32,
+    /// How much darker deeper levels are drawn
+    pub depth_shading: DepthShading,
+    /// Rock and cave floors are shaded smoothly by the noise they were
+    /// generated from rather than in bands of a few colors
+    pub smooth_terrain: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            music_volume: 0.5,
+            effects_volume: 0.75,
+            palette: Palette::Gruvbox,
+            shape_cues: false,
+            ui_scale: 1.0,
+            overseer: false,
+            movement_keys: MovementKeys::Wasd,
+            keyboard_layout: KeyboardLayout::Qwerty,
+            language: String::from(locale::DEFAULT_LANGUAGE),
+            camera_glide: false,
+            cache_chunks: false,
+            brightness: 0.0,
+            contrast: 1.0,
+            depth_shading: DepthShading::Off,
+            smooth_terrain: false,
+        }
+    }
+}
+
+impl Config {
+
+    /// Reads the config file, falling back to the defaults if there is
+    /// none yet or it can't be read
+    pub fn load() -> Config {
+        saving::load(APP_NAME, CONFIG_PROFILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), SaveError> {
+        saving::save(APP_NAME, CONFIG_PROFILE, self)
+    }
+
+    pub fn color_adjust(&self) -> ColorAdjust {
+        ColorAdjust { brightness: self.brightness, contrast: self.contrast }
+    }
+}
+
+/// Steps a volume up by a quarter, wrapping around to off after full
+pub fn next_volume(volume: f32) -> f32 {
+    if volume >= 1.0 {
+        0.0
+    } else {
+        ((volume * 4.0).floor() + 1.0) / 4.0
+    }
+}
+
+/// Steps the brightness up by a tenth, wrapping around to the darkest
+/// after the brightest
+pub fn next_brightness(brightness: f32) -> f32 {
+    if brightness >= 0.3 - f32::EPSILON {
+        -0.3
+    } else {
+        ((brightness * 10.0).round() + 1.0) / 10.0
+    }
+}
+
+/// Steps the contrast up by a quarter, wrapping around to the flattest
+/// after the sharpest
+pub fn next_contrast(contrast: f32) -> f32 {
+    if contrast >= 1.5 {
+        0.5
+    } else {
+        ((contrast * 4.0).floor() + 1.0) / 4.0
+    }
+}
+
+/// Steps the UI scale up by a half, wrapping around to 1x after 3x
+pub fn next_ui_scale(scale: f32) -> f32 {
+    if scale >= 3.0 {
+        1.0
+    } else {
+        ((scale * 2.0).floor() + 1.0) / 2.0
+    }
+}
@@ -0,0 +1,135 @@
+use quicksilver::prelude::*;
+
+use crate::color_scheme::ColorScheme;
+use crate::layer::RenderLayer;
+use crate::text_input::{Filter, TextInput};
+use crate::text_layout::{TextLayout, TextStyle};
+
+/// How many lines of output are kept
+const SCROLLBACK: usize = 200;
+/// How many lines of output are drawn above the input line
+const VISIBLE_LINES: usize = 8;
+/// Longest command line that can be typed
+const MAX_INPUT: usize = 200;
+
+/// A debug console, text typed into it is run as a command by the game
+/// when Return is pressed
+pub struct Console {
+    pub input: TextInput,
+    output: Vec<String>,
+}
+
+impl Console {
+
+    pub fn new() -> Console {
+        Console {
+            input: TextInput::new(Filter::Any, MAX_INPUT),
+            output: Vec::new(),
+        }
+    }
+
+    /// Adds a line to the output
+    pub fn print(&mut self, line: &str) {
+        println!("{}", line);
+        self.output.push(String::from(line));
+        if self.output.len() > SCROLLBACK {
+            self.output.remove(0);
+        }
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        // the key that opens the console
+        if c != '`' {
+            self.input.type_char(c);
+        }
+    }
+
+    /// Clears the input line and returns what was on it, also echoing it
+    /// to the output
+    pub fn take_input(&mut self) -> String {
+        let line = self.input.take();
+        self.print(&format!("> {}", line));
+        line
+    }
+
+    /// Draws the console across the top of the screen, sized by the UI
+    /// scale. Output too long for the screen wraps, and the last lines of
+    /// it that fit are shown.
+    pub fn draw(&self, window: &mut Window, font: &mut Font, text_layout: &TextLayout,
+                color_scheme: &ColorScheme, scale: f32) -> Result<()> {
+
+        let line_height = 22.0 * scale;
+        let padding = 6.0 * scale;
+        let style = TextStyle::new(18.0 * scale, Color::from_hex(&color_scheme.fg));
+        let input_style = FontStyle::new(
+            18.0 * scale, Color::from_hex(&color_scheme.light_yellow));
+
+        let height = (VISIBLE_LINES + 1) as f32 * line_height + padding * 2.0;
+        let area = Rectangle::new((0.0, 0.0), (window.screen_size().x, height));
+        window.draw_ex(
+            &area,
+            Col(Color::from_hex(&color_scheme.bg).with_alpha(0.9)),
+            Transform::IDENTITY,
+            RenderLayer::UiTop.z()
+        );
+
+        let width = window.screen_size().x - padding * 2.0;
+        let first = self.output.len().saturating_sub(VISIBLE_LINES);
+        let rows: Vec<String> = self.output[first..].iter()
+            .flat_map(|line| text_layout.wrap(line, style.size, width))
+            .collect();
+        let mut lines = Vec::new();
+        for line in &rows[rows.len().saturating_sub(VISIBLE_LINES)..] {
+            // rendering an empty string fails
+            let text = if line.is_empty() { " " } else { line.as_str() };
+            lines.push((lines.len(), font.render(text, &style.font_style())?));
+        }
+        let input = font.render(&format!("> {}", self.input.display()), &input_style)?;
+        lines.push((VISIBLE_LINES, input));
+
+        for (row, line) in lines.iter() {
+            let pos = Vector::new(padding, padding + *row as f32 * line_height);
+            window.draw_ex(
+                &line.area().translate(pos),
+                Img(line),
+                Transform::IDENTITY,
+                RenderLayer::UiTop.over()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a command line into the command name and its arguments
+pub fn parse(line: &str) -> (String, Vec<String>) {
+    let mut words = line.split_whitespace().map(String::from);
+    let command = words.next().unwrap_or_default();
+    (command, words.collect())
+}
+
+/// Parses the argument at index as a number, with a message naming the
+/// argument if it is missing or malformed
+pub fn arg<T: std::str::FromStr>(args: &[String], index: usize, name: &str)
+    -> std::result::Result<T, String> {
+    args.get(index)
+        .ok_or_else(|| format!("missing argument: {}", name))?
+        .parse()
+        .map_err(|_| format!("{} should be a number, got {:?}", name, args[index]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let (command, args) = parse("  export_png 10 20   5 5 ");
+        println!("{:?} {:?}", command, args);
+        assert_eq!(command, "export_png");
+        assert_eq!(args, vec!["10", "20", "5", "5"]);
+        assert_eq!(arg::<u32>(&args, 1, "y"), Ok(20));
+        assert!(arg::<u32>(&args, 4, "z").is_err());
+        assert_eq!(parse("").0, "");
+    }
+}
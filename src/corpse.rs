@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color_scheme::ColorName;
+
+/// Ticks a corpse lies before it has rotted away
+pub const DECAY_TICKS: u64 = 9000;
+/// Ticks a corpse lies outside a refuse zone before scavengers smell it
+pub const SCAVENGE_AFTER: u64 = 2000;
+
+/// What's left of something that died, shown by its entity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Corpse {
+    /// Tick it died, or was first found dead
+    pub died: u64,
+}
+
+impl Corpse {
+
+    pub fn new(died: u64) -> Corpse {
+        Corpse { died }
+    }
+
+    pub fn age(&self, tick: u64) -> u64 {
+        tick.saturating_sub(self.died)
+    }
+
+    /// Past half way to rotting away, it's drawn that way
+    pub fn is_rotting(&self, tick: u64) -> bool {
+        self.age(tick) >= DECAY_TICKS / 2
+    }
+
+    pub fn is_rotten(&self, tick: u64) -> bool {
+        self.age(tick) >= DECAY_TICKS
+    }
+
+    /// Whether it's lain about long enough to draw scavengers, if it's
+    /// not in a refuse zone
+    pub fn smells(&self, tick: u64) -> bool {
+        self.age(tick) >= SCAVENGE_AFTER
+    }
+
+    /// The color it's drawn in at tick, None while it's fresh
+    pub fn color(&self, tick: u64) -> Option<ColorName> {
+        Some(ColorName::Green).filter(|_| self.is_rotting(tick))
+    }
+}
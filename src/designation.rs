@@ -0,0 +1,439 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blueprint::Plan;
+use crate::game_map::{GameMap, TileKind};
+use crate::selection::Area;
+
+/// A stockpile taken off the map, with its name if it had one
+pub type NamedStockpile = (Area, Option<String>);
+
+/// Work the player has marked on the map for the colony
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Designations {
+    dig: BTreeSet<(u32, u32, u32)>,
+    stockpiles: Vec<Area>,
+    /// Names players gave stockpiles, dropped along with the stockpile
+    #[serde(default)]
+    stockpile_names: Vec<(Area, String)>,
+    /// The areas making up the burrow colonists can be kept to
+    #[serde(default)]
+    burrow: Vec<Area>,
+    /// Where colonists retreat to when the colony is on alert
+    #[serde(default)]
+    rally: Option<(u32, u32, u32)>,
+    /// Where caught creatures are kept
+    #[serde(default)]
+    pens: Vec<Area>,
+    /// Where the dead are carried to rot away
+    #[serde(default)]
+    refuse: Vec<Area>,
+    /// Where crops are planted
+    #[serde(default)]
+    farms: Vec<Area>,
+    /// Notes players left on tiles, for planning, in the order they were
+    /// left
+    #[serde(default)]
+    notes: Vec<((u32, u32, u32), String)>,
+    /// Constructions stamped from blueprints, waiting for a colonist with
+    /// a hammer
+    #[serde(default)]
+    builds: BTreeMap<(u32, u32, u32), Plan>,
+}
+
+impl Designations {
+
+    pub fn new() -> Designations {
+        Designations {
+            dig: BTreeSet::new(),
+            stockpiles: Vec::new(),
+            stockpile_names: Vec::new(),
+            burrow: Vec::new(),
+            rally: None,
+            pens: Vec::new(),
+            refuse: Vec::new(),
+            farms: Vec::new(),
+            notes: Vec::new(),
+            builds: BTreeMap::new(),
+        }
+    }
+
+    /// Marks every diggable tile in the area, returns the tiles that
+    /// weren't marked already
+    pub fn designate_dig(&mut self, map: &mut GameMap, area: Area) -> Vec<(u32, u32, u32)> {
+        let tiles: Vec<_> = area.tiles().collect();
+        self.mark_dig(map, &tiles)
+    }
+
+    /// Marks the tiles that can still be dug, returns the ones newly marked
+    pub fn mark_dig(&mut self, map: &mut GameMap, tiles: &[(u32, u32, u32)])
+        -> Vec<(u32, u32, u32)> {
+        let mut marked = Vec::new();
+        for &(x, y, z) in tiles {
+            let tile = map.get_tile(x, y, z);
+            if tile.is_solid() && tile.kind != TileKind::Pillar && self.dig.insert((x, y, z)) {
+                marked.push((x, y, z));
+            }
+        }
+        marked
+    }
+
+    pub fn unmark_dig(&mut self, tiles: &[(u32, u32, u32)]) {
+        for tile in tiles {
+            self.dig.remove(tile);
+        }
+    }
+
+    /// Turns the area into a stockpile, replacing any it overlaps. Returns
+    /// the stockpiles replaced, with their names.
+    pub fn add_stockpile(&mut self, area: Area) -> Vec<NamedStockpile> {
+        let removed = self.take_stockpiles(|stockpile| stockpile.intersects(&area));
+        self.stockpiles.push(area);
+        removed
+    }
+
+    /// Removes dig designations in the area and stockpiles touching it,
+    /// returns the tiles unmarked and the stockpiles removed
+    pub fn cancel(&mut self, area: Area) -> (Vec<(u32, u32, u32)>, Vec<NamedStockpile>) {
+        let unmarked: Vec<_> = self.dig.iter()
+            .filter(|&&(x, y, z)| area.contains(x, y, z))
+            .cloned()
+            .collect();
+        self.unmark_dig(&unmarked);
+        (unmarked, self.take_stockpiles(|stockpile| stockpile.intersects(&area)))
+    }
+
+    /// Puts back a stockpile that was removed, replacing any it overlaps
+    pub fn restore_stockpile(&mut self, area: Area, name: Option<String>) {
+        self.add_stockpile(area);
+        if let Some(name) = name {
+            self.stockpile_names.push((area, name));
+        }
+    }
+
+    pub fn remove_stockpile(&mut self, area: Area) {
+        self.take_stockpiles(|stockpile| *stockpile == area);
+    }
+
+    /// Removes the stockpiles matching, along with their names
+    fn take_stockpiles(&mut self, matching: impl Fn(&Area) -> bool)
+        -> Vec<NamedStockpile> {
+        let (taken, kept) = self.stockpiles.iter().partition(|stockpile| matching(stockpile));
+        self.stockpiles = kept;
+        let taken: Vec<NamedStockpile> = taken.into_iter()
+            .map(|stockpile: Area| (stockpile, self.stockpile_name(stockpile).map(String::from)))
+            .collect();
+        self.stockpile_names.retain(|(area, _)| !matching(area));
+        taken
+    }
+
+    /// Adds the area to the burrow, false if it's already part of it
+    pub fn add_burrow(&mut self, area: Area) -> bool {
+        if self.burrow.contains(&area) {
+            return false;
+        }
+        self.burrow.push(area);
+        true
+    }
+
+    /// Takes the areas touching area out of the burrow, returns them
+    pub fn cut_burrow(&mut self, area: Area) -> Vec<Area> {
+        let (cut, kept) = self.burrow.iter().partition(|burrow| burrow.intersects(&area));
+        self.burrow = kept;
+        cut
+    }
+
+    pub fn remove_burrow(&mut self, area: Area) {
+        self.burrow.retain(|burrow| *burrow != area);
+    }
+
+    pub fn in_burrow(&self, x: u32, y: u32, z: u32) -> bool {
+        self.burrow.iter().any(|burrow| burrow.contains(x, y, z))
+    }
+
+    /// The areas of the burrow on level z
+    pub fn burrow_on(&self, z: u32) -> Vec<Area> {
+        self.burrow.iter().filter(|burrow| burrow.z == z).cloned().collect()
+    }
+
+    /// Turns the area into a pen, replacing any it overlaps. Returns the
+    /// pens replaced.
+    pub fn add_pen(&mut self, area: Area) -> Vec<Area> {
+        let removed = self.cut_pens(area);
+        self.pens.push(area);
+        removed
+    }
+
+    /// Takes the pens touching area off the map, returns them
+    pub fn cut_pens(&mut self, area: Area) -> Vec<Area> {
+        let (cut, kept) = self.pens.iter().partition(|pen| pen.intersects(&area));
+        self.pens = kept;
+        cut
+    }
+
+    pub fn remove_pen(&mut self, area: Area) {
+        self.pens.retain(|pen| *pen != area);
+    }
+
+    pub fn is_pen(&self, x: u32, y: u32, z: u32) -> bool {
+        self.pen_at(x, y, z).is_some()
+    }
+
+    pub fn pen_at(&self, x: u32, y: u32, z: u32) -> Option<Area> {
+        self.pens.iter().find(|pen| pen.contains(x, y, z)).cloned()
+    }
+
+    /// The pens on level z
+    pub fn pens_on(&self, z: u32) -> Vec<Area> {
+        self.pens.iter().filter(|pen| pen.z == z).cloned().collect()
+    }
+
+    /// Turns the area into a refuse zone, replacing any it overlaps.
+    /// Returns the zones replaced.
+    pub fn add_refuse(&mut self, area: Area) -> Vec<Area> {
+        let removed = self.cut_refuse(area);
+        self.refuse.push(area);
+        removed
+    }
+
+    /// Takes the refuse zones touching area off the map, returns them
+    pub fn cut_refuse(&mut self, area: Area) -> Vec<Area> {
+        let (cut, kept) = self.refuse.iter().partition(|zone| zone.intersects(&area));
+        self.refuse = kept;
+        cut
+    }
+
+    pub fn remove_refuse(&mut self, area: Area) {
+        self.refuse.retain(|zone| *zone != area);
+    }
+
+    pub fn is_refuse(&self, x: u32, y: u32, z: u32) -> bool {
+        self.refuse.iter().any(|zone| zone.contains(x, y, z))
+    }
+
+    /// The refuse zones on level z
+    pub fn refuse_on(&self, z: u32) -> Vec<Area> {
+        self.refuse.iter().filter(|zone| zone.z == z).cloned().collect()
+    }
+
+    /// Turns the area into a farm plot, replacing any it overlaps. Returns
+    /// the plots replaced.
+    pub fn add_farm(&mut self, area: Area) -> Vec<Area> {
+        let removed = self.cut_farms(area);
+        self.farms.push(area);
+        removed
+    }
+
+    /// Takes the farm plots touching area off the map, returns them
+    pub fn cut_farms(&mut self, area: Area) -> Vec<Area> {
+        let (cut, kept) = self.farms.iter().partition(|farm| farm.intersects(&area));
+        self.farms = kept;
+        cut
+    }
+
+    pub fn remove_farm(&mut self, area: Area) {
+        self.farms.retain(|farm| *farm != area);
+    }
+
+    pub fn is_farm(&self, x: u32, y: u32, z: u32) -> bool {
+        self.farms.iter().any(|farm| farm.contains(x, y, z))
+    }
+
+    /// The farm plots on level z
+    pub fn farms_on(&self, z: u32) -> Vec<Area> {
+        self.farms.iter().filter(|farm| farm.z == z).cloned().collect()
+    }
+
+    /// Plans the construction on (x, y, z), returning the one it replaced
+    pub fn plan_build(&mut self, x: u32, y: u32, z: u32, plan: Plan) -> Option<Plan> {
+        self.builds.insert((x, y, z), plan)
+    }
+
+    /// Call once the planned construction has been built, or can't be
+    pub fn finish_build(&mut self, x: u32, y: u32, z: u32) {
+        self.builds.remove(&(x, y, z));
+    }
+
+    pub fn build_at(&self, x: u32, y: u32, z: u32) -> Option<Plan> {
+        self.builds.get(&(x, y, z)).copied()
+    }
+
+    /// The constructions planned on level z
+    pub fn builds_on(&self, z: u32) -> Vec<((u32, u32), Plan)> {
+        self.builds.iter()
+            .filter(|(&(_, _, bz), _)| bz == z)
+            .map(|(&(x, y, _), plan)| ((x, y), *plan))
+            .collect()
+    }
+
+    /// Takes the constructions planned in the area off the map, returning
+    /// them
+    pub fn cut_builds(&mut self, area: Area) -> Vec<((u32, u32, u32), Plan)> {
+        let cut: Vec<_> = self.builds.iter()
+            .filter(|(&(x, y, z), _)| area.contains(x, y, z))
+            .map(|(tile, plan)| (*tile, *plan))
+            .collect();
+        for (tile, _) in &cut {
+            self.builds.remove(tile);
+        }
+        cut
+    }
+
+    pub fn rally_point(&self) -> Option<(u32, u32, u32)> {
+        self.rally
+    }
+
+    /// Moves the rally point to (x, y, z), there is only the one
+    pub fn set_rally_point(&mut self, x: u32, y: u32, z: u32) {
+        self.rally = Some((x, y, z));
+    }
+
+    /// Call when the tile has been dug out, by whoever did it
+    pub fn finish_dig(&mut self, x: u32, y: u32, z: u32) {
+        self.dig.remove(&(x, y, z));
+    }
+
+    /// Every tile marked to be dug
+    pub fn dig_tiles(&self) -> impl Iterator<Item = &(u32, u32, u32)> {
+        self.dig.iter()
+    }
+
+    /// Tiles marked to be dug and not dug yet
+    pub fn dig_count(&self) -> usize {
+        self.dig.len()
+    }
+
+    pub fn stockpile_count(&self) -> usize {
+        self.stockpiles.len()
+    }
+
+    pub fn is_dig(&self, x: u32, y: u32, z: u32) -> bool {
+        self.dig.contains(&(x, y, z))
+    }
+
+    pub fn is_stockpile(&self, x: u32, y: u32, z: u32) -> bool {
+        self.stockpile_at(x, y, z).is_some()
+    }
+
+    pub fn stockpile_at(&self, x: u32, y: u32, z: u32) -> Option<Area> {
+        self.stockpiles.iter().find(|stockpile| stockpile.contains(x, y, z)).cloned()
+    }
+
+    /// The stockpiles on level z
+    pub fn stockpiles_on(&self, z: u32) -> Vec<Area> {
+        self.stockpiles.iter().filter(|stockpile| stockpile.z == z).cloned().collect()
+    }
+
+    pub fn stockpile_name(&self, stockpile: Area) -> Option<&str> {
+        self.stockpile_names.iter()
+            .find(|(area, _)| *area == stockpile)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// The names of the stockpiles touching the area
+    pub fn stockpile_names_in(&self, area: Area) -> Vec<&str> {
+        self.stockpile_names.iter()
+            .filter(|(stockpile, _)| stockpile.intersects(&area))
+            .map(|(_, name)| name.as_str())
+            .collect()
+    }
+
+    /// Leaves the note on the tile, replacing the one there. An empty
+    /// note takes it away.
+    pub fn set_note(&mut self, x: u32, y: u32, z: u32, text: String) {
+        match self.notes.iter_mut().find(|(tile, _)| *tile == (x, y, z)) {
+            Some(note) if !text.is_empty() => note.1 = text,
+            _ => {
+                self.notes.retain(|(tile, _)| *tile != (x, y, z));
+                if !text.is_empty() {
+                    self.notes.push(((x, y, z), text));
+                }
+            },
+        }
+    }
+
+    pub fn note_at(&self, x: u32, y: u32, z: u32) -> Option<&str> {
+        self.notes.iter()
+            .find(|(tile, _)| *tile == (x, y, z))
+            .map(|(_, text)| text.as_str())
+    }
+
+    pub fn notes(&self) -> &[((u32, u32, u32), String)] {
+        &self.notes
+    }
+
+    /// Names the stockpile on (x, y, z), an empty name clears it. False if
+    /// there is no stockpile there.
+    pub fn name_stockpile_at(&mut self, x: u32, y: u32, z: u32, name: String) -> bool {
+        let stockpile = match self.stockpile_at(x, y, z) {
+            Some(stockpile) => stockpile,
+            None => return false,
+        };
+        self.stockpile_names.retain(|(area, _)| *area != stockpile);
+        if !name.is_empty() {
+            self.stockpile_names.push((stockpile, name));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_removes_touched_stockpiles() {
+        let mut designations = Designations::new();
+        designations.add_stockpile(Area::from_corners((0, 0), (3, 3), 1));
+        designations.add_stockpile(Area::from_corners((10, 10), (12, 12), 1));
+        assert!(designations.is_stockpile(2, 2, 1));
+
+        designations.cancel(Area::from_corners((3, 3), (5, 5), 1));
+        assert!(!designations.is_stockpile(2, 2, 1));
+        assert!(designations.is_stockpile(11, 11, 1));
+
+        assert!(designations.name_stockpile_at(11, 12, 1, String::from("Quarry")));
+        assert!(!designations.name_stockpile_at(2, 2, 1, String::from("Gone")));
+        assert_eq!(designations.stockpile_names_in(Area::from_corners((0, 0), (20, 20), 1)),
+                   vec!["Quarry"]);
+        designations.add_stockpile(Area::from_corners((12, 12), (14, 14), 1));
+        assert!(designations.stockpile_names_in(Area::from_corners((0, 0), (20, 20), 1)).is_empty());
+    }
+
+    #[test]
+    fn test_burrow_areas() {
+        let mut designations = Designations::new();
+        let (hall, room) = (Area::from_corners((0, 0), (9, 2), 1), Area::from_corners((4, 3), (6, 6), 1));
+        assert!(designations.add_burrow(hall));
+        assert!(designations.add_burrow(room));
+        assert!(!designations.add_burrow(room));
+        assert!(designations.in_burrow(5, 5, 1));
+        assert!(!designations.in_burrow(5, 5, 2));
+        assert_eq!(designations.burrow_on(1).len(), 2);
+
+        // canceling designations leaves the burrow alone
+        designations.cancel(hall);
+        assert!(designations.in_burrow(0, 0, 1));
+        assert_eq!(designations.cut_burrow(Area::from_corners((5, 6), (8, 8), 1)), vec![room]);
+        assert!(!designations.in_burrow(5, 5, 1));
+        assert!(designations.in_burrow(9, 2, 1));
+    }
+
+    #[test]
+    fn test_notes_on_tiles() {
+        let mut designations = Designations::new();
+        designations.set_note(4, 5, 1, String::from("dig the hall east"));
+        designations.set_note(9, 9, 2, String::from("water above"));
+        designations.set_note(4, 5, 1, String::from("dig the hall west"));
+        println!("{:?}", designations.notes());
+        assert_eq!(designations.note_at(4, 5, 1), Some("dig the hall west"));
+        assert_eq!(designations.note_at(4, 5, 2), None);
+        assert_eq!(designations.notes()[0].0, (4, 5, 1));
+
+        // canceling designations leaves notes alone
+        designations.cancel(Area::from_corners((0, 0), (9, 9), 1));
+        designations.set_note(9, 9, 2, String::new());
+        assert_eq!(designations.notes().len(), 1);
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Write};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::save::SaveData;
+use crate::simulation::Simulation;
+
+/// Ticks between the checksums every game in a network game compares
+pub const CHECK_INTERVAL: u64 = 100;
+/// Passed on the command line to write a snapshot to DUMP_DIR with every
+/// checksum, for jmc-desync to compare
+pub const DUMP_FLAG: &str = "--desync-dumps";
+pub const DUMP_DIR: &str = "desync";
+/// How many of the latest snapshots are kept in DUMP_DIR
+const DUMPS_KEPT: u64 = 5;
+/// Lines shown of each side of a system that differs
+const DIFF_LINES: usize = 5;
+
+/// The state of the simulation as lines of text, system by system, the
+/// same on every game that ran the same ticks with the same orders. Things
+/// kept in no particular order are sorted. The random numbers all come
+/// from the seed and the tick, so those stand for where they're at. Only
+/// written out with DUMP_FLAG, checksums hash the simulation directly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub systems: Vec<(String, Vec<String>)>,
+}
+
+impl Snapshot {
+
+    /// Everything in the save every player shares, leaving out where the
+    /// player is looking and their own statistics
+    pub fn of(data: &SaveData) -> Snapshot {
+        let mut snapshot = Snapshot { tick: data.tick, systems: Vec::new() };
+        snapshot.lines("world", vec![
+            format!("seed {}", data.random_seed),
+            format!("tick {}", data.tick),
+            format!("difficulty {:?}", data.difficulty),
+            format!("name {:?}", data.colony_name),
+            format!("lost {:?}", data.lost),
+            format!("alert {}", data.alert),
+        ]);
+        snapshot.lines("entities", data.entities.iter().map(|entry| format!("{:?}", entry)).collect());
+        snapshot.unordered("map", &data.edited_tiles);
+        snapshot.unordered("explored", &data.explored_chunks);
+        snapshot.unordered("fluids", &data.active_fluids);
+        snapshot.unordered("gas", &data.active_gas);
+        snapshot.unordered("support", &data.pending_collapses);
+        snapshot.pretty("designations", &data.designations);
+        snapshot.pretty("railway", &data.railway);
+        snapshot.pretty("squad", &data.squad);
+        snapshot.unordered("paths", &data.paths);
+        snapshot.pretty("undo", &data.undo);
+        snapshot.unordered("gear", &data.gear);
+        snapshot.unordered("livestock", &data.livestock);
+        snapshot.unordered("items", &data.items);
+        snapshot.pretty("caravan", &data.caravan);
+        snapshot.unordered("afflictions", &data.afflictions);
+        snapshot.unordered("corpses", &data.corpses);
+        snapshot.unordered("carrying", &data.carrying);
+        snapshot.unordered("crops", &data.crops);
+        snapshot.unordered("pumps", &data.pumps);
+        snapshot.pretty("fires", &data.fires);
+        snapshot.unordered("buckets", &data.buckets);
+        snapshot.pretty("weather", &data.weather);
+        snapshot.pretty("rooms", &data.rooms);
+        snapshot.pretty("trends", &data.trends);
+        snapshot
+    }
+
+    fn lines(&mut self, system: &str, lines: Vec<String>) {
+        self.systems.push((String::from(system), lines));
+    }
+
+    /// A line for each element, sorted
+    fn unordered<T: Debug>(&mut self, system: &str, elements: &[T]) {
+        let mut lines: Vec<String> = elements.iter().map(|element| format!("{:?}", element)).collect();
+        lines.sort();
+        self.lines(system, lines);
+    }
+
+    /// Split over lines, so a difference shows up on the field it's in
+    fn pretty(&mut self, system: &str, value: &impl Debug) {
+        self.lines(system, format!("{:#?}", value).lines().map(String::from).collect());
+    }
+
+    /// The snapshot as text, a line naming each system followed by its
+    /// lines
+    pub fn to_text(&self) -> String {
+        let mut text = format!("tick {}\n", self.tick);
+        for (system, lines) in &self.systems {
+            text.push_str(&format!("## {}\n", system));
+            for line in lines {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Snapshot, String> {
+        let mut lines = text.lines();
+        let tick = lines.next()
+            .and_then(|line| line.strip_prefix("tick "))
+            .and_then(|tick| tick.parse().ok())
+            .ok_or("a snapshot starts with its tick")?;
+        let mut snapshot = Snapshot { tick, systems: Vec::new() };
+        for line in lines {
+            match (line.strip_prefix("## "), snapshot.systems.last_mut()) {
+                (Some(system), _) => snapshot.lines(system, Vec::new()),
+                (None, Some((_, lines))) => lines.push(String::from(line)),
+                (None, None) => return Err(format!("{:?} isn't in a system", line)),
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// What differs from the other snapshot, system by system, with some of
+    /// the lines only one of them has. Empty when they're the same.
+    pub fn diff(&self, other: &Snapshot) -> Vec<String> {
+        let mut report = Vec::new();
+        if self.tick != other.tick {
+            report.push(format!("taken at ticks {} and {}, anything that changes every tick differs", self.tick, other.tick));
+        }
+        let theirs: BTreeMap<&str, &Vec<String>> = other.systems.iter()
+            .map(|(system, lines)| (system.as_str(), lines))
+            .collect();
+        let empty = Vec::new();
+        for (system, lines) in &self.systems {
+            let other_lines = theirs.get(system.as_str()).copied().unwrap_or(&empty);
+            if lines == other_lines {
+                continue;
+            }
+            let (ours, theirs) = (only_in(lines, other_lines), only_in(other_lines, lines));
+            report.push(format!("{}: {} lines only in the first, {} only in the second", system, ours.len(), theirs.len()));
+            report.extend(ours.iter().take(DIFF_LINES).map(|line| format!("  - {}", line)));
+            report.extend(theirs.iter().take(DIFF_LINES).map(|line| format!("  + {}", line)));
+        }
+        for (system, _) in &other.systems {
+            if !self.systems.iter().any(|(ours, _)| ours == system) {
+                report.push(format!("{}: only in the second", system));
+            }
+        }
+        report
+    }
+}
+
+/// The lines of a that b doesn't have as many of
+fn only_in<'a>(a: &'a [String], b: &[String]) -> Vec<&'a String> {
+    let mut counts: BTreeMap<&String, usize> = BTreeMap::new();
+    for line in b {
+        *counts.entry(line).or_default() += 1;
+    }
+    a.iter().filter(|line| match counts.get_mut(line) {
+        Some(count) if *count > 0 => {
+            *count -= 1;
+            false
+        },
+        _ => true,
+    }).collect()
+}
+
+/// FNV-1a, the same on every machine unlike the std hasher. Values are
+/// hashed by writing them into it, nothing is kept.
+pub struct Fnv(u64);
+
+impl Fnv {
+
+    pub fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Fnv {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+        self
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Write for Fnv {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.bytes(text.as_bytes());
+        Ok(())
+    }
+}
+
+/// The hash of what the value prints as, without printing it anywhere
+fn hash_of(value: &impl Debug) -> u64 {
+    let mut fnv = Fnv::new();
+    // writing to the hash never fails
+    let _ = write!(fnv, "{:?}", value);
+    fnv.finish()
+}
+
+/// A hash of each system of the simulation, small enough to send every
+/// CHECK_INTERVAL ticks
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub tick: u64,
+    pub systems: Vec<(String, u64)>,
+}
+
+impl Checksum {
+
+    /// Hashes the systems a Snapshot has straight from the simulation. The
+    /// changed tiles and explored chunks have hashes the map keeps up as
+    /// they change, everything else is kept in order already.
+    pub fn of(sim: &Simulation) -> Checksum {
+        let map = &sim.map;
+        let systems = vec![
+            ("world", hash_of(&(map.random_seed, sim.tick, map.difficulty, &sim.name, sim.lost, sim.alert))),
+            ("entities", hash_of(&sim.entities)),
+            ("map", map.edits_hash()),
+            ("explored", map.explored_hash()),
+            ("fluids", hash_of(&sim.fluids.active())),
+            ("gas", hash_of(&sim.gas.active())),
+            ("support", hash_of(&sim.support.pending())),
+            ("designations", hash_of(&sim.designations)),
+            ("railway", hash_of(&sim.railway)),
+            ("squad", hash_of(&sim.squad)),
+            ("paths", hash_of(&sim.paths)),
+            ("undo", hash_of(&sim.undo)),
+            ("gear", hash_of(&sim.gear)),
+            ("livestock", hash_of(&sim.livestock)),
+            ("items", hash_of(&sim.items)),
+            ("caravan", hash_of(&sim.caravan)),
+            ("afflictions", hash_of(&sim.afflictions)),
+            ("corpses", hash_of(&sim.corpses)),
+            ("carrying", hash_of(&sim.carrying)),
+            ("crops", hash_of(&sim.crops)),
+            ("pumps", hash_of(&sim.pumps)),
+            ("fires", hash_of(&sim.fires)),
+            ("buckets", hash_of(&sim.buckets)),
+            ("weather", hash_of(&sim.weather)),
+            ("rooms", hash_of(&sim.rooms)),
+            ("trends", hash_of(&sim.trends)),
+        ];
+        Checksum {
+            tick: sim.tick,
+            systems: systems.into_iter().map(|(system, hash)| (String::from(system), hash)).collect(),
+        }
+    }
+
+    /// The systems whose hashes don't match the other checksum's
+    pub fn diverged(&self, other: &Checksum) -> Vec<String> {
+        let mut systems: Vec<String> = self.systems.iter()
+            .filter(|system| !other.systems.contains(system))
+            .map(|(system, _)| system.clone())
+            .collect();
+        systems.extend(other.systems.iter()
+            .filter(|(system, _)| !self.systems.iter().any(|(ours, _)| ours == system))
+            .map(|(system, _)| system.clone()));
+        systems
+    }
+}
+
+/// A player whose game stopped matching the host's
+#[derive(Clone, Debug, PartialEq)]
+pub struct Desync {
+    pub peer: u32,
+    pub tick: u64,
+    pub systems: Vec<String>,
+}
+
+impl Desync {
+
+    pub fn describe(&self) -> String {
+        format!("player {} is out of sync from tick {} at the latest, in {}",
+                self.peer, self.tick, self.systems.join(", "))
+    }
+}
+
+/// Writes the snapshot to DUMP_DIR under the peer and its tick, and drops
+/// the one from DUMPS_KEPT checks before
+pub fn write_dump(snapshot: &Snapshot, peer: u32) -> io::Result<PathBuf> {
+    let dir = Path::new(DUMP_DIR);
+    fs::create_dir_all(dir)?;
+    let path = |tick: u64| dir.join(format!("peer{}-tick{}.txt", peer, tick));
+    if let Some(old) = snapshot.tick.checked_sub(DUMPS_KEPT * CHECK_INTERVAL) {
+        let _ = fs::remove_file(path(old));
+    }
+    fs::write(path(snapshot.tick), snapshot.to_text())?;
+    Ok(path(snapshot.tick))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::fixtures::walled_room;
+    use crate::game_map::GameMap;
+    use crate::simulation::{start_position, Simulation};
+
+    #[test]
+    fn test_diverged_systems_pinpointed() {
+        let colony = || {
+            let mut sim = Simulation::new(GameMap::with_seed(10));
+            let (x, y, z) = start_position(&sim.map);
+            let player = sim.populate(x, y, z);
+            for _ in 0..3 {
+                sim.step();
+            }
+            (sim, player)
+        };
+        let (ours, player) = colony();
+        let (mut theirs, _) = colony();
+        assert_eq!(Checksum::of(&ours), Checksum::of(&theirs));
+        // a checksum names the systems the snapshots it stands for do
+        let systems = |checksum: Checksum| checksum.systems.into_iter().map(|(system, _)| system).collect::<Vec<_>>();
+        let snapshot_systems = |snapshot: &Snapshot| snapshot.systems.iter().map(|(system, _)| system.clone()).collect::<Vec<_>>();
+        let snapshot = Snapshot::of(&ours.save_data(Some(player)));
+        assert_eq!(Snapshot::of(&theirs.save_data(Some(player))), snapshot);
+        assert_eq!(Snapshot::parse(&snapshot.to_text()), Ok(snapshot.clone()));
+        assert_eq!(systems(Checksum::of(&ours)), snapshot_systems(&snapshot));
+
+        // one game's player took a hit the other's didn't
+        theirs.entities.get_mut(player).unwrap().hp -= 1;
+        let diverged = Snapshot::of(&theirs.save_data(Some(player)));
+        let report = snapshot.diff(&diverged);
+        println!("{}", report.join("\n"));
+        assert_eq!(Checksum::of(&ours).diverged(&Checksum::of(&theirs)), vec!["entities"]);
+        assert_eq!(report[0], "entities: 1 lines only in the first, 1 only in the second");
+        assert_eq!(report.len(), 3);
+        assert!(snapshot.diff(&snapshot).is_empty());
+    }
+
+    #[test]
+    fn test_rooms_found_in_the_same_order_everywhere() {
+        // every map keeps its edits in a differently seeded hash map
+        let colony = || {
+            let mut sim = Simulation::new(GameMap::with_seed(10));
+            for room in 0..3 {
+                walled_room(&mut sim.map, 1000 + room * 10, 1000, 20, 7, 5);
+            }
+            sim.rooms.find(&mut sim.map, &sim.designations);
+            assert_eq!(sim.rooms.rooms().len(), 3);
+            Checksum::of(&sim)
+        };
+        let (ours, theirs) = (colony(), colony());
+        println!("{:?}", ours.diverged(&theirs));
+        assert_eq!(ours, theirs);
+    }
+}
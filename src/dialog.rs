@@ -0,0 +1,154 @@
+use quicksilver::prelude::*;
+
+use crate::color_scheme::ColorScheme;
+use crate::layer::RenderLayer;
+use crate::text_input::TextInput;
+use crate::text_layout::{TextLayout, TextStyle};
+
+/// How many pixels across each pixel of a picture is drawn, before the UI
+/// scale
+const PICTURE_ZOOM: f32 = 2.0;
+/// Widest the text gets before it wraps, before the UI scale
+const TEXT_WIDTH: f32 = 640.0;
+
+/// A modal box with a title, some lines of text and a list of options,
+/// one of them selected
+pub struct Dialog {
+    pub title: String,
+    /// Shown under the title
+    pub picture: Option<Image>,
+    /// Shown between the title and the options
+    pub text: Vec<String>,
+    /// Typed into above the options, if the dialog asks for text
+    pub field: Option<TextInput>,
+    pub options: Vec<String>,
+    pub selected: usize,
+}
+
+impl Dialog {
+
+    pub fn new(title: &str, options: &[&str]) -> Dialog {
+        Dialog {
+            title: String::from(title),
+            picture: None,
+            text: Vec::new(),
+            field: None,
+            options: options.iter().map(|option| String::from(*option)).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn with_text(mut self, text: Vec<String>) -> Dialog {
+        self.text = text;
+        self
+    }
+
+    pub fn with_field(mut self, field: TextInput) -> Dialog {
+        self.field = Some(field);
+        self
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.options.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.options.len() - 1) % self.options.len();
+    }
+
+    /// Moves the selection with the arrow keys, returns the index of the
+    /// chosen option once Return is pressed
+    pub fn handle_input(&mut self, window: &Window) -> Option<usize> {
+        if let Some(field) = &mut self.field {
+            field.handle_keys(window);
+        }
+        if window.keyboard()[Key::Up] == ButtonState::Pressed {
+            self.select_previous();
+        }
+        if window.keyboard()[Key::Down] == ButtonState::Pressed {
+            self.select_next();
+        }
+        if window.keyboard()[Key::Return] == ButtonState::Pressed {
+            return Some(self.selected);
+        }
+        None
+    }
+
+    /// Draws the dialog centered on the screen, sized by the UI scale
+    pub fn draw(&self, window: &mut Window, font: &mut Font, text_layout: &TextLayout,
+                color_scheme: &ColorScheme, scale: f32) -> Result<()> {
+
+        let line_height = 28.0 * scale;
+        let padding = 20.0 * scale;
+
+        let title_style = FontStyle::new(
+            24.0 * scale, Color::from_hex(&color_scheme.light_yellow));
+        let option_style = FontStyle::new(
+            20.0 * scale, Color::from_hex(&color_scheme.fg));
+        let selected_style = FontStyle::new(
+            20.0 * scale, Color::from_hex(&color_scheme.light_orange));
+
+        let text_style = TextStyle::new(
+            18.0 * scale, Color::from_hex(&color_scheme.fg3));
+
+        let text_width = (TEXT_WIDTH * scale).min(window.screen_size().x - padding * 4.0);
+        let mut lines = vec![font.render(&self.title, &title_style)?];
+        for line in self.text.iter().flat_map(|line| text_layout.wrap(line, text_style.size, text_width)) {
+            // a space stands in for blank lines, there is no glyph to size an
+            // empty image by
+            let line = if line.is_empty() { " " } else { line.as_str() };
+            lines.push(font.render(line, &text_style.font_style())?);
+        }
+        if let Some(field) = &self.field {
+            lines.push(font.render(&format!("  {}", field.display()), &title_style)?);
+        }
+        for (index, option) in self.options.iter().enumerate() {
+            if index == self.selected {
+                lines.push(font.render(&format!("> {}", option), &selected_style)?);
+            } else {
+                lines.push(font.render(&format!("  {}", option), &option_style)?);
+            }
+        }
+
+        let picture_size = self.picture.as_ref()
+            .map(|picture| picture.area().size() * PICTURE_ZOOM * scale);
+        let picture_height = picture_size.map_or(0.0, |size| size.y + padding);
+        let width = lines.iter()
+            .map(|line| line.area().width())
+            .chain(picture_size.map(|size| size.x))
+            .fold(0.0, f32::max) + padding * 2.0;
+        let height = lines.len() as f32 * line_height + picture_height + padding * 2.0;
+        let area = Rectangle::new_sized((width, height))
+            .with_center(window.screen_size() / 2.0);
+
+        window.draw_ex(
+            &area,
+            Col(Color::from_hex(&color_scheme.bg)),
+            Transform::IDENTITY,
+            RenderLayer::UiTop.z()
+        );
+        if let (Some(picture), Some(size)) = (&self.picture, picture_size) {
+            let pos = area.top_left() + Vector::new((area.width() - size.x) / 2.0, padding + line_height);
+            window.draw_ex(
+                &Rectangle::new(pos, size),
+                Img(picture),
+                Transform::IDENTITY,
+                RenderLayer::UiTop.over()
+            );
+        }
+        for (index, line) in lines.iter().enumerate() {
+            // everything after the title goes below the picture
+            let below = if index == 0 { 0.0 } else { picture_height };
+            let pos = area.top_left()
+                + Vector::new(padding, padding + index as f32 * line_height + below);
+            window.draw_ex(
+                &line.area().translate(pos),
+                Img(line),
+                Transform::IDENTITY,
+                RenderLayer::UiTop.over()
+            );
+        }
+
+        Ok(())
+    }
+}
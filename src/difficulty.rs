@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::incidents::Incident;
+use crate::support::SUPPORT_RANGE;
+
+/// How hard a colony has it, picked when it lands and kept for its life.
+/// It changes how much gas worldgen puts in the rock, how far walls hold
+/// up a ceiling, how often hostile creatures turn up and incidents befall
+/// the colony and what the squad starts with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Peaceful,
+    #[default]
+    Standard,
+    Brutal,
+}
+
+pub const DIFFICULTIES: [Difficulty; 3] = [
+    Difficulty::Peaceful,
+    Difficulty::Standard,
+    Difficulty::Brutal,
+];
+
+impl Difficulty {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Peaceful => "Peaceful",
+            Difficulty::Standard => "Standard",
+            Difficulty::Brutal => "Brutal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        DIFFICULTIES.iter().copied()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The one after it, wrapping around
+    pub fn next(&self) -> Difficulty {
+        let index = DIFFICULTIES.iter().position(|difficulty| difficulty == self).unwrap_or(0);
+        DIFFICULTIES[(index + 1) % DIFFICULTIES.len()]
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Difficulty::Peaceful => "no hostiles, little gas, a larger and healthier squad",
+            Difficulty::Standard => "the colony as it was meant to be",
+            Difficulty::Brutal => "frequent hostiles, gas and cave-ins, two hurt colonists",
+        }
+    }
+
+    /// Levels below the water table that gas pockets start at
+    pub fn gas_offset(&self) -> u32 {
+        match self {
+            Difficulty::Peaceful => 24,
+            Difficulty::Standard => 16,
+            Difficulty::Brutal => 8,
+        }
+    }
+
+    /// Noise over which an open tile deep enough is a gas pocket, the lower
+    /// the more of them
+    pub fn gas_threshold(&self) -> f64 {
+        match self {
+            Difficulty::Peaceful => 0.45,
+            Difficulty::Standard => 0.3,
+            Difficulty::Brutal => 0.15,
+        }
+    }
+
+    /// How far a dug out tile may be from a wall or pillar before it caves
+    /// in
+    pub fn support_range(&self) -> u32 {
+        match self {
+            Difficulty::Peaceful => SUPPORT_RANGE + 1,
+            Difficulty::Standard => SUPPORT_RANGE,
+            Difficulty::Brutal => SUPPORT_RANGE - 1,
+        }
+    }
+
+    /// Ticks between hostile creatures turning up, None if they never do
+    pub fn spawn_interval(&self) -> Option<u64> {
+        match self {
+            Difficulty::Peaceful => None,
+            Difficulty::Standard => Some(1200),
+            Difficulty::Brutal => Some(400),
+        }
+    }
+
+    /// Most hostiles alive at once before no more turn up
+    pub fn max_hostiles(&self) -> usize {
+        match self {
+            Difficulty::Peaceful => 0,
+            Difficulty::Standard => 3,
+            Difficulty::Brutal => 8,
+        }
+    }
+
+    /// Ticks between incidents befalling the colony, None if they never do
+    pub fn incident_interval(&self) -> Option<u64> {
+        match self {
+            Difficulty::Peaceful => Some(9000),
+            Difficulty::Standard => Some(4500),
+            Difficulty::Brutal => Some(2000),
+        }
+    }
+
+    /// How likely each incident is to be the one befalling the colony
+    pub fn incident_weights(&self) -> [(Incident, u32); 5] {
+        let (tremor, gas, miner, failure, fire) = match self {
+            Difficulty::Peaceful => (1, 0, 3, 1, 0),
+            Difficulty::Standard => (3, 2, 2, 2, 1),
+            Difficulty::Brutal => (4, 4, 1, 3, 2),
+        };
+        [
+            (Incident::Tremor, tremor),
+            (Incident::GasRelease, gas),
+            (Incident::LostMiner, miner),
+            (Incident::EquipmentFailure, failure),
+            (Incident::Fire, fire),
+        ]
+    }
+
+    /// Colonists a new colony lands with, and the health each starts on
+    pub fn squad(&self) -> (usize, i32) {
+        match self {
+            Difficulty::Peaceful => (4, 5),
+            Difficulty::Standard => (3, 3),
+            Difficulty::Brutal => (2, 2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::GameMap;
+
+    #[test]
+    fn test_difficulty_presets() {
+        assert_eq!(Difficulty::from_name("brutal"), Some(Difficulty::Brutal));
+        assert_eq!(Difficulty::from_name("hard"), None);
+        assert_eq!(Difficulty::Brutal.next(), Difficulty::Peaceful);
+
+        let standard = GameMap::with_seed(10);
+        let brutal = GameMap::with_seed(10).with_difficulty(Difficulty::Brutal);
+        println!("gas from level {} and {}", standard.gas_depth, brutal.gas_depth);
+        assert!(brutal.gas_depth < standard.gas_depth);
+        assert_eq!(brutal.water_table, standard.water_table);
+        assert!(Difficulty::Peaceful.support_range() > Difficulty::Brutal.support_range());
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::entity::EntityId;
+use crate::mods::{Content, ItemDef};
+
+/// Ticks between caravans coming down the drill
+pub const CARAVAN_INTERVAL: u64 = 12000;
+/// Ticks a caravan stays to trade once it's got to a stockpile
+pub const TRADE_TICKS: u64 = 3000;
+/// Goods a caravan brings
+const CARAVAN_GOODS: usize = 6;
+/// What traders ask for their goods, in percent of what they're worth
+const MARKUP_PERCENT: u32 = 150;
+
+/// What every item of the content packs is worth, and which of them the
+/// colony has no way to make and has to trade for
+#[derive(Clone, Debug, Default)]
+pub struct Economy {
+    items: BTreeMap<String, ItemDef>,
+    /// Ids of the items no recipe makes and no livestock gives
+    imports: Vec<String>,
+}
+
+impl Economy {
+
+    pub fn new(content: &Content) -> Economy {
+        let made: BTreeSet<&String> = content.recipes.iter()
+            .flat_map(|recipe| recipe.outputs.keys())
+            .chain(content.creatures.iter()
+                .filter_map(|creature| creature.livestock.as_ref())
+                .map(|livestock| &livestock.produces))
+            .chain(content.crops.iter().map(|crop| &crop.produces))
+            .collect();
+        let imports = content.items.ids()
+            .filter(|id| !made.contains(id))
+            .cloned()
+            .collect();
+        // items that don't say how well they burn burn like what they're made of
+        let items = content.items.iter()
+            .map(|item| {
+                let mut item = item.clone();
+                if item.flammability == 0 {
+                    item.flammability = item.material.as_ref()
+                        .and_then(|material| content.materials.get(material))
+                        .map_or(0, |material| material.flammability);
+                }
+                item
+            })
+            .collect();
+        Economy::with_items(items, imports)
+    }
+
+    pub fn with_items(items: Vec<ItemDef>, imports: Vec<String>) -> Economy {
+        Economy {
+            items: items.into_iter().map(|item| (item.id.clone(), item)).collect(),
+            imports,
+        }
+    }
+
+    pub fn item(&self, id: &str) -> Option<&ItemDef> {
+        self.items.get(id)
+    }
+
+    /// What the item is worth, 0 for ones no pack defines
+    pub fn value(&self, id: &str) -> u32 {
+        self.items.get(id).map_or(0, |item| item.value)
+    }
+
+    /// What a trader asks for the item
+    pub fn price(&self, id: &str) -> u32 {
+        (self.value(id) * MARKUP_PERCENT).div_ceil(100)
+    }
+
+    /// The goods a caravan arriving at tick brings, drawn from the imports.
+    /// They come from the seed and the tick, the same in every game of a
+    /// network session.
+    pub fn pack_goods(&self, seed: u32, tick: u64) -> Vec<String> {
+        if self.imports.is_empty() {
+            return Vec::new();
+        }
+        let mut rng = oorandom::Rand32::new(((seed as u64) << 32) ^ tick);
+        let mut goods: Vec<String> = (0..CARAVAN_GOODS)
+            .map(|_| self.imports[rng.rand_range(0..self.imports.len() as u32) as usize].clone())
+            .collect();
+        goods.sort();
+        goods
+    }
+}
+
+/// How far along its visit a caravan is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visit {
+    /// Walking from the drill to the nearest stockpile
+    Arriving,
+    /// Standing at the stockpile trading until the tick
+    Trading { until: u64 },
+    /// Walking back to the drill, it's gone once it gets there
+    Leaving,
+}
+
+/// Traders come down the drill from the surface with goods to trade
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Caravan {
+    /// The entity showing the traders
+    pub entity: EntityId,
+    /// Ids of the items they have to trade
+    pub goods: Vec<String>,
+    pub visit: Visit,
+}
+
+impl Caravan {
+
+    pub fn new(entity: EntityId, goods: Vec<String>) -> Caravan {
+        Caravan { entity, goods, visit: Visit::Arriving }
+    }
+
+    pub fn is_trading(&self) -> bool {
+        matches!(self.visit, Visit::Trading { .. })
+    }
+
+    /// What the traders ask for the goods at the indices, None if one of
+    /// them isn't theirs
+    pub fn asking(&self, economy: &Economy, buy: &[usize]) -> Option<u32> {
+        buy.iter()
+            .map(|&index| self.goods.get(index).map(|id| economy.price(id)))
+            .sum()
+    }
+
+    /// Hands over the goods at the indices, taking the sold items in
+    /// their place. Returns the ids of the goods bought.
+    pub fn exchange(&mut self, buy: &[usize], sold: Vec<String>) -> Vec<String> {
+        let mut indices: Vec<usize> = buy.iter().copied().filter(|&index| index < self.goods.len()).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        let bought = indices.into_iter().rev()
+            .map(|index| self.goods.remove(index))
+            .collect();
+        self.goods.extend(sold);
+        self.goods.sort();
+        bought
+    }
+}
+
+/// Goods picked out to trade with a caravan
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Deal {
+    /// Indices into the caravan's goods
+    pub buy: BTreeSet<usize>,
+    /// The colony's items given for them
+    pub sell: BTreeSet<EntityId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_scheme::ColorName;
+    use crate::entity::Entities;
+    use crate::equipment::Gear;
+
+    fn item(id: &str, value: u32) -> ItemDef {
+        ItemDef {
+            id: String::from(id),
+            name: String::from(id),
+            glyph: '*',
+            color: ColorName::Yellow,
+            material: None,
+            tool: None,
+            armor: 0,
+            uses: None,
+            value,
+            food: 0,
+            flammability: 0,
+        }
+    }
+
+    #[test]
+    fn test_traders_mark_up_their_goods() {
+        let economy = Economy::with_items(
+            vec![item("egg", 2), item("pick", 10), item("shell", 3)],
+            vec![String::from("pick"), String::from("shell")]);
+        assert_eq!(economy.price("pick"), 15);
+        assert_eq!(economy.price("shell"), 5);
+        assert_eq!(economy.value("rock"), 0);
+
+        let goods = economy.pack_goods(10, 500);
+        println!("{:?}", goods);
+        assert_eq!(goods.len(), CARAVAN_GOODS);
+        assert!(goods.iter().all(|id| id != "egg"));
+        assert_eq!(goods, economy.pack_goods(10, 500));
+
+        let traders = Entities::new().insert(Gear::pick().place(0, 0, 0));
+        let mut caravan = Caravan::new(traders, vec![String::from("pick"), String::from("shell")]);
+        assert_eq!(caravan.asking(&economy, &[0, 1]), Some(20));
+        assert_eq!(caravan.asking(&economy, &[2]), None);
+        let bought = caravan.exchange(&[1], vec![String::from("egg"), String::from("egg")]);
+        assert_eq!(bought, vec![String::from("shell")]);
+        assert_eq!(caravan.goods, vec![String::from("egg"), String::from("egg"), String::from("pick")]);
+    }
+}
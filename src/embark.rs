@@ -0,0 +1,153 @@
+use noise::{NoiseFn, Perlin, Seedable};
+
+use crate::color_scheme::ColorName;
+use crate::game_map::GameMap;
+
+/// Tiles across each cell of the region overview
+pub const CELL_TILES: u32 = 256;
+/// Cells across and down the overview
+pub const REGION_WIDTH: u32 = 64;
+pub const REGION_HEIGHT: u32 = 32;
+/// Tiles from one hill to the next, roughly
+const HEIGHT_SCALE: f64 = 3000.0;
+const MOISTURE_SCALE: f64 = 5000.0;
+
+/// What the surface is like over a cell, from its height and moisture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Biome {
+    Ocean,
+    Desert,
+    Plains,
+    Forest,
+    Mountains,
+}
+
+impl Biome {
+
+    fn from_surface(height: f64, moisture: f64) -> Biome {
+        if height < -0.25 {
+            Biome::Ocean
+        } else if height > 0.35 {
+            Biome::Mountains
+        } else if moisture < -0.2 {
+            Biome::Desert
+        } else if moisture > 0.2 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Biome::Ocean => "ocean",
+            Biome::Desert => "desert",
+            Biome::Plains => "plains",
+            Biome::Forest => "forest",
+            Biome::Mountains => "mountains",
+        }
+    }
+
+    pub fn color(&self) -> ColorName {
+        match self {
+            Biome::Ocean => ColorName::Blue,
+            Biome::Desert => ColorName::Yellow,
+            Biome::Plains => ColorName::LightGreen,
+            Biome::Forest => ColorName::Green,
+            Biome::Mountains => ColorName::Gray,
+        }
+    }
+
+    /// Whether the lander can set down there
+    pub fn landable(&self) -> bool {
+        *self != Biome::Ocean
+    }
+}
+
+/// A cell of the overview
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Site {
+    /// Height of the surface, about -1 to 1
+    pub height: f64,
+    pub biome: Biome,
+}
+
+/// The surface around the middle of the world, a cell for every
+/// CELL_TILES square, for picking where a colony lands. It comes from the
+/// seed alone, nothing under the surface is generated for it.
+pub struct Region {
+    /// The tile at the top left corner of the first cell
+    pub origin: (u32, u32),
+    /// Row by row
+    pub sites: Vec<Site>,
+}
+
+impl Region {
+
+    pub fn survey(map: &GameMap) -> Region {
+        let (width, height, _) = map.size();
+        let origin = (
+            width / 2 - REGION_WIDTH * CELL_TILES / 2,
+            height / 2 - REGION_HEIGHT * CELL_TILES / 2,
+        );
+        let elevation = Perlin::new().set_seed(map.random_seed.wrapping_add(2));
+        let moisture = Perlin::new().set_seed(map.random_seed.wrapping_add(3));
+        let mut sites = Vec::with_capacity((REGION_WIDTH * REGION_HEIGHT) as usize);
+        for row in 0..REGION_HEIGHT {
+            for column in 0..REGION_WIDTH {
+                let (x, y) = Region::cell_center(origin, column, row);
+                let (x, y) = (x as f64, y as f64);
+                let height = elevation.get([x / HEIGHT_SCALE, y / HEIGHT_SCALE]);
+                let wet = moisture.get([x / MOISTURE_SCALE, y / MOISTURE_SCALE]);
+                sites.push(Site { height, biome: Biome::from_surface(height, wet) });
+            }
+        }
+        Region { origin, sites }
+    }
+
+    fn cell_center(origin: (u32, u32), column: u32, row: u32) -> (u32, u32) {
+        (origin.0 + column * CELL_TILES + CELL_TILES / 2,
+         origin.1 + row * CELL_TILES + CELL_TILES / 2)
+    }
+
+    pub fn site(&self, column: u32, row: u32) -> Site {
+        self.sites[(column + row * REGION_WIDTH) as usize]
+    }
+
+    /// The tile in the middle of the cell, where the colony lands
+    pub fn center(&self, column: u32, row: u32) -> (u32, u32) {
+        Region::cell_center(self.origin, column, row)
+    }
+
+    /// The landable cell nearest the middle of the region
+    pub fn default_cell(&self) -> (u32, u32) {
+        let middle = (REGION_WIDTH as i64 / 2, REGION_HEIGHT as i64 / 2);
+        (0..REGION_HEIGHT)
+            .flat_map(|row| (0..REGION_WIDTH).map(move |column| (column, row)))
+            .filter(|&(column, row)| self.site(column, row).biome.landable())
+            .min_by_key(|&(column, row)| {
+                (column as i64 - middle.0).pow(2) + (row as i64 - middle.1).pow(2)
+            })
+            .unwrap_or((middle.0 as u32, middle.1 as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_survey() {
+        let region = Region::survey(&GameMap::with_seed(10));
+        let biomes: Vec<_> = region.sites.iter().map(|site| site.biome).collect();
+        println!("{:?}", region.origin);
+        assert_eq!(biomes.len(), (REGION_WIDTH * REGION_HEIGHT) as usize);
+        assert!(biomes.iter().any(|biome| *biome != biomes[0]));
+
+        let (column, row) = region.default_cell();
+        assert!(region.site(column, row).biome.landable());
+        let (x, y) = region.center(0, 0);
+        assert_eq!((x - region.origin.0, y - region.origin.1), (CELL_TILES / 2, CELL_TILES / 2));
+        assert_eq!(Region::survey(&GameMap::with_seed(10)).sites, region.sites);
+    }
+}
@@ -0,0 +1,287 @@
+use quicksilver::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::Renderable;
+use crate::color_scheme::ColorName;
+use crate::game_map::{Tile, TileKind};
+
+/// Noise over which wall rock is soft enough for burrowers to tunnel
+/// through, the rock lining caves
+const SOFT_ROCK: f64 = 0.5;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub pos: Vector,
+    pub depth: u32,
+    pub glyph: char,
+    pub color: ColorName,
+    pub hp: i32,
+    pub max_hp: i32,
+    /// Breathing gear that keeps gas from hurting this entity
+    #[serde(default)]
+    pub gas_protection: bool,
+    /// Tiles covered, pos is the top left one
+    #[serde(default)]
+    pub footprint: Footprint,
+    /// Frames drawn instead of glyph and color, if the entity is animated
+    #[serde(default)]
+    pub renderable: Option<Renderable>,
+    /// Dangerous to colonists, the player stops walking when one shows up
+    #[serde(default)]
+    pub hostile: bool,
+    /// What it can go over or through besides open floor
+    #[serde(default)]
+    pub movement: Movement,
+}
+
+/// How many tiles an entity covers, east and south of its position
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Footprint {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Footprint {
+
+    pub const SINGLE: Footprint = Footprint { width: 1, height: 1 };
+
+    pub fn new(width: u32, height: u32) -> Footprint {
+        Footprint { width, height }
+    }
+
+    /// Offsets of every covered tile from the top left one
+    pub fn offsets(&self) -> impl Iterator<Item = (u32, u32)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |dy| (0..width).map(move |dx| (dx, dy)))
+    }
+}
+
+impl Default for Footprint {
+    fn default() -> Footprint {
+        Footprint::SINGLE
+    }
+}
+
+/// How an entity gets about, everything without a flag set walks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Movement {
+    /// Flies over the rubble cave-ins leave
+    pub flying: bool,
+    /// Lives in water and only goes where there is some
+    pub aquatic: bool,
+    /// Tunnels slowly through rubble and soft rock
+    pub burrowing: bool,
+}
+
+impl Movement {
+
+    pub const WALKING: Movement = Movement { flying: false, aquatic: false, burrowing: false };
+
+    /// Whether the tile is somewhere it can go
+    pub fn can_enter(&self, tile: &Tile) -> bool {
+        match tile.kind {
+            TileKind::Floor => !self.aquatic || tile.fluid > 0,
+            TileKind::Rubble => self.flying || self.burrowing,
+            TileKind::Wall => self.burrowing && tile.val >= SOFT_ROCK,
+            TileKind::Pillar => false,
+        }
+    }
+
+    /// Whether deep water drowns it, fliers keep their heads above it
+    pub fn can_drown(&self) -> bool {
+        !self.aquatic && !self.flying
+    }
+
+    /// Whether going onto the tile means tunnelling, which takes a while
+    pub fn is_slow_on(&self, tile: &Tile) -> bool {
+        tile.is_solid() && !(self.flying && tile.kind == TileKind::Rubble)
+    }
+}
+
+impl Entity {
+
+    /// Entities without hit points are scenery and never die
+    pub fn is_alive(&self) -> bool {
+        self.max_hp == 0 || self.hp > 0
+    }
+
+    pub fn take_damage(&mut self, amount: i32) {
+        self.hp = (self.hp - amount).max(0);
+        if self.hp == 0 {
+            self.glyph = '%';
+            self.renderable = None;
+        }
+    }
+
+    /// The glyph and color to draw, elapsed_ms into its animation
+    pub fn appearance(&self, elapsed_ms: u64) -> (char, ColorName) {
+        match self.renderable.as_ref().and_then(|renderable| renderable.frame(elapsed_ms)) {
+            Some(frame) => (frame.glyph, frame.color),
+            None => (self.glyph, self.color),
+        }
+    }
+
+    /// Whether the entity covers (x, y, z) with any part of its footprint
+    pub fn is_at(&self, x: u32, y: u32, z: u32) -> bool {
+        // tiles west or north of pos wrap around to huge offsets
+        self.depth == z 
+            && x.wrapping_sub(self.pos.x as u32) < self.footprint.width 
+            && y.wrapping_sub(self.pos.y as u32) < self.footprint.height
+    }
+
+    /// Every tile the entity covers, before wrapping around the world
+    pub fn cells(&self) -> Vec<(u32, u32)> {
+        let (x, y) = (self.pos.x as u32, self.pos.y as u32);
+        self.footprint.offsets().map(|(dx, dy)| (x + dx, y + dy)).collect()
+    }
+}
+
+/// A handle to an entity that stays valid while the entity is alive and
+/// never refers to a different entity once it has been removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    entity: Option<Entity>,
+}
+
+/// Storage for every entity in the world, addressed by EntityId. Slots of
+/// removed entities are reused with a new generation, so stale ids held by
+/// other systems simply stop resolving.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Entities {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl Entities {
+
+    pub fn new() -> Entities {
+        Entities {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity) -> EntityId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.generation += 1;
+                slot.entity = Some(entity);
+                EntityId { index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, entity: Some(entity) });
+                EntityId { index: self.slots.len() as u32 - 1, generation: 0 }
+            },
+        }
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        let entity = slot.entity.take();
+        if entity.is_some() {
+            self.free.push(id.index);
+        }
+        entity
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.slots.get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.entity.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.slots.get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.entity.as_mut())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = EntityId { index: index as u32, generation: slot.generation };
+            slot.entity.as_ref().map(|entity| (id, entity))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut Entity)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| {
+            let id = EntityId { index: index as u32, generation: slot.generation };
+            slot.entity.as_mut().map(|entity| (id, entity))
+        })
+    }
+
+    /// Whether any entity other than ignore covers (x, y, z)
+    pub fn is_occupied(&self, x: u32, y: u32, z: u32, ignore: Option<EntityId>) -> bool {
+        self.iter().any(|(id, entity)| Some(id) != ignore && entity.is_at(x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(glyph: char) -> Entity {
+        Entity {
+            pos: Vector::new(0, 0),
+            depth: 0,
+            glyph,
+            color: ColorName::Fg,
+            hp: 1,
+            max_hp: 1,
+            gas_protection: false,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: false,
+            movement: Movement::WALKING,
+        }
+    }
+
+    #[test]
+    fn test_removed_id_does_not_resolve() {
+        let mut entities = Entities::new();
+        let a = entities.insert(entity('a'));
+        let b = entities.insert(entity('b'));
+        assert_eq!(entities.remove(a).map(|e| e.glyph), Some('a'));
+        assert!(entities.get(a).is_none());
+        assert!(entities.remove(a).is_none());
+        assert_eq!(entities.get(b).map(|e| e.glyph), Some('b'));
+    }
+
+    #[test]
+    fn test_reused_slot_gets_new_generation() {
+        let mut entities = Entities::new();
+        let a = entities.insert(entity('a'));
+        entities.remove(a);
+        let c = entities.insert(entity('c'));
+        println!("a: {:?} c: {:?}", a, c);
+        assert_ne!(a, c);
+        assert!(entities.get(a).is_none());
+        assert_eq!(entities.get(c).map(|e| e.glyph), Some('c'));
+        assert_eq!(entities.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_footprint_covers_every_cell() {
+        let mut drill = entity('™');
+        drill.pos = Vector::new(10, 20);
+        drill.footprint = Footprint::new(3, 2);
+        println!("{:?}", drill.cells());
+        assert_eq!(drill.cells().len(), 6);
+        assert!(drill.is_at(12, 21, 0));
+        assert!(!drill.is_at(13, 21, 0));
+        assert!(!drill.is_at(9, 20, 0));
+        assert!(!drill.is_at(10, 22, 0));
+    }
+}
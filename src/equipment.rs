@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color_scheme::ColorName;
+use crate::entity::{Entity, Footprint, Movement};
+
+/// Uses a pick colonists land with lasts
+const PICK_USES: u32 = 400;
+/// Uses the hammer left by the lander lasts
+const HAMMER_USES: u32 = 150;
+
+/// What a tool is needed for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolKind {
+    /// Digging out rock
+    Pick,
+    /// Building pillars and track
+    Hammer,
+}
+
+impl ToolKind {
+
+    pub fn name(&self) -> &str {
+        match self {
+            ToolKind::Pick => "pick",
+            ToolKind::Hammer => "hammer",
+        }
+    }
+}
+
+/// A tool or a piece of armor, lying about or worn by a colonist
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gear {
+    pub name: String,
+    pub glyph: char,
+    pub color: ColorName,
+    /// What it's used for, None for armor
+    pub tool: Option<ToolKind>,
+    /// Damage taken off each hit while it's worn
+    #[serde(default)]
+    pub armor: i32,
+    /// Uses left before it wears out, None if it never does
+    #[serde(default)]
+    pub uses: Option<u32>,
+}
+
+impl Gear {
+
+    /// The pick every colonist lands with
+    pub fn pick() -> Gear {
+        Gear {
+            name: String::from("Pick"),
+            glyph: '(',
+            color: ColorName::LightYellow,
+            tool: Some(ToolKind::Pick),
+            armor: 0,
+            uses: Some(PICK_USES),
+        }
+    }
+
+    /// The hammer the lander brings along
+    pub fn hammer() -> Gear {
+        Gear {
+            name: String::from("Hammer"),
+            glyph: '/',
+            color: ColorName::LightYellow,
+            tool: Some(ToolKind::Hammer),
+            armor: 0,
+            uses: Some(HAMMER_USES),
+        }
+    }
+
+    pub fn is_armor(&self) -> bool {
+        self.tool.is_none()
+    }
+
+    /// The gear lying at (x, y, z), scenery that can't be hurt
+    pub fn place(&self, x: u32, y: u32, z: u32) -> Entity {
+        Entity {
+            pos: quicksilver::geom::Vector::new(x, y),
+            depth: z,
+            glyph: self.glyph,
+            color: self.color,
+            hp: 0,
+            max_hp: 0,
+            gas_protection: true,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: false,
+            movement: Movement::WALKING,
+        }
+    }
+}
+
+/// What a colonist carries, a tool in hand and armor on
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub tool: Option<Gear>,
+    pub armor: Option<Gear>,
+}
+
+impl Equipment {
+
+    pub fn has_tool(&self, kind: ToolKind) -> bool {
+        self.tool.as_ref().is_some_and(|tool| tool.tool == Some(kind))
+    }
+
+    /// Puts the gear in its slot, returns what was there before
+    pub fn equip(&mut self, gear: Gear) -> Option<Gear> {
+        let slot = if gear.is_armor() { &mut self.armor } else { &mut self.tool };
+        slot.replace(gear)
+    }
+
+    /// Wears the tool down by a use, returns its name if that wore it out
+    pub fn wear_tool(&mut self) -> Option<String> {
+        let tool = self.tool.as_mut()?;
+        let uses = tool.uses.as_mut()?;
+        *uses = uses.saturating_sub(1);
+        if *uses > 0 {
+            return None;
+        }
+        self.tool.take().map(|tool| tool.name)
+    }
+
+    /// Damage taken off each hit
+    pub fn armor(&self) -> i32 {
+        self.armor.as_ref().map_or(0, |armor| armor.armor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tools_wear_out() {
+        let mut equipment = Equipment::default();
+        assert_eq!(equipment.equip(Gear { uses: Some(2), ..Gear::pick() }), None);
+        assert!(equipment.has_tool(ToolKind::Pick));
+
+        let old = equipment.equip(Gear::hammer());
+        println!("{:?}", equipment);
+        assert_eq!(old.and_then(|pick| pick.uses), Some(2));
+        assert!(!equipment.has_tool(ToolKind::Pick));
+        assert!(equipment.has_tool(ToolKind::Hammer));
+
+        equipment.equip(Gear { uses: Some(2), ..Gear::pick() });
+        assert_eq!(equipment.wear_tool(), None);
+        assert_eq!(equipment.wear_tool(), Some(String::from("Pick")));
+        assert_eq!(equipment.tool, None);
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt;
+
+/// Something that went wrong badly enough to tell the player about, shown
+/// on the error screen instead of crashing
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameError {
+    /// A font or other file in static/ could not be loaded
+    MissingAsset { path: String, reason: String },
+    /// Part of the screen could not be drawn
+    Draw { part: &'static str, reason: String },
+    /// Hosting or joining a network game failed, or the host went away
+    Network { reason: String },
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::MissingAsset { path, reason } => 
+                write!(f, "could not load static/{}: {}", path, reason),
+            GameError::Draw { part, reason } => 
+                write!(f, "could not draw the {}: {}", part, reason),
+            GameError::Network { reason } => 
+                write!(f, "network game: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+pub type GameResult<T> = std::result::Result<T, GameError>;
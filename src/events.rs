@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+use crate::incidents::Incident;
+use crate::skills::Skill;
+
+/// What killed something
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    CaveIn,
+    Gas,
+    /// Hurt by a script, or anything else
+    Injuries,
+    /// Kept creatures nobody fed
+    Starvation,
+    /// Gas that got into them and stayed
+    Poison,
+    /// Wounds that kept bleeding
+    BloodLoss,
+    Burns,
+    /// Caught in deep water
+    Drowning,
+}
+
+impl DeathCause {
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            DeathCause::CaveIn => "a cave-in",
+            DeathCause::Gas => "toxic gas",
+            DeathCause::Injuries => "their injuries",
+            DeathCause::Starvation => "hunger",
+            DeathCause::Poison => "poisoning",
+            DeathCause::BloodLoss => "blood loss",
+            DeathCause::Burns => "their burns",
+            DeathCause::Drowning => "drowning",
+        }
+    }
+}
+
+/// How a colony was lost
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Loss {
+    /// Every colonist is dead, cause is what killed the last of them
+    SquadDied { cause: Option<DeathCause> },
+    /// Water rose over the drill the colony landed with
+    BaseFlooded,
+}
+
+impl Loss {
+
+    pub fn describe(&self) -> String {
+        match self {
+            Loss::SquadDied { cause: Some(cause) } =>
+                format!("Every colonist is dead, the last killed by {}", cause.describe()),
+            Loss::SquadDied { cause: None } => String::from("Every colonist is dead"),
+            Loss::BaseFlooded => String::from("The drill is under water"),
+        }
+    }
+}
+
+/// Why something stopped on its way somewhere
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interruption {
+    /// The way was walled up, another was found
+    Blocked,
+    /// There's no way there any more
+    CutOff,
+    /// Gas is in the way, the job is left for a while
+    Gas,
+}
+
+impl Interruption {
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Interruption::Blocked => "the way was blocked, going around",
+            Interruption::CutOff => "there's no way there any more",
+            Interruption::Gas => "gas is in the way, trying again later",
+        }
+    }
+}
+
+/// Something that happened in the world that other systems may want to
+/// react to, like playing a sound
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    Dug { x: u32, y: u32, z: u32 },
+    Hit { x: u32, y: u32, z: u32, damage: i32 },
+    WaterFlowed { x: u32, y: u32, z: u32 },
+    CaveIn { x: u32, y: u32, z: u32 },
+    /// Something living was killed
+    Died { x: u32, y: u32, z: u32, cause: DeathCause },
+    /// A cart dropped stone off at the stop at (x, y, z)
+    Hauled { x: u32, y: u32, z: u32, amount: u32 },
+    /// The colony can't go on, raised once
+    ColonyLost { x: u32, y: u32, z: u32, loss: Loss },
+    /// Digging was designated where no colonist can get to
+    NoRoute { x: u32, y: u32, z: u32 },
+    /// Something walking to (x, y, z) had to stop or go around
+    JobInterrupted { x: u32, y: u32, z: u32, reason: Interruption },
+    /// A colonist saw the hostile at (x, y, z), putting the colony on alert
+    HostileSighted { x: u32, y: u32, z: u32 },
+    /// The tool of the colonist at (x, y, z) wore out
+    WornOut { x: u32, y: u32, z: u32 },
+    /// The colonist at (x, y, z) got better at a skill
+    SkillUp { x: u32, y: u32, z: u32, skill: Skill, level: u32 },
+    /// A colonist caught the creature at (x, y, z)
+    Tamed { x: u32, y: u32, z: u32 },
+    /// A creature was born in the pen at (x, y, z)
+    Born { x: u32, y: u32, z: u32 },
+    /// A trade caravan got to the stockpile at (x, y, z) and is trading
+    CaravanArrived { x: u32, y: u32, z: u32 },
+    /// The caravan went back up the drill at (x, y, z)
+    CaravanLeft { x: u32, y: u32, z: u32 },
+    /// The incident befell the colony around (x, y, z)
+    Incident { x: u32, y: u32, z: u32, incident: Incident },
+}
+
+impl GameEvent {
+
+    /// Where in the world the event happened
+    pub fn position(&self) -> (u32, u32, u32) {
+        match *self {
+            GameEvent::Dug { x, y, z }
+            | GameEvent::Hit { x, y, z, .. }
+            | GameEvent::WaterFlowed { x, y, z }
+            | GameEvent::CaveIn { x, y, z }
+            | GameEvent::Died { x, y, z, .. }
+            | GameEvent::Hauled { x, y, z, .. }
+            | GameEvent::ColonyLost { x, y, z, .. }
+            | GameEvent::NoRoute { x, y, z }
+            | GameEvent::JobInterrupted { x, y, z, .. }
+            | GameEvent::HostileSighted { x, y, z }
+            | GameEvent::WornOut { x, y, z }
+            | GameEvent::SkillUp { x, y, z, .. }
+            | GameEvent::Tamed { x, y, z }
+            | GameEvent::Born { x, y, z }
+            | GameEvent::CaravanArrived { x, y, z }
+            | GameEvent::CaravanLeft { x, y, z }
+            | GameEvent::Incident { x, y, z, .. } => (x, y, z),
+        }
+    }
+}
+
+/// Collects the events raised during a frame so every listener can be
+/// handed all of them once, instead of systems calling each other
+pub struct EventBus {
+    events: Vec<GameEvent>,
+}
+
+impl EventBus {
+
+    pub fn new() -> EventBus {
+        EventBus {
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    /// Takes every event raised since the last drain, oldest first
+    pub fn drain(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use quicksilver::prelude::Color;
+use rusttype::{point, Font, Scale};
+
+use crate::color_scheme::{average_color, ColorName, ColorScheme};
+use crate::entity::Entities;
+use crate::game_map::GameMap;
+use crate::selection::Area;
+
+/// Where quicksilver loads assets from on desktop
+const ASSET_DIR: &str = "static";
+
+/// An RGBA image in memory
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+
+    pub fn new(width: u32, height: u32, background: Color) -> Canvas {
+        let pixel = [
+            to_byte(background.r), to_byte(background.g),
+            to_byte(background.b), to_byte(background.a),
+        ];
+        Canvas {
+            width,
+            height,
+            pixels: pixel.iter().cloned().cycle()
+                .take((width * height * 4) as usize)
+                .collect(),
+        }
+    }
+
+    /// Paints color over the pixel, coverage is how much of it is covered
+    pub fn blend(&mut self, x: u32, y: u32, color: Color, coverage: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let alpha = (coverage * color.a).clamp(0.0, 1.0);
+        let i = ((x + y * self.width) * 4) as usize;
+        for (channel, value) in [color.r, color.g, color.b].iter().enumerate() {
+            let old = self.pixels[i + channel] as f32 / 255.0;
+            self.pixels[i + channel] = to_byte(old + (value - old) * alpha);
+        }
+        self.pixels[i + 3] = 255;
+    }
+
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.blend(px, py, color, 1.0);
+            }
+        }
+    }
+
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Draws tileset glyphs into a Canvas without going through the window,
+/// using the same fonts the Tileset is rendered from
+pub struct OffscreenRenderer {
+    fonts: Vec<(Font<'static>, String)>,
+    pub tile_px: u32,
+}
+
+impl OffscreenRenderer {
+
+    /// Loads the fonts of the glyph map, pairs of font file and the glyphs
+    /// taken from it
+    pub fn new(glyph_map: &[(&str, &str)], tile_px: u32) -> io::Result<OffscreenRenderer> {
+        let mut fonts = Vec::new();
+        for (font_name, glyphs) in glyph_map {
+            let bytes = std::fs::read(Path::new(ASSET_DIR).join(font_name))?;
+            let font = Font::from_bytes(bytes).map_err(|err| io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("could not load {}: {}", font_name, err)))?;
+            fonts.push((font, String::from(*glyphs)));
+        }
+        Ok(OffscreenRenderer { fonts, tile_px })
+    }
+
+    /// Draws the glyph into the tile at column, row. Glyphs missing from
+    /// the glyph map are skipped, like the Tileset does.
+    pub fn draw_glyph(&self, canvas: &mut Canvas, column: u32, row: u32,
+                      glyph: char, color: Color) {
+        let font = match self.fonts.iter().find(|(_, glyphs)| glyphs.contains(glyph)) {
+            Some((font, _)) => font,
+            None => return,
+        };
+        let scale = Scale::uniform(self.tile_px as f32);
+        let origin = (column * self.tile_px, row * self.tile_px);
+        let ascent = font.v_metrics(scale).ascent;
+        let glyph = font.glyph(glyph).scaled(scale)
+            .positioned(point(origin.0 as f32, origin.1 as f32 + ascent));
+        if let Some(bounds) = glyph.pixel_bounding_box() {
+            glyph.draw(|x, y, coverage| {
+                let px = bounds.min.x + x as i32;
+                let py = bounds.min.y + y as i32;
+                // keep glyphs inside their own tile
+                if px >= origin.0 as i32 && py >= origin.1 as i32
+                   && px < (origin.0 + self.tile_px) as i32
+                   && py < (origin.1 + self.tile_px) as i32 {
+                    canvas.blend(px as u32, py as u32, color, coverage);
+                }
+            });
+        }
+    }
+}
+
+/// Renders the tiles and entities of an area the way draw_map shows them,
+/// one tile_px sized cell per tile
+pub fn render_area(renderer: &OffscreenRenderer, map: &mut GameMap,
+                   entities: &Entities, color_scheme: &ColorScheme,
+                   area: Area) -> Canvas {
+    let tile_px = renderer.tile_px;
+    let mut canvas = Canvas::new(
+        area.width * tile_px, area.height * tile_px,
+        Color::from_hex(color_scheme.get_color_code(&ColorName::Void)));
+
+    let wall_glyphs = map.connected_wall_glyphs(
+        area.x, area.y, area.width, area.height, area.z);
+    for (x, y, z) in area.tiles() {
+        let tile = map.get_tile(x, y, z);
+        let (column, row) = (x - area.x, y - area.y);
+        let i = (column + row * area.width) as usize;
+        let glyph = wall_glyphs[i].unwrap_or(tile.glyph);
+        let color = Color::from_hex(color_scheme.get_color_code(&tile.color));
+        renderer.draw_glyph(&mut canvas, column, row, glyph, color);
+    }
+
+    for (_, entity) in entities.iter().filter(|(_, entity)| entity.depth == area.z) {
+        let color = Color::from_hex(color_scheme.get_color_code(&entity.color));
+        for (x, y) in entity.cells() {
+            // areas may run past the east edge of the world and wrap around
+            let column = map.column_from(x, area.x);
+            if column >= area.width || y < area.y || y >= area.y + area.height {
+                continue;
+            }
+            let row = y - area.y;
+            // entities cover the tiles they stand on
+            canvas.fill_rect(
+                column * tile_px, row * tile_px, tile_px, tile_px,
+                Color::from_hex(color_scheme.get_color_code(&ColorName::Void)));
+            renderer.draw_glyph(&mut canvas, column, row, entity.glyph, color);
+        }
+    }
+
+    canvas
+}
+
+/// Renders an area with one pixel per block by block square of tiles,
+/// colored like the far zoom draws them, with entities marked on top
+pub fn render_thumbnail(map: &mut GameMap, entities: &Entities,
+                        color_scheme: &ColorScheme, area: Area, block: u32) -> Canvas {
+    let color = |name: &ColorName| Color::from_hex(color_scheme.get_color_code(name));
+    let mut canvas = Canvas::new(area.width / block, area.height / block, color(&ColorName::Void));
+
+    for py in 0..canvas.height {
+        for px in 0..canvas.width {
+            let colors: Vec<Color> = (0..block * block).map(|i| {
+                let (x, y) = (area.x + px * block + i % block, area.y + py * block + i / block);
+                let tile = map.get_tile(x, y, area.z);
+                color(&tile.color)
+            }).collect();
+            canvas.blend(px, py, average_color(&colors), 1.0);
+        }
+    }
+
+    for (_, entity) in entities.iter().filter(|(_, entity)| entity.depth == area.z) {
+        for (x, y) in entity.cells() {
+            let column = map.column_from(x, area.x);
+            if column < area.width && y >= area.y && y < area.y + area.height {
+                canvas.blend(column / block, (y - area.y) / block, color(&entity.color), 1.0);
+            }
+        }
+    }
+
+    canvas
+}
+
+/// Plots the values as a line across the part of the canvas at x, y, the
+/// first on the left and the last on the right, the highest of them along
+/// its top and 0 along its bottom
+pub fn plot(canvas: &mut Canvas, (x, y, width, height): (u32, u32, u32, u32), values: &[u32], color: Color) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let highest = values.iter().copied().max().unwrap_or(0).max(1) as i64;
+    let last = values.len().saturating_sub(1).max(1) as i64;
+    let points: Vec<(i64, i64)> = values.iter().enumerate().map(|(index, &value)| (
+        x as i64 + index as i64 * (width as i64 - 1) / last,
+        y as i64 + (height as i64 - 1) - value as i64 * (height as i64 - 1) / highest,
+    )).collect();
+    if let [(px, py)] = points[..] {
+        canvas.blend(px as u32, py as u32, color, 1.0);
+    }
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1);
+        for step in 0..=steps {
+            let px = x0 + (x1 - x0) * step / steps;
+            let py = y0 + (y1 - y0) * step / steps;
+            canvas.blend(px as u32, py as u32, color, 1.0);
+        }
+    }
+}
+
+/// A file name that doesn't clash with earlier exports, like
+/// screenshot-1589462400.png
+pub fn timestamped_file_name(prefix: &str, extension: &str) -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    format!("{}-{}.{}", prefix, seconds, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_blend() {
+        let mut canvas = Canvas::new(2, 1, Color::BLACK);
+        canvas.blend(1, 0, Color::WHITE, 0.5);
+        canvas.blend(5, 5, Color::WHITE, 1.0);
+        println!("{:?}", canvas.pixels);
+        assert_eq!(canvas.pixels, vec![0, 0, 0, 255, 128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn test_plot_spans_its_area() {
+        let mut canvas = Canvas::new(5, 4, Color::BLACK);
+        plot(&mut canvas, (1, 1, 3, 3), &[0, 4, 2], Color::WHITE);
+        let lit: Vec<(u32, u32)> = (0..canvas.height)
+            .flat_map(|y| (0..canvas.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| canvas.pixels[((x + y * canvas.width) * 4) as usize] == 255)
+            .collect();
+        println!("{:?}", lit);
+        assert_eq!(lit, vec![(2, 1), (1, 2), (3, 2), (1, 3)]);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game_map::{GameMap, MAX_FLUID};
+use crate::mods::{Content, CropDef, ItemDef};
+
+/// Ticks between crops growing
+pub const GROW_INTERVAL: u64 = 10;
+/// Deepest water a crop stands in, any deeper and it drowns
+const MAX_FLOOD: u8 = MAX_FLUID / 2;
+/// How fast a crop grows on a sunlit, watered plot
+const BEST_RATE: u64 = 4;
+/// Furthest a dug channel carries water to soil, in tiles
+pub const CHANNEL_REACH: u32 = 8;
+
+/// Something planted on a farm plot tile
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Crop {
+    /// Id of its crop definition
+    pub kind: String,
+    /// Growth so far, it's ripe at BEST_RATE times its grow ticks
+    pub progress: u64,
+}
+
+impl Crop {
+
+    pub fn new(kind: &str) -> Crop {
+        Crop { kind: String::from(kind), progress: 0 }
+    }
+
+    /// Grows it for a GROW_INTERVAL at the tile's rate
+    pub fn grow(&mut self, rate: u64) {
+        self.progress += GROW_INTERVAL * rate;
+    }
+
+    pub fn is_ripe(&self, crop: &CropDef) -> bool {
+        self.progress >= crop.grows * BEST_RATE
+    }
+}
+
+/// Whether the tile is soil something can be planted in, open ground under
+/// the sky or mud underground where water reaches it
+pub fn is_soil(map: &mut GameMap, x: u32, y: u32, z: u32) -> bool {
+    let tile = map.get_tile(x, y, z);
+    !tile.is_solid() && tile.fluid <= MAX_FLOOD && (map.is_sunlit(z) || is_irrigated(map, x, y, z))
+}
+
+/// How fast crops grow on the tile, light and water each double it. It's
+/// 0 where nothing grows.
+pub fn growth_rate(map: &mut GameMap, x: u32, y: u32, z: u32) -> u64 {
+    if !is_soil(map, x, y, z) {
+        return 0;
+    }
+    let light = if map.is_sunlit(z) { 2 } else { 1 };
+    let water = if is_irrigated(map, x, y, z) { 2 } else { 1 };
+    light * water
+}
+
+/// Whether water stands on the tile or next to it, or reaches it along a
+/// channel dug from it within CHANNEL_REACH tiles
+fn is_irrigated(map: &mut GameMap, x: u32, y: u32, z: u32) -> bool {
+    let mut seen = vec![(x, y)];
+    let mut frontier = vec![(x, y)];
+    for _ in 0..=CHANNEL_REACH {
+        let mut next = Vec::new();
+        for (cx, cy) in frontier {
+            if map.get_tile(cx, cy, z).fluid > 0 {
+                return true;
+            }
+            for (nx, ny, nz) in map.neighbors(cx, cy, z) {
+                let tile = map.get_tile(nx, ny, nz);
+                if tile.fluid > 0 {
+                    return true;
+                }
+                if tile.dug && !tile.is_solid() && !seen.contains(&(nx, ny)) {
+                    seen.push((nx, ny));
+                    next.push((nx, ny));
+                }
+            }
+        }
+        frontier = next;
+    }
+    false
+}
+
+/// A kind of crop with the food harvested from it
+#[derive(Clone, Debug)]
+pub struct Seed {
+    pub crop: CropDef,
+    pub produce: ItemDef,
+}
+
+/// The crops of the content packs, which every game has to load the same
+#[derive(Clone, Debug, Default)]
+pub struct Seeds {
+    seeds: Vec<Seed>,
+}
+
+impl Seeds {
+
+    /// Crops whose produce no pack defines can't be grown
+    pub fn new(content: &Content) -> Seeds {
+        Seeds::with_seeds(content.crops.iter()
+            .filter_map(|crop| {
+                let produce = content.items.get(&crop.produces)?.clone();
+                Some(Seed { crop: crop.clone(), produce })
+            })
+            .collect())
+    }
+
+    pub fn with_seeds(seeds: Vec<Seed>) -> Seeds {
+        Seeds { seeds }
+    }
+
+    pub fn seed(&self, kind: &str) -> Option<&Seed> {
+        self.seeds.iter().find(|seed| seed.crop.id == kind)
+    }
+
+    /// What gets planted on a plot, the first crop by id that grows in the
+    /// light there. Underground crops are only planted in the dark.
+    pub fn for_plot(&self, sunlit: bool) -> Option<&Seed> {
+        self.seeds.iter().find(|seed| seed.crop.underground != sunlit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_scheme::ColorName;
+    use crate::game_map::TileKind;
+
+    #[test]
+    fn test_crops_grow_faster_with_light_and_water() {
+        let mut map = GameMap::with_seed(3);
+        let (x, y, z) = (40, 40, 20);
+        for (tx, ty, tz) in [(x, y, z), (x + 1, y, z), (x + 5, y, z)].iter().copied() {
+            let mut tile = map.get_tile(tx, ty, tz);
+            tile.dig();
+            tile.set_fluid(0);
+            map.set_tile(tx, ty, tz, tile);
+        }
+        for (nx, ny, nz) in map.neighbors(x + 5, y, z) {
+            let mut wall = map.get_tile(nx, ny, nz);
+            wall.kind = TileKind::Wall;
+            wall.set_fluid(0);
+            map.set_tile(nx, ny, nz, wall);
+        }
+        let mut pond = map.get_tile(x + 1, y, z);
+        pond.set_fluid(MAX_FLUID);
+        map.set_tile(x + 1, y, z, pond);
+
+        // underground only the watered mud grows anything
+        assert!(!map.is_sunlit(z));
+        assert!(is_soil(&mut map, x, y, z));
+        assert!(!is_soil(&mut map, x + 1, y, z));
+        assert!(!is_soil(&mut map, x + 5, y, z));
+        assert_eq!(growth_rate(&mut map, x, y, z), 2);
+        assert_eq!(growth_rate(&mut map, x + 5, y, z), 0);
+        // until a channel is dug to water
+        for cy in y - 4..y {
+            let mut channel = map.get_tile(x + 5, cy, z);
+            channel.dig();
+            channel.set_fluid(if cy == y - 4 { MAX_FLUID } else { 0 });
+            map.set_tile(x + 5, cy, z, channel);
+        }
+        assert!(is_soil(&mut map, x + 5, y, z));
+        // while under the sky dry ground will do
+        let mut field = map.get_tile(x, y, 0);
+        field.dig();
+        field.set_fluid(0);
+        map.set_tile(x, y, 0, field);
+        assert!(is_soil(&mut map, x, y, 0));
+
+        let mushroom = CropDef {
+            id: String::from("mushroom"),
+            name: String::from("Mushroom"),
+            glyph: '♣',
+            color: ColorName::Fg,
+            produces: String::from("mushroom"),
+            grows: 100,
+            harvest: 1,
+            underground: true,
+        };
+        let mut crop = Crop::new("mushroom");
+        let mut ticks = 0;
+        while !crop.is_ripe(&mushroom) {
+            crop.grow(growth_rate(&mut map, x, y, z));
+            ticks += GROW_INTERVAL;
+        }
+        println!("ripe after {} ticks", ticks);
+        assert_eq!(ticks, mushroom.grows * 2);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_map::GameMap;
+
+/// Ticks between fires burning down and spreading
+pub const FIRE_INTERVAL: u64 = 5;
+/// Ticks a fire burns on a tile with nothing on it to feed it, long enough
+/// to catch whatever is next to it
+pub const SPARK_FUEL: u64 = 20;
+/// Ticks a crop feeds a fire for
+pub const CROP_FUEL: u64 = 60;
+/// Tiles smoke fills around a fire, it hides whatever is in it
+pub const SMOKE_RADIUS: u32 = 2;
+/// Furthest colonists look for water to fight a fire with, in tiles
+pub const WATER_REACH: u32 = 16;
+
+/// Every fire burning, with the ticks of fuel each has left
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fires {
+    burning: BTreeMap<(u32, u32, u32), u64>,
+}
+
+impl Fires {
+
+    pub fn new() -> Fires {
+        Fires::default()
+    }
+
+    /// Sets the tile alight with fuel to burn, false if it already is or
+    /// there's nothing to burn
+    pub fn ignite(&mut self, x: u32, y: u32, z: u32, fuel: u64) -> bool {
+        if fuel == 0 || self.is_burning(x, y, z) {
+            return false;
+        }
+        self.burning.insert((x, y, z), fuel);
+        true
+    }
+
+    /// False if there was no fire there
+    pub fn put_out(&mut self, x: u32, y: u32, z: u32) -> bool {
+        self.burning.remove(&(x, y, z)).is_some()
+    }
+
+    pub fn is_burning(&self, x: u32, y: u32, z: u32) -> bool {
+        self.burning.contains_key(&(x, y, z))
+    }
+
+    pub fn tiles(&self) -> Vec<(u32, u32, u32)> {
+        self.burning.keys().cloned().collect()
+    }
+
+    /// The burning tiles on level z
+    pub fn on(&self, z: u32) -> Vec<(u32, u32)> {
+        self.burning.keys().filter(|&&(_, _, fz)| fz == z).map(|&(x, y, _)| (x, y)).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.burning.is_empty()
+    }
+
+    /// Whether smoke fills the tile, it's within SMOKE_RADIUS of a fire
+    pub fn is_smoky(&self, map: &GameMap, x: u32, y: u32, z: u32) -> bool {
+        let (max_x, _, _) = map.size();
+        self.burning.keys().any(|&(fx, fy, fz)| {
+            // the world wraps around, so it's near going either way
+            let dx = map.column_from(x, fx).min(max_x - map.column_from(x, fx));
+            fz == z && dx <= SMOKE_RADIUS && fy.abs_diff(y) <= SMOKE_RADIUS
+        })
+    }
+
+    /// Uses up FIRE_INTERVAL ticks of every fire's fuel, returning the
+    /// tiles that burnt out
+    pub fn burn_down(&mut self) -> Vec<(u32, u32, u32)> {
+        let mut out = Vec::new();
+        self.burning.retain(|&tile, fuel| {
+            *fuel = fuel.saturating_sub(FIRE_INTERVAL);
+            if *fuel == 0 {
+                out.push(tile);
+            }
+            *fuel > 0
+        });
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fires_burn_through_their_fuel() {
+        let map = GameMap::with_seed(10);
+        let mut fires = Fires::new();
+        assert!(fires.ignite(10, 10, 40, SPARK_FUEL));
+        assert!(!fires.ignite(10, 10, 40, CROP_FUEL));
+        assert!(!fires.ignite(11, 10, 40, 0));
+        assert!(fires.ignite(0, 10, 40, CROP_FUEL));
+
+        assert!(fires.is_smoky(&map, 12, 12, 40));
+        assert!(!fires.is_smoky(&map, 13, 10, 40));
+        assert!(!fires.is_smoky(&map, 10, 10, 41));
+        // smoke drifts around the edge of the world
+        let (max_x, _, _) = map.size();
+        assert!(fires.is_smoky(&map, max_x - 2, 10, 40));
+
+        let mut burnt = Vec::new();
+        while !fires.is_empty() {
+            burnt.extend(fires.burn_down());
+        }
+        println!("{:?}", burnt);
+        assert_eq!(burnt, vec![(10, 10, 40), (0, 10, 40)]);
+        assert!(!fires.put_out(0, 10, 40));
+    }
+}
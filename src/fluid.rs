@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use crate::game_map::{GameMap, MAX_FLUID};
+
+/// Ticks between the units of water a pump lifts
+pub const PUMP_INTERVAL: u64 = 20;
+
+/// Moves water between tiles. Only tiles whose surroundings changed are
+/// simulated, lakes that were never disturbed cost nothing.
+pub struct FluidSim {
+    active: BTreeSet<(u32, u32, u32)>,
+}
+
+impl FluidSim {
+
+    pub fn new() -> FluidSim {
+        FluidSim {
+            active: BTreeSet::new(),
+        }
+    }
+
+    /// Positions that will be simulated on the next step
+    pub fn active(&self) -> Vec<(u32, u32, u32)> {
+        self.active.iter().cloned().collect()
+    }
+
+    /// Wakes up the tile at (x, y, z) and every tile around it, call this
+    /// whenever the tile changes
+    pub fn activate_around(&mut self, map: &GameMap, x: u32, y: u32, z: u32) {
+        self.active.insert((x, y, z));
+        self.active.extend(map.neighbors(x, y, z));
+        if z > 0 {
+            self.active.insert((x, y, z - 1));
+        }
+    }
+
+    /// Lifts a unit of water from under the pump at (x, y, z) onto the
+    /// shallowest tile next to it with rock under it to hold the water up,
+    /// returning where it went. None if there's no water under the pump or
+    /// nowhere for it to go.
+    pub fn pump(&mut self, map: &mut GameMap, x: u32, y: u32, z: u32) -> Option<(u32, u32, u32)> {
+        let (_, _, max_z) = map.size();
+        if z + 1 >= max_z {
+            return None;
+        }
+        let mut source = map.get_tile(x, y, z + 1);
+        if source.is_solid() || source.fluid == 0 {
+            return None;
+        }
+        let mut target: Option<((u32, u32, u32), u8)> = None;
+        for (nx, ny, nz) in map.neighbors(x, y, z) {
+            let tile = map.get_tile(nx, ny, nz);
+            let held = nz + 1 >= max_z || map.get_tile(nx, ny, nz + 1).is_solid();
+            if !tile.is_solid() && held && tile.fluid < MAX_FLUID
+               && target.is_none_or(|(_, fluid)| tile.fluid < fluid) {
+                target = Some(((nx, ny, nz), tile.fluid));
+            }
+        }
+        let ((tx, ty, tz), _) = target?;
+        let mut tile = map.get_tile(tx, ty, tz);
+        tile.set_fluid(tile.fluid + 1);
+        source.set_fluid(source.fluid - 1);
+        map.set_tile(tx, ty, tz, tile);
+        map.set_tile(x, y, z + 1, source);
+        self.activate_around(map, tx, ty, tz);
+        self.activate_around(map, x, y, z + 1);
+        Some((tx, ty, tz))
+    }
+
+    /// Advances the simulation by one tick. Water first falls to the level
+    /// below, whatever can't fall spreads out to shallower neighbors.
+    /// Returns the tiles water flowed into.
+    pub fn step(&mut self, map: &mut GameMap) -> Vec<(u32, u32, u32)> {
+        let (_, _, max_z) = map.size();
+        let active = std::mem::take(&mut self.active);
+        let mut flowed = Vec::new();
+
+        for (x, y, z) in active {
+            let mut tile = map.get_tile(x, y, z);
+            if tile.is_solid() || tile.fluid == 0 {
+                continue;
+            }
+
+            if z + 1 < max_z {
+                let mut below = map.get_tile(x, y, z + 1);
+                if !below.is_solid() && below.fluid < MAX_FLUID {
+                    let amount = tile.fluid.min(MAX_FLUID - below.fluid);
+                    below.set_fluid(below.fluid + amount);
+                    tile.set_fluid(tile.fluid - amount);
+                    map.set_tile(x, y, z + 1, below);
+                    map.set_tile(x, y, z, tile);
+                    self.activate_around(map, x, y, z);
+                    self.active.insert((x, y, z + 1));
+                    flowed.push((x, y, z + 1));
+                    continue;
+                }
+            }
+
+            let mut spread = false;
+            for (nx, ny, nz) in map.neighbors(x, y, z) {
+                let mut neighbor = map.get_tile(nx, ny, nz);
+                if !neighbor.is_solid() && neighbor.fluid + 1 < tile.fluid {
+                    neighbor.set_fluid(neighbor.fluid + 1);
+                    tile.set_fluid(tile.fluid - 1);
+                    map.set_tile(nx, ny, nz, neighbor);
+                    self.activate_around(map, nx, ny, nz);
+                    flowed.push((nx, ny, nz));
+                    spread = true;
+                }
+            }
+            if spread {
+                map.set_tile(x, y, z, tile);
+                self.active.insert((x, y, z));
+            }
+        }
+        flowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::fixtures::walled_room;
+    use crate::game_map::TileKind;
+    use crate::worldgen::WorldGenConfig;
+
+    fn pour(sim: &mut FluidSim, map: &mut GameMap, x: u32, y: u32, z: u32, fluid: u8) {
+        let mut tile = map.get_tile(x, y, z);
+        tile.set_fluid(fluid);
+        map.set_tile(x, y, z, tile);
+        sim.activate_around(map, x, y, z);
+    }
+
+    #[test]
+    fn test_water_table_floods_caves_below_it() {
+        // with every tile generated open what's flooded is down to the
+        // water table alone
+        let open = WorldGenConfig { open_threshold: -1.0, ..WorldGenConfig::default() };
+        let mut map = GameMap::with_seed(10).with_worldgen(open);
+        let water_table = map.water_table;
+        assert!(water_table > 0);
+        let (mut flooded, mut dry) = (0, 0);
+        for y in 1000..1032 {
+            for x in 1000..1032 {
+                let below = map.get_tile(x, y, water_table);
+                if below.kind == TileKind::Floor && below.gas == 0 {
+                    assert_eq!(below.fluid, MAX_FLUID, "dry cave at ({}, {}) under the water table", x, y);
+                    flooded += 1;
+                }
+                let above = map.get_tile(x, y, water_table - 1);
+                if above.kind == TileKind::Floor && above.fluid == 0 {
+                    dry += 1;
+                }
+            }
+        }
+        println!("water table {}: {} flooded tiles under it, {} dry above", water_table, flooded, dry);
+        assert!(flooded > 0 && dry > 0);
+    }
+
+    #[test]
+    fn test_water_falls_before_it_spreads() {
+        let mut map = GameMap::with_seed(10);
+        let mut sim = FluidSim::new();
+        let (x, y, z) = (1000, 1000, 40);
+        walled_room(&mut map, x, y, z, 5, 5);
+        // a hole in the middle of the floor
+        let mut hole = map.get_tile(x + 2, y + 2, z + 1);
+        hole.dig();
+        map.set_tile(x + 2, y + 2, z + 1, hole);
+        pour(&mut sim, &mut map, x + 2, y + 2, z, 3);
+
+        let flowed = sim.step(&mut map);
+        println!("{:?}", flowed);
+        assert_eq!(flowed, vec![(x + 2, y + 2, z + 1)]);
+        assert_eq!(map.get_tile(x + 2, y + 2, z).fluid, 0);
+        assert_eq!(map.get_tile(x + 2, y + 2, z + 1).fluid, 3);
+    }
+
+    #[test]
+    fn test_water_spreads_out_and_settles() {
+        let mut map = GameMap::with_seed(10);
+        let mut sim = FluidSim::new();
+        let (x, y, z) = (1000, 1000, 40);
+        // a corridor 6 tiles long
+        walled_room(&mut map, x, y, z, 8, 3);
+        pour(&mut sim, &mut map, x + 1, y + 1, z, MAX_FLUID);
+
+        for _ in 0..100 {
+            sim.step(&mut map);
+        }
+        let depths: Vec<u8> = (x..x + 8).map(|tx| map.get_tile(tx, y + 1, z).fluid).collect();
+        println!("{:?}", depths);
+        // none of it is lost or gets through the walls, and it comes to rest
+        // with no tile more than one deeper than the next
+        assert_eq!(depths.iter().map(|&depth| depth as u32).sum::<u32>(), MAX_FLUID as u32);
+        assert_eq!((depths[0], depths[7]), (0, 0));
+        assert!(depths[1] < MAX_FLUID && depths[3] > 0);
+        assert!(depths[1..7].windows(2).all(|pair| pair[0].abs_diff(pair[1]) <= 1));
+        assert!(sim.active().is_empty());
+    }
+}
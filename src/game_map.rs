@@ -1,12 +1,27 @@
 use oorandom;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 
-use crate::color_scheme::{ColorName, get_stone_color, get_floor_color};
-use noise::{Billow, MultiFractal, Seedable, NoiseFn, ScalePoint};
+use crate::autotile;
+use crate::billow::RowBillow;
+use crate::chunk_cache::{ChunkCache, Sample};
+use crate::color_scheme::{ColorName, ColorRamp, FLOOR_RAMP, STONE_RAMP};
+use crate::desync::Fnv;
+use crate::difficulty::Difficulty;
+use crate::logging;
+use crate::worldgen::WorldGenConfig;
+use noise::{Perlin, Seedable, NoiseFn, ScalePoint};
 use quicksilver::prelude::*;
+use rayon::prelude::*;
+
+/// Width of the band along the east edge of the world where the rock blends
+/// into the rock at the west edge, so the seam of the wrap doesn't show
+const SEAM_WIDTH: u32 = 256;
 
 pub struct GameMap {
-    map: HashMap<u32, HashMap<u32, HashMap<u32, HashMap<u32, Vec<Tile>>>>>,
+    map: HashMap<u32, HashMap<u32, HashMap<u32, Chunk>>>,
     pub chunk_size: u32,
     pub max_chuncks_x: u32,
     pub max_chuncks_y: u32,
@@ -14,10 +29,41 @@ pub struct GameMap {
     pub surface_level: u32,
     pub level_thickness: u32,
     pub random_seed: u32,
+    /// Levels at or below this z are flooded wherever the rock is open
+    pub water_table: u32,
+    /// Levels at or below this z may hold pockets of toxic gas
+    pub gas_depth: u32,
+    pub difficulty: Difficulty,
+    /// The noise settings the rock is generated with
+    pub worldgen: WorldGenConfig,
+    /// Tiles changed since generation, re-applied when their chunk is
+    /// regenerated and written to saves
+    edits: HashMap<(u32, u32, u32), Tile>,
+    /// The sum of the hashes of the edits, kept up as they change so
+    /// checksums don't go through all of them
+    edits_hash: u64,
+    /// Tiles that turned solid or open since take_reshaped last ran, the
+    /// path graph redoes the chunks they're in
+    reshaped: Vec<(u32, u32, u32)>,
+    /// Handcrafted rock generated in place of the noise, for scenarios
+    layout: Option<Layout>,
+    /// Tiles any colonist has seen, a bit for each tile of the chunks with
+    /// any seen in them, by chunk
+    explored: HashMap<(u32, u32, u32), Vec<u64>>,
+    /// The sum of the hashes of the explored words, like edits_hash
+    explored_hash: u64,
+    /// Chunks generated before, read back instead of sampling the noise
+    cache: Option<ChunkCache>,
+    /// Where the cache is kept, for moving it along with new settings
+    cache_root: Option<PathBuf>,
 }
 
 impl GameMap {
     pub fn new() -> GameMap {
+        GameMap::with_seed(oorandom::Rand32::new(10).rand_u32())
+    }
+
+    pub fn with_seed(random_seed: u32) -> GameMap {
         
         //In meters
         let planet_circumference: u32 = 20000000;
@@ -30,6 +76,11 @@ impl GameMap {
         let max_chuncks_y: u32 = planet_circumference / chunk_size;
         let max_chuncks_z: u32 = planet_crust_thickness /level_thickness / chunk_size;
         //let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(10);
+        let mut rng = oorandom::Rand32::new(random_seed as u64);
+        let water_table_depth = surface_level + rng.rand_range(0..600);
+        let water_table = water_table_depth / level_thickness;
+        let difficulty = Difficulty::default();
+        let gas_depth = water_table + difficulty.gas_offset();
         GameMap {
             map: HashMap::with_capacity(chunk_size as usize),
             chunk_size,
@@ -38,115 +89,449 @@ impl GameMap {
             max_chuncks_z,
             surface_level,
             level_thickness,
-            random_seed: oorandom::Rand32::new(10).rand_u32(),
+            random_seed,
+            water_table,
+            gas_depth,
+            difficulty,
+            worldgen: WorldGenConfig::default(),
+            edits: HashMap::new(),
+            edits_hash: 0,
+            reshaped: Vec::new(),
+            layout: None,
+            explored: HashMap::new(),
+            explored_hash: 0,
+            cache: None,
+            cache_root: None,
+        }
+    }
+
+    /// Sets how hard the colony has it, before any of the map is generated
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> GameMap {
+        self.gas_depth = self.water_table + difficulty.gas_offset();
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Generates the layout's tiles instead of noise, before any of the
+    /// map is generated
+    pub fn with_layout(mut self, layout: Layout) -> GameMap {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Generates the rock with the noise settings, before any of the map
+    /// is generated
+    pub fn with_worldgen(mut self, worldgen: WorldGenConfig) -> GameMap {
+        self.worldgen = worldgen;
+        self
+    }
+
+    /// Keeps the chunks it generates in a directory under root for its
+    /// seed, difficulty and noise settings, and reads them back from there
+    /// rather than generating them again. Set after the difficulty and the
+    /// noise settings.
+    pub fn with_chunk_cache(mut self, root: impl AsRef<Path>) -> GameMap {
+        self.cache_root = Some(root.as_ref().to_path_buf());
+        self.cache = Some(ChunkCache::new(root, &self.cache_key()));
+        self
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{}-{}{}", self.random_seed, self.difficulty.name().to_lowercase(), self.worldgen.cache_suffix())
+    }
+
+    /// Switches to new noise settings and drops the chunks nothing's been
+    /// changed in, so they're generated with them as they're next looked
+    /// at. Chunks with changes keep their rock. Returns how many dropped.
+    pub fn regenerate(&mut self, worldgen: WorldGenConfig) -> usize {
+        self.worldgen = worldgen;
+        if let Some(root) = &self.cache_root {
+            self.cache = Some(ChunkCache::new(root, &self.cache_key()));
+        }
+        let chunk_size = self.chunk_size;
+        let changed: HashSet<(u32, u32, u32)> = self.edits.keys()
+            .map(|&(x, y, z)| {
+                let (x_min, _, y_min, _, z_min, _) = GameMap::get_chunck_boundries(x, y, z, chunk_size);
+                (x_min, y_min, z_min)
+            })
+            .collect();
+        let mut dropped = Vec::new();
+        for (&x, x_map) in self.map.iter_mut() {
+            for (&y, y_map) in x_map.iter_mut() {
+                y_map.retain(|&z, _| {
+                    // chunks are kept by their center
+                    let (x_min, _, y_min, _, z_min, _) = GameMap::get_chunck_boundries(x, y, z, chunk_size);
+                    let keep = changed.contains(&(x_min, y_min, z_min));
+                    if !keep {
+                        dropped.push((x_min, y_min, z_min));
+                    }
+                    keep
+                });
+            }
         }
+        // the path graph and regions redo each chunk from a tile in it
+        self.reshaped.extend(dropped.iter().copied());
+        dropped.len()
+    }
+
+    /// Size of the world in tiles along each axis
+    pub fn size(&self) -> (u32, u32, u32) {
+        (self.max_chuncks_x * self.chunk_size,
+         self.max_chuncks_y * self.chunk_size,
+         self.max_chuncks_z * self.chunk_size)
+    }
+
+    /// The world wraps around east to west, this maps any x, including
+    /// ones past either edge, onto the world
+    pub fn wrap_x(&self, x: i64) -> u32 {
+        let (max_x, _, _) = self.size();
+        x.rem_euclid(max_x as i64) as u32
+    }
+
+    /// How many tiles east of start x is, going around the world if x is
+    /// west of start
+    pub fn column_from(&self, x: u32, start: u32) -> u32 {
+        self.wrap_x(x as i64 - start as i64)
     }
 
+    /// The tile at (x, y, z), x past the edges of the world wraps around
     pub fn get_tile(&mut self, x: u32, y:u32, z:u32) -> Tile {
-        //println!("get_tile x: {:?}, y: {:?}, z: {:?}", x, y, z);
-        //println!("map.keys: {:?}", self.map);
+        let x = self.wrap_x(x as i64);
+        let chunk = self.chunk_mut(x, y, z);
+        chunk.get(chunk.index(x, y, z))
+    }
+
+    /// Whether the level is open to the sky, only the top one is
+    pub fn is_sunlit(&self, z: u32) -> bool {
+        z == 0
+    }
+
+    /// The four orthogonal neighbors of (x, y) on level z, wrapping around
+    /// east to west. There is nothing past the north and south edges.
+    pub fn neighbors(&self, x: u32, y: u32, z: u32) -> Vec<(u32, u32, u32)> {
+        let (_, max_y, _) = self.size();
+        let mut neighbors = Vec::with_capacity(4);
+        neighbors.push((self.wrap_x(x as i64 - 1), y, z));
+        if y > 0 { neighbors.push((x, y - 1, z)); }
+        neighbors.push((self.wrap_x(x as i64 + 1), y, z));
+        if y + 1 < max_y { neighbors.push((x, y + 1, z)); }
+        neighbors
+    }
+
+    /// Replaces the tile at (x, y, z), remembering the change so it 
+    /// survives saving and chunk regeneration
+    pub fn set_tile(&mut self, x: u32, y: u32, z: u32, tile: Tile) {
+        let x = self.wrap_x(x as i64);
+        if self.get_tile(x, y, z).is_solid() != tile.is_solid() {
+            self.reshaped.push((x, y, z));
+        }
+        let chunk = self.chunk_mut(x, y, z);
+        chunk.set(chunk.index(x, y, z), &tile);
+        self.edits_hash = self.edits_hash.wrapping_add(edit_hash((x, y, z), &tile));
+        if let Some(old) = self.edits.insert((x, y, z), tile) {
+            self.edits_hash = self.edits_hash.wrapping_sub(edit_hash((x, y, z), &old));
+        }
+    }
+
+    /// A hash of every tile changed since generation, the same whatever
+    /// order they were changed in
+    pub fn edits_hash(&self) -> u64 {
+        self.edits_hash
+    }
+
+    /// The tiles that turned solid or open since it was last called
+    pub fn take_reshaped(&mut self) -> Vec<(u32, u32, u32)> {
+        std::mem::take(&mut self.reshaped)
+    }
+
+    /// Every tile changed since generation, with where it is, in order of
+    /// position so what's built from them is the same in every game
+    pub fn edited_tiles(&self) -> Vec<TileEdit> {
+        let mut edits: Vec<TileEdit> = self.edits.iter()
+            .map(|(&(x, y, z), &tile)| TileEdit { x, y, z, tile })
+            .collect();
+        edits.sort_by_key(|edit| (edit.z, edit.y, edit.x));
+        edits
+    }
+
+    /// Restores tiles changed in a previous session
+    pub fn apply_edits(&mut self, edits: Vec<TileEdit>) {
+        for edit in edits {
+            self.set_tile(edit.x, edit.y, edit.z, edit.tile);
+        }
+    }
+
+    /// Marks every tile within radius of (x, y, z) as seen
+    pub fn reveal_around(&mut self, x: u32, y: u32, z: u32, radius: u32) {
+        let (_, max_y, _) = self.size();
+        let radius = radius as i64;
+        for dy in -radius..=radius {
+            let ty = y as i64 + dy;
+            if ty < 0 || ty >= max_y as i64 {
+                continue;
+            }
+            for dx in -radius..=radius {
+                if dx * dx + dy * dy <= radius * radius {
+                    let tx = self.wrap_x(x as i64 + dx);
+                    self.explore(tx, ty as u32, z);
+                }
+            }
+        }
+    }
+
+    /// Marks the tile as seen
+    pub fn explore(&mut self, x: u32, y: u32, z: u32) {
+        let (chunk, bit) = self.explored_bit(x, y, z);
+        let words = (self.chunk_size * self.chunk_size).div_ceil(64) as usize;
+        let seen = self.explored.entry(chunk).or_insert_with(|| vec![0; words]);
+        let (index, old) = (bit / 64, seen[bit / 64]);
+        seen[index] |= 1 << (bit % 64);
+        if seen[index] != old {
+            self.explored_hash = self.explored_hash
+                .wrapping_sub(explored_hash(chunk, index, old))
+                .wrapping_add(explored_hash(chunk, index, seen[index]));
+        }
+    }
 
+    /// A hash of every tile seen, the same whatever order they were seen in
+    pub fn explored_hash(&self) -> u64 {
+        self.explored_hash
+    }
+
+    /// Whether any colonist has seen the tile
+    pub fn is_explored(&self, x: u32, y: u32, z: u32) -> bool {
+        let (chunk, bit) = self.explored_bit(x, y, z);
+        self.explored.get(&chunk).is_some_and(|seen| seen[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The chunk a tile is in and its bit in the chunk's explored tiles
+    fn explored_bit(&self, x: u32, y: u32, z: u32) -> ((u32, u32, u32), usize) {
+        let x = self.wrap_x(x as i64);
+        let chunk = (x / self.chunk_size, y / self.chunk_size, z);
+        let bit = (y % self.chunk_size) * self.chunk_size + x % self.chunk_size;
+        (chunk, bit as usize)
+    }
+
+    /// The tiles seen in each chunk with any seen, for saves
+    pub fn explored_chunks(&self) -> Vec<ExploredChunk> {
+        let mut chunks: Vec<ExploredChunk> = self.explored.iter()
+            .map(|(&(x, y, z), seen)| ExploredChunk { x, y, z, seen: seen.clone() })
+            .collect();
+        chunks.sort_by_key(|chunk| (chunk.z, chunk.y, chunk.x));
+        chunks
+    }
+
+    /// Marks the tiles seen in a previous session as seen, along with the
+    /// ones seen since
+    pub fn apply_explored(&mut self, chunks: Vec<ExploredChunk>) {
+        for chunk in chunks {
+            let seen = self.explored.entry((chunk.x, chunk.y, chunk.z)).or_insert_with(|| vec![0; chunk.seen.len()]);
+            for (index, (word, saved)) in seen.iter_mut().zip(chunk.seen).enumerate() {
+                let old = *word;
+                *word |= saved;
+                self.explored_hash = self.explored_hash
+                    .wrapping_sub(explored_hash((chunk.x, chunk.y, chunk.z), index, old))
+                    .wrapping_add(explored_hash((chunk.x, chunk.y, chunk.z), index, *word));
+            }
+        }
+    }
+
+    /// The chunk (x, y, z) is in, generated first if it hasn't been. x
+    /// has to be wrapped onto the world already.
+    fn chunk_mut(&mut self, x: u32, y:u32, z:u32) -> &mut Chunk {
         let chunk_size = self.chunk_size;
         let chunk_size_u = self.chunk_size as usize;
-        let (x_min, x_max, y_min, y_max, z_min, z_max) = GameMap::get_chunck_boundries(x, y, z, chunk_size);
-        //println!("x_min: {:?}, x_max: {:?}, y_min: {:?}, y_max: {:?}, z_min: {:?}, z_max: {:?}", x_min, x_max, y_min, y_max, z_min, z_max);
+        let bounds = GameMap::get_chunck_boundries(x, y, z, chunk_size);
+        let (x_min, x_max, y_min, y_max, z_min, z_max) = bounds;
         let calculate_center = |min, size| {min + size/2};
         let center_x = calculate_center(x_min, chunk_size);
         let center_y = calculate_center(y_min, chunk_size);
         let center_z = calculate_center(z_min, chunk_size);
 
-        //println!("center x: {:?}, y: {:?}, z: {:?}", center_x,center_y,center_z);
-        //println!("map.keys: {:?}", self.map.keys());
-        if !self.map.contains_key(&center_x) {
-            self.map.insert(center_x, HashMap::with_capacity(chunk_size_u));
-        }
-        let x_map = self.map.get_mut(&center_x).unwrap();
-        //println!("x_map.len: {:?}", x_map.len());
-
-        if !x_map.contains_key(&center_y) {
-            x_map.insert(center_y, HashMap::with_capacity(chunk_size_u));
-        }
-        let y_map = x_map.get_mut(&center_y).unwrap();
-        //println!("y_map.len: {:?}", y_map.len());
- 
-        if !y_map.contains_key(&center_z) {
-            y_map.insert(center_z, GameMap::generate_map_chunk(
-                    HashMap::with_capacity(chunk_size_u),
-                    x_min, x_max, 
-                    y_min, y_max, 
-                    z_min, z_max,
-                    &chunk_size, &self.level_thickness, 
-                    &self.random_seed)
-                );
-        }
-        let chunk = &y_map.get(&center_z).unwrap();
-        //println!("z_map.len: {:?}", z_map.len());
-        
-        let chunk_x = x % chunk_size;
-        let chunk_y = y % chunk_size;
-        let chunk_z = z % chunk_size;
-        let chunk_plane = &chunk.get(&chunk_z).unwrap();
-        let i = (chunk_x + chunk_y * chunk_size) as usize;
-        //println!("i: {:?}", i);
-        
-        //println!("get_tile returning tile: {:?}", map_plane[i]);
-        chunk_plane[i]
-    }
-
-    pub fn generate_map_chunk(mut map: HashMap<u32, Vec<Tile>>,
-                              x_min: u32, x_max: u32, 
-                              y_min: u32, y_max: u32, 
-                              z_min: u32, z_max: u32,
-                              &chunk_size: &u32, 
-                              &level_thickness: &u32, &random_seed: &u32,
-                              ) -> HashMap<u32, Vec<Tile>>{
-
-        //println!("chunk_size: {:?}", chunk_size);
-        //println!("x_min: {:?}", x_min);
-        //println!("x_max: {:?}", x_max);
-        //println!("y_min: {:?}", y_min);
-        //println!("y_max: {:?}", y_max);
-        //println!("z_min: {:?}", z_min);
-        //println!("z_max: {:?}", z_max);
-        let noise_gen = ScalePoint::new(Billow::new()
-            .set_seed(random_seed)
-            .set_frequency(0.0125)
-            .set_persistence(0.035)
-            ).set_scale(0.1);
-        for z in (z_min..z_max).rev() {
-            let mut z_map = Vec::with_capacity((chunk_size * chunk_size) as usize);
-            let z_depth = z * level_thickness;
-            for y in y_min..y_max {
-                for x in x_min..x_max {
-                    let val = noise_gen.get(
-                        [x as f64, y as f64, z_depth as f64])
-                        .abs();
-                    //println!("{}", val);
-                    //println!("x, y, z: {:?}, {:?}, {:?}", x, y, z);
-                    
-                    let mut tile = Tile {
-                        pos: Vector::new(x as f32, y as f32),
-                        depth: z,
-                        glyph: '#',
-                        color: get_stone_color(&val, &0.0, &0.5),
-                        val: val,
-                    };
-
-                    if val.abs() >= 0.6 {
-                        tile.glyph = '.';
-                        tile.color = get_floor_color(&val, &0.4, &1.0);
+        let generated = self.map.get(&center_x)
+            .and_then(|x_map| x_map.get(&center_y))
+            .is_some_and(|y_map| y_map.contains_key(&center_z));
+        if !generated {
+            let _span = logging::span(logging::WORLDGEN, "generating a chunk");
+            let mut chunk = self.generate_chunk(bounds);
+            for (&(ex, ey, ez), tile) in self.edits.iter() {
+                if (x_min..x_max).contains(&ex) 
+                   && (y_min..y_max).contains(&ey) 
+                   && (z_min..z_max).contains(&ez) {
+                    chunk.set(chunk.index(ex, ey, ez), tile);
+                }
+            }
+            self.map.entry(center_x).or_insert_with(|| HashMap::with_capacity(chunk_size_u))
+                .entry(center_y).or_insert_with(|| HashMap::with_capacity(chunk_size_u))
+                .insert(center_z, chunk);
+        }
+        self.map.get_mut(&center_x).unwrap()
+            .get_mut(&center_y).unwrap()
+            .get_mut(&center_z).unwrap()
+    }
+
+    /// Returns the connected wall glyph of every tile in the width x height
+    /// region starting at (x, y) on level z (row-major), or None for tiles
+    /// that are not walls. Neighbors across chunk edges and the east-west
+    /// seam are looked up like any other tile, everything past the north
+    /// and south edges of the world counts as rock.
+    pub fn connected_wall_glyphs(&mut self, x: u32, y: u32, 
+                                 width: u32, height: u32, z: u32) 
+        -> Vec<Option<char>> {
+
+        let padding = autotile::PADDING;
+        let max_y = self.max_chuncks_y * self.chunk_size;
+
+        let padded_width = width + padding * 2;
+        let padded_height = height + padding * 2;
+        let mut walls = Vec::with_capacity((padded_width * padded_height) as usize);
+        for py in 0..padded_height {
+            for px in 0..padded_width {
+                let wx = self.wrap_x(x as i64 + px as i64 - padding as i64);
+                let wy = (y + py).checked_sub(padding).filter(|&wy| wy < max_y);
+                let is_wall = match wy {
+                    Some(wy) => self.get_tile(wx, wy, z).is_wall(),
+                    None => true,
+                };
+                walls.push(is_wall);
+            }
+        }
+
+        autotile::wall_glyphs(&walls, width, height)
+    }
+
+    /// The glyph of every tile in rect on level z as plain text, one line
+    /// per row. Walls keep their generated '#' rather than the connected
+    /// glyphs draw_map shows.
+    pub fn export_ascii(&mut self, rect: Rectangle, z: u32) -> String {
+        let (x, y) = (rect.x() as u32, rect.y() as u32);
+        let (width, height) = (rect.width() as u32, rect.height() as u32);
+        let mut ascii = String::with_capacity(((width + 1) * height) as usize);
+        for ty in y..y + height {
+            for tx in x..x + width {
+                ascii.push(self.get_tile(tx, ty, z).glyph);
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    /// Generates the rock of the chunk within the boundries, from the
+    /// layout if there is one, otherwise from the cache if the chunk was
+    /// generated before, otherwise from the noise
+    fn generate_chunk(&self, bounds: (u32, u32, u32, u32, u32, u32)) -> Chunk {
+        if let Some(layout) = &self.layout {
+            return layout.generate_chunk(bounds, self.chunk_size);
+        }
+        let (x_min, _, y_min, _, z_min, _) = bounds;
+        let origin = (x_min, y_min, z_min);
+        let cached = self.cache.as_ref()
+            .and_then(|cache| cache.load(origin, self.chunk_size.pow(3) as usize));
+        let samples = match cached {
+            Some(samples) => samples,
+            None => {
+                let (world_width, _, _) = self.size();
+                let samples = GameMap::sample_chunk(
+                    bounds, self.level_thickness, self.random_seed, self.gas_depth,
+                    self.difficulty.gas_threshold(), world_width, &self.worldgen);
+                if let Some(cache) = &self.cache {
+                    if let Err(err) = cache.store(origin, &samples) {
+                        log::warn!(target: logging::WORLDGEN, "could not cache chunk {:?}: {}", origin, err);
                     }
+                }
+                samples
+            },
+        };
+        GameMap::build_chunk(&samples, bounds, self.chunk_size, self.water_table,
+                             self.worldgen.open_threshold)
+    }
+
+    /// Samples the noise for every tile of the chunk, level by level from
+    /// z_min and row by row within each
+    fn sample_chunk((x_min, x_max, y_min, y_max, z_min, z_max): (u32, u32, u32, u32, u32, u32),
+                    level_thickness: u32, random_seed: u32, gas_depth: u32,
+                    gas_threshold: f64, world_width: u32, worldgen: &WorldGenConfig) -> Vec<Sample> {
+        log::trace!(target: logging::WORLDGEN, "chunk x {}..{}, y {}..{}, z {}..{}", 
+                    x_min, x_max, y_min, y_max, z_min, z_max);
 
-                    if tile.color == ColorName::Void && tile.glyph == '#' {
-                        tile.glyph = '≈';
-                        tile.color = ColorName::Blue;
+        let noise_gen = RowBillow::new(
+            random_seed, worldgen.frequency, worldgen.persistence, worldgen.scale);
+        let gas_noise_gen = ScalePoint::new(Perlin::new()
+            .set_seed(random_seed.wrapping_add(1))
+            ).set_scale(worldgen.gas_scale);
+        // where each column falls in each octave's lattice is worked out
+        // once for the chunk, and again west of 0 for the columns that
+        // blend into it across the seam
+        let seam_start = world_width.saturating_sub(SEAM_WIDTH).max(x_min).min(x_max);
+        let columns = noise_gen.columns((x_min..x_max).map(|x| x as f64));
+        let wrapped = noise_gen.columns(
+            (seam_start..x_max).map(|x| x as f64 - world_width as f64));
+        let (noise_gen, gas_noise_gen) = (&noise_gen, &gas_noise_gen);
+        let (columns, wrapped) = (&columns, &wrapped);
+        // the rows of every level are sampled side by side and gathered
+        // back in order
+        let height = y_max - y_min;
+        (0..(z_max - z_min) * height).into_par_iter().flat_map_iter(|row| {
+            let (z, y) = (z_min + row / height, y_min + row % height);
+            let z_depth = z * level_thickness;
+            let mut vals = vec![0.0; columns.len()];
+            noise_gen.row(columns, y as f64, z_depth as f64, &mut vals);
+            if !wrapped.is_empty() {
+                let mut wrapped_vals = vec![0.0; wrapped.len()];
+                noise_gen.row(wrapped, y as f64, z_depth as f64, &mut wrapped_vals);
+                let seam = (seam_start - x_min) as usize;
+                for ((x, val), wrapped_val) in (seam_start..).zip(&mut vals[seam..]).zip(wrapped_vals) {
+                    *val = seam_blend(*val, wrapped_val, x, world_width);
+                }
+            }
+            (x_min..x_max).zip(vals).map(move |(x, val)| {
+                let val = val.abs();
+                let gas_pocket = val >= worldgen.open_threshold && z >= gas_depth && seamless_noise(
+                    gas_noise_gen, x, y as f64, z as f64, world_width) > gas_threshold;
+                Sample { val, gas_pocket }
+            })
+        }).collect()
+    }
+
+    /// The tiles of the chunk generated from its samples
+    fn build_chunk(samples: &[Sample],
+                   (x_min, x_max, y_min, y_max, z_min, z_max): (u32, u32, u32, u32, u32, u32),
+                   chunk_size: u32, water_table: u32, open_threshold: f64) -> Chunk {
+        let area = ((x_max - x_min) * (y_max - y_min)) as usize;
+        let mut chunk = Chunk::new(chunk_size);
+        for (z, plane) in (z_min..z_max).zip(samples.chunks(area)) {
+            for sample in plane {
+                let val = sample.val;
+                let mut tile = Tile {
+                    kind: TileKind::Wall,
+                    glyph: '#',
+                    color: STONE_RAMP.band(val),
+                    val: val,
+                    fluid: 0,
+                    gas: 0,
+                    dug: false,
+                };
+
+                if val >= open_threshold {
+                    tile.kind = TileKind::Floor;
+                    tile.glyph = '.';
+                    tile.color = FLOOR_RAMP.band(val);
+                    if sample.gas_pocket {
+                        tile.gas = MAX_GAS;
                     }
-                    z_map.push(tile);
+                    else if z >= water_table {
+                        tile.set_fluid(MAX_FLUID);
+                    }
+                }
+
+                if tile.color == ColorName::Void && tile.glyph == '#' {
+                    tile.kind = TileKind::Floor;
+                    tile.set_fluid(MAX_FLUID);
                 }
+                chunk.push(&tile);
             }
-            map.insert(z % chunk_size, z_map);
         }
-        map
+        chunk
     }
 
     /// Given x, y, z and chunk_size returns the boundries of the 
@@ -183,13 +568,429 @@ impl GameMap {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Samples noise at (x, y, z), blending it over the SEAM_WIDTH tiles before
+/// the east edge of the world into the noise just west of x = 0, so the
+/// world wraps around without a visible seam
+fn seamless_noise(noise: &impl NoiseFn<[f64; 3]>, x: u32, y: f64, z: f64, 
+                  world_width: u32) -> f64 {
+    let val = noise.get([x as f64, y, z]);
+    if x < world_width.saturating_sub(SEAM_WIDTH) {
+        return val;
+    }
+    let wrapped = noise.get([x as f64 - world_width as f64, y, z]);
+    seam_blend(val, wrapped, x, world_width)
+}
+
+/// The noise at x in the seam, blended from val towards the noise
+/// wrapped round from west of 0, all the way there at the east edge
+fn seam_blend(val: f64, wrapped: f64, x: u32, world_width: u32) -> f64 {
+    let blend = (x - world_width.saturating_sub(SEAM_WIDTH)) as f64 / SEAM_WIDTH as f64;
+    val + (wrapped - val) * blend
+}
+
+/// A level of handcrafted rock, for scenarios that need the same map every
+/// time. Each glyph of the rows is a tile: # rock, ~ water, o a pillar and
+/// anything else floor. Every other tile in the world is rock.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    /// Tile the first glyph of the first row lands on
+    pub origin: (u32, u32, u32),
+    pub rows: Vec<Vec<char>>,
+}
+
+impl Layout {
+
+    fn tile(&self, x: u32, y: u32, z: u32) -> Tile {
+        let (origin_x, origin_y, origin_z) = self.origin;
+        let glyph = if z == origin_z && x >= origin_x && y >= origin_y {
+            self.rows.get((y - origin_y) as usize)
+                .and_then(|row| row.get((x - origin_x) as usize).copied())
+                .unwrap_or('#')
+        } else {
+            '#'
+        };
+        let mut tile = Tile {
+            kind: TileKind::Wall,
+            glyph: '#',
+            color: STONE_RAMP.band(0.3),
+            val: 0.3,
+            fluid: 0,
+            gas: 0,
+            dug: false,
+        };
+        if glyph != '#' {
+            tile.kind = TileKind::Floor;
+            tile.glyph = '.';
+            tile.val = 0.8;
+            tile.color = FLOOR_RAMP.band(0.8);
+        }
+        match glyph {
+            '~' => tile.set_fluid(MAX_FLUID),
+            'o' => tile.build_pillar(),
+            _ => {},
+        }
+        tile
+    }
+
+    /// Writes the rows into the map over whatever was generated there, as
+    /// edits so they're saved
+    pub fn stamp(&self, map: &mut GameMap) {
+        let (origin_x, origin_y, z) = self.origin;
+        for (y, row) in self.rows.iter().enumerate() {
+            for x in 0..row.len() {
+                let (x, y) = (origin_x + x as u32, origin_y + y as u32);
+                map.set_tile(x, y, z, self.tile(x, y, z));
+            }
+        }
+    }
+
+    /// The tiles of the chunk with the boundries
+    fn generate_chunk(&self, (x_min, x_max, y_min, y_max, z_min, z_max): (u32, u32, u32, u32, u32, u32),
+                      chunk_size: u32) -> Chunk {
+        let mut chunk = Chunk::new(chunk_size);
+        for z in z_min..z_max {
+            for y in y_min..y_max {
+                for x in x_min..x_max {
+                    chunk.push(&self.tile(x, y, z));
+                }
+            }
+        }
+        chunk
+    }
+}
+
+/// Deepest a single tile of fluid can get
+pub const MAX_FLUID: u8 = 7;
+/// Highest concentration of gas a single tile can hold
+pub const MAX_GAS: u8 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TileKind {
+    Wall,
+    Floor,
+    /// Loose rock left behind by a cave-in
+    Rubble,
+    /// Constructed support holding up the ceiling
+    Pillar,
+}
+
+/// The hash of a changed tile, from its raw bytes so no formatting is
+/// done every time a tile changes
+fn edit_hash((x, y, z): (u32, u32, u32), tile: &Tile) -> u64 {
+    Fnv::new()
+        .bytes(&x.to_le_bytes()).bytes(&y.to_le_bytes()).bytes(&z.to_le_bytes())
+        .bytes(&[tile.kind as u8, tile.color as u8, tile.fluid, tile.gas, tile.dug as u8])
+        .bytes(&(tile.glyph as u32).to_le_bytes())
+        .bytes(&tile.val.to_bits().to_le_bytes())
+        .finish()
+}
+
+/// The hash of a word of a chunk's explored tiles, nothing for a word with
+/// none seen so a chunk just added doesn't change the sum
+fn explored_hash((x, y, z): (u32, u32, u32), index: usize, word: u64) -> u64 {
+    if word == 0 {
+        return 0;
+    }
+    Fnv::new()
+        .bytes(&x.to_le_bytes()).bytes(&y.to_le_bytes()).bytes(&z.to_le_bytes())
+        .bytes(&(index as u64).to_le_bytes())
+        .bytes(&word.to_le_bytes())
+        .finish()
+}
+
+/// What's at a place in the map. It doesn't know where it is, that's
+/// whatever it was looked up by.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Tile {
-    pub pos: Vector,
-    pub depth: u32,
+    pub kind: TileKind,
     pub glyph: char,
     pub color: ColorName,
     pub val: f64,
+    /// Depth of the water standing on this tile, 0 to MAX_FLUID
+    pub fluid: u8,
+    /// Concentration of toxic gas on this tile, 0 to MAX_GAS
+    #[serde(default)]
+    pub gas: u8,
+    /// Opened up by digging rather than generated open, only dug tiles 
+    /// can cave in
+    #[serde(default)]
+    pub dug: bool,
+}
+
+impl Tile {
+    pub fn is_wall(&self) -> bool {
+        self.kind == TileKind::Wall
+    }
+
+    /// Whether the tile blocks movement, fluids and gas
+    pub fn is_solid(&self) -> bool {
+        self.kind != TileKind::Floor
+    }
+
+    /// Whether the tile holds up the ceiling around it
+    pub fn is_support(&self) -> bool {
+        self.kind == TileKind::Wall || self.kind == TileKind::Pillar
+    }
+
+    /// Whether gas can move into this tile
+    pub fn is_open(&self) -> bool {
+        !self.is_solid() && self.fluid == 0
+    }
+
+    /// The ramp its color was picked from by its noise value, None if it
+    /// wasn't or it's been colored otherwise since
+    pub fn ramp(&self) -> Option<ColorRamp> {
+        let ramp = match self.kind {
+            TileKind::Wall => STONE_RAMP,
+            TileKind::Floor => FLOOR_RAMP,
+            _ => return None,
+        };
+        Some(ramp).filter(|ramp| !self.dug && ramp.band(self.val) == self.color)
+    }
+
+    /// Clears whatever is solid on this tile, leaving bare floor
+    pub fn dig(&mut self) {
+        self.kind = TileKind::Floor;
+        self.glyph = '.';
+        self.color = ColorName::Stone5;
+        self.dug = true;
+    }
+
+    /// Turns an open tile into a support pillar
+    pub fn build_pillar(&mut self) {
+        self.kind = TileKind::Pillar;
+        self.glyph = '•';
+        self.color = ColorName::Fg2;
+    }
+
+    /// Fills the tile with the debris of a collapsed ceiling
+    pub fn collapse(&mut self) {
+        self.kind = TileKind::Rubble;
+        self.glyph = '∷';
+        self.color = ColorName::Stone4;
+        self.fluid = 0;
+        self.gas = 0;
+    }
+
+    /// Sets the depth of the water on this tile and updates how it is drawn
+    pub fn set_fluid(&mut self, fluid: u8) {
+        self.fluid = fluid;
+        if fluid == 0 {
+            self.glyph = '.';
+            self.color = ColorName::Stone5;
+        }
+        else {
+            self.glyph = '≈';
+            self.color = if fluid > MAX_FLUID / 2 {
+                ColorName::Blue
+            } else {
+                ColorName::LightBlue
+            };
+        }
+    }
+}
+
+/// What a tile is apart from where it is and its noise value, many tiles
+/// of a chunk look the same
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Look {
+    kind: TileKind,
+    glyph: char,
+    color: ColorName,
+    fluid: u8,
+    gas: u8,
+    dug: bool,
+}
+
+/// The tiles of a chunk, each kept as an index into a palette of the
+/// distinct looks in the chunk and its noise value, cut down to an f32.
+/// Tiles are kept x then y then z.
+struct Chunk {
+    size: u32,
+    palette: Vec<Look>,
+    /// Where each look is in the palette
+    looks: HashMap<Look, u32>,
+    tiles: Indices,
+    vals: Vec<f32>,
+}
+
+/// The palette indices of a chunk's tiles, two bytes each until the chunk
+/// has had more looks than that can tell apart
+enum Indices {
+    Narrow(Vec<u16>),
+    Wide(Vec<u32>),
+}
+
+impl Chunk {
+
+    fn new(size: u32) -> Chunk {
+        let tiles = size.pow(3) as usize;
+        Chunk {
+            size,
+            palette: Vec::new(),
+            looks: HashMap::new(),
+            tiles: Indices::Narrow(Vec::with_capacity(tiles)),
+            vals: Vec::with_capacity(tiles),
+        }
+    }
+
+    /// Where the tile at (x, y, z) in the world is kept
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        let size = self.size;
+        ((x % size) + (y % size) * size + (z % size) * size * size) as usize
+    }
+
+    /// The palette index of the look, added to the palette if it's new.
+    /// Looks stay in the palette once no tile has them.
+    fn intern(&mut self, look: Look) -> u32 {
+        let palette = &mut self.palette;
+        *self.looks.entry(look).or_insert_with(|| {
+            palette.push(look);
+            (palette.len() - 1) as u32
+        })
+    }
+
+    /// Adds the tile after the ones generated so far
+    fn push(&mut self, tile: &Tile) {
+        let look = self.intern(Look::of(tile));
+        self.tiles.push(look);
+        self.vals.push(tile.val as f32);
+    }
+
+    fn get(&self, i: usize) -> Tile {
+        let look = self.palette[self.tiles.get(i)];
+        Tile {
+            kind: look.kind,
+            glyph: look.glyph,
+            color: look.color,
+            val: self.vals[i] as f64,
+            fluid: look.fluid,
+            gas: look.gas,
+            dug: look.dug,
+        }
+    }
+
+    fn set(&mut self, i: usize, tile: &Tile) {
+        let look = self.intern(Look::of(tile));
+        self.tiles.set(i, look);
+        self.vals[i] = tile.val as f32;
+    }
+}
+
+impl Indices {
+
+    fn get(&self, i: usize) -> usize {
+        match self {
+            Indices::Narrow(tiles) => tiles[i] as usize,
+            Indices::Wide(tiles) => tiles[i] as usize,
+        }
+    }
+
+    fn set(&mut self, i: usize, look: u32) {
+        match self {
+            Indices::Narrow(tiles) => match u16::try_from(look) {
+                Ok(look) => tiles[i] = look,
+                Err(_) => {
+                    self.widen();
+                    self.set(i, look);
+                },
+            },
+            Indices::Wide(tiles) => tiles[i] = look,
+        }
+    }
+
+    fn push(&mut self, look: u32) {
+        match self {
+            Indices::Narrow(tiles) => match u16::try_from(look) {
+                Ok(look) => tiles.push(look),
+                Err(_) => {
+                    self.widen();
+                    self.push(look);
+                },
+            },
+            Indices::Wide(tiles) => tiles.push(look),
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        match self {
+            Indices::Narrow(tiles) => tiles.len(),
+            Indices::Wide(tiles) => tiles.len(),
+        }
+    }
+
+    /// Switches to four bytes an index, for a look past what two can hold
+    fn widen(&mut self) {
+        if let Indices::Narrow(tiles) = self {
+            let mut wide = Vec::with_capacity(tiles.capacity());
+            wide.extend(tiles.iter().map(|&look| look as u32));
+            *self = Indices::Wide(wide);
+        }
+    }
+}
+
+impl Look {
+
+    fn of(tile: &Tile) -> Look {
+        Look {
+            kind: tile.kind,
+            glyph: tile.glyph,
+            color: tile.color,
+            fluid: tile.fluid,
+            gas: tile.gas,
+            dug: tile.dug,
+        }
+    }
+}
+
+/// A tile changed since generation and where it is, as saved
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileEdit {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub tile: Tile,
+}
+
+/// The tiles seen in a chunk, a bit each row by row, as saved
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExploredChunk {
+    /// Which chunk, in chunks rather than tiles across and down
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub seen: Vec<u64>,
+}
+
+/// Rooms built by hand for the simulations' tests
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+
+    /// Walls in a clear, dry room width by height at (x, y) on level z,
+    /// with rock above and below all of it
+    pub(crate) fn walled_room(map: &mut GameMap, x: u32, y: u32, z: u32, width: u32, height: u32) {
+        for ty in y..y + height {
+            for tx in x..x + width {
+                let mut tile = map.get_tile(tx, ty, z);
+                tile.dig();
+                tile.set_fluid(0);
+                tile.gas = 0;
+                if tx == x || ty == y || tx == x + width - 1 || ty == y + height - 1 {
+                    tile.kind = TileKind::Wall;
+                }
+                map.set_tile(tx, ty, z, tile);
+                for level in [z - 1, z + 1] {
+                    let mut rock = map.get_tile(tx, ty, level);
+                    rock.kind = TileKind::Wall;
+                    rock.set_fluid(0);
+                    rock.gas = 0;
+                    map.set_tile(tx, ty, level, rock);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -284,4 +1085,280 @@ mod tests {
         assert_eq!(min, 128);
         assert_eq!(max, 192);
     }
+
+    // Worlds are far smaller than this, it keeps x + 1 and the rounding
+    // clear of overflowing
+    const MAX_COORD: u32 = 1 << 28;
+
+    proptest::proptest! {
+        #[test]
+        fn prop_chunk_boundries_contain_the_coordinate(
+            x in 0..MAX_COORD, y in 0..MAX_COORD, z in 0..MAX_COORD, chunk_size in 1u32..1024) {
+
+            let (x_min, x_max, y_min, y_max, z_min, z_max) =
+                GameMap::get_chunck_boundries(x, y, z, chunk_size);
+            for (n, min, max) in [(x, x_min, x_max), (y, y_min, y_max), (z, z_min, z_max)] {
+                proptest::prop_assert!(min <= n && n < max, "{} not in {}..{}", n, min, max);
+                proptest::prop_assert_eq!(max - min, chunk_size);
+                proptest::prop_assert_eq!(min % chunk_size, 0);
+            }
+        }
+
+        #[test]
+        fn prop_chunk_edges_split_adjacent_chunks(
+            chunk in 1..MAX_COORD / 1024, chunk_size in 1u32..1024) {
+
+            // the last coordinate of one chunk and the first of the next
+            let edge = chunk * chunk_size;
+            let (_, before_max, ..) = GameMap::get_chunck_boundries(edge - 1, 0, 0, chunk_size);
+            let (after_min, after_max, ..) = GameMap::get_chunck_boundries(edge, 0, 0, chunk_size);
+            proptest::prop_assert_eq!(before_max, edge);
+            proptest::prop_assert_eq!(after_min, edge);
+            proptest::prop_assert_eq!(after_max, edge + chunk_size);
+        }
+
+        #[test]
+        fn prop_every_coordinate_is_in_its_own_chunk(
+            start in 0..MAX_COORD, len in 1u32..512, chunk_size in 1u32..128) {
+
+            // walking along, each coordinate lands in the chunk get_tile
+            // indexes it in, so the +1 offset never skips or repeats one
+            let mut previous: Option<(u32, u32)> = None;
+            for n in start..start + len {
+                let (min, max, ..) = GameMap::get_chunck_boundries(n, 0, 0, chunk_size);
+                proptest::prop_assert_eq!(min, n / chunk_size * chunk_size);
+                proptest::prop_assert_eq!(n - min, n % chunk_size);
+                if let Some((previous_min, previous_max)) = previous {
+                    proptest::prop_assert!(min == previous_min || min == previous_max,
+                                           "{} jumped from {}..{} to {}..{}",
+                                           n, previous_min, previous_max, min, max);
+                }
+                previous = Some((min, max));
+            }
+        }
+    }
+
+    #[test]
+    fn test_x_wraps_at_the_seam() {
+        let mut map = GameMap::with_seed(10);
+        let (max_x, _, _) = map.size();
+        let (y, z) = (10_000_000, 34);
+
+        let east_edge = map.get_tile(max_x - 1, y, z);
+        let west_edge = map.get_tile(0, y, z);
+        println!("east edge val: {} west edge val: {}", east_edge.val, west_edge.val);
+        assert!((east_edge.val - west_edge.val).abs() < 0.01);
+
+        assert_eq!(map.get_tile(max_x + 5, y, z), map.get_tile(5, y, z));
+        assert_eq!(map.wrap_x(-1), max_x - 1);
+        assert_eq!(map.column_from(2, max_x - 3), 5);
+        assert!(map.neighbors(0, y, z).contains(&(max_x - 1, y, z)));
+        assert!(map.neighbors(max_x - 1, y, z).contains(&(0, y, z)));
+    }
+
+    #[test]
+    fn test_explored_tiles_saved_by_chunk() {
+        let mut map = GameMap::with_seed(10);
+        map.explore(70, 5, 3);
+        map.explore(71, 5, 3);
+        map.explore(0, 0, 4);
+        assert!(map.is_explored(70, 5, 3));
+        assert!(!map.is_explored(70, 5, 4));
+        assert!(!map.is_explored(72, 5, 3));
+
+        let chunks = map.explored_chunks();
+        println!("{:?}", chunks.iter().map(|chunk| (chunk.x, chunk.y, chunk.z)).collect::<Vec<_>>());
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].seen.iter().map(|word| word.count_ones()).sum::<u32>(), 2);
+
+        let mut loaded = GameMap::with_seed(10);
+        loaded.explore(1, 1, 4);
+        loaded.apply_explored(chunks);
+        assert!(loaded.is_explored(71, 5, 3));
+        assert!(loaded.is_explored(0, 0, 4));
+        assert!(loaded.is_explored(1, 1, 4));
+    }
+
+    #[test]
+    fn test_map_hashed_the_same_whatever_the_order() {
+        let (mut ours, mut theirs) = (GameMap::with_seed(10), GameMap::with_seed(10));
+        let tiles = [(1000, 1000, 20), (1001, 1000, 20), (1000, 1001, 21)];
+        for &(x, y, z) in &tiles {
+            let mut tile = ours.get_tile(x, y, z);
+            tile.dig();
+            ours.set_tile(x, y, z, tile);
+            ours.explore(x, y, z);
+        }
+        for &(x, y, z) in tiles.iter().rev() {
+            // dug over a tile changed before, which isn't counted any more
+            let mut tile = theirs.get_tile(x, y, z);
+            tile.set_fluid(1);
+            theirs.set_tile(x, y, z, tile);
+            tile.set_fluid(0);
+            tile.dig();
+            theirs.set_tile(x, y, z, tile);
+        }
+        let mut seen = GameMap::with_seed(10);
+        seen.explore(1000, 1001, 21);
+        theirs.apply_explored(seen.explored_chunks());
+        theirs.explore(1000, 1000, 20);
+        theirs.explore(1001, 1000, 20);
+        theirs.explore(1001, 1000, 20);
+        println!("edits {:x} {:x}, explored {:x} {:x}", ours.edits_hash(), theirs.edits_hash(), ours.explored_hash(), theirs.explored_hash());
+        assert_eq!(ours.edits_hash(), theirs.edits_hash());
+        assert_eq!(ours.explored_hash(), theirs.explored_hash());
+
+        let mut tile = theirs.get_tile(1000, 1000, 20);
+        tile.gas = 1;
+        theirs.set_tile(1000, 1000, 20, tile);
+        assert_ne!(ours.edits_hash(), theirs.edits_hash());
+    }
+
+    #[test]
+    fn test_chunk_tiles_kept_in_a_palette() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (10_000_000, 10_000_020, 34);
+        let mut tile = map.get_tile(x, y, z);
+        let chunk = map.chunk_mut(x, y, z);
+        println!("{} looks for {} tiles", chunk.palette.len(), chunk.tiles.len());
+        assert_eq!(chunk.tiles.len(), 64 * 64 * 64);
+        assert!(chunk.palette.len() < 256);
+
+        tile.dig();
+        tile.set_fluid(3);
+        map.set_tile(x, y, z, tile);
+        assert_eq!(map.get_tile(x, y, z), tile);
+        assert!(map.edited_tiles().contains(&TileEdit { x, y, z, tile }));
+    }
+
+    #[test]
+    fn test_chunk_takes_any_number_of_looks() {
+        let mut chunk = Chunk::new(4);
+        let tile = GameMap::with_seed(10).get_tile(0, 0, 40);
+        for _ in 0..64 {
+            chunk.push(&tile);
+        }
+        // more looks than two bytes can tell apart, all on one tile
+        let looks = u16::MAX as u32 + 10;
+        let glyph = |look: u32| std::char::from_u32(0x1_0000 + look).unwrap();
+        for look in 0..looks {
+            chunk.set(5, &Tile { glyph: glyph(look), ..tile });
+        }
+        chunk.push(&Tile { glyph: 'x', ..tile });
+        println!("{} looks for {} tiles", chunk.palette.len(), chunk.tiles.len());
+        assert!(matches!(chunk.tiles, Indices::Wide(_)));
+        assert_eq!(chunk.get(5), Tile { glyph: glyph(looks - 1), val: tile.val as f32 as f64, ..tile });
+        assert_eq!(chunk.get(6).glyph, tile.glyph);
+        assert_eq!(chunk.get(64).glyph, 'x');
+    }
+
+    #[test]
+    fn test_cached_chunks_generate_the_same_tiles() {
+        let root = std::env::temp_dir().join("jmc-cached-chunks-test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (x, y, z) = (10_000_000, 10_000_020, 34);
+        let generated = GameMap::with_seed(10).get_tile(x, y, z);
+
+        // the first map generates the chunk and caches it, the second
+        // reads it back
+        let mut first = GameMap::with_seed(10).with_chunk_cache(&root);
+        let mut second = GameMap::with_seed(10).with_chunk_cache(&root);
+        assert_eq!(first.get_tile(x, y, z), generated);
+        let cached = std::fs::read_dir(root.join("v1-10-standard")).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "chunk"))
+            .count();
+        println!("{} chunks cached", cached);
+        assert_eq!(cached, 1);
+        assert_eq!(second.get_tile(x, y, z), generated);
+        assert_eq!(second.export_ascii(Rectangle::new((x, y), (64, 16)), z),
+                   include_str!("../tests/golden/export_ascii_seed_10.txt"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_chunks_sampled_the_same_on_any_number_of_threads() {
+        let map = GameMap::with_seed(10);
+        let (world_width, _, _) = map.size();
+        let sample = |threads: usize, (x, y, z): (u32, u32, u32)| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            let bounds = GameMap::get_chunck_boundries(x, y, z, map.chunk_size);
+            pool.install(|| GameMap::sample_chunk(
+                bounds, map.level_thickness, map.random_seed, map.gas_depth,
+                map.difficulty.gas_threshold(), world_width, &map.worldgen))
+        };
+        // one chunk in the middle of the world, one deep down among the
+        // flooded gas pockets across the seam
+        for position in [(10_000_000, 10_000_020, 34), (world_width - 1, 1000, map.gas_depth + 1)] {
+            let serial = sample(1, position);
+            println!("{} samples, {} open", serial.len(),
+                     serial.iter().filter(|sample| sample.val >= map.worldgen.open_threshold).count());
+            assert_eq!(serial.len(), 64 * 64 * 64);
+            assert!(serial == sample(4, position));
+        }
+    }
+
+    #[test]
+    fn test_chunks_sampled_as_billow_samples_them() {
+        use noise::{Billow, MultiFractal};
+        let map = GameMap::with_seed(10);
+        let (world_width, _, _) = map.size();
+        // read at run time, as the game reads them from its config
+        let (frequency, persistence, scale) = std::hint::black_box(
+            (map.worldgen.frequency, map.worldgen.persistence, map.worldgen.scale));
+        let billow = ScalePoint::new(Billow::new()
+            .set_seed(map.random_seed)
+            .set_frequency(frequency)
+            .set_persistence(persistence)
+            ).set_scale(scale);
+        // in the middle of the world, and across the seam
+        for position in [(10_000_000, 10_000_020, 34), (world_width - 1, 1000, 34)] {
+            let bounds = GameMap::get_chunck_boundries(position.0, position.1, position.2, map.chunk_size);
+            let (x_min, x_max, y_min, y_max, z_min, z_max) = bounds;
+            let samples = GameMap::sample_chunk(
+                bounds, map.level_thickness, map.random_seed, map.gas_depth,
+                map.difficulty.gas_threshold(), world_width, &map.worldgen);
+            let tiles = (z_min..z_max).flat_map(|z| (y_min..y_max)
+                .flat_map(move |y| (x_min..x_max).map(move |x| (x, y, z))));
+            let different = tiles.zip(&samples).filter(|&((x, y, z), sample)| {
+                let val = seamless_noise(&billow, x, y as f64, (z * map.level_thickness) as f64,
+                                         world_width).abs();
+                val.to_bits() != sample.val.to_bits()
+            }).count();
+            println!("{} of {} samples different", different, samples.len());
+            assert_eq!(different, 0);
+        }
+    }
+
+    #[test]
+    fn test_regenerate_keeps_changed_chunks() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (10_000_000, 10_000_020, 34);
+        let mut tile = map.get_tile(x, y, z);
+        let beside = map.get_tile(x + 1, y, z);
+        tile.dig();
+        map.set_tile(x, y, z, tile);
+        map.take_reshaped();
+        // the chunk east of it is generated but nothing's changed in it
+        map.get_tile(x + 64, y, z);
+
+        // with everything open cave, only the unchanged chunk opens up
+        let open = WorldGenConfig { open_threshold: 0.0, ..WorldGenConfig::default() };
+        assert_eq!(map.regenerate(open), 1);
+        let reshaped = map.take_reshaped();
+        println!("{:?}", reshaped);
+        assert_eq!(reshaped, vec![GameMap::get_chunck_boundries(x + 64, y, z, 64)]
+            .into_iter().map(|(x_min, _, y_min, _, z_min, _)| (x_min, y_min, z_min)).collect::<Vec<_>>());
+        assert!(!map.get_tile(x + 64, y, z).is_solid());
+        assert_eq!(map.get_tile(x, y, z), tile);
+        assert_eq!(map.get_tile(x + 1, y, z), beside);
+    }
+
+    #[test]
+    fn test_export_ascii_matches_golden_file() {
+        let mut map = GameMap::with_seed(10);
+        let rect = Rectangle::new((10_000_000, 10_000_020), (64, 16));
+        let ascii = map.export_ascii(rect, 34);
+        println!("{}", ascii);
+        assert_eq!(ascii, include_str!("../tests/golden/export_ascii_seed_10.txt"));
+    }
 }
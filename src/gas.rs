@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use crate::game_map::GameMap;
+
+/// Spreads toxic gas out of breached pockets. Like the fluid simulation
+/// only tiles whose surroundings changed are simulated.
+pub struct GasSim {
+    active: BTreeSet<(u32, u32, u32)>,
+}
+
+impl GasSim {
+
+    pub fn new() -> GasSim {
+        GasSim {
+            active: BTreeSet::new(),
+        }
+    }
+
+    /// Positions that will be simulated on the next step
+    pub fn active(&self) -> Vec<(u32, u32, u32)> {
+        self.active.iter().cloned().collect()
+    }
+
+    /// Wakes up the tile at (x, y, z) and every tile around it, call this
+    /// whenever the tile changes
+    pub fn activate_around(&mut self, map: &GameMap, x: u32, y: u32, z: u32) {
+        self.active.insert((x, y, z));
+        self.active.extend(neighbors(map, x, y, z));
+    }
+
+    /// Advances the simulation by one tick. Gas evens out with open 
+    /// neighbors, including the levels above and below, and a thin layer 
+    /// that can't spread any further slowly dissipates.
+    pub fn step(&mut self, map: &mut GameMap) {
+        let active = std::mem::take(&mut self.active);
+        // gas moved this tick waits for the next one, or whichever way the
+        // tiles happen to be gone through it would thin out on arrival
+        let mut arrived = BTreeSet::new();
+
+        for (x, y, z) in active {
+            let mut tile = map.get_tile(x, y, z);
+            if tile.gas == 0 || arrived.contains(&(x, y, z)) {
+                continue;
+            }
+
+            let mut spread = false;
+            for (nx, ny, nz) in neighbors(map, x, y, z) {
+                let mut neighbor = map.get_tile(nx, ny, nz);
+                if neighbor.is_open() && neighbor.gas + 1 < tile.gas {
+                    neighbor.gas += 1;
+                    tile.gas -= 1;
+                    map.set_tile(nx, ny, nz, neighbor);
+                    self.activate_around(map, nx, ny, nz);
+                    arrived.insert((nx, ny, nz));
+                    spread = true;
+                }
+            }
+
+            if !spread && tile.gas == 1 {
+                tile.gas = 0;
+                map.set_tile(x, y, z, tile);
+                self.activate_around(map, x, y, z);
+            }
+            else if spread {
+                map.set_tile(x, y, z, tile);
+                self.active.insert((x, y, z));
+            }
+        }
+    }
+}
+
+/// The orthogonal neighbors of (x, y, z) on its own level and the levels
+/// directly above and below
+fn neighbors(map: &GameMap, x: u32, y: u32, z: u32) -> Vec<(u32, u32, u32)> {
+    let (_, _, max_z) = map.size();
+    let mut neighbors = map.neighbors(x, y, z);
+    if z > 0 { neighbors.push((x, y, z - 1)); }
+    if z + 1 < max_z { neighbors.push((x, y, z + 1)); }
+    neighbors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::fixtures::walled_room;
+    use crate::game_map::MAX_GAS;
+
+    #[test]
+    fn test_gas_spreads_to_the_walls_and_dissipates() {
+        let mut map = GameMap::with_seed(10);
+        let mut sim = GasSim::new();
+        let (x, y, z) = (1000, 1000, 40);
+        // a corridor 6 tiles long with the gas let out at one end
+        walled_room(&mut map, x, y, z, 8, 3);
+        let mut source = map.get_tile(x + 1, y + 1, z);
+        source.gas = MAX_GAS;
+        map.set_tile(x + 1, y + 1, z, source);
+        sim.activate_around(&map, x + 1, y + 1, z);
+
+        let gas = |map: &mut GameMap| -> Vec<u8> {
+            (x..x + 8).map(|tx| map.get_tile(tx, y + 1, z).gas).collect()
+        };
+        sim.step(&mut map);
+        let spread = gas(&mut map);
+        println!("{:?}", spread);
+        assert!(spread[1] < MAX_GAS && spread[2] > 0);
+        assert_eq!(spread.iter().map(|&gas| gas as u32).sum::<u32>(), MAX_GAS as u32);
+
+        for _ in 0..5 {
+            sim.step(&mut map);
+        }
+        let spread = gas(&mut map);
+        println!("{:?}", spread);
+        assert!(spread[3] > 0);
+        // the thin edge of it is already thinning out
+        assert!(spread.iter().map(|&gas| gas as u32).sum::<u32>() < MAX_GAS as u32);
+        // nothing gets into the rock around the corridor
+        assert_eq!((spread[0], spread[7]), (0, 0));
+        assert_eq!(map.get_tile(x + 1, y, z).gas, 0);
+        assert_eq!(map.get_tile(x + 1, y + 1, z - 1).gas, 0);
+        assert_eq!(map.get_tile(x + 1, y + 1, z + 1).gas, 0);
+
+        // a thin layer with nowhere to go thins out until it's gone
+        for _ in 0..200 {
+            sim.step(&mut map);
+        }
+        let spread = gas(&mut map);
+        println!("{:?}", spread);
+        assert!(spread.iter().all(|&gas| gas == 0));
+        assert!(sim.active().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use crate::locale;
+use crate::mods;
+use crate::text_layout;
+use crate::worldgen;
+
+/// Passed on the command line to reload assets as they're edited
+pub const WATCH_FLAG: &str = "--watch";
+/// How long files have to stay unchanged before they're reloaded, so a
+/// save that writes a file in several goes is only loaded once it's done
+const QUIET: Duration = Duration::from_millis(250);
+
+/// What's loaded again when the files it's made from change
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reload {
+    /// The fonts in static/ the tileset and text are rendered from
+    Tileset,
+    /// The json files of the content packs and their load order
+    Content,
+    /// The string bundles in lang/
+    Language,
+    /// The noise settings in worldgen.json
+    WorldGen,
+}
+
+/// Where each kind of file lives, and the extensions that count
+const WATCHED: [(&str, Reload, &[&str]); 4] = [
+    (text_layout::ASSET_DIR, Reload::Tileset, &["ttf", "otf"]),
+    (mods::MODS_DIR, Reload::Content, &["json", "txt"]),
+    (locale::LANG_DIR, Reload::Language, &["txt"]),
+    (worldgen::CONFIG_FILE, Reload::WorldGen, &["json"]),
+];
+
+/// What a changed file means reloading, None if nothing is made from it
+pub fn classify(dirs: &[(PathBuf, Reload, &[&str])], path: &Path) -> Option<Reload> {
+    let extension = path.extension()?.to_str()?;
+    dirs.iter()
+        .find(|(dir, _, extensions)| path.starts_with(dir) && extensions.contains(&extension))
+        .map(|(_, reload, _)| *reload)
+}
+
+/// Reloads that have come in, held until the files go quiet
+#[derive(Debug, Default)]
+pub struct Pending {
+    reloads: BTreeSet<Reload>,
+    last_change: Option<Instant>,
+}
+
+impl Pending {
+
+    pub fn note(&mut self, reload: Reload, at: Instant) {
+        self.reloads.insert(reload);
+        self.last_change = Some(at);
+    }
+
+    /// Everything noted, once nothing's changed for QUIET
+    pub fn take(&mut self, now: Instant) -> BTreeSet<Reload> {
+        match self.last_change {
+            Some(at) if now.duration_since(at) >= QUIET => {
+                self.last_change = None;
+                std::mem::take(&mut self.reloads)
+            },
+            _ => BTreeSet::new(),
+        }
+    }
+}
+
+/// Watches the fonts, content packs and string bundles on disk while the
+/// game runs, so they can be reloaded as artists and modders save them.
+/// Only on desktop, there are no files to watch on the web.
+pub struct AssetWatcher {
+    #[cfg(not(target_arch = "wasm32"))]
+    _watcher: notify::RecommendedWatcher,
+    dirs: Vec<(PathBuf, Reload, &'static [&'static str])>,
+    changes: Receiver<PathBuf>,
+    pending: Pending,
+}
+
+impl AssetWatcher {
+
+    /// Starts watching every watched directory there is
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start() -> Result<AssetWatcher, String> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        let (sender, changes) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    for path in event.paths {
+                        let _ = sender.send(path);
+                    }
+                },
+                Ok(_) => (),
+                Err(err) => log::warn!("watching for changed assets: {}", err),
+            }
+        }).map_err(|err| format!("could not watch for changed assets: {}", err))?;
+
+        // the paths of changes start with the path watched, so it's
+        // watched by its full path to match them up whatever it's given as.
+        // A file is watched through the directory it's in, since editors
+        // often save by writing a new file over it.
+        let mut dirs = Vec::new();
+        for (watched, reload, extensions) in WATCHED.iter() {
+            let watched = Path::new(watched);
+            let (dir, file, mode) = match (watched.extension(), watched.file_name()) {
+                (Some(_), Some(file)) => {
+                    let parent = watched.parent().filter(|parent| !parent.as_os_str().is_empty());
+                    (parent.unwrap_or_else(|| Path::new(".")), Some(file), RecursiveMode::NonRecursive)
+                },
+                _ => (watched, None, RecursiveMode::Recursive),
+            };
+            let dir = match dir.canonicalize() {
+                Ok(dir) => dir,
+                Err(_) => continue,
+            };
+            watcher.watch(&dir, mode)
+                .map_err(|err| format!("could not watch {}: {}", dir.display(), err))?;
+            dirs.push((file.map_or_else(|| dir.clone(), |file| dir.join(file)), *reload, *extensions));
+        }
+        Ok(AssetWatcher { _watcher: watcher, dirs, changes, pending: Pending::default() })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn start() -> Result<AssetWatcher, String> {
+        Err(String::from("assets can't be watched on the web"))
+    }
+
+    /// What to reload for the files changed since last time, once they've
+    /// stopped changing
+    pub fn poll(&mut self, now: Instant) -> BTreeSet<Reload> {
+        for path in self.changes.try_iter() {
+            if let Some(reload) = classify(&self.dirs, &path) {
+                self.pending.note(reload, now);
+            }
+        }
+        self.pending.take(now)
+    }
+
+    /// Directories being watched, for telling the player
+    pub fn dirs(&self) -> impl Iterator<Item = &Path> {
+        self.dirs.iter().map(|(dir, _, _)| dir.as_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_reload_once_quiet() {
+        let dirs: Vec<_> = WATCHED.iter()
+            .map(|(dir, reload, extensions)| (Path::new("/game").join(dir), *reload, *extensions))
+            .collect();
+        let reload = |path: &str| classify(&dirs, Path::new(path));
+        assert_eq!(reload("/game/static/square.ttf"), Some(Reload::Tileset));
+        assert_eq!(reload("/game/mods/cave_life/creatures.json"), Some(Reload::Content));
+        assert_eq!(reload("/game/mods/load_order.txt"), Some(Reload::Content));
+        assert_eq!(reload("/game/lang/fr.txt"), Some(Reload::Language));
+        assert_eq!(reload("/game/worldgen.json"), Some(Reload::WorldGen));
+        assert_eq!(reload("/game/save.json"), None);
+        // sounds and editors' backups aren't loaded from
+        assert_eq!(reload("/game/static/dig.ogg"), None);
+        assert_eq!(reload("/game/lang/fr.txt~"), None);
+        assert_eq!(reload("/elsewhere/static/square.ttf"), None);
+
+        let start = Instant::now();
+        let mut pending = Pending::default();
+        pending.note(Reload::Content, start);
+        pending.note(Reload::Tileset, start + QUIET / 2);
+        println!("{:?}", pending);
+        assert!(pending.take(start + QUIET).is_empty());
+        let taken: Vec<_> = pending.take(start + QUIET * 2).into_iter().collect();
+        assert_eq!(taken, vec![Reload::Tileset, Reload::Content]);
+        assert!(pending.take(start + QUIET * 3).is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::Difficulty;
+
+/// Set apart from the streams the spawner draws from, for ticks both are
+/// due
+const INCIDENT_STREAM: u64 = 0x1c1d_e000;
+
+/// Something that befalls the colony out of the blue now and then
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Incident {
+    /// The ground shakes and the ceiling comes down near a colonist
+    Tremor,
+    /// A pocket of gas breaks open near a colonist
+    GasRelease,
+    /// A miner lost in the tunnels finds the colony and joins the squad
+    LostMiner,
+    /// A colonist's tool breaks in their hands
+    EquipmentFailure,
+    /// Something near a colonist catches fire
+    Fire,
+}
+
+impl Incident {
+
+    pub const ALL: [Incident; 5] = [
+        Incident::Tremor,
+        Incident::GasRelease,
+        Incident::LostMiner,
+        Incident::EquipmentFailure,
+        Incident::Fire,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Incident::Tremor => "tremor",
+            Incident::GasRelease => "gas_release",
+            Incident::LostMiner => "lost_miner",
+            Incident::EquipmentFailure => "equipment_failure",
+            Incident::Fire => "fire",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Incident> {
+        Incident::ALL.iter().copied().find(|incident| incident.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Incident::Tremor => "A tremor brought the ceiling down",
+            Incident::GasRelease => "Gas burst out of the rock",
+            Incident::LostMiner => "A lost miner found the colony and joined the squad",
+            Incident::EquipmentFailure => "A tool broke in a colonist's hands",
+            Incident::Fire => "A fire broke out",
+        }
+    }
+}
+
+/// The random stream for whatever befalls the colony at tick, the same in
+/// every game of a network session
+pub fn rng(seed: u32, tick: u64) -> oorandom::Rand32 {
+    oorandom::Rand32::new(((seed as u64) << 32) ^ tick ^ INCIDENT_STREAM)
+}
+
+/// The incident befalling the colony at tick, if one is due. They come as
+/// often as the difficulty says, drawn by the weights it gives them.
+pub fn due(difficulty: Difficulty, tick: u64, rng: &mut oorandom::Rand32) -> Option<Incident> {
+    let interval = difficulty.incident_interval()?;
+    if tick == 0 || !tick.is_multiple_of(interval) {
+        return None;
+    }
+    pick(&difficulty.incident_weights(), rng)
+}
+
+/// One of the incidents, each as likely as its weight says
+fn pick(weights: &[(Incident, u32)], rng: &mut oorandom::Rand32) -> Option<Incident> {
+    let total: u32 = weights.iter().map(|&(_, weight)| weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.rand_range(0..total);
+    for &(incident, weight) in weights {
+        if roll < weight {
+            return Some(incident);
+        }
+        roll -= weight;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incidents_follow_the_weights() {
+        let interval = Difficulty::Brutal.incident_interval().unwrap();
+        assert_eq!(due(Difficulty::Brutal, interval - 1, &mut rng(10, interval - 1)), None);
+        assert_eq!(due(Difficulty::Brutal, interval, &mut rng(10, interval)),
+                   due(Difficulty::Brutal, interval, &mut rng(10, interval)));
+
+        let weights = [(Incident::Tremor, 0), (Incident::LostMiner, 3), (Incident::GasRelease, 1)];
+        let mut stream = rng(10, 0);
+        let picked: Vec<Incident> = (0..400).filter_map(|_| pick(&weights, &mut stream)).collect();
+        let miners = picked.iter().filter(|&&incident| incident == Incident::LostMiner).count();
+        println!("{} lost miners out of {}", miners, picked.len());
+        assert_eq!(picked.len(), 400);
+        assert!(!picked.contains(&Incident::Tremor));
+        assert!(miners > 250 && miners < 350);
+        assert_eq!(pick(&[(Incident::Tremor, 0)], &mut stream), None);
+        assert_eq!(Incident::from_name("Gas_Release"), Some(Incident::GasRelease));
+    }
+}
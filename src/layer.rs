@@ -0,0 +1,55 @@
+/// What's drawn over what, each layer over every one before it. Everything
+/// drawn to the window is drawn on one of these rather than at a Z value
+/// of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderLayer {
+    /// The rock, the ground and what's built on it
+    Terrain,
+    /// Water and gas
+    Fluids,
+    /// Items, gear and corpses lying about
+    Items,
+    /// Colonists, creatures and everything else that stands up
+    Entities,
+    /// Weather, smoke, particles and the glyphs beside whoever's afflicted
+    Effects,
+    /// Designations, selections, overlays and the other markers on the map
+    Overlay,
+    /// The panels and text around the map
+    UiPanels,
+    /// Dialogs, the console and the panels opened over everything else
+    UiTop,
+}
+
+impl RenderLayer {
+
+    /// The Z value to draw at, what's drawn on the layer's own backgrounds
+    /// goes at over()
+    pub fn z(self) -> i32 {
+        self as i32 * 2
+    }
+
+    /// The Z value for text and pictures on the layer's backgrounds, over
+    /// them but still under the next layer
+    pub fn over(self) -> i32 {
+        self.z() + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_stack_in_order() {
+        let layers = [
+            RenderLayer::Terrain, RenderLayer::Fluids, RenderLayer::Items, RenderLayer::Entities,
+            RenderLayer::Effects, RenderLayer::Overlay, RenderLayer::UiPanels, RenderLayer::UiTop,
+        ];
+        for pair in layers.windows(2) {
+            println!("{:?} {} {}", pair, pair[0].over(), pair[1].z());
+            assert!(pair[0].z() < pair[0].over());
+            assert!(pair[0].over() < pair[1].z());
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! The simulation side of the game, shared by the game and the headless
+//! server. Nothing in here opens a window or draws.
+
+// things here are made with new(), several of them (the map, scripts)
+// aren't anything sensible by default
+#![allow(clippy::new_without_default)]
+
+pub mod game_map;
+pub mod worldgen;
+pub mod chunk_cache;
+pub mod billow;
+pub mod color_scheme;
+pub mod autotile;
+pub mod save;
+pub mod fluid;
+pub mod gas;
+pub mod support;
+pub mod entity;
+pub mod equipment;
+pub mod selection;
+pub mod designation;
+pub mod events;
+pub mod pathfinding;
+pub mod path_graph;
+pub mod regions;
+pub mod sight;
+pub mod rail;
+pub mod blueprint;
+pub mod scripting;
+pub mod mods;
+pub mod stats;
+pub mod stock;
+pub mod trends;
+pub mod chronicle;
+pub mod error;
+pub mod animation;
+pub mod travel;
+pub mod squad;
+pub mod skills;
+pub mod status;
+pub mod livestock;
+pub mod corpse;
+pub mod farming;
+pub mod fire;
+pub mod rooms;
+pub mod weather;
+pub mod economy;
+pub mod incidents;
+pub mod logging;
+pub mod timestep;
+pub mod net;
+pub mod simulation;
+pub mod undo;
+pub mod embark;
+pub mod difficulty;
+pub mod spawner;
+pub mod scenario;
+pub mod agent;
+pub mod desync;
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use crate::mods::{Content, CreatureDef, ItemDef, LivestockDef};
+
+/// Ticks after being fed a kept creature gets hungry, it stops producing
+/// and breeding until it's fed again
+pub const HUNGRY_AFTER: u64 = 3000;
+/// Ticks after being fed a kept creature starts starving
+pub const STARVING_AFTER: u64 = 9000;
+/// Ticks between the hits a starving creature takes
+pub const STARVE_INTERVAL: u64 = 300;
+/// Tiles of pen each creature in it needs for another to be born there
+pub const PEN_ROOM: u32 = 4;
+
+/// How far along being kept a creature is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Taming {
+    /// Left alone
+    Wild,
+    /// Marked to be caught by the next colonist free to
+    Marked,
+    /// Caught, walks to a pen and stays there
+    Tame,
+}
+
+/// A creature of a kind that can be kept, wild or tame
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Animal {
+    /// Id of its creature definition
+    pub kind: String,
+    pub taming: Taming,
+    /// Tick it was last fed, or caught. Food that keeps it fed longer puts
+    /// it past then.
+    pub fed: u64,
+    /// Tick it last left something lying about, or was caught
+    pub produced: u64,
+    /// Tick it last bred, or was caught or born
+    pub bred: u64,
+}
+
+impl Animal {
+
+    pub fn wild(kind: &str) -> Animal {
+        Animal {
+            kind: String::from(kind),
+            taming: Taming::Wild,
+            fed: 0,
+            produced: 0,
+            bred: 0,
+        }
+    }
+
+    /// One born in a pen at tick, as tame as its parents
+    pub fn born(kind: &str, tick: u64) -> Animal {
+        let mut animal = Animal::wild(kind);
+        animal.tame(tick);
+        animal
+    }
+
+    /// Caught at tick, it starts out fed
+    pub fn tame(&mut self, tick: u64) {
+        self.taming = Taming::Tame;
+        self.fed = tick;
+        self.produced = tick;
+        self.bred = tick;
+    }
+
+    pub fn is_tame(&self) -> bool {
+        self.taming == Taming::Tame
+    }
+
+    pub fn is_hungry(&self, tick: u64) -> bool {
+        self.is_tame() && tick.saturating_sub(self.fed) >= HUNGRY_AFTER
+    }
+
+    pub fn is_starving(&self, tick: u64) -> bool {
+        self.is_tame() && tick.saturating_sub(self.fed) >= STARVING_AFTER
+    }
+
+    /// Whether a colonist should see to it, to catch or feed it
+    pub fn needs_tending(&self, tick: u64) -> bool {
+        self.taming == Taming::Marked || self.is_hungry(tick)
+    }
+
+    /// Whether it's due to leave something lying about
+    pub fn produces(&self, tick: u64, livestock: &LivestockDef) -> bool {
+        !self.is_hungry(tick) && tick.saturating_sub(self.produced) >= livestock.every
+    }
+
+    /// Whether it's fed and it's been long enough since it last bred
+    pub fn can_breed(&self, tick: u64, livestock: &LivestockDef) -> bool {
+        livestock.breed_every > 0 && !self.is_hungry(tick)
+            && tick.saturating_sub(self.bred) >= livestock.breed_every
+    }
+}
+
+/// A kind of creature that can be kept, with the item it gives
+#[derive(Clone, Debug)]
+pub struct Breed {
+    pub creature: CreatureDef,
+    pub livestock: LivestockDef,
+    pub produce: ItemDef,
+}
+
+/// The creatures of the content packs that can be kept, which every game
+/// has to load the same
+#[derive(Clone, Debug, Default)]
+pub struct Ranch {
+    breeds: Vec<Breed>,
+}
+
+impl Ranch {
+
+    /// Creatures whose produce no pack defines can't be kept
+    pub fn new(content: &Content) -> Ranch {
+        Ranch::with_breeds(content.creatures.iter()
+            .filter_map(|creature| {
+                let livestock = creature.livestock.clone()?;
+                let produce = content.items.get(&livestock.produces)?.clone();
+                Some(Breed { creature: creature.clone(), livestock, produce })
+            })
+            .collect())
+    }
+
+    pub fn with_breeds(breeds: Vec<Breed>) -> Ranch {
+        Ranch { breeds }
+    }
+
+    pub fn breed(&self, kind: &str) -> Option<&Breed> {
+        self.breeds.iter().find(|breed| breed.creature.id == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kept_creatures_need_feeding() {
+        let livestock = LivestockDef { produces: String::from("egg"), every: 100, breed_every: 0 };
+        let mut animal = Animal::wild("hen");
+        assert!(!animal.needs_tending(HUNGRY_AFTER * 2));
+        animal.taming = Taming::Marked;
+        assert!(animal.needs_tending(0));
+
+        animal.tame(1000);
+        println!("{:?}", animal);
+        assert!(!animal.needs_tending(1000));
+        assert!(!animal.produces(1099, &livestock));
+        assert!(animal.produces(1100, &livestock));
+        assert!(!animal.can_breed(5000, &livestock));
+
+        let hungry = 1000 + HUNGRY_AFTER;
+        assert!(animal.is_hungry(hungry));
+        assert!(!animal.produces(hungry, &livestock));
+        assert!(!animal.is_starving(hungry));
+        assert!(animal.is_starving(1000 + STARVING_AFTER));
+        animal.fed = hungry;
+        assert!(!animal.needs_tending(hungry));
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Where the string bundles are read from, a file for each language named
+/// by its code, like fr.txt
+pub const LANG_DIR: &str = "lang";
+/// The language every string is written in first, built into the game
+/// so there's always something to fall back to
+pub const DEFAULT_LANGUAGE: &str = "en";
+const ENGLISH: &str = include_str!("../lang/en.txt");
+
+/// The text the UI shows in a language, looked up by key. A bundle is a
+/// line for each string, `key = text`, with `{name}` wherever something
+/// is filled in and # starting a comment line. The `language` key is what
+/// the language calls itself.
+pub struct Locale {
+    strings: HashMap<String, String>,
+    english: HashMap<String, String>,
+}
+
+impl Locale {
+
+    /// The language's bundle from LANG_DIR, English if there is none or it
+    /// can't be read
+    pub fn load(language: &str) -> Locale {
+        if language == DEFAULT_LANGUAGE {
+            return Locale::english();
+        }
+        let path = Path::new(LANG_DIR).join(format!("{}.txt", language));
+        match fs::read_to_string(&path) {
+            Ok(text) => Locale { strings: parse(&text), english: parse(ENGLISH) },
+            Err(err) => {
+                log::warn!("could not read {}: {}", path.display(), err);
+                Locale::english()
+            },
+        }
+    }
+
+    pub fn english() -> Locale {
+        Locale { strings: HashMap::new(), english: parse(ENGLISH) }
+    }
+
+    /// The text for the key, in English when the bundle doesn't have it
+    /// and the key itself when nothing does
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).or_else(|| self.english.get(key)).map_or(key, String::as_str)
+    }
+
+    /// The text for the key with each {name} in it filled in
+    pub fn format(&self, key: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+        let mut text = String::from(self.get(key));
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+
+    /// What the language calls itself
+    pub fn name(&self) -> &str {
+        self.get("language")
+    }
+}
+
+/// The code of every language with a bundle in LANG_DIR and English, in
+/// order of their codes
+pub fn languages() -> Vec<String> {
+    let mut codes: Vec<String> = match fs::read_dir(LANG_DIR) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    if !codes.iter().any(|code| code == DEFAULT_LANGUAGE) {
+        codes.push(String::from(DEFAULT_LANGUAGE));
+    }
+    codes.sort();
+    codes
+}
+
+/// The strings of a bundle by key, lines that aren't `key = text` are
+/// skipped
+fn parse(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (String::from(key.trim()), String::from(text.trim())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strings_fall_back_to_english() {
+        let french = Locale {
+            strings: parse("# a comment\nlanguage = Français\npause.resume = Reprendre\nnot a string"),
+            english: parse(ENGLISH),
+        };
+        println!("{} {}", french.name(), french.get("pause.resume"));
+        assert_eq!(french.get("pause.resume"), "Reprendre");
+        assert_eq!(french.get("pause.quit"), "Quit");
+        assert_eq!(french.get("no.such.key"), "no.such.key");
+        assert_eq!(french.format("log.tamed", &[("x", &1), ("y", &2), ("z", &3)]),
+                   "A creature was caught at (1, 2, 3)");
+
+        // every language shipped has the same keys as English
+        let english = parse(ENGLISH);
+        for code in languages() {
+            let text = fs::read_to_string(Path::new(LANG_DIR).join(format!("{}.txt", code))).unwrap();
+            let missing: Vec<_> = english.keys().filter(|key| !parse(&text).contains_key(*key)).collect();
+            assert!(missing.is_empty(), "{} is missing {:?}", code, missing);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Targets the subsystems log under, filter on them with JMC_LOG
+pub const WORLDGEN: &str = "worldgen";
+pub const RENDER: &str = "render";
+pub const JOBS: &str = "jobs";
+pub const SAVE: &str = "save";
+pub const CONFIG: &str = "config";
+
+/// Where the log is written, relative to the working directory
+pub const LOG_DIR: &str = "logs";
+const LOG_FILE: &str = "jmc.log";
+/// The log moves to jmc.log.1 once it grows past this, the older ones
+/// shift along and the oldest is dropped
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+const KEPT_LOGS: u32 = 3;
+
+/// Names the environment variable holding the filter, like
+/// "info,worldgen=debug,render=trace"
+const FILTER_VAR: &str = "JMC_LOG";
+
+/// Which level to log at, overall and for particular targets
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    pub default: LevelFilter,
+    pub targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+
+    /// Reads comma separated "level" and "target=level" entries, anything
+    /// that isn't a level is skipped
+    pub fn parse(spec: &str) -> Filter {
+        let mut filter = Filter { default: LevelFilter::Info, targets: Vec::new() };
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            match entry.split_once('=') {
+                Some((target, level)) => if let Ok(level) = level.parse() {
+                    filter.targets.push((String::from(target), level));
+                },
+                None => if let Ok(level) = entry.parse() {
+                    filter.default = level;
+                },
+            }
+        }
+        filter
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets.iter()
+            .rev()
+            .find(|(name, _)| target.starts_with(name.as_str()))
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most detailed level anything is logged at
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+}
+
+/// Writes log records to a file in LOG_DIR, starting a new one when it
+/// gets too big
+struct FileLogger {
+    filter: Filter,
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileLogger {
+
+    fn rotate(&self) {
+        for index in (1..KEPT_LOGS).rev() {
+            let _ = fs::rename(numbered(&self.path, index), numbered(&self.path, index + 1));
+        }
+        let _ = fs::rename(&self.path, numbered(&self.path, 1));
+    }
+}
+
+fn numbered(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+fn open(path: &Path) -> Option<File> {
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+impl Log for FileLogger {
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut file = match self.file.lock() {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        let too_big = file.as_ref()
+            .and_then(|file| file.metadata().ok())
+            .is_some_and(|metadata| metadata.len() > MAX_LOG_BYTES);
+        if too_big {
+            *file = None;
+            self.rotate();
+            *file = open(&self.path);
+        }
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0.0, |time| time.as_secs_f64());
+        if let Some(file) = file.as_mut() {
+            let _ = writeln!(file, "{:.3} {:5} {}: {}",
+                             seconds, record.level(), record.target(), record.args());
+        }
+        if record.level() <= Level::Warn {
+            eprintln!("{}: {}", record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Starts logging to LOG_DIR with the filter from JMC_LOG, info and up by
+/// default. The game runs on without a log if the file can't be opened.
+pub fn init() {
+    let filter = Filter::parse(&std::env::var(FILTER_VAR).unwrap_or_default());
+    let path = Path::new(LOG_DIR).join(LOG_FILE);
+    let file = fs::create_dir_all(LOG_DIR).ok().and_then(|_| open(&path));
+    if file.is_none() {
+        eprintln!("could not open {}, not logging", path.display());
+    }
+    let max_level = filter.max_level();
+    let logger = FileLogger { filter, path, file: Mutex::new(file) };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Logs how long it lived when dropped, put one at the top of something
+/// to time it
+pub struct Span {
+    target: &'static str,
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        log::debug!(target: self.target, "{} took {:?}", self.name, self.start.elapsed());
+    }
+}
+
+/// Times the rest of the enclosing scope, only when debug logging is on
+/// for the target
+pub fn span(target: &'static str, name: &'static str) -> Option<Span> {
+    if log::log_enabled!(target: target, Level::Debug) {
+        Some(Span { target, name, start: Instant::now() })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_targets() {
+        let filter = Filter::parse("warn, worldgen=trace ,render=debug,jobs=loud");
+        println!("{:?}", filter);
+        assert_eq!(filter.default, LevelFilter::Warn);
+        assert_eq!(filter.level_for(WORLDGEN), LevelFilter::Trace);
+        assert_eq!(filter.level_for(RENDER), LevelFilter::Debug);
+        assert_eq!(filter.level_for(JOBS), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert_eq!(Filter::parse("").level_for("mc"), LevelFilter::Info);
+    }
+}
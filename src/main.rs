@@ -1,22 +1,154 @@
-use enum_map::{enum_map, Enum, EnumMap};
 use quicksilver::prelude::*;
-use quicksilver::graphics::View;
+use quicksilver::graphics::{Surface, View};
 
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
-mod game_map;
-mod color_scheme;
 mod camera;
+mod dialog;
+mod command_menu;
+mod console;
+mod export;
+mod layer;
+mod panel;
+mod ui;
+mod config;
+mod sound;
+mod overlay;
+mod bindings;
+mod assets;
+mod particles;
+mod offscreen;
+mod profiler;
+mod chunk_grid;
+mod text_input;
+mod slots;
+mod site_picker;
+mod tutorial;
+mod locale;
+mod text_layout;
+mod appearance;
+mod hot_reload;
 
-use game_map::GameMap;
-use color_scheme::{ColorScheme, ColorName};
+use mc::{
+    animation, blueprint, chunk_cache, rooms, color_scheme, desync, designation, difficulty, embark, entity, equipment, error, events, game_map, logging, mods, net,
+    pathfinding, rail, save, scenario, scripting, selection, simulation, skills, spawner, stats, support, timestep,
+    travel, livestock, economy, incidents, chronicle, status, farming, trends, worldgen,
+};
+
+use game_map::{GameMap, Tile, TileKind};
+use worldgen::WorldGenConfig;
+use desync::{Checksum, Snapshot};
+use blueprint::Blueprint;
+use rooms::RoomType;
+use color_scheme::{average_color, Blend, ColorScheme, ColorName};
 use camera::Camera;
+use dialog::Dialog;
+use entity::{Entity, EntityId, Footprint, Movement};
+use equipment::Gear;
+use skills::Skill;
+use status::Status;
+use save::{SaveData, SlotInfo, Thumbnail};
+use selection::{Area, AreaCommand, Selection};
+use overlay::{Overlay, OverlayContext};
+use scripting::{Scripts, World};
+use mods::Content;
+use stats::Stats;
+use chronicle::Chronicle;
+use bindings::{Action, Bindings};
+use error::{GameError, GameResult};
+use assets::Assets;
+use animation::{AnimationClock, Renderable};
+use particles::Particles;
+use offscreen::OffscreenAlerts;
+use travel::{Destination, Travel};
+use simulation::Simulation;
+use spawner::Spawner;
+use livestock::{Ranch, Taming};
+use farming::Seeds;
+use economy::Economy;
+use incidents::Incident;
+use difficulty::Difficulty;
+use profiler::{Profiler, System, Timer};
+use chunk_grid::ChunkGrid;
+use timestep::FixedTimestep;
+use net::{Build, Client, Host, Named, Order, Role, Session};
+use command_menu::{Command, Menu, Mode, Naming, Reading, Trading, MAX_NAME_LEN};
+use console::Console;
+use text_input::{Filter, TextInput};
+use export::OffscreenRenderer;
+use layer::RenderLayer;
+use panel::{Anchor, Panel};
+use ui::{UiComponent, UiRegistry};
+use slots::{SlotAction, SlotChoice, SlotScreen};
+use site_picker::{SiteChoice, SitePicker};
+use appearance::{AppearanceChoice, AppearancePicker};
+use hot_reload::{AssetWatcher, Reload};
+use tutorial::{Progress, Tutorial};
+use scenario::{PlacementKind, Scenario};
+use events::{GameEvent, Loss};
+use config::Config;
+use locale::Locale;
+use text_layout::{TextLayout, TextStyle};
+use sound::Audio;
 
 const FONT_MONONOKI: &'static str = "mononoki-Regular.ttf";
 const FONT_SQUARE: &'static str = "square.ttf";
 const FONT_ZODIAC_SQUARE: &'static str = "zodiac-square.ttf";
 
+/// Fonts the tileset is rendered from and the glyphs taken from each
+const TILESET_GLYPHS: [(&str, &str); 2] = [
+    (FONT_SQUARE, "#@g.%08*"),
+    (FONT_ZODIAC_SQUARE, "™↺∆░▒▓∷•‧≈╠╬╣╔╗╚╝╦╩═║"),
+];
+
+/// Width of a column of the help screen at 1x UI scale
+const HELP_COLUMN_WIDTH: f32 = 400.0;
+/// Widest a note shown under the mouse gets before it wraps, at 1x UI
+/// scale
+const NOTE_WIDTH: f32 = 320.0;
+
+/// Largest area export_png renders, in tiles per side
+const MAX_EXPORT_SIZE: u32 = 1024;
+
+const CONSOLE_HELP: &str = "help: list commands
+export_png x y width height [z]: render an area of a level, the current one by default, to a png
+screenshot: render the map view to a png, same as F12
+export_ascii: write the glyphs of the map view to a text file
+drive dx dy: send the closest drill or other large machine on this level dx, dy tiles
+travel x y: send the player to x, y on the level they're on, same as right clicking there
+explore: send the player to see the nearest open tiles they haven't seen yet, until nothing is left nearby
+mods: list the loaded content packs and what they add
+spawn creature: put a creature from a content pack in front of the player
+incident name: make a tremor, gas_release, lost_miner, equipment_failure or fire befall the colony now
+blueprint export|import [file]: write the blueprint last copied to a json file, or read one back to stamp
+colors scheme: switch to a color scheme from a content pack
+appearance [glyph color]: pick the player's glyph and color, or set them straight away like appearance g LightAqua";
+
+/// Screen position of the top left corner of the map view, at 1x UI scale
+const MAP_OFFSET_PX: (f32, f32) = (50.0, 100.0);
+/// Size of a tile at 1x UI scale
+const TILE_SIZE_PX: f32 = 18.0;
+/// At or below this zoom the map is drawn as blocks of averaged tiles
+const FAR_ZOOM: f32 = 0.25;
+/// Tiles per side of a block in the far zoom view
+const FAR_ZOOM_BLOCK: u32 = 4;
+/// Dust or drops the weather blows into view each frame on the surface
+const WEATHER_PARTICLES: u32 = 2;
+/// How many tiles the map view spans at 1x UI scale, fewer fit on the
+/// screen as the scale goes up
+const VIEWPORT_TILES: (f32, f32) = (60.0, 30.0);
+/// Tiles the picture in picture spans
+const PIP_TILES: (u32, u32) = (16, 10);
+/// Pixels between the two views of a split map at 1x UI scale
+const SPLIT_GAP_PX: f32 = 8.0;
+/// Tiles kept between the selection cursor and the edges of the view as
+/// it's moved with the keyboard
+const CURSOR_MARGIN: f32 = 3.0;
+/// Entries of the colony's history shown at once
+const CHRONICLE_ROWS: usize = 15;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 struct Position<T> {
     x: T,
@@ -24,148 +156,267 @@ struct Position<T> {
     z: T,
 }
 
-struct Tileset {
-    tile_map: HashMap<char, Image>,
+/// The parts of the screen drawn over the void, in the order they're drawn.
+/// The ones without a toggle key are toggled from the view menu, or show
+/// whenever what they're about is there.
+fn register_ui(ui: &mut UiRegistry<Game>) {
+    ui.register(UiComponent::new("title", true, Game::draw_title).with_toggle(Action::ToggleTitle));
+    ui.register(UiComponent::new("map", true, Game::draw_map_view).with_toggle(Action::ToggleMap));
+    ui.register(UiComponent::new("pip", true, Game::draw_pip));
+    ui.register(UiComponent::new("credits", false, Game::draw_credits).with_toggle(Action::ToggleCredits));
+    ui.register(UiComponent::new("debug", true, Game::draw_debug).with_toggle(Action::ToggleDebug));
+    // the selected colonist's health, gear and skills
+    ui.register(UiComponent::new("details", true, Game::draw_details));
+    // the tick and the weather forecast
+    ui.register(UiComponent::new("clock", true, Game::draw_clock));
+    // how many of each item the stockpiles hold
+    ui.register(UiComponent::new("resources", true, Game::draw_resources));
+    ui.register(UiComponent::new("alert", true, Game::draw_alert));
+    ui.register(UiComponent::new("trade", true, Game::draw_trade));
+    ui.register(UiComponent::new("chronicle", true, Game::draw_chronicle));
+    ui.register(UiComponent::new("notes", true, Game::draw_notes));
+    ui.register(UiComponent::new("rooms", true, Game::draw_rooms));
+    ui.register(UiComponent::new("selection", true, Game::draw_selection));
+    ui.register(UiComponent::new("command bar", true, Game::draw_command_bar));
+    ui.register(UiComponent::new("tutorial", true, Game::draw_tutorial));
+    ui.register(UiComponent::new("help", false, Game::draw_help).with_toggle(Action::Help));
 }
 
-impl Tileset {
+enum GameState {
+    Playing,
+    Paused(Dialog),
+    Settings(Dialog),
+    Statistics(Dialog),
+    ConfirmQuit(Dialog),
+    /// Asks for the seed of a new colony
+    NewColony(Dialog),
+    /// Picks a slot to save into or load from
+    Slots(SlotScreen),
+    /// Picks where a new colony lands
+    Embark(SitePicker),
+    /// Asks what the colony that just landed is called
+    NameColony(Dialog),
+    /// Picks the glyph and color the player is drawn with
+    Appearance(AppearancePicker),
+    /// Sums up a colony that was lost or a scenario that was won
+    GameOver(Dialog),
+    /// Lists the scenarios there are to play
+    Scenarios(Dialog, Vec<Scenario>),
+    /// Lists the errors that happened, the game carries on if it can
+    Failed(Dialog),
+    /// Offers to restore the colony autosaved when the window last closed,
+    /// going on to the state after it otherwise
+    Restore(Dialog, SlotInfo, Box<GameState>),
+    Quitting,
+}
 
-    fn new(glyph_map: Vec<(String, String)>, tile_size_px: Vector) -> Tileset {
-        Tileset {
-            tile_map: Tileset::render(glyph_map, tile_size_px),
-        }
-    }
-    
-    fn render(glyph_map: Vec<(String, String)>, tile_size_px: Vector) 
-        -> HashMap<char, Image> {
+// Options of the quit confirmation dialog, in display order
+const QUIT_SAVE: usize = 0;
+const QUIT_DISCARD: usize = 1;
+const QUIT_CANCEL: usize = 2;
 
-        let mut tile_map = HashMap::new();
-        for (font_name, glyphs) in glyph_map {
-            tile_map.extend(Font::load(font_name).and_then(move |font: Font| {
-                let tiles = font
-                    .render(glyphs.as_str(), &FontStyle::new(tile_size_px.y, Color::WHITE))
-                    .expect("could not render tileset.");
-                let mut _tile_map = HashMap::new();
-                for (index, glyph) in glyphs.chars().enumerate() {
-                    let pos = (index as u32 * tile_size_px.x as u32, 0);
-                    let tile = tiles.subimage(Rectangle::new(pos, tile_size_px));
-                    _tile_map.insert(glyph, tile);
-                }
-                Ok(_tile_map)
-            }).wait().unwrap());
-        }
+// Options of the error screen
+const FAILED_CONTINUE: usize = 0;
+const FAILED_QUIT: usize = 1;
 
-        tile_map
+// Options of the autosave prompt, the other discards it
+const RESTORE_AUTOSAVE: usize = 0;
 
-    }
+// Options of the pause menu
+const PAUSE_RESUME: usize = 0;
+const PAUSE_SAVE: usize = 1;
+const PAUSE_SAVE_AS: usize = 2;
+const PAUSE_LOAD: usize = 3;
+const PAUSE_NEW_COLONY: usize = 4;
+const PAUSE_STATISTICS: usize = 5;
+const PAUSE_SETTINGS: usize = 6;
+const PAUSE_QUIT: usize = 7;
+
+// Options of the game over screen
+const GAME_OVER_LOAD: usize = 0;
+const GAME_OVER_NEW_COLONY: usize = 1;
+const GAME_OVER_WATCH: usize = 2;
+const GAME_OVER_QUIT: usize = 3;
+
+/// Size of a slot's thumbnail in pixels, each the average of a square of
+/// THUMBNAIL_BLOCK tiles
+const THUMBNAIL_SIZE: (u32, u32) = (96, 48);
+const THUMBNAIL_BLOCK: u32 = 2;
+
+/// Size in pixels of each graph on the statistics screen, one above the
+/// other with a gap between
+const GRAPH_SIZE: (u32, u32) = (trends::DAYS_KEPT as u32 * 4, 24);
+const GRAPH_GAP: u32 = 4;
+
+// Options of the new colony dialog
+const NEW_COLONY_START: usize = 0;
+const NEW_COLONY_SCENARIOS: usize = 1;
+/// Digits in the largest seed
+const SEED_DIGITS: usize = 10;
 
+// Options of the settings screen
+const SETTING_FOLLOW: usize = 0;
+const SETTING_TITLE: usize = 1;
+const SETTING_DEBUG: usize = 2;
+const SETTING_MUSIC: usize = 3;
+const SETTING_EFFECTS: usize = 4;
+const SETTING_PALETTE: usize = 5;
+const SETTING_SHAPE_CUES: usize = 6;
+const SETTING_UI_SCALE: usize = 7;
+const SETTING_OVERSEER: usize = 8;
+const SETTING_MOVEMENT_KEYS: usize = 9;
+const SETTING_GLIDE: usize = 10;
+const SETTING_CACHE_CHUNKS: usize = 11;
+const SETTING_KEYBOARD_LAYOUT: usize = 12;
+const SETTING_LANGUAGE: usize = 13;
+const SETTING_BRIGHTNESS: usize = 14;
+const SETTING_CONTRAST: usize = 15;
+const SETTING_DEPTH_SHADING: usize = 16;
+const SETTING_SMOOTH_TERRAIN: usize = 17;
+const SETTINGS_BACK: usize = 18;
+
+/// The second view when the map is split down the middle, each with a
+/// camera of its own
+struct SplitView {
+    /// The camera of the view without the keyboard, the game's camera is
+    /// the one with it
+    camera: Camera,
+    /// Whether the view with the keyboard is the one on the right
+    focus_right: bool,
 }
 
-#[derive(Enum)]
-enum UiComponent {
-    Map,
-    Title,
-    Credits,
-    Debug,
+/// A small view in the corner of the screen following one entity about,
+/// drawn into a picture of its own so it can go over the map
+struct Pip {
+    /// Follows whoever's watched
+    camera: Camera,
+    surface: Surface,
 }
 
 struct Game {
     title: Asset<Image>,
+    ui_font: Asset<Font>,
+    /// Measures text in the UI font so panels can wrap it
+    text_layout: TextLayout,
     font_info: Vec<Asset<Image>>,
-    map: GameMap,
-    entities: Vec<Entity>,
-    player_id: usize,
-    tileset: Tileset,
+    /// The colony itself, everything else is how it's shown and steered
+    sim: Simulation,
+    /// The selected colonist, the one the keyboard and move orders steer.
+    /// None while overseeing the colony, when nobody is steered.
+    player_id: Option<EntityId>,
+    /// Direction of the player's last step, where pillars get built
+    player_facing: (i32, i32),
+    assets: Assets,
+    /// Plays entity and tile animations, drawing only
+    animation_clock: AnimationClock,
+    /// Dust, splashes and hit flashes drawn over the map
+    particles: Particles,
+    /// Arrows at the edge of the map toward cave-ins, fights and floods out
+    /// of view
+    alerts: OffscreenAlerts,
+    /// Per system timings for the debug overlay
+    profiler: Profiler,
     tile_size_px: Vector,
     color_scheme: ColorScheme,
     camera: Camera,
-    ui_components: EnumMap<UiComponent, bool>,
+    split: Option<SplitView>,
+    pip: Option<Pip>,
+    ui: UiRegistry<Game>,
     input_timer: Instant,
+    /// Counts out simulation ticks at TICKS_PER_SECOND
+    timestep: FixedTimestep,
+    /// When the last update ran, play time is counted from it
+    last_update: Instant,
+    /// How long the colony has been played, dialogs don't count
+    play_time: Duration,
+    /// The slot Save writes to, named after the colony
+    slot_profile: String,
+    /// What the window's title bar was last set to
+    window_title: String,
+    /// Where the player is walking to on their own, if anywhere
+    travel: Option<Travel>,
+    /// The scenario being played through instead of a colony, if any
+    scenario: Option<Tutorial>,
+    state: GameState,
+    /// Open menus and tools, Escape pops the topmost one
+    modes: Vec<Mode>,
+    console: Console,
+    audio: Audio,
+    config: Config,
+    /// Summary of the last inspected area
+    inspection: Option<String>,
+    /// The blueprint last copied or imported, stamped from the blueprint
+    /// menu
+    blueprint: Option<Blueprint>,
+    overlays: Vec<Box<dyn Overlay>>,
+    /// Index of the overlay the map is colored by, if any
+    overlay: Option<usize>,
+    /// Debug lines over the map showing where chunks and tiles meet
+    chunk_grid: ChunkGrid,
+    /// The network game this is part of, if any
+    net: Option<Session>,
+    /// Who this player is in a network game, orders are told apart by it
+    peer: u32,
+    scripts: Scripts,
+    /// Materials, items, recipes, creatures and color schemes from mods
+    content: Content,
+    stats: Stats,
+    chronicle: Chronicle,
+    bindings: Bindings,
+    /// The UI's text in the language picked in settings
+    locale: Locale,
+    /// Reloads fonts, content packs and languages as they're edited, when
+    /// started with --watch
+    watcher: Option<AssetWatcher>,
+    /// Writes out the snapshot behind each checksum in a network game, when
+    /// started with --desync-dumps
+    desync_dumps: bool,
+    /// Page of the help screen shown, clamped to the last one when drawn
+    help_page: usize,
+    /// Everything that went wrong so far, listed on the error screen
+    errors: Vec<GameError>,
 }
 
 impl State for Game {
     /// Load the assets and initialize the game
     fn new() -> Result<Self> {
-        let color_scheme = ColorScheme {
-            bg:           String::from("#282828"),
-            fg:           String::from("#ebdbb2"),
-            fg0:          String::from("#fbf1c7"),
-            fg1:          String::from("#ebdbb2"),
-            fg2:          String::from("#d5c4a1"),
-            fg3:          String::from("#bdae93"),
-            fg4:          String::from("#a89984"),
-            gray:         String::from("#a89984"),
-            light_gray:   String::from("#928374"),
-            red:          String::from("#cc241d"),
-            light_red:    String::from("#fb4934"),
-            green:        String::from("#98971a"),
-            light_green:  String::from("#b8bb26"),
-            yellow:       String::from("#d79921"),
-            light_yellow: String::from("#fabd2f"),
-            blue:         String::from("#458588"),
-            light_blue:   String::from("#83a598"),
-            purple:       String::from("#b16286"),
-            light_purple: String::from("#d3869b"),
-            aqua:         String::from("#689d6a"),
-            light_aqua:   String::from("#8ec07c"),
-            orange:       String::from("#d65d0e"),
-            light_orange: String::from("#fe8019"),
-            void:         String::from("#1d2021"),
-            stone0:       String::from("#282828"),
-            stone1:       String::from("#32302f"),
-            stone2:       String::from("#3c3836"),
-            stone3:       String::from("#504945"),
-            stone4:       String::from("#665c54"),
-            stone5:       String::from("#7c6f64"),
-            stone6:       String::from("#928374"),
-        };
-
-        let ui_components = enum_map! {
-            UiComponent::Title => true,
-            UiComponent::Map => true,
-            UiComponent::Credits => false,
-            UiComponent::Debug => true,
-        };
-
-        let title_style = FontStyle::new(72.0, Color::from_hex(&color_scheme.fg));
-        let title = Asset::new(Font::load(FONT_MONONOKI).and_then(move |font| {
-            font.render("Janus 7 Mining Colony", &title_style)
-        }));
+        let config = Config::load();
+        let mut color_scheme = ColorScheme::from_palette(config.palette);
+        color_scheme.adjust = config.color_adjust();
+
+        let mut ui = UiRegistry::new();
+        register_ui(&mut ui);
+
+        let font_info = render_font_info(&color_scheme, config.ui_scale);
+
+        let ui_font = Asset::new(Font::load(FONT_MONONOKI));
+
+        let (session, joined_world, network_error) = match start_network() {
+            Ok((session, world)) => (session, world, None),
+            Err(err) => (None, None, Some(err)),
+        };
+        let peer = match &session {
+            Some(Session::Client(client)) => client.peer,
+            _ => net::HOST_PEER,
+        };
+        let joined = joined_world.is_some();
+        // the slot saved last, or the save from before there were slots
+        let slot = save::load_slots().into_iter().next();
+        let slot_profile = slot.as_ref()
+            .map_or_else(|| String::from(save::SAVE_PROFILE), |slot| slot.profile.clone());
+        let save_data = joined_world.or_else(|| save::load_game(&slot_profile).ok());
+        let fresh = save_data.is_none();
+
+        let map = match &save_data {
+            Some(data) => world_map(&config, data.random_seed, data.difficulty),
+            None => GameMap::new(),
+        };
 
-        let mononoki_font_info_style = FontStyle::new(20.0, Color::from_hex(&color_scheme.fg));
-        let square_font_info_style = FontStyle::new(12.0, Color::from_hex(&color_scheme.fg));
-        let zodiac_square_font_info_style = FontStyle::new(12.0, Color::from_hex(&color_scheme.fg));
-        let font_info = vec! {
-            Asset::new(Font::load(FONT_MONONOKI).and_then(move |font| {
-                font.render(
-                    "Mononoki font by Matthias Tellen, terms: SIL Open Font License 1.1",
-                    &mononoki_font_info_style,
-                    )
-            })),
-            Asset::new(Font::load(FONT_SQUARE).and_then(move |font| {
-                font.render(
-                    "Square font by Wouter Van Oortmerssen, terms: CC BY 3.0",
-                    &square_font_info_style,
-                    )
-            })),
-            Asset::new(Font::load(FONT_ZODIAC_SQUARE).and_then(move |font| {
-                font.render(
-                    "Zodiac Square font by Elementalist, terms: CC0",
-                    &zodiac_square_font_info_style,
-                    )
-            })),
-        };
-
-        let map = GameMap::new();
-
-        let camera_width = 60;
-        let camera_height = 30;
+        let (camera_width, camera_height) = viewport_tiles(config.ui_scale);
        
-        let initial_pos_x = (map.max_chuncks_x * map.chunk_size) / 2;
-        let initial_pos_y = (map.max_chuncks_y * map.chunk_size) / 2;
-        let initial_pos_z = 32;
+        let (initial_pos_x, initial_pos_y, initial_pos_z) = simulation::start_position(&map);
 
-        let camera = Camera::new(
+        let mut camera = Camera::new(
             initial_pos_x, 
             initial_pos_y, 
             initial_pos_z, 
@@ -175,386 +426,3940 @@ impl State for Game {
             (camera_width, camera_height),
         ); 
         
-        let mut entities = generate_entities(
-            initial_pos_x, initial_pos_y, initial_pos_z);
-        let player_id = entities.len();
-        entities.push(Entity {
-            pos: Vector::new(initial_pos_x + 29, initial_pos_y + 20),
-            depth: initial_pos_z,
-            glyph: '0',
-            color: ColorName::LightOrange,
-            hp: 3,
-            max_hp: 5,
-        });
+        let mut sim = Simulation::new(map);
+        if let (Some(slot), false) = (&slot, joined) {
+            sim.name = slot.name.clone();
+        }
+        let mut stats = Stats::new();
+        let mut chronicle = Chronicle::new();
 
-        let tile_size_px = Vector::new(18, 18);
-        let glyph_map = vec! {
-            (String::from(FONT_SQUARE), 
-             String::from("#@g.%08*")),
+        let (content, mut startup_log) = Content::load(Path::new(mods::MODS_DIR));
+        sim.spawner = Spawner::new(&content);
+        sim.ranch = Ranch::new(&content);
+        sim.seeds = Seeds::new(&content);
+        sim.economy = Economy::new(&content);
 
-            (String::from(FONT_ZODIAC_SQUARE), 
-             String::from("™↺∆░▒▓∷•‧≈╠╬╣╔╗╚╝╦╩═║")),
+        let mut scripts = Scripts::new();
+        let script_log = scripts.load_dir(Path::new(scripting::SCRIPTS_DIR));
+        startup_log.extend(script_log);
+        startup_log.extend(sim.run_worldgen(&scripts));
+
+        let player_id = match save_data {
+            Some(data) => {
+                sim.restore(&data);
+                camera.go_to(data.camera_x, data.camera_y, data.camera_z);
+                camera.follow = data.camera_follow;
+                stats = data.stats;
+                chronicle = data.chronicle;
+                // someone joining takes a colonist nobody else is steering
+                match sim.squad.unsteered(&sim.entities) {
+                    Some(id) if joined => Some(id),
+                    _ => data.player_id,
+                }
+            },
+            None => Some(sim.populate(initial_pos_x, initial_pos_y, initial_pos_z)),
         };
-        let tileset = Tileset::new(glyph_map, tile_size_px);
+        let player_id = steered_colonist(&sim, player_id, config.overseer);
+        if joined {
+            if let Some(player) = player_id.and_then(|id| sim.entities.get(id)) {
+                camera.center_on(player.pos.x, player.pos.y, player.depth);
+            }
+        }
+
+        let tile_size_px = Vector::new(TILE_SIZE_PX, TILE_SIZE_PX) * config.ui_scale;
+        let mut assets = Assets::new(&TILESET_GLYPHS, tile_size_px);
+        assets.require(&content.glyphs());
+        assets.require(&Status::ALL.iter().map(Status::glyph).collect::<String>());
+        // seedlings, the farm plot cue, pumps, rain and notes
+        assets.require(",\"¤|¶");
+        let tileset_error = assets.render().err();
         
         let input_timer = Instant::now();
+        let bindings = Bindings::new(config.movement_keys, config.keyboard_layout);
+        let locale = Locale::load(&config.language);
 
-        Ok(Self {
-            title,
+        let watcher = if std::env::args().any(|arg| arg == hot_reload::WATCH_FLAG) {
+            match AssetWatcher::start() {
+                Ok(watcher) => {
+                    startup_log.extend(watcher.dirs().map(|dir| format!("watching {} for changes", dir.display())));
+                    Some(watcher)
+                },
+                Err(err) => {
+                    startup_log.push(err);
+                    None
+                },
+            }
+        } else {
+            None
+        };
+
+        let mut console = Console::new();
+        for line in startup_log {
+            console.print(&line);
+        }
+
+        let mut game = Self {
+            title: render_title(&color_scheme, config.ui_scale, &sim.name),
+            ui_font,
+            text_layout: TextLayout::load(FONT_MONONOKI),
             font_info,
-            map,
-            entities,
+            sim,
             player_id,
-            tileset,
+            player_facing: (0, 1),
+            assets,
+            animation_clock: AnimationClock::new(),
+            particles: Particles::new(),
+            alerts: OffscreenAlerts::new(),
+            profiler: Profiler::new(),
             tile_size_px,
             color_scheme,
             camera,
-            ui_components,
+            split: None,
+            pip: None,
+            ui,
             input_timer,
-        })
+            timestep: FixedTimestep::new(timestep::TICKS_PER_SECOND),
+            last_update: Instant::now(),
+            play_time: Duration::from_secs(slot.as_ref().map_or(0, |slot| slot.play_time_secs)),
+            slot_profile,
+            window_title: String::new(),
+            travel: None,
+            scenario: None,
+            state: GameState::Playing,
+            modes: Vec::new(),
+            console,
+            audio: Audio::new(),
+            config,
+            inspection: None,
+            blueprint: None,
+            overlays: overlay::overlays(),
+            overlay: None,
+            chunk_grid: ChunkGrid::Off,
+            net: session,
+            peer,
+            scripts,
+            content,
+            stats,
+            chronicle,
+            bindings,
+            locale,
+            watcher,
+            desync_dumps: std::env::args().any(|arg| arg == desync::DUMP_FLAG),
+            help_page: 0,
+            errors: Vec::new(),
+        };
+        if let Some(err) = tileset_error {
+            game.report(err);
+        }
+        if let Some(err) = network_error {
+            game.report(err);
+        }
+        if let Some(Session::Host(host)) = &game.net {
+            let port = host.port().unwrap_or(net::DEFAULT_PORT);
+            game.console.print(&format!("hosting {} on port {}", game.sim.name, port));
+        }
+        game.claim_control();
+        // a new game starts by picking where to land, the colony at the
+        // middle of the map stands in until then
+        if fresh && game.net.is_none() {
+            game.state = GameState::Embark(SitePicker::new(&game.sim.map, false));
+        } else if let Some(loss) = game.sim.lost {
+            game.open_game_over(loss);
+        }
+        if game.net.is_none() {
+            if let Some(slot) = save::load_autosave_slot() {
+                game.open_restore(slot);
+            }
+        }
+        Ok(game)
+    }
+
+    /// Handle window events that are not polled in update
+    fn event(&mut self, event: &Event, _window: &mut Window) -> Result<()> {
+        if let Event::Typed(c) = event {
+            match (&mut self.state, self.modes.last_mut()) {
+                (GameState::NewColony(dialog), _)
+                | (GameState::NameColony(dialog), _)
+                | (GameState::Slots(SlotScreen { dialog, .. }), _) => if let Some(field) = &mut dialog.field {
+                    field.type_char(*c);
+                },
+                (_, Some(Mode::Console)) => self.console.type_char(*c),
+                (_, Some(Mode::Name(naming))) => naming.input.type_char(*c),
+                _ => {},
+            }
+        }
+        if let Event::Closed = event {
+            match self.state {
+                GameState::Quitting => {},
+                // the autosave is still there for next time
+                GameState::Restore(..) => self.state = GameState::Quitting,
+                _ if self.unsaved() => {
+                    // Some backends tear the window down right after the 
+                    // close request, keep a copy in case the dialog never 
+                    // gets answered
+                    self.autosave();
+                    self.open_quit_dialog();
+                },
+                _ => self.state = GameState::Quitting,
+            }
+        }
+        Ok(())
     }
 
     /// Process keyboard and mouse, update the game state
     fn update(&mut self, window: &mut Window) -> Result<()> {
+        let timer = Timer::start(System::Input);
+        let result = self.update_game(window);
+        self.profiler.stop(timer);
+        result
+    }
+
+    /// Draw stuff on the screen
+    fn draw(&mut self, window: &mut Window) -> Result<()> {
+        let timer = Timer::start(System::RenderUi);
+        let window_view = View::new(
+                Rectangle::new(Vector::new(0.0, 0.0), window.screen_size()
+        ));
+        window.set_view(window_view);
+        window.clear(Color::from_hex(&self.color_scheme.void))?;
+
+        // a part that fails to draw is reported and skipped, so the rest
+        // of the screen and the error screen still show
+        for (name, draw) in self.ui.visible() {
+            let result = draw(self, window);
+            self.check_draw(name, result);
+        }
+
+        if let Some(Mode::Console) = self.modes.last() {
+            let (console, color_scheme) = (&self.console, &self.color_scheme);
+            let (scale, text_layout) = (self.config.ui_scale, &self.text_layout);
+            let result = self.ui_font.execute(|font| {
+                console.draw(window, font, text_layout, color_scheme, scale)
+            });
+            self.check_draw("console", result);
+        }
+
+        if let GameState::Paused(dialog) 
+             | GameState::Settings(dialog) 
+             | GameState::Statistics(dialog) 
+             | GameState::ConfirmQuit(dialog) 
+             | GameState::NewColony(dialog) 
+             | GameState::NameColony(dialog)
+             | GameState::GameOver(dialog)
+             | GameState::Scenarios(dialog, _)
+             | GameState::Slots(SlotScreen { dialog, .. })
+             | GameState::Failed(dialog)
+             | GameState::Restore(dialog, ..) = &self.state {
+            let color_scheme = &self.color_scheme;
+            let (scale, text_layout) = (self.config.ui_scale, &self.text_layout);
+            let result = self.ui_font.execute(|font| {
+                dialog.draw(window, font, text_layout, color_scheme, scale)
+            });
+            self.check_draw("dialog", result);
+        }
+
+        if let GameState::Embark(picker) = &self.state {
+            let color_scheme = &self.color_scheme;
+            let scale = self.config.ui_scale;
+            let result = self.ui_font.execute(|font| {
+                picker.draw(window, font, color_scheme, scale)
+            });
+            self.check_draw("site picker", result);
+        }
+
+        if let GameState::Appearance(picker) = &self.state {
+            let (color_scheme, glyphs, locale) = (&self.color_scheme, self.assets.for_zoom(1.0), &self.locale);
+            let scale = self.config.ui_scale;
+            let result = self.ui_font.execute(|font| {
+                picker.draw(window, font, &glyphs, color_scheme, locale, scale)
+            });
+            self.check_draw("appearance picker", result);
+        }
+
+        self.profiler.stop(timer);
+        self.profiler.end_frame();
+        Ok(())
+    }
+
+}
+
+impl Game {
+
+    /// Blows the weather's dust or drops across the view while the camera
+    /// is on the surface
+    fn blow_weather(&mut self) {
+        let z = self.camera.z_position;
+        let renderable = match self.sim.weather.weather.particle() {
+            Some(renderable) if self.sim.map.is_sunlit(z) => renderable,
+            _ => return,
+        };
+        let now_ms = self.animation_clock.elapsed_ms();
+        let mut rng = oorandom::Rand32::new(now_ms);
+        let viewport = self.camera.viewport;
+        let (width, height) = (viewport.width().max(1.0) as u32, viewport.height().max(1.0) as u32);
+        for _ in 0..WEATHER_PARTICLES {
+            let x = self.sim.map.wrap_x(viewport.x() as i64 + rng.rand_range(0..width) as i64);
+            let y = viewport.y() as u32 + rng.rand_range(0..height);
+            self.particles.spawn(x, y, z, renderable.clone(), now_ms);
+        }
+    }
+
+    /// Process keyboard and mouse, then step the simulation when it's due
+    fn update_game(&mut self, window: &mut Window) -> Result<()> {
         use ButtonState::*;
 
-        if self.input_timer.elapsed() >= Duration::from_millis(100) {
-            // camera controls
-            let camera = &mut self.camera;
+        self.audio.update(&self.config);
+        self.dispatch_events();
+        if let Err(err) = self.assets.update() {
+            self.report(err);
+        }
+        self.hot_reload();
+        self.particles.update(self.animation_clock.elapsed_ms());
+        let view = self.alert_view();
+        self.alerts.update(&view, self.animation_clock.elapsed_ms());
+        self.blow_weather();
 
-            if window.keyboard()[Key::Left].is_down() {
-                self.input_timer = Instant::now();
-                camera.move_left();
+        // the simulation is paused while a dialog is open, the ticks that
+        // come due in the meantime are dropped
+        let window_title = self.locale.format("window.title", &[("name", &self.sim.name)]);
+        if window_title != self.window_title {
+            window.set_title(&window_title);
+            self.window_title = window_title;
+        }
+
+        let ticks = self.timestep.advance();
+        let elapsed = self.last_update.elapsed();
+        self.last_update = Instant::now();
+        if self.update_dialog(window) {
+            return Ok(());
+        }
+        self.play_time += elapsed;
+
+        // the cursor scrolls the view while selecting, not whoever's followed
+        if let Some(id) = self.camera.follow.filter(|_| self.selection().is_none()) {
+            match self.sim.entities.get(id) {
+                Some(entity) => self.camera.center_on(
+                    entity.pos.x, entity.pos.y, entity.depth),
+                None => self.camera.follow = None,
             }
-            if window.keyboard()[Key::Right].is_down() {
-                self.input_timer = Instant::now();
-                camera.move_right();
+        }
+        // the picture in picture closes once whoever it watched is gone
+        let entities = &self.sim.entities;
+        if let Some(pip) = &mut self.pip {
+            match pip.camera.follow.and_then(|id| entities.get(id)) {
+                Some(entity) => pip.camera.center_on(entity.pos.x, entity.pos.y, entity.depth),
+                None => self.pip = None,
             }
-            if window.keyboard()[Key::Up].is_down() {
-                self.input_timer = Instant::now();
-                camera.move_up();
+        }
+
+        let simulation = Timer::start(System::Simulation);
+        self.run_ticks(ticks);
+        self.profiler.stop(simulation);
+        self.update_tutorial(window);
+
+        match self.modes.last_mut() {
+            Some(Mode::Menu(menu)) => {
+                if let Some(command) = menu.pressed(window) {
+                    self.run_command(command);
+                }
+            },
+            Some(Mode::Select(_)) => self.update_selection(window),
+            Some(Mode::Trade(_)) => self.update_trade(window),
+            Some(Mode::Chronicle(_)) => self.update_chronicle(window),
+            Some(Mode::Notes(_)) => self.update_notes(window),
+            Some(Mode::Rooms(_)) => self.update_rooms(window),
+            Some(Mode::Console) => {
+                self.console.input.handle_keys(window);
+                if window.keyboard()[Key::Return] == Pressed {
+                    let line = self.console.take_input();
+                    self.run_console_command(&line);
+                }
+            },
+            Some(Mode::Name(naming)) => {
+                naming.input.handle_keys(window);
+                if window.keyboard()[Key::Return] == Pressed {
+                    let (target, name) = (naming.target, naming.input.text());
+                    self.modes.pop();
+                    self.issue(Order::Name { target, name: String::from(name.trim()) });
+                }
+            },
+            None => {},
+        }
+
+        let pressed = self.bindings.pressed_actions(window);
+        if pressed[Action::Screenshot] {
+            let message = self.screenshot().unwrap_or_else(|err| err);
+            self.console.print(&message);
+        }
+
+        // the arrows pan the view every frame rather than a tile at a time
+        if !self.typing() {
+            let held = self.bindings.held_actions(window);
+            let (mut direction, mut fast) = (Vector::ZERO, false);
+            for (action, held) in held.iter() {
+                if let (true, Some((way, quick))) = (*held, action.pan()) {
+                    direction += way;
+                    fast |= quick;
+                }
             }
-            if window.keyboard()[Key::Down].is_down() {
-                self.input_timer = Instant::now();
-                camera.move_down();
+            if direction != Vector::ZERO {
+                self.camera.pan(direction, fast);
             }
-            if window.keyboard()[Key::Period].is_down() {
+        }
+        self.camera.update(elapsed.as_secs_f32(), self.config.camera_glide);
+
+        if !self.typing() && self.input_timer.elapsed() >= Duration::from_millis(100) {
+            // camera controls
+            let camera = &mut self.camera;
+            let held = self.bindings.held_actions(window);
+            let (x, y, z) = (camera.viewport.x(), camera.viewport.y(), camera.z_position);
+            if held[Action::LevelDown] {
                 self.input_timer = Instant::now();
                 camera.lower();
             }
-            if window.keyboard()[Key::Comma].is_down() {
+            if held[Action::LevelUp] {
                 self.input_timer = Instant::now();
                 camera.elevate();
             }
-            if window.keyboard()[Key::RBracket].is_down() {
+            if held[Action::ZoomIn] {
                 self.input_timer = Instant::now();
                 camera.zoom_in();
             }
-            if window.keyboard()[Key::LBracket].is_down() {
+            if held[Action::ZoomOut] {
                 self.input_timer = Instant::now();
                 camera.zoom_out();
             }
-            // ctrl + section
-            if window.keyboard()[Key::LControl].is_down() || 
-               window.keyboard()[Key::RControl].is_down() {
-                // ctrl + left
-                if window.keyboard()[Key::Left].is_down() { 
-                    self.input_timer = Instant::now();
-                    camera.go_to(0.0, 
-                                 camera.viewport.y(), 
-                                 camera.z_position);
-                }
-                // ctrl + right
-                if window.keyboard()[Key::Right].is_down() { 
-                    self.input_timer = Instant::now();
-                    camera.go_to(camera.max_x as f32, 
-                                 camera.viewport.y(), 
-                                 camera.z_position);
-                }
-                // ctrl + up
-                if window.keyboard()[Key::Up].is_down() { 
-                    self.input_timer = Instant::now();
-                    camera.go_to(camera.viewport.x(), 
-                                 0.0, 
-                                 camera.z_position);
-                }
-                // ctrl + down
-                if window.keyboard()[Key::Down].is_down() { 
-                    self.input_timer = Instant::now();
-                    camera.go_to(camera.viewport.x(), 
-                                 camera.viewport.y(), 
-                                 camera.z_position);
-                }
-                // ctrl + rbracket
-                if window.keyboard()[Key::RBracket].is_down() { 
-                    self.input_timer = Instant::now();
-                    camera.go_to(camera.viewport.x(), 
-                                 camera.viewport.y(), 
-                                 camera.max_z);
-                }
-                // ctrl + lbracket
-                if window.keyboard()[Key::LBracket].is_down() {
-                    self.input_timer = Instant::now();
-                    camera.go_to(
-                        camera.viewport.x(), 
-                        camera.viewport.y(), 
-                        0);
-                }
+            if held[Action::JumpWest] {
+                self.input_timer = Instant::now();
+                camera.go_to(0.0, y, z);
             }
-        
-            // player controls
-            let player = &mut self.entities[self.player_id];
-            if window.keyboard()[Key::A].is_down() {
+            if held[Action::JumpEast] {
                 self.input_timer = Instant::now();
-                player.pos.x -= 1.0;
+                camera.go_to(camera.max_x as f32, y, z);
             }
-            if window.keyboard()[Key::D].is_down() {
+            if held[Action::JumpNorth] {
                 self.input_timer = Instant::now();
-                player.pos.x += 1.0;
+                camera.go_to(x, 0.0, z);
             }
-            if window.keyboard()[Key::W].is_down() {
+            if held[Action::JumpSouth] {
                 self.input_timer = Instant::now();
-                player.pos.y -= 1.0;
+                camera.go_to(x, camera.max_y as f32, z);
             }
-            if window.keyboard()[Key::S].is_down() {
+            if held[Action::JumpDeepest] {
                 self.input_timer = Instant::now();
-                player.pos.y += 1.0;
-            } 
-       
+                camera.go_to(x, y, camera.max_z);
+            }
+            if held[Action::JumpSurface] {
+                self.input_timer = Instant::now();
+                camera.go_to(x, y, 0);
+            }
+        
+            // player controls
+            for (action, held) in held.iter() {
+                if let (true, Some((dx, dy))) = (*held, action.step()) {
+                    self.input_timer = Instant::now();
+                    self.move_cursor_or_player(dx, dy);
+                }
+            }
         }
 
-        if window.keyboard()[Key::Escape].is_down() {
-            window.close();
+        if pressed[Action::Pause] {
+            if self.modes.pop().is_some() {
+                self.inspection = None;
+            } else {
+                self.open_pause_menu(None);
+                return Ok(());
+            }
         }
-        // ui controls
-        let ui_components = &mut self.ui_components;
-        if window.keyboard()[Key::C] == Pressed {
-            ui_components[UiComponent::Credits] = !ui_components[UiComponent::Credits];
+
+        if pressed[Action::FitSelection] {
+            if let Some(area) = self.selected_area() {
+                self.camera.follow = None;
+                self.camera.fit(area.x, area.y, area.width, area.height, area.z);
+            }
         }
 
-        if window.keyboard()[Key::M] == Pressed {
-            ui_components[UiComponent::Map] = !ui_components[UiComponent::Map];
+        // the hotkeys below clash with menu keys
+        if !self.modes.is_empty() {
+            return Ok(());
         }
 
-        if window.keyboard()[Key::T] == Pressed {
-            ui_components[UiComponent::Title] = !ui_components[UiComponent::Title];
+        if pressed[Action::CommandMenu] {
+            self.modes.push(Mode::Menu(Menu::Main));
         }
 
-        if window.keyboard()[Key::B] == Pressed {
-            ui_components[UiComponent::Debug] = !ui_components[UiComponent::Debug];
+        if pressed[Action::Console] {
+            self.modes.push(Mode::Console);
         }
 
-        Ok(())
-    }
+        // ui controls
+        self.ui.toggle_pressed(&pressed);
 
-    /// Draw stuff on the screen
-    fn draw(&mut self, window: &mut Window) -> Result<()> {
-        let window_view = View::new(
-                Rectangle::new(Vector::new(0.0, 0.0), window.screen_size()
-        ));
-        window.set_view(window_view);
-        window.clear(Color::from_hex(&self.color_scheme.void))?;
+        if self.ui.is_visible("help") {
+            if pressed[Action::HelpNextPage] {
+                self.help_page += 1;
+            }
+            if pressed[Action::HelpPreviousPage] {
+                self.help_page = self.help_page.saturating_sub(1);
+            }
+        }
 
-        if self.ui_components[UiComponent::Title] {
-            self.draw_title(window).unwrap();
+        if pressed[Action::JumpToEvent] {
+            match self.chronicle.entries().len() {
+                0 => self.console.print("nothing has happened worth going to"),
+                count => self.jump_to_entry(count - 1),
+            }
         }
 
-        if self.ui_components[UiComponent::Map] {
-            self.draw_map(window).unwrap();
+        if pressed[Action::ToggleFollow] {
+            self.toggle_follow();
         }
 
-        if self.ui_components[UiComponent::Credits] {
-            self.draw_credits(window).unwrap();
+        if pressed[Action::TogglePip] {
+            self.toggle_pip();
         }
 
-        if self.ui_components[UiComponent::Debug] {
-            self.draw_debug(window).unwrap();
+        if pressed[Action::ToggleSplit] {
+            self.toggle_split();
         }
-        
-        Ok(())
-    }
 
-}
+        if pressed[Action::SwitchView] {
+            self.switch_view();
+        }
 
-impl Game {
+        if pressed[Action::CycleOverlay] {
+            self.cycle_overlay();
+        }
 
-    // fn draw_something(&mut self, window: &mut Window) -> Result<()> {
-    //     Ok(())
-    // }
+        if pressed[Action::CycleChunkGrid] {
+            self.chunk_grid = self.chunk_grid.next();
+        }
 
-    fn draw_title(&mut self, window: &mut Window) -> Result<()> {
-        self.title.execute(|image| {
-            window.draw(
-                &image
-                    .area()
-                    .with_center((window.screen_size().x as u32 / 2, 40)),
-                Img(&image),
-            );
-            Ok(())
-        })?;
-        Ok(())
-    }
+        if window.mouse()[MouseButton::Right] == Pressed && !self.config.overseer {
+            self.order_move(window.mouse().pos());
+        }
 
-    fn draw_map(&mut self, window: &mut Window) -> Result<()> {
+        if window.mouse()[MouseButton::Left] == Pressed {
+            self.click_colonist(window.mouse().pos());
+        }
 
-        let (tileset, map, entities) = (
-            &mut self.tileset.tile_map, 
-            &mut self.map, 
-            &self.entities
-        );
-        
-        let camera = &self.camera;
-        
-        let tile_size_px = self.tile_size_px * camera.zoom_factor;
+        if pressed[Action::ToggleOverseer] {
+            self.set_overseer(!self.config.overseer);
+        }
 
-        let (camera_x, camera_y, camera_z) = (
-            (camera.viewport.x()) as u32, 
-            (camera.viewport.y()) as u32, 
-            camera.z_position,
-        );       
-        let camera_size_x = camera.viewport.width();
-        let camera_size_y = camera.viewport.height();
-        
-        let color_scheme = &self.color_scheme;
+        if pressed[Action::NextColonist] {
+            if let Some(id) = self.sim.squad.next_after(self.player_id, &self.sim.entities) {
+                self.select_colonist(id);
+            }
+        }
 
-        let offset_px = Vector::new(50, 100);
-        
-        let origin_offset = Vector::new(-(camera_x as i32), - (camera_y as i32));
-        //println!("camera_pos: {:?}", self.camera_pos);
+        if pressed[Action::CancelMove] {
+            self.travel = None;
+            self.issue(Order::CancelMoves);
+        }
 
-        for x in camera_x..camera_x + camera_size_x as u32 {
-            for y in camera_y..camera_y + camera_size_y as u32 {
-                //println!("camera_z: {:?}", camera_z);
-                let tile = map.get_tile(x, y, camera_z);
-                let pos_px = tile.pos
-                    .translate(origin_offset)
-                    .times(tile_size_px);
-                //println!("x: {:?}, y: {:?}, z: {:?}", x, y, camera_z);
-                //println!("{:?}", tile);
-                let tile_color = Color::from_hex(
-                    color_scheme.get_color_code(&tile.color));
-                if camera.zoom_factor > 0.5 {
-                    if let Some(image) = tileset.get(&tile.glyph) {
-                        window.draw_ex(
-                            &Rectangle::new(
-                                offset_px + pos_px, image.area().size()
-                            ),
-                            Blended(&image, tile_color),
-                            Transform::scale(
-                                (camera.zoom_factor, camera.zoom_factor)
-                            ),
-                            0 // Z value
-                        );
-                    }
-                }
-                else {
-                    window.draw_ex(
-                            &Rectangle::new(
-                                offset_px + pos_px, self.tile_size_px
-                            ),
-                            tile_color,
-                            Transform::scale(
-                                (camera.zoom_factor, camera.zoom_factor)),
-                            0 // Z value
-                        );
+        // construction
+        if pressed[Action::BuildPillar] {
+            self.build(Build::Pillar);
+        }
+        if pressed[Action::Undo] {
+            self.issue(Order::Undo);
+        }
 
-                }
-            }
+        if pressed[Action::PlaceNote] {
+            self.place_note(window.mouse().pos());
         }
 
-        for entity in entities.iter() {
-            if entity.depth == camera_z 
-               && (entity.pos.x as u32) >= camera_x 
-               && (entity.pos.x as u32) < (camera_x + camera_size_x as u32)
-               && (entity.pos.y as u32) >= camera_y
-               && (entity.pos.y as u32) < (camera_y + camera_size_y as u32) 
-            {
-                if let Some(image) = tileset.get(&entity.glyph) {
-                    let pos_px = entity.pos
-                        .translate(origin_offset)
-                        .times(tile_size_px);
-                    let entity_color = Color::from_hex(
-                        color_scheme.get_color_code(&entity.color));
-                    window.draw_ex(
-                        &Rectangle::new(
-                            offset_px + pos_px, image.area().size()),
-                        Blended(&image, entity_color),
-                        Transform::scale(
-                                (camera.zoom_factor, camera.zoom_factor)),
-                        1 // Z value
+        Ok(())
+    }
 
-                    );
+    /// Runs the ticks that are due. In a network game the host runs them
+    /// with everyone's orders and passes the orders on, the others run the
+    /// ticks the host sent instead of their own.
+    fn run_ticks(&mut self, due: u32) {
+        match self.net.take() {
+            None => for _ in 0..due {
+                self.step_simulation();
+            },
+            Some(Session::Host(mut host)) => {
+                for peer in host.accept(|| self.save_data()) {
+                    self.console.print(&format!("player {} joined", peer));
                 }
-            }
+                for peer in host.receive(&self.sim.squad) {
+                    self.console.print(&format!("player {} left", peer));
+                }
+                for desync in host.take_desyncs() {
+                    self.console.print(&desync.describe());
+                }
+                for _ in 0..due {
+                    for (peer, order) in host.tick(self.sim.tick + 1) {
+                        self.apply_order(peer, order);
+                    }
+                    self.step_simulation();
+                    if let Some(checksum) = self.check_sync() {
+                        host.record(checksum);
+                    }
+                }
+                self.net = Some(Session::Host(host));
+            },
+            Some(Session::Client(mut client)) => match client.receive() {
+                Ok(ticks) => {
+                    for (tick, orders) in ticks {
+                        for (peer, order) in orders {
+                            self.apply_order(peer, order);
+                        }
+                        self.step_simulation();
+                        if self.sim.tick != tick {
+                            log::warn!("ran tick {} as {}, out of step with the host", tick, self.sim.tick);
+                        }
+                        if let Some(checksum) = self.check_sync() {
+                            if let Err(err) = client.send_checksum(checksum) {
+                                log::warn!("could not send the checksum: {}", err);
+                            }
+                        }
+                    }
+                    self.net = Some(Session::Client(client));
+                },
+                Err(err) => self.report(GameError::Network {
+                    reason: format!("lost the host ({}), carrying on alone", err),
+                }),
+            },
         }
+    }
 
-        Ok(())
+    /// The checksum of the simulation every desync::CHECK_INTERVAL ticks,
+    /// for the host to tell whether everyone's game still matches, writing
+    /// out the snapshot too with --desync-dumps
+    fn check_sync(&mut self) -> Option<Checksum> {
+        if !self.sim.tick.is_multiple_of(desync::CHECK_INTERVAL) {
+            return None;
+        }
+        if self.desync_dumps {
+            let snapshot = Snapshot::of(&self.sim.save_data(self.player_id));
+            if let Err(err) = desync::write_dump(&snapshot, self.peer) {
+                log::warn!("could not write the snapshot: {}", err);
+            }
+        }
+        Some(Checksum::of(&self.sim))
     }
 
-    fn draw_credits(&mut self, window: &mut Window) -> Result<()> {
-        let mut y_offset = 60;
-        for fi in self.font_info.iter_mut() {
-            fi.execute(|image| {
-                window.draw(
-                    &image
-                        .area()
-                        .translate((2, window.screen_size().y as i32 - y_offset)),
-                    Img(&image),
-                );
-                Ok(())
-            })?;
-            y_offset -= 20;
+    /// Gives an order, right away in single player. In a network game it
+    /// goes through the host, which runs it for everyone on the next tick.
+    fn issue(&mut self, order: Order) {
+        match &mut self.net {
+            None => self.apply_order(net::HOST_PEER, order),
+            Some(Session::Host(host)) => host.queue(net::HOST_PEER, order),
+            Some(Session::Client(client)) => if let Err(err) = client.send(order) {
+                self.console.print(&format!("could not send the order: {}", err));
+            },
         }
+    }
 
-        Ok(())
+    /// Tells everyone which colonist this player steers, so the rest of
+    /// the squad knows to work on their own
+    fn claim_control(&mut self) {
+        self.issue(Order::Control { colonist: self.player_id });
     }
 
-    fn draw_debug(&mut self, window: &mut Window) -> Result<()> {
-        let mononoki_font_info_style = FontStyle::new(
-            20.0, Color::from_hex(&self.color_scheme.fg));
+    /// The colonist the player steers, None while overseeing the colony
+    fn player(&self) -> Option<&Entity> {
+        self.player_id.and_then(|id| self.sim.entities.get(id))
+    }
 
-        let player_id = self.player_id;
-        let player = &self.entities[player_id];
-        let tile = self.map.get_tile(
-            player.pos.x as u32, player.pos.y as u32, self.camera.z_position);
+    /// Builds in front of the player
+    fn build(&mut self, build: Build) {
+        if let Some(colonist) = self.player_id {
+            self.issue(Order::Build { colonist, facing: self.player_facing, build });
+        }
+    }
 
-        let debug_string = format!("Player Pos: (x: {:?} y: {:?})  Tile: (Color: {:?} glyph: {:?} val: {:?})\n
-Camera Pos: (x: {:?} y: {:?} z: {:?}), Zoom Factor: {:?}, viewport size: {:?}",
-                                   player.pos.x,
-                                   player.pos.y,
-                                   tile.color,
-                                   tile.glyph,
-                                   tile.val,
-                                   self.camera.viewport.x(),
-                                   self.camera.viewport.y(),
+    /// Carries out an order the player on peer gave
+    fn apply_order(&mut self, peer: u32, order: Order) {
+        if let Err(err) = self.sim.apply_order(peer, order) {
+            if peer == self.peer {
+                self.console.print(&err);
+            }
+        }
+    }
+
+    /// Advances the world by one tick, keeping the player's walk going and
+    /// handing on what happened during it
+    fn step_simulation(&mut self) {
+        let times = self.sim.step();
+        self.profiler.record(System::Fluid, times.fluid);
+        self.profiler.record(System::Ai, times.ai);
+        self.update_travel();
+        self.dispatch_events();
+    }
+
+    /// Handles input for the open dialog, returns false if none is open
+    fn update_dialog(&mut self, window: &mut Window) -> bool {
+        let escape = window.keyboard()[Key::Escape] == ButtonState::Pressed;
+        match &mut self.state {
+            GameState::Paused(dialog) => {
+                let choice = if escape {
+                    Some(PAUSE_RESUME)
+                } else {
+                    dialog.handle_input(window)
+                };
+                match choice {
+                    Some(PAUSE_SAVE) => {
+                        let (name, profile) = (self.sim.name.clone(), self.slot_profile.clone());
+                        self.save_to_slot(name, profile);
+                    },
+                    Some(PAUSE_SAVE_AS) => self.open_slots(SlotAction::Save),
+                    Some(PAUSE_LOAD) => self.open_slots(SlotAction::Load),
+                    Some(PAUSE_NEW_COLONY) => self.open_new_colony(),
+                    Some(PAUSE_STATISTICS) => self.open_statistics(),
+                    Some(PAUSE_SETTINGS) => self.open_settings(0),
+                    Some(PAUSE_QUIT) if self.unsaved() => self.open_quit_dialog(),
+                    Some(PAUSE_QUIT) => self.quit(window),
+                    Some(_) => self.state = GameState::Playing,
+                    None => {},
+                }
+            },
+            GameState::Settings(dialog) => {
+                let choice = if escape {
+                    Some(SETTINGS_BACK)
+                } else {
+                    dialog.handle_input(window)
+                };
+                match choice {
+                    Some(SETTINGS_BACK) => self.open_pause_menu(None),
+                    Some(setting) => {
+                        self.toggle_setting(setting);
+                        self.open_settings(setting);
+                    },
+                    None => {},
+                }
+            },
+            GameState::NewColony(dialog) => {
+                let choice = if escape { None } else { dialog.handle_input(window) };
+                match choice {
+                    Some(NEW_COLONY_START) => {
+                        let seed = dialog.field.as_ref().map(TextInput::text).unwrap_or_default();
+                        match parse_seed(&seed) {
+                            Ok(seed) => {
+                                let map = GameMap::with_seed(seed.unwrap_or_else(random_seed));
+                                self.state = GameState::Embark(SitePicker::new(&map, true));
+                            },
+                            Err(err) => dialog.text[0] = err,
+                        }
+                    },
+                    Some(NEW_COLONY_SCENARIOS) => self.open_scenarios(),
+                    Some(_) => self.open_pause_menu(None),
+                    None if escape => self.open_pause_menu(None),
+                    None => {},
+                }
+            },
+            GameState::Slots(screen) => {
+                let action = screen.action;
+                match screen.handle_input(window, escape) {
+                    Some(SlotChoice::Save { name, profile }) => self.save_to_slot(name, profile),
+                    Some(SlotChoice::Load(slot)) => self.load_slot(slot),
+                    Some(SlotChoice::Delete(slot)) => {
+                        if let Err(err) = save::delete_slot(&slot.profile) {
+                            log::warn!(target: logging::SAVE, "could not delete {:?}: {:?}", slot.profile, err);
+                        }
+                        self.open_slots(action);
+                    },
+                    Some(SlotChoice::Back) => self.open_pause_menu(None),
+                    None => {},
+                }
+            },
+            GameState::Embark(picker) => {
+                let (seed, difficulty) = (picker.seed, picker.difficulty);
+                match picker.handle_input(window, escape) {
+                    Some(SiteChoice::Land(site)) => {
+                        self.new_colony(seed, difficulty, site);
+                        self.open_name_colony();
+                    },
+                    Some(SiteChoice::Tutorial) => self.start_tutorial(),
+                    Some(SiteChoice::Cancel) => self.open_new_colony(),
+                    None => {},
+                }
+            },
+            GameState::NameColony(dialog) => {
+                let name = dialog.field.as_ref().map(TextInput::text).unwrap_or_default();
+                let name = name.trim();
+                match dialog.handle_input(window) {
+                    _ if escape => self.state = GameState::Playing,
+                    Some(_) if name.is_empty() => {},
+                    Some(_) if !self.name_colony(name) => {
+                        if let GameState::NameColony(dialog) = &mut self.state {
+                            dialog.text[0] = format!("{} is taken by a saved colony", name);
+                        }
+                    },
+                    Some(_) => self.open_appearance(),
+                    None => {},
+                }
+            },
+            GameState::Appearance(picker) => {
+                match picker.handle_input(window, escape) {
+                    Some(AppearanceChoice::Pick(glyph, color)) => {
+                        if let Some(id) = self.player_id {
+                            self.sim.set_appearance(id, glyph, color);
+                        }
+                        self.state = GameState::Playing;
+                    },
+                    Some(AppearanceChoice::Cancel) => self.state = GameState::Playing,
+                    None => {},
+                }
+            },
+            GameState::GameOver(dialog) => {
+                let choice = if escape {
+                    Some(GAME_OVER_WATCH)
+                } else {
+                    dialog.handle_input(window)
+                };
+                match choice {
+                    Some(GAME_OVER_LOAD) => self.open_slots(SlotAction::Load),
+                    Some(GAME_OVER_NEW_COLONY) => self.open_new_colony(),
+                    Some(GAME_OVER_QUIT) if self.unsaved() => self.open_quit_dialog(),
+                    Some(GAME_OVER_QUIT) => self.quit(window),
+                    Some(_) => self.state = GameState::Playing,
+                    None => {},
+                }
+            },
+            GameState::Scenarios(dialog, scenarios) => {
+                let choice = if escape { Some(scenarios.len()) } else { dialog.handle_input(window) };
+                match choice {
+                    Some(option) if option < scenarios.len() => {
+                        let scenario = scenarios.swap_remove(option);
+                        self.start_scenario(scenario);
+                    },
+                    Some(_) => self.open_new_colony(),
+                    None => dialog.text = describe_scenario(scenarios.get(dialog.selected)),
+                }
+            },
+            GameState::Statistics(dialog) => {
+                if escape || dialog.handle_input(window).is_some() {
+                    self.open_pause_menu(None);
+                }
+            },
+            GameState::Failed(dialog) => {
+                let choice = if escape {
+                    Some(FAILED_CONTINUE)
+                } else {
+                    dialog.handle_input(window)
+                };
+                match choice {
+                    Some(FAILED_QUIT) if self.unsaved() => self.open_quit_dialog(),
+                    Some(FAILED_QUIT) => self.quit(window),
+                    Some(_) => self.state = GameState::Playing,
+                    None => {},
+                }
+            },
+            GameState::ConfirmQuit(dialog) => {
+                let choice = if escape {
+                    Some(QUIT_CANCEL)
+                } else {
+                    dialog.handle_input(window)
+                };
+                match choice {
+                    Some(QUIT_SAVE) if self.save_current() => self.quit(window),
+                    Some(QUIT_DISCARD) => self.quit(window),
+                    Some(_) => self.state = GameState::Playing,
+                    None => {},
+                }
+            },
+            // nothing is thrown away on escape, only on picking Discard
+            GameState::Restore(dialog, ..) => {
+                if let Some(choice) = dialog.handle_input(window) {
+                    if let GameState::Restore(_, slot, then) = std::mem::replace(&mut self.state, GameState::Playing) {
+                        self.state = *then;
+                        if choice == RESTORE_AUTOSAVE {
+                            self.restore_autosave(slot);
+                        }
+                    }
+                    self.clear_autosave();
+                }
+            },
+            GameState::Playing | GameState::Quitting => return false,
+        }
+        true
+    }
+
+    /// Pauses on the pause menu, saying why if it wasn't the player
+    fn open_pause_menu(&mut self, reason: Option<&str>) {
+        let locale = &self.locale;
+        let title = match reason {
+            Some(reason) => locale.format("pause.title_because", &[("reason", &reason)]),
+            None => String::from(locale.get("pause.title")),
+        };
+        let options: Vec<&str> = [
+            "pause.resume", "pause.save", "pause.save_as", "pause.load", "pause.new_colony",
+            "pause.statistics", "pause.settings", "pause.quit",
+        ].iter().map(|key| locale.get(key)).collect();
+        self.state = GameState::Paused(Dialog::new(&title, &options));
+    }
+
+    /// Lists the scenarios in the scenarios directory, the ones that
+    /// couldn't be read go to the console
+    fn open_scenarios(&mut self) {
+        let (scenarios, errors) = scenario::load_dir(Path::new(scenario::SCENARIOS_DIR));
+        for err in errors {
+            self.console.print(&err);
+        }
+        let mut dialog = Dialog::new("Scenarios", &[]);
+        dialog.options = scenarios.iter().map(|scenario| scenario.title.clone()).collect();
+        dialog.options.push(String::from("Back"));
+        dialog.text = describe_scenario(scenarios.first());
+        self.state = GameState::Scenarios(dialog, scenarios);
+    }
+
+    fn open_new_colony(&mut self) {
+        if let Err(err) = self.single_player_only() {
+            self.open_pause_menu(Some(&err));
+            return;
+        }
+        self.state = GameState::NewColony(Dialog::new("New colony", &["Start", "Scenarios", "Cancel"])
+            .with_text(vec![
+                String::from("Seed, blank for a random one:"),
+                String::from("the colony now is lost unless it was saved"),
+            ])
+            .with_field(TextInput::new(Filter::Digits, SEED_DIGITS)));
+    }
+
+    /// Replaces the colony with a new one grown from the seed, its squad
+    /// landing on the site
+    fn new_colony(&mut self, seed: u32, difficulty: Difficulty, site: (u32, u32)) {
+        let mut sim = Simulation::new(world_map(&self.config, seed, difficulty));
+        for line in sim.run_worldgen(&self.scripts) {
+            self.console.print(&line);
+        }
+        let (x, y, z) = simulation::landing_position(&sim.map, site);
+        let player_id = sim.populate(x, y, z);
+        sim.unsaved_changes = true;
+        self.camera.go_to(x as f32, y as f32, z);
+        self.camera.follow = None;
+        self.stats = Stats::new();
+        self.chronicle = Chronicle::new();
+        sim.name = format!("{} {}", simulation::DEFAULT_NAME, seed);
+        self.slot_profile = save::slot_profile(&sim.name);
+        self.play_time = Duration::default();
+        self.replace_colony(sim, Some(player_id));
+        self.console.print(&format!("{} landed, seed {}, {}", self.sim.name, seed, difficulty.name()));
+    }
+
+    /// Plays the tutorial's handcrafted cave
+    fn start_tutorial(&mut self) {
+        let path = Path::new(scenario::SCENARIOS_DIR).join(scenario::TUTORIAL_FILE);
+        match Scenario::load(&path) {
+            Ok(scenario) => self.start_scenario(scenario),
+            Err(err) => {
+                self.console.print(&err);
+                self.open_pause_menu(Some("the tutorial could not be loaded"));
+            },
+        }
+    }
+
+    /// Replaces the colony with the scenario's map and what it starts
+    /// with, which isn't saved
+    fn start_scenario(&mut self, scenario: Scenario) {
+        let difficulty = scenario.difficulty.unwrap_or_default();
+        let map = world_map(&self.config, scenario.seed.unwrap_or(0), difficulty);
+        let (width, height, _) = map.size();
+        let site = if scenario.seed.is_some() && scenario.rows.is_empty() {
+            let region = embark::Region::survey(&map);
+            let (column, row) = region.default_cell();
+            region.center(column, row)
+        } else {
+            (width / 2, height / 2)
+        };
+        let (x, y, z) = simulation::landing_position(&map, site);
+        let layout = scenario.layout((x, y), z);
+        let mut sim = match (scenario.seed, layout) {
+            (None, Some(layout)) => Simulation::new(map.with_layout(layout)),
+            (_, layout) => {
+                let mut sim = Simulation::new(map);
+                for line in sim.run_worldgen(&self.scripts) {
+                    self.console.print(&line);
+                }
+                if let Some(layout) = layout {
+                    layout.stamp(&mut sim.map);
+                }
+                sim
+            },
+        };
+        for placement in &scenario.placements {
+            let (dx, dy) = placement.offset;
+            let (px, py) = (sim.map.wrap_x(x as i64 + dx), (y as i64 + dy).clamp(0, height as i64 - 1) as u32);
+            let placed = match placement.kind {
+                PlacementKind::Creature => self.content.creatures.get(&placement.id)
+                    .map(|creature| sim.spawn_creature(creature, px, py, z)),
+                // tools and armor can be picked up
+                PlacementKind::Item => self.content.items.get(&placement.id)
+                    .map(|item| sim.place_item(item, px, py, z)),
+            };
+            if placed.is_none() {
+                self.console.print(&format!("{}: there is no {:?} in the content packs",
+                                            scenario.title, placement.id));
+            }
+        }
+        let player_id = sim.populate(x, y, z);
+        self.camera.go_to(x as f32, y as f32, z);
+        self.camera.follow = None;
+        self.stats = Stats::new();
+        self.chronicle = Chronicle::new();
+        sim.name = scenario.title.clone();
+        self.play_time = Duration::default();
+        self.replace_colony(sim, Some(player_id));
+        self.scenario = Some(Tutorial::new(scenario));
+        self.state = GameState::Playing;
+    }
+
+    /// Moves the tutorial on once the player has done what its step asks
+    fn update_tutorial(&mut self, window: &Window) {
+        let viewport = self.camera.viewport;
+        let player = self.player().map_or((0.0, 0.0), |player| (player.pos.x, player.pos.y));
+        let now = Progress {
+            view: (viewport.x(), viewport.y()),
+            level: self.camera.z_position,
+            player,
+            dug: self.stats.tiles_dug,
+            designated: self.sim.designations.dig_count(),
+            stockpiles: self.sim.designations.stockpile_count(),
+        };
+        let proceed = self.modes.is_empty() && window.keyboard()[Key::Return] == ButtonState::Pressed;
+        let mut won = None;
+        if let Some(tutorial) = &mut self.scenario {
+            if tutorial.update(now, proceed) && tutorial.is_finished() {
+                let message = format!("{} finished", tutorial.title());
+                self.console.print(&message);
+            }
+            if tutorial.check_victory(&self.stats, self.sim.tick) {
+                won = Some(tutorial.title().to_string());
+            }
+        }
+        if let Some(title) = won {
+            self.console.print(&format!("{} won", title));
+            self.travel = None;
+            self.open_summary(&format!("{} won", title), String::from("every win condition is met"));
+        }
+    }
+
+    /// Whether there's anything Quit should offer to save, the tutorial
+    /// never is
+    fn unsaved(&self) -> bool {
+        self.sim.unsaved_changes && self.scenario.is_none()
+    }
+
+    /// Asks what to call the colony that just landed
+    fn open_name_colony(&mut self) {
+        self.state = GameState::NameColony(Dialog::new("Name the colony", &["Found colony"])
+            .with_text(vec![String::new()])
+            .with_field(TextInput::new(Filter::Name, MAX_NAME_LEN).with_text(&self.sim.name)));
+    }
+
+    /// Lets the player pick how they look, starting on how they look now
+    fn open_appearance(&mut self) {
+        let current = self.player().map_or(('@', ColorName::LightPurple), |player| player.appearance(0));
+        self.state = GameState::Appearance(AppearancePicker::new(self.assets.glyphs(), current));
+    }
+
+    /// Names the colony, and the slot it will be saved in. False if a saved
+    /// colony already goes by the name.
+    fn name_colony(&mut self, name: &str) -> bool {
+        let profile = save::slot_profile(name);
+        if profile != self.slot_profile && save::load_slots().iter().any(|slot| slot.profile == profile) {
+            return false;
+        }
+        self.console.print(&format!("{} is now called {}", self.sim.name, name));
+        self.sim.name = String::from(name);
+        self.slot_profile = profile;
+        self.retitle();
+        true
+    }
+
+    /// Renders the title again after the colony's name or the colors changed
+    fn retitle(&mut self) {
+        self.title = render_title(&self.color_scheme, self.config.ui_scale, &self.sim.name);
+    }
+
+    /// Replaces the colony with the one saved in the slot
+    fn load_slot(&mut self, slot: SlotInfo) {
+        let profile = slot.profile.clone();
+        self.load_colony(slot, &profile);
+    }
+
+    /// Replaces the colony with the one autosaved when the window last
+    /// closed. It goes on to be saved in its slot, and counts as unsaved
+    /// until it is.
+    fn restore_autosave(&mut self, slot: SlotInfo) {
+        if self.load_colony(slot, save::AUTOSAVE_PROFILE) {
+            self.sim.unsaved_changes = true;
+        }
+    }
+
+    /// Replaces the colony with the one saved in profile, which from then
+    /// on is saved in the slot. False if it couldn't be loaded.
+    fn load_colony(&mut self, slot: SlotInfo, profile: &str) -> bool {
+        if let Err(err) = self.single_player_only() {
+            self.open_pause_menu(Some(&err));
+            return false;
+        }
+        let data = match save::load_game(profile) {
+            Ok(data) => data,
+            Err(err) => {
+                log::warn!(target: logging::SAVE, "could not load {:?}: {:?}", profile, err);
+                self.open_pause_menu(Some(&format!("{} could not be loaded", slot.name)));
+                return false;
+            },
+        };
+        let mut sim = Simulation::new(world_map(&self.config, data.random_seed, data.difficulty));
+        for line in sim.run_worldgen(&self.scripts) {
+            self.console.print(&line);
+        }
+        sim.restore(&data);
+        self.camera.go_to(data.camera_x, data.camera_y, data.camera_z);
+        self.camera.follow = data.camera_follow;
+        self.stats = data.stats;
+        self.chronicle = data.chronicle;
+        self.play_time = Duration::from_secs(slot.play_time_secs);
+        self.console.print(&format!("loaded {}", slot.name));
+        sim.name = slot.name;
+        self.slot_profile = slot.profile;
+        self.replace_colony(sim, data.player_id);
+        self.state = GameState::Playing;
+        if let Some(loss) = self.sim.lost {
+            self.open_game_over(loss);
+        }
+        true
+    }
+
+    /// Swaps in another colony, dropping what was going on in the old one
+    fn replace_colony(&mut self, sim: Simulation, player_id: Option<EntityId>) {
+        self.sim = sim;
+        self.scenario = None;
+        self.apply_content();
+        self.retitle();
+        self.player_id = steered_colonist(&self.sim, player_id, self.config.overseer);
+        self.player_facing = (0, 1);
+        self.travel = None;
+        self.particles = Particles::new();
+        self.alerts = OffscreenAlerts::new();
+        self.modes.clear();
+        self.inspection = None;
+        self.claim_control();
+    }
+
+    fn open_slots(&mut self, action: SlotAction) {
+        let allowed = match action {
+            SlotAction::Load => self.single_player_only(),
+            SlotAction::Save => match self.net {
+                Some(Session::Client(_)) => Err(String::from("the host keeps the colony")),
+                _ if self.scenario.is_some() => Err(String::from("scenarios aren't saved")),
+                _ => Ok(()),
+            },
+        };
+        match allowed {
+            Ok(()) => self.state = GameState::Slots(
+                SlotScreen::new(action, save::load_slots(), self.unsaved())),
+            Err(err) => self.open_pause_menu(Some(&err)),
+        }
+    }
+
+    /// Logs an error and opens the error screen listing every error so
+    /// far, errors already reported are ignored so one that happens every
+    /// frame doesn't flood the log
+    fn report(&mut self, error: GameError) {
+        if self.errors.contains(&error) {
+            return;
+        }
+        eprintln!("error: {}", error);
+        self.errors.push(error);
+        if let GameState::Quitting = self.state {
+            return;
+        }
+        let mut text: Vec<String> = self.errors.iter().map(|error| error.to_string()).collect();
+        if self.errors.iter().any(|error| matches!(error, GameError::MissingAsset { .. })) {
+            text.push(String::new());
+            text.push(String::from("Check that the static directory is next to the game"));
+        }
+        self.state = GameState::Failed(
+            Dialog::new("Something went wrong", &["Continue", "Quit"]).with_text(text));
+    }
+
+    fn check_draw(&mut self, part: &'static str, result: Result<()>) {
+        if let Err(err) = result {
+            self.report(GameError::Draw { part, reason: err.to_string() });
+        }
+    }
+
+    /// Sums up the colony that was lost: how, how long it lasted, and the
+    /// player's statistics
+    fn open_game_over(&mut self, loss: Loss) {
+        self.open_summary(&format!("{} is lost", self.sim.name), loss.describe());
+    }
+
+    /// Sums up the colony once it's lost or its scenario is won
+    fn open_summary(&mut self, title: &str, reason: String) {
+        let mut text = vec![
+            reason,
+            format!("{} lasted {} ticks", self.sim.name, self.sim.tick),
+            String::new(),
+        ];
+        text.extend(self.stats.summary(self.sim.map.level_thickness));
+        self.state = GameState::GameOver(Dialog::new(
+            title,
+            &["Load", "New colony", "Keep watching", "Quit"],
+        ).with_text(text));
+    }
+
+    fn open_statistics(&mut self) {
+        let mut text = self.sim.trends.summary();
+        text.push(String::new());
+        text.extend(self.stats.summary(self.sim.map.level_thickness));
+        let graphs = self.graphs();
+        let mut dialog = Dialog::new("Statistics", &["Back"]).with_text(text);
+        dialog.picture = Image::from_raw(graphs.pixels(), graphs.width, graphs.height, PixelFormat::RGBA).ok();
+        self.state = GameState::Statistics(dialog);
+    }
+
+    /// The graphs of what the colony made and used up each day, stone
+    /// mined, food grown then food eaten
+    fn graphs(&self) -> export::Canvas {
+        let color = |name: &ColorName| Color::from_hex(self.color_scheme.get_color_code(name));
+        let series = self.sim.trends.series();
+        let (width, height) = GRAPH_SIZE;
+        let mut canvas = export::Canvas::new(
+            width, height * series.len() as u32 + GRAPH_GAP * (series.len() as u32 - 1), color(&ColorName::Bg));
+        let colors = [ColorName::Orange, ColorName::Green, ColorName::Red];
+        for (index, ((_, series), line)) in series.iter().zip(colors.iter()).enumerate() {
+            let y = index as u32 * (height + GRAPH_GAP);
+            canvas.fill_rect(0, y + height - 1, width, 1, color(&ColorName::Gray));
+            export::plot(&mut canvas, (0, y, width, height), &series.days(), color(line));
+        }
+        canvas
+    }
+
+    /// Shows the settings with their current values, with the given
+    /// option selected
+    fn open_settings(&mut self, selected: usize) {
+        let locale = &self.locale;
+        let on_off = |on: bool| locale.get(if on { "settings.on" } else { "settings.off" });
+        let setting = |key: &str, value: &dyn std::fmt::Display| locale.format(key, &[("value", value)]);
+        let follow = setting("settings.follow", &on_off(self.camera.follow.is_some()));
+        let title = setting("settings.show_title", &on_off(self.ui.is_visible("title")));
+        let debug = setting("settings.debug", &on_off(self.ui.is_visible("debug")));
+        let music = setting("settings.music", &(self.config.music_volume * 100.0).round());
+        let effects = setting("settings.effects", &(self.config.effects_volume * 100.0).round());
+        let palette = setting("settings.palette", &self.config.palette.name());
+        let shape_cues = setting("settings.shape_cues", &on_off(self.config.shape_cues));
+        let ui_scale = setting("settings.ui_scale", &self.config.ui_scale);
+        let control = setting("settings.control", 
+                              &locale.get(if self.config.overseer { "settings.overseer" } else { "settings.direct" }));
+        let movement_keys = setting("settings.movement_keys", &self.config.movement_keys.name());
+        let glide = setting("settings.glide", &on_off(self.config.camera_glide));
+        let cache_chunks = setting("settings.cache_chunks", &on_off(self.config.cache_chunks));
+        let keyboard_layout = setting("settings.keyboard_layout", &self.config.keyboard_layout.name());
+        let language = setting("settings.language", &locale.name());
+        let brightness = setting("settings.brightness", &format!("{:+.0}", self.config.brightness * 100.0));
+        let contrast = setting("settings.contrast", &(self.config.contrast * 100.0).round());
+        let depth_shading = setting("settings.depth_shading", &self.config.depth_shading.name());
+        let smooth_terrain = setting("settings.smooth_terrain", &on_off(self.config.smooth_terrain));
+        let mut dialog = Dialog::new(
+            locale.get("settings.title"), 
+            &[&follow, &title, &debug, &music, &effects, &palette, &shape_cues, 
+              &ui_scale, &control, &movement_keys, &glide, &cache_chunks, 
+              &keyboard_layout, &language, &brightness, &contrast, &depth_shading,
+              &smooth_terrain, locale.get("settings.back")]);
+        dialog.selected = selected;
+        self.state = GameState::Settings(dialog);
+    }
+
+    fn toggle_setting(&mut self, setting: usize) {
+        match setting {
+            SETTING_FOLLOW => self.toggle_follow(),
+            SETTING_TITLE => self.run_command(Command::Toggle("title")),
+            SETTING_DEBUG => self.run_command(Command::Toggle("debug")),
+            SETTING_MUSIC => {
+                self.config.music_volume = config::next_volume(self.config.music_volume);
+            },
+            SETTING_EFFECTS => {
+                self.config.effects_volume = config::next_volume(self.config.effects_volume);
+            },
+            SETTING_PALETTE => {
+                self.config.palette = self.config.palette.next();
+                self.color_scheme = ColorScheme::from_palette(self.config.palette);
+                self.color_scheme.adjust = self.config.color_adjust();
+            },
+            SETTING_SHAPE_CUES => self.config.shape_cues = !self.config.shape_cues,
+            SETTING_UI_SCALE => {
+                self.config.ui_scale = config::next_ui_scale(self.config.ui_scale);
+                self.apply_ui_scale();
+            },
+            SETTING_OVERSEER => self.set_overseer(!self.config.overseer),
+            SETTING_MOVEMENT_KEYS => {
+                self.config.movement_keys = self.config.movement_keys.next();
+                self.bindings = Bindings::new(self.config.movement_keys, self.config.keyboard_layout);
+            },
+            SETTING_GLIDE => self.config.camera_glide = !self.config.camera_glide,
+            SETTING_CACHE_CHUNKS => self.config.cache_chunks = !self.config.cache_chunks,
+            SETTING_KEYBOARD_LAYOUT => {
+                self.config.keyboard_layout = self.config.keyboard_layout.next();
+                self.bindings = Bindings::new(self.config.movement_keys, self.config.keyboard_layout);
+            },
+            SETTING_LANGUAGE => {
+                let languages = locale::languages();
+                let current = languages.iter().position(|code| *code == self.config.language);
+                let next = current.map_or(0, |index| (index + 1) % languages.len());
+                self.config.language = languages[next].clone();
+                self.locale = Locale::load(&self.config.language);
+            },
+            SETTING_BRIGHTNESS => {
+                self.config.brightness = config::next_brightness(self.config.brightness);
+                self.color_scheme.adjust = self.config.color_adjust();
+            },
+            SETTING_CONTRAST => {
+                self.config.contrast = config::next_contrast(self.config.contrast);
+                self.color_scheme.adjust = self.config.color_adjust();
+            },
+            SETTING_DEPTH_SHADING => self.config.depth_shading = self.config.depth_shading.next(),
+            SETTING_SMOOTH_TERRAIN => self.config.smooth_terrain = !self.config.smooth_terrain,
+            _ => {},
+        }
+        if let Err(err) = self.config.save() {
+            log::warn!(target: logging::CONFIG, "could not save the config: {:?}", err);
+        }
+    }
+
+    /// Hands the content packs' creatures, crops and items to the colony
+    fn apply_content(&mut self) {
+        self.sim.spawner = Spawner::new(&self.content);
+        self.sim.ranch = Ranch::new(&self.content);
+        self.sim.seeds = Seeds::new(&self.content);
+        self.sim.economy = Economy::new(&self.content);
+        self.sim.recount_stock();
+    }
+
+    /// Loads again whatever the watcher saw the files of change
+    fn hot_reload(&mut self) {
+        let reloads = match &mut self.watcher {
+            Some(watcher) => watcher.poll(Instant::now()),
+            None => return,
+        };
+        for reload in reloads {
+            match reload {
+                Reload::Tileset => self.reload_tileset(),
+                Reload::Content => self.reload_content(),
+                Reload::Language => {
+                    self.locale = Locale::load(&self.config.language);
+                    self.console.print("reloaded the language bundles");
+                },
+                Reload::WorldGen => self.reload_worldgen(),
+            }
+        }
+    }
+
+    /// Renders the tileset and text again from the fonts as they are now
+    fn reload_tileset(&mut self) {
+        self.ui_font = Asset::new(Font::load(FONT_MONONOKI));
+        self.text_layout = TextLayout::load(FONT_MONONOKI);
+        match self.assets.render() {
+            Ok(()) => self.console.print("reloaded the tileset"),
+            Err(err) => self.report(err),
+        }
+        self.retitle();
+        self.font_info = render_font_info(&self.color_scheme, self.config.ui_scale);
+    }
+
+    /// Reads the content packs again. Every game in a network game has to
+    /// load the same ones, so they're left as they are until the next one.
+    fn reload_content(&mut self) {
+        if self.net.is_some() {
+            self.console.print("content packs changed, they load in the next game so everyone has the same");
+            return;
+        }
+        let (content, log) = Content::load(Path::new(mods::MODS_DIR));
+        self.content = content;
+        self.apply_content();
+        for line in log {
+            self.console.print(&line);
+        }
+        let added = self.assets.require(&self.content.glyphs());
+        if !added.is_empty() {
+            if let Err(err) = self.assets.render() {
+                self.report(err);
+            }
+        }
+        self.console.print(&format!("reloaded {} content packs", self.content.packs.len()));
+    }
+
+    /// Generates the rock nothing's been changed in again with the noise
+    /// settings as they are now, single player only for the same reason as
+    /// the content packs
+    fn reload_worldgen(&mut self) {
+        if self.net.is_some() {
+            self.console.print("worldgen settings changed, they apply to the next game so everyone has the same");
+            return;
+        }
+        let worldgen = match WorldGenConfig::load(Path::new(worldgen::CONFIG_FILE)) {
+            Ok(worldgen) => worldgen,
+            Err(err) => {
+                self.console.print(&err);
+                return;
+            },
+        };
+        if worldgen == self.sim.map.worldgen {
+            return;
+        }
+        let dropped = self.sim.map.regenerate(worldgen);
+        self.console.print(&format!("regenerating {} unchanged chunks with the new worldgen settings", dropped));
+    }
+
+    /// Re-renders the tileset and text at the size of the UI scale and
+    /// fits the map view to the screen again
+    fn apply_ui_scale(&mut self) {
+        let scale = self.config.ui_scale;
+        self.tile_size_px = Vector::new(TILE_SIZE_PX, TILE_SIZE_PX) * scale;
+        if let Err(err) = self.assets.resize(self.tile_size_px) {
+            self.report(err);
+        }
+        self.retitle();
+        self.font_info = render_font_info(&self.color_scheme, scale);
+        let tiles = self.view_tiles();
+        self.camera.resize(tiles);
+        if let Some(split) = &mut self.split {
+            split.camera.resize(tiles);
+        }
+        // the picture is drawn at the tile size, so it's made again
+        if let Some(id) = self.pip.as_ref().and_then(|pip| pip.camera.follow) {
+            self.pip = None;
+            self.watch(id);
+        }
+    }
+
+    /// How many tiles each map view spans, half as many across when the
+    /// map is split
+    fn view_tiles(&self) -> (u32, u32) {
+        let (width, height) = viewport_tiles(self.config.ui_scale);
+        if self.split.is_some() {
+            (width / 2, height)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Screen position of the top left corner of the map view with the
+    /// keyboard
+    fn map_offset_px(&self) -> Vector {
+        self.view_offset_px(self.split.as_ref().is_some_and(|split| split.focus_right))
+    }
+
+    /// Screen position of the top left corner of the left map view, or the
+    /// right one of a split map
+    fn view_offset_px(&self, right: bool) -> Vector {
+        let offset = Vector::from(MAP_OFFSET_PX) * self.config.ui_scale;
+        if right {
+            let (width, _) = self.view_tiles();
+            offset + Vector::new(width as f32 * self.tile_size_px.x + SPLIT_GAP_PX * self.config.ui_scale, 0.0)
+        } else {
+            offset
+        }
+    }
+
+    /// The camera of the view with the keyboard, or of the other one
+    fn view_camera(&self, focused: bool) -> &Camera {
+        match (&self.split, focused) {
+            (Some(split), false) => &split.camera,
+            _ => &self.camera,
+        }
+    }
+
+    /// Splits the map into two views looking at the same place to start
+    /// with, or joins them again into the one with the keyboard
+    fn toggle_split(&mut self) {
+        match self.split.take() {
+            Some(_) => self.camera.resize(self.view_tiles()),
+            None => {
+                self.split = Some(SplitView { camera: self.camera.clone(), focus_right: false });
+                let tiles = self.view_tiles();
+                self.camera.resize(tiles);
+                if let Some(split) = &mut self.split {
+                    split.camera.resize(tiles);
+                    split.camera.follow = None;
+                }
+            },
+        }
+    }
+
+    /// Watches the selected colonist in a picture in the corner, or stops
+    /// watching them if they are already
+    fn toggle_pip(&mut self) {
+        match self.player_id {
+            Some(id) if self.pip.as_ref().is_some_and(|pip| pip.camera.follow == Some(id)) => self.pip = None,
+            Some(id) => self.watch(id),
+            None => self.pip = None,
+        }
+    }
+
+    /// Puts the entity in the picture in picture, in place of whoever it
+    /// watched before
+    fn watch(&mut self, id: EntityId) {
+        let (width, height, depth) = self.sim.map.size();
+        let (pip_width, pip_height) = PIP_TILES;
+        let mut camera = Camera::new(0, 0, 0, width - pip_width, height - pip_height, depth, PIP_TILES);
+        camera.follow = Some(id);
+        if let Some(entity) = self.sim.entities.get(id) {
+            camera.center_on(entity.pos.x, entity.pos.y, entity.depth);
+        }
+        let size = self.tile_size_px.times(Vector::new(pip_width, pip_height));
+        match Surface::new(size.x as u32, size.y as u32) {
+            Ok(surface) => self.pip = Some(Pip { camera, surface }),
+            Err(err) => self.report(GameError::Draw { part: "picture in picture", reason: err.to_string() }),
+        }
+    }
+
+    /// Hands the keyboard to the other view of a split map
+    fn switch_view(&mut self) {
+        if let Some(split) = &mut self.split {
+            std::mem::swap(&mut self.camera, &mut split.camera);
+            split.focus_right = !split.focus_right;
+        }
+    }
+
+    /// What the view with the keyboard covers, for telling what's out of it
+    fn alert_view(&self) -> offscreen::View {
+        let viewport = self.camera.viewport;
+        let (world_width, _, _) = self.sim.map.size();
+        offscreen::View {
+            x: viewport.x(),
+            y: viewport.y(),
+            width: viewport.width(),
+            height: viewport.height(),
+            world_width,
+        }
+    }
+
+    /// Hands the events raised since the last call to every system that
+    /// listens for them
+    fn dispatch_events(&mut self) {
+        let center = self.camera.viewport.center();
+        let listener = (center.x, center.y, self.camera.z_position);
+        let now_ms = self.animation_clock.elapsed_ms();
+        let view = self.alert_view();
+        for event in self.sim.events.drain() {
+            self.audio.on_event(&event, listener, &self.config);
+            self.particles.on_event(&event, now_ms);
+            if let Some(pan) = self.alerts.on_event(&event, &view, now_ms) {
+                self.audio.cue(pan, &self.config);
+            }
+            if let GameEvent::Hit { x, y, z, .. } = event {
+                if self.player().is_some_and(|player| player.is_at(x, y, z)) {
+                    self.stop_travel("took damage");
+                }
+            }
+            let locale = &self.locale;
+            let at = |key: &str, x: u32, y: u32, z: u32| locale.format(key, &[("x", &x), ("y", &y), ("z", &z)]);
+            if let GameEvent::JobInterrupted { x, y, z, reason } = event {
+                let line = locale.format("log.stopped", &[("x", &x), ("y", &y), ("z", &z), ("reason", &reason.describe())]);
+                self.console.print(&line);
+            }
+            if let GameEvent::SkillUp { x, y, z, skill, level } = event {
+                let who = self.sim.entities.iter()
+                    .find(|(_, entity)| entity.is_at(x, y, z))
+                    .and_then(|(id, _)| self.sim.squad.name(id))
+                    .unwrap_or(locale.get("log.a_colonist"));
+                self.console.print(&locale.format("log.skill_up", &[("who", &who), ("skill", &skill.name()), ("level", &level)]));
+            }
+            if let GameEvent::Tamed { x, y, z } = event {
+                self.console.print(&at("log.tamed", x, y, z));
+            }
+            if let GameEvent::Born { x, y, z } = event {
+                self.console.print(&at("log.born", x, y, z));
+            }
+            if let GameEvent::CaravanArrived { x, y, z } = event {
+                self.console.print(&at("log.caravan_arrived", x, y, z));
+            }
+            if let GameEvent::CaravanLeft { .. } = event {
+                self.console.print(locale.get("log.caravan_left"));
+            }
+            if let GameEvent::Incident { x, y, z, incident } = event {
+                let line = locale.format("log.incident", &[("incident", &incident.describe()), ("x", &x), ("y", &y), ("z", &z)]);
+                self.console.print(&line);
+            }
+            if let GameEvent::WornOut { x, y, z } = event {
+                self.console.print(&at("log.worn_out", x, y, z));
+            }
+            if let GameEvent::HostileSighted { x, y, z } = event {
+                self.console.print(&at("log.hostile_sighted", x, y, z));
+            }
+            if let GameEvent::NoRoute { x, y, z } = event {
+                self.console.print(&at("log.no_route", x, y, z));
+            }
+            if let GameEvent::ColonyLost { loss, .. } = event {
+                let line = locale.format("log.colony_lost", &[("colony", &self.sim.name), ("loss", &loss.describe())]);
+                self.console.print(&line);
+                self.travel = None;
+                self.open_game_over(loss);
+            }
+            let (sim, chronicle) = (&self.sim, &mut self.chronicle);
+            let who = match event {
+                GameEvent::Died { x, y, z, .. } | GameEvent::SkillUp { x, y, z, .. } => sim.squad.members().iter()
+                    .find(|id| sim.entities.get(**id).is_some_and(|colonist| colonist.is_at(x, y, z)))
+                    .map(|id| sim.squad.name(*id).unwrap_or("A colonist")),
+                _ => None,
+            };
+            chronicle.on_event(&event, sim.tick, who);
+            for achievement in self.stats.on_event(&event) {
+                let line = self.locale.format("log.achievement", &[
+                    ("colony", &self.sim.name), ("name", &achievement.name), ("description", &achievement.description),
+                ]);
+                self.console.print(&line);
+            }
+            let lines = self.sim.script_event(&self.scripts, &event, self.player_id);
+            for line in lines {
+                self.console.print(&line);
+            }
+        }
+        for warning in self.sim.stock.take_warnings() {
+            self.console.print(&warning);
+        }
+    }
+
+    fn open_restore(&mut self, slot: SlotInfo) {
+        let title = format!("Restore {}?", slot.name);
+        let dialog = Dialog::new(&title, &["Restore", "Discard"]).with_text(vec![
+            String::from("the game closed before it could be saved,"),
+            format!("a copy was kept at tick {}", slot.tick),
+        ]);
+        let then = std::mem::replace(&mut self.state, GameState::Playing);
+        self.state = GameState::Restore(dialog, slot, Box::new(then));
+    }
+
+    fn open_quit_dialog(&mut self) {
+        self.state = GameState::ConfirmQuit(Dialog::new(
+            "Quit Janus 7 Mining Colony?",
+            &["Save and quit", "Quit without saving", "Cancel"],
+        ));
+    }
+
+    fn quit(&mut self, window: &mut Window) {
+        self.clear_autosave();
+        self.state = GameState::Quitting;
+        window.close();
+    }
+
+    /// Moves the player one tile, digging out the rock instead if the way
+    /// is blocked
+    fn move_player(&mut self, dx: i32, dy: i32) {
+        let colonist = match self.player_id {
+            Some(id) => id,
+            None => return,
+        };
+        self.travel = None;
+        self.player_facing = (dx, dy);
+        self.issue(Order::Step { colonist, dx, dy });
+    }
+
+    /// Hands the keyboard and move orders to another colonist, the camera
+    /// follows them if it was following the last one. Picking one while
+    /// overseeing takes direct control of them.
+    fn select_colonist(&mut self, id: EntityId) {
+        if self.player_id == Some(id) {
+            return;
+        }
+        self.travel = None;
+        if self.camera.follow.is_some() && self.camera.follow == self.player_id {
+            self.camera.follow = Some(id);
+        }
+        self.player_id = Some(id);
+        self.player_facing = (0, 1);
+        if self.config.overseer {
+            self.set_overseer(false);
+        } else {
+            self.claim_control();
+        }
+    }
+
+    /// Selects the squad member on the tile clicked on, if any
+    fn click_colonist(&mut self, point: Vector) {
+        let (x, y) = match self.screen_to_tile(point) {
+            Some(tile) => tile,
+            None => return,
+        };
+        let z = self.camera.z_position;
+        let clicked = self.sim.squad.members().iter().cloned().find(|id| {
+            self.sim.entities.get(*id).is_some_and(|colonist| colonist.is_alive() && colonist.is_at(x, y, z))
+        });
+        if let Some(id) = clicked {
+            self.select_colonist(id);
+        }
+    }
+
+    /// Sends the closest entity bigger than one tile on the current level
+    /// dx, dy tiles from where it is
+    fn drive_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        let dx: i64 = console::arg(args, 0, "dx")?;
+        let dy: i64 = console::arg(args, 1, "dy")?;
+        let z = self.camera.z_position;
+        let center = self.camera.viewport.center();
+        let (id, vehicle) = self.sim.entities.iter()
+            .filter(|(_, entity)| entity.depth == z && entity.footprint != Footprint::SINGLE)
+            .min_by_key(|(_, entity)| {
+                ((entity.pos.x - center.x).abs() + (entity.pos.y - center.y).abs()) as u32
+            })
+            .ok_or_else(|| String::from("nothing bigger than one tile on this level"))?;
+        let start = (vehicle.pos.x as u32, vehicle.pos.y as u32);
+        let goal = (
+            self.sim.map.wrap_x(start.0 as i64 + dx),
+            (start.1 as i64 + dy).max(0) as u32,
+        );
+        if self.net.is_some() {
+            self.issue(Order::MoveTo { colonist: id, x: goal.0, y: goal.1 });
+            return Ok(String::from("on its way"));
+        }
+        let steps = self.sim.send_to(id, goal)?;
+        Ok(format!("on its way, {} steps", steps))
+    }
+
+    /// Sends the player to x, y on the level they're on
+    fn travel_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        let x: i64 = console::arg(args, 0, "x")?;
+        let y: u32 = console::arg(args, 1, "y")?;
+        let z = self.player().map_or(0, |player| player.depth);
+        let steps = self.travel_to(self.sim.map.wrap_x(x), y, z)?;
+        Ok(format!("on the way, {} steps", steps))
+    }
+
+    /// Sends the player walking to (x, y, z) until they get there, take
+    /// damage or see a new hostile
+    fn travel_to(&mut self, x: u32, y: u32, z: u32) -> std::result::Result<usize, String> {
+        self.single_player_only()?;
+        let id = self.player_id.ok_or_else(|| String::from("there is no player"))?;
+        let player = self.sim.entities.get(id)
+            .ok_or_else(|| String::from("there is no player"))?;
+        if player.depth != z {
+            return Err(String::from("the player is on another level"));
+        }
+        let steps = self.sim.send_to(id, (x, y))?;
+        let seen = self.hostiles_in_sight();
+        self.travel = Some(Travel::new(Destination::Tile(x, y), seen));
+        Ok(steps)
+    }
+
+    /// Errs in a network game, for what changes the world without going
+    /// through an order
+    /// Whether a text field has the keyboard, so typing doesn't move
+    /// anything
+    fn typing(&self) -> bool {
+        matches!(self.modes.last(), Some(Mode::Console) | Some(Mode::Name(_)))
+    }
+
+    fn single_player_only(&self) -> std::result::Result<(), String> {
+        match self.net {
+            Some(_) => Err(String::from("that only works in single player")),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the player off to the nearest tiles they haven't seen
+    fn explore(&mut self) -> std::result::Result<String, String> {
+        self.single_player_only()?;
+        let seen = self.hostiles_in_sight();
+        self.travel = Some(Travel::new(Destination::Explore, seen));
+        self.update_travel();
+        match self.travel {
+            Some(_) => Ok(String::from("exploring, Backspace to stop")),
+            None => Err(String::from("nothing left to explore nearby")),
+        }
+    }
+
+    /// Hostiles the player can see from where they are
+    fn hostiles_in_sight(&self) -> HashSet<EntityId> {
+        match self.player() {
+            Some(player) => travel::hostiles_in_sight(
+                &self.sim.entities, &self.sim.map, 
+                player.pos.x as u32, player.pos.y as u32, player.depth),
+            None => HashSet::new(),
+        }
+    }
+
+    fn stop_travel(&mut self, reason: &str) {
+        if self.travel.take().is_some() {
+            if let Some(id) = self.player_id {
+                self.sim.paths.remove(&id);
+            }
+            self.console.print(&format!("stopped walking, {}", reason));
+        }
+    }
+
+    /// Keeps the player's walk going, stopping it for new hostiles and
+    /// picking the next tile nobody has seen when exploring
+    fn update_travel(&mut self) {
+        let (id, x, y, z, footprint) = match self.player_id.zip(self.player()) {
+            Some((id, player)) if player.is_alive() => (
+                id, player.pos.x as u32, player.pos.y as u32, player.depth, player.footprint),
+            _ => {
+                self.travel = None;
+                return;
+            },
+        };
+        let (destination, seen_hostiles) = match &self.travel {
+            Some(travel) => (travel.destination, &travel.seen_hostiles),
+            None => return,
+        };
+
+        let hostiles = self.hostiles_in_sight();
+        if hostiles.difference(seen_hostiles).next().is_some() {
+            self.stop_travel("a hostile came into sight");
+            return;
+        }
+
+        let goal = self.sim.paths.get(&id).and_then(|path| path.last()).cloned();
+        match destination {
+            // follow_paths drops the path once they arrive or are stuck
+            Destination::Tile(..) => if goal.is_none() {
+                self.travel = None;
+            },
+            Destination::Explore => {
+                if goal.is_some_and(|(gx, gy)| !self.sim.map.is_explored(gx, gy, z)) {
+                    return;
+                }
+                let entities = &self.sim.entities;
+                let path = pathfinding::find_nearest(
+                    &mut self.sim.map, (x, y), z, footprint, Movement::WALKING,
+                    |bx, by| entities.is_occupied(bx, by, z, Some(id)),
+                    |map, gx, gy| !map.is_explored(gx, gy, z));
+                match path {
+                    Some(path) => {
+                        self.sim.paths.insert(id, path);
+                    },
+                    None => self.stop_travel("nothing left to explore nearby"),
+                }
+            },
+        }
+    }
+
+    /// Sends the player to the tile clicked on, if it's on their level
+    fn order_move(&mut self, point: Vector) {
+        let goal = match self.screen_to_tile(point) {
+            Some(goal) => goal,
+            None => return,
+        };
+        let z = self.camera.z_position;
+        // network games only have plain move orders, the player's sight
+        // isn't shared
+        if self.net.is_some() {
+            let on_level = self.player_id.filter(|_| self.player().is_some_and(|player| player.depth == z));
+            if let Some(colonist) = on_level {
+                self.issue(Order::MoveTo { colonist, x: goal.0, y: goal.1 });
+            }
+            return;
+        }
+        if let Err(err) = self.travel_to(goal.0, goal.1, z) {
+            self.console.print(&err);
+        }
+    }
+
+    /// The area being picked, if selecting is the active mode
+    fn selection(&self) -> Option<&Selection> {
+        match self.modes.last() {
+            Some(Mode::Select(selection)) => Some(selection),
+            _ => None,
+        }
+    }
+
+    /// The tiles highlighted for the selection, where the blueprint would
+    /// go when stamping one
+    fn selected_area(&self) -> Option<Area> {
+        let selection = self.selection()?;
+        match (selection.command, &self.blueprint) {
+            (AreaCommand::Stamp, Some(blueprint)) => Some(Area {
+                x: selection.cursor.0,
+                y: selection.cursor.1,
+                width: blueprint.width,
+                height: blueprint.height,
+                z: selection.z,
+            }),
+            _ => Some(selection.area()),
+        }
+    }
+
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Open(menu) => self.modes.push(Mode::Menu(menu)),
+            Command::Area(area_command) => self.start_selection(area_command),
+            Command::BuildPillar => {
+                // the player can't move with a menu open
+                self.modes.clear();
+                self.build(Build::Pillar);
+            },
+            Command::BuildPump => {
+                self.modes.clear();
+                self.build(Build::Pump);
+            },
+            Command::BuildTrack(track) => {
+                self.modes.clear();
+                self.build(Build::Track(track));
+            },
+            Command::PlaceMinecart => {
+                self.modes.clear();
+                self.build(Build::Minecart);
+            },
+            Command::CycleOverlay => self.cycle_overlay(),
+            Command::ToggleFollow => self.toggle_follow(),
+            Command::Explore => {
+                self.modes.clear();
+                let message = self.explore().unwrap_or_else(|err| err);
+                self.console.print(&message);
+            },
+            Command::Toggle(component) => {
+                self.ui.toggle(component);
+            },
+            Command::NameColonist => {
+                let id = match self.player_id {
+                    Some(id) => id,
+                    None => return,
+                };
+                let current = self.sim.squad.name(id).unwrap_or_default();
+                let naming = Naming::new(Named::Colonist(id), current);
+                self.modes.clear();
+                self.modes.push(Mode::Name(naming));
+            },
+            Command::NameStockpile => {
+                let (x, y, z) = match self.player() {
+                    Some(player) => (player.pos.x as u32, player.pos.y as u32, player.depth),
+                    None => return,
+                };
+                let stockpile = match self.sim.designations.stockpile_at(x, y, z) {
+                    Some(stockpile) => stockpile,
+                    None => {
+                        self.console.print("stand in a stockpile to name it");
+                        return;
+                    },
+                };
+                let current = self.sim.designations.stockpile_name(stockpile).unwrap_or_default();
+                let naming = Naming::new(Named::Stockpile { x, y, z }, current);
+                self.modes.clear();
+                self.modes.push(Mode::Name(naming));
+            },
+            Command::KeepToBurrow => {
+                let colonist = match self.player_id {
+                    Some(id) => id,
+                    None => return,
+                };
+                let kept = !self.sim.squad.is_burrowed(colonist);
+                self.issue(Order::KeepToBurrow { colonist, kept });
+                self.console.print(if kept {
+                    "kept to the burrow"
+                } else {
+                    "free to go anywhere again"
+                });
+            },
+            Command::PickUp => {
+                self.modes.clear();
+                if let Some(colonist) = self.player_id {
+                    self.issue(Order::Equip { colonist });
+                }
+            },
+            Command::ToggleAlert => {
+                self.modes.clear();
+                self.issue(Order::Alert { raised: !self.sim.alert });
+            },
+            Command::Chronicle => {
+                self.modes.clear();
+                self.modes.push(Mode::Chronicle(Reading::new(self.chronicle.entries().len())));
+            },
+            Command::RotateBlueprint => match self.blueprint.take() {
+                Some(blueprint) => {
+                    let rotated = blueprint.rotated();
+                    self.console.print(&format!("blueprint turned, now {} x {}", rotated.width, rotated.height));
+                    self.blueprint = Some(rotated);
+                },
+                None => self.console.print("no blueprint yet, copy one first"),
+            },
+            Command::Notes => {
+                self.modes.clear();
+                self.modes.push(Mode::Notes(Reading::new(self.sim.designations.notes().len())));
+            },
+            Command::Rooms => {
+                self.modes.clear();
+                self.modes.push(Mode::Rooms(Reading::new(0)));
+            },
+            Command::Trade => {
+                self.modes.clear();
+                if self.sim.caravan.as_ref().is_some_and(|caravan| caravan.is_trading()) {
+                    self.modes.push(Mode::Trade(Trading::new()));
+                } else {
+                    self.console.print("there is no caravan trading, one comes down the drill now and then");
+                }
+            },
+        }
+    }
+
+    /// What can be traded: the ids of the caravan's goods, then the
+    /// colony's items lying in stockpiles on the caravan's level
+    fn trade_goods(&self) -> (Vec<String>, Vec<(EntityId, String)>) {
+        let caravan = match &self.sim.caravan {
+            Some(caravan) if caravan.is_trading() => caravan,
+            _ => return (Vec::new(), Vec::new()),
+        };
+        let z = self.sim.entities.get(caravan.entity).map_or(0, |traders| traders.depth);
+        let yours = self.sim.items.iter()
+            .filter(|(id, _)| self.sim.entities.get(**id).is_some_and(|entity| entity.depth == z
+                && self.sim.designations.is_stockpile(entity.pos.x as u32, entity.pos.y as u32, z)))
+            .map(|(id, item)| (*id, item.clone()))
+            .collect();
+        (caravan.goods.clone(), yours)
+    }
+
+    /// Takes the camera to where the entry under the cursor happened once
+    /// Return is pressed, closing the history
+    fn update_chronicle(&mut self, window: &Window) {
+        let cursor = match self.modes.last() {
+            Some(Mode::Chronicle(reading)) => reading.cursor,
+            _ => return,
+        };
+        let picked = if window.keyboard()[Key::Return] == ButtonState::Pressed {
+            Some(cursor)
+        } else if window.mouse()[MouseButton::Left] == ButtonState::Pressed {
+            match self.chronicle_entry_at(window, cursor) {
+                Some(index) => Some(index),
+                None => return,
+            }
+        } else {
+            return;
+        };
+        self.modes.pop();
+        if let Some(index) = picked {
+            self.jump_to_entry(index);
+        }
+    }
+
+    /// The entry clicked on in the chronicle, scrolled to the cursor
+    fn chronicle_entry_at(&mut self, window: &Window, cursor: usize) -> Option<usize> {
+        let (lines, first) = self.chronicle_lines(cursor);
+        let panel = self.text_panel(Anchor::Center, RenderLayer::UiTop, &lines);
+        let mouse = window.mouse().pos();
+        let mut row = None;
+        let text_layout = &self.text_layout;
+        let result = self.ui_font.execute(|font| {
+            row = panel.row_at(window, font, text_layout, mouse)?;
+            Ok(())
+        });
+        self.check_draw("chronicle", result);
+        // the title takes the first row
+        let index = first + row?.checked_sub(1)?;
+        Some(index).filter(|&index| index < self.chronicle.entries().len())
+    }
+
+    /// Takes the camera to where the entry in the chronicle happened
+    fn jump_to_entry(&mut self, index: usize) {
+        if let Some(entry) = self.chronicle.entries().get(index) {
+            let (x, y, z) = (entry.x, entry.y, entry.z);
+            self.camera.follow = None;
+            self.camera.center_on(x as f32, y as f32, z);
+        }
+    }
+
+    /// Takes the camera to the note under the cursor once Return is
+    /// pressed, closing the list, or removes it with X
+    fn update_notes(&mut self, window: &Window) {
+        let cursor = match self.modes.last() {
+            Some(Mode::Notes(reading)) => reading.cursor,
+            _ => return,
+        };
+        let (x, y, z) = match self.sim.designations.notes().get(cursor) {
+            Some(&(tile, _)) => tile,
+            None => return,
+        };
+        if window.keyboard()[Key::X] == ButtonState::Pressed {
+            self.issue(Order::Name { target: Named::Note { x, y, z }, name: String::new() });
+        } else if window.keyboard()[Key::Return] == ButtonState::Pressed {
+            self.modes.pop();
+            self.camera.follow = None;
+            self.camera.center_on(x as f32, y as f32, z);
+        }
+    }
+
+    /// Gives the room under the cursor the next type with Space, or takes
+    /// the camera there once Return is pressed, closing the list
+    fn update_rooms(&mut self, window: &Window) {
+        let cursor = match self.modes.last() {
+            Some(Mode::Rooms(reading)) => reading.cursor,
+            _ => return,
+        };
+        let (room, kind) = match self.sim.rooms.rooms().get(cursor) {
+            Some(room) => ((room.tiles[0], room.z), room.kind),
+            None => return,
+        };
+        let ((x, y), z) = room;
+        if window.keyboard()[Key::Space] == ButtonState::Pressed {
+            self.issue(Order::Room { x, y, z, kind: RoomType::next(kind) });
+        } else if window.keyboard()[Key::Return] == ButtonState::Pressed {
+            self.modes.pop();
+            self.camera.follow = None;
+            self.camera.center_on(x as f32, y as f32, z);
+        }
+    }
+
+    /// Picks goods out for the deal with Space and offers it with Return,
+    /// closing the trade screen once the caravan stops trading
+    fn update_trade(&mut self, window: &Window) {
+        use ButtonState::*;
+
+        if !self.sim.caravan.as_ref().is_some_and(|caravan| caravan.is_trading()) {
+            self.modes.pop();
+            self.console.print("the caravan is done trading");
+            return;
+        }
+        let (theirs, yours) = self.trade_goods();
+        let trading = match self.modes.last_mut() {
+            Some(Mode::Trade(trading)) => trading,
+            _ => return,
+        };
+        if window.keyboard()[Key::Space] == Pressed {
+            let cursor = trading.cursor;
+            if cursor < theirs.len() {
+                if !trading.deal.buy.insert(cursor) {
+                    trading.deal.buy.remove(&cursor);
+                }
+            } else if let Some((id, _)) = yours.get(cursor - theirs.len()) {
+                if !trading.deal.sell.insert(*id) {
+                    trading.deal.sell.remove(id);
+                }
+            }
+        }
+        if window.keyboard()[Key::Return] == Pressed {
+            let deal = std::mem::take(&mut trading.deal);
+            self.modes.pop();
+            self.issue(Order::Trade { buy: deal.buy.into_iter().collect(), sell: deal.sell.into_iter().collect() });
+        }
+    }
+
+    fn run_console_command(&mut self, line: &str) {
+        let (command, args) = console::parse(line);
+        let result = match command.as_str() {
+            "" => return,
+            "help" => Ok(self.console_help()),
+            "export_png" => self.export_png_command(&args),
+            "screenshot" => self.screenshot(),
+            "export_ascii" => self.export_ascii(),
+            "drive" => self.drive_command(&args),
+            "travel" => self.travel_command(&args),
+            "explore" => self.explore(),
+            "mods" => Ok(self.mods_command()),
+            "spawn" => self.single_player_only().and_then(|_| self.spawn_command(&args)),
+            "incident" => self.single_player_only().and_then(|_| self.incident_command(&args)),
+            "colors" => self.colors_command(&args),
+            "appearance" => self.single_player_only().and_then(|_| self.appearance_command(&args)),
+            "blueprint" => self.blueprint_command(&args),
+            _ if self.scripts.has_command(&command) => self.single_player_only().and_then(|_| {
+                let result = self.scripts.run_command(World {
+                    map: &mut self.sim.map,
+                    entities: &mut self.sim.entities,
+                    player_id: self.player_id,
+                }, &command, &args);
+                let effects = self.scripts.take_effects();
+                for line in self.sim.apply_script_effects(effects) {
+                    self.console.print(&line);
+                }
+                result
+            }),
+            _ => Err(format!("unknown command {:?}, try help", command)),
+        };
+        match result {
+            Ok(output) => {
+                for line in output.lines() {
+                    self.console.print(line);
+                }
+            },
+            Err(err) => self.console.print(&format!("error: {}", err)),
+        }
+    }
+
+    fn mods_command(&self) -> String {
+        let content = &self.content;
+        if content.packs.is_empty() {
+            return format!("no content packs in {}/", mods::MODS_DIR);
+        }
+        let list = |ids: Vec<&String>| ids.iter()
+            .map(|id| id.as_str()).collect::<Vec<_>>().join(", ");
+        format!("load order: {}\nmaterials: {}\nitems: {}\nrecipes: {}\ncreatures: {}\ncolor schemes: {}",
+                content.packs.join(", "),
+                list(content.materials.ids().collect()),
+                list(content.items.ids().collect()),
+                list(content.recipes.ids().collect()),
+                list(content.creatures.ids().collect()),
+                list(content.color_schemes.ids().collect()))
+    }
+
+    /// Puts a creature from a content pack on the open tile in front of
+    /// the player
+    fn spawn_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        let id: String = console::arg(args, 0, "creature")?;
+        let creature = self.content.creatures.get(&id)
+            .ok_or_else(|| format!("no creature {:?}, mods lists them", id))?;
+        let (x, y, z) = self.facing_tile().ok_or("nowhere to put it")?;
+        let entities = &self.sim.entities;
+        let blocked = |x, y| entities.is_occupied(x, y, z, None);
+        if !pathfinding::fits(&mut self.sim.map, (x, y), z, creature.footprint, creature.movement, &blocked) {
+            return Err(String::from("there is no room in front of the player"));
+        }
+        let creature = creature.clone();
+        self.sim.spawn_creature(&creature, x, y, z);
+        let name = creature.name;
+        Ok(format!("spawned {} at {}, {}, {}", name, x, y, z))
+    }
+
+    /// Opens the appearance picker, or sets the player's glyph and color
+    /// straight away when they're given
+    fn appearance_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        if args.is_empty() {
+            self.open_appearance();
+            return Ok(String::new());
+        }
+        let glyph: char = console::arg(args, 0, "glyph")?;
+        let name: String = console::arg(args, 1, "color")?;
+        if !self.assets.glyphs().contains(&glyph) {
+            return Err(format!("the tileset has no {:?}", glyph));
+        }
+        let color: ColorName = serde_json::from_value(serde_json::Value::from(name.as_str()))
+            .map_err(|_| format!("no color {:?}, try one like LightAqua", name))?;
+        let id = self.player_id.ok_or_else(|| String::from("there is no player"))?;
+        self.sim.set_appearance(id, glyph, color);
+        Ok(format!("the player is now a {:?} {}", color, glyph))
+    }
+
+    /// Makes the incident befall the colony straight away, the message about
+    /// it comes with the other events
+    fn incident_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        let name: String = console::arg(args, 0, "name")?;
+        let incident = Incident::from_name(&name)
+            .ok_or_else(|| format!("no incident {:?}, try tremor, gas_release, lost_miner, equipment_failure or fire", name))?;
+        let mut rng = incidents::rng(self.sim.map.random_seed, self.sim.tick);
+        if !self.sim.befall(incident, &mut rng) {
+            return Err(String::from("there was nowhere for it to happen"));
+        }
+        Ok(String::new())
+    }
+
+    /// Writes the blueprint to a file, or reads one back in to be stamped
+    fn blueprint_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        let action: String = console::arg(args, 0, "export or import")?;
+        match action.as_str() {
+            "export" => {
+                let blueprint = self.blueprint.as_ref()
+                    .ok_or("no blueprint yet, copy one from the blueprint menu")?;
+                let file_name = export::timestamped_file_name("blueprint", "json");
+                std::fs::write(&file_name, blueprint.to_json()?)
+                    .map_err(|err| format!("could not write {}: {}", file_name, err))?;
+                Ok(format!("wrote {}", file_name))
+            },
+            "import" => {
+                let file_name: String = console::arg(args, 1, "file")?;
+                let text = std::fs::read_to_string(&file_name)
+                    .map_err(|err| format!("could not read {}: {}", file_name, err))?;
+                let blueprint = Blueprint::from_json(&text)
+                    .map_err(|err| format!("{} is no blueprint: {}", file_name, err))?;
+                let message = format!("read a {} x {} blueprint from {}", blueprint.width, blueprint.height, file_name);
+                self.blueprint = Some(blueprint);
+                Ok(message)
+            },
+            _ => Err(format!("no blueprint action {:?}, try export or import", action)),
+        }
+    }
+
+    /// Switches to a color scheme from a content pack until the palette is
+    /// changed in settings
+    fn colors_command(&mut self, args: &[String]) -> std::result::Result<String, String> {
+        let id: String = console::arg(args, 0, "scheme")?;
+        let scheme = self.content.color_schemes.get(&id)
+            .ok_or_else(|| format!("no color scheme {:?}, mods lists them", id))?;
+        self.color_scheme = scheme.to_color_scheme()?;
+        self.color_scheme.adjust = self.config.color_adjust();
+        let message = format!("colors: {}", scheme.name);
+        self.retitle();
+        self.font_info = render_font_info(&self.color_scheme, self.config.ui_scale);
+        Ok(message)
+    }
+
+    /// The built in commands followed by the ones scripts add
+    fn console_help(&self) -> String {
+        let mut help = String::from(CONSOLE_HELP);
+        for (command, script) in self.scripts.commands() {
+            help.push_str(&format!("\n{}: from {}", command, script));
+        }
+        help
+    }
+
+    fn export_png_command(&mut self, args: &[String]) 
+        -> std::result::Result<String, String> {
+        let (max_x, max_y, max_z) = self.sim.map.size();
+        let x = console::arg(args, 0, "x")?;
+        let y = console::arg(args, 1, "y")?;
+        let width: u32 = console::arg(args, 2, "width")?;
+        let height: u32 = console::arg(args, 3, "height")?;
+        let z = match args.get(4) {
+            Some(_) => console::arg(args, 4, "z")?,
+            None => self.camera.z_position,
+        };
+        if width == 0 || height == 0 
+           || width > MAX_EXPORT_SIZE || height > MAX_EXPORT_SIZE {
+            return Err(format!("width and height should be 1 to {}", MAX_EXPORT_SIZE));
+        }
+        if x + width > max_x || y + height > max_y || z >= max_z {
+            return Err(format!("the world is {} x {} x {} tiles", max_x, max_y, max_z));
+        }
+        self.export_png(Area { x, y, width, height, z }, "map")
+    }
+
+    /// Renders the tiles in the map view to a png, the window itself can't
+    /// be read back so this goes through the offscreen renderer too
+    fn screenshot(&mut self) -> std::result::Result<String, String> {
+        let (_, max_y, _) = self.sim.map.size();
+        let viewport = self.camera.viewport;
+        let (x, y) = (viewport.x() as u32, viewport.y() as u32);
+        // the view may run past the east edge, get_tile wraps those tiles
+        let area = Area {
+            x,
+            y,
+            width: viewport.width() as u32,
+            height: (viewport.height() as u32).min(max_y - y),
+            z: self.camera.z_position,
+        };
+        self.export_png(area, "screenshot")
+    }
+
+    /// Writes the tiles in the map view to a text file, one glyph per tile
+    fn export_ascii(&mut self) -> std::result::Result<String, String> {
+        let ascii = self.sim.map.export_ascii(self.camera.viewport, self.camera.z_position);
+        let file_name = export::timestamped_file_name("map", "txt");
+        std::fs::write(&file_name, ascii)
+            .map_err(|err| format!("could not write {}: {}", file_name, err))?;
+        Ok(format!("wrote {}", file_name))
+    }
+
+    fn export_png(&mut self, area: Area, prefix: &str) 
+        -> std::result::Result<String, String> {
+        let renderer = OffscreenRenderer::new(&TILESET_GLYPHS, self.tile_size_px.y as u32)
+            .map_err(|err| format!("could not load the tileset fonts: {}", err))?;
+        let canvas = export::render_area(
+            &renderer, &mut self.sim.map, &self.sim.entities, &self.color_scheme, area);
+        let file_name = export::timestamped_file_name(prefix, "png");
+        canvas.save_png(Path::new(&file_name))
+            .map_err(|err| format!("could not write {}: {}", file_name, err))?;
+        Ok(format!("wrote {} ({} x {} px)", file_name, canvas.width, canvas.height))
+    }
+
+    fn toggle_follow(&mut self) {
+        self.camera.follow = match self.camera.follow {
+            Some(_) => None,
+            None => self.player_id,
+        };
+    }
+
+    /// Moves the selection cursor while selecting an area, the player when
+    /// no mode is active
+    fn move_cursor_or_player(&mut self, dx: i32, dy: i32) {
+        let (max_x, max_y, _) = self.sim.map.size();
+        let rows = match self.modes.last() {
+            Some(Mode::Trade(_)) => {
+                let (theirs, yours) = self.trade_goods();
+                theirs.len() + yours.len()
+            },
+            Some(Mode::Chronicle(_)) => self.chronicle.entries().len(),
+            Some(Mode::Notes(_)) => self.sim.designations.notes().len(),
+            Some(Mode::Rooms(_)) => self.sim.rooms.rooms().len(),
+            _ => 0,
+        };
+        match self.modes.last_mut() {
+            Some(Mode::Select(selection)) => {
+                selection.move_cursor(dx, dy, max_x, max_y);
+                let (x, y) = selection.cursor;
+                self.camera.keep_in_view(x, y, CURSOR_MARGIN);
+            },
+            Some(Mode::Trade(trading)) => trading.move_cursor(dy, rows),
+            Some(Mode::Chronicle(reading)) | Some(Mode::Notes(reading)) | Some(Mode::Rooms(reading)) =>
+                reading.move_cursor(dy, rows),
+            Some(_) => {},
+            None if self.config.overseer => {
+                match dx {
+                    -1 => self.camera.move_left(),
+                    1 => self.camera.move_right(),
+                    _ => {},
+                }
+                match dy {
+                    -1 => self.camera.move_up(),
+                    1 => self.camera.move_down(),
+                    _ => {},
+                }
+            },
+            None => self.move_player(dx, dy),
+        }
+    }
+
+    /// Switches between steering a colonist and overseeing the colony with
+    /// a free camera, where nobody is steered and every colonist works on
+    /// their own. Steering again takes up the first living colonist.
+    fn set_overseer(&mut self, overseer: bool) {
+        self.config.overseer = overseer;
+        if overseer {
+            self.player_id = None;
+            self.travel = None;
+            self.camera.follow = None;
+        } else if !self.player().is_some_and(|player| player.is_alive()) {
+            self.player_id = self.sim.squad.next_after(self.player_id, &self.sim.entities);
+            self.player_facing = (0, 1);
+        }
+        self.claim_control();
+        if let Err(err) = self.config.save() {
+            log::warn!(target: logging::CONFIG, "could not save the config: {:?}", err);
+        }
+    }
+
+    /// Starts picking an area for the command, with the cursor on the
+    /// player or in the middle of the view if there is no player
+    fn start_selection(&mut self, command: AreaCommand) {
+        let z = self.camera.z_position;
+        let (x, y) = match self.player() {
+            Some(player) if player.depth == z => (player.pos.x, player.pos.y),
+            _ => {
+                let center = self.camera.viewport.center();
+                (center.x, center.y)
+            },
+        };
+        self.modes.push(Mode::Select(Selection::new(command, x as u32, y as u32, z)));
+        self.inspection = None;
+    }
+
+    /// Moves the selection with Return and the mouse, hands the area to
+    /// the active command once both corners are picked
+    fn update_selection(&mut self, window: &Window) {
+        use ButtonState::*;
+
+        let hovered = self.screen_to_tile(window.mouse().pos());
+        let selection = match self.modes.last_mut() {
+            Some(Mode::Select(selection)) => selection,
+            _ => return,
+        };
+        selection.z = self.camera.z_position;
+
+        let mut finished = None;
+        if window.keyboard()[Key::Return] == Pressed {
+            finished = selection.confirm();
+        }
+        if let Some(tile) = hovered {
+            match window.mouse()[MouseButton::Left] {
+                Pressed => {
+                    selection.cursor = tile;
+                    selection.anchor = Some(tile);
+                },
+                Held => selection.cursor = tile,
+                Released if selection.anchor.is_some() => {
+                    selection.cursor = tile;
+                    finished = selection.confirm();
+                },
+                _ => {},
+            }
+        }
+
+        if let Some(area) = finished {
+            let command = selection.command;
+            self.apply_area_command(command, area);
+        }
+    }
+
+    fn apply_area_command(&mut self, command: AreaCommand, area: Area) {
+        match command {
+            AreaCommand::Inspect => self.inspection = Some(self.inspect(area)),
+            AreaCommand::Travel => {
+                self.modes.clear();
+                if let Err(err) = self.travel_to(area.x, area.y, area.z) {
+                    self.console.print(&err);
+                }
+            },
+            AreaCommand::Copy => {
+                let blueprint = self.sim.capture_blueprint(area);
+                if blueprint.is_empty() {
+                    self.console.print("nothing has been built or dug there to copy");
+                    return;
+                }
+                self.console.print(&format!("copied {} plans into a blueprint", blueprint.plans.len()));
+                self.blueprint = Some(blueprint);
+            },
+            AreaCommand::Stamp => match self.blueprint.clone() {
+                Some(blueprint) => self.issue(Order::Stamp { blueprint, x: area.x, y: area.y, z: area.z }),
+                None => self.console.print("no blueprint yet, copy one first"),
+            },
+            _ => self.issue(Order::Area { command, area }),
+        }
+    }
+
+    /// Counts what is in the area
+    fn inspect(&mut self, area: Area) -> String {
+        let (mut walls, mut floors, mut rubble, mut pillars) = (0, 0, 0, 0);
+        let (mut flooded, mut gas, mut marked) = (0, 0, 0);
+        for (x, y, z) in area.tiles() {
+            let tile = self.sim.map.get_tile(x, y, z);
+            match tile.kind {
+                TileKind::Wall => walls += 1,
+                TileKind::Floor => floors += 1,
+                TileKind::Rubble => rubble += 1,
+                TileKind::Pillar => pillars += 1,
+            }
+            if tile.fluid > 0 {
+                flooded += 1;
+            }
+            if tile.gas > 0 {
+                gas += 1;
+            }
+            if self.sim.designations.is_dig(x, y, z) {
+                marked += 1;
+            }
+        }
+        let living = self.sim.entities.iter()
+            .filter(|(_, entity)| entity.max_hp > 0 && entity.is_alive())
+            .filter(|(_, entity)| area.contains(
+                entity.pos.x as u32, entity.pos.y as u32, entity.depth))
+            .count();
+
+        let (mut waiting, mut delivered) = (0, 0);
+        for (x, y, z) in area.tiles() {
+            waiting += self.sim.railway.stone_at(x, y, z).unwrap_or(0);
+            delivered += self.sim.railway.delivered_at(x, y, z);
+        }
+        let hauled: u32 = self.sim.railway.carts.iter()
+            .filter(|cart| self.sim.entities.get(cart.entity).is_some_and(|entity| area.contains(
+                entity.pos.x as u32, entity.pos.y as u32, entity.depth)))
+            .map(|cart| cart.cargo)
+            .sum();
+
+        let squad = &self.sim.squad;
+        let mut names: Vec<&str> = self.sim.entities.iter()
+            .filter(|(_, entity)| area.contains(
+                entity.pos.x as u32, entity.pos.y as u32, entity.depth))
+            .filter_map(|(id, _)| squad.name(id))
+            .collect();
+        names.extend(self.sim.designations.stockpile_names_in(area));
+        names.extend(self.sim.designations.notes().iter()
+            .filter(|((x, y, z), _)| area.contains(*x, *y, *z))
+            .map(|(_, text)| text.as_str()));
+        let named = if names.is_empty() {
+            String::new()
+        } else {
+            format!(", named: {}", names.join(", "))
+        };
+
+        // gear lying about and the tools colonists carry
+        let describe = |gear: &Gear| match gear.uses {
+            Some(uses) => format!("{} ({} uses)", gear.name, uses),
+            None => gear.name.clone(),
+        };
+        let mut gear: Vec<String> = self.sim.entities.iter()
+            .filter(|(_, entity)| area.contains(
+                entity.pos.x as u32, entity.pos.y as u32, entity.depth))
+            .filter_map(|(id, _)| match self.sim.gear.get(&id) {
+                Some(lying) => Some(describe(lying)),
+                None => squad.equipment(id)
+                    .and_then(|equipment| equipment.tool.as_ref())
+                    .map(|tool| format!("{}, carried", describe(tool))),
+            })
+            .collect();
+        gear.sort();
+        let gear = if gear.is_empty() {
+            String::new()
+        } else {
+            format!(", gear: {}", gear.join(", "))
+        };
+
+        let (mut tame, mut hungry, mut marked_to_catch) = (0, 0, 0);
+        for (id, animal) in &self.sim.livestock {
+            let inside = self.sim.entities.get(*id).is_some_and(|entity| area.contains(
+                entity.pos.x as u32, entity.pos.y as u32, entity.depth));
+            if !inside {
+                continue;
+            }
+            match animal.taming {
+                Taming::Tame if animal.is_hungry(self.sim.tick) => hungry += 1,
+                Taming::Tame => tame += 1,
+                Taming::Marked => marked_to_catch += 1,
+                Taming::Wild => {},
+            }
+        }
+        let livestock = if tame + hungry + marked_to_catch == 0 {
+            String::new()
+        } else {
+            format!(", livestock: {} fed {} hungry {} to catch", tame, hungry, marked_to_catch)
+        };
+        let (mut fresh, mut rotting) = (0, 0);
+        for (id, corpse) in &self.sim.corpses {
+            let inside = self.sim.entities.get(*id).is_some_and(|entity| area.contains(
+                entity.pos.x as u32, entity.pos.y as u32, entity.depth));
+            match (inside, corpse.is_rotting(self.sim.tick)) {
+                (false, _) => {},
+                (true, false) => fresh += 1,
+                (true, true) => rotting += 1,
+            }
+        }
+        let corpses = if fresh + rotting == 0 {
+            String::new()
+        } else {
+            format!(", corpses: {} fresh {} rotting", fresh, rotting)
+        };
+        let (mut growing, mut ripe) = (0, 0);
+        for (&(x, y, z), crop) in &self.sim.crops {
+            if !area.contains(x, y, z) {
+                continue;
+            }
+            match self.sim.seeds.seed(&crop.kind) {
+                Some(seed) if crop.is_ripe(&seed.crop) => ripe += 1,
+                _ => growing += 1,
+            }
+        }
+        let crops = if growing + ripe == 0 {
+            String::new()
+        } else {
+            format!(", crops: {} growing {} ripe", growing, ripe)
+        };
+        let fires = self.sim.fires.tiles().into_iter()
+            .filter(|&(x, y, z)| area.contains(x, y, z))
+            .count();
+        let fires = if fires == 0 { String::new() } else { format!(", fires: {}", fires) };
+        // levels count down, caves on the water table's level and deeper
+        // were generated flooded
+        let water_table = self.sim.map.water_table;
+        let water_table = if area.z >= water_table {
+            format!(", water table: level {}, this level is under it", water_table)
+        } else {
+            format!(", water table: level {}, {} levels down", water_table, water_table - area.z)
+        };
+
+        format!("walls: {}, floors: {}, rubble: {}, pillars: {}, flooded: {}, gas: {}, marked for digging: {}, living: {}, stone at stops: {} waiting {} delivered, in carts: {}{}{}{}{}{}{}{}",
+                walls, floors, rubble, pillars, flooded, gas, marked, living, waiting, delivered, hauled, water_table, gear, livestock, corpses, crops, fires, named)
+    }
+
+    /// Starts writing the note for the tile under the mouse, or the one
+    /// in the middle of the view, starting off with the note there
+    fn place_note(&mut self, point: Vector) {
+        let z = self.camera.z_position;
+        let (x, y) = self.screen_to_tile(point).unwrap_or_else(|| {
+            let center = self.camera.viewport.center();
+            (center.x as u32, center.y as u32)
+        });
+        let current = self.sim.designations.note_at(x, y, z).unwrap_or_default();
+        let naming = Naming::new(Named::Note { x, y, z }, current);
+        self.modes.push(Mode::Name(naming));
+    }
+
+    /// The map tile drawn under a point on the screen, if any
+    fn screen_to_tile(&self, point: Vector) -> Option<(u32, u32)> {
+        let zoom = self.camera.zoom_factor;
+        // tiles are scaled around their center when drawn
+        let origin = self.map_offset_px() 
+            + self.tile_size_px * ((1.0 - zoom) / 2.0);
+        let tile_size_px = self.tile_size_px * zoom;
+        let x = ((point.x - origin.x) / tile_size_px.x).floor();
+        let y = ((point.y - origin.y) / tile_size_px.y).floor();
+        if x < 0.0 || y < 0.0 
+           || x >= self.camera.viewport.width().floor() 
+           || y >= self.camera.viewport.height().floor() {
+            return None;
+        }
+        Some((
+            self.sim.map.wrap_x(self.camera.viewport.x() as i64 + x as i64), 
+            self.camera.viewport.y() as u32 + y as u32,
+        ))
+    }
+
+    /// Switches to the next overlay, going back to the plain map after
+    /// the last one
+    fn cycle_overlay(&mut self) {
+        self.overlay = match self.overlay {
+            None if !self.overlays.is_empty() => Some(0),
+            Some(index) if index + 1 < self.overlays.len() => Some(index + 1),
+            _ => None,
+        };
+    }
+
+    /// The tile the living player is facing
+    fn facing_tile(&self) -> Option<(u32, u32, u32)> {
+        self.player_id.and_then(|id| self.sim.facing_tile_of(id, self.player_facing))
+    }
+
+    /// Writes the colony to its slot, returns whether it succeeded
+    fn save_current(&mut self) -> bool {
+        if let Some(Session::Client(_)) = self.net {
+            log::warn!(target: logging::SAVE, "the host keeps the colony, not saving {:?}", self.sim.name);
+            return true;
+        }
+        if self.scenario.is_some() {
+            return true;
+        }
+        let info = self.slot_info();
+        match save::save_slot(info, &self.save_data()) {
+            Ok(()) => {
+                self.sim.unsaved_changes = false;
+                true
+            },
+            Err(err) => {
+                log::warn!(target: logging::SAVE, "could not save to {:?}: {:?}", self.slot_profile, err);
+                false
+            },
+        }
+    }
+
+    /// What the slot list shows about the colony as it is now
+    fn slot_info(&mut self) -> SlotInfo {
+        SlotInfo {
+            name: self.sim.name.clone(),
+            profile: self.slot_profile.clone(),
+            play_time_secs: self.play_time.as_secs(),
+            tick: self.sim.tick,
+            saved_at: save::now_secs(),
+            thumbnail: self.thumbnail(),
+        }
+    }
+
+    /// Makes the slot the colony's and saves into it, back on the pause menu
+    fn save_to_slot(&mut self, name: String, profile: String) {
+        if self.scenario.is_some() {
+            self.open_pause_menu(Some("scenarios aren't saved"));
+            return;
+        }
+        self.sim.name = name;
+        self.slot_profile = profile;
+        self.retitle();
+        let reason = if self.save_current() {
+            format!("saved {}", self.sim.name)
+        } else {
+            String::from("the colony could not be saved")
+        };
+        self.open_pause_menu(Some(&reason));
+    }
+
+    /// Keeps a copy of the colony apart from its slot, for when the game
+    /// might be gone before the player can save
+    fn autosave(&mut self) {
+        if let Some(Session::Client(_)) = self.net {
+            return;
+        }
+        if self.scenario.is_some() {
+            return;
+        }
+        let info = self.slot_info();
+        if let Err(err) = save::autosave(&info, &self.save_data()) {
+            log::warn!(target: logging::SAVE, "could not save to {:?}: {:?}", save::AUTOSAVE_PROFILE, err);
+        }
+    }
+
+    /// Clears the autosave once the player answered whether to keep the
+    /// colony
+    fn clear_autosave(&self) {
+        if let Err(err) = save::clear_autosave() {
+            log::warn!(target: logging::SAVE, "could not clear {:?}: {:?}", save::AUTOSAVE_PROFILE, err);
+        }
+    }
+
+    /// The map around the middle of the view, drawn small for the slot list
+    fn thumbnail(&mut self) -> Thumbnail {
+        let (width, height) = (THUMBNAIL_SIZE.0 * THUMBNAIL_BLOCK, THUMBNAIL_SIZE.1 * THUMBNAIL_BLOCK);
+        let (_, max_y, _) = self.sim.map.size();
+        let viewport = self.camera.viewport;
+        let center_x = (viewport.x() + viewport.width() / 2.0) as i64;
+        let center_y = (viewport.y() + viewport.height() / 2.0).max(0.0) as u32;
+        let area = Area {
+            x: self.sim.map.wrap_x(center_x - width as i64 / 2),
+            y: center_y.saturating_sub(height / 2).min(max_y.saturating_sub(height)),
+            width,
+            height,
+            z: self.camera.z_position,
+        };
+        let canvas = export::render_thumbnail(
+            &mut self.sim.map, &self.sim.entities, &self.color_scheme, area, THUMBNAIL_BLOCK);
+        Thumbnail { width: canvas.width, height: canvas.height, pixels: canvas.into_pixels() }
+    }
+
+    /// Everything needed to restore the colony as it is
+    fn save_data(&self) -> SaveData {
+        SaveData {
+            camera_x: self.camera.viewport.x(),
+            camera_y: self.camera.viewport.y(),
+            camera_z: self.camera.z_position,
+            camera_follow: self.camera.follow,
+            stats: self.stats.clone(),
+            chronicle: self.chronicle.clone(),
+            ..self.sim.save_data(self.player_id)
+        }
+    }
+
+    // fn draw_something(&mut self, window: &mut Window) -> Result<()> {
+    //     Ok(())
+    // }
+
+    fn draw_title(&mut self, window: &mut Window) -> Result<()> {
+        let scale = self.config.ui_scale;
+        let mut title = None;
+        self.title.execute(|image| {
+            title = Some(image.clone());
+            Ok(())
+        })?;
+        let title = match title {
+            Some(title) => title,
+            None => return Ok(()),
+        };
+        // centered 40 pixels down
+        let panel = Panel::new(Anchor::Top, RenderLayer::UiPanels)
+            .with_margin((0.0, 40.0 * scale - title.area().height() / 2.0))
+            .with_image(title);
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// The tick and the weather, with what it turns to and when, in the
+    /// top left corner above the map
+    fn draw_clock(&mut self, window: &mut Window) -> Result<()> {
+        let forecast = &self.sim.weather;
+        let lines = [
+            format!("Tick {}", self.sim.tick),
+            format!("Surface: {}", forecast.weather.name()),
+            format!("{} in {} ticks", forecast.next.name(), forecast.turns_in(self.sim.tick)),
+        ];
+        let scale = self.config.ui_scale;
+        let panel = self.text_panel(Anchor::TopLeft, RenderLayer::UiPanels, &lines)
+            .with_margin((10.0 * scale, 6.0 * scale));
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// What the stockpiles hold down the right of the screen, with the food
+    /// first
+    fn draw_resources(&mut self, window: &mut Window) -> Result<()> {
+        let stock = &self.sim.stock;
+        let mut lines = vec![String::from("Stockpiles")];
+        lines.push(if stock.is_low_on_food() {
+            format!("Food: {}, running low", stock.food())
+        } else {
+            format!("Food: {}", stock.food())
+        });
+        let economy = &self.sim.economy;
+        lines.extend(stock.counts().map(|(kind, count)| {
+            let name = economy.item(kind).map_or(kind, |item| item.name.as_str());
+            format!("{}: {}", name, count)
+        }));
+        let scale = self.config.ui_scale;
+        // under the frame timings
+        let panel = self.text_panel(Anchor::TopRight, RenderLayer::UiPanels, &lines)
+            .with_margin((10.0 * scale, 220.0 * scale));
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// A banner across the top of the screen while the colony is on alert
+    fn draw_alert(&mut self, window: &mut Window) -> Result<()> {
+        if !self.sim.alert {
+            return Ok(());
+        }
+        let text = match self.sim.designations.rally_point() {
+            Some((x, y, z)) => format!("ALERT: retreating to the rally point at ({}, {}, {}), Tab A stands down", x, y, z),
+            None => String::from("ALERT: no rally point, Tab R places one, Tab A stands down"),
+        };
+        let scale = self.config.ui_scale;
+        let panel = Panel::new(Anchor::Top, RenderLayer::UiPanels)
+            .with_margin((0.0, 70.0 * scale))
+            .with_padding(4.0 * scale)
+            .with_background(Color::from_hex(&self.color_scheme.red))
+            .stretched()
+            .centered()
+            .with_text(&text, TextStyle::new(20.0 * scale, Color::from_hex(&self.color_scheme.fg)));
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// The map with the note under the mouse and the chunk grid over it
+    fn draw_map_view(&mut self, window: &mut Window) -> Result<()> {
+        let map_timer = Timer::start(System::RenderMap);
+        let result = self.draw_view(window, true);
+        self.check_draw("map", result);
+        if self.split.is_some() {
+            let result = self.draw_view(window, false);
+            self.check_draw("other view", result);
+            // the view with the keyboard is framed
+            let (width, height) = self.view_tiles();
+            let area = Rectangle::new(self.map_offset_px(), self.tile_size_px.times(Vector::new(width, height)));
+            let color = Color::from_hex(self.color_scheme.get_color_code(&ColorName::LightYellow));
+            for side in outline(area, (2.0 * self.config.ui_scale).max(1.0)).iter() {
+                window.draw_ex(side, Col(color), Transform::IDENTITY, RenderLayer::Overlay.over());
+            }
+        }
+        let result = self.draw_hovered_note(window);
+        self.check_draw("note", result);
+        if self.chunk_grid != ChunkGrid::Off {
+            let result = self.draw_chunk_grid(window);
+            self.check_draw("chunk grid", result);
+        }
+        self.draw_alerts(window);
+        self.profiler.stop(map_timer);
+        Ok(())
+    }
+
+    /// Draws the map around whoever's watched into the picture in picture,
+    /// and the picture in the bottom right corner
+    fn draw_pip(&mut self, window: &mut Window) -> Result<()> {
+        let pip = match self.pip.take() {
+            Some(pip) => pip,
+            None => return Ok(()),
+        };
+        let result = pip.surface.render_to(window, |window| self.draw_map(window, &pip.camera, Vector::ZERO));
+        let name = pip.camera.follow.and_then(|id| self.sim.squad.name(id)).unwrap_or("someone");
+        let scale = self.config.ui_scale;
+        let panel = Panel::new(Anchor::BottomRight, RenderLayer::UiPanels)
+            .with_margin(Vector::new(10.0, 40.0) * scale)
+            .with_padding(4.0 * scale)
+            .with_background(Color::from_hex(&self.color_scheme.bg))
+            .with_text(&format!("Watching {}", name), TextStyle::new(14.0 * scale, Color::from_hex(&self.color_scheme.fg)))
+            .with_image(pip.surface.image().clone());
+        self.pip = Some(pip);
+        result?;
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// Draws the map as seen by the view with the keyboard, or the other
+    /// one when the map is split
+    fn draw_view(&mut self, window: &mut Window, focused: bool) -> Result<()> {
+        let camera = self.view_camera(focused).clone();
+        let offset_px = self.view_offset_px(self.split.as_ref().is_some_and(|split| split.focus_right == focused));
+        self.draw_map(window, &camera, offset_px)
+    }
+
+    /// Draws the map as the camera sees it, the top left corner of the view
+    /// at offset_px
+    fn draw_map(&mut self, window: &mut Window, camera: &Camera, offset_px: Vector) -> Result<()> {
+        let _span = logging::span(logging::RENDER, "draw_map");
+        if camera.zoom_factor <= FAR_ZOOM {
+            return self.draw_map_far(window, camera, offset_px);
+        }
+        let selected_area = self.selected_area();
+        let cursor = self.selection().map(|selection| (selection.cursor, selection.z));
+        // the room picked in the list of rooms is highlighted like a selection
+        let picked_room = match self.modes.last() {
+            Some(Mode::Rooms(reading)) => self.sim.rooms.rooms().get(reading.cursor).cloned(),
+            _ => None,
+        };
+        // whatever's lying about is drawn under whoever stands on it
+        let lying: HashSet<EntityId> = self.sim.entities.iter()
+            .filter(|&(id, entity)| entity.depth == camera.z_position && self.sim.is_lying_about(id))
+            .map(|(id, _)| id)
+            .collect();
+
+        // the level and what's built on it darken with depth, whoever's on
+        // it and the markers over it don't
+        let shade = self.config.depth_shading.shade(camera.z_position, self.sim.map.size().2);
+
+        // without a tileset the map is still drawn, as colored tiles
+        let glyphs = self.assets.for_zoom(camera.zoom_factor);
+        let (map, entities, support, designations, railway) = (
+            &mut self.sim.map, 
+            &self.sim.entities,
+            &self.sim.support,
+            &self.sim.designations,
+            &self.sim.railway,
+        );
+        let (crops, seeds, pumps, fires) = (&self.sim.crops, &self.sim.seeds, &self.sim.pumps, &self.sim.fires);
+        let sunlit = map.is_sunlit(camera.z_position);
+        let weather_tint = self.sim.weather.weather.tint().filter(|_| sunlit);
+        let overlays = &self.overlays;
+        let overlay = self.overlay.and_then(|index| overlays.get(index));
+        let overlay_context = OverlayContext {
+            designations,
+            support,
+            level_thickness: map.level_thickness,
+        };
+        
+        let tile_size_px = self.tile_size_px * camera.zoom_factor;
+        // the glyph images are render_scale times the tile size already
+        let glyph_scale = camera.zoom_factor / glyphs.scale;
+        let tile_center_px = offset_px + self.tile_size_px / 2.0;
+
+        let (camera_x, camera_y, camera_z) = (
+            (camera.viewport.x()) as u32, 
+            (camera.viewport.y()) as u32, 
+            camera.z_position,
+        );       
+        let camera_size_x = camera.viewport.width();
+        let camera_size_y = camera.viewport.height();
+        
+        let color_scheme = &self.color_scheme;
+        let shape_cues = self.config.shape_cues;
+        let smooth_terrain = self.config.smooth_terrain;
+        let elapsed_ms = self.animation_clock.elapsed_ms();
+        let (deep_water, shallow_water) = (
+            Renderable::water(ColorName::Blue),
+            Renderable::water(ColorName::LightBlue),
+        );
+        
+
+        let wall_glyphs = if camera.zoom_factor > 0.5 {
+            map.connected_wall_glyphs(
+                camera_x, camera_y, 
+                camera_size_x as u32, camera_size_y as u32, 
+                camera_z)
+        } else {
+            Vec::new()
+        };
+
+        for column in 0..camera_size_x as u32 {
+            // the view may run past the east edge and wrap around
+            let x = map.wrap_x(camera_x as i64 + column as i64);
+            for y in camera_y..camera_y + camera_size_y as u32 {
+                let tile = map.get_tile(x, y, camera_z);
+                let pos_px = Vector::new(column, y - camera_y)
+                    .times(tile_size_px);
+                let tile_color = tile_color(color_scheme, &tile, shade, smooth_terrain);
+                if camera.zoom_factor > 0.5 {
+                    let i = (column + (y - camera_y) * camera_size_x as u32) as usize;
+                    // markers shown as colored overlays get a glyph of their
+                    // own as well, for players who can't tell the colors apart
+                    let cue = if !shape_cues {
+                        None
+                    } else if support.is_pending(x, y, camera_z) {
+                        Some('*')
+                    } else if tile.gas > 0 {
+                        Some('▒')
+                    } else if designations.is_dig(x, y, camera_z) {
+                        Some('∆')
+                    } else if designations.is_stockpile(x, y, camera_z) {
+                        Some('░')
+                    } else if designations.is_pen(x, y, camera_z) {
+                        Some('▓')
+                    } else if designations.is_refuse(x, y, camera_z) {
+                        Some('‧')
+                    } else if designations.is_farm(x, y, camera_z) && !crops.contains_key(&(x, y, camera_z)) {
+                        Some('"')
+                    } else if designations.build_at(x, y, camera_z).is_some() {
+                        Some('+')
+                    } else {
+                        None
+                    };
+                    let track = railway.track_at(x, y, camera_z);
+                    let track_glyph = railway.glyph(map, x, y, camera_z);
+                    // seedlings all look alike, ripe crops look like what they are
+                    let fixture = if fires.is_burning(x, y, camera_z) {
+                        // fires flicker between orange and red
+                        let flicker = ((elapsed_ms + animation::phase_ms(x, y)) / 150).is_multiple_of(2);
+                        Some(('^', if flicker { ColorName::Orange } else { ColorName::Red }))
+                    } else if pumps.contains(&(x, y, camera_z)) {
+                        Some(('¤', ColorName::LightAqua))
+                    } else {
+                        crops.get(&(x, y, camera_z)).map(|crop| {
+                            match seeds.seed(&crop.kind).filter(|seed| crop.is_ripe(&seed.crop)) {
+                                Some(seed) => (seed.crop.glyph, seed.crop.color),
+                                None => (',', ColorName::Green),
+                            }
+                        })
+                    };
+                    // water shimmers unless something else is shown on it
+                    let water = if tile.fluid == 0 || cue.is_some() || track.is_some() || fixture.is_some() {
+                        None
+                    } else if tile.color == ColorName::Blue {
+                        Some(&deep_water)
+                    } else {
+                        Some(&shallow_water)
+                    };
+                    let shimmer = water.and_then(
+                        |water| water.frame(elapsed_ms + animation::phase_ms(x, y)));
+                    let glyph_color = match (fixture, track, shimmer) {
+                        (Some((_, color)), _, _) if cue.is_none() => color_scheme.shaded(&color, shade),
+                        (_, Some(track), _) if cue.is_none() => color_scheme.shaded(&track.color(), shade),
+                        (_, _, Some(frame)) => color_scheme.shaded(&frame.color, shade),
+                        _ => tile_color,
+                    };
+                    let glyph = cue.or(fixture.map(|(glyph, _)| glyph)).or(track_glyph)
+                        .or(wall_glyphs[i]).unwrap_or(tile.glyph);
+                    let layer = if water.is_some() { RenderLayer::Fluids } else { RenderLayer::Terrain };
+                    if let Some(image) = glyphs.get(glyph) {
+                        window.draw_ex(
+                            &Rectangle::new_sized(image.area().size())
+                                .with_center(tile_center_px + pos_px),
+                            Blended(image, glyph_color),
+                            Transform::scale((glyph_scale, glyph_scale)),
+                            layer.z()
+                        );
+                    }
+                }
+                else {
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            tile_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Terrain.z()
+                        );
+
+                }
+
+                if support.is_pending(x, y, camera_z) {
+                    let warning_color = color_scheme.color(&ColorName::Red)
+                        .with_alpha(0.35);
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            warning_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Overlay.z()
+                        );
+                }
+
+                let designation_color = if designations.is_dig(x, y, camera_z) {
+                    Some(color_scheme.color(&ColorName::Yellow)
+                        .with_alpha(0.3))
+                } else if designations.is_stockpile(x, y, camera_z) {
+                    Some(color_scheme.color(&ColorName::Aqua)
+                        .with_alpha(0.25))
+                } else if designations.is_pen(x, y, camera_z) {
+                    Some(color_scheme.color(&ColorName::Orange)
+                        .with_alpha(0.25))
+                } else if designations.is_refuse(x, y, camera_z) {
+                    Some(color_scheme.color(&ColorName::Purple)
+                        .with_alpha(0.25))
+                } else if designations.is_farm(x, y, camera_z) {
+                    Some(color_scheme.color(&ColorName::Green)
+                        .with_alpha(0.25))
+                } else if designations.build_at(x, y, camera_z).is_some() {
+                    Some(color_scheme.color(&ColorName::LightAqua)
+                        .with_alpha(0.3))
+                } else {
+                    None
+                };
+                if let Some(designation_color) = designation_color {
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            designation_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Overlay.z()
+                        );
+                }
+
+                if selected_area.is_some_and(|area| area.contains(x, y, camera_z))
+                   || picked_room.as_ref().is_some_and(|room| room.contains(x, y, camera_z)) {
+                    let selection_color = color_scheme.color(&ColorName::LightYellow)
+                        .with_alpha(0.4);
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            selection_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Overlay.z()
+                        );
+                }
+
+                if let Some((tint, strength)) = weather_tint {
+                    let weather_color = color_scheme.color(&tint)
+                        .with_alpha(strength);
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            weather_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Effects.z()
+                        );
+                }
+
+                if fires.is_smoky(map, x, y, camera_z) {
+                    let smoke_color = color_scheme.color(&ColorName::Gray)
+                        .with_alpha(0.4);
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            smoke_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Effects.z()
+                        );
+                }
+
+                if tile.gas > 0 {
+                    let gas_color = color_scheme.color(&ColorName::LightGreen)
+                        .with_alpha(0.08 * tile.gas as f32);
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            gas_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Fluids.z()
+                        );
+                }
+
+                let tint = overlay.and_then(|overlay| {
+                    overlay.tint(&overlay_context, &tile, x, y, camera_z)
+                });
+                if let Some((tint_color, strength)) = tint {
+                    let overlay_color = color_scheme.color(&tint_color)
+                        .with_alpha(0.15 + 0.6 * strength);
+                    window.draw_ex(
+                            &Rectangle::new(
+                                offset_px + pos_px, self.tile_size_px
+                            ),
+                            overlay_color,
+                            Transform::scale(
+                                (camera.zoom_factor, camera.zoom_factor)),
+                            RenderLayer::Overlay.z()
+                        );
+                }
+            }
+        }
+
+        // a dotted line along every ordered move, with a bigger dot at the end
+        let path_color = color_scheme.color(&ColorName::LightYellow)
+            .with_alpha(0.7);
+        for (id, path) in self.sim.paths.iter() {
+            if entities.get(*id).map(|entity| entity.depth) != Some(camera_z) {
+                continue;
+            }
+            for (step, &(x, y)) in path.iter().enumerate() {
+                let column = map.column_from(x, camera_x);
+                if column >= camera_size_x as u32 
+                   || y < camera_y || y >= camera_y + camera_size_y as u32 {
+                    continue;
+                }
+                let pos_px = Vector::new(column, y - camera_y)
+                    .times(tile_size_px);
+                let dot_size = if step + 1 == path.len() { 0.5 } else { 0.2 };
+                window.draw_ex(
+                    &Rectangle::new_sized(self.tile_size_px * dot_size)
+                        .with_center(tile_center_px + pos_px),
+                    Col(path_color),
+                    Transform::scale((camera.zoom_factor, camera.zoom_factor)),
+                    RenderLayer::Overlay.z()
+                );
+            }
+        }
+
+        // a red disc on the rally point
+        if let Some((x, y, _)) = designations.rally_point().filter(|&(_, _, z)| z == camera_z) {
+            let column = map.column_from(x, camera_x);
+            if column < camera_size_x as u32 && y >= camera_y && y < camera_y + camera_size_y as u32 {
+                let rally_color = color_scheme.color(&ColorName::LightRed);
+                let center = tile_center_px + Vector::new(column, y - camera_y).times(tile_size_px);
+                window.draw_ex(
+                    &Circle::new(center, tile_size_px.x * 0.45),
+                    Col(rally_color.with_alpha(0.6)),
+                    Transform::IDENTITY,
+                    RenderLayer::Overlay.z()
+                );
+            }
+        }
+
+        // the keyboard cursor is framed so it stands out from the area picked
+        if let Some(((x, y), _)) = cursor.filter(|&(_, z)| z == camera_z) {
+            let column = map.column_from(x, camera_x);
+            if column < camera_size_x as u32 && y >= camera_y && y < camera_y + camera_size_y as u32 {
+                let cursor_color = color_scheme.color(&ColorName::LightYellow);
+                let center = tile_center_px + Vector::new(column, y - camera_y).times(tile_size_px);
+                let cell = Rectangle::new_sized(tile_size_px).with_center(center);
+                let edge = (2.0 * self.config.ui_scale).max(1.0);
+                for side in outline(cell, edge).iter() {
+                    window.draw_ex(side, Col(cursor_color), Transform::IDENTITY, RenderLayer::Overlay.over());
+                }
+            }
+        }
+
+        // the selected colonist stands on a highlight when there's a choice
+        let squad_size = self.sim.squad.members().len();
+        let selected = self.player_id.and_then(|id| entities.get(id))
+            .filter(|colonist| squad_size > 1 && colonist.depth == camera_z);
+        if let Some(colonist) = selected {
+            let highlight = color_scheme.color(&ColorName::LightYellow)
+                .with_alpha(0.3);
+            for (x, y) in colonist.cells() {
+                let column = map.column_from(x, camera_x);
+                if column >= camera_size_x as u32 
+                   || y < camera_y || y >= camera_y + camera_size_y as u32 {
+                    continue;
+                }
+                let pos_px = Vector::new(column, y - camera_y)
+                    .times(tile_size_px);
+                window.draw_ex(
+                    &Rectangle::new(offset_px + pos_px, self.tile_size_px),
+                    Col(highlight),
+                    Transform::scale((camera.zoom_factor, camera.zoom_factor)),
+                    RenderLayer::Entities.z()
+                );
+            }
+        }
+
+        let afflictions = &self.sim.afflictions;
+        for (id, entity) in entities.iter() {
+            if entity.depth != camera_z {
+                continue;
+            }
+            let layer = if lying.contains(&id) {
+                RenderLayer::Items.z()
+            } else {
+                RenderLayer::Entities.over()
+            };
+            let (glyph, color) = entity.appearance(elapsed_ms);
+            let image = match glyphs.get(glyph) {
+                Some(image) => image,
+                None => continue,
+            };
+            let entity_color = color_scheme.color(&color);
+            // entities bigger than a tile show their glyph on every tile
+            // they cover
+            for (x, y) in entity.cells() {
+                let column = map.column_from(x, camera_x);
+                if column >= camera_size_x as u32 
+                   || y < camera_y || y >= camera_y + camera_size_y as u32 {
+                    continue;
+                }
+                let pos_px = Vector::new(column, y - camera_y)
+                    .times(tile_size_px);
+                window.draw_ex(
+                    &Rectangle::new_sized(image.area().size())
+                        .with_center(tile_center_px + pos_px),
+                    Blended(image, entity_color),
+                    Transform::scale((glyph_scale, glyph_scale)),
+                    layer
+                );
+            }
+
+            // what ails it goes down its top right corner in small glyphs
+            let statuses = afflictions.get(&id).into_iter().flat_map(|afflictions| afflictions.statuses());
+            let (x, y) = (entity.pos.x as u32 + entity.footprint.width - 1, entity.pos.y as u32);
+            let column = map.column_from(x, camera_x);
+            if column >= camera_size_x as u32 || y < camera_y || y >= camera_y + camera_size_y as u32 {
+                continue;
+            }
+            let corner_px = Vector::new(column, y - camera_y).times(tile_size_px)
+                + Vector::new(tile_size_px.x * 0.35, -tile_size_px.y * 0.35);
+            for (row, status) in statuses.enumerate() {
+                let image = match glyphs.get(status.glyph()) {
+                    Some(image) => image,
+                    None => continue,
+                };
+                let status_px = corner_px + Vector::new(0.0, tile_size_px.y * 0.3 * row as f32);
+                window.draw_ex(
+                    &Rectangle::new_sized(image.area().size())
+                        .with_center(tile_center_px + status_px),
+                    Blended(image, color_scheme.color(&status.color())),
+                    Transform::scale((glyph_scale * 0.4, glyph_scale * 0.4)),
+                    RenderLayer::Effects.z()
+                );
+            }
+        }
+
+        // notes are small flags in the top left corner of their tile
+        for &((x, y, z), _) in designations.notes() {
+            let column = map.column_from(x, camera_x);
+            if z != camera_z || column >= camera_size_x as u32 || y < camera_y || y >= camera_y + camera_size_y as u32 {
+                continue;
+            }
+            let image = match glyphs.get('¶') {
+                Some(image) => image,
+                None => break,
+            };
+            let flag_px = Vector::new(column, y - camera_y).times(tile_size_px)
+                + Vector::new(-tile_size_px.x * 0.3, -tile_size_px.y * 0.3);
+            window.draw_ex(
+                &Rectangle::new_sized(image.area().size())
+                    .with_center(tile_center_px + flag_px),
+                Blended(image, color_scheme.color(&ColorName::LightYellow)),
+                Transform::scale((glyph_scale * 0.5, glyph_scale * 0.5)),
+                RenderLayer::Overlay.over()
+            );
+        }
+
+        for particle in self.particles.iter() {
+            let column = map.column_from(particle.x, camera_x);
+            if particle.z != camera_z || column >= camera_size_x as u32 
+               || particle.y < camera_y || particle.y >= camera_y + camera_size_y as u32 {
+                continue;
+            }
+            let frame = match particle.frame(elapsed_ms) {
+                Some(frame) => frame,
+                None => continue,
+            };
+            let image = match glyphs.get(frame.glyph) {
+                Some(image) => image,
+                None => continue,
+            };
+            let pos_px = Vector::new(column, particle.y - camera_y)
+                .times(tile_size_px);
+            window.draw_ex(
+                &Rectangle::new_sized(image.area().size())
+                    .with_center(tile_center_px + pos_px),
+                Blended(image, color_scheme.color(&frame.color)),
+                Transform::scale((glyph_scale, glyph_scale)),
+                RenderLayer::Effects.z()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Draws the map zoomed far out as one quad per FAR_ZOOM_BLOCK sized
+    /// block, colored with the average of a few tiles sampled from it, so
+    /// far fewer quads are drawn than there are tiles in view
+    fn draw_map_far(&mut self, window: &mut Window, camera: &Camera, offset_px: Vector) -> Result<()> {
+        let (zoom, viewport, z) = (camera.zoom_factor, camera.viewport, camera.z_position);
+        let shade = self.config.depth_shading.shade(z, self.sim.map.size().2);
+        // match where draw_map puts tiles, which are scaled around their center
+        let origin = offset_px + self.tile_size_px * ((1.0 - zoom) / 2.0);
+        let tile_size_px = self.tile_size_px * zoom;
+
+        let (start_x, start_y) = (viewport.x() as u32, viewport.y() as u32);
+        let (end_x, end_y) = (
+            start_x + viewport.width() as u32, start_y + viewport.height() as u32);
+        // blocks are aligned to the world so they don't shimmer while panning
+        let first_x = start_x - start_x % FAR_ZOOM_BLOCK;
+        let first_y = start_y - start_y % FAR_ZOOM_BLOCK;
+        let sample_step = (FAR_ZOOM_BLOCK / 2).max(1) as usize;
+
+        for block_y in (first_y..end_y).step_by(FAR_ZOOM_BLOCK as usize) {
+            for block_x in (first_x..end_x).step_by(FAR_ZOOM_BLOCK as usize) {
+                // clip the blocks on the edges to the view
+                let (x0, y0) = (block_x.max(start_x), block_y.max(start_y));
+                let x1 = (block_x + FAR_ZOOM_BLOCK).min(end_x);
+                let y1 = (block_y + FAR_ZOOM_BLOCK).min(end_y);
+
+                // the overview only shows what someone has seen
+                let mut colors = Vec::new();
+                for y in (y0..y1).step_by(sample_step) {
+                    for x in (x0..x1).step_by(sample_step) {
+                        if !self.sim.map.is_explored(x, y, z) {
+                            continue;
+                        }
+                        let tile = self.sim.map.get_tile(x, y, z);
+                        colors.push(tile_color(&self.color_scheme, &tile, shade, self.config.smooth_terrain));
+                    }
+                }
+
+                let top_left = origin + Vector::new(
+                    (x0 - start_x) as f32 * tile_size_px.x,
+                    (y0 - start_y) as f32 * tile_size_px.y);
+                let size = Vector::new(
+                    (x1 - x0) as f32 * tile_size_px.x,
+                    (y1 - y0) as f32 * tile_size_px.y);
+                window.draw_ex(
+                    &Rectangle::new(top_left, size),
+                    Col(average_color(&colors)),
+                    Transform::IDENTITY,
+                    RenderLayer::Terrain.z()
+                );
+            }
+        }
+
+        for (id, entity) in self.sim.entities.iter() {
+            if entity.depth != z {
+                continue;
+            }
+            let layer = if self.sim.is_lying_about(id) {
+                RenderLayer::Items.z()
+            } else {
+                RenderLayer::Entities.over()
+            };
+            for (x, y) in entity.cells() {
+                // the view may run past the east edge and wrap around
+                let column = self.sim.map.column_from(x, start_x);
+                if column >= end_x - start_x || y < start_y || y >= end_y || !self.sim.map.is_explored(x, y, z) {
+                    continue;
+                }
+                let top_left = origin + Vector::new(
+                    column as f32 * tile_size_px.x,
+                    (y - start_y) as f32 * tile_size_px.y);
+                window.draw_ex(
+                    &Rectangle::new(top_left, tile_size_px),
+                    Col(self.color_scheme.color(&entity.color)),
+                    Transform::IDENTITY,
+                    layer
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws which command the selection is for and its size above the map
+    fn draw_selection(&mut self, window: &mut Window) -> Result<()> {
+        let selection = match self.selection() {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+        let area = selection.area();
+        let readout = match (&self.inspection, selection.anchor) {
+            (Some(inspection), None) => format!(
+                "{}: {} x {}  {}", 
+                selection.command.name(), area.width, area.height, inspection),
+            (_, None) => format!(
+                "{}: Return or drag to pick the first corner, Escape to stop", 
+                selection.command.name()),
+            (_, Some(_)) => format!(
+                "{}: {} x {} ({} tiles)", 
+                selection.command.name(), area.width, area.height, 
+                area.width * area.height),
+        };
+        let scale = self.config.ui_scale;
+        let style = TextStyle::new(
+            20.0 * scale, Color::from_hex(&self.color_scheme.light_yellow));
+        // just above the map
+        let panel = Panel::new(Anchor::TopLeft, RenderLayer::UiPanels)
+            .with_margin(self.map_offset_px() - Vector::new(0.0, 26.0 * scale))
+            .with_text(&readout, style);
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// The scenario's prompt, if one is being played
+    fn draw_tutorial(&mut self, window: &mut Window) -> Result<()> {
+        let tutorial = match &self.scenario {
+            Some(tutorial) => tutorial,
+            None => return Ok(()),
+        };
+        let color_scheme = &self.color_scheme;
+        let (scale, text_layout) = (self.config.ui_scale, &self.text_layout);
+        self.ui_font.execute(|font| {
+            tutorial.draw(window, font, text_layout, color_scheme, scale)
+        })
+    }
+
+    /// Draws the keys of the active mode along the bottom of the screen
+    fn draw_command_bar(&mut self, window: &mut Window) -> Result<()> {
+        let hints = match self.modes.last() {
+            Some(mode) => mode.hints(&self.locale),
+            None if self.config.overseer => String::from(
+                "Tab: commands  v: direct control  w a s d, arrows: scroll  [ ]: zoom  , .: level  o: overlay  F1: help  Esc: quit"),
+            None => String::from(
+                "Tab: commands  Ctrl+Tab: colonist  w a s d: move  arrows: scroll  [ ]: zoom  , .: level  o: overlay  F1: help  Esc: quit"),
+        };
+        let hints = match self.overlay.and_then(|index| self.overlays.get(index)) {
+            Some(overlay) => format!("{}  [{}]", hints, overlay.name()),
+            None => hints,
+        };
+        let scale = self.config.ui_scale;
+        let panel = Panel::new(Anchor::BottomLeft, RenderLayer::UiPanels)
+            .with_padding(3.0 * scale)
+            .with_background(Color::from_hex(&self.color_scheme.bg))
+            .stretched()
+            .with_text(&hints, TextStyle::new(18.0 * scale, Color::from_hex(&self.color_scheme.fg)));
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// Draws the key bindings in columns across the screen, as many pages
+    /// as it takes
+    fn draw_help(&mut self, window: &mut Window) -> Result<()> {
+        let scale = self.config.ui_scale;
+        let line_height = 22.0 * scale;
+        let padding = 20.0 * scale;
+        let area = Rectangle::new(
+            Vector::new(padding, padding * 3.0),
+            window.screen_size() - Vector::new(padding * 2.0, padding * 6.0));
+        let rows = (((area.height() - padding * 2.0) / line_height) as usize).max(2) - 1;
+        let column_width = (HELP_COLUMN_WIDTH * scale).min(area.width() - padding * 2.0);
+        let columns = (((area.width() - padding * 2.0) / column_width) as usize).max(1);
+
+        let lines = self.bindings.help_lines();
+        let per_page = rows * columns;
+        let pages = lines.len().div_ceil(per_page);
+        self.help_page = self.help_page.min(pages - 1);
+        let page_lines = &lines[self.help_page * per_page..];
+
+        let next = lines.iter()
+            .find(|line| line.ends_with(Action::HelpNextPage.description()))
+            .cloned()
+            .unwrap_or_default();
+        let title = format!("Keys, page {} of {}  ({})", self.help_page + 1, pages, next);
+        let title_style = FontStyle::new(
+            20.0 * scale, Color::from_hex(&self.color_scheme.light_yellow));
+        let style = FontStyle::new(18.0 * scale, Color::from_hex(&self.color_scheme.fg));
+        let background = Color::from_hex(&self.color_scheme.bg);
+
+        self.ui_font.execute(|font| {
+            window.draw_ex(&area, Col(background), Transform::IDENTITY, RenderLayer::UiTop.z());
+            let title = font.render(&title, &title_style)?;
+            window.draw_ex(
+                &title.area().translate(area.top_left() + Vector::new(padding, padding)),
+                Img(&title),
+                Transform::IDENTITY,
+                RenderLayer::UiTop.over()
+            );
+            for (index, line) in page_lines.iter().take(per_page).enumerate() {
+                let (column, row) = (index / rows, index % rows + 1);
+                let image = font.render(line, &style)?;
+                let pos = area.top_left() + Vector::new(
+                    padding + column as f32 * column_width,
+                    padding + row as f32 * line_height);
+                window.draw_ex(
+                    &image.area().translate(pos),
+                    Img(&image),
+                    Transform::IDENTITY,
+                    RenderLayer::UiTop.over()
+                );
+            }
+            Ok(())
+        })
+    }
+
+    fn draw_credits(&mut self, window: &mut Window) -> Result<()> {
+        let scale = self.config.ui_scale;
+        let line_height = 20.0 * scale;
+        let mut lines = Vec::new();
+        for fi in self.font_info.iter_mut() {
+            fi.execute(|image| {
+                lines.push(image.clone());
+                Ok(())
+            })?;
+        }
+        // the first line 90 pixels up from the bottom
+        let mut panel = Panel::new(Anchor::BottomLeft, RenderLayer::UiPanels)
+            .with_margin((2.0, 90.0 * scale - lines.len() as f32 * line_height))
+            .with_line_height(line_height);
+        for image in lines {
+            panel = panel.with_image(image);
+        }
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// Chunk borders labeled with chunk coordinates over the map, and a
+    /// line between every tile when the grid is on too
+    fn draw_chunk_grid(&mut self, window: &mut Window) -> Result<()> {
+        let zoom = self.camera.zoom_factor;
+        // match where draw_map puts tiles, which are scaled around their center
+        let origin = self.map_offset_px() 
+            + self.tile_size_px * ((1.0 - zoom) / 2.0);
+        let tile_size_px = self.tile_size_px * zoom;
+        let scale = self.config.ui_scale;
+
+        let viewport = self.camera.viewport;
+        let (columns, rows) = (viewport.width() as u32, viewport.height() as u32);
+        let view_size_px = Vector::new(
+            columns as f32 * tile_size_px.x, rows as f32 * tile_size_px.y);
+        let (max_x, max_y, _) = self.sim.map.size();
+        let chunk_size = self.sim.map.chunk_size;
+        let chunk_columns = chunk_grid::chunks_in_view(
+            viewport.x() as u32, columns, chunk_size, max_x);
+        let chunk_rows = chunk_grid::chunks_in_view(
+            viewport.y() as u32, rows, chunk_size, max_y);
+        let chunk_z = self.camera.z_position / chunk_size;
+
+        let chunk_color = Color::from_hex(
+            self.color_scheme.get_color_code(&ColorName::LightRed));
+        let tile_color = Color::from_hex(&self.color_scheme.fg).with_alpha(0.25);
+        let mut lines = Vec::new();
+        // below a few pixels per tile the grid would cover the map
+        if self.chunk_grid == ChunkGrid::Tiles && tile_size_px.x >= 4.0 {
+            for column in 1..columns {
+                let x = origin.x + column as f32 * tile_size_px.x;
+                lines.push((Rectangle::new((x, origin.y), (1.0, view_size_px.y)), tile_color));
+            }
+            for row in 1..rows {
+                let y = origin.y + row as f32 * tile_size_px.y;
+                lines.push((Rectangle::new((origin.x, y), (view_size_px.x, 1.0)), tile_color));
+            }
+        }
+        let thickness = 2.0 * scale;
+        for (column, _) in chunk_columns.iter().filter(|(column, _)| *column > 0) {
+            let x = origin.x + *column as f32 * tile_size_px.x - thickness / 2.0;
+            lines.push((Rectangle::new((x, origin.y), (thickness, view_size_px.y)), chunk_color));
+        }
+        for (row, _) in chunk_rows.iter().filter(|(row, _)| *row > 0) {
+            let y = origin.y + *row as f32 * tile_size_px.y - thickness / 2.0;
+            lines.push((Rectangle::new((origin.x, y), (view_size_px.x, thickness)), chunk_color));
+        }
+        for (line, color) in lines {
+            window.draw_ex(&line, Col(color), Transform::IDENTITY, RenderLayer::Overlay.z());
+        }
+
+        // each chunk's coordinates in its top left corner, or in the corner
+        // of the view for the ones that start off screen
+        let mut labels = Vec::new();
+        for (row, chunk_y) in chunk_rows.iter() {
+            for (column, chunk_x) in chunk_columns.iter() {
+                let pos = origin + Vector::new(
+                    *column as f32 * tile_size_px.x, *row as f32 * tile_size_px.y)
+                    + Vector::new(4.0, 2.0) * scale;
+                labels.push((pos, format!("{}, {}, {}", chunk_x, chunk_y, chunk_z)));
+            }
+        }
+        let style = FontStyle::new(14.0 * scale, chunk_color);
+        self.ui_font.execute(|font| {
+            for (pos, label) in labels.iter() {
+                let image = font.render(label, &style)?;
+                window.draw_ex(&image.area().translate(*pos), Img(&image), Transform::IDENTITY, RenderLayer::Overlay.over());
+            }
+            Ok(())
+        })
+    }
+
+    /// Draws an arrow on the edge of the view with the keyboard toward each
+    /// alert, flashing when it's new
+    fn draw_alerts(&mut self, window: &mut Window) {
+        let zoom = self.camera.zoom_factor;
+        let tile_size_px = self.tile_size_px * zoom;
+        let viewport = self.camera.viewport;
+        let view_size_px = Vector::new(viewport.width() * tile_size_px.x, viewport.height() * tile_size_px.y);
+        // match where draw_map puts tiles, which are scaled around their center
+        let center_px = self.map_offset_px() + self.tile_size_px * ((1.0 - zoom) / 2.0) + view_size_px / 2.0;
+        let size = 18.0 * self.config.ui_scale;
+        let view = self.alert_view();
+        let now_ms = self.animation_clock.elapsed_ms();
+        for alert in self.alerts.iter() {
+            let (dx, dy) = view.offset(alert.x, alert.y);
+            let (dx, dy) = (dx * tile_size_px.x, dy * tile_size_px.y);
+            let (x, y) = offscreen::edge_point(dx, dy, view_size_px.x, view_size_px.y, size);
+            let [tip, left, right] = offscreen::arrow((center_px.x + x, center_px.y + y), dx, dy, size);
+            let color = self.color_scheme.color(&alert.alarm.color()).with_alpha(alert.alpha(now_ms));
+            window.draw_ex(&Triangle::new(tip, left, right), Col(color), Transform::IDENTITY, RenderLayer::Overlay.over());
+        }
+    }
+
+    /// The selected colonist's health, what they carry, how skilled they
+    /// are and what ails them, in the top left under the title
+    fn draw_details(&mut self, window: &mut Window) -> Result<()> {
+        let (id, colonist) = match self.player_id.zip(self.player()) {
+            Some(player) => player,
+            None => return Ok(()),
+        };
+        let squad = &self.sim.squad;
+        let gear = |slot: Option<&Gear>| match slot {
+            Some(Gear { name, uses: Some(uses), .. }) => format!("{} ({} uses)", name, uses),
+            Some(gear) => gear.name.clone(),
+            None => String::from("none"),
+        };
+        let equipment = squad.equipment(id);
+        let mut lines = vec![
+            format!("{}  hp {}/{}", squad.name(id).unwrap_or("Colonist"), colonist.hp, colonist.max_hp),
+            format!("Tool: {}", gear(equipment.and_then(|equipment| equipment.tool.as_ref()))),
+            format!("Armor: {}", gear(equipment.and_then(|equipment| equipment.armor.as_ref()))),
+        ];
+        let skills = squad.skills(id).cloned().unwrap_or_default();
+        lines.extend(Skill::ALL.iter().map(|&skill| format!(
+            "{} {} ({} xp)", skill.name(), skills.level(skill), skills.experience(skill))));
+        if let Some(afflictions) = self.sim.afflictions.get(&id) {
+            let statuses: Vec<&str> = afflictions.statuses().map(|status| status.name()).collect();
+            lines.push(format!("Ailing: {}", statuses.join(", ")));
+        }
+
+        let scale = self.config.ui_scale;
+        let panel = self.text_panel(Anchor::TopLeft, RenderLayer::UiPanels, &lines)
+            .with_margin((10.0 * scale, 110.0 * scale));
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// The caravan's goods with what the traders ask for each and the
+    /// colony's with what they're worth, in the middle of the screen
+    fn draw_trade(&mut self, window: &mut Window) -> Result<()> {
+        let trading = match self.modes.last() {
+            Some(Mode::Trade(trading)) => trading,
+            _ => return Ok(()),
+        };
+        let (theirs, yours) = self.trade_goods();
+        let economy = &self.sim.economy;
+        let name = |id: &str| economy.item(id).map_or_else(|| String::from(id), |item| item.name.clone());
+        let row = |index: usize, picked: bool, text: String| format!(
+            "{} [{}] {}", if index == trading.cursor { ">" } else { " " }, if picked { "x" } else { " " }, text);
+
+        let mut lines = vec![String::from("The caravan's goods, and what they ask")];
+        lines.extend(theirs.iter().enumerate().map(|(index, id)| row(
+            index, trading.deal.buy.contains(&index), format!("{}  {}", name(id), economy.price(id)))));
+        lines.push(String::from("Your goods in stockpiles, and what they're worth"));
+        lines.extend(yours.iter().enumerate().map(|(index, (entity, id))| row(
+            theirs.len() + index, trading.deal.sell.contains(entity), format!("{}  {}", name(id), economy.value(id)))));
+        let asking: u32 = trading.deal.buy.iter().filter_map(|&index| theirs.get(index)).map(|id| economy.price(id)).sum();
+        let offered: u32 = yours.iter()
+            .filter(|(entity, _)| trading.deal.sell.contains(entity))
+            .map(|(_, id)| economy.value(id))
+            .sum();
+        lines.push(format!("Offered {} for {}", offered, asking));
+        self.draw_panel(window, &lines)
+    }
+
+    /// Every note left on the map with where it is, the cursor's marked
+    fn draw_notes(&mut self, window: &mut Window) -> Result<()> {
+        let reading = match self.modes.last() {
+            Some(Mode::Notes(reading)) => reading,
+            _ => return Ok(()),
+        };
+        let notes = self.sim.designations.notes();
+        let mut lines = vec![String::from("Notes")];
+        if notes.is_empty() {
+            lines.push(String::from("No notes yet, N leaves one on the tile under the mouse"));
+        }
+        let first = reading.cursor.saturating_sub(CHRONICLE_ROWS / 2)
+            .min(notes.len().saturating_sub(CHRONICLE_ROWS));
+        lines.extend(notes.iter().enumerate().skip(first).take(CHRONICLE_ROWS).map(|(index, ((x, y, z), text))| format!(
+            "{} {}  ({}, {}, {})", if index == reading.cursor { ">" } else { " " }, text, x, y, z)));
+        self.draw_panel(window, &lines)
+    }
+
+    /// The note on the tile under the mouse, beside the mouse
+    fn draw_hovered_note(&mut self, window: &mut Window) -> Result<()> {
+        let mouse = window.mouse().pos();
+        let z = self.camera.z_position;
+        let text = match self.screen_to_tile(mouse) {
+            Some((x, y)) => match self.sim.designations.note_at(x, y, z) {
+                Some(text) => text,
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        let scale = self.config.ui_scale;
+        let style = TextStyle::new(16.0 * scale, Color::from_hex(&self.color_scheme.fg));
+        // long notes wrap, and notes too wide for the right of the mouse
+        // go on its left
+        let (padding, max_width) = (4.0 * scale, NOTE_WIDTH * scale);
+        let width = self.text_layout.measure(text, style.size).min(max_width) + padding * 2.0;
+        let mut pos = mouse + Vector::new(12.0, 12.0) * scale;
+        if pos.x + width > window.screen_size().x {
+            pos.x = (mouse.x - 12.0 * scale - width).max(0.0);
+        }
+        let panel = Panel::new(Anchor::TopLeft, RenderLayer::UiPanels)
+            .with_margin(pos)
+            .with_padding(padding)
+            .with_max_width(max_width)
+            .with_background(Color::from_hex(&self.color_scheme.bg))
+            .with_text(text, style);
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// Every walled in room with its type, size and quality, the cursor's
+    /// marked
+    fn draw_rooms(&mut self, window: &mut Window) -> Result<()> {
+        let reading = match self.modes.last() {
+            Some(Mode::Rooms(reading)) => reading,
+            _ => return Ok(()),
+        };
+        let rooms = self.sim.rooms.rooms();
+        let mut lines = vec![String::from("Rooms")];
+        if rooms.is_empty() {
+            lines.push(String::from("No rooms yet, dig out a space and leave rock all round it"));
+        }
+        let first = reading.cursor.saturating_sub(CHRONICLE_ROWS / 2)
+            .min(rooms.len().saturating_sub(CHRONICLE_ROWS));
+        lines.extend(rooms.iter().enumerate().skip(first).take(CHRONICLE_ROWS).map(|(index, room)| {
+            let (x, y) = room.tiles[0];
+            let kind = room.kind.map_or("room", |kind| kind.name());
+            let unmet = room.unmet.as_ref().map(|unmet| format!(", {}", unmet)).unwrap_or_default();
+            format!("{} {}, {} tiles, {} ({}){}  ({}, {}, {})",
+                    if index == reading.cursor { ">" } else { " " },
+                    kind, room.size(), room.quality_name(), room.quality, unmet, x, y, room.z)
+        }));
+        self.draw_panel(window, &lines)
+    }
+
+    /// The entries of the colony's history around the cursor, in the
+    /// middle of the screen
+    fn draw_chronicle(&mut self, window: &mut Window) -> Result<()> {
+        let reading = match self.modes.last() {
+            Some(Mode::Chronicle(reading)) => reading,
+            _ => return Ok(()),
+        };
+        let (lines, _) = self.chronicle_lines(reading.cursor);
+        self.draw_panel(window, &lines)
+    }
+
+    /// The lines of the chronicle shown around the cursor, and the index of
+    /// the first entry shown
+    fn chronicle_lines(&self, cursor: usize) -> (Vec<String>, usize) {
+        let entries = self.chronicle.entries();
+        let mut lines = vec![format!("The history of {}", self.sim.name)];
+        if entries.is_empty() {
+            lines.push(String::from("Nothing worth remembering has happened yet"));
+        }
+        let first = cursor.saturating_sub(CHRONICLE_ROWS / 2)
+            .min(entries.len().saturating_sub(CHRONICLE_ROWS));
+        lines.extend(entries.iter().enumerate().skip(first).take(CHRONICLE_ROWS).map(|(index, entry)| format!(
+            "{} tick {}  {}  ({}, {}, {})", if index == cursor { ">" } else { " " },
+            entry.tick, entry.text, entry.x, entry.y, entry.z)));
+        (lines, first)
+    }
+
+    /// Lines of text on a box in the middle of the screen
+    fn draw_panel(&mut self, window: &mut Window, lines: &[String]) -> Result<()> {
+        let panel = self.text_panel(Anchor::Center, RenderLayer::UiTop, lines);
+        self.draw_ui_panel(window, panel)
+    }
+
+    /// A panel of lines of text in the usual size and colors
+    fn text_panel(&self, anchor: Anchor, layer: RenderLayer, lines: &[String]) -> Panel {
+        let scale = self.config.ui_scale;
+        Panel::new(anchor, layer)
+            .with_padding(8.0 * scale)
+            .with_line_height(22.0 * scale)
+            .with_background(Color::from_hex(&self.color_scheme.bg))
+            .with_lines(lines, TextStyle::new(18.0 * scale, Color::from_hex(&self.color_scheme.fg)))
+    }
+
+    /// Draws the panel, its text in the UI font
+    fn draw_ui_panel(&mut self, window: &mut Window, panel: Panel) -> Result<()> {
+        let text_layout = &self.text_layout;
+        self.ui_font.execute(|font| panel.draw(window, font, text_layout))
+    }
+
+    fn draw_debug(&mut self, window: &mut Window) -> Result<()> {
+        let scale = self.config.ui_scale;
+        let mononoki_font_info_style = TextStyle::new(
+            20.0 * scale, Color::from_hex(&self.color_scheme.fg));
+
+        // frame timings in the top right, under the title
+        let timings = Panel::new(Anchor::TopRight, RenderLayer::UiPanels)
+            .with_margin((10.0 * scale, 60.0 * scale))
+            .with_text(&self.profiler.lines().join("\n"), mononoki_font_info_style);
+        self.draw_ui_panel(window, timings)?;
+
+        let entities = &self.sim.entities;
+        let player = match self.player_id.and_then(|id| entities.get(id)) {
+            Some(player) => player,
+            None => return Ok(()),
+        };
+        let tile = self.sim.map.get_tile(
+            player.pos.x as u32, player.pos.y as u32, self.camera.z_position);
+
+        let debug_string = format!("Player Pos: (x: {:?} y: {:?})  Tile: (Kind: {:?} Color: {:?} glyph: {:?} val: {:?} fluid: {:?})\n
+Camera Pos: (x: {:?} y: {:?} z: {:?}), Zoom Factor: {:?}, viewport size: {:?}, Water table: {:?}",
+                                   player.pos.x,
+                                   player.pos.y,
+                                   tile.kind,
+                                   tile.color,
+                                   tile.glyph,
+                                   tile.val,
+                                   tile.fluid,
+                                   self.camera.viewport.x(),
+                                   self.camera.viewport.y(),
                                    self.camera.z_position,
                                    self.camera.zoom_factor,
                                    self.camera.viewport.size(),
+                                   self.sim.map.water_table,
                                   );
         let mut debug_info = Asset::new(Font::load(FONT_MONONOKI).and_then(move |font| {
             font.render(
                 debug_string.as_str(),
-                &mononoki_font_info_style,
+                &mononoki_font_info_style.font_style(),
                 )
         }));
 
-        debug_info.execute(|image| {
-            window.draw(
-                &image
-                    .area()
-                    .translate((2, window.screen_size().y as i32 - 60)),
-                Img(&image),
-            );
+        let mut image = None;
+        debug_info.execute(|info| {
+            image = Some(info.clone());
             Ok(())
         })?;
-
-        Ok(())
+        let image = match image {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        // its top 90 pixels up from the bottom
+        let panel = Panel::new(Anchor::BottomLeft, RenderLayer::UiPanels)
+            .with_margin((2.0, 90.0 * scale - image.area().height()))
+            .with_image(image);
+        self.draw_ui_panel(window, panel)
     }
 
 }
 
 fn main() {
     std::env::set_var("WINIT_HIDPI_FACTOR", "1.0");
+    logging::init();
     let settings = Settings {
          scale: quicksilver::graphics::ImageScaleStrategy::Blur,
         ..Default::default()
@@ -562,118 +4367,139 @@ fn main() {
     run::<Game>("Janus 7 Mining Colony", Vector::new(1280, 720), settings);
 }
 
-#[derive(Clone, Debug, PartialEq)]
-struct Entity {
-    pos: Vector,
-    depth: u32,
-    glyph: char,
-    color: ColorName,
-    hp: i32,
-    max_hp: i32,
-}
-
-fn generate_entities(
-    initial_pos_x: u32, initial_pos_y: u32, initial_pos_z: u32) 
-    -> Vec<Entity> {
-    vec![
-        Entity {
-            pos: Vector::new(initial_pos_x + 27, initial_pos_y + 18),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Red,
-            hp: 1,
-            max_hp: 1,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 27, initial_pos_y + 19),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Green,
-            hp: 1,
-            max_hp: 1,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 27, initial_pos_y + 20),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Orange,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 28, initial_pos_y + 18),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Purple,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 28, initial_pos_y + 19),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Yellow,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 28, initial_pos_y + 20),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Aqua,
-            hp: 0,
-            max_hp: 0,
+/// Hosts or joins a network game if the command line asks to, joining
+/// brings back the host's colony
+fn start_network() -> GameResult<(Option<Session>, Option<SaveData>)> {
+    let args: Vec<String> = std::env::args().collect();
+    let network_error = |reason| GameError::Network { reason };
+    match net::parse_args(&args).map_err(network_error)? {
+        Some(Role::Host(port)) => {
+            let host = Host::listen(port).map_err(
+                |err| network_error(format!("could not host on port {}: {}", port, err)))?;
+            Ok((Some(Session::Host(host)), None))
         },
-        Entity {
-            pos: Vector::new(initial_pos_x + 29, initial_pos_y + 18),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Gray,
-            hp: 0,
-            max_hp: 0,
+        Some(Role::Join(address)) => {
+            let (client, world) = Client::join(&address).map_err(
+                |err| network_error(format!("could not join {}: {}", address, err)))?;
+            Ok((Some(Session::Client(client)), Some(world)))
         },
-        // ░▒▓∷•‧≈╠╬╣╔╗╚╝╦╩═║
-        Entity {
-            pos: Vector::new(initial_pos_x + 25, initial_pos_y + 19),
-            depth: initial_pos_z,
-            glyph: '║',
-            color: ColorName::Yellow,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 25, initial_pos_y + 18),
-            depth: initial_pos_z,
-            glyph: '║',
-            color: ColorName::Yellow,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 25, initial_pos_y + 17),
-            depth: initial_pos_z,
-            glyph: '╔',
-            color: ColorName::Yellow,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 26, initial_pos_y + 17),
-            depth: initial_pos_z,
-            glyph: '═',
-            color: ColorName::Yellow,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(initial_pos_x + 29, initial_pos_y + 19),
-            depth: initial_pos_z,
-            glyph: '@',
-            color: ColorName::Blue,
-            hp: 3,
-            max_hp: 5,
-        }
+        None => Ok((None, None)),
+    }
+}
+
+/// The world grown from the seed, its chunks kept on disk if the player
+/// turned that on
+fn world_map(config: &Config, seed: u32, difficulty: Difficulty) -> GameMap {
+    let map = GameMap::with_seed(seed).with_difficulty(difficulty)
+        .with_worldgen(WorldGenConfig::load_or_default());
+    if config.cache_chunks {
+        map.with_chunk_cache(chunk_cache::CACHE_DIR)
+    } else {
+        map
+    }
+}
+
+/// Who to steer in a colony started or loaded with saved at the helm, the
+/// first living colonist if that was nobody and nobody when overseeing
+fn steered_colonist(sim: &Simulation, saved: Option<EntityId>, overseer: bool) -> Option<EntityId> {
+    match saved {
+        _ if overseer => None,
+        Some(id) => Some(id),
+        None => sim.squad.next_after(None, &sim.entities),
+    }
+}
+
+/// A seed from the clock, for a new colony with none typed in
+fn random_seed() -> u32 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+    now.map_or(0, |time| time.subsec_nanos() ^ time.as_secs() as u32)
+}
+
+/// What the scenarios screen says about the selected scenario
+fn describe_scenario(scenario: Option<&Scenario>) -> Vec<String> {
+    let scenario = match scenario {
+        Some(scenario) => scenario,
+        None => return vec![String::from("Scenarios are read from the scenarios directory")],
+    };
+    let mut text = scenario.about.clone();
+    text.extend(scenario.victory.iter().map(|victory| format!("to win: {}", victory.describe())));
+    if let Some(difficulty) = scenario.difficulty {
+        text.push(format!("difficulty: {}", difficulty.name()));
+    }
+    text
+}
+
+/// Reads the seed typed into the new colony dialog, None when it's blank
+fn parse_seed(seed: &str) -> std::result::Result<Option<u32>, String> {
+    if seed.is_empty() {
+        return Ok(None);
+    }
+    seed.parse().map(Some).map_err(|_| format!("Seeds go up to {}:", u32::MAX))
+}
+
+/// The tile's color darkened by shade, blended along the ramp its noise
+/// value colored it from when smooth is on rather than banded
+fn tile_color(scheme: &ColorScheme, tile: &Tile, shade: f32, smooth: bool) -> Color {
+    match tile.ramp().filter(|_| smooth) {
+        Some(ramp) => ramp.with_blend(Blend::Smooth).shaded(scheme, tile.val, shade),
+        None => scheme.shaded(&tile.color, shade),
+    }
+}
+
+/// The four sides of a frame edge thick just inside the area
+fn outline(area: Rectangle, edge: f32) -> [Rectangle; 4] {
+    [
+        Rectangle::new(area.pos, (area.width(), edge)),
+        Rectangle::new(area.pos + Vector::new(0.0, area.height() - edge), (area.width(), edge)),
+        Rectangle::new(area.pos, (edge, area.height())),
+        Rectangle::new(area.pos + Vector::new(area.width() - edge, 0.0), (edge, area.height())),
     ]
 }
 
+/// Size of the map view in tiles at the UI scale
+fn viewport_tiles(scale: f32) -> (u32, u32) {
+    (
+        (VIEWPORT_TILES.0 / scale).round() as u32,
+        (VIEWPORT_TILES.1 / scale).round() as u32,
+    )
+}
+
+/// The colony's name, drawn across the top of the screen
+fn render_title(color_scheme: &ColorScheme, scale: f32, name: &str) -> Asset<Image> {
+    let title_style = FontStyle::new(72.0 * scale, Color::from_hex(&color_scheme.fg));
+    let name = String::from(name);
+    Asset::new(Font::load(FONT_MONONOKI).and_then(move |font| {
+        font.render(&name, &title_style)
+    }))
+}
+
+fn render_font_info(color_scheme: &ColorScheme, scale: f32) -> Vec<Asset<Image>> {
+    let mononoki_font_info_style = FontStyle::new(
+        20.0 * scale, Color::from_hex(&color_scheme.fg));
+    let square_font_info_style = FontStyle::new(
+        12.0 * scale, Color::from_hex(&color_scheme.fg));
+    let zodiac_square_font_info_style = FontStyle::new(
+        12.0 * scale, Color::from_hex(&color_scheme.fg));
+    vec! {
+        Asset::new(Font::load(FONT_MONONOKI).and_then(move |font| {
+            font.render(
+                "Mononoki font by Matthias Tellen, terms: SIL Open Font License 1.1",
+                &mononoki_font_info_style,
+                )
+        })),
+        Asset::new(Font::load(FONT_SQUARE).and_then(move |font| {
+            font.render(
+                "Square font by Wouter Van Oortmerssen, terms: CC BY 3.0",
+                &square_font_info_style,
+                )
+        })),
+        Asset::new(Font::load(FONT_ZODIAC_SQUARE).and_then(move |font| {
+            font.render(
+                "Zodiac Square font by Elementalist, terms: CC0",
+                &zodiac_square_font_info_style,
+                )
+        })),
+    }
+}
+
 
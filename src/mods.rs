@@ -0,0 +1,517 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::animation::Renderable;
+use crate::color_scheme::{ColorName, ColorScheme, Palette};
+use crate::entity::{Entity, Footprint, Movement};
+use crate::equipment::{Gear, ToolKind};
+
+/// Where content packs are discovered at startup, one directory per pack
+pub const MODS_DIR: &str = "mods";
+/// Optional list of pack names in mods/, one per line, in the order they
+/// load. Packs it doesn't list load after the listed ones, by name.
+const LOAD_ORDER_FILE: &str = "load_order.txt";
+
+/// Something a content pack can add, read from a json file in the pack
+/// holding an array of them
+pub trait Definition: DeserializeOwned {
+    /// The file in a pack the definitions are read from
+    const FILE: &'static str;
+    /// What the definition is called in the log
+    const KIND: &'static str;
+
+    fn id(&self) -> &str;
+}
+
+/// Not dug up or used by anything yet, packs can already define them
+#[allow(dead_code)]
+#[derive(Clone, Debug, Deserialize)]
+pub struct MaterialDef {
+    pub id: String,
+    pub name: String,
+    pub color: ColorName,
+    /// How long the material takes to dig out, rock is 1
+    #[serde(default = "default_hardness")]
+    pub hardness: u32,
+    /// Ticks things made of it feed a fire for, 0 if they don't burn
+    #[serde(default)]
+    pub flammability: u64,
+}
+
+fn default_hardness() -> u32 {
+    1
+}
+
+/// Not made or hauled by anything yet, scenarios can place them. Tools and
+/// armor can be picked up and worn by colonists, the rest traded.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemDef {
+    pub id: String,
+    pub name: String,
+    pub glyph: char,
+    pub color: ColorName,
+    /// Id of the material the item is made of, if any
+    #[serde(default)]
+    pub material: Option<String>,
+    /// What the item does as a tool, if it's one
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// Damage it takes off each hit when worn, armor has some
+    #[serde(default)]
+    pub armor: i32,
+    /// Uses before it wears out, None if it never does
+    #[serde(default)]
+    pub uses: Option<u32>,
+    /// What traders give for it
+    #[serde(default = "default_value")]
+    pub value: u32,
+    /// Ticks it keeps a creature fed for past the usual, 0 if it isn't food
+    #[serde(default)]
+    pub food: u64,
+    /// Ticks it feeds a fire for, 0 to go by what it's made of
+    #[serde(default)]
+    pub flammability: u64,
+}
+
+fn default_value() -> u32 {
+    1
+}
+
+impl ItemDef {
+
+    /// The item as something a colonist can carry, None if it isn't a tool
+    /// or armor
+    pub fn gear(&self) -> Option<Gear> {
+        if self.tool.is_none() && self.armor <= 0 {
+            return None;
+        }
+        Some(Gear {
+            name: self.name.clone(),
+            glyph: self.glyph,
+            color: self.color,
+            tool: self.tool,
+            armor: self.armor,
+            uses: self.uses,
+        })
+    }
+
+    /// The item lying at (x, y, z), scenery that can't be hurt
+    pub fn place(&self, x: u32, y: u32, z: u32) -> Entity {
+        Entity {
+            pos: quicksilver::geom::Vector::new(x, y),
+            depth: z,
+            glyph: self.glyph,
+            color: self.color,
+            hp: 0,
+            max_hp: 0,
+            gas_protection: true,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: false,
+            movement: Movement::WALKING,
+        }
+    }
+}
+
+/// Nothing crafts yet, packs can already define them
+#[allow(dead_code)]
+#[derive(Clone, Debug, Deserialize)]
+pub struct RecipeDef {
+    pub id: String,
+    pub name: String,
+    /// Item ids used up and how many of each
+    pub inputs: BTreeMap<String, u32>,
+    /// Item ids made and how many of each
+    pub outputs: BTreeMap<String, u32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CreatureDef {
+    pub id: String,
+    pub name: String,
+    pub glyph: char,
+    pub color: ColorName,
+    pub hp: i32,
+    #[serde(default)]
+    pub gas_protection: bool,
+    #[serde(default)]
+    pub footprint: Footprint,
+    /// Frames the creature cycles through instead of glyph and color
+    #[serde(default)]
+    pub renderable: Option<Renderable>,
+    #[serde(default)]
+    pub hostile: bool,
+    /// Whether it flies, lives in water or burrows, it walks if none
+    #[serde(default)]
+    pub movement: Movement,
+    /// What it gives kept in a pen, None if it can't be kept
+    #[serde(default)]
+    pub livestock: Option<LivestockDef>,
+    /// Drawn to corpses left lying about
+    #[serde(default)]
+    pub scavenger: bool,
+}
+
+/// What a creature kept in a pen gives and how often
+#[derive(Clone, Debug, Deserialize)]
+pub struct LivestockDef {
+    /// Id of the item it leaves lying about now and then, like eggs
+    pub produces: String,
+    /// Ticks between each one
+    #[serde(default = "default_produce_every")]
+    pub every: u64,
+    /// Ticks between litters, 0 if it doesn't breed
+    #[serde(default = "default_breed_every")]
+    pub breed_every: u64,
+}
+
+fn default_produce_every() -> u64 {
+    1800
+}
+
+fn default_breed_every() -> u64 {
+    6000
+}
+
+impl CreatureDef {
+
+    /// A new creature of this kind at (x, y, z)
+    pub fn spawn(&self, x: u32, y: u32, z: u32) -> Entity {
+        Entity {
+            pos: quicksilver::geom::Vector::new(x, y),
+            depth: z,
+            glyph: self.glyph,
+            color: self.color,
+            hp: self.hp,
+            max_hp: self.hp,
+            gas_protection: self.gas_protection,
+            footprint: self.footprint,
+            renderable: self.renderable.clone(),
+            hostile: self.hostile,
+            movement: self.movement,
+        }
+    }
+}
+
+/// A color scheme made from one of the presets with some of its colors
+/// replaced, colors are named like the fields of ColorScheme
+#[derive(Clone, Debug, Deserialize)]
+pub struct ColorSchemeDef {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub base: Option<Palette>,
+    #[serde(default)]
+    pub colors: HashMap<String, String>,
+}
+
+impl ColorSchemeDef {
+
+    pub fn to_color_scheme(&self) -> Result<ColorScheme, String> {
+        let base = ColorScheme::from_palette(self.base.unwrap_or(Palette::Gruvbox));
+        let mut value = serde_json::to_value(base).map_err(|err| err.to_string())?;
+        let fields = value.as_object_mut().ok_or("color scheme is not an object")?;
+        for (name, code) in &self.colors {
+            match fields.get_mut(name) {
+                Some(field) => *field = serde_json::Value::from(code.as_str()),
+                None => return Err(format!("unknown color {:?}", name)),
+            }
+        }
+        serde_json::from_value(value).map_err(|err| err.to_string())
+    }
+}
+
+/// Grown on farm plots for the item it gives
+#[derive(Clone, Debug, Deserialize)]
+pub struct CropDef {
+    pub id: String,
+    pub name: String,
+    /// Drawn on the plot once it's ripe, seedlings are all alike
+    pub glyph: char,
+    pub color: ColorName,
+    /// Id of the item harvested from it
+    pub produces: String,
+    /// Ticks it takes to ripen on a sunlit, watered plot. It takes twice
+    /// as long with only light or water.
+    pub grows: u64,
+    /// Items harvested from each tile of it
+    #[serde(default = "default_harvest")]
+    pub harvest: u32,
+    /// Grows in the dark, it's planted on plots underground
+    #[serde(default)]
+    pub underground: bool,
+}
+
+fn default_harvest() -> u32 {
+    1
+}
+
+impl Definition for MaterialDef {
+    const FILE: &'static str = "materials.json";
+    const KIND: &'static str = "material";
+    fn id(&self) -> &str { &self.id }
+}
+
+impl Definition for ItemDef {
+    const FILE: &'static str = "items.json";
+    const KIND: &'static str = "item";
+    fn id(&self) -> &str { &self.id }
+}
+
+impl Definition for RecipeDef {
+    const FILE: &'static str = "recipes.json";
+    const KIND: &'static str = "recipe";
+    fn id(&self) -> &str { &self.id }
+}
+
+impl Definition for CreatureDef {
+    const FILE: &'static str = "creatures.json";
+    const KIND: &'static str = "creature";
+    fn id(&self) -> &str { &self.id }
+}
+
+impl Definition for CropDef {
+    const FILE: &'static str = "crops.json";
+    const KIND: &'static str = "crop";
+    fn id(&self) -> &str { &self.id }
+}
+
+impl Definition for ColorSchemeDef {
+    const FILE: &'static str = "color_schemes.json";
+    const KIND: &'static str = "color scheme";
+    fn id(&self) -> &str { &self.id }
+}
+
+/// Definitions of one kind by id, with the pack each came from
+pub struct Registry<T> {
+    defs: BTreeMap<String, (T, String)>,
+}
+
+impl<T> Registry<T> {
+
+    fn new() -> Registry<T> {
+        Registry {
+            defs: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&T> {
+        self.defs.get(id).map(|(def, _)| def)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.defs.contains_key(id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.defs.keys()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.defs.values().map(|(def, _)| def)
+    }
+}
+
+impl<T: Definition> Registry<T> {
+
+    /// Reads the pack's file of these definitions, if it has one. Later
+    /// packs win when two define the same id, which is reported in the log.
+    fn load_pack(&mut self, pack: &str, dir: &Path, log: &mut Vec<String>) {
+        let path = dir.join(T::FILE);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return,
+        };
+        let defs: Vec<T> = match serde_json::from_str(&text) {
+            Ok(defs) => defs,
+            Err(err) => {
+                log.push(format!("error: {}: {}", path.display(), err));
+                return;
+            },
+        };
+        for def in defs {
+            let id = String::from(def.id());
+            if let Some((_, previous)) = self.defs.get(&id) {
+                let conflict = if previous == pack {
+                    format!("conflict: {} defines {} {:?} twice, keeping the last one",
+                            pack, T::KIND, id)
+                } else {
+                    format!("conflict: {} overrides {} {:?} from {}",
+                            pack, T::KIND, id, previous)
+                };
+                log.push(conflict);
+            }
+            self.defs.insert(id, (def, String::from(pack)));
+        }
+    }
+}
+
+/// Everything the content packs add, merged in load order
+pub struct Content {
+    /// Packs in the order they loaded
+    pub packs: Vec<String>,
+    pub materials: Registry<MaterialDef>,
+    pub items: Registry<ItemDef>,
+    pub recipes: Registry<RecipeDef>,
+    pub creatures: Registry<CreatureDef>,
+    pub crops: Registry<CropDef>,
+    pub color_schemes: Registry<ColorSchemeDef>,
+}
+
+impl Content {
+
+    pub fn new() -> Content {
+        Content {
+            packs: Vec::new(),
+            materials: Registry::new(),
+            items: Registry::new(),
+            recipes: Registry::new(),
+            creatures: Registry::new(),
+            crops: Registry::new(),
+            color_schemes: Registry::new(),
+        }
+    }
+
+    /// Loads every pack in dir, returning the log of what was loaded and
+    /// any errors and conflicts. A missing dir just means there are no mods.
+    pub fn load(dir: &Path) -> (Content, Vec<String>) {
+        let mut content = Content::new();
+        let mut log = Vec::new();
+        for pack in load_order(dir, &mut log) {
+            let pack_dir = dir.join(&pack);
+            content.materials.load_pack(&pack, &pack_dir, &mut log);
+            content.items.load_pack(&pack, &pack_dir, &mut log);
+            content.recipes.load_pack(&pack, &pack_dir, &mut log);
+            content.creatures.load_pack(&pack, &pack_dir, &mut log);
+            content.crops.load_pack(&pack, &pack_dir, &mut log);
+            content.color_schemes.load_pack(&pack, &pack_dir, &mut log);
+            log.push(format!("loaded mod {}", pack));
+            content.packs.push(pack);
+        }
+        log.extend(content.check_references());
+        (content, log)
+    }
+
+    /// Warnings for definitions naming ids nothing defines
+    fn check_references(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for item in self.items.iter() {
+            if let Some(material) = &item.material {
+                if !self.materials.contains(material) {
+                    warnings.push(format!("warning: item {:?} is made of unknown material {:?}",
+                                          item.id, material));
+                }
+            }
+        }
+        for recipe in self.recipes.iter() {
+            for item in recipe.inputs.keys().chain(recipe.outputs.keys()) {
+                if !self.items.contains(item) {
+                    warnings.push(format!("warning: recipe {:?} uses unknown item {:?}",
+                                          recipe.id, item));
+                }
+            }
+        }
+        for creature in self.creatures.iter() {
+            if let Some(livestock) = &creature.livestock {
+                if !self.items.contains(&livestock.produces) {
+                    warnings.push(format!("warning: creature {:?} produces unknown item {:?}",
+                                          creature.id, livestock.produces));
+                }
+            }
+        }
+        for crop in self.crops.iter() {
+            if !self.items.contains(&crop.produces) {
+                warnings.push(format!("warning: crop {:?} produces unknown item {:?}",
+                                      crop.id, crop.produces));
+            }
+        }
+        for scheme in self.color_schemes.iter() {
+            if let Err(err) = scheme.to_color_scheme() {
+                warnings.push(format!("warning: color scheme {:?}: {}", scheme.id, err));
+            }
+        }
+        warnings
+    }
+
+    /// Glyphs items, creatures and crops are drawn with, for the tileset
+    pub fn glyphs(&self) -> String {
+        let mut glyphs: Vec<char> = self.items.iter().map(|item| item.glyph)
+            .chain(self.creatures.iter().map(|creature| creature.glyph))
+            .chain(self.crops.iter().map(|crop| crop.glyph))
+            .collect();
+        glyphs.sort_unstable();
+        glyphs.dedup();
+        glyphs.into_iter().collect()
+    }
+}
+
+/// Pack directories in dir in the order they load
+fn load_order(dir: &Path, log: &mut Vec<String>) -> Vec<String> {
+    let mut found: Vec<String> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    found.sort();
+
+    let listed = fs::read_to_string(dir.join(LOAD_ORDER_FILE)).unwrap_or_default();
+    let mut order = Vec::new();
+    for line in listed.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || order.iter().any(|pack| pack == line) {
+            continue;
+        }
+        match found.iter().position(|pack| pack == line) {
+            Some(index) => order.push(found.remove(index)),
+            None => log.push(format!("warning: {} lists missing mod {}",
+                                     LOAD_ORDER_FILE, line)),
+        }
+    }
+    order.extend(found);
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_later_packs_override_earlier_ones() {
+        let dir = std::env::temp_dir().join("jmc_test_mods");
+        let _ = fs::remove_dir_all(&dir);
+        write(&dir.join("b_base/creatures.json"),
+              r#"[{"id": "beetle", "name": "Cave beetle", "glyph": "g", "color": "Green", "hp": 2}]"#);
+        write(&dir.join("a_tweaks/creatures.json"),
+              r#"[{"id": "beetle", "name": "Armored beetle", "glyph": "g", "color": "Yellow", "hp": 6}]"#);
+        write(&dir.join("a_tweaks/recipes.json"),
+              r#"[{"id": "smelt", "name": "Smelt", "inputs": {"ore": 2}, "outputs": {"ingot": 1}}]"#);
+        write(&dir.join("a_tweaks/color_schemes.json"),
+              r##"[{"id": "dusk", "name": "Dusk", "colors": {"bg": "#000000"}}]"##);
+        write(&dir.join(LOAD_ORDER_FILE), "b_base\na_tweaks\nmissing\n");
+
+        let (content, log) = Content::load(&dir);
+        println!("{:#?}", log);
+        assert_eq!(content.packs, vec!["b_base", "a_tweaks"]);
+        assert_eq!(content.creatures.get("beetle").map(|beetle| beetle.hp), Some(6));
+        assert!(log.iter().any(|line| line.starts_with("conflict: a_tweaks overrides")));
+        assert!(log.iter().any(|line| line.contains("missing mod missing")));
+        assert!(log.iter().any(|line| line.contains("unknown item \"ore\"")));
+        let dusk = content.color_schemes.get("dusk").unwrap().to_color_scheme().unwrap();
+        assert_eq!(dusk.bg, "#000000");
+        assert_eq!(dusk.fg, ColorScheme::gruvbox().fg);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,627 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::blueprint::Blueprint;
+use crate::desync::{Checksum, Desync};
+use crate::entity::EntityId;
+use crate::rail::Track;
+use crate::rooms::RoomType;
+use crate::save::SaveData;
+use crate::selection::{Area, AreaCommand};
+use crate::squad::Squad;
+
+pub const DEFAULT_PORT: u16 = 7878;
+/// The host, and the only player in a single player game
+pub const HOST_PEER: u32 = 0;
+
+/// How long joining waits for the host to send the world
+const JOIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many of its own checksums the host keeps to compare peers' against,
+/// a peer further behind than that isn't checked
+const CHECKSUMS_KEPT: usize = 16;
+/// How many messages may wait to go out to a peer that isn't taking them
+/// before it's dropped, thirty seconds of ticks
+const OUTGOING_KEPT: usize = 300;
+
+/// Something built in front of a colonist
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Build {
+    Pillar,
+    Track(Track),
+    /// A minecart heading away from the colonist
+    Minecart,
+    /// A pump lifting water from the level below, or taking down the one
+    /// already there
+    Pump,
+}
+
+/// Something players can give a name
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Named {
+    Colonist(EntityId),
+    /// The stockpile covering the tile
+    Stockpile { x: u32, y: u32, z: u32 },
+    /// The note left on the tile, naming it leaves one there
+    Note { x: u32, y: u32, z: u32 },
+}
+
+/// A player's order to the colony. In a network game these are the only
+/// way players change the world, every peer applies the same orders on the
+/// same tick so their simulations stay the same.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Order {
+    /// Steps the colonist a tile, digging out the rock instead if the way
+    /// is blocked
+    Step { colonist: EntityId, dx: i32, dy: i32 },
+    /// Walks the colonist to a tile on its level
+    MoveTo { colonist: EntityId, x: u32, y: u32 },
+    /// Cancels every move order
+    CancelMoves,
+    /// Designates or lays track over the area
+    Area { command: AreaCommand, area: Area },
+    /// Plans the blueprint's constructions and digs with its top left
+    /// corner on the tile
+    Stamp { blueprint: Blueprint, x: u32, y: u32, z: u32 },
+    /// Gives the walled in room around the tile the type, or takes its
+    /// type away
+    Room { x: u32, y: u32, z: u32, kind: Option<RoomType> },
+    /// Builds on the tile the colonist is facing
+    Build { colonist: EntityId, facing: (i32, i32), build: Build },
+    /// The colonist the player steers by hand, None when overseeing
+    Control { colonist: Option<EntityId> },
+    /// Names a colonist or stockpile or writes a note, an empty name
+    /// clears it
+    Name { target: Named, name: String },
+    /// Keeps the colonist to the burrow, or lets them go anywhere again
+    KeepToBurrow { colonist: EntityId, kept: bool },
+    /// Puts the colony on alert or stands it down
+    Alert { raised: bool },
+    /// Picks up the tool or armor next to the colonist, putting down what
+    /// they had in its slot
+    Equip { colonist: EntityId },
+    /// Trades the caravan's goods at the indices for the colony's items
+    /// lying in stockpiles
+    Trade { buy: Vec<usize>, sell: Vec<EntityId> },
+    /// Takes back the latest designation or track the peer ordered
+    Undo,
+}
+
+impl Order {
+
+    /// The colonist the order acts through, only the player steering them
+    /// may give it
+    pub fn colonist(&self) -> Option<EntityId> {
+        match self {
+            Order::Step { colonist, .. } | Order::MoveTo { colonist, .. } | Order::Build { colonist, .. }
+            | Order::KeepToBurrow { colonist, .. } | Order::Equip { colonist } => Some(*colonist),
+            _ => None,
+        }
+    }
+}
+
+/// The orders for a tick, each with the peer that gave it
+pub type Orders = Vec<(u32, Order)>;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ClientMessage {
+    Order(Order),
+    /// The peer's checksum of its simulation, every desync::CHECK_INTERVAL
+    /// ticks
+    Checksum(Checksum),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum HostMessage {
+    /// Sent once on joining, the colony as it is and the joining peer
+    Welcome { peer: u32, world: Box<SaveData> },
+    /// The orders given for a tick, applied before it runs
+    Tick { tick: u64, orders: Orders },
+}
+
+/// How the game was asked to start, from the command line
+#[derive(Clone, Debug, PartialEq)]
+pub enum Role {
+    Host(u16),
+    Join(String),
+}
+
+/// Reads "--host [port]" or "--join <address>" from the arguments
+pub fn parse_args(args: &[String]) -> Result<Option<Role>, String> {
+    let position = |flag| args.iter().position(|arg| arg == flag);
+    if let Some(index) = position("--host") {
+        return match args.get(index + 1).filter(|arg| !arg.starts_with("--")) {
+            Some(port) => port.parse().map(|port| Some(Role::Host(port)))
+                .map_err(|_| format!("{:?} is not a port", port)),
+            None => Ok(Some(Role::Host(DEFAULT_PORT))),
+        };
+    }
+    if let Some(index) = position("--join") {
+        let address = args.get(index + 1).ok_or("--join needs an address, like 127.0.0.1")?;
+        let address = if address.contains(':') {
+            address.clone()
+        } else {
+            format!("{}:{}", address, DEFAULT_PORT)
+        };
+        return Ok(Some(Role::Join(address)));
+    }
+    Ok(None)
+}
+
+/// Reads "--port <port>" from the server's arguments
+pub fn parse_port(args: &[String]) -> Result<u16, String> {
+    match args.iter().position(|arg| arg == "--port") {
+        Some(index) => {
+            let port = args.get(index + 1).ok_or("--port needs a number")?;
+            port.parse().map_err(|_| format!("{:?} is not a port", port))
+        },
+        None => Ok(DEFAULT_PORT),
+    }
+}
+
+/// The message as a line of json
+fn to_line<T: Serialize>(message: &T) -> io::Result<String> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Writes the lines sent down the channel to the stream on a thread of its
+/// own, so a peer slow to read holds up only that thread. The connection
+/// is shut once the channel closes or a write fails, and the channel the
+/// other way round.
+fn spawn_writer(mut stream: TcpStream) -> SyncSender<String> {
+    let (sender, receiver) = mpsc::sync_channel::<String>(OUTGOING_KEPT);
+    thread::spawn(move || {
+        for line in receiver {
+            if stream.write_all(line.as_bytes()).is_err() {
+                break;
+            }
+        }
+        let _ = stream.shutdown(Shutdown::Both);
+    });
+    sender
+}
+
+/// Reads lines of json off the stream on a thread of its own, the channel
+/// closes when the connection does
+fn spawn_reader<T: DeserializeOwned + Send + 'static>(stream: TcpStream) -> Receiver<T> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let message = match line.map(|line| serde_json::from_str(&line)) {
+                Ok(Ok(message)) => message,
+                Ok(Err(err)) => {
+                    log::warn!("dropping the connection, bad message: {}", err);
+                    break;
+                },
+                Err(_) => break,
+            };
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+struct Peer {
+    id: u32,
+    outgoing: SyncSender<String>,
+    incoming: Receiver<ClientMessage>,
+}
+
+impl Peer {
+
+    fn new(id: u32, stream: TcpStream) -> io::Result<Peer> {
+        let incoming = spawn_reader(stream.try_clone()?);
+        Ok(Peer { id, outgoing: spawn_writer(stream), incoming })
+    }
+
+    /// Queues the line to go out, false once the peer is gone or so far
+    /// behind it's as good as
+    fn send(&self, line: String) -> bool {
+        match self.outgoing.try_send(line) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                log::warn!("peer {} fell {} messages behind", self.id, OUTGOING_KEPT);
+                false
+            },
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+/// Runs the simulation for everyone, collecting orders from the peers and
+/// sending them back out with each tick
+pub struct Host {
+    listener: TcpListener,
+    peers: Vec<Peer>,
+    next_peer: u32,
+    /// Orders for the next tick, the host's own included
+    queued: Orders,
+    /// The host's latest checksums, oldest first
+    checksums: VecDeque<Checksum>,
+    desyncs: Vec<Desync>,
+    /// Peers already found out of sync, reported only the once
+    out_of_sync: BTreeSet<u32>,
+}
+
+impl Host {
+
+    pub fn listen(port: u16) -> io::Result<Host> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Host {
+            listener,
+            peers: Vec::new(),
+            next_peer: HOST_PEER + 1,
+            queued: Vec::new(),
+            checksums: VecDeque::new(),
+            desyncs: Vec::new(),
+            out_of_sync: BTreeSet::new(),
+        })
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.listener.local_addr().ok().map(|address| address.port())
+    }
+
+    /// Sends whoever connected since the last call the world as it is
+    /// now, returning the peers that joined
+    pub fn accept(&mut self, world: impl Fn() -> SaveData) -> Vec<u32> {
+        let mut joined = Vec::new();
+        while let Ok((stream, address)) = self.listener.accept() {
+            let id = self.next_peer;
+            let welcome = HostMessage::Welcome { peer: id, world: Box::new(world()) };
+            // the peer's own threads block on it, never the game
+            let connected = stream.set_nonblocking(false)
+                .and_then(|_| stream.set_nodelay(true))
+                .and_then(|_| to_line(&welcome))
+                .and_then(|welcome| Ok((Peer::new(id, stream)?, welcome)));
+            match connected {
+                Ok((peer, welcome)) => {
+                    log::info!("peer {} joined from {}", id, address);
+                    self.next_peer += 1;
+                    peer.send(welcome);
+                    self.peers.push(peer);
+                    joined.push(id);
+                },
+                Err(err) => log::warn!("could not welcome {}: {}", address, err),
+            }
+        }
+        joined
+    }
+
+    pub fn queue(&mut self, peer: u32, order: Order) {
+        self.queued.push((peer, order));
+    }
+
+    /// Queues the orders the peers sent, returning the peers that left.
+    /// Their colonists are let go on the next tick. Orders for a member of
+    /// the squad the peer isn't steering are turned away.
+    pub fn receive(&mut self, squad: &Squad) -> Vec<u32> {
+        let mut left = Vec::new();
+        for peer in self.peers.iter() {
+            loop {
+                match peer.incoming.try_recv() {
+                    Ok(ClientMessage::Order(order)) => match order.colonist() {
+                        Some(colonist) if squad.members().contains(&colonist)
+                            && steering(&self.queued, squad, peer.id) != Some(colonist) => {
+                            log::warn!("peer {} gave an order to colonist {:?}, who they don't steer",
+                                       peer.id, colonist);
+                        },
+                        _ => self.queued.push((peer.id, order)),
+                    },
+                    Ok(ClientMessage::Checksum(theirs)) => {
+                        let ours = self.checksums.iter().find(|ours| ours.tick == theirs.tick);
+                        let systems = ours.map(|ours| ours.diverged(&theirs)).unwrap_or_default();
+                        if !systems.is_empty() && self.out_of_sync.insert(peer.id) {
+                            log::warn!("peer {} out of sync at tick {}: {:?}", peer.id, theirs.tick, systems);
+                            self.desyncs.push(Desync { peer: peer.id, tick: theirs.tick, systems });
+                        }
+                    },
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        left.push(peer.id);
+                        break;
+                    },
+                }
+            }
+        }
+        self.drop_peers(&left);
+        left
+    }
+
+    /// Keeps the host's own checksum for the peers' to be compared against
+    pub fn record(&mut self, checksum: Checksum) {
+        self.checksums.push_back(checksum);
+        if self.checksums.len() > CHECKSUMS_KEPT {
+            self.checksums.pop_front();
+        }
+    }
+
+    /// The peers found out of sync since the last call
+    pub fn take_desyncs(&mut self) -> Vec<Desync> {
+        std::mem::take(&mut self.desyncs)
+    }
+
+    /// Takes the orders for the next tick, queueing them to go out to every
+    /// peer
+    pub fn tick(&mut self, tick: u64) -> Orders {
+        let orders = std::mem::take(&mut self.queued);
+        let message = HostMessage::Tick { tick, orders: orders.clone() };
+        let line = match to_line(&message) {
+            Ok(line) => line,
+            Err(err) => {
+                log::warn!("could not send tick {}: {}", tick, err);
+                return orders;
+            },
+        };
+        let left: Vec<u32> = self.peers.iter()
+            .filter(|peer| !peer.send(line.clone()))
+            .map(|peer| peer.id)
+            .collect();
+        self.drop_peers(&left);
+        orders
+    }
+
+    fn drop_peers(&mut self, left: &[u32]) {
+        for id in left {
+            log::info!("peer {} left", id);
+            self.queued.push((*id, Order::Control { colonist: None }));
+        }
+        self.peers.retain(|peer| !left.contains(&peer.id));
+        self.out_of_sync.retain(|id| !left.contains(id));
+    }
+}
+
+/// The colonist the peer steers once the orders already queued for the
+/// next tick are applied
+fn steering(queued: &Orders, squad: &Squad, peer: u32) -> Option<EntityId> {
+    queued.iter().rev()
+        .find_map(|(from, order)| match order {
+            Order::Control { colonist } if *from == peer => Some(*colonist),
+            _ => None,
+        })
+        .unwrap_or_else(|| squad.steered_by(peer))
+}
+
+/// A connection to a host, the simulation only moves when the host says
+pub struct Client {
+    pub peer: u32,
+    outgoing: SyncSender<String>,
+    incoming: Receiver<HostMessage>,
+}
+
+impl Client {
+
+    /// Connects to the host and waits for the colony
+    pub fn join(address: &str) -> io::Result<(Client, SaveData)> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let incoming = spawn_reader(stream.try_clone()?);
+        match incoming.recv_timeout(JOIN_TIMEOUT) {
+            Ok(HostMessage::Welcome { peer, world }) => {
+                log::info!("joined {} as peer {}", address, peer);
+                Ok((Client { peer, outgoing: spawn_writer(stream), incoming }, *world))
+            },
+            Ok(message) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("expected the colony, got {:?}", message))),
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "the host never sent the colony")),
+        }
+    }
+
+    pub fn send(&self, order: Order) -> io::Result<()> {
+        self.queue(&ClientMessage::Order(order))
+    }
+
+    pub fn send_checksum(&self, checksum: Checksum) -> io::Result<()> {
+        self.queue(&ClientMessage::Checksum(checksum))
+    }
+
+    /// Queues the message for the writer thread, so the game never waits
+    /// on a host slow to read
+    fn queue(&self, message: &ClientMessage) -> io::Result<()> {
+        match self.outgoing.try_send(to_line(message)?) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock, format!("the host fell {} messages behind", OUTGOING_KEPT))),
+            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted, "the host left")),
+        }
+    }
+
+    /// The ticks the host ran since the last call with their orders, an
+    /// error once the host is gone
+    pub fn receive(&mut self) -> io::Result<Vec<(u64, Orders)>> {
+        let mut ticks = Vec::new();
+        loop {
+            match self.incoming.try_recv() {
+                Ok(HostMessage::Tick { tick, orders }) => ticks.push((tick, orders)),
+                Ok(HostMessage::Welcome { .. }) => {},
+                Err(TryRecvError::Empty) => return Ok(ticks),
+                Err(TryRecvError::Disconnected) if ticks.is_empty() => return Err(
+                    io::Error::new(io::ErrorKind::ConnectionAborted, "the host left")),
+                // run what did arrive, the next call reports the host gone
+                Err(TryRecvError::Disconnected) => return Ok(ticks),
+            }
+        }
+    }
+}
+
+/// The network game this instance is part of
+pub enum Session {
+    Host(Host),
+    Client(Client),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_scheme::ColorName;
+    use crate::entity::{Entities, Entity, Footprint, Movement};
+    use quicksilver::geom::Vector;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&args("mc")), Ok(None));
+        assert_eq!(parse_args(&args("mc --host")), Ok(Some(Role::Host(DEFAULT_PORT))));
+        assert_eq!(parse_args(&args("mc --host 9000")), Ok(Some(Role::Host(9000))));
+        assert!(parse_args(&args("mc --host nine")).is_err());
+        assert_eq!(parse_args(&args("mc --join 10.0.0.2")),
+                   Ok(Some(Role::Join(String::from("10.0.0.2:7878")))));
+        assert_eq!(parse_args(&args("mc --join [::1]:9000")),
+                   Ok(Some(Role::Join(String::from("[::1]:9000")))));
+        assert!(parse_args(&args("mc --join")).is_err());
+        assert_eq!(parse_port(&args("jmc-server")), Ok(DEFAULT_PORT));
+        assert_eq!(parse_port(&args("jmc-server --port 9000")), Ok(9000));
+        assert!(parse_port(&args("jmc-server --port")).is_err());
+    }
+
+    /// A client connected to the host as peer 1, and its end of the
+    /// connection
+    fn connect(host: &mut Host) -> (Client, TcpStream) {
+        let stream = TcpStream::connect(("127.0.0.1", host.port().unwrap())).unwrap();
+        let incoming = spawn_reader(stream.try_clone().unwrap());
+        let client = Client { peer: 1, outgoing: spawn_writer(stream.try_clone().unwrap()), incoming };
+        // accepting would send the colony, skip straight to the peer
+        let (accepted, _) = loop {
+            if let Ok(connection) = host.listener.accept() {
+                break connection;
+            }
+        };
+        accepted.set_nonblocking(false).unwrap();
+        host.peers.push(Peer::new(1, accepted).unwrap());
+        (client, stream)
+    }
+
+    #[test]
+    fn test_orders_reach_the_host() {
+        let mut host = Host::listen(0).unwrap();
+        let (mut client, stream) = connect(&mut host);
+        let squad = Squad::default();
+
+        let order = Order::Area {
+            command: AreaCommand::Dig,
+            area: Area::from_corners((1, 2), (3, 4), 5),
+        };
+        client.send(order.clone()).unwrap();
+        while host.queued.is_empty() {
+            host.receive(&squad);
+        }
+        host.queue(HOST_PEER, Order::CancelMoves);
+        let orders = host.tick(7);
+        println!("{:?}", orders);
+        assert_eq!(orders, vec![(1, order), (HOST_PEER, Order::CancelMoves)]);
+
+        let ticks = loop {
+            let ticks = client.receive().unwrap();
+            if !ticks.is_empty() {
+                break ticks;
+            }
+        };
+        assert_eq!(ticks, vec![(7, orders)]);
+
+        // the peer's game differs from the host's in where the entities are
+        let checksum = |entities| Checksum {
+            tick: 100,
+            systems: vec![(String::from("map"), 1), (String::from("entities"), entities)],
+        };
+        host.record(checksum(2));
+        client.send_checksum(checksum(3)).unwrap();
+        let desyncs = loop {
+            host.receive(&squad);
+            let desyncs = host.take_desyncs();
+            if !desyncs.is_empty() {
+                break desyncs;
+            }
+        };
+        assert_eq!(desyncs, vec![Desync { peer: 1, tick: 100, systems: vec![String::from("entities")] }]);
+
+        stream.shutdown(Shutdown::Both).unwrap();
+        while host.receive(&squad).is_empty() {}
+        assert_eq!(host.queued, vec![(1, Order::Control { colonist: None })]);
+    }
+
+    #[test]
+    fn test_orders_for_colonists_steered_by_others_turned_away() {
+        let mut entities = Entities::new();
+        let mut colonist = || entities.insert(Entity {
+            pos: Vector::new(0, 0),
+            depth: 0,
+            glyph: '0',
+            color: ColorName::LightOrange,
+            hp: 5,
+            max_hp: 5,
+            gas_protection: false,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: false,
+            movement: Movement::WALKING,
+        });
+        let (hosts, theirs, vehicle) = (colonist(), colonist(), colonist());
+        let mut squad = Squad::new(vec![hosts, theirs]);
+        squad.steer(HOST_PEER, Some(hosts));
+        let mut host = Host::listen(0).unwrap();
+        let (client, _stream) = connect(&mut host);
+
+        // the host's colonist, then one they take control of in the same
+        // tick, and something that isn't in the squad at all
+        let orders = vec![
+            Order::Step { colonist: hosts, dx: 1, dy: 0 },
+            Order::Control { colonist: Some(theirs) },
+            Order::Step { colonist: theirs, dx: 1, dy: 0 },
+            Order::MoveTo { colonist: vehicle, x: 3, y: 4 },
+            Order::Equip { colonist: hosts },
+            Order::Alert { raised: true },
+        ];
+        for order in &orders {
+            client.send(order.clone()).unwrap();
+        }
+        while host.queued.last().map(|(_, order)| order) != Some(&Order::Alert { raised: true }) {
+            host.receive(&squad);
+        }
+        let queued: Vec<Order> = host.queued.iter().map(|(_, order)| order.clone()).collect();
+        println!("{:?}", queued);
+        assert_eq!(queued, vec![orders[1].clone(), orders[2].clone(), orders[3].clone(), orders[5].clone()]);
+    }
+
+    #[test]
+    fn test_stalled_peer_dropped_without_holding_up_the_host() {
+        let mut host = Host::listen(0).unwrap();
+        // connects and never reads a thing
+        let _stalled = TcpStream::connect(("127.0.0.1", host.port().unwrap())).unwrap();
+        let (accepted, _) = loop {
+            if let Ok(connection) = host.listener.accept() {
+                break connection;
+            }
+        };
+        accepted.set_nonblocking(false).unwrap();
+        host.peers.push(Peer::new(1, accepted).unwrap());
+
+        // the socket's buffers fill up, then the peer's queue does
+        let mut tick = 0;
+        while !host.peers.is_empty() {
+            tick += 1;
+            assert!(tick < 10_000, "the stalled peer was never dropped");
+            for _ in 0..1000 {
+                host.queue(HOST_PEER, Order::CancelMoves);
+            }
+            host.tick(tick);
+        }
+        println!("dropped after {} ticks", tick);
+        assert!(tick > OUTGOING_KEPT as u64);
+        assert_eq!(host.queued, vec![(1, Order::Control { colonist: None })]);
+    }
+}
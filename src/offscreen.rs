@@ -0,0 +1,233 @@
+use crate::color_scheme::ColorName;
+use crate::events::GameEvent;
+use crate::incidents::Incident;
+
+/// How long an arrow points toward something after it last happened
+const SHOWN_MS: u64 = 4000;
+/// How long a new arrow flashes before it starts fading
+const FLASH_MS: u64 = 1200;
+/// Things of the same kind on a level this many tiles apart or closer share
+/// an arrow, and only the first of them plays the cue
+const NEARBY_TILES: u32 = 12;
+/// Oldest alerts are dropped past this many, so arrows don't ring the view
+const MAX_ALERTS: usize = 8;
+
+/// The kinds of things worth turning the player's head for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alarm {
+    CaveIn,
+    /// Something was hurt, killed or is on its way to do so
+    Combat,
+    Flood,
+}
+
+impl Alarm {
+
+    /// The alarm the event raises when it happens out of view, if it
+    /// matters enough for one
+    pub fn for_event(event: &GameEvent) -> Option<Alarm> {
+        match event {
+            GameEvent::CaveIn { .. }
+            | GameEvent::Incident { incident: Incident::Tremor, .. } => Some(Alarm::CaveIn),
+            GameEvent::Hit { .. }
+            | GameEvent::Died { .. }
+            | GameEvent::HostileSighted { .. } => Some(Alarm::Combat),
+            GameEvent::WaterFlowed { .. } => Some(Alarm::Flood),
+            GameEvent::Dug { .. }
+            | GameEvent::Hauled { .. }
+            | GameEvent::ColonyLost { .. }
+            | GameEvent::NoRoute { .. }
+            | GameEvent::JobInterrupted { .. }
+            | GameEvent::WornOut { .. }
+            | GameEvent::SkillUp { .. }
+            | GameEvent::Tamed { .. }
+            | GameEvent::Born { .. }
+            | GameEvent::CaravanArrived { .. }
+            | GameEvent::CaravanLeft { .. }
+            | GameEvent::Incident { .. } => None,
+        }
+    }
+
+    pub fn color(&self) -> ColorName {
+        match self {
+            Alarm::CaveIn => ColorName::LightYellow,
+            Alarm::Combat => ColorName::LightRed,
+            Alarm::Flood => ColorName::LightAqua,
+        }
+    }
+}
+
+/// The part of the world the player is looking at, in tiles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// The world's width, the view wraps around at it
+    pub world_width: u32,
+}
+
+impl View {
+
+    /// How far the middle of the tile is from the middle of the view, the
+    /// shorter way around the world
+    pub fn offset(&self, x: u32, y: u32) -> (f32, f32) {
+        let world = self.world_width as f32;
+        let dx = (x as f32 + 0.5 - (self.x + self.width / 2.0)).rem_euclid(world);
+        let dx = if dx > world / 2.0 { dx - world } else { dx };
+        (dx, y as f32 + 0.5 - (self.y + self.height / 2.0))
+    }
+
+    /// Whether the tile is in view on some level. Only what's outside it
+    /// gets an arrow, there's no way across to point to another level.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        let (dx, dy) = self.offset(x, y);
+        dx.abs() <= self.width / 2.0 && dy.abs() <= self.height / 2.0
+    }
+}
+
+/// Something important that happened out of view
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    pub alarm: Alarm,
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    /// When it last happened around there
+    pub at_ms: u64,
+    pub raised_ms: u64,
+}
+
+impl Alert {
+
+    /// How opaque its arrow is at now_ms, flashing when it's new and
+    /// fading out after
+    pub fn alpha(&self, now_ms: u64) -> f32 {
+        let flashing = now_ms.saturating_sub(self.raised_ms);
+        if flashing < FLASH_MS {
+            return if (flashing / 200).is_multiple_of(2) { 1.0 } else { 0.35 };
+        }
+        let age = now_ms.saturating_sub(self.at_ms);
+        (1.0 - age as f32 / SHOWN_MS as f32).clamp(0.0, 1.0)
+    }
+}
+
+/// Arrows at the edge of the map view pointing toward cave-ins, fights and
+/// floods out of view, raised off the event bus
+pub struct OffscreenAlerts {
+    alerts: Vec<Alert>,
+}
+
+impl OffscreenAlerts {
+
+    pub fn new() -> OffscreenAlerts {
+        OffscreenAlerts {
+            alerts: Vec::new(),
+        }
+    }
+
+    /// Raises an alert for the event if it's important and out of view,
+    /// returning where the cue for it plays from -1.0 on the left to 1.0 on
+    /// the right. None when there's nothing new to hear, an event near an
+    /// alert of its kind only keeps that one showing.
+    pub fn on_event(&mut self, event: &GameEvent, view: &View, now_ms: u64) -> Option<f32> {
+        let alarm = Alarm::for_event(event)?;
+        let (x, y, z) = event.position();
+        if view.contains(x, y) {
+            return None;
+        }
+        let world_width = view.world_width;
+        let nearby = self.alerts.iter_mut().find(|alert| {
+            let dx = alert.x.abs_diff(x).min(world_width - alert.x.abs_diff(x));
+            alert.alarm == alarm && alert.z == z && dx.max(alert.y.abs_diff(y)) <= NEARBY_TILES
+        });
+        if let Some(alert) = nearby {
+            alert.at_ms = now_ms;
+            return None;
+        }
+        if self.alerts.len() >= MAX_ALERTS {
+            self.alerts.remove(0);
+        }
+        self.alerts.push(Alert { alarm, x, y, z, at_ms: now_ms, raised_ms: now_ms });
+        let (dx, dy) = view.offset(x, y);
+        Some(dx / (dx * dx + dy * dy).sqrt())
+    }
+
+    /// Drops the alerts that have faded out or come into view
+    pub fn update(&mut self, view: &View, now_ms: u64) {
+        self.alerts.retain(|alert| {
+            now_ms.saturating_sub(alert.at_ms) < SHOWN_MS && !view.contains(alert.x, alert.y)
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Alert> {
+        self.alerts.iter()
+    }
+}
+
+/// Where along the edge of a view width by height across the arrow toward
+/// (dx, dy) from its middle goes, inset in from the edge, as an offset
+/// from the middle
+pub fn edge_point(dx: f32, dy: f32, width: f32, height: f32, inset: f32) -> (f32, f32) {
+    let (half_width, half_height) = ((width / 2.0 - inset).max(0.0), (height / 2.0 - inset).max(0.0));
+    // a direction straight along an axis divides by zero into infinity,
+    // which the other axis always beats
+    let scale = (half_width / dx.abs()).min(half_height / dy.abs());
+    (dx * scale, dy * scale)
+}
+
+/// The corners of an arrow size long centered on center, pointing along
+/// (dx, dy)
+pub fn arrow(center: (f32, f32), dx: f32, dy: f32, size: f32) -> [(f32, f32); 3] {
+    let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+    let (along_x, along_y) = (dx / length * size / 2.0, dy / length * size / 2.0);
+    // half as wide as it's long
+    let (across_x, across_y) = (-along_y / 2.0, along_x / 2.0);
+    let (x, y) = center;
+    [
+        (x + along_x, y + along_y),
+        (x - along_x + across_x, y - along_y + across_y),
+        (x - along_x - across_x, y - along_y - across_y),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alerts_point_out_of_view() {
+        let view = View { x: 100.0, y: 50.0, width: 40.0, height: 20.0, world_width: 1000 };
+        let mut alerts = OffscreenAlerts::new();
+        // in view there's nothing to point at, and digging is never alarming
+        assert_eq!(alerts.on_event(&GameEvent::CaveIn { x: 110, y: 55, z: 3 }, &view, 0), None);
+        assert_eq!(alerts.on_event(&GameEvent::Dug { x: 300, y: 55, z: 3 }, &view, 0), None);
+
+        // east of the view is heard on the right, a flood near one only
+        // keeps the arrow showing
+        let pan = alerts.on_event(&GameEvent::WaterFlowed { x: 200, y: 60, z: 3 }, &view, 0);
+        println!("{:?} {:?}", pan, alerts.iter().collect::<Vec<_>>());
+        assert!(pan.is_some_and(|pan| pan > 0.9));
+        assert_eq!(alerts.on_event(&GameEvent::WaterFlowed { x: 205, y: 62, z: 3 }, &view, 1000), None);
+        // west across the edge of the world is heard on the left
+        let hit = GameEvent::Hit { x: 990, y: 60, z: 3, damage: 2 };
+        assert!(alerts.on_event(&hit, &view, 1000).is_some_and(|pan| pan < -0.9));
+        assert_eq!(alerts.iter().count(), 2);
+
+        assert_eq!(alerts.iter().next().unwrap().alpha(100), 1.0);
+        assert_eq!(alerts.iter().next().unwrap().alpha(300), 0.35);
+        alerts.update(&view, 4500);
+        assert_eq!(alerts.iter().map(|alert| alert.alarm).collect::<Vec<_>>(), vec![Alarm::Flood, Alarm::Combat]);
+        let moved = View { x: 180.0, ..view };
+        alerts.update(&moved, 4500);
+        assert_eq!(alerts.iter().map(|alert| alert.alarm).collect::<Vec<_>>(), vec![Alarm::Combat]);
+
+        // the arrow sits on the edge it points through
+        let (dx, dy) = view.offset(200, 70);
+        assert!((edge_point(dx, dy, 40.0, 20.0, 1.0).0 - 19.0).abs() < 1e-4);
+        assert_eq!(edge_point(0.0, -30.0, 40.0, 20.0, 1.0), (0.0, -9.0));
+        let [tip, ..] = arrow((0.0, 0.0), 3.0, 0.0, 2.0);
+        assert_eq!(tip, (1.0, 0.0));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::color_scheme::{ColorName, ColorRamp};
+use crate::designation::Designations;
+use crate::game_map::{Tile, MAX_FLUID, MAX_GAS};
+use crate::support::Support;
+
+/// Temperature at the surface of Janus 7, in degrees Celsius
+const SURFACE_TEMPERATURE: f32 = -20.0;
+/// How much warmer the rock gets per meter down
+const GEOTHERMAL_GRADIENT: f32 = 0.025;
+/// Rock from the frozen surface down to the hottest depths
+const TEMPERATURE_RAMP: ColorRamp = ColorRamp::new(&[
+    ColorName::Blue, ColorName::LightBlue, ColorName::LightYellow, ColorName::Orange, ColorName::Red,
+], -40.0, 100.0);
+
+/// What overlays may look at besides the tile itself
+pub struct OverlayContext<'a> {
+    pub designations: &'a Designations,
+    pub support: &'a Support,
+    /// Meters of rock per level
+    pub level_thickness: u32,
+}
+
+/// A data layer the map can be re-colored by. draw_map asks the active
+/// overlay for a tint on every tile in view.
+pub trait Overlay {
+
+    fn name(&self) -> &'static str;
+
+    /// The color to tint the tile at (x, y, z) with and how strongly, from
+    /// 0.0 to 1.0. None leaves the tile as it is.
+    fn tint(&self, context: &OverlayContext, tile: &Tile, x: u32, y: u32, z: u32)
+        -> Option<(ColorName, f32)>;
+}
+
+/// Every overlay, in the order the overlay hotkey cycles through them
+pub fn overlays() -> Vec<Box<dyn Overlay>> {
+    vec![
+        Box::new(DesignationOverlay),
+        Box::new(BurrowOverlay),
+        Box::new(FluidOverlay),
+        Box::new(GasOverlay),
+        Box::new(CollapseOverlay),
+        Box::new(TemperatureOverlay),
+    ]
+}
+
+/// Work marked for the colony
+pub struct DesignationOverlay;
+
+impl Overlay for DesignationOverlay {
+
+    fn name(&self) -> &'static str {
+        "Designations"
+    }
+
+    fn tint(&self, context: &OverlayContext, _tile: &Tile, x: u32, y: u32, z: u32)
+        -> Option<(ColorName, f32)> {
+        if context.designations.is_dig(x, y, z) {
+            Some((ColorName::Yellow, 1.0))
+        } else if context.designations.is_stockpile(x, y, z) {
+            Some((ColorName::Aqua, 1.0))
+        } else if context.designations.is_pen(x, y, z) {
+            Some((ColorName::Orange, 0.6))
+        } else if context.designations.is_refuse(x, y, z) {
+            Some((ColorName::Purple, 0.6))
+        } else if context.designations.is_farm(x, y, z) {
+            Some((ColorName::Green, 0.6))
+        } else {
+            None
+        }
+    }
+}
+
+/// The area colonists can be kept to
+pub struct BurrowOverlay;
+
+impl Overlay for BurrowOverlay {
+
+    fn name(&self) -> &'static str {
+        "Burrow"
+    }
+
+    fn tint(&self, context: &OverlayContext, _tile: &Tile, x: u32, y: u32, z: u32)
+        -> Option<(ColorName, f32)> {
+        if context.designations.in_burrow(x, y, z) {
+            Some((ColorName::Purple, 0.5))
+        } else {
+            None
+        }
+    }
+}
+
+/// Depth of standing water
+pub struct FluidOverlay;
+
+impl Overlay for FluidOverlay {
+
+    fn name(&self) -> &'static str {
+        "Water depth"
+    }
+
+    fn tint(&self, _context: &OverlayContext, tile: &Tile, _x: u32, _y: u32, _z: u32)
+        -> Option<(ColorName, f32)> {
+        if tile.fluid == 0 {
+            return None;
+        }
+        Some((ColorName::LightBlue, tile.fluid as f32 / MAX_FLUID as f32))
+    }
+}
+
+/// Concentration of toxic gas
+pub struct GasOverlay;
+
+impl Overlay for GasOverlay {
+
+    fn name(&self) -> &'static str {
+        "Gas"
+    }
+
+    fn tint(&self, _context: &OverlayContext, tile: &Tile, _x: u32, _y: u32, _z: u32)
+        -> Option<(ColorName, f32)> {
+        if tile.gas == 0 {
+            return None;
+        }
+        Some((ColorName::LightGreen, tile.gas as f32 / MAX_GAS as f32))
+    }
+}
+
+/// Ceilings about to come down
+pub struct CollapseOverlay;
+
+impl Overlay for CollapseOverlay {
+
+    fn name(&self) -> &'static str {
+        "Cave-in risk"
+    }
+
+    fn tint(&self, context: &OverlayContext, tile: &Tile, x: u32, y: u32, z: u32)
+        -> Option<(ColorName, f32)> {
+        if context.support.is_pending(x, y, z) {
+            Some((ColorName::Red, 1.0))
+        } else if tile.dug && !tile.is_solid() {
+            Some((ColorName::Green, 0.3))
+        } else {
+            None
+        }
+    }
+}
+
+/// How warm the rock is, it heats up the deeper it lies
+pub struct TemperatureOverlay;
+
+impl Overlay for TemperatureOverlay {
+
+    fn name(&self) -> &'static str {
+        "Temperature"
+    }
+
+    fn tint(&self, context: &OverlayContext, tile: &Tile, _x: u32, _y: u32, z: u32)
+        -> Option<(ColorName, f32)> {
+        let celsius = temperature(tile, z, context.level_thickness);
+        // tinted more strongly the further from freezing
+        let strength = if celsius < 0.0 { -celsius / 40.0 } else { celsius / 100.0 };
+        Some((TEMPERATURE_RAMP.band(celsius as f64), strength.min(1.0)))
+    }
+}
+
+/// Degrees Celsius on the tile, water stays a little cooler than the rock
+pub fn temperature(tile: &Tile, z: u32, level_thickness: u32) -> f32 {
+    let depth_m = (z * level_thickness) as f32;
+    let water_cooling = if tile.fluid > 0 { 5.0 } else { 0.0 };
+    SURFACE_TEMPERATURE + depth_m * GEOTHERMAL_GRADIENT - water_cooling
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::GameMap;
+
+    #[test]
+    fn test_deeper_is_warmer() {
+        let mut map = GameMap::with_seed(10);
+        let shallow = map.get_tile(1000, 1000, 2);
+        let deep = map.get_tile(1000, 1000, 200);
+        let shallow_temperature = temperature(&shallow, 2, map.level_thickness);
+        let deep_temperature = temperature(&deep, 200, map.level_thickness);
+        println!("shallow: {} deep: {}", shallow_temperature, deep_temperature);
+        assert!(shallow_temperature < 0.0);
+        assert!(deep_temperature > shallow_temperature);
+
+        let (designations, support) = (Designations::new(), Support::new());
+        let context = OverlayContext {
+            designations: &designations,
+            support: &support,
+            level_thickness: map.level_thickness,
+        };
+        let tint = TemperatureOverlay.tint(&context, &shallow, 1000, 1000, 2);
+        println!("{:?}", tint);
+        assert_eq!(tint.map(|(color, _)| color), Some(ColorName::Blue));
+    }
+}
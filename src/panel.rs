@@ -0,0 +1,250 @@
+use quicksilver::prelude::*;
+
+use crate::layer::RenderLayer;
+use crate::text_layout::{TextLayout, TextStyle};
+
+/// The corner, edge or middle of the screen a panel keeps to
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+
+    /// The top left corner of a panel of the size kept to this anchor, margin
+    /// away from the edges it's against. Margins push panels in the middle
+    /// of an edge along it too, right and down.
+    pub fn place(self, size: Vector, screen: Vector, margin: Vector) -> Vector {
+        let x = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => margin.x,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => (screen.x - size.x) / 2.0 + margin.x,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => screen.x - size.x - margin.x,
+        };
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => margin.y,
+            Anchor::Left | Anchor::Center | Anchor::Right => (screen.y - size.y) / 2.0 + margin.y,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => screen.y - size.y - margin.y,
+        };
+        Vector::new(x, y)
+    }
+
+    /// How wide a panel kept to this anchor can get before it runs off the
+    /// screen
+    pub fn room(self, screen: Vector, margin: Vector) -> f32 {
+        match self {
+            Anchor::Top | Anchor::Center | Anchor::Bottom => screen.x - margin.x.abs() * 2.0,
+            _ => screen.x - margin.x,
+        }
+    }
+}
+
+/// Something shown on a panel, each under the one before
+pub enum Widget {
+    Text(String, TextStyle),
+    Image(Image),
+}
+
+/// A line of a panel, rendered
+struct Row {
+    image: Image,
+    height: f32,
+    /// Which widget it's from, text can wrap onto several rows
+    widget: usize,
+}
+
+/// A box of text and pictures kept to a place on the screen, sized to fit
+/// what's on it
+pub struct Panel {
+    pub anchor: Anchor,
+    /// How far it's kept from the edges it's anchored to
+    pub margin: Vector,
+    /// Space between its edges and what's on it
+    pub padding: f32,
+    /// Fewest pixels each line takes up, taller text or pictures take more
+    pub line_height: f32,
+    /// None to show what's on it straight over whatever's under it
+    pub background: Option<Color>,
+    /// Spans the width of the screen rather than what's on it
+    pub stretch: bool,
+    /// What's on it is centered across it rather than kept to its left
+    pub centered: bool,
+    /// Widest its text gets before it wraps onto the next line, None to
+    /// wrap it at the edges of the screen
+    pub max_width: Option<f32>,
+    pub layer: RenderLayer,
+    pub widgets: Vec<Widget>,
+}
+
+impl Panel {
+
+    pub fn new(anchor: Anchor, layer: RenderLayer) -> Panel {
+        Panel {
+            anchor,
+            margin: Vector::ZERO,
+            padding: 0.0,
+            line_height: 0.0,
+            background: None,
+            stretch: false,
+            centered: false,
+            max_width: None,
+            layer,
+            widgets: Vec::new(),
+        }
+    }
+
+    pub fn with_margin(mut self, margin: impl Into<Vector>) -> Panel {
+        self.margin = margin.into();
+        self
+    }
+
+    pub fn with_padding(mut self, padding: f32) -> Panel {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_line_height(mut self, line_height: f32) -> Panel {
+        self.line_height = line_height;
+        self
+    }
+
+    pub fn with_background(mut self, background: Color) -> Panel {
+        self.background = Some(background);
+        self
+    }
+
+    pub fn stretched(mut self) -> Panel {
+        self.stretch = true;
+        self
+    }
+
+    pub fn centered(mut self) -> Panel {
+        self.centered = true;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Panel {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Adds a line of text under what's on it already
+    pub fn with_text(mut self, text: &str, style: TextStyle) -> Panel {
+        self.widgets.push(Widget::Text(String::from(text), style));
+        self
+    }
+
+    /// Adds each line of text in the same style
+    pub fn with_lines(mut self, lines: &[String], style: TextStyle) -> Panel {
+        self.widgets.extend(lines.iter().map(|line| Widget::Text(line.clone(), style)));
+        self
+    }
+
+    pub fn with_image(mut self, image: Image) -> Panel {
+        self.widgets.push(Widget::Image(image));
+        self
+    }
+
+    /// Where it goes on the window, with what's on it rendered a row at a
+    /// time, each with its height and the widget it's from. Text too wide
+    /// for it wraps onto more rows.
+    fn layout(&self, window: &Window, font: &Font, text_layout: &TextLayout)
+              -> Result<(Rectangle, Vec<Row>)> {
+        let screen = window.screen_size();
+        let max_width = self.max_width
+            .unwrap_or(f32::INFINITY)
+            .min(self.anchor.room(screen, self.margin) - self.padding * 2.0);
+        let mut images = Vec::new();
+        for (index, widget) in self.widgets.iter().enumerate() {
+            match widget {
+                Widget::Text(text, style) => {
+                    for line in text_layout.wrap(text, style.size, max_width) {
+                        // a space stands in for blank lines, there is no
+                        // glyph to size an empty image by
+                        let line = if line.is_empty() { " " } else { line.as_str() };
+                        images.push((font.render(line, &style.font_style())?, index));
+                    }
+                },
+                Widget::Image(image) => images.push((image.clone(), index)),
+            }
+        }
+        let rows: Vec<f32> = images.iter()
+            .map(|(image, _)| image.area().height().max(self.line_height))
+            .collect();
+        let content_width = images.iter().map(|(image, _)| image.area().width()).fold(0.0, f32::max);
+        let width = if self.stretch {
+            screen.x - self.margin.x * 2.0
+        } else {
+            content_width + self.padding * 2.0
+        };
+        let size = Vector::new(width, rows.iter().sum::<f32>() + self.padding * 2.0);
+        let area = Rectangle::new(self.anchor.place(size, screen, self.margin), size);
+        let rows = images.into_iter().zip(rows)
+            .map(|((image, widget), height)| Row { image, height, widget })
+            .collect();
+        Ok((area, rows))
+    }
+
+    /// Which widget is at the point on the window, counting from the top
+    pub fn row_at(&self, window: &Window, font: &Font, text_layout: &TextLayout,
+                  point: Vector) -> Result<Option<usize>> {
+        let (area, rows) = self.layout(window, font, text_layout)?;
+        if !area.contains(point) {
+            return Ok(None);
+        }
+        let mut y = area.y() + self.padding;
+        for row in &rows {
+            if point.y >= y && point.y < y + row.height {
+                return Ok(Some(row.widget));
+            }
+            y += row.height;
+        }
+        Ok(None)
+    }
+
+    /// Draws it where it's anchored on the window, text in the font
+    /// wrapped where the layout measures it too wide
+    pub fn draw(&self, window: &mut Window, font: &Font, text_layout: &TextLayout) -> Result<()> {
+        let (area, rows) = self.layout(window, font, text_layout)?;
+        if let Some(background) = self.background {
+            window.draw_ex(&area, Col(background), Transform::IDENTITY, self.layer.z());
+        }
+        let mut y = area.y() + self.padding;
+        for Row { image, height, .. } in &rows {
+            let x = if self.centered {
+                area.x() + (area.width() - image.area().width()) / 2.0
+            } else {
+                area.x() + self.padding
+            };
+            window.draw_ex(&image.area().translate((x, y)), Img(image), Transform::IDENTITY, self.layer.over());
+            y += height;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchors_place_panels() {
+        let (size, screen, margin) = (Vector::new(100, 50), Vector::new(800, 600), Vector::new(10, 6));
+        let placed = |anchor: Anchor| anchor.place(size, screen, margin);
+        println!("{:?}", placed(Anchor::Center));
+        assert_eq!(placed(Anchor::TopLeft), Vector::new(10, 6));
+        assert_eq!(placed(Anchor::Top), Vector::new(360, 6));
+        assert_eq!(placed(Anchor::TopRight), Vector::new(690, 6));
+        assert_eq!(placed(Anchor::Center), Vector::new(360, 281));
+        assert_eq!(placed(Anchor::BottomLeft), Vector::new(10, 544));
+        assert_eq!(placed(Anchor::BottomRight), Vector::new(690, 544));
+    }
+}
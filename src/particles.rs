@@ -0,0 +1,135 @@
+use crate::animation::{Frame, Renderable};
+use crate::color_scheme::ColorName;
+use crate::events::GameEvent;
+
+/// Oldest particles are dropped past this many, so a flood of events
+/// can't bog down drawing
+const MAX_PARTICLES: usize = 256;
+
+/// A short lived effect drawn over a tile, above entities. Only drawn,
+/// nothing in the simulation ever sees one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Particle {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    /// Played once from spawned_ms, the particle is gone when it ends
+    pub renderable: Renderable,
+    pub spawned_ms: u64,
+}
+
+impl Particle {
+
+    /// The frame showing at now_ms, None once the particle is done
+    pub fn frame(&self, now_ms: u64) -> Option<&Frame> {
+        let age_ms = now_ms.saturating_sub(self.spawned_ms);
+        if age_ms >= self.renderable.duration_ms() {
+            return None;
+        }
+        self.renderable.frame(age_ms)
+    }
+}
+
+/// The particles for each kind of event that has one
+fn effect_for(event: &GameEvent) -> Option<Renderable> {
+    let frames = match event {
+        // a puff of dust settling where the rock was
+        GameEvent::Dug { .. } | GameEvent::CaveIn { .. } => vec![
+            Frame::new('▓', ColorName::Stone2, 80),
+            Frame::new('▒', ColorName::Stone3, 120),
+            Frame::new('░', ColorName::Stone4, 160),
+        ],
+        GameEvent::WaterFlowed { .. } => vec![
+            Frame::new('•', ColorName::LightAqua, 100),
+            Frame::new('‧', ColorName::LightBlue, 150),
+        ],
+        GameEvent::Hit { .. } => vec![
+            Frame::new('*', ColorName::LightRed, 60),
+            Frame::new('*', ColorName::Red, 60),
+            Frame::new('*', ColorName::LightRed, 60),
+        ],
+        GameEvent::Died { .. } => vec![
+            Frame::new('*', ColorName::Red, 150),
+            Frame::new('‧', ColorName::Red, 250),
+        ],
+        GameEvent::Hauled { .. }
+        | GameEvent::ColonyLost { .. }
+        | GameEvent::NoRoute { .. }
+        | GameEvent::JobInterrupted { .. }
+        | GameEvent::HostileSighted { .. }
+        | GameEvent::WornOut { .. }
+        | GameEvent::SkillUp { .. }
+        | GameEvent::Tamed { .. }
+        | GameEvent::Born { .. }
+        | GameEvent::CaravanArrived { .. }
+        | GameEvent::CaravanLeft { .. }
+        | GameEvent::Incident { .. } => return None,
+    };
+    Some(Renderable::new(frames))
+}
+
+/// Transient visuals spawned off the event bus
+pub struct Particles {
+    particles: Vec<Particle>,
+}
+
+impl Particles {
+
+    pub fn new() -> Particles {
+        Particles {
+            particles: Vec::new(),
+        }
+    }
+
+    /// Spawns the event's effect, replacing any still playing on its tile
+    pub fn on_event(&mut self, event: &GameEvent, now_ms: u64) {
+        let renderable = match effect_for(event) {
+            Some(renderable) => renderable,
+            None => return,
+        };
+        let (x, y, z) = event.position();
+        self.spawn(x, y, z, renderable, now_ms);
+    }
+
+    /// Plays the effect on the tile from now_ms, replacing any still
+    /// playing there
+    pub fn spawn(&mut self, x: u32, y: u32, z: u32, renderable: Renderable, now_ms: u64) {
+        self.particles.retain(|particle| (particle.x, particle.y, particle.z) != (x, y, z));
+        if self.particles.len() >= MAX_PARTICLES {
+            self.particles.remove(0);
+        }
+        self.particles.push(Particle { x, y, z, renderable, spawned_ms: now_ms });
+    }
+
+    /// Drops the particles that have finished playing
+    pub fn update(&mut self, now_ms: u64) {
+        self.particles.retain(|particle| particle.frame(now_ms).is_some());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_expire() {
+        let mut particles = Particles::new();
+        particles.on_event(&GameEvent::Dug { x: 1, y: 2, z: 3 }, 1000);
+        particles.on_event(&GameEvent::Hit { x: 1, y: 2, z: 3, damage: 1 }, 1100);
+        particles.on_event(&GameEvent::Hauled { x: 4, y: 2, z: 3, amount: 5 }, 1100);
+        let glyphs: Vec<_> = particles.iter()
+            .filter_map(|particle| particle.frame(1150).map(|frame| frame.glyph))
+            .collect();
+        println!("{:?}", glyphs);
+        assert_eq!(glyphs, vec!['*']);
+
+        particles.update(1279);
+        assert_eq!(particles.iter().count(), 1);
+        particles.update(1280);
+        assert_eq!(particles.iter().count(), 0);
+    }
+}
@@ -0,0 +1,373 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::entity::{Footprint, Movement};
+use crate::game_map::GameMap;
+use crate::pathfinding::{self, MAX_SEARCH};
+
+/// Open stretches of a chunk's edge longer than this get a portal at each
+/// end rather than one in the middle, so paths don't bend towards it
+const LONG_ENTRANCE: u32 = 8;
+
+/// A chunk of a level as (x, y, z) in chunks
+type ClusterKey = (u32, u32, u32);
+/// Portals or the goal and the steps to them
+type Steps = Vec<((u32, u32), u32)>;
+
+/// One chunk of one level as the path graph sees it: which tiles are open,
+/// the portals where it can be left and how far apart they are inside it
+struct Cluster {
+    origin: (u32, u32),
+    size: u32,
+    open: Vec<bool>,
+    /// Steps between portals without leaving the chunk
+    edges: HashMap<(u32, u32), Steps>,
+    /// The tiles across the edge from each portal, in the next chunk over
+    links: HashMap<(u32, u32), Vec<(u32, u32)>>,
+}
+
+impl Cluster {
+
+    fn build(map: &mut GameMap, (cx, cy, z): ClusterKey) -> Cluster {
+        let size = map.chunk_size;
+        let (x0, y0) = (cx * size, cy * size);
+        let (_, max_y, _) = map.size();
+        let mut open = Vec::with_capacity((size * size) as usize);
+        for y in y0..y0 + size {
+            for x in x0..x0 + size {
+                open.push(!map.get_tile(x, y, z).is_solid());
+            }
+        }
+
+        let west = map.wrap_x(x0 as i64 - 1);
+        let east = map.wrap_x((x0 + size) as i64);
+        let mut entrances = Vec::new();
+        if y0 > 0 {
+            entrances.extend(entrances_along(map, z, size, |i| (x0 + i, y0), |i| (x0 + i, y0 - 1)));
+        }
+        if y0 + size < max_y {
+            entrances.extend(entrances_along(map, z, size, |i| (x0 + i, y0 + size - 1), |i| (x0 + i, y0 + size)));
+        }
+        entrances.extend(entrances_along(map, z, size, |i| (x0, y0 + i), |i| (west, y0 + i)));
+        entrances.extend(entrances_along(map, z, size, |i| (x0 + size - 1, y0 + i), |i| (east, y0 + i)));
+
+        let mut cluster = Cluster {
+            origin: (x0, y0), size, open, edges: HashMap::new(), links: HashMap::new(),
+        };
+        for (portal, across) in entrances {
+            cluster.links.entry(portal).or_default().push(across);
+        }
+        let portals: Vec<(u32, u32)> = cluster.links.keys().cloned().collect();
+        for &portal in &portals {
+            let steps = cluster.distances(portal, |_, _| false);
+            let edges = cluster.portal_steps(&steps).into_iter()
+                .filter(|&(other, _)| other != portal)
+                .collect();
+            cluster.edges.insert(portal, edges);
+        }
+        cluster
+    }
+
+    fn index(&self, (x, y): (u32, u32)) -> usize {
+        ((x - self.origin.0) + (y - self.origin.1) * self.size) as usize
+    }
+
+    /// Steps from a tile of the chunk to every tile of it without leaving
+    /// it or crossing blocked ones, u32::MAX where it can't get
+    fn distances(&self, from: (u32, u32), blocked: impl Fn(u32, u32) -> bool) -> Vec<u32> {
+        let (x0, y0) = self.origin;
+        let mut steps = vec![u32::MAX; self.open.len()];
+        let mut open = VecDeque::new();
+        steps[self.index(from)] = 0;
+        open.push_back(from);
+        while let Some((x, y)) = open.pop_front() {
+            let next_steps = steps[self.index((x, y))] + 1;
+            let neighbors = [
+                (x > x0).then(|| (x - 1, y)),
+                (x + 1 < x0 + self.size).then_some((x + 1, y)),
+                (y > y0).then(|| (x, y - 1)),
+                (y + 1 < y0 + self.size).then_some((x, y + 1)),
+            ];
+            for &next in neighbors.iter().flatten() {
+                let index = self.index(next);
+                if self.open[index] && steps[index] == u32::MAX && !blocked(next.0, next.1) {
+                    steps[index] = next_steps;
+                    open.push_back(next);
+                }
+            }
+        }
+        steps
+    }
+
+    /// The steps after from to to without leaving the chunk, worked out on
+    /// the tiles it remembers instead of the map
+    fn walk(&self, from: (u32, u32), to: (u32, u32), blocked: impl Fn(u32, u32) -> bool)
+        -> Option<Vec<(u32, u32)>> {
+
+        if blocked(to.0, to.1) {
+            return None;
+        }
+        let steps = self.distances(to, blocked);
+        let mut left = steps[self.index(from)];
+        if left == u32::MAX {
+            return None;
+        }
+        let (x0, y0) = self.origin;
+        let mut path = Vec::with_capacity(left as usize);
+        let (mut x, mut y) = from;
+        while left > 0 {
+            let neighbors = [
+                (x > x0).then(|| (x - 1, y)),
+                (x + 1 < x0 + self.size).then_some((x + 1, y)),
+                (y > y0).then(|| (x, y - 1)),
+                (y + 1 < y0 + self.size).then_some((x, y + 1)),
+            ];
+            let &next = neighbors.iter().flatten()
+                .find(|&&next| steps[self.index(next)] == left - 1)?;
+            path.push(next);
+            (x, y) = next;
+            left -= 1;
+        }
+        Some(path)
+    }
+
+    /// The portals distances reached and how far each was
+    fn portal_steps(&self, steps: &[u32]) -> Steps {
+        self.links.keys()
+            .map(|&portal| (portal, steps[self.index(portal)]))
+            .filter(|&(_, steps)| steps != u32::MAX)
+            .collect()
+    }
+}
+
+/// Where an edge of a chunk can be crossed: runs of tiles open on both
+/// sides, as the tile inside and the one across. inside and outside give
+/// the tiles i along the edge.
+fn entrances_along(map: &mut GameMap, z: u32, size: u32,
+                   inside: impl Fn(u32) -> (u32, u32),
+                   outside: impl Fn(u32) -> (u32, u32)) -> Vec<((u32, u32), (u32, u32))> {
+    let mut entrances = Vec::new();
+    let mut run_start = None;
+    for i in 0..=size {
+        let crossable = i < size && {
+            let (ix, iy) = inside(i);
+            let (ox, oy) = outside(i);
+            !map.get_tile(ix, iy, z).is_solid() && !map.get_tile(ox, oy, z).is_solid()
+        };
+        match (crossable, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(first)) => {
+                let last = i - 1;
+                if last - first + 1 > LONG_ENTRANCE {
+                    entrances.push((inside(first), outside(first)));
+                    entrances.push((inside(last), outside(last)));
+                } else {
+                    let middle = (first + last) / 2;
+                    entrances.push((inside(middle), outside(middle)));
+                }
+                run_start = None;
+            },
+            _ => {},
+        }
+    }
+    entrances
+}
+
+/// Hierarchical pathfinding over the map's chunks. Each chunk of a level
+/// is looked at once for the portals on its edges and the steps between
+/// them, a long way is found over those first and only then walked out
+/// tile by tile a chunk at a time. Chunks are worked out when a search
+/// first reaches them and forgotten when one of their tiles turns solid or
+/// open.
+#[derive(Default)]
+pub struct PathGraph {
+    clusters: HashMap<ClusterKey, Cluster>,
+}
+
+impl PathGraph {
+
+    pub fn new() -> PathGraph {
+        PathGraph { clusters: HashMap::new() }
+    }
+
+    /// How many chunks are worked out
+    pub fn cached(&self) -> usize {
+        self.clusters.len()
+    }
+
+    /// Forgets the chunks of tiles that turned solid or open, and the ones
+    /// across the edge from them when the tile is on it
+    pub fn update(&mut self, map: &GameMap, reshaped: &[(u32, u32, u32)]) {
+        let size = map.chunk_size;
+        let (width, _, _) = map.size();
+        let columns = width / size;
+        for &(x, y, z) in reshaped {
+            let (cx, cy) = (x / size, y / size);
+            self.clusters.remove(&(cx, cy, z));
+            if x % size == 0 {
+                self.clusters.remove(&((cx + columns - 1) % columns, cy, z));
+            }
+            if x % size == size - 1 {
+                self.clusters.remove(&((cx + 1) % columns, cy, z));
+            }
+            if y % size == 0 && cy > 0 {
+                self.clusters.remove(&(cx, cy - 1, z));
+            }
+            if y % size == size - 1 {
+                self.clusters.remove(&(cx, cy + 1, z));
+            }
+        }
+    }
+
+    /// Like pathfinding::find_path, but a way further than a chunk is
+    /// planned over the portals first. The graph has to be kept up to date
+    /// with the map's reshaped tiles. Footprints bigger than a tile, things
+    /// that don't walk and ways that blocked tiles get in the way of fall
+    /// back to the flat search.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path(&mut self, map: &mut GameMap, start: (u32, u32), goal: (u32, u32), z: u32,
+                     footprint: Footprint, movement: Movement, blocked: impl Fn(u32, u32) -> bool)
+        -> Option<Vec<(u32, u32)>> {
+
+        if footprint != Footprint::SINGLE || movement != Movement::WALKING
+           || pathfinding::distance(map, start, goal) <= map.chunk_size {
+            return pathfinding::find_path(map, start, goal, z, footprint, movement, blocked);
+        }
+        let waypoints = self.waypoints(map, start, goal, z)?;
+        let size = map.chunk_size;
+        let mut path = Vec::new();
+        let mut from = start;
+        for waypoint in waypoints {
+            // every leg either crosses an edge or stays in one chunk
+            let leg = if pathfinding::distance(map, from, waypoint) == 1 {
+                (!blocked(waypoint.0, waypoint.1)).then(|| vec![waypoint])
+            } else {
+                self.cluster(map, (from.0 / size, from.1 / size, z)).walk(from, waypoint, &blocked)
+            };
+            match leg {
+                Some(leg) => path.extend(leg),
+                None => return pathfinding::find_path(map, start, goal, z, footprint, movement, blocked),
+            }
+            from = waypoint;
+        }
+        Some(path)
+    }
+
+    fn cluster(&mut self, map: &mut GameMap, key: ClusterKey) -> &Cluster {
+        self.clusters.entry(key).or_insert_with(|| Cluster::build(map, key))
+    }
+
+    /// The portals a way from start to goal goes through, ending at goal,
+    /// found with A* over the chunks' portals
+    fn waypoints(&mut self, map: &mut GameMap, start: (u32, u32), goal: (u32, u32), z: u32)
+        -> Option<Vec<(u32, u32)>> {
+
+        let size = map.chunk_size;
+        let key = |(x, y): (u32, u32)| (x / size, y / size, z);
+        let goal_cluster = self.cluster(map, key(goal));
+        let to_goal: HashMap<(u32, u32), u32> = goal_cluster
+            .portal_steps(&goal_cluster.distances(goal, |_, _| false))
+            .into_iter()
+            .collect();
+        let start_cluster = self.cluster(map, key(start));
+        let start_steps = start_cluster.distances(start, |_, _| false);
+        let mut from_start = start_cluster.portal_steps(&start_steps);
+        if key(start) == key(goal) && start_steps[start_cluster.index(goal)] != u32::MAX {
+            from_start.push((goal, start_steps[start_cluster.index(goal)]));
+        }
+
+        let mut open = BinaryHeap::new();
+        let mut came_from = HashMap::new();
+        let mut cost = HashMap::new();
+        open.push(Reverse((pathfinding::distance(map, start, goal), start)));
+        cost.insert(start, 0);
+
+        while let Some(Reverse((_, current))) = open.pop() {
+            if current == goal {
+                return Some(pathfinding::walk_back(&came_from, start, goal));
+            }
+            if cost.len() > MAX_SEARCH {
+                return None;
+            }
+            let next_steps = if current == start {
+                from_start.clone()
+            } else {
+                let cluster = self.cluster(map, key(current));
+                let mut next_steps = cluster.edges.get(&current).cloned().unwrap_or_default();
+                next_steps.extend(cluster.links.get(&current).into_iter().flatten().map(|&across| (across, 1)));
+                if let Some(&steps) = to_goal.get(&current).filter(|_| key(current) == key(goal)) {
+                    next_steps.push((goal, steps));
+                }
+                next_steps
+            };
+            for (next, steps) in next_steps {
+                let next_cost = cost[&current] + steps;
+                if cost.get(&next).is_some_and(|&known| known <= next_cost) {
+                    continue;
+                }
+                cost.insert(next, next_cost);
+                came_from.insert(next, current);
+                open.push(Reverse((next_cost + pathfinding::distance(map, next, goal), next)));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::TileKind;
+
+    /// Walls in a room three chunks wide and one high at chunk (cx, cy),
+    /// with a wall down the middle of the middle chunk open only at the
+    /// bottom
+    fn wide_room(map: &mut GameMap, cx: u32, cy: u32, z: u32) -> (u32, u32, u32, u32) {
+        let size = map.chunk_size;
+        let (x, y, width, height) = (cx * size, cy * size, size * 3, size);
+        for ty in y..y + height {
+            for tx in x..x + width {
+                let mut tile = map.get_tile(tx, ty, z);
+                let border = tx == x || ty == y || tx == x + width - 1 || ty == y + height - 1;
+                let divider = tx == x + width / 2 && ty < y + height - 3;
+                if border || divider {
+                    tile.kind = TileKind::Wall;
+                } else {
+                    tile.dig();
+                }
+                map.set_tile(tx, ty, z, tile);
+            }
+        }
+        (x, y, width, height)
+    }
+
+    #[test]
+    fn test_path_graph_goes_around_the_divider() {
+        let mut map = GameMap::with_seed(10);
+        let z = 40;
+        let (x, y, width, _) = wide_room(&mut map, 16, 16, z);
+        let (start, goal) = ((x + 2, y + 2), (x + width - 3, y + 2));
+        let mut graph = PathGraph::new();
+
+        let path = graph.find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false).unwrap();
+        println!("{} steps over {} chunks", path.len(), graph.cached());
+        assert_eq!(path.last(), Some(&goal));
+        let mut previous = start;
+        for &step in &path {
+            assert_eq!(pathfinding::distance(&map, previous, step), 1);
+            assert!(!map.get_tile(step.0, step.1, z).is_solid());
+            previous = step;
+        }
+        assert!(path.len() > (width as usize - 5) + 2 * 55);
+
+        // a gap at the top makes the way much shorter once the chunk is redone
+        let mut tile = map.get_tile(x + width / 2, y + 2, z);
+        tile.dig();
+        map.set_tile(x + width / 2, y + 2, z, tile);
+        let reshaped = map.take_reshaped();
+        graph.update(&map, &reshaped);
+        let shorter = graph.find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false).unwrap();
+        println!("{} steps through the gap", shorter.len());
+        assert!(shorter.len() < width as usize + 8);
+    }
+}
@@ -0,0 +1,226 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::entity::{Footprint, Movement};
+use crate::game_map::GameMap;
+
+/// Most tiles a search looks at before giving up, the world is far too
+/// big to search all of it for an unreachable goal
+pub const MAX_SEARCH: usize = 4096;
+
+/// Finds the shortest path for something of the given footprint and
+/// movement from start to goal on level z, stepping between orthogonal
+/// neighbors and wrapping around east to west. Positions are the top left
+/// tile of the footprint, every tile it covers has to be one the movement
+/// can go on that blocked doesn't rule out. Returns the steps after start,
+/// ending at goal.
+pub fn find_path(map: &mut GameMap, start: (u32, u32), goal: (u32, u32), z: u32,
+                 footprint: Footprint, movement: Movement, blocked: impl Fn(u32, u32) -> bool)
+    -> Option<Vec<(u32, u32)>> {
+
+    if start == goal {
+        return Some(Vec::new());
+    }
+    if !fits(map, goal, z, footprint, movement, &blocked) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::new();
+    let mut cost = HashMap::new();
+    open.push(Reverse((distance(map, start, goal), start)));
+    cost.insert(start, 0);
+
+    while let Some(Reverse((_, current))) = open.pop() {
+        if current == goal {
+            return Some(walk_back(&came_from, start, goal));
+        }
+        if cost.len() > MAX_SEARCH {
+            return None;
+        }
+        let next_cost = cost[&current] + 1;
+        for (x, y, _) in map.neighbors(current.0, current.1, z) {
+            let next = (x, y);
+            if cost.get(&next).is_some_and(|&known| known <= next_cost)
+               || !fits(map, next, z, footprint, movement, &blocked) {
+                continue;
+            }
+            cost.insert(next, next_cost);
+            came_from.insert(next, current);
+            open.push(Reverse((next_cost + distance(map, next, goal), next)));
+        }
+    }
+    None
+}
+
+/// Finds the closest position is_goal accepts that something of the given
+/// footprint and movement can get to from start on level z, searching
+/// outwards step by step. Returns the steps after start, ending there.
+pub fn find_nearest(map: &mut GameMap, start: (u32, u32), z: u32, footprint: Footprint, movement: Movement,
+                    blocked: impl Fn(u32, u32) -> bool,
+                    is_goal: impl Fn(&GameMap, u32, u32) -> bool)
+    -> Option<Vec<(u32, u32)>> {
+
+    let mut open = VecDeque::new();
+    let mut came_from = HashMap::new();
+    open.push_back(start);
+    came_from.insert(start, start);
+
+    while let Some(current) = open.pop_front() {
+        if current != start && is_goal(map, current.0, current.1) {
+            return Some(walk_back(&came_from, start, current));
+        }
+        if came_from.len() > MAX_SEARCH {
+            return None;
+        }
+        for (x, y, _) in map.neighbors(current.0, current.1, z) {
+            let next = (x, y);
+            if came_from.contains_key(&next) || !fits(map, next, z, footprint, movement, &blocked) {
+                continue;
+            }
+            came_from.insert(next, current);
+            open.push_back(next);
+        }
+    }
+    None
+}
+
+/// Whether every tile of the footprint placed at pos is one the movement
+/// can go on, open floor for walkers
+pub fn fits(map: &mut GameMap, pos: (u32, u32), z: u32, footprint: Footprint, movement: Movement,
+            blocked: &impl Fn(u32, u32) -> bool) -> bool {
+    let (_, max_y, _) = map.size();
+    footprint.offsets().all(|(dx, dy)| {
+        let x = map.wrap_x(pos.0 as i64 + dx as i64);
+        let y = pos.1 + dy;
+        y < max_y && movement.can_enter(&map.get_tile(x, y, z)) && !blocked(x, y)
+    })
+}
+
+/// Steps between the two positions ignoring obstacles, the short way
+/// around the world
+pub fn distance(map: &GameMap, a: (u32, u32), b: (u32, u32)) -> u32 {
+    let east = map.column_from(b.0, a.0);
+    let west = map.column_from(a.0, b.0);
+    east.min(west) + (a.1 as i64 - b.1 as i64).unsigned_abs() as u32
+}
+
+pub(crate) fn walk_back(came_from: &HashMap<(u32, u32), (u32, u32)>,
+             start: (u32, u32), goal: (u32, u32)) -> Vec<(u32, u32)> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while let Some(&previous) = came_from.get(&current) {
+        if previous == start {
+            break;
+        }
+        path.push(previous);
+        current = previous;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Walls in a 12 x 7 room at (x, y) with a wall down its middle that
+    /// has a gap of the given width at the top
+    fn room_with_gap(map: &mut GameMap, x: u32, y: u32, z: u32, gap: u32) {
+        for ty in y..y + 7 {
+            for tx in x..x + 12 {
+                let mut tile = map.get_tile(tx, ty, z);
+                let border = tx == x || ty == y || tx == x + 11 || ty == y + 6;
+                let divider = tx == x + 6 && ty > y + gap;
+                if border || divider {
+                    tile.kind = crate::game_map::TileKind::Wall;
+                } else {
+                    tile.dig();
+                }
+                map.set_tile(tx, ty, z, tile);
+            }
+        }
+    }
+
+    #[test]
+    fn test_footprint_needs_a_wide_enough_gap() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 40);
+        room_with_gap(&mut map, x, y, z, 1);
+        let (start, goal) = ((x + 2, y + 3), (x + 8, y + 3));
+
+        let path = find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false);
+        println!("single tile: {:?}", path);
+        assert_eq!(path.as_ref().and_then(|path| path.last()), Some(&goal));
+        assert!(path.unwrap().iter().any(|&(px, py)| px == x + 6 && py == y + 1));
+
+        let wide = Footprint::new(2, 2);
+        assert!(find_path(&mut map, start, goal, z, wide, Movement::WALKING, |_, _| false).is_none());
+
+        room_with_gap(&mut map, x, y, z, 2);
+        let path = find_path(&mut map, start, goal, z, wide, Movement::WALKING, |_, _| false);
+        println!("two by two: {:?}", path);
+        assert!(path.is_some());
+    }
+
+    #[test]
+    fn test_blocked_tiles_are_avoided() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 40);
+        room_with_gap(&mut map, x, y, z, 1);
+        let (start, goal) = ((x + 2, y + 3), (x + 8, y + 3));
+        let gap = (x + 6, y + 1);
+        let path = find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING,
+                             |bx, by| (bx, by) == gap);
+        println!("{:?}", path);
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn test_nearest_goal_is_found_through_the_gap() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 40);
+        room_with_gap(&mut map, x, y, z, 1);
+        let start = (x + 2, y + 3);
+        // only the east half of the room counts, the way there is around the divider
+        let path = find_nearest(&mut map, start, z, Footprint::SINGLE, Movement::WALKING, |_, _| false,
+                                |_, gx, _| gx > x + 6);
+        println!("{:?}", path);
+        let path = path.unwrap();
+        assert_eq!(path.last(), Some(&(x + 7, y + 1)));
+        assert_eq!(path.len(), 7);
+    }
+
+    #[test]
+    fn test_movement_decides_the_way_across() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 40);
+        room_with_gap(&mut map, x, y, z, 0);
+        let (start, goal) = ((x + 2, y + 3), (x + 8, y + 3));
+        let mut rubble = map.get_tile(x + 6, y + 3, z);
+        rubble.collapse();
+        map.set_tile(x + 6, y + 3, z, rubble);
+
+        let flying = Movement { flying: true, ..Movement::WALKING };
+        let burrowing = Movement { burrowing: true, ..Movement::WALKING };
+        let aquatic = Movement { aquatic: true, ..Movement::WALKING };
+        assert!(find_path(&mut map, start, goal, z, Footprint::SINGLE, Movement::WALKING, |_, _| false).is_none());
+        let path = find_path(&mut map, start, goal, z, Footprint::SINGLE, flying, |_, _| false);
+        println!("flying: {:?}", path);
+        assert!(path.unwrap().contains(&(x + 6, y + 3)));
+        assert!(find_path(&mut map, start, goal, z, Footprint::SINGLE, burrowing, |_, _| false).is_some());
+
+        // the eel only swims where there's water, all the way to the goal
+        assert!(find_path(&mut map, start, goal, z, Footprint::SINGLE, aquatic, |_, _| false).is_none());
+        for tx in x + 1..x + 11 {
+            let mut tile = map.get_tile(tx, y + 1, z);
+            tile.dig();
+            tile.set_fluid(crate::game_map::MAX_FLUID);
+            map.set_tile(tx, y + 1, z, tile);
+        }
+        let water = (x + 2, y + 1);
+        let path = find_path(&mut map, water, (x + 8, y + 1), z, Footprint::SINGLE, aquatic, |_, _| false);
+        assert_eq!(path.map(|path| path.len()), Some(6));
+        assert!(find_path(&mut map, water, goal, z, Footprint::SINGLE, aquatic, |_, _| false).is_none());
+    }
+}
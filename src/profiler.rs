@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use enum_map::{Enum, EnumMap};
+
+/// How much of each new frame goes into the averages, lower is steadier
+const SMOOTHING: f64 = 0.1;
+
+/// The parts of a frame that are timed on their own
+#[derive(Clone, Copy, Debug, PartialEq, Enum)]
+pub enum System {
+    /// Everything update does outside of the simulation
+    Input,
+    /// A simulation tick, fluid and AI included
+    Simulation,
+    Fluid,
+    Ai,
+    RenderMap,
+    /// Everything draw does outside of the map
+    RenderUi,
+}
+
+impl System {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            System::Input => "input",
+            System::Simulation => "simulation",
+            System::Fluid => "  fluid",
+            System::Ai => "  ai",
+            System::RenderMap => "render map",
+            System::RenderUi => "render ui",
+        }
+    }
+}
+
+/// Started when a system starts, handed back to the profiler when it's done
+pub struct Timer {
+    system: System,
+    start: Instant,
+}
+
+impl Timer {
+
+    pub fn start(system: System) -> Timer {
+        Timer { system, start: Instant::now() }
+    }
+}
+
+/// Time spent in each system, smoothed over the last few frames so the
+/// numbers hold still long enough to read
+pub struct Profiler {
+    /// Summed over every update and draw since the last end_frame
+    frame: EnumMap<System, Duration>,
+    average_ms: EnumMap<System, f64>,
+}
+
+impl Profiler {
+
+    pub fn new() -> Profiler {
+        Profiler {
+            frame: EnumMap::default(),
+            average_ms: EnumMap::default(),
+        }
+    }
+
+    /// Adds the time since the timer started to its system
+    pub fn stop(&mut self, timer: Timer) -> Duration {
+        let elapsed = timer.start.elapsed();
+        self.frame[timer.system] += elapsed;
+        elapsed
+    }
+
+    /// Adds time measured somewhere a timer can't go to the system
+    pub fn record(&mut self, system: System, elapsed: Duration) {
+        self.frame[system] += elapsed;
+    }
+
+    /// Folds this frame's timings into the averages and starts the next
+    pub fn end_frame(&mut self) {
+        // update and draw are timed whole, what they ran is taken back out
+        self.frame[System::Input] = self.frame[System::Input]
+            .saturating_sub(self.frame[System::Simulation]);
+        self.frame[System::RenderUi] = self.frame[System::RenderUi]
+            .saturating_sub(self.frame[System::RenderMap]);
+        for (system, duration) in self.frame.iter_mut() {
+            let ms = duration.as_secs_f64() * 1000.0;
+            self.average_ms[system] = self.average_ms[system] * (1.0 - SMOOTHING)
+                + ms * SMOOTHING;
+            *duration = Duration::default();
+        }
+    }
+
+    /// A line per system for the debug overlay
+    pub fn lines(&self) -> Vec<String> {
+        self.average_ms.iter()
+            .map(|(system, ms)| format!("{:<11}{:>7.2} ms", system.name(), ms))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_are_smoothed() {
+        let mut profiler = Profiler::new();
+        profiler.frame[System::Fluid] = Duration::from_millis(10);
+        profiler.frame[System::Simulation] = Duration::from_millis(12);
+        profiler.frame[System::Input] = Duration::from_millis(15);
+        profiler.end_frame();
+        profiler.end_frame();
+        println!("{:?}", profiler.lines());
+        let expected = 10.0 * SMOOTHING * (1.0 - SMOOTHING);
+        assert!((profiler.average_ms[System::Fluid] - expected).abs() < 1e-9);
+        assert!((profiler.average_ms[System::Input] - expected * 0.3).abs() < 1e-9);
+        assert_eq!(profiler.average_ms[System::Ai], 0.0);
+        assert_eq!(profiler.lines()[2], format!("  fluid    {:>7.2} ms", expected));
+    }
+}
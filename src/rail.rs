@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::autotile;
+use crate::color_scheme::ColorName;
+use crate::entity::{Entities, EntityId};
+use crate::game_map::GameMap;
+
+/// Most stone a minecart holds
+pub const CART_CAPACITY: u32 = 10;
+/// Sim ticks a cart waits at a stop while it is loaded or unloaded
+pub const STOP_TICKS: u32 = 10;
+/// Dug out stone goes to the closest stop within this many tiles
+pub const COLLECT_RANGE: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+
+    pub fn offset(&self) -> (i64, i64) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        self.turn_right().turn_right()
+    }
+
+    pub fn turn_right(&self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
+
+    pub fn turn_left(&self) -> Direction {
+        self.opposite().turn_right()
+    }
+
+    /// The autotile bit of a connection in this direction
+    fn mask(&self) -> u8 {
+        match self {
+            Direction::North => autotile::NORTH,
+            Direction::East => autotile::EAST,
+            Direction::South => autotile::SOUTH,
+            Direction::West => autotile::WEST,
+        }
+    }
+}
+
+/// A piece of track laid on the floor
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Track {
+    Rail,
+    /// Carts stop here to pick up or drop off stone
+    Stop,
+    /// A junction, carts passing over it leave in the direction it is set
+    /// to if there is track that way
+    Switch(Direction),
+}
+
+impl Track {
+
+    pub fn color(&self) -> ColorName {
+        match self {
+            Track::Rail => ColorName::Fg3,
+            Track::Stop => ColorName::Yellow,
+            Track::Switch(_) => ColorName::LightRed,
+        }
+    }
+}
+
+/// A cart running on the tracks, drawn as its entity
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Minecart {
+    pub entity: EntityId,
+    pub heading: Direction,
+    pub cargo: u32,
+    /// Stop the cargo was picked up at, it is dropped off at any other
+    pub loaded_at: Option<(u32, u32, u32)>,
+    /// Ticks left standing at a stop
+    pub wait: u32,
+}
+
+/// The tracks laid in the colony, the carts running on them and the stone
+/// at each stop
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Railway {
+    tracks: BTreeMap<(u32, u32, u32), Track>,
+    /// Dug out stone waiting at each stop to be picked up
+    stops: BTreeMap<(u32, u32, u32), u32>,
+    /// Stone carts have dropped off at each stop
+    delivered: BTreeMap<(u32, u32, u32), u32>,
+    pub carts: Vec<Minecart>,
+}
+
+impl Railway {
+
+    pub fn new() -> Railway {
+        Railway {
+            tracks: BTreeMap::new(),
+            stops: BTreeMap::new(),
+            delivered: BTreeMap::new(),
+            carts: Vec::new(),
+        }
+    }
+
+    pub fn track_at(&self, x: u32, y: u32, z: u32) -> Option<Track> {
+        self.tracks.get(&(x, y, z)).cloned()
+    }
+
+    /// Lays track on the tile, replacing what was there. Laying a switch on
+    /// a switch turns it to the next direction instead.
+    pub fn lay(&mut self, x: u32, y: u32, z: u32, track: Track) {
+        let track = match (self.track_at(x, y, z), track) {
+            (Some(Track::Switch(set)), Track::Switch(_)) => Track::Switch(set.turn_right()),
+            _ => track,
+        };
+        if track == Track::Stop {
+            self.stops.entry((x, y, z)).or_insert(0);
+        } else {
+            self.stops.remove(&(x, y, z));
+            self.delivered.remove(&(x, y, z));
+        }
+        self.tracks.insert((x, y, z), track);
+    }
+
+    /// Tears up the track on the tile, the stone at a stop is lost with it
+    pub fn remove(&mut self, x: u32, y: u32, z: u32) {
+        self.tracks.remove(&(x, y, z));
+        self.stops.remove(&(x, y, z));
+        self.delivered.remove(&(x, y, z));
+    }
+
+    pub fn add_cart(&mut self, entity: EntityId, heading: Direction) {
+        self.carts.push(Minecart { entity, heading, cargo: 0, loaded_at: None, wait: 0 });
+    }
+
+    /// Stone waiting to be picked up at the stop, None if there is no stop
+    /// there
+    pub fn stone_at(&self, x: u32, y: u32, z: u32) -> Option<u32> {
+        self.stops.get(&(x, y, z)).cloned()
+    }
+
+    /// Stone carts have dropped off at the stop
+    pub fn delivered_at(&self, x: u32, y: u32, z: u32) -> u32 {
+        self.delivered.get(&(x, y, z)).cloned().unwrap_or(0)
+    }
+
+    /// Drops a piece of stone dug out at (x, y, z) off at the closest stop
+    /// on the level within COLLECT_RANGE, returns whether there was one
+    pub fn collect(&mut self, map: &GameMap, x: u32, y: u32, z: u32) -> bool {
+        self.collect_from(map, x, y, z, 1, 0)
+    }
+
+    /// Drops pieces of stone dug out at (x, y, z) off at the closest stop
+    /// on the level within COLLECT_RANGE and reach more tiles, returns
+    /// whether there was one
+    pub fn collect_from(&mut self, map: &GameMap, x: u32, y: u32, z: u32,
+                        pieces: u32, reach: u32) -> bool {
+        let closest = self.stops.keys()
+            .filter(|stop| stop.2 == z)
+            .map(|&stop| (distance(map, (x, y), (stop.0, stop.1)), stop))
+            .filter(|(distance, _)| *distance <= COLLECT_RANGE + reach)
+            .min();
+        match closest {
+            Some((_, stop)) => {
+                *self.stops.entry(stop).or_insert(0) += pieces;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// The glyph the track on the tile is drawn with, rails join up with
+    /// the track around them and switches show the way they are set
+    pub fn glyph(&self, map: &GameMap, x: u32, y: u32, z: u32) -> Option<char> {
+        let track = self.track_at(x, y, z)?;
+        let glyph = match track {
+            Track::Stop => '▓',
+            Track::Switch(set) => autotile::connected_wall_glyph(
+                set.mask() | set.opposite().mask()),
+            Track::Rail => {
+                let mask = [Direction::North, Direction::East, Direction::South, Direction::West]
+                    .iter()
+                    .filter(|direction| self.next_to(map, x, y, z, **direction).is_some())
+                    .fold(0, |mask, direction| mask | direction.mask());
+                autotile::connected_wall_glyph(mask)
+            },
+        };
+        Some(glyph)
+    }
+
+    /// Moves every cart a tile along the track, loading and unloading at
+    /// stops on the way. Carts don't run into entities, they wait for them
+    /// to move. Returns the stone unloaded and the stops it was dropped at.
+    pub fn step(&mut self, map: &GameMap, entities: &mut Entities)
+        -> Vec<((u32, u32, u32), u32)> {
+        let mut unloaded = Vec::new();
+        let mut carts = std::mem::take(&mut self.carts);
+        carts.retain(|cart| entities.get(cart.entity).is_some());
+        for cart in carts.iter_mut() {
+            if cart.wait > 0 {
+                cart.wait -= 1;
+                continue;
+            }
+            let (x, y, z) = match entities.get(cart.entity) {
+                Some(entity) => (entity.pos.x as u32, entity.pos.y as u32, entity.depth),
+                None => continue,
+            };
+            let heading = match self.route(map, x, y, z, cart.heading) {
+                Some(heading) => heading,
+                None => continue,
+            };
+            let (nx, ny) = match self.next_to(map, x, y, z, heading) {
+                Some(next) => next,
+                None => continue,
+            };
+            cart.heading = heading;
+            if entities.is_occupied(nx, ny, z, Some(cart.entity)) {
+                continue;
+            }
+            if let Some(entity) = entities.get_mut(cart.entity) {
+                entity.pos.x = nx as f32;
+                entity.pos.y = ny as f32;
+            }
+            if self.track_at(nx, ny, z) == Some(Track::Stop) {
+                if let Some(amount) = self.service(cart, (nx, ny, z)) {
+                    unloaded.push(((nx, ny, z), amount));
+                }
+            }
+        }
+        self.carts = carts;
+        unloaded
+    }
+
+    /// Drops the cargo off at the stop if it came from elsewhere, or picks
+    /// up what is waiting there if the cart is empty. Returns how much was
+    /// dropped off.
+    fn service(&mut self, cart: &mut Minecart, stop: (u32, u32, u32)) -> Option<u32> {
+        let waiting = self.stops.entry(stop).or_insert(0);
+        if cart.cargo > 0 && cart.loaded_at != Some(stop) {
+            let amount = cart.cargo;
+            *self.delivered.entry(stop).or_insert(0) += amount;
+            cart.cargo = 0;
+            cart.loaded_at = None;
+            cart.wait = STOP_TICKS;
+            return Some(amount);
+        }
+        if cart.cargo == 0 && *waiting > 0 {
+            cart.cargo = (*waiting).min(CART_CAPACITY);
+            *waiting -= cart.cargo;
+            cart.loaded_at = Some(stop);
+            cart.wait = STOP_TICKS;
+        }
+        None
+    }
+
+    /// Which way a cart heading this way leaves the tile: where a switch
+    /// on it points, else straight on, else around a bend, else back the
+    /// way it came at the end of the line
+    fn route(&self, map: &GameMap, x: u32, y: u32, z: u32, heading: Direction)
+        -> Option<Direction> {
+        if let Some(Track::Switch(set)) = self.track_at(x, y, z) {
+            if set != heading.opposite() && self.next_to(map, x, y, z, set).is_some() {
+                return Some(set);
+            }
+        }
+        [heading, heading.turn_right(), heading.turn_left(), heading.opposite()]
+            .iter()
+            .find(|direction| self.next_to(map, x, y, z, **direction).is_some())
+            .cloned()
+    }
+
+    /// The neighbor of the tile in the direction, if there is track on it
+    fn next_to(&self, map: &GameMap, x: u32, y: u32, z: u32, direction: Direction)
+        -> Option<(u32, u32)> {
+        let (dx, dy) = direction.offset();
+        let nx = map.wrap_x(x as i64 + dx);
+        let ny = y as i64 + dy;
+        if ny < 0 {
+            return None;
+        }
+        let ny = ny as u32;
+        self.tracks.contains_key(&(nx, ny, z)).then_some((nx, ny))
+    }
+}
+
+/// Steps between the tiles, the short way around the world
+fn distance(map: &GameMap, a: (u32, u32), b: (u32, u32)) -> u32 {
+    map.column_from(a.0, b.0).min(map.column_from(b.0, a.0)) + a.1.abs_diff(b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_scheme::ColorName;
+    use crate::entity::{Entity, Footprint, Movement};
+    use quicksilver::prelude::Vector;
+
+    fn cart_at(entities: &mut Entities, x: u32, y: u32) -> EntityId {
+        entities.insert(Entity {
+            pos: Vector::new(x, y),
+            depth: 0,
+            glyph: '↺',
+            color: ColorName::Orange,
+            hp: 0,
+            max_hp: 0,
+            gas_protection: true,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: false,
+            movement: Movement::WALKING,
+        })
+    }
+
+    fn position(entities: &Entities, id: EntityId) -> (u32, u32) {
+        let entity = entities.get(id).unwrap();
+        (entity.pos.x as u32, entity.pos.y as u32)
+    }
+
+    #[test]
+    fn test_cart_follows_bends_and_turns_back_at_the_end() {
+        let map = GameMap::with_seed(10);
+        let mut entities = Entities::new();
+        let mut railway = Railway::new();
+        // an L: east along y = 0, then south along x = 3
+        for x in 0..4 {
+            railway.lay(x, 0, 0, Track::Rail);
+        }
+        for y in 1..3 {
+            railway.lay(3, y, 0, Track::Rail);
+        }
+        let cart = cart_at(&mut entities, 0, 0);
+        railway.add_cart(cart, Direction::East);
+
+        let mut visited = Vec::new();
+        for _ in 0..7 {
+            railway.step(&map, &mut entities);
+            visited.push(position(&entities, cart));
+        }
+        println!("{:?}", visited);
+        assert_eq!(visited, vec![(1, 0), (2, 0), (3, 0), (3, 1), (3, 2), (3, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn test_switch_picks_the_branch() {
+        let map = GameMap::with_seed(10);
+        let mut entities = Entities::new();
+        let mut railway = Railway::new();
+        // a T: east along y = 1 with a branch north and south at x = 2
+        for x in 0..5 {
+            railway.lay(x, 1, 0, Track::Rail);
+        }
+        railway.lay(2, 0, 0, Track::Rail);
+        railway.lay(2, 2, 0, Track::Rail);
+        railway.lay(2, 1, 0, Track::Switch(Direction::North));
+        // laying a switch again turns it
+        railway.lay(2, 1, 0, Track::Switch(Direction::North));
+        println!("switch: {:?}", railway.track_at(2, 1, 0));
+        assert_eq!(railway.track_at(2, 1, 0), Some(Track::Switch(Direction::East)));
+        railway.lay(2, 1, 0, Track::Switch(Direction::North));
+
+        let cart = cart_at(&mut entities, 0, 1);
+        railway.add_cart(cart, Direction::East);
+        for _ in 0..3 {
+            railway.step(&map, &mut entities);
+        }
+        println!("{:?}", position(&entities, cart));
+        assert_eq!(position(&entities, cart), (2, 2));
+    }
+
+    #[test]
+    fn test_cart_carries_stone_between_stops() {
+        let map = GameMap::with_seed(10);
+        let mut entities = Entities::new();
+        let mut railway = Railway::new();
+        for x in 0..6 {
+            railway.lay(x, 0, 0, Track::Rail);
+        }
+        railway.lay(0, 0, 0, Track::Stop);
+        railway.lay(5, 0, 0, Track::Stop);
+        for _ in 0..3 {
+            assert!(railway.collect(&map, 1, 2, 0));
+        }
+        assert!(!railway.collect(&map, 40, 2, 0));
+
+        let cart = cart_at(&mut entities, 1, 0);
+        railway.add_cart(cart, Direction::West);
+        for _ in 0..40 {
+            railway.step(&map, &mut entities);
+        }
+        println!("first stop: {:?} second stop: {:?} cart: {:?}",
+                 railway.stone_at(0, 0, 0), railway.delivered_at(5, 0, 0), railway.carts[0]);
+        assert_eq!(railway.stone_at(0, 0, 0), Some(0));
+        assert_eq!(railway.delivered_at(5, 0, 0), 3);
+        assert_eq!(railway.delivered_at(0, 0, 0), 0);
+        assert_eq!(railway.carts[0].cargo, 0);
+    }
+}
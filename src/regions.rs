@@ -0,0 +1,234 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::game_map::GameMap;
+
+/// Most tiles a region is flooded out to. Past it the region is left
+/// unbounded, it could be joined to anything, the open surface is far too
+/// big to label all of.
+pub const MAX_REGION: usize = 16384;
+
+pub type RegionId = u32;
+
+/// Labels the open tiles of each level by which ones can be walked between,
+/// so a job nobody can get to is turned down without searching for a way
+/// there. Tiles are labeled by flooding out from the first one asked about.
+/// A tile that opens up joins the regions around it, one that turns solid
+/// drops its region to be flooded again when next asked about, since it
+/// might have split it.
+pub struct Regions {
+    /// The region each labeled tile was flooded into, merged ones are
+    /// found through parents
+    labels: HashMap<(u32, u32, u32), RegionId>,
+    /// Region each region was merged into, itself if it wasn't
+    parents: Vec<RegionId>,
+    /// Tiles of each region that's still labeled, empty for merged ones
+    members: Vec<Vec<(u32, u32, u32)>>,
+    /// Whether the flood of each region was cut off at MAX_REGION
+    unbounded: Vec<bool>,
+}
+
+impl Regions {
+
+    pub fn new() -> Regions {
+        Regions {
+            labels: HashMap::new(),
+            parents: Vec::new(),
+            members: Vec::new(),
+            unbounded: Vec::new(),
+        }
+    }
+
+    /// The region the open tile at (x, y, z) is in, flooding it if it isn't
+    /// labeled yet. None for solid tiles.
+    pub fn region(&mut self, map: &mut GameMap, x: u32, y: u32, z: u32) -> Option<RegionId> {
+        let x = map.wrap_x(x as i64);
+        if let Some(&label) = self.labels.get(&(x, y, z)) {
+            return Some(self.root(label));
+        }
+        if map.get_tile(x, y, z).is_solid() {
+            return None;
+        }
+        let region = self.parents.len() as RegionId;
+        self.parents.push(region);
+        self.members.push(Vec::new());
+        self.unbounded.push(false);
+        self.flood(map, (x, y, z), region);
+        Some(self.root(region))
+    }
+
+    /// Whether something at a could walk to b on level z. It's only false
+    /// when they're known to be apart, anything about unbounded regions or
+    /// solid tiles is taken to be reachable.
+    pub fn connected(&mut self, map: &mut GameMap, a: (u32, u32), b: (u32, u32), z: u32) -> bool {
+        match (self.region(map, a.0, a.1, z), self.region(map, b.0, b.1, z)) {
+            (Some(a), Some(b)) => a == b || self.unbounded[a as usize] || self.unbounded[b as usize],
+            _ => true,
+        }
+    }
+
+    /// Whether something at from could get next to the tile at to, to dig
+    /// it out
+    pub fn reaches_beside(&mut self, map: &mut GameMap, from: (u32, u32), to: (u32, u32), z: u32) -> bool {
+        for (x, y, _) in map.neighbors(to.0, to.1, z) {
+            if !map.get_tile(x, y, z).is_solid() && self.connected(map, from, (x, y), z) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Catches up with tiles that turned solid or open
+    pub fn update(&mut self, map: &mut GameMap, reshaped: &[(u32, u32, u32)]) {
+        for &(x, y, z) in reshaped {
+            if map.get_tile(x, y, z).is_solid() {
+                if let Some(&label) = self.labels.get(&(x, y, z)) {
+                    let root = self.root(label);
+                    self.forget(root);
+                }
+                continue;
+            }
+            let neighbors: Vec<RegionId> = map.neighbors(x, y, z).into_iter()
+                .filter_map(|tile| self.labels.get(&tile).copied())
+                .collect();
+            if let Some(&first) = neighbors.first() {
+                let region = self.root(first);
+                for other in neighbors {
+                    self.union(region, other);
+                }
+                // open tiles the new one leads to are flooded into it too
+                let region = self.root(region);
+                self.flood(map, (x, y, z), region);
+            }
+        }
+    }
+
+    fn root(&mut self, mut region: RegionId) -> RegionId {
+        while self.parents[region as usize] != region {
+            let parent = self.parents[region as usize];
+            self.parents[region as usize] = self.parents[parent as usize];
+            region = parent;
+        }
+        region
+    }
+
+    fn union(&mut self, a: RegionId, b: RegionId) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a == b {
+            return;
+        }
+        // the smaller one moves into the bigger
+        let (into, from) = if self.members[a as usize].len() >= self.members[b as usize].len() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parents[from as usize] = into;
+        let moved = std::mem::take(&mut self.members[from as usize]);
+        self.members[into as usize].extend(moved);
+        let unbounded = self.unbounded[from as usize] || self.members[into as usize].len() > MAX_REGION;
+        self.unbounded[into as usize] |= unbounded;
+    }
+
+    /// Drops the labels of the region so it's flooded again
+    fn forget(&mut self, region: RegionId) {
+        for tile in std::mem::take(&mut self.members[region as usize]) {
+            self.labels.remove(&tile);
+        }
+        self.unbounded[region as usize] = false;
+    }
+
+    /// Labels the open tiles reachable from start with region, joining the
+    /// regions it runs into, until the region is MAX_REGION tiles big
+    fn flood(&mut self, map: &mut GameMap, start: (u32, u32, u32), region: RegionId) {
+        let mut open = VecDeque::new();
+        open.push_back(start);
+        let mut region = region;
+        while let Some(tile) = open.pop_front() {
+            match self.labels.get(&tile).copied() {
+                Some(label) if self.root(label) == region && tile != start => continue,
+                Some(label) if self.root(label) != region => {
+                    self.union(region, label);
+                    region = self.root(region);
+                    continue;
+                },
+                Some(_) => {},
+                None => {
+                    self.labels.insert(tile, region);
+                    self.members[region as usize].push(tile);
+                },
+            }
+            if self.members[region as usize].len() >= MAX_REGION {
+                self.unbounded[region as usize] = true;
+                return;
+            }
+            let (x, y, z) = tile;
+            for next in map.neighbors(x, y, z) {
+                let unseen = self.labels.get(&next).is_none_or(|&label| self.root_of(label) != region);
+                if unseen && !map.get_tile(next.0, next.1, next.2).is_solid() {
+                    open.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// root without shortening the way there, for where self is borrowed
+    fn root_of(&self, mut region: RegionId) -> RegionId {
+        while self.parents[region as usize] != region {
+            region = self.parents[region as usize];
+        }
+        region
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::TileKind;
+
+    /// Digs out a 12 x 7 room at (x, y) walled in all around, split down
+    /// the middle by a wall
+    fn split_room(map: &mut GameMap, x: u32, y: u32, z: u32) {
+        for ty in y..y + 7 {
+            for tx in x..x + 12 {
+                let mut tile = map.get_tile(tx, ty, z);
+                let border = tx == x || ty == y || tx == x + 11 || ty == y + 6;
+                if border || tx == x + 6 {
+                    tile.kind = TileKind::Wall;
+                } else {
+                    tile.dig();
+                }
+                map.set_tile(tx, ty, z, tile);
+            }
+        }
+    }
+
+    #[test]
+    fn test_regions_join_and_split() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 40);
+        split_room(&mut map, x, y, z);
+        let mut regions = Regions::new();
+        let (west, east) = ((x + 2, y + 3), (x + 9, y + 3));
+
+        assert!(!regions.connected(&mut map, west, east, z));
+        assert!(regions.connected(&mut map, west, (x + 5, y + 5), z));
+        assert!(regions.reaches_beside(&mut map, west, (x + 6, y + 3), z));
+        assert!(!regions.reaches_beside(&mut map, west, (x + 11, y + 3), z));
+
+        // digging through the wall joins them
+        let mut tile = map.get_tile(x + 6, y + 3, z);
+        tile.dig();
+        map.set_tile(x + 6, y + 3, z, tile);
+        let reshaped = map.take_reshaped();
+        regions.update(&mut map, &reshaped);
+        println!("{:?} {:?}", regions.region(&mut map, west.0, west.1, z), regions.region(&mut map, east.0, east.1, z));
+        assert!(regions.connected(&mut map, west, east, z));
+
+        // and walling it up again splits them
+        tile.kind = TileKind::Pillar;
+        map.set_tile(x + 6, y + 3, z, tile);
+        let reshaped = map.take_reshaped();
+        regions.update(&mut map, &reshaped);
+        assert!(!regions.connected(&mut map, west, east, z));
+    }
+}
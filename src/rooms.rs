@@ -0,0 +1,299 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::designation::Designations;
+use crate::game_map::{GameMap, TileKind};
+
+/// Most tiles a room has, open space spilling past it isn't walled in
+pub const MAX_ROOM_SIZE: usize = 400;
+/// Ticks between the rooms being looked for again
+pub const ROOM_INTERVAL: u64 = 100;
+/// Ticks between colonists resting in a dormitory getting a hit point back
+pub const REST_INTERVAL: u64 = 200;
+
+/// What a room is for, each does something for the colony once it has
+/// what it needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoomType {
+    /// Wounded colonists in it heal
+    Dormitory,
+    /// Items stockpiled in it don't burn
+    Storehouse,
+    /// Building in it teaches crafting twice as fast
+    WorkshopHall,
+}
+
+impl RoomType {
+
+    pub const ALL: [RoomType; 3] = [
+        RoomType::Dormitory,
+        RoomType::Storehouse,
+        RoomType::WorkshopHall,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoomType::Dormitory => "dormitory",
+            RoomType::Storehouse => "storehouse",
+            RoomType::WorkshopHall => "workshop hall",
+        }
+    }
+
+    /// The type after it when cycling through them, None after the last
+    pub fn next(kind: Option<RoomType>) -> Option<RoomType> {
+        match kind {
+            None => Some(RoomType::Dormitory),
+            Some(RoomType::Dormitory) => Some(RoomType::Storehouse),
+            Some(RoomType::Storehouse) => Some(RoomType::WorkshopHall),
+            Some(RoomType::WorkshopHall) => None,
+        }
+    }
+
+    /// Fewest tiles the room needs
+    pub fn min_size(&self) -> usize {
+        match self {
+            RoomType::Dormitory => 9,
+            RoomType::Storehouse => 4,
+            RoomType::WorkshopHall => 16,
+        }
+    }
+
+    /// What the room still needs to count as this type, None if it has it
+    pub fn unmet(&self, room: &Room, map: &mut GameMap, designations: &Designations) -> Option<String> {
+        if room.size() < self.min_size() {
+            return Some(format!("needs {} tiles", self.min_size()));
+        }
+        match self {
+            RoomType::Dormitory if room.tiles.iter().any(|&(x, y)| {
+                let tile = map.get_tile(x, y, room.z);
+                tile.fluid > 0 || tile.gas > 0
+            }) => Some(String::from("needs to be dry and clear of gas")),
+            RoomType::Storehouse if !room.tiles.iter().any(|&(x, y)| designations.is_stockpile(x, y, room.z)) =>
+                Some(String::from("needs a stockpile in it")),
+            _ => None,
+        }
+    }
+}
+
+/// Open tiles on a level walled in all round
+#[derive(Clone, Debug, PartialEq)]
+pub struct Room {
+    pub z: u32,
+    /// Its tiles, sorted
+    pub tiles: Vec<(u32, u32)>,
+    pub kind: Option<RoomType>,
+    /// What it still needs to count as its type
+    pub unmet: Option<String>,
+    pub quality: u32,
+}
+
+impl Room {
+
+    pub fn size(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn contains(&self, x: u32, y: u32, z: u32) -> bool {
+        z == self.z && self.tiles.binary_search(&(x, y)).is_ok()
+    }
+
+    /// The type it counts as, None if it has none or is missing what
+    /// that needs
+    pub fn working_as(&self) -> Option<RoomType> {
+        self.kind.filter(|_| self.unmet.is_none())
+    }
+
+    /// How it's described, from its quality
+    pub fn quality_name(&self) -> &'static str {
+        match self.quality {
+            0..=14 => "cramped",
+            15..=29 => "plain",
+            30..=49 => "fine",
+            _ => "grand",
+        }
+    }
+}
+
+/// The open tiles reached from (x, y, z) and whether they're walled in,
+/// they aren't if it's solid, under the sky or opens out into more than
+/// MAX_ROOM_SIZE tiles, where it stops. There are no doors, so only rock,
+/// rubble and pillars wall a room in.
+fn fill(map: &mut GameMap, x: u32, y: u32, z: u32) -> (Vec<(u32, u32)>, bool) {
+    if map.is_sunlit(z) || map.get_tile(x, y, z).is_solid() {
+        return (vec![(x, y)], false);
+    }
+    let mut seen = BTreeSet::new();
+    seen.insert((x, y));
+    let mut frontier = vec![(x, y)];
+    while let Some((fx, fy)) = frontier.pop() {
+        for (nx, ny, nz) in map.neighbors(fx, fy, z) {
+            if !map.get_tile(nx, ny, nz).is_solid() && seen.insert((nx, ny)) {
+                if seen.len() > MAX_ROOM_SIZE {
+                    return (seen.into_iter().collect(), false);
+                }
+                frontier.push((nx, ny));
+            }
+        }
+    }
+    (seen.into_iter().collect(), true)
+}
+
+/// How good a room is, bigger is better up to a point and pillars holding
+/// it up count for something, while standing water and gas count against
+/// it
+pub fn quality(map: &mut GameMap, tiles: &[(u32, u32)], z: u32) -> u32 {
+    let mut pillars = BTreeSet::new();
+    let mut spoilt = 0;
+    for &(x, y) in tiles {
+        let tile = map.get_tile(x, y, z);
+        if tile.fluid > 0 || tile.gas > 0 {
+            spoilt += 1;
+        }
+        for (nx, ny, nz) in map.neighbors(x, y, z) {
+            if map.get_tile(nx, ny, nz).kind == TileKind::Pillar {
+                pillars.insert((nx, ny));
+            }
+        }
+    }
+    let score = tiles.len().min(40) as u32 + pillars.len() as u32 * 5;
+    score.saturating_sub(spoilt * 5)
+}
+
+/// The types players gave rooms and the rooms found last time they were
+/// looked for
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Rooms {
+    /// A tile of each room given a type, the room it's in gets the type
+    assigned: Vec<((u32, u32, u32), RoomType)>,
+    /// Found again when loaded
+    #[serde(skip)]
+    found: Vec<Room>,
+}
+
+impl Rooms {
+
+    pub fn new() -> Rooms {
+        Rooms::default()
+    }
+
+    /// Gives the room found around (x, y, z) the type, or takes its type
+    /// away. False if there's no room there.
+    pub fn assign(&mut self, x: u32, y: u32, z: u32, kind: Option<RoomType>) -> bool {
+        let room = match self.room_at(x, y, z) {
+            Some(room) => room.clone(),
+            None => return false,
+        };
+        self.assigned.retain(|&((ax, ay, az), _)| !room.contains(ax, ay, az));
+        if let Some(kind) = kind {
+            self.assigned.push(((x, y, z), kind));
+        }
+        for found in &mut self.found {
+            if found.contains(x, y, z) {
+                found.kind = kind;
+            }
+        }
+        true
+    }
+
+    /// Looks for the rooms around the dug out tiles and the ones given a
+    /// type, checking what each needs
+    pub fn find(&mut self, map: &mut GameMap, designations: &Designations) {
+        let mut seeds: Vec<(u32, u32, u32)> = self.assigned.iter().map(|(tile, _)| *tile).collect();
+        seeds.extend(map.edited_tiles().iter()
+            .filter(|edit| edit.tile.dug && !edit.tile.is_solid())
+            .map(|edit| (edit.x, edit.y, edit.z)));
+        let mut seen = BTreeSet::new();
+        let mut found = Vec::new();
+        for (x, y, z) in seeds {
+            if seen.contains(&(x, y, z)) {
+                continue;
+            }
+            // the tiles of open space that isn't walled in aren't in a
+            // room either
+            let (tiles, walled_in) = fill(map, x, y, z);
+            seen.extend(tiles.iter().map(|&(tx, ty)| (tx, ty, z)));
+            if !walled_in {
+                continue;
+            }
+            let mut room = Room { z, tiles, kind: None, unmet: None, quality: 0 };
+            room.kind = self.assigned.iter()
+                .find(|&&((ax, ay, az), _)| room.contains(ax, ay, az))
+                .map(|(_, kind)| *kind);
+            room.unmet = room.kind.and_then(|kind| kind.unmet(&room, map, designations));
+            room.quality = quality(map, &room.tiles, z);
+            found.push(room);
+        }
+        self.found = found;
+    }
+
+    pub fn rooms(&self) -> &[Room] {
+        &self.found
+    }
+
+    pub fn room_at(&self, x: u32, y: u32, z: u32) -> Option<&Room> {
+        self.found.iter().find(|room| room.contains(x, y, z))
+    }
+
+    /// The type the room around (x, y, z) counts as, if it has what that
+    /// needs
+    pub fn working_at(&self, x: u32, y: u32, z: u32) -> Option<RoomType> {
+        self.room_at(x, y, z).and_then(|room| room.working_as())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::Area;
+
+    #[test]
+    fn test_rooms_found_and_given_types() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 20);
+        for ty in y..y + 5 {
+            for tx in x..x + 9 {
+                let mut tile = map.get_tile(tx, ty, z);
+                let wall = tx == x || ty == y || tx >= x + 7 || ty == y + 4;
+                if wall {
+                    tile.kind = TileKind::Wall;
+                } else {
+                    tile.dig();
+                }
+                tile.set_fluid(0);
+                tile.gas = 0;
+                map.set_tile(tx, ty, z, tile);
+            }
+        }
+        let mut designations = Designations::new();
+        let mut rooms = Rooms::new();
+        rooms.find(&mut map, &designations);
+        println!("{:?}", rooms.rooms());
+        assert_eq!(rooms.rooms().len(), 1);
+        let room = rooms.room_at(x + 3, y + 2, z).unwrap();
+        assert_eq!(room.size(), 18);
+        assert_eq!(room.quality_name(), "plain");
+        assert!(rooms.room_at(x, y, z).is_none());
+
+        assert!(rooms.assign(x + 1, y + 1, z, Some(RoomType::Storehouse)));
+        rooms.find(&mut map, &designations);
+        assert_eq!(rooms.room_at(x + 6, y + 3, z).unwrap().unmet.as_deref(), Some("needs a stockpile in it"));
+        assert_eq!(rooms.working_at(x + 6, y + 3, z), None);
+        designations.add_stockpile(Area { x: x + 1, y: y + 1, width: 2, height: 2, z });
+        rooms.find(&mut map, &designations);
+        assert_eq!(rooms.working_at(x + 6, y + 3, z), Some(RoomType::Storehouse));
+        assert!(rooms.assign(x + 2, y + 2, z, Some(RoomType::WorkshopHall)));
+        assert_eq!(rooms.assigned.len(), 1);
+        rooms.find(&mut map, &designations);
+        assert_eq!(rooms.working_at(x + 1, y + 1, z), Some(RoomType::WorkshopHall));
+
+        // digging into the wall makes it bigger and keeps its type
+        let mut alcove = map.get_tile(x + 7, y + 2, z);
+        alcove.dig();
+        map.set_tile(x + 7, y + 2, z, alcove);
+        rooms.find(&mut map, &designations);
+        assert_eq!(rooms.room_at(x + 7, y + 2, z).unwrap().size(), 19);
+        assert_eq!(rooms.working_at(x + 7, y + 2, z), Some(RoomType::WorkshopHall));
+        assert!(!rooms.assign(x, y, z, None));
+    }
+}
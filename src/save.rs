@@ -0,0 +1,307 @@
+use quicksilver::geom::Vector;
+use quicksilver::saving::{self, SaveError};
+use serde::{Deserialize, Serialize};
+
+use crate::chronicle::Chronicle;
+use crate::designation::Designations;
+use crate::difficulty::Difficulty;
+use crate::economy::Caravan;
+use crate::entity::{Entities, EntityId};
+use crate::equipment::Gear;
+use crate::events::Loss;
+use crate::game_map::{ExploredChunk, Tile, TileEdit};
+use crate::farming::Crop;
+use crate::fire::Fires;
+use crate::livestock::Animal;
+use crate::corpse::Corpse;
+use crate::status::Afflictions;
+use crate::rail::Railway;
+use crate::rooms::Rooms;
+use crate::squad::Squad;
+use crate::stats::Stats;
+use crate::trends::Trends;
+use crate::undo::UndoStack;
+use crate::weather::Forecast;
+
+pub const APP_NAME: &str = "janus7-mining-colony";
+
+/// The slot of a colony from before there were slots, and of the first one
+pub const SAVE_PROFILE: &str = "colony";
+/// Where the list of save slots is kept
+const SLOTS_PROFILE: &str = "slots";
+/// Slots made by the player are kept under this plus their name
+const SLOT_PREFIX: &str = "slot-";
+/// Written when the window is closed with unsaved changes, so progress
+/// survives even if the window goes away before the player can answer
+pub const AUTOSAVE_PROFILE: &str = "autosave";
+/// The slot the autosaved colony belongs to
+const AUTOSAVE_SLOT_PROFILE: &str = "autosave-slot";
+
+/// Everything needed to restore a colony. The terrain itself is not stored,
+/// it is regenerated from random_seed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveData {
+    pub random_seed: u32,
+    pub entities: Entities,
+    /// The colonist the player steers, None when saved overseeing the colony
+    pub player_id: Option<EntityId>,
+    pub camera_x: f32,
+    pub camera_y: f32,
+    pub camera_z: u32,
+    #[serde(default)]
+    pub camera_follow: Option<EntityId>,
+    /// Changed tiles from saves made before edited_tiles, read but not
+    /// written
+    #[serde(default, skip_serializing)]
+    pub tile_edits: Vec<OldTileEdit>,
+    #[serde(default)]
+    pub edited_tiles: Vec<TileEdit>,
+    #[serde(default)]
+    pub active_fluids: Vec<(u32, u32, u32)>,
+    #[serde(default)]
+    pub active_gas: Vec<(u32, u32, u32)>,
+    #[serde(default)]
+    pub pending_collapses: Vec<((u32, u32, u32), u64)>,
+    #[serde(default)]
+    pub designations: Designations,
+    #[serde(default)]
+    pub railway: Railway,
+    #[serde(default)]
+    pub tick: u64,
+    #[serde(default)]
+    pub stats: Stats,
+    /// The colony's history, kept by each player like their stats
+    #[serde(default)]
+    pub chronicle: Chronicle,
+    /// Tiles any colonist has seen
+    #[serde(default)]
+    pub explored_chunks: Vec<ExploredChunk>,
+    #[serde(default)]
+    pub squad: Squad,
+    /// Where entities sent somewhere are still headed
+    #[serde(default)]
+    pub paths: Vec<(EntityId, Vec<(u32, u32)>)>,
+    #[serde(default)]
+    pub undo: UndoStack,
+    #[serde(default)]
+    pub colony_name: String,
+    #[serde(default)]
+    pub lost: Option<Loss>,
+    /// Saves from before there were difficulties are Standard
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    /// Whether the colony was on alert
+    #[serde(default)]
+    pub alert: bool,
+    /// Tools and armor lying about, by the entity showing each
+    #[serde(default)]
+    pub gear: Vec<(EntityId, Gear)>,
+    /// Creatures that can be kept, wild or tame, by their entity
+    #[serde(default)]
+    pub livestock: Vec<(EntityId, Animal)>,
+    /// Items lying about that aren't gear, by the entity showing each
+    #[serde(default)]
+    pub items: Vec<(EntityId, String)>,
+    /// The trade caravan visiting, if one is
+    #[serde(default)]
+    pub caravan: Option<Caravan>,
+    /// What ails each entity suffering from something
+    #[serde(default)]
+    pub afflictions: Vec<(EntityId, Afflictions)>,
+    /// The dead lying about, by their entity
+    #[serde(default)]
+    pub corpses: Vec<(EntityId, Corpse)>,
+    /// The corpse each colonist is carrying off to a refuse zone
+    #[serde(default)]
+    pub carrying: Vec<(EntityId, EntityId)>,
+    /// What's planted on the farm plots, by tile
+    #[serde(default)]
+    pub crops: Vec<((u32, u32, u32), Crop)>,
+    /// Tiles with a pump on them
+    #[serde(default)]
+    pub pumps: Vec<(u32, u32, u32)>,
+    /// Every fire burning
+    #[serde(default)]
+    pub fires: Fires,
+    /// Colonists carrying water to a fire
+    #[serde(default)]
+    pub buckets: Vec<EntityId>,
+    /// The weather on the surface and what's coming
+    #[serde(default)]
+    pub weather: Forecast,
+    /// The types players gave rooms, the rooms are found again
+    #[serde(default)]
+    pub rooms: Rooms,
+    /// What the colony made and used up each day
+    #[serde(default)]
+    pub trends: Trends,
+}
+
+impl SaveData {
+
+    /// Every changed tile with where it is, however the save kept them
+    pub fn edits(&self) -> Vec<TileEdit> {
+        self.tile_edits.iter().map(OldTileEdit::edit)
+            .chain(self.edited_tiles.iter().copied())
+            .collect()
+    }
+}
+
+/// A changed tile as saved when tiles still carried where they are
+#[derive(Debug, Deserialize)]
+pub struct OldTileEdit {
+    pos: Vector,
+    depth: u32,
+    #[serde(flatten)]
+    tile: Tile,
+}
+
+impl OldTileEdit {
+
+    fn edit(&self) -> TileEdit {
+        TileEdit { x: self.pos.x as u32, y: self.pos.y as u32, z: self.depth, tile: self.tile }
+    }
+}
+
+pub fn save_game(profile: &str, data: &SaveData) -> Result<(), SaveError> {
+    saving::save(APP_NAME, profile, data)
+}
+
+pub fn load_game(profile: &str) -> Result<SaveData, SaveError> {
+    saving::load(APP_NAME, profile)
+}
+
+/// A small picture of the map around the camera, taken when the slot was
+/// saved
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA, row by row
+    pub pixels: Vec<u8>,
+}
+
+/// What the save screen shows about a slot, without loading the colony
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SlotInfo {
+    /// The colony's name, which is the slot's too
+    pub name: String,
+    /// Where the colony itself is saved
+    pub profile: String,
+    pub play_time_secs: u64,
+    pub tick: u64,
+    /// Seconds since the epoch
+    pub saved_at: u64,
+    #[serde(default)]
+    pub thumbnail: Thumbnail,
+}
+
+/// The clock slots are stamped with, seconds since the epoch
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// The save profile a slot named name is kept in, names that differ only
+/// in case or punctuation share one
+pub fn slot_profile(name: &str) -> String {
+    let name: String = name.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    format!("{}{}", SLOT_PREFIX, name)
+}
+
+/// Every slot, the most recently saved first
+pub fn load_slots() -> Vec<SlotInfo> {
+    let mut slots: Vec<SlotInfo> = saving::load(APP_NAME, SLOTS_PROFILE).unwrap_or_default();
+    slots.sort_by_key(|slot| std::cmp::Reverse(slot.saved_at));
+    slots
+}
+
+/// Saves the colony into the slot and lists it first
+pub fn save_slot(info: SlotInfo, data: &SaveData) -> Result<(), SaveError> {
+    save_game(&info.profile, data)?;
+    let slots = with_slot(load_slots(), info);
+    saving::save(APP_NAME, SLOTS_PROFILE, &slots)
+}
+
+/// Takes the slot off the list and clears the colony saved in it
+pub fn delete_slot(profile: &str) -> Result<(), SaveError> {
+    let slots = without_slot(load_slots(), profile);
+    saving::save(APP_NAME, SLOTS_PROFILE, &slots)?;
+    saving::save_raw(APP_NAME, profile, &[])
+}
+
+/// Saves the colony apart from its slot, for the next launch to offer to
+/// restore it
+pub fn autosave(info: &SlotInfo, data: &SaveData) -> Result<(), SaveError> {
+    save_game(AUTOSAVE_PROFILE, data)?;
+    saving::save(APP_NAME, AUTOSAVE_SLOT_PROFILE, info)
+}
+
+/// The slot of the colony autosaved when the window last closed, if the
+/// player never got to answer whether to save it
+pub fn load_autosave_slot() -> Option<SlotInfo> {
+    saving::load(APP_NAME, AUTOSAVE_SLOT_PROFILE).ok()
+}
+
+/// Clears the autosave once it's restored, discarded or saved over
+pub fn clear_autosave() -> Result<(), SaveError> {
+    saving::save_raw(APP_NAME, AUTOSAVE_SLOT_PROFILE, &[])?;
+    saving::save_raw(APP_NAME, AUTOSAVE_PROFILE, &[])
+}
+
+/// The list with info first, replacing what was listed for its profile
+fn with_slot(slots: Vec<SlotInfo>, info: SlotInfo) -> Vec<SlotInfo> {
+    let mut slots = without_slot(slots, &info.profile);
+    slots.insert(0, info);
+    slots
+}
+
+fn without_slot(mut slots: Vec<SlotInfo>, profile: &str) -> Vec<SlotInfo> {
+    slots.retain(|slot| slot.profile != profile);
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(name: &str, saved_at: u64) -> SlotInfo {
+        SlotInfo {
+            name: String::from(name),
+            profile: slot_profile(name),
+            play_time_secs: 0,
+            tick: 0,
+            saved_at,
+            thumbnail: Thumbnail::default(),
+        }
+    }
+
+    #[test]
+    fn test_slot_list() {
+        assert_eq!(slot_profile(" Old Mo's pit "), "slot-old-mo-s-pit");
+        assert_eq!(slot_profile("OLD MO'S PIT"), slot_profile("old mo's pit"));
+
+        let slots = with_slot(Vec::new(), slot("Pit", 1));
+        let slots = with_slot(slots, slot("Shaft", 2));
+        let slots = with_slot(slots, slot("pit", 3));
+        let names: Vec<_> = slots.iter().map(|slot| slot.name.as_str()).collect();
+        println!("{:?}", names);
+        assert_eq!(names, vec!["pit", "Shaft"]);
+        assert_eq!(without_slot(slots, "slot-shaft").len(), 1);
+    }
+
+    #[test]
+    fn test_old_tile_edits_keep_where_they_are() {
+        let json = r#"[{"pos": {"x": 70.0, "y": 5.0}, "depth": 3, "kind": "Floor", "glyph": ".",
+                        "color": "Stone5", "val": 0.8, "fluid": 0, "dug": true}]"#;
+        let old: Vec<OldTileEdit> = serde_json::from_str(json).unwrap();
+        let edit = old[0].edit();
+        println!("{:?}", edit);
+        assert_eq!((edit.x, edit.y, edit.z), (70, 5, 3));
+        assert!(edit.tile.dug);
+        assert_eq!(edit.tile.gas, 0);
+    }
+}
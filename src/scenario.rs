@@ -0,0 +1,328 @@
+use std::fs;
+use std::path::Path;
+
+use crate::difficulty::Difficulty;
+use crate::game_map::Layout;
+use crate::stats::Stats;
+
+/// Where scenarios are read from
+pub const SCENARIOS_DIR: &str = "scenarios";
+/// The scenario that walks new players through the game
+pub const TUTORIAL_FILE: &str = "tutorial.txt";
+
+/// What the player has to do to get past a step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    /// Press Return
+    Continue,
+    /// Scroll the view this many tiles away from where it was
+    Scroll(u32),
+    /// Look at another level
+    ChangeLevel,
+    /// Walk the player this many tiles away from where they were
+    Walk(u32),
+    /// Dig out this many tiles
+    Dig(u32),
+    /// Have this many tiles designated to be dug
+    Designate(u32),
+    /// Make a stockpile
+    Stockpile,
+}
+
+impl Goal {
+
+    /// Reads a goal from the words after step
+    fn parse(words: &[&str]) -> Result<Goal, String> {
+        let goal = *words.first().ok_or("the step has no goal")?;
+        let count = || -> Result<u32, String> {
+            let count = words.get(1).ok_or(format!("{} needs a count", goal))?;
+            count.parse().map_err(|_| format!("{:?} is not a count", count))
+        };
+        Ok(match goal {
+            "continue" => Goal::Continue,
+            "scroll" => Goal::Scroll(count()?),
+            "level" => Goal::ChangeLevel,
+            "walk" => Goal::Walk(count()?),
+            "dig" => Goal::Dig(count()?),
+            "designate" => Goal::Designate(count()?),
+            "stockpile" => Goal::Stockpile,
+            goal => return Err(format!("unknown goal {:?}", goal)),
+        })
+    }
+}
+
+/// A prompt shown until its goal is met
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub goal: Goal,
+    pub prompt: Vec<String>,
+}
+
+/// What a challenge asks of the colony, it's won once every one is met
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Victory {
+    /// Dig out this many tiles
+    Dig(u64),
+    /// Dig down to this level
+    Depth(u32),
+    /// Haul this much stone by cart
+    Haul(u64),
+    /// Last this many ticks
+    Survive(u64),
+}
+
+impl Victory {
+
+    fn parse(words: &[&str]) -> Result<Victory, String> {
+        let condition = *words.first().ok_or("win needs a condition")?;
+        let count = words.get(1).ok_or(format!("{} needs a number", condition))?;
+        let bad_count = |_| format!("{:?} is not a number", count);
+        Ok(match condition {
+            "dig" => Victory::Dig(count.parse().map_err(bad_count)?),
+            "depth" => Victory::Depth(count.parse().map_err(bad_count)?),
+            "haul" => Victory::Haul(count.parse().map_err(bad_count)?),
+            "survive" => Victory::Survive(count.parse().map_err(bad_count)?),
+            condition => return Err(format!("unknown win condition {:?}", condition)),
+        })
+    }
+
+    pub fn is_met(&self, stats: &Stats, tick: u64) -> bool {
+        match *self {
+            Victory::Dig(tiles) => stats.tiles_dug >= tiles,
+            Victory::Depth(z) => stats.deepest_level.is_some_and(|deepest| deepest >= z),
+            Victory::Haul(stone) => stats.stone_hauled >= stone,
+            Victory::Survive(ticks) => tick >= ticks,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Victory::Dig(tiles) => format!("dig out {} tiles", tiles),
+            Victory::Depth(z) => format!("dig down to level {}", z),
+            Victory::Haul(stone) => format!("haul {} stone by cart", stone),
+            Victory::Survive(ticks) => format!("last {} ticks", ticks),
+        }
+    }
+}
+
+/// Whether something placed at the start comes from the content packs'
+/// creatures or items
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlacementKind {
+    Creature,
+    Item,
+}
+
+/// A creature or item from the content packs the scenario starts with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub kind: PlacementKind,
+    pub id: String,
+    /// Tiles from where the squad lands
+    pub offset: (i64, i64),
+}
+
+/// A tutorial or challenge read from a text file: the map, what the
+/// colony starts with, the steps the player is led through and what wins
+/// it. Every line is optional, but there has to be a map or a seed and
+/// something to do.
+///
+/// ```text
+/// # comments start with a hash
+/// title Tutorial
+/// about Shown when picking the scenario, a line each
+/// seed 1234         rock grown from the seed, otherwise the map is all
+///                   there is and rock is all around it
+/// difficulty Brutal
+/// | #########       a row of the map, see Layout for the glyphs, the squad
+/// | #...@...#       lands on the @. With a seed the rows are dug into the
+/// | #########       generated rock.
+/// creature rock_worm 4 -1    a creature or item from the content packs,
+/// item ore 2 0               placed this far from where the squad lands
+/// step walk 3       a goal: continue, scroll n, level, walk n, dig n,
+/// > Walk around     designate n or stockpile, with the lines of its prompt
+/// win depth 40      won once all are met: dig n, depth z, haul n, survive n
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub title: String,
+    pub about: Vec<String>,
+    pub seed: Option<u32>,
+    pub difficulty: Option<Difficulty>,
+    pub rows: Vec<Vec<char>>,
+    /// Where in the rows the squad lands, if there are any
+    pub landing: Option<(u32, u32)>,
+    pub placements: Vec<Placement>,
+    pub steps: Vec<Step>,
+    pub victory: Vec<Victory>,
+}
+
+impl Scenario {
+
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        Scenario::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<Scenario, String> {
+        let mut scenario = Scenario {
+            title: String::from("Scenario"),
+            about: Vec::new(),
+            seed: None,
+            difficulty: None,
+            rows: Vec::new(),
+            landing: None,
+            placements: Vec::new(),
+            steps: Vec::new(),
+            victory: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let error = |reason: String| format!("line {}: {}", number + 1, reason);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let rest = || line.trim_start()
+                .split_once(char::is_whitespace)
+                .map_or("", |(_, rest)| rest.trim());
+            if let Some(row) = line.strip_prefix('|') {
+                scenario.rows.push(row.strip_prefix(' ').unwrap_or(row).chars().collect());
+                continue;
+            }
+            if let Some(prompt) = line.strip_prefix('>') {
+                let step = scenario.steps.last_mut()
+                    .ok_or_else(|| error(String::from("a prompt before any step")))?;
+                step.prompt.push(String::from(prompt.trim()));
+                continue;
+            }
+            match words.first().copied() {
+                None => {},
+                Some(comment) if comment.starts_with('#') => {},
+                Some("title") => scenario.title = String::from(rest()),
+                Some("about") => scenario.about.push(String::from(rest())),
+                Some("seed") => scenario.seed = Some(rest().parse()
+                    .map_err(|_| error(format!("{:?} is not a seed", rest())))?),
+                Some("difficulty") => scenario.difficulty = Some(Difficulty::from_name(rest())
+                    .ok_or_else(|| error(format!("{:?} is not a difficulty", rest())))?),
+                Some(kind @ ("creature" | "item")) => {
+                    let placement = Placement::parse(kind, &words[1..]).map_err(error)?;
+                    scenario.placements.push(placement);
+                },
+                Some("step") => {
+                    let goal = Goal::parse(&words[1..]).map_err(error)?;
+                    scenario.steps.push(Step { goal, prompt: Vec::new() });
+                },
+                Some("win") => scenario.victory.push(Victory::parse(&words[1..]).map_err(error)?),
+                Some(_) => return Err(error(format!("can't make out {:?}", line))),
+            }
+        }
+        scenario.landing = scenario.rows.iter().enumerate()
+            .find_map(|(y, row)| row.iter().position(|&c| c == '@').map(|x| (x as u32, y as u32)));
+        if !scenario.rows.is_empty() && scenario.landing.is_none() {
+            return Err(String::from("the map has no @ for the squad to land on"));
+        }
+        if scenario.rows.is_empty() && scenario.seed.is_none() {
+            return Err(String::from("there is no map or seed to play on"));
+        }
+        if scenario.steps.is_empty() && scenario.victory.is_empty() {
+            return Err(String::from("there is nothing to do, add a step or a win condition"));
+        }
+        Ok(scenario)
+    }
+
+    /// The map placed so the squad lands on site on level z, if there is
+    /// one
+    pub fn layout(&self, (x, y): (u32, u32), z: u32) -> Option<Layout> {
+        let (landing_x, landing_y) = self.landing?;
+        Some(Layout {
+            origin: (x - landing_x, y - landing_y, z),
+            rows: self.rows.clone(),
+        })
+    }
+
+    /// Whether every win condition is met, never for a scenario without
+    /// any
+    pub fn is_won(&self, stats: &Stats, tick: u64) -> bool {
+        !self.victory.is_empty() && self.victory.iter().all(|victory| victory.is_met(stats, tick))
+    }
+}
+
+impl Placement {
+
+    fn parse(kind: &str, words: &[&str]) -> Result<Placement, String> {
+        let kind = if kind == "item" { PlacementKind::Item } else { PlacementKind::Creature };
+        let (id, dx, dy) = match words {
+            [id, dx, dy] => (id, dx, dy),
+            _ => return Err(String::from("needs an id and how far from the landing, like rock_worm 4 -2")),
+        };
+        let offset = |n: &str| n.parse().map_err(|_| format!("{:?} is not a number", n));
+        Ok(Placement { kind, id: String::from(*id), offset: (offset(dx)?, offset(dy)?) })
+    }
+}
+
+/// Every scenario in dir by file name, with the errors of the ones that
+/// couldn't be read. A missing dir just means there are none.
+pub fn load_dir(dir: &Path) -> (Vec<Scenario>, Vec<String>) {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    let mut scenarios = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        match Scenario::load(&path) {
+            Ok(scenario) => scenarios.push(scenario),
+            Err(err) => errors.push(err),
+        }
+    }
+    (scenarios, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::{GameMap, TileKind};
+
+    #[test]
+    fn test_tutorial_scenario() {
+        let scenario = Scenario::parse(include_str!("../scenarios/tutorial.txt")).unwrap();
+        println!("{} steps, landing at {:?}", scenario.steps.len(), scenario.landing);
+        assert_eq!(scenario.title, "Tutorial");
+        assert_eq!(scenario.steps[1].goal, Goal::Scroll(8));
+        assert!(scenario.steps.iter().all(|step| !step.prompt.is_empty()));
+
+        let mut map = GameMap::with_seed(10).with_layout(scenario.layout((1000, 2000), 32).unwrap());
+        assert_eq!(map.get_tile(1000, 2000, 32).kind, TileKind::Floor);
+        assert_eq!(map.get_tile(1000, 1990, 32).kind, TileKind::Wall);
+        assert_eq!(map.get_tile(1000, 2000, 33).kind, TileKind::Wall);
+        assert_eq!(map.get_tile(1000, 1500, 32).kind, TileKind::Wall);
+
+        assert_eq!(Scenario::parse("| @\n> hi").unwrap_err(), "line 2: a prompt before any step");
+        assert_eq!(Scenario::parse("| @\nstep dig").unwrap_err(), "line 2: dig needs a count");
+        assert!(Scenario::parse("| .\nstep stockpile").is_err());
+    }
+
+    #[test]
+    fn test_challenge_scenario() {
+        let text = "title Deep\nseed 42\ndifficulty brutal\ncreature rock_worm 4 -2\nwin depth 40\nwin dig 10";
+        let scenario = Scenario::parse(text).unwrap();
+        println!("{:?}", scenario);
+        assert_eq!(scenario.seed, Some(42));
+        assert_eq!(scenario.difficulty, Some(Difficulty::Brutal));
+        assert_eq!(scenario.placements[0].kind, PlacementKind::Creature);
+        assert_eq!(scenario.placements[0].offset, (4, -2));
+        assert!(scenario.layout((10, 10), 5).is_none());
+
+        let mut stats = Stats::new();
+        stats.tiles_dug = 12;
+        assert!(!scenario.is_won(&stats, 100));
+        stats.deepest_level = Some(41);
+        assert!(scenario.is_won(&stats, 100));
+        assert_eq!(Scenario::parse("seed 1\nstep continue\nwin fly 3").unwrap_err(),
+                   "line 3: unknown win condition \"fly\"");
+        assert_eq!(Scenario::parse("seed 1").unwrap_err(),
+                   "there is nothing to do, add a step or a win condition");
+        assert!(Scenario::parse(include_str!("../scenarios/deep_dig.txt")).is_ok());
+    }
+}
@@ -0,0 +1,456 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+
+use crate::entity::{Entities, EntityId};
+use crate::events::{DeathCause, GameEvent};
+use crate::game_map::{GameMap, MAX_FLUID, MAX_GAS};
+use crate::status::Status;
+
+/// Where mods are loaded from at startup, every .rhai file in it is a script
+pub const SCRIPTS_DIR: &str = "scripts";
+/// A script function named command_<name> is run by the console command <name>
+const COMMAND_PREFIX: &str = "command_";
+/// Called with no arguments once the map is made, before any of it is
+/// generated or restored from a save
+const WORLDGEN_HOOK: &str = "on_worldgen";
+/// Most operations a single script call may take, so a runaway loop in a
+/// mod can't freeze the game
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// The parts of the game a script call works on. They are lent to the
+/// scripts for the length of the call.
+pub struct World<'a> {
+    pub map: &'a mut GameMap,
+    pub entities: &'a mut Entities,
+    pub player_id: Option<EntityId>,
+}
+
+/// What scripts did that the rest of the game has to catch up with
+#[derive(Default)]
+pub struct ScriptEffects {
+    /// Lines printed by scripts
+    pub printed: Vec<String>,
+    /// Events raised by scripts, for the event bus
+    pub events: Vec<GameEvent>,
+    /// Tiles dug out by scripts
+    pub dug: Vec<(u32, u32, u32)>,
+    /// Tiles whose water or gas scripts changed
+    pub changed: Vec<(u32, u32, u32)>,
+    /// Statuses scripts gave entities
+    pub afflicted: Vec<(EntityId, Status)>,
+    /// Tiles scripts set alight
+    pub ignited: Vec<(u32, u32, u32)>,
+}
+
+/// What the functions registered with the engine reach, the game's map and
+/// entities are swapped in here while a script runs
+struct ScriptWorld {
+    map: GameMap,
+    entities: Entities,
+    player_id: Option<EntityId>,
+    effects: ScriptEffects,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    functions: HashSet<String>,
+}
+
+/// Mods written in rhai. Scripts define functions the game calls: console
+/// commands, on_<event> reactions to events and on_worldgen.
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+    world: Rc<RefCell<ScriptWorld>>,
+}
+
+impl Scripts {
+
+    pub fn new() -> Scripts {
+        let world = Rc::new(RefCell::new(ScriptWorld {
+            map: GameMap::with_seed(0),
+            entities: Entities::new(),
+            player_id: None,
+            effects: ScriptEffects::default(),
+        }));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        register_api(&mut engine, &world);
+        Scripts {
+            engine,
+            scripts: Vec::new(),
+            world,
+        }
+    }
+
+    /// Loads every script in dir in name order, returning a line for each
+    /// one loaded or failed. A missing dir just means there are no mods.
+    pub fn load_dir(&mut self, dir: &Path) -> Vec<String> {
+        let mut paths: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        paths.sort();
+        paths.iter().map(|path| {
+            let name = path.display().to_string();
+            let loaded = fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|source| self.add(&name, &source));
+            match loaded {
+                Ok(()) => format!("loaded {}", name),
+                Err(err) => format!("error: {}: {}", name, err),
+            }
+        }).collect()
+    }
+
+    /// Compiles a script and adds it after the ones already loaded
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self.engine.compile(source).map_err(|err| err.to_string())?;
+        let functions = ast.iter_functions()
+            .map(|function| function.name.to_string())
+            .collect();
+        self.scripts.push(Script { name: String::from(name), ast, functions });
+        Ok(())
+    }
+
+    /// Console commands scripts add, with the script each one comes from
+    pub fn commands(&self) -> Vec<(String, &str)> {
+        self.scripts.iter().flat_map(|script| {
+            script.functions.iter()
+                .filter_map(|function| function.strip_prefix(COMMAND_PREFIX))
+                .map(move |command| (String::from(command), script.name.as_str()))
+        }).collect()
+    }
+
+    pub fn has_command(&self, command: &str) -> bool {
+        let function = format!("{}{}", COMMAND_PREFIX, command);
+        self.scripts.iter().any(|script| script.functions.contains(&function))
+    }
+
+    /// Runs a script console command with its arguments as an array of
+    /// strings, returning what the function returned
+    pub fn run_command(&self, world: World, command: &str, args: &[String])
+        -> Result<String, String> {
+        let function = format!("{}{}", COMMAND_PREFIX, command);
+        let script = self.scripts.iter()
+            .find(|script| script.functions.contains(&function))
+            .ok_or_else(|| format!("no script defines {}", function))?;
+        let args: Array = args.iter().cloned().map(Dynamic::from).collect();
+        let result = self.lend(world, || {
+            self.engine.call_fn::<Dynamic>(&mut Scope::new(), &script.ast, &function, (args,))
+        });
+        match result {
+            Ok(value) if value.is_unit() => Ok(String::new()),
+            Ok(value) => Ok(value.to_string()),
+            Err(err) => Err(format!("{}: {}", script.name, err)),
+        }
+    }
+
+    /// Calls the on_<event> function of every script that has one, for
+    /// example on_dug(x, y, z), on_hit(x, y, z, damage) or
+    /// on_hauled(x, y, z, amount). Returns the
+    /// errors scripts ran into.
+    pub fn on_event(&self, world: World, event: &GameEvent) -> Vec<String> {
+        let (x, y, z) = event.position();
+        let (x, y, z) = (x as i64, y as i64, z as i64);
+        match *event {
+            GameEvent::Dug { .. } => self.call_all(world, "on_dug", (x, y, z)),
+            GameEvent::Hit { damage, .. } =>
+                self.call_all(world, "on_hit", (x, y, z, damage as i64)),
+            GameEvent::WaterFlowed { .. } =>
+                self.call_all(world, "on_water_flowed", (x, y, z)),
+            GameEvent::CaveIn { .. } => self.call_all(world, "on_cave_in", (x, y, z)),
+            GameEvent::Died { .. } => self.call_all(world, "on_died", (x, y, z)),
+            GameEvent::Hauled { amount, .. } =>
+                self.call_all(world, "on_hauled", (x, y, z, amount as i64)),
+            GameEvent::ColonyLost { .. } => self.call_all(world, "on_colony_lost", (x, y, z)),
+            GameEvent::NoRoute { .. } => self.call_all(world, "on_no_route", (x, y, z)),
+            GameEvent::JobInterrupted { .. } => self.call_all(world, "on_job_interrupted", (x, y, z)),
+            GameEvent::HostileSighted { .. } => self.call_all(world, "on_hostile_sighted", (x, y, z)),
+            GameEvent::WornOut { .. } => self.call_all(world, "on_worn_out", (x, y, z)),
+            GameEvent::SkillUp { level, .. } => self.call_all(world, "on_skill_up", (x, y, z, level as i64)),
+            GameEvent::Tamed { .. } => self.call_all(world, "on_tamed", (x, y, z)),
+            GameEvent::Born { .. } => self.call_all(world, "on_born", (x, y, z)),
+            GameEvent::CaravanArrived { .. } => self.call_all(world, "on_caravan_arrived", (x, y, z)),
+            GameEvent::CaravanLeft { .. } => self.call_all(world, "on_caravan_left", (x, y, z)),
+            GameEvent::Incident { incident, .. } =>
+                self.call_all(world, "on_incident", (x, y, z, String::from(incident.name()))),
+        }
+    }
+
+    /// Lets scripts tweak a new or reloaded world before any of it is
+    /// generated, it runs every time so the result is the same after loading
+    pub fn on_worldgen(&self, world: World) -> Vec<String> {
+        self.call_all(world, WORLDGEN_HOOK, ())
+    }
+
+    /// Everything scripts did since the last call
+    pub fn take_effects(&self) -> ScriptEffects {
+        std::mem::take(&mut self.world.borrow_mut().effects)
+    }
+
+    fn call_all(&self, world: World, function: &str,
+                args: impl rhai::FuncArgs + Clone) -> Vec<String> {
+        let scripts: Vec<&Script> = self.scripts.iter()
+            .filter(|script| script.functions.contains(function))
+            .collect();
+        if scripts.is_empty() {
+            return Vec::new();
+        }
+        self.lend(world, || {
+            scripts.iter().filter_map(|script| {
+                self.engine.call_fn::<Dynamic>(
+                        &mut Scope::new(), &script.ast, function, args.clone())
+                    .err()
+                    .map(|err| format!("{}: {}", script.name, err))
+            }).collect()
+        })
+    }
+
+    /// Swaps the game's map and entities in for the scripts to use while
+    /// call runs, then back
+    fn lend<T>(&self, world: World, call: impl FnOnce() -> T) -> T {
+        let swap = |world: &mut World| {
+            let mut script_world = self.world.borrow_mut();
+            std::mem::swap(&mut script_world.map, world.map);
+            std::mem::swap(&mut script_world.entities, world.entities);
+            script_world.player_id = world.player_id;
+        };
+        let mut world = world;
+        swap(&mut world);
+        let result = call();
+        swap(&mut world);
+        result
+    }
+}
+
+/// A position passed in by a script as a tile on the map, x wraps around
+/// the world like everywhere else
+fn tile_position(map: &GameMap, x: i64, y: i64, z: i64) -> Option<(u32, u32, u32)> {
+    let (_, max_y, max_z) = map.size();
+    if y < 0 || z < 0 || y >= max_y as i64 || z >= max_z as i64 {
+        return None;
+    }
+    Some((map.wrap_x(x), y as u32, z as u32))
+}
+
+/// The functions scripts can call
+fn register_api(engine: &mut Engine, world: &Rc<RefCell<ScriptWorld>>) {
+    let w = world.clone();
+    engine.on_print(move |text| {
+        w.borrow_mut().effects.printed.push(String::from(text));
+    });
+
+    let w = world.clone();
+    engine.register_fn("is_solid", move |x: i64, y: i64, z: i64| {
+        let world = &mut *w.borrow_mut();
+        match tile_position(&world.map, x, y, z) {
+            Some((x, y, z)) => world.map.get_tile(x, y, z).is_solid(),
+            None => true,
+        }
+    });
+
+    let w = world.clone();
+    engine.register_fn("dig", move |x: i64, y: i64, z: i64| {
+        let world = &mut *w.borrow_mut();
+        let (x, y, z) = match tile_position(&world.map, x, y, z) {
+            Some(position) => position,
+            None => return false,
+        };
+        let mut tile = world.map.get_tile(x, y, z);
+        if !tile.is_solid() {
+            return false;
+        }
+        tile.dig();
+        world.map.set_tile(x, y, z, tile);
+        world.effects.dug.push((x, y, z));
+        world.effects.events.push(GameEvent::Dug { x, y, z });
+        true
+    });
+
+    let w = world.clone();
+    engine.register_fn("water", move |x: i64, y: i64, z: i64| {
+        let world = &mut *w.borrow_mut();
+        tile_position(&world.map, x, y, z)
+            .map_or(0, |(x, y, z)| world.map.get_tile(x, y, z).fluid as i64)
+    });
+
+    let w = world.clone();
+    engine.register_fn("set_water", move |x: i64, y: i64, z: i64, depth: i64| {
+        let world = &mut *w.borrow_mut();
+        if let Some((x, y, z)) = tile_position(&world.map, x, y, z) {
+            let mut tile = world.map.get_tile(x, y, z);
+            if !tile.is_solid() {
+                tile.set_fluid(depth.clamp(0, MAX_FLUID as i64) as u8);
+                world.map.set_tile(x, y, z, tile);
+                world.effects.changed.push((x, y, z));
+            }
+        }
+    });
+
+    let w = world.clone();
+    engine.register_fn("gas", move |x: i64, y: i64, z: i64| {
+        let world = &mut *w.borrow_mut();
+        tile_position(&world.map, x, y, z)
+            .map_or(0, |(x, y, z)| world.map.get_tile(x, y, z).gas as i64)
+    });
+
+    let w = world.clone();
+    engine.register_fn("set_gas", move |x: i64, y: i64, z: i64, amount: i64| {
+        let world = &mut *w.borrow_mut();
+        if let Some((x, y, z)) = tile_position(&world.map, x, y, z) {
+            let mut tile = world.map.get_tile(x, y, z);
+            if !tile.is_solid() {
+                tile.gas = amount.clamp(0, MAX_GAS as i64) as u8;
+                world.map.set_tile(x, y, z, tile);
+                world.effects.changed.push((x, y, z));
+            }
+        }
+    });
+
+    let w = world.clone();
+    engine.register_fn("water_table", move || w.borrow().map.water_table as i64);
+    let w = world.clone();
+    engine.register_fn("set_water_table", move |z: i64| {
+        w.borrow_mut().map.water_table = z.max(0) as u32;
+    });
+    let w = world.clone();
+    engine.register_fn("gas_depth", move || w.borrow().map.gas_depth as i64);
+    let w = world.clone();
+    engine.register_fn("set_gas_depth", move |z: i64| {
+        w.borrow_mut().map.gas_depth = z.max(0) as u32;
+    });
+
+    let w = world.clone();
+    engine.register_fn("entity_at", move |x: i64, y: i64, z: i64| {
+        let world = &*w.borrow();
+        tile_position(&world.map, x, y, z)
+            .and_then(|(x, y, z)| world.entities.iter()
+                .find(|(_, entity)| entity.is_at(x, y, z)))
+            .map_or(String::new(), |(_, entity)| entity.glyph.to_string())
+    });
+
+    let w = world.clone();
+    engine.register_fn("hurt", move |x: i64, y: i64, z: i64, damage: i64| {
+        let world = &mut *w.borrow_mut();
+        let (x, y, z) = match tile_position(&world.map, x, y, z) {
+            Some(position) => position,
+            None => return 0,
+        };
+        let mut hurt = 0;
+        for (_, entity) in world.entities.iter_mut() {
+            if entity.max_hp > 0 && entity.is_alive() && entity.is_at(x, y, z) {
+                entity.take_damage(damage as i32);
+                hurt += 1;
+                if !entity.is_alive() {
+                    world.effects.events.push(GameEvent::Died { x, y, z, cause: DeathCause::Injuries });
+                }
+            }
+        }
+        if hurt > 0 {
+            world.effects.events.push(GameEvent::Hit { x, y, z, damage: damage as i32 });
+        }
+        hurt
+    });
+
+    let w = world.clone();
+    engine.register_fn("afflict", move |x: i64, y: i64, z: i64, status: &str| {
+        let world = &mut *w.borrow_mut();
+        let (x, y, z, status) = match (tile_position(&world.map, x, y, z), Status::from_name(status)) {
+            (Some((x, y, z)), Some(status)) => (x, y, z, status),
+            _ => return 0,
+        };
+        let afflicted: Vec<EntityId> = world.entities.iter()
+            .filter(|(_, entity)| entity.max_hp > 0 && entity.is_alive() && entity.is_at(x, y, z))
+            .map(|(id, _)| id)
+            .collect();
+        let count = afflicted.len() as i64;
+        world.effects.afflicted.extend(afflicted.into_iter().map(|id| (id, status)));
+        count
+    });
+
+    let w = world.clone();
+    engine.register_fn("ignite", move |x: i64, y: i64, z: i64| {
+        let world = &mut *w.borrow_mut();
+        match tile_position(&world.map, x, y, z) {
+            Some(tile) => {
+                world.effects.ignited.push(tile);
+                true
+            },
+            None => false,
+        }
+    });
+
+    let w = world.clone();
+    engine.register_fn("player", move || {
+        let world = &*w.borrow();
+        match world.player_id.and_then(|id| world.entities.get(id)) {
+            Some(player) => {
+                let mut map = rhai::Map::new();
+                map.insert("x".into(), Dynamic::from(player.pos.x as i64));
+                map.insert("y".into(), Dynamic::from(player.pos.y as i64));
+                map.insert("z".into(), Dynamic::from(player.depth as i64));
+                map.insert("hp".into(), Dynamic::from(player.hp as i64));
+                Dynamic::from(map)
+            },
+            None => Dynamic::UNIT,
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_reach_the_map() {
+        let mut scripts = Scripts::new();
+        scripts.add("test", r#"
+            fn command_tunnel(args) {
+                let x = parse_int(args[0]);
+                for dx in 0..3 {
+                    dig(x + dx, 1000, 40);
+                }
+                print("dug");
+                "tunnel at " + x
+            }
+            fn on_dug(x, y, z) {
+                set_water(x, y, z, 2);
+            }
+        "#).unwrap();
+        assert!(scripts.has_command("tunnel"));
+        assert!(!scripts.has_command("dig"));
+
+        let mut map = GameMap::with_seed(10);
+        for x in 2000..2003 {
+            let mut tile = map.get_tile(x, 1000, 40);
+            tile.kind = crate::game_map::TileKind::Wall;
+            map.set_tile(x, 1000, 40, tile);
+        }
+        let mut entities = Entities::new();
+        let world = World { map: &mut map, entities: &mut entities, player_id: None };
+        let output = scripts.run_command(world, "tunnel", &[String::from("2000")]);
+        println!("{:?}", output);
+        assert_eq!(output, Ok(String::from("tunnel at 2000")));
+        assert!(!map.get_tile(2001, 1000, 40).is_solid());
+
+        let effects = scripts.take_effects();
+        assert_eq!(effects.printed, vec![String::from("dug")]);
+        assert_eq!(effects.events.len(), 3);
+
+        let world = World { map: &mut map, entities: &mut entities, player_id: None };
+        let errors = scripts.on_event(world, &effects.events[0]);
+        assert!(errors.is_empty());
+        assert_eq!(map.get_tile(2000, 1000, 40).fluid, 2);
+        assert_eq!(scripts.take_effects().changed, vec![(2000, 1000, 40)]);
+    }
+}
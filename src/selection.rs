@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+
+/// A rectangle of tiles on a single z-level
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Area {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub z: u32,
+}
+
+impl Area {
+
+    /// The smallest area containing both corners
+    pub fn from_corners(a: (u32, u32), b: (u32, u32), z: u32) -> Area {
+        Area {
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            width: a.0.max(b.0) - a.0.min(b.0) + 1,
+            height: a.1.max(b.1) - a.1.min(b.1) + 1,
+            z,
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32, z: u32) -> bool {
+        z == self.z
+            && x >= self.x && x < self.x + self.width
+            && y >= self.y && y < self.y + self.height
+    }
+
+    pub fn intersects(&self, other: &Area) -> bool {
+        self.z == other.z
+            && self.x < other.x + other.width && other.x < self.x + self.width
+            && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+
+    /// Every tile position in the area, row by row
+    pub fn tiles(&self) -> impl Iterator<Item = (u32, u32, u32)> {
+        let area = *self;
+        (area.y..area.y + area.height).flat_map(move |y| {
+            (area.x..area.x + area.width).map(move |x| (x, y, area.z))
+        })
+    }
+}
+
+/// What happens to an area once it has been selected
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AreaCommand {
+    Dig,
+    Stockpile,
+    Cancel,
+    Inspect,
+    Track,
+    /// Picks a single tile to send the player to
+    Travel,
+    /// Adds the area to the burrow
+    Burrow,
+    /// Takes the burrow areas touching it out of the burrow
+    Unburrow,
+    /// Picks the tile colonists retreat to on alert
+    Rally,
+    /// Turns the area into a pen for caught creatures
+    Pen,
+    /// Takes the pens touching it off the map
+    Unpen,
+    /// Marks the creatures in the area that can be kept to be caught
+    Capture,
+    /// Turns the area into a refuse zone the dead are carried to
+    Refuse,
+    /// Turns the area into a farm plot crops are planted on
+    Farm,
+    /// Takes the farm plots touching it off the map
+    Unfarm,
+    /// Copies the constructions and digs in the area into a blueprint
+    Copy,
+    /// Picks the tile the blueprint's top left corner is stamped on
+    Stamp,
+}
+
+impl AreaCommand {
+
+    pub fn name(&self) -> &str {
+        match self {
+            AreaCommand::Dig => "Designate dig",
+            AreaCommand::Stockpile => "Build stockpile",
+            AreaCommand::Cancel => "Cancel designations",
+            AreaCommand::Inspect => "Inspect",
+            AreaCommand::Track => "Lay track",
+            AreaCommand::Travel => "Travel to",
+            AreaCommand::Burrow => "Add to burrow",
+            AreaCommand::Unburrow => "Remove from burrow",
+            AreaCommand::Rally => "Place rally point",
+            AreaCommand::Pen => "Build pen",
+            AreaCommand::Unpen => "Remove pen",
+            AreaCommand::Capture => "Capture creatures",
+            AreaCommand::Refuse => "Place refuse zone",
+            AreaCommand::Farm => "Place farm plot",
+            AreaCommand::Unfarm => "Remove farm plot",
+            AreaCommand::Copy => "Copy to blueprint",
+            AreaCommand::Stamp => "Stamp blueprint",
+        }
+    }
+
+    /// Whether the command takes a single tile, picked with one Return or
+    /// click, rather than a rectangle
+    pub fn is_single_tile(&self) -> bool {
+        matches!(self, AreaCommand::Travel | AreaCommand::Rally | AreaCommand::Stamp)
+    }
+}
+
+/// A rectangle being picked for an area command, either by moving the
+/// cursor and pressing Return on both corners or by dragging the mouse
+pub struct Selection {
+    pub command: AreaCommand,
+    pub cursor: (u32, u32),
+    /// The first corner, once it has been marked
+    pub anchor: Option<(u32, u32)>,
+    pub z: u32,
+}
+
+impl Selection {
+
+    pub fn new(command: AreaCommand, x: u32, y: u32, z: u32) -> Selection {
+        Selection {
+            command,
+            cursor: (x, y),
+            anchor: None,
+            z,
+        }
+    }
+
+    pub fn move_cursor(&mut self, dx: i32, dy: i32, max_x: u32, max_y: u32) {
+        let x = (self.cursor.0 as i32 + dx).max(0).min(max_x as i32 - 1);
+        let y = (self.cursor.1 as i32 + dy).max(0).min(max_y as i32 - 1);
+        self.cursor = (x as u32, y as u32);
+    }
+
+    /// Marks the first corner at the cursor, or finishes the selection and
+    /// returns the area if the first corner was marked already. Single tile
+    /// commands finish on the cursor right away.
+    pub fn confirm(&mut self) -> Option<Area> {
+        if self.command.is_single_tile() {
+            self.anchor = None;
+            return Some(Area::from_corners(self.cursor, self.cursor, self.z));
+        }
+        match self.anchor.take() {
+            Some(anchor) => Some(Area::from_corners(anchor, self.cursor, self.z)),
+            None => {
+                self.anchor = Some(self.cursor);
+                None
+            },
+        }
+    }
+
+    /// The area between the first corner and the cursor, just the tile
+    /// under the cursor until a corner is marked
+    pub fn area(&self) -> Area {
+        Area::from_corners(self.anchor.unwrap_or(self.cursor), self.cursor, self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_area_from_corners() {
+        let area = Area::from_corners((5, 2), (3, 4), 7);
+        println!("{:?}", area);
+        assert_eq!(area, Area { x: 3, y: 2, width: 3, height: 3, z: 7 });
+        assert_eq!(area.tiles().count(), 9);
+        assert!(area.contains(5, 4, 7));
+        assert!(!area.contains(5, 4, 6));
+        assert!(!area.contains(6, 4, 7));
+    }
+
+    #[test]
+    fn test_selection_needs_two_corners() {
+        let mut selection = Selection::new(AreaCommand::Dig, 10, 10, 0);
+        assert_eq!(selection.confirm(), None);
+        selection.move_cursor(-2, 1, 64, 64);
+        selection.move_cursor(0, -20, 64, 64);
+        let area = selection.confirm();
+        println!("{:?}", area);
+        assert_eq!(area, Some(Area { x: 8, y: 0, width: 3, height: 11, z: 0 }));
+        assert_eq!(selection.anchor, None);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::game_map::GameMap;
+
+/// Furthest a colonist can see, in tiles
+pub const SIGHT_RANGE: i64 = 12;
+
+/// Whether something at from could see the tile at to on level z: it's in
+/// range and the line between them runs through open tiles only, none of
+/// them hidden, like smoke hides them. The world wraps around, so from sees
+/// east or west, whichever is closer.
+pub fn can_see(map: &mut GameMap, from: (u32, u32), to: (u32, u32), z: u32,
+               hidden: impl Fn(&GameMap, u32, u32) -> bool) -> bool {
+    can_see_within(map, from, to, z, SIGHT_RANGE, hidden)
+}
+
+/// Like can_see, for when the weather only lets them see range tiles
+pub fn can_see_within(map: &mut GameMap, from: (u32, u32), to: (u32, u32), z: u32, range: i64,
+                      hidden: impl Fn(&GameMap, u32, u32) -> bool) -> bool {
+    let (max_x, _, _) = map.size();
+    let mut dx = map.column_from(to.0, from.0) as i64;
+    if dx > max_x as i64 / 2 {
+        dx -= max_x as i64;
+    }
+    let dy = to.1 as i64 - from.1 as i64;
+    if dx * dx + dy * dy > range * range {
+        return false;
+    }
+    line(dx, dy).into_iter()
+        .skip(1)
+        .take_while(|&step| step != (dx, dy))
+        .all(|(sx, sy)| {
+            let (x, y) = (map.wrap_x(from.0 as i64 + sx), (from.1 as i64 + sy) as u32);
+            !map.get_tile(x, y, z).is_solid() && !hidden(map, x, y)
+        })
+}
+
+/// The offsets from (0, 0) to (dx, dy) along a straight line, both ends
+/// included
+fn line(dx: i64, dy: i64) -> Vec<(i64, i64)> {
+    let (sx, sy) = (dx.signum(), dy.signum());
+    let (run, rise) = (dx.abs(), -dy.abs());
+    let (mut x, mut y, mut err) = (0, 0, run + rise);
+    let mut steps = vec![(0, 0)];
+    while (x, y) != (dx, dy) {
+        let doubled = 2 * err;
+        if doubled >= rise {
+            err += rise;
+            x += sx;
+        }
+        if doubled <= run {
+            err += run;
+            y += sy;
+        }
+        steps.push((x, y));
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::TileKind;
+
+    #[test]
+    fn test_walls_block_sight() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = (1000, 1000, 40);
+        for ty in y..y + 5 {
+            for tx in x..x + 22 {
+                let mut tile = map.get_tile(tx, ty, z);
+                tile.dig();
+                map.set_tile(tx, ty, z, tile);
+            }
+        }
+        println!("{:?}", line(5, -2));
+        assert_eq!(line(5, -2).last(), Some(&(5, -2)));
+        let clear = |_: &GameMap, _, _| false;
+        assert!(can_see(&mut map, (x, y), (x + 10, y + 4), z, clear));
+        assert!(!can_see(&mut map, (x, y + 2), (x + 21, y + 2), z, clear));
+
+        let mut tile = map.get_tile(x + 5, y + 2, z);
+        tile.kind = TileKind::Wall;
+        map.set_tile(x + 5, y + 2, z, tile);
+        assert!(!can_see(&mut map, (x + 1, y + 2), (x + 9, y + 2), z, clear));
+        assert!(can_see(&mut map, (x + 1, y + 1), (x + 9, y + 1), z, clear));
+        assert!(!can_see_within(&mut map, (x + 1, y + 1), (x + 9, y + 1), z, 4, clear));
+        assert!(!can_see(&mut map, (x + 1, y + 1), (x + 9, y + 1), z, |_, hx, _| hx == x + 4));
+    }
+}
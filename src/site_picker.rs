@@ -0,0 +1,162 @@
+use quicksilver::prelude::*;
+
+use crate::color_scheme::ColorScheme;
+use crate::layer::RenderLayer;
+use crate::difficulty::Difficulty;
+use crate::embark::{Region, REGION_HEIGHT, REGION_WIDTH};
+use crate::game_map::GameMap;
+
+/// Size of a cell of the overview on screen, before the UI scale
+const CELL_PX: f32 = 12.0;
+
+/// What the player did on the site picker
+pub enum SiteChoice {
+    /// Land on the tile
+    Land((u32, u32)),
+    /// Play the tutorial instead
+    Tutorial,
+    Cancel,
+}
+
+/// The embark screen: an overview of the region a new colony lands in,
+/// colored by biome and shaded by height, with a cursor on the site
+pub struct SitePicker {
+    /// Seed of the map being landed on
+    pub seed: u32,
+    /// How hard the colony will have it, Tab picks another
+    pub difficulty: Difficulty,
+    region: Region,
+    cursor: (u32, u32),
+    /// Whether Escape backs out, there is nowhere to go back to on startup
+    cancelable: bool,
+    /// Why the last pick didn't work
+    message: Option<String>,
+}
+
+impl SitePicker {
+
+    pub fn new(map: &GameMap, cancelable: bool) -> SitePicker {
+        let region = Region::survey(map);
+        let cursor = region.default_cell();
+        SitePicker {
+            seed: map.random_seed, difficulty: map.difficulty, region, cursor, cancelable, message: None,
+        }
+    }
+
+    /// Moves the cursor with the arrow keys, Tab changes the difficulty and
+    /// Return lands on the cell under it
+    pub fn handle_input(&mut self, window: &Window, escape: bool) -> Option<SiteChoice> {
+        let pressed = |key| window.keyboard()[key] == ButtonState::Pressed;
+        if escape && self.cancelable {
+            return Some(SiteChoice::Cancel);
+        }
+        let (column, row) = self.cursor;
+        if pressed(Key::Left) {
+            self.cursor.0 = (column + REGION_WIDTH - 1) % REGION_WIDTH;
+        }
+        if pressed(Key::Right) {
+            self.cursor.0 = (column + 1) % REGION_WIDTH;
+        }
+        if pressed(Key::Up) {
+            self.cursor.1 = row.saturating_sub(1);
+        }
+        if pressed(Key::Down) {
+            self.cursor.1 = (row + 1).min(REGION_HEIGHT - 1);
+        }
+        if pressed(Key::Tab) {
+            self.difficulty = self.difficulty.next();
+        }
+        if pressed(Key::T) {
+            return Some(SiteChoice::Tutorial);
+        }
+        if self.cursor != (column, row) {
+            self.message = None;
+        }
+        if pressed(Key::Return) {
+            let (column, row) = self.cursor;
+            let site = self.region.site(column, row);
+            if site.biome.landable() {
+                return Some(SiteChoice::Land(self.region.center(column, row)));
+            }
+            self.message = Some(format!("The lander can't set down on {}", site.biome.name()));
+        }
+        None
+    }
+
+    /// Draws the overview centered on the screen, with what's under the
+    /// cursor below it
+    pub fn draw(&self, window: &mut Window, font: &mut Font,
+                color_scheme: &ColorScheme, scale: f32) -> Result<()> {
+        let cell = CELL_PX * scale;
+        let line_height = 28.0 * scale;
+        let padding = 20.0 * scale;
+
+        let (column, row) = self.cursor;
+        let site = self.region.site(column, row);
+        let title = font.render("Pick a landing site", &FontStyle::new(
+            24.0 * scale, Color::from_hex(&color_scheme.light_yellow)))?;
+        let text_style = FontStyle::new(18.0 * scale, Color::from_hex(&color_scheme.fg3));
+        let mut lines = vec![
+            format!("{}, height {:.2}, seed {}", site.biome.name(), site.height, self.seed),
+            format!("{}: {}", self.difficulty.name(), self.difficulty.describe()),
+            String::from(if self.cancelable {
+                "arrows: move, Tab: difficulty, Return: land here, T: tutorial, Esc: back"
+            } else {
+                "arrows: move, Tab: difficulty, Return: land here, T: tutorial"
+            }),
+        ];
+        if let Some(message) = &self.message {
+            lines.push(message.clone());
+        }
+        let lines = lines.iter()
+            .map(|line| font.render(line, &text_style))
+            .collect::<Result<Vec<Image>>>()?;
+
+        let map_size = Vector::new(REGION_WIDTH as f32 * cell, REGION_HEIGHT as f32 * cell);
+        let size = Vector::new(map_size.x, map_size.y + (lines.len() + 1) as f32 * line_height)
+            + Vector::new(padding, padding) * 2.0;
+        let area = Rectangle::new_sized(size).with_center(window.screen_size() / 2.0);
+        window.draw_ex(&area, Col(Color::from_hex(&color_scheme.bg)), Transform::IDENTITY, RenderLayer::UiTop.z());
+        window.draw_ex(
+            &title.area().translate(area.top_left() + Vector::new(padding, padding)),
+            Img(&title),
+            Transform::IDENTITY,
+            RenderLayer::UiTop.over(),
+        );
+
+        let map_origin = area.top_left() + Vector::new(padding, padding + line_height);
+        for row in 0..REGION_HEIGHT {
+            for column in 0..REGION_WIDTH {
+                let site = self.region.site(column, row);
+                // higher ground is lighter
+                let shade = (0.75 + site.height * 0.35).clamp(0.3, 1.0) as f32;
+                let color = Color::from_hex(color_scheme.get_color_code(&site.biome.color()));
+                let color = Color { r: color.r * shade, g: color.g * shade, b: color.b * shade, a: 1.0 };
+                let pos = map_origin + Vector::new(column as f32 * cell, row as f32 * cell);
+                window.draw_ex(&Rectangle::new(pos, (cell, cell)), Col(color), Transform::IDENTITY, RenderLayer::UiTop.over());
+            }
+        }
+        let cursor = map_origin + Vector::new(column as f32 * cell, row as f32 * cell);
+        let marker = Color::from_hex(&color_scheme.light_orange);
+        let thickness = (2.0 * scale).max(1.0);
+        for (offset, edge) in [
+            ((0.0, 0.0), (cell, thickness)),
+            ((0.0, cell - thickness), (cell, thickness)),
+            ((0.0, 0.0), (thickness, cell)),
+            ((cell - thickness, 0.0), (thickness, cell)),
+        ] {
+            window.draw_ex(
+                &Rectangle::new(cursor + Vector::from(offset), edge),
+                Col(marker),
+                Transform::IDENTITY,
+                RenderLayer::UiTop.over(),
+            );
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            let pos = map_origin + Vector::new(0.0, map_size.y + index as f32 * line_height + padding / 2.0);
+            window.draw_ex(&line.area().translate(pos), Img(line), Transform::IDENTITY, RenderLayer::UiTop.over());
+        }
+        Ok(())
+    }
+}
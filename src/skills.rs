@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+/// Highest level a skill goes to
+pub const MAX_LEVEL: u32 = 10;
+/// Experience the first level takes, each one after takes this much more
+/// than the one before
+const LEVEL_XP: u32 = 10;
+
+/// What colonists get better at with practice
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Skill {
+    /// Digging out rock, faster and with more stone to show for it
+    Mining,
+    /// Carrying dug out stone to stops, from further away
+    Hauling,
+    /// Building pillars and track, wearing the hammer down less
+    Crafting,
+}
+
+impl Skill {
+
+    pub const ALL: [Skill; 3] = [Skill::Mining, Skill::Hauling, Skill::Crafting];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Skill::Mining => "Mining",
+            Skill::Hauling => "Hauling",
+            Skill::Crafting => "Crafting",
+        }
+    }
+}
+
+/// How much practice a colonist has had at each skill
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Skills {
+    mining: u32,
+    hauling: u32,
+    crafting: u32,
+}
+
+impl Skills {
+
+    pub fn experience(&self, skill: Skill) -> u32 {
+        match skill {
+            Skill::Mining => self.mining,
+            Skill::Hauling => self.hauling,
+            Skill::Crafting => self.crafting,
+        }
+    }
+
+    /// Levels start at 0 and take LEVEL_XP more experience each
+    pub fn level(&self, skill: Skill) -> u32 {
+        let experience = self.experience(skill);
+        (0..MAX_LEVEL)
+            .take_while(|level| experience >= xp_for(level + 1))
+            .count() as u32
+    }
+
+    /// Adds a use's worth of experience, returns the new level if it went up
+    pub fn practice(&mut self, skill: Skill) -> Option<u32> {
+        let before = self.level(skill);
+        let experience = match skill {
+            Skill::Mining => &mut self.mining,
+            Skill::Hauling => &mut self.hauling,
+            Skill::Crafting => &mut self.crafting,
+        };
+        *experience = experience.saturating_add(1);
+        Some(self.level(skill)).filter(|&level| level > before)
+    }
+
+    /// Whether this use of the skill leaves the tool unworn, skilled hands
+    /// wear theirs down less often
+    pub fn spares_tool(&self, skill: Skill) -> bool {
+        let every = 1 + self.level(skill) / 3;
+        !self.experience(skill).is_multiple_of(every)
+    }
+}
+
+/// Experience it takes to reach level
+fn xp_for(level: u32) -> u32 {
+    LEVEL_XP * level * (level + 1) / 2
+}
+
+/// Tiles a miner digs at once, from those next to them
+pub fn tiles_per_dig(mining: u32) -> usize {
+    1 + mining as usize / 3
+}
+
+/// Pieces of stone a dug out tile yields
+pub fn stone_per_tile(mining: u32) -> u32 {
+    1 + mining / 5
+}
+
+/// Extra tiles from a stop stone is carried over
+pub fn haul_range(hauling: u32) -> u32 {
+    hauling * 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_take_more_practice_each() {
+        let mut skills = Skills::default();
+        let levels: Vec<Option<u32>> = (0..30).map(|_| skills.practice(Skill::Mining)).collect();
+        println!("{:?}", levels);
+        assert_eq!(levels[9], Some(1));
+        assert_eq!(levels[29], Some(2));
+        assert_eq!(levels.iter().flatten().count(), 2);
+        assert_eq!(skills.level(Skill::Mining), 2);
+        assert_eq!(skills.level(Skill::Hauling), 0);
+
+        for _ in 0..1000 {
+            skills.practice(Skill::Crafting);
+        }
+        assert_eq!(skills.level(Skill::Crafting), MAX_LEVEL);
+        assert_eq!(tiles_per_dig(skills.level(Skill::Mining)), 1);
+    }
+}
@@ -0,0 +1,223 @@
+use quicksilver::prelude::*;
+
+use crate::command_menu::MAX_NAME_LEN;
+use crate::dialog::Dialog;
+use crate::save::{self, SlotInfo};
+use crate::text_input::{Filter, TextInput};
+
+/// Whether the slot screen saves the colony into a slot or loads one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlotAction {
+    Save,
+    Load,
+}
+
+/// A question asked about the selected slot before doing something that
+/// can't be undone
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Confirm {
+    Overwrite,
+    Delete,
+    /// Loading over a colony with unsaved changes
+    Discard,
+}
+
+/// What the player picked on the slot screen, for the game to carry out
+pub enum SlotChoice {
+    Save { name: String, profile: String },
+    Load(SlotInfo),
+    Delete(SlotInfo),
+    Back,
+}
+
+/// Lists the save slots as the options of a dialog, the selected one's
+/// details and thumbnail above them. Delete removes the selected slot.
+pub struct SlotScreen {
+    pub action: SlotAction,
+    pub dialog: Dialog,
+    slots: Vec<SlotInfo>,
+    confirm: Option<Confirm>,
+    /// Whether loading would throw away unsaved changes
+    unsaved: bool,
+    /// Option the thumbnail was made for, it's remade when that changes
+    pictured: Option<usize>,
+}
+
+impl SlotScreen {
+
+    pub fn new(action: SlotAction, slots: Vec<SlotInfo>, unsaved: bool) -> SlotScreen {
+        let title = match action {
+            SlotAction::Save => "Save colony",
+            SlotAction::Load => "Load colony",
+        };
+        let mut options: Vec<String> = slots.iter().map(|slot| slot.name.clone()).collect();
+        if action == SlotAction::Save {
+            options.insert(0, String::from("New slot"));
+        }
+        options.push(String::from("Back"));
+        let mut dialog = Dialog::new(title, &[]);
+        dialog.options = options;
+        let mut screen = SlotScreen {
+            action, dialog, slots, confirm: None, unsaved, pictured: None,
+        };
+        screen.refresh();
+        screen
+    }
+
+    /// The slot shown as option, if it is one
+    fn slot(&self, option: usize) -> Option<&SlotInfo> {
+        match self.action {
+            SlotAction::Save => option.checked_sub(1).and_then(|i| self.slots.get(i)),
+            SlotAction::Load => self.slots.get(option),
+        }
+    }
+
+    fn selected(&self) -> Option<&SlotInfo> {
+        self.slot(self.dialog.selected)
+    }
+
+    /// Answers a question with Return or Escape, names a new slot, or
+    /// moves through the slots, returns what the player chose
+    pub fn handle_input(&mut self, window: &Window, escape: bool) -> Option<SlotChoice> {
+        let pressed = |key| window.keyboard()[key] == ButtonState::Pressed;
+        let choice = if let Some(confirm) = self.confirm {
+            self.confirm = None;
+            match (escape, pressed(Key::Return), self.selected().cloned()) {
+                (false, true, Some(slot)) => Some(match confirm {
+                    Confirm::Overwrite => SlotChoice::Save { name: slot.name, profile: slot.profile },
+                    Confirm::Delete => SlotChoice::Delete(slot),
+                    Confirm::Discard => SlotChoice::Load(slot),
+                }),
+                (false, false, _) => {
+                    self.confirm = Some(confirm);
+                    return None;
+                },
+                _ => None,
+            }
+        } else if let Some(field) = &mut self.dialog.field {
+            field.handle_keys(window);
+            if escape {
+                self.dialog.field = None;
+                None
+            } else if pressed(Key::Return) {
+                self.name_slot()
+            } else {
+                return None;
+            }
+        } else if escape {
+            return Some(SlotChoice::Back);
+        } else if pressed(Key::Delete) && self.selected().is_some() {
+            self.confirm = Some(Confirm::Delete);
+            None
+        } else {
+            match self.dialog.handle_input(window) {
+                Some(option) => self.choose(option),
+                None => None,
+            }
+        };
+        self.refresh();
+        choice
+    }
+
+    fn choose(&mut self, option: usize) -> Option<SlotChoice> {
+        match (self.action, self.slot(option).cloned()) {
+            (SlotAction::Save, Some(_)) => self.confirm = Some(Confirm::Overwrite),
+            (SlotAction::Save, None) if option == 0 => {
+                self.dialog.field = Some(TextInput::new(Filter::Name, MAX_NAME_LEN));
+            },
+            (SlotAction::Load, Some(_)) if self.unsaved => self.confirm = Some(Confirm::Discard),
+            (SlotAction::Load, Some(slot)) => return Some(SlotChoice::Load(slot)),
+            (_, None) => return Some(SlotChoice::Back),
+        }
+        None
+    }
+
+    /// Saves into a new slot with the typed name, or asks to overwrite the
+    /// slot that already has it
+    fn name_slot(&mut self) -> Option<SlotChoice> {
+        let name = self.dialog.field.as_ref().map(TextInput::text).unwrap_or_default();
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        self.dialog.field = None;
+        let profile = save::slot_profile(name);
+        let taken = self.slots.iter().position(|slot| {
+            slot.profile == profile || slot.name.eq_ignore_ascii_case(name)
+        });
+        match taken {
+            Some(i) => {
+                self.dialog.selected = i + 1;
+                self.confirm = Some(Confirm::Overwrite);
+                None
+            },
+            None => Some(SlotChoice::Save { name: String::from(name), profile }),
+        }
+    }
+
+    /// Fills in the text for the selected option and whatever is being
+    /// asked or typed, and its thumbnail
+    fn refresh(&mut self) {
+        let now = save::now_secs();
+        let text = match (self.confirm, self.selected()) {
+            (Some(confirm), Some(slot)) => vec![
+                match confirm {
+                    Confirm::Overwrite => format!("Overwrite {}?", slot.name),
+                    Confirm::Delete => format!("Delete {}?", slot.name),
+                    Confirm::Discard => String::from("Load it and lose the unsaved changes?"),
+                },
+                String::from("Return: yes, Escape: no"),
+            ],
+            _ if self.dialog.field.is_some() => vec![String::from("Name of the new slot:")],
+            (_, Some(slot)) => vec![
+                format!("played {}, tick {}", play_time(slot.play_time_secs), slot.tick),
+                format!("saved {}, Delete to remove", ago(now.saturating_sub(slot.saved_at))),
+            ],
+            (_, None) if self.slots.is_empty() => vec![String::from("Nothing has been saved yet")],
+            (_, None) => vec![String::new()],
+        };
+        self.dialog.text = text;
+
+        let selected = self.selected().map(|_| self.dialog.selected);
+        if selected != self.pictured {
+            self.pictured = selected;
+            self.dialog.picture = self.selected()
+                .map(|slot| &slot.thumbnail)
+                .filter(|thumbnail| thumbnail.width > 0 && thumbnail.height > 0)
+                .and_then(|thumbnail| Image::from_raw(
+                    &thumbnail.pixels, thumbnail.width, thumbnail.height, PixelFormat::RGBA).ok());
+        }
+    }
+}
+
+/// Play time like 2h 05m
+fn play_time(secs: u64) -> String {
+    format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+}
+
+/// How long ago something happened, roughly
+fn ago(secs: u64) -> String {
+    let (count, unit) = match secs {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_details() {
+        println!("{} {}", play_time(7530), ago(7530));
+        assert_eq!(play_time(7530), "2h 05m");
+        assert_eq!(play_time(59), "0h 00m");
+        assert_eq!(ago(30), "just now");
+        assert_eq!(ago(60), "1 minute ago");
+        assert_eq!(ago(7530), "2 hours ago");
+        assert_eq!(ago(3 * 86400), "3 days ago");
+    }
+}
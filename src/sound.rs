@@ -0,0 +1,215 @@
+use enum_map::{enum_map, Enum, EnumMap};
+use quicksilver::prelude::*;
+
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::events::GameEvent;
+
+const MUSIC_FILE: &str = "music.wav";
+/// Length of the music track, quicksilver can't tell when a sound is done
+/// playing so the track is restarted on a timer
+const MUSIC_LENGTH: Duration = Duration::from_secs(8);
+
+/// Effects further than this many tiles from the camera can't be heard
+const HEARING_RANGE: f32 = 48.0;
+/// Distance in tiles that one level up or down counts for
+const LEVEL_DISTANCE: f32 = 8.0;
+/// How loud the cue for something happening out of view is, before the
+/// effects volume
+const CUE_VOLUME: f32 = 0.7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Enum)]
+pub enum Effect {
+    Dig,
+    Hit,
+    Water,
+    Rumble,
+    /// The cue for something happening out of view, split into one file
+    /// for each speaker since quicksilver can't pan a sound
+    CueLeft,
+    CueRight,
+}
+
+impl Effect {
+
+    /// The effect played for the event, if it makes a sound
+    pub fn for_event(event: &GameEvent) -> Option<Effect> {
+        match event {
+            GameEvent::Dug { .. } => Some(Effect::Dig),
+            GameEvent::Hit { .. } | GameEvent::Died { .. } => Some(Effect::Hit),
+            GameEvent::WaterFlowed { .. } => Some(Effect::Water),
+            GameEvent::CaveIn { .. } => Some(Effect::Rumble),
+            GameEvent::Hauled { .. }
+            | GameEvent::ColonyLost { .. }
+            | GameEvent::NoRoute { .. }
+            | GameEvent::JobInterrupted { .. }
+            | GameEvent::HostileSighted { .. }
+            | GameEvent::WornOut { .. }
+            | GameEvent::SkillUp { .. }
+            | GameEvent::Tamed { .. }
+            | GameEvent::Born { .. }
+            | GameEvent::CaravanArrived { .. }
+            | GameEvent::CaravanLeft { .. }
+            | GameEvent::Incident { .. } => None,
+        }
+    }
+
+    fn file(&self) -> &'static str {
+        match self {
+            Effect::Dig => "dig.wav",
+            Effect::Hit => "hit.wav",
+            Effect::Water => "water.wav",
+            Effect::Rumble => "rumble.wav",
+            Effect::CueLeft => "cue_left.wav",
+            Effect::CueRight => "cue_right.wav",
+        }
+    }
+
+    /// Shortest time between two plays of the effect, so a flood doesn't
+    /// play a splash for every tile
+    fn cooldown(&self) -> Duration {
+        match self {
+            Effect::Dig => Duration::from_millis(80),
+            Effect::Hit => Duration::from_millis(80),
+            Effect::Water => Duration::from_millis(700),
+            Effect::Rumble => Duration::from_millis(500),
+            Effect::CueLeft | Effect::CueRight => Duration::from_millis(1500),
+        }
+    }
+}
+
+/// Plays the music and the sound effects of game events. Both are scaled
+/// by their channel volume from the config.
+pub struct Audio {
+    effects: EnumMap<Effect, Asset<Sound>>,
+    last_played: EnumMap<Effect, Option<Instant>>,
+    music: Asset<Sound>,
+    music_started: Option<Instant>,
+    /// Turned off after the first error, so a missing sound file or audio
+    /// device doesn't fail every frame
+    enabled: bool,
+}
+
+impl Audio {
+
+    pub fn new() -> Audio {
+        Audio {
+            effects: enum_map! {
+                effect => Asset::new(Sound::load(effect.file())),
+            },
+            last_played: EnumMap::default(),
+            music: Asset::new(Sound::load(MUSIC_FILE)),
+            music_started: None,
+            enabled: true,
+        }
+    }
+
+    /// Starts the music, and starts it over once the track is done. Call
+    /// every frame.
+    pub fn update(&mut self, config: &Config) {
+        if !self.enabled || config.music_volume <= 0.0 {
+            self.music_started = None;
+            return;
+        }
+        let done = self.music_started
+            .is_none_or(|started| started.elapsed() >= MUSIC_LENGTH);
+        if !done {
+            return;
+        }
+        let volume = config.music_volume;
+        let mut started = false;
+        let result = self.music.execute(|music| {
+            music.set_volume(volume);
+            music.play()?;
+            started = true;
+            Ok(())
+        });
+        if started {
+            self.music_started = Some(Instant::now());
+        }
+        self.check(result);
+    }
+
+    /// Plays the effect of the event, quieter the further the event is from
+    /// the listener at (x, y, z)
+    pub fn on_event(&mut self, event: &GameEvent, listener: (f32, f32, u32),
+                    config: &Config) {
+        let effect = match Effect::for_event(event) {
+            Some(effect) => effect,
+            None => return,
+        };
+        let volume = config.effects_volume * falloff(event.position(), listener);
+        self.play(effect, volume);
+    }
+
+    /// Plays the cue for something happening out of view, from the left
+    /// speaker at pan -1.0 through both to the right one at 1.0
+    pub fn cue(&mut self, pan: f32, config: &Config) {
+        let (left, right) = pan_volumes(pan);
+        let volume = config.effects_volume * CUE_VOLUME;
+        self.play(Effect::CueLeft, volume * left);
+        self.play(Effect::CueRight, volume * right);
+    }
+
+    fn play(&mut self, effect: Effect, volume: f32) {
+        let cooling_down = self.last_played[effect]
+            .is_some_and(|played| played.elapsed() < effect.cooldown());
+        if !self.enabled || volume <= 0.0 || cooling_down {
+            return;
+        }
+        self.last_played[effect] = Some(Instant::now());
+        let result = self.effects[effect].execute(|sound| {
+            sound.set_volume(volume);
+            sound.play()
+        });
+        self.check(result);
+    }
+
+    fn check(&mut self, result: Result<()>) {
+        if let Err(err) = result {
+            println!("could not play sound, audio is off: {:?}", err);
+            self.enabled = false;
+        }
+    }
+}
+
+/// How loud a sound at source is for the listener, from 1.0 right next to
+/// them down to 0.0 at HEARING_RANGE
+pub fn falloff(source: (u32, u32, u32), listener: (f32, f32, u32)) -> f32 {
+    let dx = source.0 as f32 + 0.5 - listener.0;
+    let dy = source.1 as f32 + 0.5 - listener.1;
+    let dz = (source.2 as f32 - listener.2 as f32) * LEVEL_DISTANCE;
+    let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+    (1.0 - distance / HEARING_RANGE).max(0.0)
+}
+
+/// How loud the left and right speakers play a sound panned from -1.0 on
+/// the left to 1.0 on the right, keeping it as loud overall wherever it is
+pub fn pan_volumes(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_falloff() {
+        let near = falloff((10, 10, 5), (10.5, 10.5, 5));
+        let far = falloff((30, 10, 5), (10.5, 10.5, 5));
+        let below = falloff((10, 10, 8), (10.5, 10.5, 5));
+        println!("near: {} far: {} below: {}", near, far, below);
+        assert_eq!(near, 1.0);
+        assert!(far > 0.0 && far < near);
+        assert!(below < near);
+        assert_eq!(falloff((100, 10, 5), (10.5, 10.5, 5)), 0.0);
+
+        let (left, right) = pan_volumes(-1.0);
+        assert_eq!((left, right.abs() < 1e-6), (1.0, true));
+        let (left, right) = pan_volumes(0.0);
+        assert!((left - right).abs() < 1e-6 && (left * left + right * right - 1.0).abs() < 1e-6);
+        assert!(pan_volumes(0.5).1 > pan_volumes(0.5).0);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::entity::{Entities, Entity, EntityId};
+use crate::game_map::GameMap;
+use crate::mods::{Content, CreatureDef};
+
+/// Closest and furthest from a colonist a hostile turns up, in tiles
+const SPAWN_DISTANCE: (i32, i32) = (12, 24);
+/// Closest and furthest from a corpse a scavenger turns up, in tiles
+const SCAVENGER_DISTANCE: (i32, i32) = (6, 12);
+/// Tiles tried for room to put a hostile before waiting for the next time
+const SPAWN_TRIES: u32 = 20;
+/// Ticks between creatures that can be kept wandering in, whatever the
+/// difficulty
+const WILDLIFE_INTERVAL: u64 = 1500;
+/// Most wild creatures that can be kept about at once
+const MAX_WILDLIFE: usize = 6;
+/// Set apart from the streams hostiles and wildlife are drawn from
+const SCAVENGER_STREAM: u64 = 0x5ca7_e000;
+
+/// Brings the hostile creatures of the content packs in out of the dark
+/// around the squad, as often as the map's difficulty says, and now and
+/// then one that can be kept. Where and what comes from the seed and the
+/// tick, so every game in a network session spawns the same creature in
+/// the same place.
+#[derive(Clone, Debug, Default)]
+pub struct Spawner {
+    creatures: Vec<CreatureDef>,
+    /// Passive creatures that can be caught and kept
+    wildlife: Vec<CreatureDef>,
+    /// Creatures drawn to corpses left lying about
+    scavengers: Vec<CreatureDef>,
+}
+
+impl Spawner {
+
+    pub fn new(content: &Content) -> Spawner {
+        Spawner::with_creatures(content.creatures.iter().cloned().collect())
+    }
+
+    /// Hostile creatures attack, passive ones that can be kept wander in
+    /// now and then and scavengers come for corpses, the rest are left to
+    /// scenarios
+    pub fn with_creatures(creatures: Vec<CreatureDef>) -> Spawner {
+        let scavengers = creatures.iter().filter(|creature| creature.scavenger).cloned().collect();
+        let (hostile, passive): (Vec<CreatureDef>, Vec<CreatureDef>) = creatures.into_iter()
+            .partition(|creature| creature.hostile);
+        let wildlife = passive.into_iter().filter(|creature| creature.livestock.is_some()).collect();
+        Spawner { creatures: hostile, wildlife, scavengers }
+    }
+
+    /// The hostile turning up at tick near one of the squad, if one is
+    /// due and there is room for it
+    pub fn due(&self, map: &mut GameMap, entities: &Entities, squad: &[EntityId],
+               tick: u64) -> Option<Entity> {
+        let interval = map.difficulty.spawn_interval()?;
+        if self.creatures.is_empty() || tick == 0 || !tick.is_multiple_of(interval) {
+            return None;
+        }
+        let hostiles = entities.iter()
+            .filter(|(_, entity)| entity.hostile && entity.is_alive())
+            .count();
+        if hostiles >= map.difficulty.max_hostiles() {
+            return None;
+        }
+        let colonists: Vec<&Entity> = squad.iter()
+            .filter_map(|id| entities.get(*id))
+            .filter(|colonist| colonist.is_alive())
+            .collect();
+        if colonists.is_empty() {
+            return None;
+        }
+
+        let mut rng = oorandom::Rand32::new(((map.random_seed as u64) << 32) ^ tick);
+        let colonist = colonists[rng.rand_range(0..colonists.len() as u32) as usize];
+        let creature = &self.creatures[rng.rand_range(0..self.creatures.len() as u32) as usize];
+        place_near(map, entities, colonist, creature, SPAWN_DISTANCE, &mut rng)
+    }
+
+    /// The creature that can be kept wandering in at tick near one of the
+    /// squad, with its kind, if one is due, fewer than MAX_WILDLIFE of them
+    /// are about and there is room for it
+    pub fn wildlife_due(&self, map: &mut GameMap, entities: &Entities, squad: &[EntityId],
+                        wild: usize, tick: u64) -> Option<(Entity, String)> {
+        if self.wildlife.is_empty() || tick == 0 || !tick.is_multiple_of(WILDLIFE_INTERVAL)
+           || wild >= MAX_WILDLIFE {
+            return None;
+        }
+        let colonists: Vec<&Entity> = squad.iter()
+            .filter_map(|id| entities.get(*id))
+            .filter(|colonist| colonist.is_alive())
+            .collect();
+        if colonists.is_empty() {
+            return None;
+        }
+
+        // a different stream from the hostiles', for ticks both are due
+        let mut rng = oorandom::Rand32::new(((map.random_seed as u64) << 32) ^ tick ^ u32::MAX as u64);
+        let colonist = colonists[rng.rand_range(0..colonists.len() as u32) as usize];
+        let creature = &self.wildlife[rng.rand_range(0..self.wildlife.len() as u32) as usize];
+        place_near(map, entities, colonist, creature, SPAWN_DISTANCE, &mut rng)
+            .map(|spawned| (spawned, creature.id.clone()))
+    }
+
+    /// The scavenger turning up at tick near one of the corpses, if any
+    /// are left lying about, one is due and there is room for it. They
+    /// come as often as hostiles do and count towards their number.
+    pub fn scavenger_due(&self, map: &mut GameMap, entities: &Entities, corpses: &[EntityId],
+                         tick: u64) -> Option<Entity> {
+        let interval = map.difficulty.spawn_interval()?;
+        if self.scavengers.is_empty() || corpses.is_empty() || tick == 0 || !tick.is_multiple_of(interval) {
+            return None;
+        }
+        let hostiles = entities.iter()
+            .filter(|(_, entity)| entity.hostile && entity.is_alive())
+            .count();
+        if hostiles >= map.difficulty.max_hostiles() {
+            return None;
+        }
+
+        // yet another stream, hostiles and wildlife can be due the same tick
+        let mut rng = oorandom::Rand32::new(((map.random_seed as u64) << 32) ^ tick ^ SCAVENGER_STREAM);
+        let corpse = entities.get(corpses[rng.rand_range(0..corpses.len() as u32) as usize])?;
+        let creature = &self.scavengers[rng.rand_range(0..self.scavengers.len() as u32) as usize];
+        place_near(map, entities, corpse, creature, SCAVENGER_DISTANCE, &mut rng)
+    }
+}
+
+/// A new creature somewhere open between distance tiles from the entity,
+/// None if there's no room in SPAWN_TRIES tries
+fn place_near(map: &mut GameMap, entities: &Entities, near_to: &Entity, creature: &CreatureDef,
+              (near, far): (i32, i32), rng: &mut oorandom::Rand32) -> Option<Entity> {
+    let (_, max_y, _) = map.size();
+    for _ in 0..SPAWN_TRIES {
+        let dx = rng.rand_range(0..(far * 2 + 1) as u32) as i32 - far;
+        let dy = rng.rand_range(0..(far * 2 + 1) as u32) as i32 - far;
+        if dx.abs().max(dy.abs()) < near {
+            continue;
+        }
+        let x = map.wrap_x(near_to.pos.x as i64 + dx as i64);
+        let y = near_to.pos.y as i64 + dy as i64;
+        if y < 0 || y + creature.footprint.height as i64 > max_y as i64 {
+            continue;
+        }
+        let spawned = creature.spawn(x, y as u32, near_to.depth);
+        let has_room = spawned.cells().into_iter().all(|(cx, cy)| {
+            creature.movement.can_enter(&map.get_tile(cx, cy, spawned.depth))
+                && !entities.is_occupied(cx, cy, spawned.depth, None)
+        });
+        if has_room {
+            return Some(spawned);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_scheme::ColorName;
+    use crate::difficulty::Difficulty;
+    use crate::entity::{Footprint, Movement};
+    use crate::simulation::{self, Simulation};
+
+    fn worm() -> CreatureDef {
+        CreatureDef {
+            id: String::from("worm"),
+            name: String::from("Worm"),
+            glyph: '%',
+            color: ColorName::Purple,
+            hp: 2,
+            gas_protection: false,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: true,
+            movement: Movement::WALKING,
+            livestock: None,
+            scavenger: true,
+        }
+    }
+
+    #[test]
+    fn test_hostiles_spawn_by_difficulty() {
+        let spawner = Spawner::with_creatures(vec![worm()]);
+        let map = GameMap::with_seed(10).with_difficulty(Difficulty::Brutal);
+        let mut sim = Simulation::new(map);
+        let (x, y, z) = simulation::start_position(&sim.map);
+        sim.populate(x, y, z);
+        let interval = Difficulty::Brutal.spawn_interval().unwrap();
+
+        let squad = sim.squad.members().to_vec();
+        assert!(spawner.due(&mut sim.map, &sim.entities, &squad, interval - 1).is_none());
+        let spawned = (1..=10)
+            .find_map(|i| spawner.due(&mut sim.map, &sim.entities, &squad, interval * i))
+            .expect("nowhere for a hostile to turn up");
+        println!("{:?}", spawned.pos);
+        assert!(spawned.hostile);
+        assert_eq!(spawned.depth, z);
+        assert!(!sim.map.get_tile(spawned.pos.x as u32, spawned.pos.y as u32, z).is_solid());
+
+        // worms come for the dead too, close by
+        let dead = &squad[..1];
+        let scavenger = (1..=10)
+            .find_map(|i| spawner.scavenger_due(&mut sim.map, &sim.entities, dead, interval * i))
+            .expect("nowhere for a scavenger to turn up");
+        let corpse = sim.entities.get(dead[0]).unwrap();
+        let distance = (scavenger.pos.x - corpse.pos.x).abs().max((scavenger.pos.y - corpse.pos.y).abs());
+        assert!(distance <= SCAVENGER_DISTANCE.1 as f32);
+        assert!(spawner.scavenger_due(&mut sim.map, &sim.entities, &[], interval).is_none());
+
+        sim.map.difficulty = Difficulty::Peaceful;
+        assert!(spawner.due(&mut sim.map, &sim.entities, &squad, interval * 3).is_none());
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::designation::Designations;
+use crate::entity::{Entities, EntityId};
+use crate::equipment::{Equipment, Gear, ToolKind};
+use crate::game_map::GameMap;
+use crate::skills::{Skill, Skills};
+
+/// The colonists under the player's control. The selected one takes the
+/// keyboard and move orders, the others dig out designations on their own.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Squad {
+    members: Vec<EntityId>,
+    /// The colonist each player steers by hand, by peer. They're left out
+    /// of jobs.
+    #[serde(default)]
+    steered: BTreeMap<u32, EntityId>,
+    /// Names players gave colonists
+    #[serde(default)]
+    names: BTreeMap<EntityId, String>,
+    /// Members kept to the burrow, they only dig and wander inside it
+    #[serde(default)]
+    burrowed: BTreeSet<EntityId>,
+    /// What each member carries
+    #[serde(default)]
+    equipment: BTreeMap<EntityId, Equipment>,
+    /// How practiced each member is
+    #[serde(default)]
+    skills: BTreeMap<EntityId, Skills>,
+}
+
+impl Squad {
+
+    pub fn new(members: Vec<EntityId>) -> Squad {
+        Squad {
+            members,
+            steered: BTreeMap::new(),
+            names: BTreeMap::new(),
+            burrowed: BTreeSet::new(),
+            equipment: BTreeMap::new(),
+            skills: BTreeMap::new(),
+        }
+    }
+
+    pub fn members(&self) -> &[EntityId] {
+        &self.members
+    }
+
+    /// The next living member after current, going around to the first.
+    /// With no current member that's the first living one.
+    pub fn next_after(&self, current: Option<EntityId>, entities: &Entities) -> Option<EntityId> {
+        let start = current.and_then(|current| self.members.iter().position(|id| *id == current))
+            .map_or(0, |index| index + 1);
+        (0..self.members.len())
+            .map(|offset| self.members[(start + offset) % self.members.len()])
+            .find(|id| Some(*id) != current && entities.get(*id).is_some_and(|entity| entity.is_alive()))
+    }
+
+    /// Hands the player on peer a colonist to steer, None lets theirs go
+    /// back to work
+    pub fn steer(&mut self, peer: u32, colonist: Option<EntityId>) {
+        match colonist {
+            Some(id) => self.steered.insert(peer, id),
+            None => self.steered.remove(&peer),
+        };
+    }
+
+    /// The colonist the player on peer steers
+    pub fn steered_by(&self, peer: u32) -> Option<EntityId> {
+        self.steered.get(&peer).copied()
+    }
+
+    pub fn is_steered(&self, id: EntityId) -> bool {
+        self.steered.values().any(|steered| *steered == id)
+    }
+
+    /// The first living member nobody is steering
+    pub fn unsteered(&self, entities: &Entities) -> Option<EntityId> {
+        self.members.iter().cloned()
+            .find(|id| !self.is_steered(*id) && entities.get(*id).is_some_and(|entity| entity.is_alive()))
+    }
+
+    pub fn name(&self, id: EntityId) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Names a member, an empty name clears it. False if id isn't one.
+    pub fn rename(&mut self, id: EntityId, name: String) -> bool {
+        if !self.members.contains(&id) {
+            return false;
+        }
+        if name.is_empty() {
+            self.names.remove(&id);
+        } else {
+            self.names.insert(id, name);
+        }
+        true
+    }
+
+    pub fn is_burrowed(&self, id: EntityId) -> bool {
+        self.burrowed.contains(&id)
+    }
+
+    /// Keeps a member to the burrow or lets them go anywhere again. False
+    /// if id isn't one.
+    pub fn keep_to_burrow(&mut self, id: EntityId, kept: bool) -> bool {
+        if !self.members.contains(&id) {
+            return false;
+        }
+        if kept {
+            self.burrowed.insert(id);
+        } else {
+            self.burrowed.remove(&id);
+        }
+        true
+    }
+
+    pub fn equipment(&self, id: EntityId) -> Option<&Equipment> {
+        self.equipment.get(&id)
+    }
+
+    pub fn has_tool(&self, id: EntityId, kind: ToolKind) -> bool {
+        self.equipment.get(&id).is_some_and(|equipment| equipment.has_tool(kind))
+    }
+
+    /// Hands a member the gear, returns what they had in its slot. The gear
+    /// comes back if id isn't one.
+    pub fn equip(&mut self, id: EntityId, gear: Gear) -> Option<Gear> {
+        if !self.members.contains(&id) {
+            return Some(gear);
+        }
+        self.equipment.entry(id).or_default().equip(gear)
+    }
+
+    /// Wears a member's tool down by a use, returns its name if that wore
+    /// it out
+    pub fn wear_tool(&mut self, id: EntityId) -> Option<String> {
+        self.equipment.get_mut(&id)?.wear_tool()
+    }
+
+    /// Damage taken off each hit on a member
+    pub fn armor(&self, id: EntityId) -> i32 {
+        self.equipment.get(&id).map_or(0, Equipment::armor)
+    }
+
+    pub fn skills(&self, id: EntityId) -> Option<&Skills> {
+        self.skills.get(&id)
+    }
+
+    pub fn level(&self, id: EntityId, skill: Skill) -> u32 {
+        self.skills.get(&id).map_or(0, |skills| skills.level(skill))
+    }
+
+    /// Gives a member a use's worth of practice, returns their new level if
+    /// it went up
+    pub fn practice(&mut self, id: EntityId, skill: Skill) -> Option<u32> {
+        if !self.members.contains(&id) {
+            return None;
+        }
+        self.skills.entry(id).or_default().practice(skill)
+    }
+
+    /// Takes a newcomer into the squad
+    pub fn join(&mut self, id: EntityId) {
+        if !self.members.contains(&id) {
+            self.members.push(id);
+        }
+    }
+
+    /// Breaks a member's tool, returns its name if they had one
+    pub fn break_tool(&mut self, id: EntityId) -> Option<String> {
+        self.equipment.get_mut(&id)?.tool.take().map(|tool| tool.name)
+    }
+
+    /// Hands the gear to every member who has never carried anything, for
+    /// saves from before colonists had equipment
+    pub fn equip_missing(&mut self, gear: &Gear) {
+        for &id in &self.members {
+            self.equipment.entry(id).or_insert_with(|| Equipment { tool: Some(gear.clone()), armor: None });
+        }
+    }
+
+    /// Forgets members that are gone from the world, like the buried
+    pub fn retain_existing(&mut self, entities: &Entities) {
+        self.members.retain(|id| entities.get(*id).is_some());
+        self.names.retain(|id, _| entities.get(*id).is_some());
+        self.burrowed.retain(|id| entities.get(*id).is_some());
+        self.equipment.retain(|id, _| entities.get(*id).is_some());
+        self.skills.retain(|id, _| entities.get(*id).is_some());
+    }
+}
+
+/// A tile designated for digging next to (x, y, z), if there is one. One
+/// kept to the burrow only digs inside it.
+pub fn adjacent_dig(map: &GameMap, designations: &Designations, x: u32, y: u32, z: u32,
+                    burrowed: bool) -> Option<(u32, u32)> {
+    map.neighbors(x, y, z).into_iter()
+        .find(|&(nx, ny, nz)| designations.is_dig(nx, ny, nz)
+              && (!burrowed || designations.in_burrow(nx, ny, nz)))
+        .map(|(nx, ny, _)| (nx, ny))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{Entity, Footprint, Movement};
+    use crate::color_scheme::ColorName;
+    use quicksilver::geom::Vector;
+
+    fn colonist(entities: &mut Entities, hp: i32) -> EntityId {
+        entities.insert(Entity {
+            pos: Vector::new(0, 0),
+            depth: 0,
+            glyph: '0',
+            color: ColorName::LightOrange,
+            hp,
+            max_hp: 5,
+            gas_protection: false,
+            footprint: Footprint::SINGLE,
+            renderable: None,
+            hostile: false,
+            movement: Movement::WALKING,
+        })
+    }
+
+    #[test]
+    fn test_next_skips_the_dead() {
+        let mut entities = Entities::new();
+        let (a, dead, c) = (colonist(&mut entities, 5), colonist(&mut entities, 0),
+                            colonist(&mut entities, 5));
+        let mut squad = Squad::new(vec![a, dead, c]);
+        println!("{:?}", squad);
+        assert_eq!(squad.next_after(Some(a), &entities), Some(c));
+        assert_eq!(squad.next_after(Some(c), &entities), Some(a));
+        assert_eq!(squad.next_after(None, &entities), Some(a));
+
+        entities.remove(c);
+        squad.retain_existing(&entities);
+        assert_eq!(squad.members(), &[a, dead]);
+        assert_eq!(squad.next_after(Some(a), &entities), None);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::GameEvent;
+
+/// A milestone for the colony, reached once its test passes on the stats
+pub struct Achievement {
+    pub name: &'static str,
+    pub description: &'static str,
+    reached: fn(&Stats) -> bool,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 6] = [
+    Achievement {
+        name: "Breaking ground",
+        description: "Dig out your first tile",
+        reached: |stats| stats.tiles_dug >= 1,
+    },
+    Achievement {
+        name: "Tunnel rat",
+        description: "Dig out 1000 tiles",
+        reached: |stats| stats.tiles_dug >= 1000,
+    },
+    Achievement {
+        name: "Into the deep",
+        description: "Dig on level 50 or below",
+        reached: |stats| stats.deepest_level.is_some_and(|z| z >= 50),
+    },
+    Achievement {
+        name: "Mind your head",
+        description: "Live through a cave-in",
+        reached: |stats| stats.cave_ins >= 1,
+    },
+    Achievement {
+        name: "Rolling stock",
+        description: "Haul 100 stone by minecart",
+        reached: |stats| stats.stone_hauled >= 100,
+    },
+    Achievement {
+        name: "Casualties of the rock",
+        description: "Lose a colonist",
+        reached: |stats| stats.deaths >= 1,
+    },
+];
+
+/// Running totals of what happened in the colony, counted off the event
+/// bus and kept in the save
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub tiles_dug: u64,
+    /// Deepest level anything was dug out on
+    pub deepest_level: Option<u32>,
+    pub cave_ins: u64,
+    /// Damage taken by everything living, colonists and creatures alike
+    pub damage_taken: u64,
+    pub deaths: u64,
+    pub stone_hauled: u64,
+    /// Names of the achievements reached so far
+    unlocked: BTreeSet<String>,
+}
+
+impl Stats {
+
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Counts the event, returns the achievements it unlocked
+    pub fn on_event(&mut self, event: &GameEvent) -> Vec<&'static Achievement> {
+        match *event {
+            GameEvent::Dug { z, .. } => {
+                self.tiles_dug += 1;
+                self.deepest_level = Some(self.deepest_level.map_or(z, |deepest| deepest.max(z)));
+            },
+            GameEvent::Hit { damage, .. } => self.damage_taken += damage.max(0) as u64,
+            GameEvent::CaveIn { .. } => self.cave_ins += 1,
+            GameEvent::Died { .. } => self.deaths += 1,
+            GameEvent::Hauled { amount, .. } => self.stone_hauled += amount as u64,
+            GameEvent::WaterFlowed { .. }
+            | GameEvent::ColonyLost { .. }
+            | GameEvent::NoRoute { .. }
+            | GameEvent::JobInterrupted { .. }
+            | GameEvent::HostileSighted { .. }
+            | GameEvent::WornOut { .. }
+            | GameEvent::SkillUp { .. }
+            | GameEvent::Tamed { .. }
+            | GameEvent::Born { .. }
+            | GameEvent::CaravanArrived { .. }
+            | GameEvent::CaravanLeft { .. }
+            | GameEvent::Incident { .. } => return Vec::new(),
+        }
+        let mut unlocked = Vec::new();
+        for achievement in ACHIEVEMENTS.iter() {
+            if !self.unlocked.contains(achievement.name) && (achievement.reached)(self) {
+                self.unlocked.insert(String::from(achievement.name));
+                unlocked.push(achievement);
+            }
+        }
+        unlocked
+    }
+
+    pub fn is_unlocked(&self, achievement: &Achievement) -> bool {
+        self.unlocked.contains(achievement.name)
+    }
+
+    /// The statistics screen, a line per stat then every achievement
+    pub fn summary(&self, level_thickness: u32) -> Vec<String> {
+        let deepest = match self.deepest_level {
+            Some(z) => format!("level {} ({} m)", z, z * level_thickness),
+            None => String::from("none yet"),
+        };
+        let mut lines = vec![
+            format!("Tiles dug: {}", self.tiles_dug),
+            format!("Deepest dig: {}", deepest),
+            format!("Cave-ins: {}", self.cave_ins),
+            format!("Damage taken: {}", self.damage_taken),
+            format!("Deaths: {}", self.deaths),
+            format!("Stone hauled by cart: {}", self.stone_hauled),
+            String::new(),
+            format!("Achievements: {} of {}", self.unlocked.len(), ACHIEVEMENTS.len()),
+        ];
+        for achievement in ACHIEVEMENTS.iter() {
+            let mark = if self.is_unlocked(achievement) { "*" } else { " " };
+            lines.push(format!("{} {}: {}", mark, achievement.name, achievement.description));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_achievements_unlock_once() {
+        let mut stats = Stats::new();
+        let unlocked = stats.on_event(&GameEvent::Dug { x: 0, y: 0, z: 60 });
+        let names: Vec<_> = unlocked.iter().map(|achievement| achievement.name).collect();
+        println!("{:?}", names);
+        assert_eq!(names, vec!["Breaking ground", "Into the deep"]);
+        assert!(stats.on_event(&GameEvent::Dug { x: 1, y: 0, z: 2 }).is_empty());
+        assert_eq!(stats.tiles_dug, 2);
+        assert_eq!(stats.deepest_level, Some(60));
+
+        stats.on_event(&GameEvent::Hauled { x: 0, y: 0, z: 2, amount: 60 });
+        let unlocked = stats.on_event(&GameEvent::Hauled { x: 0, y: 0, z: 2, amount: 40 });
+        assert_eq!(unlocked.len(), 1);
+        assert_eq!(stats.stone_hauled, 100);
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color_scheme::ColorName;
+use crate::events::DeathCause;
+
+/// Something ailing an entity for a while
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    /// From breathing gas, hurts now and then and slows them down
+    Poisoned,
+    /// From being hit, hurts often and slows them down
+    Bleeding,
+    /// From a blow to the head, they can't move or work
+    Stunned,
+    /// Hurts most often and they drop their work, water puts it out
+    Burning,
+}
+
+impl Status {
+
+    pub const ALL: [Status; 4] = [
+        Status::Poisoned,
+        Status::Bleeding,
+        Status::Stunned,
+        Status::Burning,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Poisoned => "poisoned",
+            Status::Bleeding => "bleeding",
+            Status::Stunned => "stunned",
+            Status::Burning => "burning",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Status> {
+        Status::ALL.iter().copied().find(|status| status.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Ticks it lasts once taken
+    pub fn duration(&self) -> u64 {
+        match self {
+            Status::Poisoned => 900,
+            Status::Bleeding => 400,
+            Status::Stunned => 60,
+            Status::Burning => 200,
+        }
+    }
+
+    /// Ticks between the hits it does, None if it does no harm itself
+    pub fn hurts_every(&self) -> Option<u64> {
+        match self {
+            Status::Poisoned => Some(150),
+            Status::Bleeding => Some(80),
+            Status::Stunned => None,
+            Status::Burning => Some(30),
+        }
+    }
+
+    /// What it is put down as when it kills
+    pub fn cause(&self) -> DeathCause {
+        match self {
+            Status::Poisoned => DeathCause::Poison,
+            Status::Bleeding => DeathCause::BloodLoss,
+            Status::Stunned => DeathCause::Injuries,
+            Status::Burning => DeathCause::Burns,
+        }
+    }
+
+    /// Drawn small beside whoever is suffering it
+    pub fn glyph(&self) -> char {
+        match self {
+            Status::Poisoned => '+',
+            Status::Bleeding => '\'',
+            Status::Stunned => '*',
+            Status::Burning => '^',
+        }
+    }
+
+    pub fn color(&self) -> ColorName {
+        match self {
+            Status::Poisoned => ColorName::LightGreen,
+            Status::Bleeding => ColorName::Red,
+            Status::Stunned => ColorName::LightYellow,
+            Status::Burning => ColorName::Orange,
+        }
+    }
+}
+
+/// Every status an entity is suffering, with the tick each wears off
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Afflictions {
+    effects: Vec<(Status, u64)>,
+}
+
+impl Afflictions {
+
+    pub fn new() -> Afflictions {
+        Afflictions::default()
+    }
+
+    /// Starts the status at tick, or draws it out if it's already there
+    pub fn afflict(&mut self, status: Status, tick: u64) {
+        let until = tick + status.duration();
+        match self.effects.iter_mut().find(|(had, _)| *had == status) {
+            Some((_, had_until)) => *had_until = (*had_until).max(until),
+            None => self.effects.push((status, until)),
+        }
+    }
+
+    pub fn cure(&mut self, status: Status) {
+        self.effects.retain(|(had, _)| *had != status);
+    }
+
+    pub fn has(&self, status: Status) -> bool {
+        self.effects.iter().any(|(had, _)| *had == status)
+    }
+
+    pub fn statuses(&self) -> impl Iterator<Item = Status> + '_ {
+        self.effects.iter().map(|(status, _)| *status)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Drops whatever has worn off by tick, returning the statuses that
+    /// hurt this tick
+    pub fn tick(&mut self, tick: u64) -> Vec<Status> {
+        self.effects.retain(|&(_, until)| until > tick);
+        self.effects.iter()
+            .filter(|(status, until)| status.hurts_every()
+                .is_some_and(|every| (until - tick).is_multiple_of(every)))
+            .map(|(status, _)| *status)
+            .collect()
+    }
+
+    /// Whether they get to take a step at tick, the stunned can't and the
+    /// poisoned and bleeding only manage every other tick
+    pub fn moves_on(&self, tick: u64) -> bool {
+        if self.has(Status::Stunned) {
+            return false;
+        }
+        let slowed = self.has(Status::Poisoned) || self.has(Status::Bleeding);
+        !slowed || tick.is_multiple_of(2)
+    }
+
+    /// Whether they can get on with their work
+    pub fn can_work(&self) -> bool {
+        !self.has(Status::Stunned) && !self.has(Status::Burning)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statuses_hurt_until_they_wear_off() {
+        let mut afflictions = Afflictions::new();
+        afflictions.afflict(Status::Burning, 100);
+        afflictions.afflict(Status::Stunned, 100);
+        assert!(!afflictions.can_work());
+        assert!(!afflictions.moves_on(100));
+
+        let hits: Vec<u64> = (101..400).filter(|&tick| !afflictions.tick(tick).is_empty()).collect();
+        println!("{:?}", hits);
+        assert_eq!(hits, vec![120, 150, 180, 210, 240, 270]);
+        assert!(afflictions.is_empty());
+        assert!(afflictions.moves_on(401));
+
+        afflictions.afflict(Status::Bleeding, 500);
+        afflictions.afflict(Status::Bleeding, 450);
+        assert_eq!(afflictions.statuses().collect::<Vec<_>>(), vec![Status::Bleeding]);
+        assert!(afflictions.can_work());
+        assert!(!afflictions.moves_on(501));
+        assert!(afflictions.moves_on(502));
+        assert!(afflictions.tick(500 + Status::Bleeding.duration() - 1).is_empty());
+        afflictions.cure(Status::Bleeding);
+        assert!(afflictions.is_empty());
+        assert_eq!(Status::from_name("Burning"), Some(Status::Burning));
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+/// Fewest food items the stockpiles can hold before the colony is warned
+pub const LOW_FOOD: u32 = 5;
+
+/// What the stockpiles hold, counted as items are put in them and taken
+/// out rather than by looking through them all
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stock {
+    /// Items of each kind, by item id
+    counts: BTreeMap<String, u32>,
+    /// Items of every kind that feed livestock
+    food: u32,
+    /// Set once the colony has been warned food is low, until it isn't
+    low_food: bool,
+    /// Warnings not yet passed on to the players
+    warnings: Vec<String>,
+}
+
+impl Stock {
+
+    pub fn new() -> Stock {
+        Stock::default()
+    }
+
+    /// Counts an item put in a stockpile
+    pub fn store(&mut self, kind: &str, food: bool) {
+        *self.counts.entry(String::from(kind)).or_insert(0) += 1;
+        if food {
+            self.food += 1;
+            if self.food >= LOW_FOOD {
+                self.low_food = false;
+            }
+        }
+    }
+
+    /// Counts an item taken out of a stockpile, warning the first time that
+    /// leaves food low
+    pub fn take(&mut self, kind: &str, food: bool) {
+        if let Some(count) = self.counts.get_mut(kind) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(kind);
+            }
+        }
+        if food {
+            self.food = self.food.saturating_sub(1);
+            if self.food < LOW_FOOD && !self.low_food {
+                self.low_food = true;
+                self.warnings.push(format!("Food is running low, {} left in the stockpiles", self.food));
+            }
+        }
+    }
+
+    /// Forgets every count, to count them again after the stockpiles
+    /// themselves changed. Warnings already given stand.
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.food = 0;
+    }
+
+    pub fn count(&self, kind: &str) -> u32 {
+        self.counts.get(kind).copied().unwrap_or(0)
+    }
+
+    /// Each kind of item stockpiled with how many there are, by id
+    pub fn counts(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.counts.iter().map(|(kind, count)| (kind.as_str(), *count))
+    }
+
+    pub fn food(&self) -> u32 {
+        self.food
+    }
+
+    pub fn is_low_on_food(&self) -> bool {
+        self.food < LOW_FOOD
+    }
+
+    /// The warnings given since last time
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stock_counted_and_warns_once_when_food_runs_low() {
+        let mut stock = Stock::new();
+        for _ in 0..LOW_FOOD {
+            stock.store("mushroom", true);
+        }
+        stock.store("chitin", false);
+        assert_eq!(stock.count("mushroom"), LOW_FOOD);
+        assert_eq!(stock.counts().collect::<Vec<_>>(), vec![("chitin", 1), ("mushroom", LOW_FOOD)]);
+        assert!(!stock.is_low_on_food());
+
+        stock.take("chitin", false);
+        assert!(stock.take_warnings().is_empty());
+        stock.take("mushroom", true);
+        stock.take("mushroom", true);
+        let warnings = stock.take_warnings();
+        println!("{:?}", warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(stock.count("chitin"), 0);
+
+        // it only warns again once there's been enough since
+        stock.store("mushroom", true);
+        stock.take("mushroom", true);
+        assert!(stock.take_warnings().is_empty());
+        stock.store("mushroom", true);
+        stock.store("mushroom", true);
+        stock.take("mushroom", true);
+        assert_eq!(stock.take_warnings().len(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use crate::game_map::GameMap;
+
+/// How far a dug out tile may be from the nearest wall or pillar before its
+/// ceiling comes down, on Standard difficulty
+pub const SUPPORT_RANGE: u32 = 3;
+/// Sim ticks between a ceiling losing its support and the cave-in
+pub const COLLAPSE_DELAY: u64 = 50;
+
+/// Keeps track of dug out tiles that lost their support and the tick at 
+/// which each of them caves in
+pub struct Support {
+    pending: BTreeMap<(u32, u32, u32), u64>,
+}
+
+impl Support {
+
+    pub fn new() -> Support {
+        Support {
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Tiles waiting to cave in and the tick they collapse at
+    pub fn pending(&self) -> Vec<((u32, u32, u32), u64)> {
+        self.pending.iter().map(|(&pos, &tick)| (pos, tick)).collect()
+    }
+
+    pub fn restore(&mut self, pending: Vec<((u32, u32, u32), u64)>) {
+        self.pending.extend(pending);
+    }
+
+    pub fn is_pending(&self, x: u32, y: u32, z: u32) -> bool {
+        self.pending.contains_key(&(x, y, z))
+    }
+
+    /// Re-checks every dug tile that (x, y, z) could have been holding up,
+    /// call this whenever a support is removed
+    pub fn check_around(&mut self, map: &mut GameMap, x: u32, y: u32, z: u32, tick: u64) {
+        for (nx, ny) in tiles_in_range(map, x, y) {
+            let tile = map.get_tile(nx, ny, z);
+            if tile.dug && !tile.is_solid() 
+               && !self.is_pending(nx, ny, z) 
+               && !is_supported(map, nx, ny, z) {
+                self.pending.insert((nx, ny, z), tick + COLLAPSE_DELAY);
+            }
+        }
+    }
+
+    /// Removes and returns the tiles due to cave in at tick. Tiles that 
+    /// were shored up in the meantime are dropped without collapsing.
+    pub fn due_collapses(&mut self, map: &mut GameMap, tick: u64) -> Vec<(u32, u32, u32)> {
+        let due: Vec<(u32, u32, u32)> = self.pending.iter()
+            .filter(|(_, &collapse_tick)| collapse_tick <= tick)
+            .map(|(&pos, _)| pos)
+            .collect();
+
+        let mut collapses = Vec::new();
+        for (x, y, z) in due {
+            self.pending.remove(&(x, y, z));
+            let tile = map.get_tile(x, y, z);
+            if !tile.is_solid() && !is_supported(map, x, y, z) {
+                collapses.push((x, y, z));
+            }
+        }
+        collapses
+    }
+}
+
+/// Whether a wall or pillar stands within the difficulty's support range
+/// of (x, y, z)
+pub fn is_supported(map: &mut GameMap, x: u32, y: u32, z: u32) -> bool {
+    tiles_in_range(map, x, y).into_iter()
+        .any(|(nx, ny)| map.get_tile(nx, ny, z).is_support())
+}
+
+/// The tiles within support range of (x, y), wrapping around east to west
+fn tiles_in_range(map: &GameMap, x: u32, y: u32) -> Vec<(u32, u32)> {
+    let (_, max_y, _) = map.size();
+    let support_range = map.difficulty.support_range();
+    let y_range = y.saturating_sub(support_range)..=(y + support_range).min(max_y - 1);
+    let range = support_range as i64;
+    let mut tiles = Vec::new();
+    for ny in y_range {
+        for dx in -range..=range {
+            tiles.push((map.wrap_x(x as i64 + dx), ny));
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_map::TileKind;
+
+    const ROOM: (u32, u32, u32) = (1000, 1000, 40);
+
+    /// Digs out a room 13 tiles across inside a ring of wall, its middle
+    /// held up by a pillar, then knocks the pillar out and checks around it
+    /// at tick 100
+    fn room_losing_its_pillar(map: &mut GameMap, support: &mut Support) {
+        let (x, y, z) = ROOM;
+        for ty in y..y + 15 {
+            for tx in x..x + 15 {
+                let mut tile = map.get_tile(tx, ty, z);
+                tile.dig();
+                tile.set_fluid(0);
+                if tx == x || ty == y || tx == x + 14 || ty == y + 14 {
+                    tile.kind = TileKind::Wall;
+                } else if (tx, ty) == (x + 7, y + 7) {
+                    tile.build_pillar();
+                }
+                map.set_tile(tx, ty, z, tile);
+            }
+        }
+        assert!(support.pending().is_empty());
+        let mut pillar = map.get_tile(x + 7, y + 7, z);
+        pillar.dig();
+        map.set_tile(x + 7, y + 7, z, pillar);
+        support.check_around(map, x + 7, y + 7, z, 100);
+    }
+
+    #[test]
+    fn test_losing_a_support_brings_the_ceiling_down() {
+        let mut map = GameMap::with_seed(10);
+        assert_eq!(map.difficulty.support_range(), SUPPORT_RANGE);
+        let (x, y, z) = ROOM;
+        let mut support = Support::new();
+        room_losing_its_pillar(&mut map, &mut support);
+        println!("{:?}", support.pending());
+        // the 7 by 7 middle of the room is out of reach of the walls, the
+        // tiles nearer them stay up
+        assert!(support.is_pending(x + 7, y + 7, z));
+        assert!(support.is_pending(x + 10, y + 4, z));
+        assert!(!support.is_pending(x + 3, y + 7, z));
+        assert!(is_supported(&mut map, x + 3, y + 7, z));
+        assert!(!is_supported(&mut map, x + 4, y + 7, z));
+        assert_eq!(support.pending().len(), 49);
+    }
+
+    #[test]
+    fn test_cave_in_waits_out_the_delay() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = ROOM;
+        let mut support = Support::new();
+        room_losing_its_pillar(&mut map, &mut support);
+        assert!(support.pending().iter().all(|&(_, tick)| tick == 100 + COLLAPSE_DELAY));
+
+        assert!(support.due_collapses(&mut map, 100 + COLLAPSE_DELAY - 1).is_empty());
+        assert_eq!(support.pending().len(), 49);
+        // checking again doesn't put the collapse off
+        support.check_around(&mut map, x + 7, y + 7, z, 120);
+        assert!(support.pending().iter().all(|&(_, tick)| tick == 100 + COLLAPSE_DELAY));
+
+        let collapses = support.due_collapses(&mut map, 100 + COLLAPSE_DELAY);
+        assert_eq!(collapses.len(), 49);
+        assert!(collapses.contains(&(x + 7, y + 7, z)));
+        assert!(support.pending().is_empty());
+    }
+
+    #[test]
+    fn test_shoring_up_cancels_the_cave_in() {
+        let mut map = GameMap::with_seed(10);
+        let (x, y, z) = ROOM;
+        let mut support = Support::new();
+        room_losing_its_pillar(&mut map, &mut support);
+
+        // a pillar put up off the middle saves the tiles it reaches
+        let mut pillar = map.get_tile(x + 5, y + 5, z);
+        pillar.build_pillar();
+        map.set_tile(x + 5, y + 5, z, pillar);
+        let collapses = support.due_collapses(&mut map, 100 + COLLAPSE_DELAY);
+        println!("{:?}", collapses);
+        assert!(support.pending().is_empty());
+        assert!(!collapses.contains(&(x + 7, y + 7, z)));
+        assert!(collapses.contains(&(x + 10, y + 10, z)));
+        assert!(collapses.contains(&(x + 4, y + 9, z)));
+        // the 7 by 7 middle less the 5 by 5 of it the new pillar reaches
+        assert_eq!(collapses.len(), 49 - 25);
+    }
+}
@@ -0,0 +1,159 @@
+use quicksilver::prelude::*;
+
+/// Which characters a text field takes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Anything printable
+    Any,
+    Digits,
+    /// Letters, digits, spaces, hyphens and apostrophes
+    Name,
+}
+
+impl Filter {
+
+    pub fn allows(&self, c: char) -> bool {
+        match self {
+            Filter::Any => !c.is_control(),
+            Filter::Digits => c.is_ascii_digit(),
+            Filter::Name => c.is_alphanumeric() || c == ' ' || c == '-' || c == '\'',
+        }
+    }
+}
+
+/// A line of text being typed, with a cursor that can be moved around in
+/// it. Characters come from typed events, editing keys from the keyboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextInput {
+    chars: Vec<char>,
+    /// Index of the character the cursor is in front of
+    cursor: usize,
+    filter: Filter,
+    max_len: usize,
+}
+
+impl TextInput {
+
+    pub fn new(filter: Filter, max_len: usize) -> TextInput {
+        TextInput { chars: Vec::new(), cursor: 0, filter, max_len }
+    }
+
+    /// Starts the field off with text, the cursor at its end
+    pub fn with_text(mut self, text: &str) -> TextInput {
+        for c in text.chars() {
+            self.type_char(c);
+        }
+        self
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Inserts c at the cursor if the filter takes it and there's room
+    pub fn type_char(&mut self, c: char) {
+        if self.filter.allows(c) && self.chars.len() < self.max_len {
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    /// Removes the character before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    /// Removes the character after the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    /// Empties the field, returning what was in it
+    pub fn take(&mut self) -> String {
+        let text = self.text();
+        self.chars.clear();
+        self.cursor = 0;
+        text
+    }
+
+    /// Applies the editing keys pressed this frame
+    pub fn handle_keys(&mut self, window: &Window) {
+        let pressed = |key| window.keyboard()[key] == ButtonState::Pressed;
+        if pressed(Key::Back) {
+            self.backspace();
+        }
+        if pressed(Key::Delete) {
+            self.delete();
+        }
+        if pressed(Key::Left) {
+            self.move_left();
+        }
+        if pressed(Key::Right) {
+            self.move_right();
+        }
+        if pressed(Key::Home) {
+            self.cursor = 0;
+        }
+        if pressed(Key::End) {
+            self.cursor = self.chars.len();
+        }
+    }
+
+    /// The text with the cursor drawn in, an underscore at the end or a bar
+    /// between characters
+    pub fn display(&self) -> String {
+        let before: String = self.chars[..self.cursor].iter().collect();
+        let after: String = self.chars[self.cursor..].iter().collect();
+        if after.is_empty() {
+            format!("{}_", before)
+        } else {
+            format!("{}|{}", before, after)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editing_at_the_cursor() {
+        let mut input = TextInput::new(Filter::Any, 10).with_text("helo");
+        input.move_left();
+        input.type_char('l');
+        println!("{}", input.display());
+        assert_eq!(input.display(), "hell|o");
+        input.move_left();
+        input.backspace();
+        input.delete();
+        assert_eq!(input.text(), "heo");
+        input.move_right();
+        input.move_right();
+        input.move_right();
+        assert_eq!(input.display(), "heo_");
+        assert_eq!(input.take(), "heo");
+        assert_eq!(input.display(), "_");
+    }
+
+    #[test]
+    fn test_filters_and_length() {
+        let seed = TextInput::new(Filter::Digits, 4).with_text("12a34\n5");
+        assert_eq!(seed.text(), "1234");
+        let name = TextInput::new(Filter::Name, 20).with_text("Old Mo's pit #2\t");
+        assert_eq!(name.text(), "Old Mo's pit 2");
+        assert!(!Filter::Any.allows('\u{8}'));
+    }
+}
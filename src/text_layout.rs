@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+use quicksilver::prelude::{Color, FontStyle};
+use rusttype::{Font, Scale};
+
+/// Where quicksilver loads assets from on desktop
+pub const ASSET_DIR: &str = "static";
+
+/// The size and color of text, kept apart so text can be measured at its
+/// size before it's rendered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    pub size: f32,
+    pub color: Color,
+}
+
+impl TextStyle {
+
+    pub fn new(size: f32, color: Color) -> TextStyle {
+        TextStyle { size, color }
+    }
+
+    pub fn font_style(&self) -> FontStyle {
+        FontStyle::new(self.size, self.color)
+    }
+}
+
+/// Measures and wraps text the way the UI font renders it, from the
+/// advance widths of its glyphs. Widths are kept once looked up, so text
+/// laid out every frame doesn't go back to the font.
+pub struct TextLayout {
+    font: Option<Font<'static>>,
+    /// Advance widths by glyph and the bits of the size they're at
+    advances: RefCell<HashMap<(char, u32), f32>>,
+}
+
+impl TextLayout {
+
+    /// Measures with the font from the asset directory. Without it glyphs
+    /// are taken to be half as wide as they're tall, wide ones as wide.
+    pub fn load(font_name: &str) -> TextLayout {
+        let path = Path::new(ASSET_DIR).join(font_name);
+        let font = match std::fs::read(&path).map(Font::from_bytes) {
+            Ok(Ok(font)) => Some(font),
+            Ok(Err(err)) => {
+                log::warn!("could not load {} to measure text with: {}", path.display(), err);
+                None
+            },
+            Err(err) => {
+                log::warn!("could not read {} to measure text with: {}", path.display(), err);
+                None
+            },
+        };
+        TextLayout { font, advances: RefCell::new(HashMap::new()) }
+    }
+
+    /// How far along the line the glyph takes text at the size. Glyphs
+    /// the font doesn't have take up as much as the box it draws instead.
+    pub fn advance(&self, glyph: char, size: f32) -> f32 {
+        let font = &self.font;
+        *self.advances.borrow_mut().entry((glyph, size.to_bits())).or_insert_with(|| match font {
+            Some(font) => font.glyph(glyph).scaled(Scale::uniform(size)).h_metrics().advance_width,
+            None if is_wide(glyph) => size,
+            None => size / 2.0,
+        })
+    }
+
+    /// How wide the text is on a single line
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        text.chars().map(|glyph| self.advance(glyph, size)).sum()
+    }
+
+    /// The text split into lines no wider than max_width
+    pub fn wrap(&self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        wrap_with(text, max_width, |glyph| self.advance(glyph, size))
+    }
+}
+
+/// Whether the glyph takes up two columns of a monospaced line, like
+/// Chinese, Japanese and Korean text and emoji
+pub fn is_wide(glyph: char) -> bool {
+    matches!(glyph as u32,
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF | 0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD)
+}
+
+/// Splits text into lines no wider than max_width, glyphs as wide as the
+/// advance says. Lines break between words where they can and between
+/// glyphs where a word is too long for a line, or isn't spaced out from
+/// the next like Chinese and Japanese. Newlines always break, and spaces
+/// are kept except where a line broke.
+pub fn wrap_with(text: &str, max_width: f32, advance: impl Fn(char) -> f32) -> Vec<String> {
+    let space = advance(' ');
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut width = 0.0;
+        // the line was started by breaking the one before it
+        let mut broken = false;
+        for (index, word) in paragraph.split(' ').enumerate() {
+            let word_width: f32 = word.chars().map(&advance).sum();
+            if index > 0 && !(broken && line.is_empty()) {
+                if width + space + word_width <= max_width || line.trim().is_empty() {
+                    line.push(' ');
+                    width += space;
+                } else {
+                    break_line(&mut lines, &mut line);
+                    width = 0.0;
+                    broken = true;
+                }
+            }
+            for glyph in word.chars() {
+                let glyph_width = advance(glyph);
+                if width + glyph_width > max_width && !line.trim().is_empty() {
+                    break_line(&mut lines, &mut line);
+                    width = 0.0;
+                    broken = true;
+                }
+                line.push(glyph);
+                width += glyph_width;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Ends the line where it broke, without the spaces it broke at
+fn break_line(lines: &mut Vec<String>, line: &mut String) {
+    lines.push(String::from(line.trim_end()));
+    line.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_wraps_to_width() {
+        // a column for narrow glyphs and two for wide ones
+        let columns = |text: &str, max_width: f32| wrap_with(
+            text, max_width, |glyph| if is_wide(glyph) { 2.0 } else { 1.0 });
+        println!("{:?}", columns("go 坑道を掘る now", 7.0));
+        assert_eq!(columns("dig the tunnel", 8.0), vec!["dig the", "tunnel"]);
+        assert_eq!(columns("坑道を掘る", 6.0), vec!["坑道を", "掘る"]);
+        assert_eq!(columns("go 坑道を掘る now", 7.0), vec!["go", "坑道を", "掘る", "now"]);
+        assert_eq!(columns("a\n\nb", 8.0), vec!["a", "", "b"]);
+        assert_eq!(columns("  Tab: commands  Esc: quit", 16.0), vec!["  Tab: commands", "Esc: quit"]);
+        assert_eq!(columns("tunnelling", 4.0), vec!["tunn", "elli", "ng"]);
+
+        // the UI font is monospaced, and measured from its metrics
+        let layout = TextLayout::load("mononoki-Regular.ttf");
+        let m = layout.measure("m", 18.0);
+        assert!(m > 0.0);
+        assert!((layout.measure("mmmm", 18.0) - m * 4.0).abs() < 0.01);
+        assert_eq!(layout.wrap("mm mm", 18.0, m * 4.0), vec!["mm", "mm"]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+/// Simulation ticks per second. Fluids, jobs and everything else that
+/// changes the world moves in whole ticks, never by how long a frame took.
+pub const TICKS_PER_SECOND: u32 = 10;
+
+/// Most ticks run in one update, time past that is dropped so a long stall
+/// slows the game down instead of freezing it while it catches up
+const MAX_TICKS_PER_UPDATE: u32 = 5;
+
+/// Turns the wall clock into a count of simulation ticks, carrying the
+/// time left over from one update into the next
+pub struct FixedTimestep {
+    tick_length: Duration,
+    accumulator: Duration,
+    last: Instant,
+}
+
+impl FixedTimestep {
+
+    pub fn new(ticks_per_second: u32) -> FixedTimestep {
+        FixedTimestep {
+            tick_length: Duration::from_secs(1) / ticks_per_second,
+            accumulator: Duration::default(),
+            last: Instant::now(),
+        }
+    }
+
+    /// How many ticks came due since the last call
+    pub fn advance(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        self.advance_by(elapsed)
+    }
+
+    /// How many ticks came due with elapsed more time on the clock
+    pub fn advance_by(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_length {
+            self.accumulator -= self.tick_length;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_UPDATE {
+            self.accumulator = Duration::default();
+            ticks = MAX_TICKS_PER_UPDATE;
+        }
+        ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leftover_time_carries_over() {
+        let mut timestep = FixedTimestep::new(20);
+        let ticks: Vec<_> = [30, 30, 30, 10, 0, 1000].iter()
+            .map(|ms| timestep.advance_by(Duration::from_millis(*ms)))
+            .collect();
+        println!("{:?}", ticks);
+        assert_eq!(ticks, vec![0, 1, 0, 1, 0, MAX_TICKS_PER_UPDATE]);
+        assert_eq!(timestep.accumulator, Duration::default());
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use crate::entity::{Entities, EntityId};
+use crate::game_map::GameMap;
+use crate::pathfinding;
+
+/// How far the player sees in tiles, for exploring and spotting hostiles
+pub const SIGHT_RADIUS: u32 = 8;
+
+/// Where the player is walking to on their own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Destination {
+    Tile(u32, u32),
+    /// The nearest open tile nobody has seen yet, then the next one
+    Explore,
+}
+
+/// A walk the player was sent on, it stops when they take damage or a
+/// hostile comes into sight
+#[derive(Clone, Debug, PartialEq)]
+pub struct Travel {
+    pub destination: Destination,
+    /// Hostiles that were in sight already when the walk started, only
+    /// new ones stop it
+    pub seen_hostiles: HashSet<EntityId>,
+}
+
+impl Travel {
+
+    pub fn new(destination: Destination, seen_hostiles: HashSet<EntityId>) -> Travel {
+        Travel { destination, seen_hostiles }
+    }
+}
+
+/// Living hostile entities within sight of (x, y, z)
+pub fn hostiles_in_sight(entities: &Entities, map: &GameMap, x: u32, y: u32, z: u32)
+    -> HashSet<EntityId> {
+    entities.iter()
+        .filter(|(_, entity)| entity.hostile && entity.is_alive() && entity.depth == z)
+        .filter(|(_, entity)| {
+            let pos = (entity.pos.x as u32, entity.pos.y as u32);
+            pathfinding::distance(map, pos, (x, y)) <= SIGHT_RADIUS
+        })
+        .map(|(id, _)| id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reveal_wraps_around_the_world() {
+        let mut map = GameMap::with_seed(10);
+        let (max_x, _, _) = map.size();
+        map.reveal_around(0, 0, 5, SIGHT_RADIUS);
+        println!("{} chunks", map.explored_chunks().len());
+        assert!(map.is_explored(max_x - SIGHT_RADIUS, 0, 5));
+        assert!(map.is_explored(0, SIGHT_RADIUS, 5));
+        assert!(!map.is_explored(0, SIGHT_RADIUS + 1, 5));
+        assert!(!map.is_explored(SIGHT_RADIUS, SIGHT_RADIUS, 5));
+        assert!(!map.is_explored(0, 0, 4));
+    }
+}
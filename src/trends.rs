@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Ticks in a day on Janus 7, what the graphs count by
+pub const DAY_TICKS: u64 = 1200;
+/// Days each graph goes back
+pub const DAYS_KEPT: usize = 30;
+
+/// How much of something there was each day, today last. The oldest day
+/// is dropped once DAYS_KEPT have gone by.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Series {
+    days: VecDeque<u32>,
+}
+
+impl Series {
+
+    pub fn new() -> Series {
+        Series::default()
+    }
+
+    /// Counts amount more for today
+    pub fn add(&mut self, amount: u32) {
+        if self.days.is_empty() {
+            self.days.push_back(0);
+        }
+        if let Some(today) = self.days.back_mut() {
+            *today += amount;
+        }
+    }
+
+    /// Starts counting a new day
+    fn roll(&mut self) {
+        if self.days.len() >= DAYS_KEPT {
+            self.days.pop_front();
+        }
+        self.days.push_back(0);
+    }
+
+    /// Each day's amount, the oldest first
+    pub fn days(&self) -> Vec<u32> {
+        self.days.iter().copied().collect()
+    }
+
+    pub fn today(&self) -> u32 {
+        self.days.back().copied().unwrap_or(0)
+    }
+
+    /// The most there was in a day
+    pub fn most(&self) -> u32 {
+        self.days.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// What the colony makes and uses up each day, counted as it happens
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Trends {
+    /// Stone dug out of the rock
+    pub mined: Series,
+    /// Food harvested off the farms
+    pub grown: Series,
+    /// Food taken from the stockpiles to feed livestock
+    pub eaten: Series,
+}
+
+impl Trends {
+
+    pub fn new() -> Trends {
+        Trends::default()
+    }
+
+    /// Starts a new day on every graph once one is due
+    pub fn step(&mut self, tick: u64) {
+        if tick.is_multiple_of(DAY_TICKS) {
+            for series in [&mut self.mined, &mut self.grown, &mut self.eaten].iter_mut() {
+                series.roll();
+            }
+        }
+    }
+
+    /// Each graph with what it's of, in the order they're drawn
+    pub fn series(&self) -> [(&'static str, &Series); 3] {
+        [
+            ("Stone mined", &self.mined),
+            ("Food grown", &self.grown),
+            ("Food eaten", &self.eaten),
+        ]
+    }
+
+    /// A line for each graph saying what it shows
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!("Each day of the last {}, top to bottom:", DAYS_KEPT)];
+        lines.extend(self.series().iter().map(|(name, series)|
+            format!("{}: {} today, {} at most", name, series.today(), series.most())));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trends_keep_the_last_days() {
+        let mut trends = Trends::new();
+        trends.mined.add(3);
+        trends.mined.add(2);
+        assert_eq!(trends.mined.days(), vec![5]);
+        assert!(trends.eaten.days().is_empty());
+
+        for tick in 1..=DAY_TICKS * 2 {
+            trends.step(tick);
+        }
+        trends.eaten.add(1);
+        println!("{:?}", trends);
+        assert_eq!(trends.mined.days(), vec![5, 0, 0]);
+        assert_eq!(trends.eaten.days(), vec![0, 1]);
+        assert_eq!(trends.summary()[3], "Food eaten: 1 today, 1 at most");
+
+        for tick in 0..DAY_TICKS * DAYS_KEPT as u64 {
+            trends.step(tick);
+        }
+        assert_eq!(trends.mined.days().len(), DAYS_KEPT);
+        assert_eq!(trends.mined.most(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use quicksilver::prelude::*;
+
+use crate::color_scheme::ColorScheme;
+use crate::layer::RenderLayer;
+use crate::panel::{Anchor, Panel};
+use crate::scenario::{Goal, Scenario, Step};
+use crate::stats::Stats;
+use crate::text_layout::{TextLayout, TextStyle};
+
+/// Gap between the prompt and the bottom of the screen, before the UI
+/// scale, leaving room for the command bar
+const BOTTOM_MARGIN: f32 = 40.0;
+
+/// How far along the player is, taken every frame so a step's goal can be
+/// measured from where the step started
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// Top left corner of the view
+    pub view: (f32, f32),
+    pub level: u32,
+    pub player: (f32, f32),
+    pub dug: u64,
+    pub designated: usize,
+    pub stockpiles: usize,
+}
+
+impl Progress {
+
+    /// Whether goal has been met since start
+    fn meets(&self, goal: Goal, start: &Progress) -> bool {
+        let distance = |(x, y): (f32, f32), (start_x, start_y): (f32, f32)| {
+            (x - start_x).abs().max((y - start_y).abs())
+        };
+        match goal {
+            Goal::Continue => false,
+            Goal::Scroll(tiles) => distance(self.view, start.view) >= tiles as f32,
+            Goal::ChangeLevel => self.level != start.level,
+            Goal::Walk(tiles) => distance(self.player, start.player) >= tiles as f32,
+            Goal::Dig(tiles) => self.dug >= start.dug + tiles as u64,
+            Goal::Designate(tiles) => self.designated >= tiles as usize,
+            Goal::Stockpile => self.stockpiles > start.stockpiles,
+        }
+    }
+}
+
+/// Leads the player through a scenario's steps, showing the prompt of the
+/// one they're on until its goal is met, then what's left to win it
+pub struct Tutorial {
+    scenario: Scenario,
+    step: usize,
+    /// Where the player was when the step started
+    start: Option<Progress>,
+    won: bool,
+}
+
+impl Tutorial {
+
+    pub fn new(scenario: Scenario) -> Tutorial {
+        Tutorial { scenario, step: 0, start: None, won: false }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.scenario.title
+    }
+
+    /// Whether the scenario was won just now, true only the once
+    pub fn check_victory(&mut self, stats: &Stats, tick: u64) -> bool {
+        if self.won || !self.scenario.is_won(stats, tick) {
+            return false;
+        }
+        self.won = true;
+        true
+    }
+
+    pub fn current(&self) -> Option<&Step> {
+        self.scenario.steps.get(self.step)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+
+    /// Moves on to the next step once the current one's goal is met, or
+    /// Return was pressed on one that asks for it. Returns whether it did.
+    pub fn update(&mut self, now: Progress, proceed: bool) -> bool {
+        let goal = match self.current() {
+            Some(step) => step.goal,
+            None => return false,
+        };
+        let start = *self.start.get_or_insert(now);
+        let met = match goal {
+            Goal::Continue => proceed,
+            goal => now.meets(goal, &start),
+        };
+        if met {
+            self.step += 1;
+            self.start = None;
+        }
+        met
+    }
+
+    /// Draws the prompt, or the win conditions once the steps are done, in
+    /// a box at the bottom of the screen, under any dialog
+    pub fn draw(&self, window: &mut Window, font: &mut Font, text_layout: &TextLayout,
+                color_scheme: &ColorScheme, scale: f32) -> Result<()> {
+        let (title, text) = match self.current() {
+            Some(step) => (
+                format!("{} {}/{}", self.scenario.title, self.step + 1, self.scenario.steps.len()),
+                step.prompt.clone(),
+            ),
+            None if !self.won && !self.scenario.victory.is_empty() => (
+                format!("{} to win:", self.scenario.title),
+                self.scenario.victory.iter().map(|victory| victory.describe()).collect(),
+            ),
+            None => return Ok(()),
+        };
+        Panel::new(Anchor::Bottom, RenderLayer::UiPanels)
+            .with_margin((0.0, BOTTOM_MARGIN * scale))
+            .with_padding(12.0 * scale)
+            .with_line_height(24.0 * scale)
+            .with_background(Color::from_hex(&color_scheme.bg))
+            .with_text(&title, TextStyle::new(20.0 * scale, Color::from_hex(&color_scheme.light_yellow)))
+            .with_lines(&text, TextStyle::new(18.0 * scale, Color::from_hex(&color_scheme.fg)))
+            .draw(window, font, text_layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_advance_on_their_goals() {
+        let scenario = Scenario::parse("| @\nstep continue\nstep scroll 4\nstep dig 2\n").unwrap();
+        let mut tutorial = Tutorial::new(scenario);
+        let mut now = Progress { dug: 5, ..Progress::default() };
+
+        assert!(!tutorial.update(now, false));
+        assert!(tutorial.update(now, true));
+        now.view = (3.0, 0.0);
+        assert!(!tutorial.update(now, true));
+        now.view = (3.0, -4.0);
+        assert!(tutorial.update(now, false));
+        now.dug = 6;
+        tutorial.update(now, false);
+        println!("{:?}", tutorial.current());
+        assert_eq!(tutorial.current().map(|step| step.goal), Some(Goal::Dig(2)));
+        now.dug = 7;
+        assert!(!tutorial.update(now, false));
+        now.dug = 8;
+        assert!(tutorial.update(now, false));
+        assert!(tutorial.is_finished());
+    }
+}
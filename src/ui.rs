@@ -0,0 +1,119 @@
+use enum_map::EnumMap;
+use quicksilver::prelude::*;
+
+use crate::bindings::Action;
+
+/// Draws a component onto the window from what it's part of
+pub type DrawFn<T> = fn(&mut T, &mut Window) -> Result<()>;
+
+/// A part of the screen, shown or hidden by the player
+pub struct UiComponent<T> {
+    /// What it's toggled by and reported under if it fails to draw
+    pub name: &'static str,
+    pub visible: bool,
+    /// The key action that shows or hides it, if there is one
+    pub toggle: Option<Action>,
+    pub draw: DrawFn<T>,
+}
+
+impl<T> UiComponent<T> {
+
+    pub fn new(name: &'static str, visible: bool, draw: DrawFn<T>) -> UiComponent<T> {
+        UiComponent { name, visible, toggle: None, draw }
+    }
+
+    pub fn with_toggle(mut self, action: Action) -> UiComponent<T> {
+        self.toggle = Some(action);
+        self
+    }
+}
+
+/// Every component of the screen, drawn in the order they were registered.
+/// Whatever draws a panel registers it here instead of being drawn by
+/// name.
+pub struct UiRegistry<T> {
+    components: Vec<UiComponent<T>>,
+}
+
+impl<T> UiRegistry<T> {
+
+    pub fn new() -> UiRegistry<T> {
+        UiRegistry { components: Vec::new() }
+    }
+
+    /// Adds the component after the others, or in place of the one
+    /// registered under its name already
+    pub fn register(&mut self, component: UiComponent<T>) {
+        match self.components.iter_mut().find(|registered| registered.name == component.name) {
+            Some(registered) => *registered = component,
+            None => self.components.push(component),
+        }
+    }
+
+    /// False for components that aren't registered
+    pub fn is_visible(&self, name: &str) -> bool {
+        self.components.iter().any(|component| component.name == name && component.visible)
+    }
+
+    /// Shows the component if it's hidden and hides it if it's shown,
+    /// false if there's no such component
+    pub fn toggle(&mut self, name: &str) -> bool {
+        match self.components.iter_mut().find(|component| component.name == name) {
+            Some(component) => {
+                component.visible = !component.visible;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Toggles the components whose keys were pressed
+    pub fn toggle_pressed(&mut self, pressed: &EnumMap<Action, bool>) {
+        for component in &mut self.components {
+            if component.toggle.is_some_and(|action| pressed[action]) {
+                component.visible = !component.visible;
+            }
+        }
+    }
+
+    /// The name and draw function of every component being shown, in the
+    /// order they're drawn
+    pub fn visible(&self) -> Vec<(&'static str, DrawFn<T>)> {
+        self.components.iter()
+            .filter(|component| component.visible)
+            .map(|component| (component.name, component.draw))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw_nothing(_: &mut u32, _: &mut Window) -> Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn test_components_registered_and_toggled() {
+        let mut ui = UiRegistry::new();
+        ui.register(UiComponent::new("map", true, draw_nothing));
+        ui.register(UiComponent::new("help", false, draw_nothing).with_toggle(Action::Help));
+        ui.register(UiComponent::new("clock", true, draw_nothing));
+        let names = |ui: &UiRegistry<u32>| ui.visible().iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        println!("{:?}", names(&ui));
+        assert_eq!(names(&ui), vec!["map", "clock"]);
+
+        let mut pressed = EnumMap::default();
+        pressed[Action::Help] = true;
+        ui.toggle_pressed(&pressed);
+        assert!(ui.is_visible("help"));
+        assert!(ui.toggle("map"));
+        assert!(!ui.toggle("minimap"));
+        assert_eq!(names(&ui), vec!["help", "clock"]);
+
+        // registering it again replaces it where it was
+        ui.register(UiComponent::new("map", true, draw_nothing));
+        assert_eq!(names(&ui), vec!["map", "help", "clock"]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blueprint::Plan;
+use crate::designation::{Designations, NamedStockpile};
+use crate::game_map::GameMap;
+use crate::rail::{Railway, Track};
+use crate::selection::Area;
+
+/// Most edits kept for undoing, the oldest are forgotten past it
+pub const UNDO_LIMIT: usize = 50;
+
+/// Track laid on a tile, with what was there before and what was laid
+pub type TrackChange = ((u32, u32, u32), Option<Track>, Track);
+
+/// What one designation or track order changed, enough to take it back.
+/// Pillars and minecarts aren't edits, and neither is the digging done on
+/// designated tiles, the world has already moved on from those.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Edit {
+    /// Tiles newly designated to be dug
+    pub marked: Vec<(u32, u32, u32)>,
+    /// Tiles that were designated before a cancel
+    pub unmarked: Vec<(u32, u32, u32)>,
+    pub added_stockpile: Option<Area>,
+    /// Stockpiles replaced or canceled, with their names
+    pub removed_stockpiles: Vec<NamedStockpile>,
+    pub track: Vec<TrackChange>,
+    #[serde(default)]
+    pub added_burrow: Option<Area>,
+    /// Areas taken out of the burrow
+    #[serde(default)]
+    pub removed_burrows: Vec<Area>,
+    #[serde(default)]
+    pub added_pen: Option<Area>,
+    /// Pens replaced or removed
+    #[serde(default)]
+    pub removed_pens: Vec<Area>,
+    #[serde(default)]
+    pub added_refuse: Option<Area>,
+    /// Refuse zones replaced or canceled
+    #[serde(default)]
+    pub removed_refuse: Vec<Area>,
+    #[serde(default)]
+    pub added_farm: Option<Area>,
+    /// Farm plots replaced or removed
+    #[serde(default)]
+    pub removed_farms: Vec<Area>,
+    /// Tiles newly planned to be built on, with the plan each had before
+    #[serde(default)]
+    pub planned: Vec<((u32, u32, u32), Option<Plan>)>,
+    /// Constructions that were planned before a cancel
+    #[serde(default)]
+    pub unplanned: Vec<((u32, u32, u32), Plan)>,
+}
+
+impl Edit {
+
+    pub fn is_empty(&self) -> bool {
+        *self == Edit::default()
+    }
+
+    /// Puts the designations and track back the way they were. Tiles dug
+    /// out since aren't marked again, and track changed since is left.
+    pub fn revert(self, map: &mut GameMap, designations: &mut Designations,
+                  railway: &mut Railway) {
+        designations.unmark_dig(&self.marked);
+        designations.mark_dig(map, &self.unmarked);
+        if let Some(area) = self.added_stockpile {
+            designations.remove_stockpile(area);
+        }
+        for (area, name) in self.removed_stockpiles {
+            designations.restore_stockpile(area, name);
+        }
+        if let Some(area) = self.added_burrow {
+            designations.remove_burrow(area);
+        }
+        for area in self.removed_burrows {
+            designations.add_burrow(area);
+        }
+        if let Some(area) = self.added_pen {
+            designations.remove_pen(area);
+        }
+        for area in self.removed_pens {
+            designations.add_pen(area);
+        }
+        if let Some(area) = self.added_refuse {
+            designations.remove_refuse(area);
+        }
+        for area in self.removed_refuse {
+            designations.add_refuse(area);
+        }
+        if let Some(area) = self.added_farm {
+            designations.remove_farm(area);
+        }
+        for area in self.removed_farms {
+            designations.add_farm(area);
+        }
+        for ((x, y, z), before) in self.planned.into_iter().rev() {
+            match before {
+                Some(plan) => { designations.plan_build(x, y, z, plan); },
+                None => designations.finish_build(x, y, z),
+            }
+        }
+        for ((x, y, z), plan) in self.unplanned {
+            designations.plan_build(x, y, z, plan);
+        }
+        for ((x, y, z), before, laid) in self.track.into_iter().rev() {
+            if railway.track_at(x, y, z) == Some(laid) {
+                railway.remove(x, y, z);
+                if let Some(before) = before {
+                    railway.lay(x, y, z, before);
+                }
+            }
+        }
+    }
+}
+
+/// The players' latest edits, each undone by the player who made it
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UndoStack {
+    /// Oldest first, with the peer that made each
+    edits: VecDeque<(u32, Edit)>,
+}
+
+impl UndoStack {
+
+    pub fn new() -> UndoStack {
+        UndoStack { edits: VecDeque::new() }
+    }
+
+    /// Remembers the edit, unless it changed nothing
+    pub fn push(&mut self, peer: u32, edit: Edit) {
+        if edit.is_empty() {
+            return;
+        }
+        self.edits.push_back((peer, edit));
+        if self.edits.len() > UNDO_LIMIT {
+            self.edits.pop_front();
+        }
+    }
+
+    /// Takes the peer's latest edit off the stack
+    pub fn pop(&mut self, peer: u32) -> Option<Edit> {
+        let index = self.edits.iter().rposition(|(by, _)| *by == peer)?;
+        self.edits.remove(index).map(|(_, edit)| edit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_designations_and_track() {
+        let mut map = GameMap::with_seed(10);
+        let mut designations = Designations::new();
+        let mut railway = Railway::new();
+        let mut undo = UndoStack::new();
+        let pile = Area::from_corners((0, 0), (3, 3), 40);
+
+        designations.add_stockpile(pile);
+        designations.name_stockpile_at(0, 0, 40, String::from("Quarry"));
+        let (unmarked, removed_stockpiles) = designations.cancel(pile);
+        undo.push(1, Edit { unmarked, removed_stockpiles, ..Edit::default() });
+        railway.lay(5, 5, 40, Track::Stop);
+        undo.push(0, Edit { track: vec![((5, 5, 40), None, Track::Stop)], ..Edit::default() });
+        undo.push(0, Edit::default());
+
+        undo.pop(0).unwrap().revert(&mut map, &mut designations, &mut railway);
+        assert_eq!(railway.track_at(5, 5, 40), None);
+        assert!(undo.pop(0).is_none());
+
+        let edit = undo.pop(1).unwrap();
+        println!("{:?}", edit);
+        edit.revert(&mut map, &mut designations, &mut railway);
+        assert_eq!(designations.stockpile_at(2, 2, 40), Some(pile));
+        assert_eq!(designations.stockpile_name(pile), Some("Quarry"));
+
+        for i in 0..UNDO_LIMIT + 5 {
+            undo.push(0, Edit { marked: vec![(i as u32, 0, 0)], ..Edit::default() });
+        }
+        assert_eq!(undo.edits.len(), UNDO_LIMIT);
+        assert_eq!(undo.edits[0].1.marked, vec![(5, 0, 0)]);
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{Frame, Renderable};
+use crate::color_scheme::ColorName;
+use crate::sight::SIGHT_RANGE;
+
+/// Keeps the weather's rolls apart from the incidents' on the same tick
+const WEATHER_STREAM: u64 = 0x5745_4154_4845_5200;
+/// Ticks between rain falling on the surface around the squad
+pub const RAIN_INTERVAL: u64 = 40;
+/// Furthest from a colonist the rain they'd notice falls, in tiles
+pub const RAIN_RADIUS: u32 = 8;
+
+/// The generator for the weather's rolls at tick, the same for every
+/// game on the seed
+pub fn rng(seed: u32, tick: u64) -> oorandom::Rand32 {
+    oorandom::Rand32::new(((seed as u64) << 32) ^ tick ^ WEATHER_STREAM)
+}
+
+/// What the sky over the surface of Janus 7 is doing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weather {
+    #[default]
+    Clear,
+    /// Hides all but what's close and makes outdoor work slow going
+    DustStorm,
+    /// Fills the hollows on the surface and slows outdoor work a little
+    AcidRain,
+}
+
+impl Weather {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Weather::Clear => "clear",
+            Weather::DustStorm => "dust storm",
+            Weather::AcidRain => "acid rain",
+        }
+    }
+
+    /// How far anyone on the surface can see in it, in tiles
+    pub fn sight_range(&self) -> i64 {
+        match self {
+            Weather::Clear => SIGHT_RANGE,
+            Weather::DustStorm => 4,
+            Weather::AcidRain => 8,
+        }
+    }
+
+    /// Colonists on the surface get to work one job round in this many
+    pub fn work_every(&self) -> u64 {
+        match self {
+            Weather::Clear => 1,
+            Weather::DustStorm => 3,
+            Weather::AcidRain => 2,
+        }
+    }
+
+    /// Units of water each colonist on the surface sees fall near them
+    /// every RAIN_INTERVAL ticks
+    pub fn rainfall(&self) -> u8 {
+        match self {
+            Weather::AcidRain => 2,
+            Weather::Clear | Weather::DustStorm => 0,
+        }
+    }
+
+    /// The tint over the surface while it lasts, with its strength
+    pub fn tint(&self) -> Option<(ColorName, f32)> {
+        match self {
+            Weather::Clear => None,
+            Weather::DustStorm => Some((ColorName::LightOrange, 0.35)),
+            Weather::AcidRain => Some((ColorName::LightGreen, 0.15)),
+        }
+    }
+
+    /// What drifts across the surface while it lasts, blown dust or drops
+    pub fn particle(&self) -> Option<Renderable> {
+        let frames = match self {
+            Weather::Clear => return None,
+            Weather::DustStorm => vec![
+                Frame::new('░', ColorName::LightOrange, 120),
+                Frame::new('‧', ColorName::Orange, 120),
+            ],
+            Weather::AcidRain => vec![
+                Frame::new('|', ColorName::LightGreen, 80),
+                Frame::new('‧', ColorName::Green, 120),
+            ],
+        };
+        Some(Renderable::new(frames))
+    }
+
+    /// The shortest and longest it lasts, in ticks
+    fn lasts(&self) -> (u32, u32) {
+        match self {
+            Weather::Clear => (3000, 6000),
+            Weather::DustStorm => (600, 1500),
+            Weather::AcidRain => (800, 2000),
+        }
+    }
+
+    /// What comes after it, storms always clear up and clear skies turn
+    /// to dust twice as often as to rain
+    fn follows(&self, rng: &mut oorandom::Rand32) -> Weather {
+        match self {
+            Weather::Clear if rng.rand_range(0..3) == 0 => Weather::AcidRain,
+            Weather::Clear => Weather::DustStorm,
+            Weather::DustStorm | Weather::AcidRain => Weather::Clear,
+        }
+    }
+}
+
+/// The weather now, when it turns and what it turns to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Forecast {
+    pub weather: Weather,
+    /// Tick the next weather sets in
+    pub until: u64,
+    pub next: Weather,
+}
+
+impl Forecast {
+
+    pub fn new() -> Forecast {
+        Forecast::default()
+    }
+
+    /// Turns to the next weather once it's due, picking what follows it
+    /// and how long it lasts from the seed and the tick. Returns whether
+    /// it turned.
+    pub fn step(&mut self, seed: u32, tick: u64) -> bool {
+        if tick < self.until {
+            return false;
+        }
+        let mut rng = rng(seed, tick);
+        self.weather = self.next;
+        self.next = self.weather.follows(&mut rng);
+        let (shortest, longest) = self.weather.lasts();
+        self.until = tick + rng.rand_range(shortest..longest + 1) as u64;
+        true
+    }
+
+    /// Ticks until the next weather sets in
+    pub fn turns_in(&self, tick: u64) -> u64 {
+        self.until.saturating_sub(tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weather_turns_when_forecast() {
+        let mut forecast = Forecast::new();
+        assert!(forecast.step(7, 0));
+        assert_eq!(forecast.weather, Weather::Clear);
+        assert_ne!(forecast.next, Weather::Clear);
+
+        let mut seen = Vec::new();
+        for tick in 1..50_000 {
+            let turning = forecast.turns_in(tick) == 0;
+            assert_eq!(forecast.step(7, tick), turning);
+            if turning {
+                seen.push(forecast.weather);
+            }
+        }
+        println!("{:?}", seen);
+        assert!(seen.contains(&Weather::DustStorm));
+        assert!(seen.contains(&Weather::AcidRain));
+        // storms always clear up before the next one
+        assert!(seen.windows(2).all(|pair| pair[0] == Weather::Clear || pair[1] == Weather::Clear));
+
+        let mut again = Forecast::new();
+        for tick in 0..50_000 {
+            again.step(7, tick);
+        }
+        assert_eq!(again, forecast);
+    }
+}
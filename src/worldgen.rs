@@ -0,0 +1,102 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::logging;
+
+/// Where the settings the rock is generated with are read from, next to
+/// the game. Without it worlds are generated the way they always have.
+pub const CONFIG_FILE: &str = "worldgen.json";
+
+/// The noise settings the rock of every world is generated with. Any left
+/// out of the file keep their default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldGenConfig {
+    /// How fast the rock's noise changes, higher packs more caves in
+    pub frequency: f64,
+    /// How much each finer octave of the noise adds, higher makes rougher
+    /// cave walls
+    pub persistence: f64,
+    /// What tile coordinates are scaled by before sampling the rock
+    pub scale: f64,
+    /// What tile coordinates are scaled by before sampling the gas pockets
+    pub gas_scale: f64,
+    /// Noise at and above which the rock is open cave
+    pub open_threshold: f64,
+}
+
+impl Default for WorldGenConfig {
+    fn default() -> WorldGenConfig {
+        WorldGenConfig {
+            frequency: 0.0125,
+            persistence: 0.035,
+            scale: 0.1,
+            gas_scale: 0.05,
+            open_threshold: 0.6,
+        }
+    }
+}
+
+impl WorldGenConfig {
+
+    /// The settings in the file, the defaults if there isn't one
+    pub fn load(path: &Path) -> Result<WorldGenConfig, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| format!("could not read {}: {}", path.display(), err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(WorldGenConfig::default()),
+            Err(err) => Err(format!("could not read {}: {}", path.display(), err)),
+        }
+    }
+
+    /// The settings in CONFIG_FILE, the defaults if it's missing or can't
+    /// be read
+    pub fn load_or_default() -> WorldGenConfig {
+        WorldGenConfig::load(Path::new(CONFIG_FILE)).unwrap_or_else(|err| {
+            log::warn!(target: logging::WORLDGEN, "{}, generating with the defaults", err);
+            WorldGenConfig::default()
+        })
+    }
+
+    /// Tells chunks cached with these settings apart from other settings',
+    /// empty for the defaults so their caches are kept
+    pub fn cache_suffix(&self) -> String {
+        if *self == WorldGenConfig::default() {
+            return String::new();
+        }
+        // FNV-1a over the settings, the same on every build
+        let fields = [self.frequency, self.persistence, self.scale, self.gas_scale, self.open_threshold];
+        let hash = fields.iter()
+            .flat_map(|field| field.to_bits().to_le_bytes())
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3));
+        format!("-{:016x}", hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_left_out_keep_their_defaults() {
+        let root = std::env::temp_dir().join("jmc-worldgen-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join(CONFIG_FILE);
+        assert_eq!(WorldGenConfig::load(&path), Ok(WorldGenConfig::default()));
+
+        fs::write(&path, r#"{ "frequency": 0.02 }"#).unwrap();
+        let tuned = WorldGenConfig::load(&path).unwrap();
+        println!("{:?} {}", tuned, tuned.cache_suffix());
+        assert_eq!(tuned, WorldGenConfig { frequency: 0.02, ..WorldGenConfig::default() });
+        assert_eq!(WorldGenConfig::default().cache_suffix(), "");
+        assert_ne!(tuned.cache_suffix(), "");
+
+        fs::write(&path, "{ frequency").unwrap();
+        assert!(WorldGenConfig::load(&path).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}